@@ -0,0 +1,264 @@
+use std::{
+    fs,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, bail};
+use serde::{Deserialize, Serialize};
+use starpls_common::{read_source, FileId};
+use starpls_ide::{AnalysisSnapshot, FilePosition};
+use starpls_syntax::{parse_module, SyntaxKind, TextSize};
+
+use crate::{
+    check::create_analysis, impact::add_workspace_files, symbols::OutputFormat, BenchArgs,
+};
+
+/// The phases that are benchmarked, in the order that they're run. Each phase runs against the
+/// database left behind by the previous ones, so e.g. the time for `infer` doesn't include
+/// parsing and lowering, and `hover` and `completion` measure the work left after inference.
+const PHASES: &[&str] = &["parse", "lower", "infer", "hover", "completion"];
+
+#[derive(Serialize, Deserialize)]
+struct BenchReport {
+    files: usize,
+    bytes: usize,
+    iterations: usize,
+    phases: Vec<PhaseReport>,
+    /// The peak resident set size of the process, in kilobytes. Only available on Linux.
+    peak_rss_kb: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct PhaseReport {
+    phase: String,
+    /// The number of files or positions that the phase ran against in each iteration.
+    operations: usize,
+    min_ms: f64,
+    median_ms: f64,
+    max_ms: f64,
+    /// How much the resident set size grew during the phase in the last iteration, in kilobytes.
+    /// Only available on Linux.
+    rss_growth_kb: Option<i64>,
+}
+
+/// A file to benchmark, along with the positions to request hovers and completions at.
+struct BenchFile {
+    file_id: FileId,
+    positions: Vec<TextSize>,
+}
+
+/// Runs each analysis phase against the Starlark files in a directory, with a fresh database
+/// for each iteration, and reports how long each phase took. If a baseline report is given, fails
+/// if any phase got slower than the threshold allows.
+pub(crate) fn run_bench(args: BenchArgs) -> anyhow::Result<()> {
+    if args.iterations == 0 {
+        bail!("--iterations must be at least 1");
+    }
+    let baseline = args
+        .baseline
+        .as_ref()
+        .map(|path| -> anyhow::Result<BenchReport> {
+            Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+        })
+        .transpose()?;
+
+    let mut timings = vec![Vec::with_capacity(args.iterations); PHASES.len()];
+    let mut operations = vec![0; PHASES.len()];
+    let mut rss_growth = vec![None; PHASES.len()];
+    let mut files_and_bytes = (0, 0);
+    for _ in 0..args.iterations {
+        let (mut analysis, interner, workspace) = create_analysis(args.output_base.clone())?;
+        let dir = match &args.path {
+            Some(path) => PathBuf::from(path)
+                .canonicalize()
+                .map_err(|_| anyhow!("Could not resolve the directory {:?}.", path))?,
+            None => workspace.canonicalize().unwrap_or(workspace),
+        };
+        add_workspace_files(&mut analysis, &interner, &dir)?;
+
+        let mut entries = interner.entries();
+        entries.sort_by_key(|(file_id, _)| *file_id);
+        let mut bytes = 0;
+        let files = entries
+            .into_iter()
+            .filter_map(|(file_id, path)| {
+                let contents = read_source(&path).ok()?.text;
+                bytes += contents.len();
+                Some(BenchFile {
+                    file_id,
+                    positions: sample_positions(&contents, args.max_positions_per_file),
+                })
+            })
+            .collect::<Vec<_>>();
+        files_and_bytes = (files.len(), bytes);
+
+        let snap = analysis.snapshot();
+        for (i, phase) in PHASES.iter().enumerate() {
+            let rss_before = current_rss_kb();
+            let start = Instant::now();
+            operations[i] = run_phase(&snap, phase, &files)?;
+            timings[i].push(start.elapsed());
+            rss_growth[i] = rss_before
+                .zip(current_rss_kb())
+                .map(|(before, after)| after as i64 - before as i64);
+        }
+    }
+
+    let report = BenchReport {
+        files: files_and_bytes.0,
+        bytes: files_and_bytes.1,
+        iterations: args.iterations,
+        phases: PHASES
+            .iter()
+            .enumerate()
+            .map(|(i, phase)| {
+                let mut durations = timings[i].clone();
+                durations.sort();
+                PhaseReport {
+                    phase: phase.to_string(),
+                    operations: operations[i],
+                    min_ms: millis(durations[0]),
+                    median_ms: millis(durations[durations.len() / 2]),
+                    max_ms: millis(durations[durations.len() - 1]),
+                    rss_growth_kb: rss_growth[i],
+                }
+            })
+            .collect(),
+        peak_rss_kb: read_proc_status("VmHWM"),
+    };
+
+    match args.format {
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&report)?),
+        OutputFormat::Text => {
+            println!(
+                "Files: {} ({} bytes), {} iterations",
+                report.files, report.bytes, report.iterations
+            );
+            for phase in &report.phases {
+                println!(
+                    "  {}: median {:.2}ms, min {:.2}ms, max {:.2}ms over {} operations{}",
+                    phase.phase,
+                    phase.median_ms,
+                    phase.min_ms,
+                    phase.max_ms,
+                    phase.operations,
+                    phase
+                        .rss_growth_kb
+                        .map(|kb| format!(", RSS {:+} KB", kb))
+                        .unwrap_or_default()
+                );
+            }
+            if let Some(kb) = report.peak_rss_kb {
+                println!("Peak RSS: {} KB", kb);
+            }
+        }
+    }
+
+    let baseline = match baseline {
+        Some(baseline) => baseline,
+        None => return Ok(()),
+    };
+    let mut regressions = Vec::new();
+    for phase in &report.phases {
+        let prev = match baseline
+            .phases
+            .iter()
+            .find(|prev| prev.phase == phase.phase)
+        {
+            Some(prev) if prev.median_ms > 0.0 => prev,
+            _ => continue,
+        };
+        let change = (phase.median_ms - prev.median_ms) / prev.median_ms * 100.0;
+        eprintln!(
+            "{}: {:.2}ms -> {:.2}ms ({:+.1}%)",
+            phase.phase, prev.median_ms, phase.median_ms, change
+        );
+        if change > args.threshold {
+            regressions.push(phase.phase.clone());
+        }
+    }
+    if !regressions.is_empty() {
+        bail!(
+            "{} got more than {}% slower than the baseline",
+            regressions.join(", "),
+            args.threshold
+        );
+    }
+    Ok(())
+}
+
+/// Runs a single phase against every file, returning the number of operations performed.
+fn run_phase(snap: &AnalysisSnapshot, phase: &str, files: &[BenchFile]) -> anyhow::Result<usize> {
+    let mut operations = 0;
+    for file in files {
+        match phase {
+            "parse" => {
+                snap.parse(file.file_id)?;
+                operations += 1;
+            }
+            "lower" => {
+                snap.lower(file.file_id)?;
+                operations += 1;
+            }
+            "infer" => {
+                snap.diagnostics(file.file_id)?;
+                operations += 1;
+            }
+            "hover" | "completion" => {
+                for &pos in &file.positions {
+                    let pos = FilePosition {
+                        file_id: file.file_id,
+                        pos,
+                    };
+                    if phase == "hover" {
+                        snap.hover(pos)?;
+                    } else {
+                        snap.completion(pos, None)?;
+                    }
+                    operations += 1;
+                }
+            }
+            _ => unreachable!("unknown phase {:?}", phase),
+        }
+    }
+    Ok(operations)
+}
+
+/// Picks up to `max` positions at the ends of names, spread evenly over the file.
+fn sample_positions(contents: &str, max: usize) -> Vec<TextSize> {
+    let tree = parse_module(contents, &mut |_| {});
+    let ends = tree
+        .syntax()
+        .descendants_with_tokens()
+        .filter_map(|element| element.into_token())
+        .filter(|token| {
+            token.kind() == SyntaxKind::IDENT
+                && token
+                    .parent()
+                    .map_or(false, |parent| parent.kind() == SyntaxKind::NAME_REF)
+        })
+        .map(|token| token.text_range().end())
+        .collect::<Vec<_>>();
+    if ends.len() <= max {
+        return ends;
+    }
+    (0..max).map(|i| ends[i * ends.len() / max]).collect()
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+fn current_rss_kb() -> Option<u64> {
+    read_proc_status("VmRSS")
+}
+
+/// Reads a memory statistic in kilobytes from `/proc/self/status`.
+fn read_proc_status(key: &str) -> Option<u64> {
+    let status = fs::read_to_string("/proc/self/status").ok()?;
+    status.lines().find_map(|line| {
+        let value = line.strip_prefix(key)?.strip_prefix(':')?;
+        value.trim().strip_suffix("kB")?.trim().parse().ok()
+    })
+}
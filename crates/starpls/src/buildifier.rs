@@ -0,0 +1,345 @@
+//! Support for using a Buildifier binary, configured with `--buildifier`, as the formatter and as
+//! an additional linter. This is meant for workspaces whose CI checks that files are formatted
+//! exactly like Buildifier formats them, since the builtin formatter's output can differ in corner
+//! cases.
+//!
+//! Documents are formatted by piping them through `buildifier`, while range formatting still uses
+//! the builtin formatter, since Buildifier only formats whole files. Open documents are linted
+//! with `buildifier --lint=warn --format=json` once edits to them have settled, separately from
+//! the server's own diagnostics so that a slow run doesn't hold those up. Warnings are reported
+//! with Buildifier's categories as their codes, e.g. `unused-variable`, linking to their
+//! documentation.
+//!
+//! If the binary can't be found, documents are formatted with the builtin formatter instead, and
+//! running it is retried every so often in case it's installed later.
+
+use std::{
+    hash::{Hash, Hasher},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    process::{Command, Output, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, bail};
+use line_index::{LineCol, LineIndex};
+use parking_lot::Mutex;
+use rustc_hash::{FxHashMap, FxHasher};
+use serde::Deserialize;
+use starpls_bazel::APIContext;
+use starpls_common::FileId;
+use starpls_ide::TextEdit;
+use starpls_syntax::{TextRange, TextSize};
+
+use crate::{
+    convert::{self, FileLineIndex},
+    document::DocumentSource,
+    server::ServerSnapshot,
+};
+
+/// How long to wait after the last edit to a document before linting it.
+pub(crate) const DEBOUNCE_INTERVAL: Duration = Duration::from_millis(500);
+
+/// How long to wait for Buildifier to format or lint a file.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long to wait before trying to run Buildifier again after it wasn't found.
+const RETRY_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Deserialize)]
+struct LintOutput {
+    #[serde(default)]
+    files: Vec<LintedFile>,
+}
+
+#[derive(Deserialize)]
+struct LintedFile {
+    #[serde(default)]
+    warnings: Vec<Warning>,
+}
+
+#[derive(Clone, Deserialize)]
+struct Warning {
+    start: Position,
+    end: Position,
+    category: String,
+    message: String,
+    url: Option<String>,
+}
+
+/// A one-based line and column, with the column in characters.
+#[derive(Clone, Copy, Deserialize)]
+struct Position {
+    line: u32,
+    column: u32,
+}
+
+pub(crate) struct Buildifier {
+    program: PathBuf,
+    args: Vec<String>,
+    workspace: PathBuf,
+    /// When the binary was last found to be missing.
+    missing_since: Mutex<Option<Instant>>,
+    /// The warnings last reported for each file, along with the hash of the contents they were
+    /// reported for.
+    cache: Mutex<FxHashMap<FileId, (u64, Vec<Warning>)>>,
+}
+
+impl Buildifier {
+    pub(crate) fn new(program: &str, args: Vec<String>, workspace: PathBuf) -> Self {
+        // Programs given as paths are resolved against the workspace, while bare names are looked
+        // up on the `PATH`.
+        let program = if program.contains('/') {
+            workspace.join(program)
+        } else {
+            PathBuf::from(program)
+        };
+        Self {
+            program,
+            args,
+            workspace,
+            missing_since: Mutex::new(None),
+            cache: Default::default(),
+        }
+    }
+
+    /// Formats an open document. Returns `None` if Buildifier isn't available, in which case the
+    /// builtin formatter should be used instead.
+    pub(crate) fn format_document(
+        &self,
+        snapshot: &ServerSnapshot,
+        file_id: FileId,
+    ) -> Option<Vec<TextEdit>> {
+        let (path, api_context, contents) = document(snapshot, file_id)?;
+        match self.run(&path, api_context, &contents, &[]) {
+            Ok(Some(output)) if output.status.success() => {
+                let formatted = String::from_utf8_lossy(&output.stdout);
+                let range = TextRange::up_to(TextSize::of(contents.as_str()));
+                Some(
+                    starpls_ide::minimal_edit(&contents, range, &formatted)
+                        .into_iter()
+                        .collect(),
+                )
+            }
+            Ok(Some(output)) => {
+                // Buildifier fails on files with syntax errors, which are already reported.
+                eprintln!(
+                    "server: buildifier failed to format {:?}: {}",
+                    path,
+                    String::from_utf8_lossy(&output.stderr).trim()
+                );
+                Some(Vec::new())
+            }
+            Ok(None) => None,
+            Err(err) => {
+                eprintln!("server: buildifier failed to format {:?}: {}", path, err);
+                Some(Vec::new())
+            }
+        }
+    }
+
+    /// Lints the given files, skipping those that aren't open in an editor. Failures are logged
+    /// rather than reported, and leave a file's previous warnings in place.
+    pub(crate) fn lint_files(
+        &self,
+        snapshot: &ServerSnapshot,
+        file_ids: Vec<FileId>,
+    ) -> Vec<(FileId, Vec<lsp_types::Diagnostic>)> {
+        let encoding = snapshot.config.position_encoding();
+        file_ids
+            .into_iter()
+            .filter(|file_id| is_open(snapshot, *file_id) && !snapshot.is_read_only(*file_id))
+            .filter_map(|file_id| {
+                let (path, api_context, contents) = document(snapshot, file_id)?;
+                let warnings = match self.lint(file_id, &path, api_context, &contents) {
+                    Ok(warnings) => warnings,
+                    Err(err) => {
+                        eprintln!("server: buildifier failed to lint {:?}: {}", path, err);
+                        return None;
+                    }
+                };
+                let line_index = LineIndex::new(&contents);
+                let has_bom = snapshot.document_manager.read().has_bom(file_id);
+                let diagnostics = warnings
+                    .into_iter()
+                    .filter_map(|warning| {
+                        let start = offset(&contents, &line_index, warning.start)?;
+                        let end = offset(&contents, &line_index, warning.end)?;
+                        let range = TextRange::new(start, end.max(start));
+                        Some(lsp_types::Diagnostic {
+                            range: convert::lsp_range_from_text_range(
+                                range,
+                                FileLineIndex {
+                                    index: &line_index,
+                                    has_bom,
+                                },
+                                encoding,
+                            )?,
+                            severity: Some(lsp_types::DiagnosticSeverity::WARNING),
+                            code: Some(lsp_types::NumberOrString::String(warning.category)),
+                            code_description: warning
+                                .url
+                                .and_then(|url| lsp_types::Url::parse(&url).ok())
+                                .map(|href| lsp_types::CodeDescription { href }),
+                            source: Some("buildifier".to_string()),
+                            message: warning.message,
+                            related_information: None,
+                            tags: None,
+                            data: None,
+                        })
+                    })
+                    .collect();
+                Some((file_id, diagnostics))
+            })
+            .collect()
+    }
+
+    fn lint(
+        &self,
+        file_id: FileId,
+        path: &Path,
+        api_context: Option<APIContext>,
+        contents: &str,
+    ) -> anyhow::Result<Vec<Warning>> {
+        let mut hasher = FxHasher::default();
+        contents.hash(&mut hasher);
+        let hash = hasher.finish();
+        if let Some((cached_hash, warnings)) = self.cache.lock().get(&file_id) {
+            if *cached_hash == hash {
+                return Ok(warnings.clone());
+            }
+        }
+
+        let output = match self.run(
+            path,
+            api_context,
+            contents,
+            &["--mode=check", "--lint=warn", "--format=json"],
+        )? {
+            Some(output) => output,
+            None => return Ok(Vec::new()),
+        };
+
+        // Buildifier exits with an error if it finds any warnings, so only its output is checked.
+        let warnings = match serde_json::from_slice::<LintOutput>(&output.stdout) {
+            Ok(output) => output
+                .files
+                .into_iter()
+                .flat_map(|file| file.warnings)
+                .collect::<Vec<_>>(),
+            Err(_) => bail!("{}", String::from_utf8_lossy(&output.stderr).trim()),
+        };
+        self.cache.lock().insert(file_id, (hash, warnings.clone()));
+        Ok(warnings)
+    }
+
+    /// Runs Buildifier on a file's contents, which are passed on stdin. Returns `None` if the
+    /// binary couldn't be found.
+    fn run(
+        &self,
+        path: &Path,
+        api_context: Option<APIContext>,
+        contents: &str,
+        args: &[&str],
+    ) -> anyhow::Result<Option<Output>> {
+        if let Some(missing_since) = *self.missing_since.lock() {
+            if missing_since.elapsed() < RETRY_INTERVAL {
+                return Ok(None);
+            }
+        }
+
+        // Buildifier picks some of its rules based on the file's path relative to the workspace.
+        let relative_path = path.strip_prefix(&self.workspace).unwrap_or(path);
+        let file_type = match api_context {
+            Some(APIContext::Build) => "build",
+            Some(APIContext::Bzl) => "bzl",
+            Some(APIContext::Module) => "module",
+            Some(APIContext::Workspace) => "workspace",
+            Some(APIContext::Repo) | None => "auto",
+        };
+        let mut child = match Command::new(&self.program)
+            .args(&self.args)
+            .args(args)
+            .arg(format!("--type={}", file_type))
+            .arg(format!("--path={}", relative_path.display()))
+            .current_dir(&self.workspace)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+        {
+            Ok(child) => child,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                let mut missing_since = self.missing_since.lock();
+                if missing_since.is_none() {
+                    eprintln!(
+                        "server: buildifier {:?} not found, using the builtin formatter",
+                        self.program
+                    );
+                }
+                *missing_since = Some(Instant::now());
+                return Ok(None);
+            }
+            Err(err) => return Err(err.into()),
+        };
+        *self.missing_since.lock() = None;
+
+        // Write the contents and wait for the output on separate threads, so that neither blocks
+        // on a full pipe, and so that a hung Buildifier times out.
+        let mut stdin = child
+            .stdin
+            .take()
+            .ok_or_else(|| anyhow!("failed to open stdin"))?;
+        let contents = contents.to_string();
+        thread::spawn(move || {
+            let _ = stdin.write_all(contents.as_bytes());
+        });
+        let (sender, receiver) = crossbeam_channel::bounded(1);
+        thread::spawn(move || {
+            let _ = sender.send(child.wait_with_output());
+        });
+        match receiver.recv_timeout(TIMEOUT) {
+            Ok(output) => Ok(Some(output?)),
+            Err(_) => bail!("timed out"),
+        }
+    }
+}
+
+fn document(
+    snapshot: &ServerSnapshot,
+    file_id: FileId,
+) -> Option<(PathBuf, Option<APIContext>, String)> {
+    let document_manager = snapshot.document_manager.read();
+    let document = document_manager.get(file_id)?;
+    Some((
+        document_manager.lookup_by_file_id(file_id),
+        document.api_context.clone(),
+        document.contents.clone(),
+    ))
+}
+
+fn is_open(snapshot: &ServerSnapshot, file_id: FileId) -> bool {
+    matches!(
+        snapshot
+            .document_manager
+            .read()
+            .get(file_id)
+            .map(|document| &document.source),
+        Some(DocumentSource::Editor(_))
+    )
+}
+
+fn offset(contents: &str, line_index: &LineIndex, pos: Position) -> Option<TextSize> {
+    let line_start = line_index.offset(LineCol {
+        line: pos.line.checked_sub(1)?,
+        col: 0,
+    })?;
+    let col = contents[usize::from(line_start)..]
+        .chars()
+        .take_while(|c| *c != '\n')
+        .take(pos.column.saturating_sub(1) as usize)
+        .map(char::len_utf8)
+        .sum::<usize>();
+    Some(line_start + TextSize::from(col as u32))
+}
@@ -1,56 +1,58 @@
-use std::{fmt::Write, fs, path::PathBuf, process, sync::Arc};
+use std::{
+    fmt::Write,
+    fs,
+    path::{Path, PathBuf},
+    process::{self, Command},
+    sync::Arc,
+    thread,
+    time::Duration,
+};
 
 use anyhow::anyhow;
 use rustc_hash::FxHashMap;
-use starpls_bazel::client::{BazelCLI, BazelClient};
-use starpls_common::Severity;
-use starpls_ide::{Analysis, Change};
+use serde::{Deserialize, Serialize};
+use starpls_bazel::{
+    bzlmod::ModuleResolver,
+    client::{BazelCLI, BazelClient},
+};
+use starpls_common::{read_source, Diagnostic, FileId, Severity};
+use starpls_ide::{parse_module_file, Analysis, AnalysisSnapshot, Change};
 
 use crate::{
     document::{self, DefaultFileLoader, PathInterner},
+    impact::add_workspace_files,
     server::{load_bazel_build_language, load_bazel_builtins},
+    CheckArgs,
 };
 
-pub(crate) fn run_check(paths: Vec<String>, output_base: Option<String>) -> anyhow::Result<()> {
-    let bazel_client = Arc::new(BazelCLI::default());
-    let info = bazel_client.info()?;
-    let external_output_base = output_base
-        .map(PathBuf::from)
-        .unwrap_or_else(|| info.output_base.join("external"));
+/// How often to poll the filesystem for changes in watch mode.
+const WATCH_INTERVAL: Duration = Duration::from_millis(500);
 
-    let bzlmod_enabled = info
-        .workspace
-        .join("MODULE.bazel")
-        .try_exists()
-        .unwrap_or(false)
-        && {
-            eprintln!("server: checking for `bazel mod dump_repo_mapping` capability");
-            match bazel_client.dump_repo_mapping("") {
-                Ok(_) => true,
-                Err(_) => {
-                    eprintln!("server: installed Bazel version doesn't support `bazel mod dump_repo_mapping`, disabling bzlmod support");
-                    false
-                }
-            }
-        };
+pub(crate) fn run_check(args: CheckArgs) -> anyhow::Result<()> {
+    let CheckArgs {
+        paths,
+        output_base,
+        baseline,
+        write_baseline,
+        watch,
+        since,
+    } = args;
+    if write_baseline && baseline.is_none() {
+        return Err(anyhow!(
+            "`--write_baseline` requires `--baseline` to be set."
+        ));
+    }
+    if write_baseline && watch {
+        return Err(anyhow!(
+            "`--write_baseline` cannot be used together with `--watch`."
+        ));
+    }
 
-    let (fetch_repo_sender, _) = crossbeam_channel::unbounded();
-    let builtins = load_bazel_builtins()?;
-    let rules = load_bazel_build_language(&*bazel_client)?;
-    let interner = Arc::new(PathInterner::default());
-    let loader = DefaultFileLoader::new(
-        bazel_client,
-        interner.clone(),
-        info.workspace,
-        external_output_base,
-        fetch_repo_sender,
-        bzlmod_enabled,
-    );
-    let mut analysis = Analysis::new(Arc::new(loader), Default::default());
+    let (mut analysis, interner, workspace) = create_analysis(output_base)?;
+    let workspace = workspace.canonicalize().unwrap_or(workspace);
     let mut change = Change::default();
     let mut file_ids = Vec::new();
     let mut original_paths = FxHashMap::default();
-    analysis.set_builtin_defs(builtins, rules);
 
     for path in &paths {
         let err = || anyhow!("Could not resolve the path {:?} as a Starlark file.", path);
@@ -59,52 +61,408 @@ pub(crate) fn run_check(paths: Vec<String>, output_base: Option<String>) -> anyh
             continue;
         }
 
-        let contents = fs::read_to_string(&resolved).map_err(|_| err())?;
+        let contents = read_source(&resolved).map_err(|_| err())?.text;
         let (dialect, api_context) = match document::dialect_and_api_context_for_path(&resolved) {
             Some(res) => res,
             None => return Err(err()),
         };
 
         let file_id = interner.intern_path(resolved);
-        original_paths.insert(file_id, path);
+        original_paths.insert(file_id, path.clone());
         change.create_file(file_id, dialect, api_context, contents);
         file_ids.push(file_id);
     }
 
     analysis.apply_change(change);
 
+    // Also check all files changed since the given revision, along with every file that
+    // transitively loads one of them.
+    if let Some(rev) = since {
+        add_workspace_files(&mut analysis, &interner, &workspace)?;
+        let snap = analysis.snapshot();
+        let mut affected_file_ids = Vec::new();
+        for path in changed_files_since(&workspace, &rev)? {
+            let file_id = match interner.lookup_by_path_buf(&path) {
+                Some(file_id) => file_id,
+                None => continue,
+            };
+            affected_file_ids.push(file_id);
+            if let Some(graph) = snap.load_graph(file_id)? {
+                affected_file_ids.extend(graph.loaders);
+            }
+        }
+        drop(snap);
+
+        for file_id in affected_file_ids {
+            if original_paths.contains_key(&file_id) {
+                continue;
+            }
+            let path = interner.lookup_by_file_id(file_id);
+            let display_path = path.strip_prefix(&workspace).unwrap_or(&path);
+            original_paths.insert(file_id, display_path.to_string_lossy().to_string());
+            file_ids.push(file_id);
+        }
+    }
+
+    let checker = Checker {
+        workspace,
+        interner,
+        original_paths,
+    };
+
+    if write_baseline {
+        let baseline = baseline.unwrap();
+        let new_baseline = checker.collect_baseline(&analysis, &file_ids)?;
+        new_baseline.write(&baseline)?;
+        eprintln!(
+            "check: recorded {} diagnostic(s) to {}",
+            new_baseline.len(),
+            baseline
+        );
+        return Ok(());
+    }
+
+    let known_diagnostics = match &baseline {
+        Some(baseline) => Baseline::read(baseline)?,
+        None => Baseline::default(),
+    };
+
+    let mut remaining_baseline = known_diagnostics.clone();
     let snap = analysis.snapshot();
-    let mut rendered_diagnostics = String::new();
+    let mut reports = FxHashMap::default();
     let mut has_error = false;
+    let mut num_suppressed = 0;
+
+    for file_id in file_ids.iter().copied() {
+        let report = checker.check_file(&snap, file_id, &mut remaining_baseline)?;
+        print!("{}", report.rendered);
+        has_error |= report.has_error;
+        num_suppressed += report.num_suppressed;
+        reports.insert(file_id, report);
+    }
+
+    if num_suppressed > 0 {
+        eprintln!(
+            "check: suppressed {} diagnostic(s) found in the baseline",
+            num_suppressed
+        );
+    }
+
+    if watch {
+        drop(snap);
+        return checker.watch(analysis, &file_ids, known_diagnostics, reports);
+    }
+
+    if has_error {
+        process::exit(1);
+    }
+
+    Ok(())
+}
 
-    for file_id in file_ids.into_iter() {
-        let line_index = snap.line_index(file_id).unwrap().unwrap();
+/// Returns the paths of all files that were modified, added, or left untracked since the given
+/// git revision, relative to the workspace root.
+fn changed_files_since(workspace: &Path, rev: &str) -> anyhow::Result<Vec<PathBuf>> {
+    let run_git = |args: &[&str]| -> anyhow::Result<String> {
+        let output = Command::new("git")
+            .args(args)
+            .current_dir(workspace)
+            .output()?;
+        if !output.status.success() {
+            return Err(anyhow!(
+                "`git {}` failed: {}",
+                args.join(" "),
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        Ok(String::from_utf8(output.stdout)?)
+    };
+
+    let diff = run_git(&["diff", "--name-only", "--relative", rev, "--"])?;
+    let untracked = run_git(&["ls-files", "--others", "--exclude-standard"])?;
+    Ok(diff
+        .lines()
+        .chain(untracked.lines())
+        .filter(|line| !line.is_empty())
+        .map(|line| workspace.join(line))
+        .collect())
+}
+
+/// The rendered diagnostics for a single file.
+#[derive(PartialEq, Eq)]
+struct FileReport {
+    rendered: String,
+    has_error: bool,
+    num_suppressed: usize,
+}
+
+struct Checker {
+    workspace: PathBuf,
+    interner: Arc<PathInterner>,
+    original_paths: FxHashMap<FileId, String>,
+}
+
+impl Checker {
+    fn check_file(
+        &self,
+        snap: &AnalysisSnapshot,
+        file_id: FileId,
+        known_diagnostics: &mut Baseline,
+    ) -> anyhow::Result<FileReport> {
+        let line_index = snap.line_index(file_id)?.unwrap();
+        let baseline_path = self.baseline_path(file_id);
+        let mut report = FileReport {
+            rendered: String::new(),
+            has_error: false,
+            num_suppressed: 0,
+        };
 
         for diagnostic in snap.diagnostics(file_id)? {
+            // Diagnostics recorded in the baseline are neither reported nor counted as failures.
+            if known_diagnostics.remove(&BaselineEntry::new(baseline_path.clone(), &diagnostic)) {
+                report.num_suppressed += 1;
+                continue;
+            }
+
             let start = line_index.line_col(diagnostic.range.range.start());
             writeln!(
-                &mut rendered_diagnostics,
-                "{}:{}:{} - {}: {}",
-                original_paths.get(&file_id).unwrap(),
+                &mut report.rendered,
+                "{}:{}:{} - {}[{}]: {}",
+                self.original_paths.get(&file_id).unwrap(),
                 start.line + 1,
                 start.col + 1,
                 match diagnostic.severity {
+                    Severity::Hint => "hint",
                     Severity::Warning => "warn",
                     Severity::Error => {
-                        has_error = true;
+                        report.has_error = true;
                         "error"
                     }
                 },
+                diagnostic.code.name(),
                 diagnostic.message,
             )?;
         }
+
+        Ok(report)
     }
 
-    print!("{}", rendered_diagnostics);
+    fn collect_baseline(
+        &self,
+        analysis: &Analysis,
+        file_ids: &[FileId],
+    ) -> anyhow::Result<Baseline> {
+        let snap = analysis.snapshot();
+        let mut baseline = Baseline::default();
+        for file_id in file_ids.iter().copied() {
+            let baseline_path = self.baseline_path(file_id);
+            for diagnostic in snap.diagnostics(file_id)? {
+                baseline.insert(BaselineEntry::new(baseline_path.clone(), &diagnostic));
+            }
+        }
+        Ok(baseline)
+    }
 
-    if has_error {
-        process::exit(1);
+    /// Polls every file known to the analysis for changes, and re-reports diagnostics for
+    /// any checked file whose diagnostics changed as a result. Only modified files are
+    /// updated, so the incremental engine avoids re-analyzing anything unaffected.
+    fn watch(
+        &self,
+        mut analysis: Analysis,
+        file_ids: &[FileId],
+        known_diagnostics: Baseline,
+        mut reports: FxHashMap<FileId, FileReport>,
+    ) -> anyhow::Result<()> {
+        let modified_time = |path: &Path| {
+            fs::metadata(path)
+                .and_then(|metadata| metadata.modified())
+                .ok()
+        };
+        let mut modified_times = self
+            .interner
+            .entries()
+            .into_iter()
+            .map(|(file_id, path)| (file_id, modified_time(&path)))
+            .collect::<FxHashMap<_, _>>();
+
+        eprintln!("check: watching for changes");
+
+        loop {
+            thread::sleep(WATCH_INTERVAL);
+
+            let mut change = Change::default();
+            let mut num_changed = 0;
+            for (file_id, path) in self.interner.entries() {
+                let current = modified_time(&path);
+                let previous = modified_times.insert(file_id, current);
+                if previous.is_none() || previous == Some(current) {
+                    continue;
+                }
+                if let Ok(decoded) = read_source(&path) {
+                    change.update_file(file_id, decoded.text);
+                    num_changed += 1;
+                }
+            }
+
+            if num_changed == 0 {
+                continue;
+            }
+
+            eprintln!("check: {} file(s) changed, re-checking", num_changed);
+            analysis.apply_change(change);
+
+            let snap = analysis.snapshot();
+            let mut remaining_baseline = known_diagnostics.clone();
+            for file_id in file_ids.iter().copied() {
+                let report = match self.check_file(&snap, file_id, &mut remaining_baseline) {
+                    Ok(report) => report,
+                    Err(_) => continue,
+                };
+                if reports.get(&file_id) == Some(&report) {
+                    continue;
+                }
+                if report.rendered.is_empty() {
+                    eprintln!(
+                        "check: {} no longer has any diagnostics",
+                        self.original_paths.get(&file_id).unwrap()
+                    );
+                }
+                print!("{}", report.rendered);
+                reports.insert(file_id, report);
+            }
+        }
     }
 
-    Ok(())
+    fn baseline_path(&self, file_id: FileId) -> String {
+        let path = self.interner.lookup_by_file_id(file_id);
+        path.strip_prefix(&self.workspace)
+            .unwrap_or(&path)
+            .to_string_lossy()
+            .replace('\\', "/")
+    }
+}
+
+/// A single diagnostic recorded in a baseline file. Line and column numbers are
+/// deliberately omitted so that unrelated edits don't invalidate the baseline.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+struct BaselineEntry {
+    path: String,
+    severity: String,
+    message: String,
+}
+
+impl BaselineEntry {
+    fn new(path: String, diagnostic: &Diagnostic) -> Self {
+        Self {
+            path,
+            severity: match diagnostic.severity {
+                Severity::Hint => "hint",
+                Severity::Warning => "warn",
+                Severity::Error => "error",
+            }
+            .to_string(),
+            message: diagnostic.message.clone(),
+        }
+    }
+}
+
+/// The set of diagnostics that are already known to exist, as recorded by `--write_baseline`.
+/// Identical diagnostics in the same file are tracked by count.
+#[derive(Clone, Default)]
+struct Baseline {
+    entries: FxHashMap<BaselineEntry, usize>,
+}
+
+impl Baseline {
+    fn read(path: &str) -> anyhow::Result<Self> {
+        let contents = fs::read_to_string(path)
+            .map_err(|err| anyhow!("Could not read baseline file {:?}: {}", path, err))?;
+        let entries: Vec<BaselineEntry> = serde_json::from_str(&contents)
+            .map_err(|err| anyhow!("Could not parse baseline file {:?}: {}", path, err))?;
+        let mut baseline = Self::default();
+        entries.into_iter().for_each(|entry| baseline.insert(entry));
+        Ok(baseline)
+    }
+
+    fn write(&self, path: &str) -> anyhow::Result<()> {
+        let mut entries = self
+            .entries
+            .iter()
+            .flat_map(|(entry, count)| std::iter::repeat(entry).take(*count))
+            .collect::<Vec<_>>();
+        entries.sort_by(|a, b| {
+            (&a.path, &a.message, &a.severity).cmp(&(&b.path, &b.message, &b.severity))
+        });
+        let mut contents = serde_json::to_string_pretty(&entries)?;
+        contents.push('\n');
+        fs::write(path, contents)?;
+        Ok(())
+    }
+
+    fn insert(&mut self, entry: BaselineEntry) {
+        *self.entries.entry(entry).or_default() += 1;
+    }
+
+    fn remove(&mut self, entry: &BaselineEntry) -> bool {
+        match self.entries.get_mut(entry) {
+            Some(count) if *count > 0 => {
+                *count -= 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.entries.values().sum()
+    }
+}
+
+/// Sets up an `Analysis` for the current Bazel workspace, returning it along with
+/// its path interner and the path to the workspace root.
+pub(crate) fn create_analysis(
+    output_base: Option<String>,
+) -> anyhow::Result<(Analysis, Arc<PathInterner>, PathBuf)> {
+    let bazel_client = Arc::new(BazelCLI::default());
+    let info = bazel_client.info()?;
+    let external_output_base = output_base
+        .map(PathBuf::from)
+        .unwrap_or_else(|| info.output_base.join("external"));
+
+    let bzlmod_enabled = info
+        .workspace
+        .join("MODULE.bazel")
+        .try_exists()
+        .unwrap_or(false);
+    if bzlmod_enabled {
+        eprintln!("server: checking for `bazel mod dump_repo_mapping` capability");
+        if bazel_client.dump_repo_mapping("").is_err() {
+            eprintln!("server: installed Bazel version doesn't support `bazel mod dump_repo_mapping`, resolving repositories from MODULE.bazel files instead");
+        }
+    }
+
+    let (fetch_repo_sender, _) = crossbeam_channel::unbounded();
+    let builtins = load_bazel_builtins()?;
+    let rules = load_bazel_build_language(&*bazel_client)?;
+    let interner = Arc::new(PathInterner::default());
+    let module_resolver = Arc::new(ModuleResolver::new(
+        info.workspace.clone(),
+        external_output_base.clone(),
+        parse_module_file,
+    ));
+    let loader = DefaultFileLoader::new(
+        bazel_client,
+        interner.clone(),
+        info.workspace.clone(),
+        external_output_base,
+        fetch_repo_sender,
+        bzlmod_enabled,
+        module_resolver,
+        None,
+    );
+    let mut analysis = Analysis::new(Arc::new(loader), Default::default());
+    analysis.set_builtin_defs(builtins, rules);
+    #[cfg(feature = "lint-plugins")]
+    analysis.set_lint_rules(crate::lint_plugins::lint_rules());
+    Ok((analysis, interner, info.workspace))
 }
@@ -1,6 +1,6 @@
 use lsp_types::ClientCapabilities;
 
-use crate::ServerArgs;
+use crate::{convert::PositionEncoding, ServerArgs};
 
 #[derive(Default)]
 pub(crate) struct ServerConfig {
@@ -18,4 +18,61 @@ impl ServerConfig {
     pub(crate) fn has_text_document_definition_link_support(&self) -> bool {
         try_or_default!(self.caps.text_document.as_ref()?.definition?.link_support)
     }
+
+    pub(crate) fn has_document_changes_support(&self) -> bool {
+        try_or_default!(
+            self.caps
+                .workspace
+                .as_ref()?
+                .workspace_edit
+                .as_ref()?
+                .document_changes
+        )
+    }
+
+    pub(crate) fn has_create_file_support(&self) -> bool {
+        self.has_document_changes_support()
+            && try_or_default!(Some(
+                self.caps
+                    .workspace
+                    .as_ref()?
+                    .workspace_edit
+                    .as_ref()?
+                    .resource_operations
+                    .as_ref()?
+                    .contains(&lsp_types::ResourceOperationKind::Create)
+            ))
+    }
+
+    pub(crate) fn has_change_annotation_support(&self) -> bool {
+        self.has_document_changes_support()
+            && try_or_default!(Some(
+                self.caps
+                    .workspace
+                    .as_ref()?
+                    .workspace_edit
+                    .as_ref()?
+                    .change_annotation_support
+                    .is_some()
+            ))
+    }
+
+    pub(crate) fn has_configuration_support(&self) -> bool {
+        try_or_default!(self.caps.workspace.as_ref()?.configuration)
+    }
+
+    pub(crate) fn has_inlay_hint_refresh_support(&self) -> bool {
+        try_or_default!(
+            self.caps
+                .workspace
+                .as_ref()?
+                .inlay_hint
+                .as_ref()?
+                .refresh_support
+        )
+    }
+
+    pub(crate) fn position_encoding(&self) -> PositionEncoding {
+        PositionEncoding::negotiate(&self.caps)
+    }
 }
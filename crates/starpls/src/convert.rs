@@ -1,7 +1,8 @@
-use std::path::PathBuf;
+use std::{ops::Deref, path::PathBuf};
 
 use anyhow::anyhow;
-use line_index::{LineIndex, WideEncoding, WideLineCol};
+use line_index::{LineCol, LineIndex, WideEncoding, WideLineCol};
+use lsp_types::{ClientCapabilities, PositionEncodingKind};
 use starpls_common::{Diagnostic, FileId, Severity};
 use starpls_ide::{DocumentSymbol, SymbolKind, SymbolTag};
 use starpls_syntax::{TextRange, TextSize};
@@ -13,15 +14,110 @@ pub(crate) fn path_buf_from_url(url: &lsp_types::Url) -> anyhow::Result<PathBuf>
         .map_err(|_| anyhow!("url is not a file: {}", url))
 }
 
-pub(crate) fn lsp_diagnostic_from_native(
+/// The encoding of the `character` offsets of LSP positions, which is negotiated with the client
+/// during initialization.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) enum PositionEncoding {
+    Utf8,
+    #[default]
+    Utf16,
+    Utf32,
+}
+
+impl PositionEncoding {
+    /// Picks UTF-8 if the client supports it, since it matches our internal representation of text
+    /// and doesn't require converting every position. Otherwise, picks the first encoding in the
+    /// client's list of preferences that we support, falling back to UTF-16, which all clients
+    /// must support.
+    pub(crate) fn negotiate(caps: &ClientCapabilities) -> Self {
+        let encodings = caps
+            .general
+            .as_ref()
+            .and_then(|general| general.position_encodings.as_deref())
+            .unwrap_or_default();
+        if encodings.contains(&PositionEncodingKind::UTF8) {
+            return PositionEncoding::Utf8;
+        }
+        encodings
+            .iter()
+            .find_map(|encoding| match encoding.as_str() {
+                "utf-16" => Some(PositionEncoding::Utf16),
+                "utf-32" => Some(PositionEncoding::Utf32),
+                _ => None,
+            })
+            .unwrap_or_default()
+    }
+
+    pub(crate) fn kind(self) -> PositionEncodingKind {
+        match self {
+            PositionEncoding::Utf8 => PositionEncodingKind::UTF8,
+            PositionEncoding::Utf16 => PositionEncodingKind::UTF16,
+            PositionEncoding::Utf32 => PositionEncodingKind::UTF32,
+        }
+    }
+
+    fn wide(self) -> Option<WideEncoding> {
+        match self {
+            PositionEncoding::Utf8 => None,
+            PositionEncoding::Utf16 => Some(WideEncoding::Utf16),
+            PositionEncoding::Utf32 => Some(WideEncoding::Utf32),
+        }
+    }
+
+    /// The width of a byte order mark in this encoding.
+    fn bom_width(self) -> u32 {
+        match self {
+            PositionEncoding::Utf8 => BOM.len_utf8() as u32,
+            PositionEncoding::Utf16 => BOM.len_utf16() as u32,
+            PositionEncoding::Utf32 => 1,
+        }
+    }
+}
+
+const BOM: char = '\u{feff}';
+
+/// The line index of a file, along with whether the client's copy of the file starts with a byte
+/// order mark. The byte order mark is removed from the text that's analyzed, but the client counts
+/// it as a character on the first line.
+#[derive(Clone, Copy)]
+pub(crate) struct FileLineIndex<'a> {
+    pub(crate) index: &'a LineIndex,
+    pub(crate) has_bom: bool,
+}
+
+impl<'a> From<&'a LineIndex> for FileLineIndex<'a> {
+    fn from(index: &'a LineIndex) -> Self {
+        Self {
+            index,
+            has_bom: false,
+        }
+    }
+}
+
+impl Deref for FileLineIndex<'_> {
+    type Target = LineIndex;
+
+    fn deref(&self) -> &LineIndex {
+        self.index
+    }
+}
+
+pub(crate) fn lsp_diagnostic_from_native<'a>(
     diagnostic: Diagnostic,
-    line_index: &LineIndex,
+    line_index: impl Into<FileLineIndex<'a>>,
+    encoding: PositionEncoding,
 ) -> Option<lsp_types::Diagnostic> {
     Some(lsp_types::Diagnostic {
-        range: lsp_range_from_text_range(diagnostic.range.range, &line_index)?,
+        range: lsp_range_from_text_range(diagnostic.range.range, line_index, encoding)?,
         severity: Some(lsp_severity_from_native(diagnostic.severity)),
-        code: None,
-        code_description: None,
+        code: Some(lsp_types::NumberOrString::String(
+            diagnostic.code.name().to_string(),
+        )),
+        code_description: diagnostic
+            .code
+            .docs_url()
+            .and_then(|url| lsp_types::Url::parse(&url).ok())
+            .map(|href| lsp_types::CodeDescription { href }),
         source: Some("starpls".to_string()),
         message: diagnostic.message,
         related_information: None,
@@ -30,29 +126,64 @@ pub(crate) fn lsp_diagnostic_from_native(
     })
 }
 
-pub(crate) fn lsp_range_from_text_range(
+pub(crate) fn lsp_range_from_text_range<'a>(
     text_range: TextRange,
-    line_index: &LineIndex,
+    line_index: impl Into<FileLineIndex<'a>>,
+    encoding: PositionEncoding,
 ) -> Option<lsp_types::Range> {
-    let start = line_index.to_wide(WideEncoding::Utf16, line_index.line_col(text_range.start()))?;
-    let end = line_index.to_wide(WideEncoding::Utf16, line_index.line_col(text_range.end()))?;
+    let line_index = line_index.into();
     Some(lsp_types::Range {
-        start: lsp_types::Position {
-            line: start.line,
-            character: start.col,
-        },
-        end: lsp_types::Position {
-            line: end.line,
-            character: end.col,
-        },
+        start: lsp_position_from_text_size(text_range.start(), line_index, encoding)?,
+        end: lsp_position_from_text_size(text_range.end(), line_index, encoding)?,
     })
 }
 
-fn wide_line_col_from_lsp_position(pos: lsp_types::Position) -> WideLineCol {
-    WideLineCol {
-        line: pos.line,
-        col: pos.character,
+pub(crate) fn lsp_position_from_text_size<'a>(
+    offset: TextSize,
+    line_index: impl Into<FileLineIndex<'a>>,
+    encoding: PositionEncoding,
+) -> Option<lsp_types::Position> {
+    let line_index = line_index.into();
+    let line_col = line_index.line_col(offset);
+    let (line, mut character) = match encoding.wide() {
+        Some(wide) => {
+            let line_col = line_index.to_wide(wide, line_col)?;
+            (line_col.line, line_col.col)
+        }
+        None => (line_col.line, line_col.col),
+    };
+    if line_index.has_bom && line == 0 {
+        character += encoding.bom_width();
     }
+    Some(lsp_types::Position { line, character })
+}
+
+/// Converts an LSP position to a text offset. Returns `None` if the position is out of bounds.
+pub(crate) fn text_size_from_lsp_position_with_index<'a>(
+    line_index: impl Into<FileLineIndex<'a>>,
+    encoding: PositionEncoding,
+    pos: lsp_types::Position,
+) -> Option<TextSize> {
+    let line_index = line_index.into();
+    let col = if line_index.has_bom && pos.line == 0 {
+        pos.character.saturating_sub(encoding.bom_width())
+    } else {
+        pos.character
+    };
+    let line_col = match encoding.wide() {
+        Some(wide) => line_index.to_utf8(
+            wide,
+            WideLineCol {
+                line: pos.line,
+                col,
+            },
+        )?,
+        None => LineCol {
+            line: pos.line,
+            col,
+        },
+    };
+    line_index.offset(line_col)
 }
 
 pub(crate) fn text_size_from_lsp_position(
@@ -60,25 +191,56 @@ pub(crate) fn text_size_from_lsp_position(
     file_id: FileId,
     pos: lsp_types::Position,
 ) -> anyhow::Result<Option<TextSize>> {
-    let line_index = match snapshot.analysis_snapshot.line_index(file_id)? {
+    let line_index = match snapshot.line_index(file_id)? {
         Some(line_index) => line_index,
         None => return Ok(None),
     };
-    let line_col =
-        match line_index.to_utf8(WideEncoding::Utf16, wide_line_col_from_lsp_position(pos)) {
-            Some(line_col) => line_col,
-            None => return Ok(None),
-        };
-    Ok(line_index.offset(line_col))
+    Ok(text_size_from_lsp_position_with_index(
+        line_index,
+        snapshot.config.position_encoding(),
+        pos,
+    ))
 }
 
 fn lsp_severity_from_native(severity: Severity) -> lsp_types::DiagnosticSeverity {
     match severity {
+        Severity::Hint => lsp_types::DiagnosticSeverity::HINT,
         Severity::Error => lsp_types::DiagnosticSeverity::ERROR,
         Severity::Warning => lsp_types::DiagnosticSeverity::WARNING,
     }
 }
 
+pub(crate) fn lsp_symbol_kind_from_native(kind: SymbolKind) -> lsp_types::SymbolKind {
+    match kind {
+        SymbolKind::File => lsp_types::SymbolKind::FILE,
+        SymbolKind::Module => lsp_types::SymbolKind::MODULE,
+        SymbolKind::Namespace => lsp_types::SymbolKind::NAMESPACE,
+        SymbolKind::Package => lsp_types::SymbolKind::PACKAGE,
+        SymbolKind::Class => lsp_types::SymbolKind::CLASS,
+        SymbolKind::Method => lsp_types::SymbolKind::METHOD,
+        SymbolKind::Property => lsp_types::SymbolKind::PROPERTY,
+        SymbolKind::Field => lsp_types::SymbolKind::FIELD,
+        SymbolKind::Constructor => lsp_types::SymbolKind::CONSTRUCTOR,
+        SymbolKind::Enum => lsp_types::SymbolKind::ENUM,
+        SymbolKind::Interface => lsp_types::SymbolKind::INTERFACE,
+        SymbolKind::Function => lsp_types::SymbolKind::FUNCTION,
+        SymbolKind::Variable => lsp_types::SymbolKind::VARIABLE,
+        SymbolKind::Constant => lsp_types::SymbolKind::CONSTANT,
+        SymbolKind::String => lsp_types::SymbolKind::STRING,
+        SymbolKind::Number => lsp_types::SymbolKind::NUMBER,
+        SymbolKind::Boolean => lsp_types::SymbolKind::BOOLEAN,
+        SymbolKind::Array => lsp_types::SymbolKind::ARRAY,
+        SymbolKind::Object => lsp_types::SymbolKind::OBJECT,
+        SymbolKind::Key => lsp_types::SymbolKind::KEY,
+        SymbolKind::Null => lsp_types::SymbolKind::NULL,
+        SymbolKind::EnumMember => lsp_types::SymbolKind::ENUM_MEMBER,
+        SymbolKind::Struct => lsp_types::SymbolKind::STRUCT,
+        SymbolKind::Event => lsp_types::SymbolKind::EVENT,
+        SymbolKind::Operator => lsp_types::SymbolKind::OPERATOR,
+        SymbolKind::TypeParameter => lsp_types::SymbolKind::TYPE_PARAMETER,
+    }
+}
+
 #[allow(deprecated)]
 pub(crate) fn lsp_document_symbol_from_native(
     DocumentSymbol {
@@ -90,39 +252,13 @@ pub(crate) fn lsp_document_symbol_from_native(
         selection_range,
         children,
     }: DocumentSymbol,
-    line_index: &LineIndex,
+    line_index: FileLineIndex,
+    encoding: PositionEncoding,
 ) -> Option<lsp_types::DocumentSymbol> {
     Some(lsp_types::DocumentSymbol {
         name,
         detail,
-        kind: match kind {
-            SymbolKind::File => lsp_types::SymbolKind::FILE,
-            SymbolKind::Module => lsp_types::SymbolKind::MODULE,
-            SymbolKind::Namespace => lsp_types::SymbolKind::NAMESPACE,
-            SymbolKind::Package => lsp_types::SymbolKind::PACKAGE,
-            SymbolKind::Class => lsp_types::SymbolKind::CLASS,
-            SymbolKind::Method => lsp_types::SymbolKind::METHOD,
-            SymbolKind::Property => lsp_types::SymbolKind::PROPERTY,
-            SymbolKind::Field => lsp_types::SymbolKind::FIELD,
-            SymbolKind::Constructor => lsp_types::SymbolKind::CONSTRUCTOR,
-            SymbolKind::Enum => lsp_types::SymbolKind::ENUM,
-            SymbolKind::Interface => lsp_types::SymbolKind::INTERFACE,
-            SymbolKind::Function => lsp_types::SymbolKind::FUNCTION,
-            SymbolKind::Variable => lsp_types::SymbolKind::VARIABLE,
-            SymbolKind::Constant => lsp_types::SymbolKind::CONSTANT,
-            SymbolKind::String => lsp_types::SymbolKind::STRING,
-            SymbolKind::Number => lsp_types::SymbolKind::NUMBER,
-            SymbolKind::Boolean => lsp_types::SymbolKind::BOOLEAN,
-            SymbolKind::Array => lsp_types::SymbolKind::ARRAY,
-            SymbolKind::Object => lsp_types::SymbolKind::OBJECT,
-            SymbolKind::Key => lsp_types::SymbolKind::KEY,
-            SymbolKind::Null => lsp_types::SymbolKind::NULL,
-            SymbolKind::EnumMember => lsp_types::SymbolKind::ENUM_MEMBER,
-            SymbolKind::Struct => lsp_types::SymbolKind::STRUCT,
-            SymbolKind::Event => lsp_types::SymbolKind::EVENT,
-            SymbolKind::Operator => lsp_types::SymbolKind::OPERATOR,
-            SymbolKind::TypeParameter => lsp_types::SymbolKind::TYPE_PARAMETER,
-        },
+        kind: lsp_symbol_kind_from_native(kind),
         tags: tags.map(|tags| {
             tags.into_iter()
                 .map(|tag| match tag {
@@ -130,14 +266,136 @@ pub(crate) fn lsp_document_symbol_from_native(
                 })
                 .collect()
         }),
-        range: lsp_range_from_text_range(range, line_index)?,
-        selection_range: lsp_range_from_text_range(selection_range, line_index)?,
+        range: lsp_range_from_text_range(range, line_index, encoding)?,
+        selection_range: lsp_range_from_text_range(selection_range, line_index, encoding)?,
         children: children.map(|children| {
             children
                 .into_iter()
-                .filter_map(|child| lsp_document_symbol_from_native(child, line_index))
+                .filter_map(|child| lsp_document_symbol_from_native(child, line_index, encoding))
                 .collect()
         }),
         deprecated: None,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use line_index::LineIndex;
+    use lsp_types::{
+        ClientCapabilities, GeneralClientCapabilities, Position, PositionEncodingKind,
+    };
+    use starpls_common::normalize;
+    use starpls_syntax::TextSize;
+
+    use super::{
+        lsp_position_from_text_size, text_size_from_lsp_position_with_index, FileLineIndex,
+        PositionEncoding,
+    };
+
+    fn negotiate(position_encodings: Option<Vec<PositionEncodingKind>>) -> PositionEncoding {
+        PositionEncoding::negotiate(&ClientCapabilities {
+            general: Some(GeneralClientCapabilities {
+                position_encodings,
+                ..Default::default()
+            }),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_negotiate_prefers_utf8() {
+        assert_eq!(
+            negotiate(Some(vec![
+                PositionEncodingKind::UTF16,
+                PositionEncodingKind::UTF8
+            ])),
+            PositionEncoding::Utf8
+        );
+    }
+
+    #[test]
+    fn test_negotiate_utf32() {
+        assert_eq!(
+            negotiate(Some(vec![PositionEncodingKind::UTF32])),
+            PositionEncoding::Utf32
+        );
+    }
+
+    #[test]
+    fn test_negotiate_defaults_to_utf16() {
+        assert_eq!(negotiate(None), PositionEncoding::Utf16);
+        assert_eq!(
+            PositionEncoding::negotiate(&ClientCapabilities::default()),
+            PositionEncoding::Utf16
+        );
+    }
+
+    #[test]
+    fn test_non_ascii_columns() {
+        let text = "s = \"h\u{e9}llo\u{1f600}\"\nx = 1\n";
+        let index = LineIndex::new(text);
+
+        // The closing quote comes after a two-byte and a four-byte character.
+        let offset = TextSize::from(15);
+        for (encoding, character) in [
+            (PositionEncoding::Utf8, 15),
+            (PositionEncoding::Utf16, 12),
+            (PositionEncoding::Utf32, 11),
+        ] {
+            let pos = Position { line: 0, character };
+            assert_eq!(
+                lsp_position_from_text_size(offset, &index, encoding),
+                Some(pos),
+                "{:?}",
+                encoding
+            );
+            assert_eq!(
+                text_size_from_lsp_position_with_index(&index, encoding, pos),
+                Some(offset),
+                "{:?}",
+                encoding
+            );
+        }
+    }
+
+    #[test]
+    fn test_bom_shifts_first_line() {
+        let (text, _, has_bom) = normalize("\u{feff}x = 1\ny = 2\n".to_string());
+        assert!(has_bom);
+        let index = LineIndex::new(&text);
+        let line_index = FileLineIndex {
+            index: &index,
+            has_bom,
+        };
+        for (encoding, character) in [
+            (PositionEncoding::Utf8, 7),
+            (PositionEncoding::Utf16, 5),
+            (PositionEncoding::Utf32, 5),
+        ] {
+            // The `1` on the first line is after the byte order mark.
+            let pos = Position { line: 0, character };
+            assert_eq!(
+                lsp_position_from_text_size(TextSize::from(4), line_index, encoding),
+                Some(pos)
+            );
+            assert_eq!(
+                text_size_from_lsp_position_with_index(line_index, encoding, pos),
+                Some(TextSize::from(4))
+            );
+
+            // Other lines aren't affected.
+            let pos = Position {
+                line: 1,
+                character: 0,
+            };
+            assert_eq!(
+                lsp_position_from_text_size(TextSize::from(6), line_index, encoding),
+                Some(pos)
+            );
+            assert_eq!(
+                text_size_from_lsp_position_with_index(line_index, encoding, pos),
+                Some(TextSize::from(6))
+            );
+        }
+    }
+}
@@ -0,0 +1,153 @@
+//! Runs every analysis over a tree of Starlark files and reports the ones that panic, so that
+//! crashes can be found before the server is pointed at the tree, e.g. by a CI bot.
+
+use std::{
+    panic::{self, AssertUnwindSafe},
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+use anyhow::{anyhow, bail};
+use starpls_common::read_source;
+use starpls_ide::FilePosition;
+use starpls_syntax::{line_index, parse_module, TextSize};
+
+use crate::{check::create_analysis, impact::add_workspace_files};
+
+/// A panic caught while analyzing a file.
+struct Crash {
+    path: PathBuf,
+    operation: &'static str,
+    /// The line and column of the position that the operation was requested at, if any.
+    position: Option<(u32, u32)>,
+    message: String,
+}
+
+pub(crate) fn run_crash_check(
+    path: String,
+    max_positions_per_file: usize,
+    output_base: Option<String>,
+) -> anyhow::Result<()> {
+    let (mut analysis, interner, _) = create_analysis(output_base)?;
+    let dir = PathBuf::from(&path)
+        .canonicalize()
+        .map_err(|_| anyhow!("Could not resolve the directory {:?}.", path))?;
+    add_workspace_files(&mut analysis, &interner, &dir)?;
+
+    // Replace the default hook, which would print every panic as it happens, with one that keeps
+    // the message and location of the last panic for the report.
+    let last_panic = Arc::new(Mutex::new(None));
+    let prev_hook = panic::take_hook();
+    panic::set_hook({
+        let last_panic = last_panic.clone();
+        Box::new(move |info| *last_panic.lock().unwrap() = Some(info.to_string()))
+    });
+
+    let mut entries = interner.entries();
+    entries.sort_by(|(_, a), (_, b)| a.cmp(b));
+    let snap = analysis.snapshot();
+    let mut crashes = Vec::new();
+    let mut operations = 0;
+    for (file_id, path) in &entries {
+        let contents = match read_source(path) {
+            Ok(decoded) => decoded.text,
+            Err(_) => continue,
+        };
+        let mut run = |operation: &'static str, pos: Option<TextSize>, f: &dyn Fn()| {
+            operations += 1;
+            if panic::catch_unwind(AssertUnwindSafe(f)).is_err() {
+                let message = last_panic
+                    .lock()
+                    .unwrap()
+                    .take()
+                    .unwrap_or_else(|| "unknown panic".to_string());
+                crashes.push(Crash {
+                    path: path.clone(),
+                    operation,
+                    position: pos.map(|pos| {
+                        let line_col = line_index(&contents).line_col(pos);
+                        (line_col.line + 1, line_col.col + 1)
+                    }),
+                    message,
+                });
+            }
+        };
+
+        run("parse", None, &|| {
+            let _ = snap.parse(*file_id);
+        });
+        run("lower", None, &|| {
+            let _ = snap.lower(*file_id);
+        });
+        run("diagnostics", None, &|| {
+            let _ = snap.diagnostics(*file_id);
+        });
+        run("document symbols", None, &|| {
+            let _ = snap.document_symbols(*file_id);
+        });
+        for pos in sample_positions(&contents, max_positions_per_file) {
+            let file_pos = FilePosition {
+                file_id: *file_id,
+                pos,
+            };
+            run("hover", Some(pos), &|| {
+                let _ = snap.hover(file_pos.clone());
+            });
+            run("completion", Some(pos), &|| {
+                let _ = snap.completion(file_pos.clone(), None);
+            });
+            run("goto definition", Some(pos), &|| {
+                let _ = snap.goto_definition(file_pos.clone());
+            });
+            run("signature help", Some(pos), &|| {
+                let _ = snap.signature_help(file_pos.clone());
+            });
+        }
+    }
+    panic::set_hook(prev_hook);
+
+    for crash in &crashes {
+        match crash.position {
+            Some((line, col)) => eprintln!(
+                "{}:{}:{}: {} panicked: {}",
+                crash.path.display(),
+                line,
+                col,
+                crash.operation,
+                crash.message
+            ),
+            None => eprintln!(
+                "{}: {} panicked: {}",
+                crash.path.display(),
+                crash.operation,
+                crash.message
+            ),
+        }
+    }
+    println!(
+        "Checked {} files with {} operations, {} panicked",
+        entries.len(),
+        operations,
+        crashes.len()
+    );
+    if !crashes.is_empty() {
+        bail!("found {} panics", crashes.len());
+    }
+    Ok(())
+}
+
+/// Picks up to `max` positions at the starts of tokens, spread evenly over the file.
+fn sample_positions(contents: &str, max: usize) -> Vec<TextSize> {
+    let tree = parse_module(contents, &mut |_| {});
+    let starts = tree
+        .syntax()
+        .descendants_with_tokens()
+        .filter_map(|element| element.into_token())
+        .filter(|token| !token.kind().is_trivia_token())
+        .map(|token| token.text_range().start())
+        .collect::<Vec<_>>();
+    if starts.len() <= max {
+        return starts;
+    }
+    (0..max).map(|i| starts[i * starts.len() / max]).collect()
+}
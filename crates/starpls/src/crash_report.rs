@@ -0,0 +1,225 @@
+//! Reports for internal errors, i.e. panics in request handlers.
+//!
+//! When a handler panics, a report containing the server version, the failed request, the
+//! backtrace of the panic, and the most recent events handled by the server is written to the
+//! system's temporary directory, so that it can be attached to a bug report. An excerpt of the
+//! file that the request was made against is only included if the user opts in, and even then
+//! the contents of its strings and comments are masked.
+
+use std::{
+    backtrace::Backtrace,
+    cell::RefCell,
+    collections::VecDeque,
+    fmt::Write as _,
+    fs, panic,
+    path::PathBuf,
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+
+use parking_lot::Mutex;
+
+use crate::{convert, server::ServerSnapshot};
+
+/// The number of recent events kept for inclusion in reports.
+const TRACE_CAPACITY: usize = 100;
+
+/// The number of lines included on either side of the requested position in file excerpts.
+const EXCERPT_CONTEXT_LINES: u32 = 5;
+
+thread_local! {
+    /// The backtrace of the last panic on the current thread, which is captured by the panic hook
+    /// and taken when the panic is caught.
+    static LAST_BACKTRACE: RefCell<Option<Backtrace>> = const { RefCell::new(None) };
+}
+
+/// Installs a panic hook that captures the backtrace of each panic, in addition to running the
+/// previously installed hook.
+pub(crate) fn install_panic_hook() {
+    let prev_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        LAST_BACKTRACE.with(|backtrace| *backtrace.borrow_mut() = Some(Backtrace::force_capture()));
+        prev_hook(info);
+    }));
+}
+
+/// Takes the backtrace of the last panic on the current thread.
+pub(crate) fn take_backtrace() -> Option<Backtrace> {
+    LAST_BACKTRACE.with(|backtrace| backtrace.borrow_mut().take())
+}
+
+pub(crate) struct CrashReporter {
+    start: Instant,
+    trace: Mutex<VecDeque<String>>,
+    include_source: bool,
+}
+
+impl CrashReporter {
+    pub(crate) fn new(include_source: bool) -> Self {
+        Self {
+            start: Instant::now(),
+            trace: Default::default(),
+            include_source,
+        }
+    }
+
+    /// Records an event, e.g. an incoming request, for inclusion in future reports.
+    pub(crate) fn record(&self, event: String) {
+        let mut trace = self.trace.lock();
+        if trace.len() == TRACE_CAPACITY {
+            trace.pop_front();
+        }
+        trace.push_back(format!(
+            "[{:>10.3}s] {}",
+            self.start.elapsed().as_secs_f64(),
+            event
+        ));
+    }
+
+    /// Writes a report for a request whose handler panicked, returning the path of the report.
+    pub(crate) fn write_report(
+        &self,
+        snapshot: &ServerSnapshot,
+        req: &lsp_server::Request,
+        message: &str,
+        backtrace: Option<Backtrace>,
+    ) -> anyhow::Result<PathBuf> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_millis())
+            .unwrap_or_default();
+        let mut report = String::new();
+        writeln!(report, "starpls crash report")?;
+        writeln!(report)?;
+        writeln!(report, "Version: {}", env!("CARGO_PKG_VERSION"))?;
+        writeln!(report, "Timestamp: {}", timestamp)?;
+        writeln!(report, "Request: {} (id {})", req.method, req.id)?;
+        writeln!(report, "Error: {}", message)?;
+        writeln!(report)?;
+        writeln!(report, "Parameters:")?;
+        writeln!(report, "{}", serde_json::to_string_pretty(&req.params)?)?;
+        writeln!(report)?;
+        writeln!(report, "Backtrace:")?;
+        match backtrace {
+            Some(backtrace) => writeln!(report, "{}", backtrace)?,
+            None => writeln!(report, "(unavailable)")?,
+        }
+        writeln!(report)?;
+        writeln!(report, "Recent events:")?;
+        for event in self.trace.lock().iter() {
+            writeln!(report, "{}", event)?;
+        }
+        if self.include_source {
+            if let Some(excerpt) = excerpt(snapshot, &req.params) {
+                writeln!(report)?;
+                writeln!(report, "Source excerpt (strings and comments masked):")?;
+                write!(report, "{}", excerpt)?;
+            }
+        }
+
+        let path = std::env::temp_dir().join(format!(
+            "starpls-crash-{}-{}.txt",
+            std::process::id(),
+            timestamp
+        ));
+        fs::write(&path, report)?;
+        Ok(path)
+    }
+}
+
+/// Returns the lines around the position that a request was made at, for requests that are made
+/// at a position in a document.
+fn excerpt(snapshot: &ServerSnapshot, params: &serde_json::Value) -> Option<String> {
+    let uri = params.get("textDocument")?.get("uri")?.as_str()?;
+    let path = convert::path_buf_from_url(&lsp_types::Url::parse(uri).ok()?).ok()?;
+    let line = params
+        .get("position")
+        .or_else(|| params.get("range").and_then(|range| range.get("start")))
+        .and_then(|position| position.get("line"))
+        .and_then(|line| line.as_u64())
+        .unwrap_or_default() as u32;
+
+    let document_manager = snapshot.document_manager.read();
+    let file_id = document_manager.lookup_by_path_buf(&path)?;
+    let contents = anonymize(&document_manager.get(file_id)?.contents);
+    let start = line.saturating_sub(EXCERPT_CONTEXT_LINES);
+    let mut excerpt = String::new();
+    for (i, text) in contents
+        .lines()
+        .enumerate()
+        .skip(start as usize)
+        .take((line - start + EXCERPT_CONTEXT_LINES + 1) as usize)
+    {
+        let marker = if i as u32 == line { '>' } else { ' ' };
+        let _ = writeln!(excerpt, "{} {:>5} | {}", marker, i + 1, text);
+    }
+    Some(excerpt)
+}
+
+/// Masks the contents of the string literals and comments in the given source text, since these
+/// are the parts most likely to contain sensitive information, e.g. internal labels, URLs, and
+/// prose. Code structure and identifiers are left intact.
+fn anonymize(text: &str) -> String {
+    let mut res = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    let mut quote: Option<(char, bool)> = None;
+    let mut in_comment = false;
+    let mask = |c: char| if c.is_whitespace() { c } else { '*' };
+
+    while let Some(c) = chars.next() {
+        if in_comment {
+            in_comment = c != '\n';
+            res.push(mask(c));
+            continue;
+        }
+
+        match quote {
+            Some((quote_char, is_triple)) => {
+                if c == '\\' {
+                    res.push(c);
+                    if let Some(next) = chars.next() {
+                        res.push(mask(next));
+                    }
+                } else if c == quote_char {
+                    if !is_triple {
+                        quote = None;
+                        res.push(c);
+                    } else if chars.peek() == Some(&quote_char) {
+                        chars.next();
+                        if chars.peek() == Some(&quote_char) {
+                            chars.next();
+                            quote = None;
+                            res.extend([c; 3]);
+                        } else {
+                            res.push_str("**");
+                        }
+                    } else {
+                        res.push('*');
+                    }
+                } else if c == '\n' && !is_triple {
+                    // Unterminated strings end at the end of the line.
+                    quote = None;
+                    res.push(c);
+                } else {
+                    res.push(mask(c));
+                }
+            }
+            None => {
+                res.push(c);
+                if c == '#' {
+                    in_comment = true;
+                } else if c == '"' || c == '\'' {
+                    let mut lookahead = chars.clone();
+                    let is_triple = lookahead.next() == Some(c) && lookahead.next() == Some(c);
+                    if is_triple {
+                        chars.next();
+                        chars.next();
+                        res.extend([c; 2]);
+                    }
+                    quote = Some((c, is_triple));
+                }
+            }
+        }
+    }
+
+    res
+}
@@ -6,12 +6,18 @@ use starpls_common::FileId;
 
 use crate::event_loop::Task;
 
+/// Collects the files changed by a burst of edits, and sends them as a single task once no edits
+/// have been made for the given duration.
 pub(crate) struct AnalysisDebouncer {
     pub(crate) sender: Sender<Vec<FileId>>,
 }
 
 impl AnalysisDebouncer {
-    pub(crate) fn new(duration: Duration, sink: Sender<Task>) -> Self {
+    pub(crate) fn new(
+        duration: Duration,
+        sink: Sender<Task>,
+        to_task: fn(Vec<FileId>) -> Task,
+    ) -> Self {
         let (source_tx, source_rx) = crossbeam_channel::unbounded::<Vec<FileId>>();
 
         std::thread::spawn(move || {
@@ -23,7 +29,7 @@ impl AnalysisDebouncer {
                         Ok(file_ids) => pending_file_ids.extend(file_ids.into_iter()),
                         Err(RecvTimeoutError::Disconnected) => break,
                         Err(RecvTimeoutError::Timeout) => {
-                            sink.send(Task::AnalysisRequested(pending_file_ids.drain().collect()))
+                            sink.send(to_task(pending_file_ids.drain().collect()))
                                 .unwrap();
                             active = false;
                         }
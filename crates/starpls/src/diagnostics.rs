@@ -6,6 +6,9 @@ use starpls_common::FileId;
 #[derive(Default)]
 pub(crate) struct DiagnosticsManager {
     diagnostics: HashMap<FileId, Vec<lsp_types::Diagnostic>>,
+    /// Buildifier's lint warnings, which are computed separately from the server's own
+    /// diagnostics.
+    buildifier_diagnostics: HashMap<FileId, Vec<lsp_types::Diagnostic>>,
     files_with_changed_diagnostics: Vec<FileId>,
 }
 
@@ -28,6 +31,15 @@ impl DiagnosticsManager {
         self.files_with_changed_diagnostics.push(file_id);
     }
 
+    pub(crate) fn set_buildifier_diagnostics(
+        &mut self,
+        file_id: FileId,
+        diagnostics: Vec<Diagnostic>,
+    ) {
+        self.buildifier_diagnostics.insert(file_id, diagnostics);
+        self.files_with_changed_diagnostics.push(file_id);
+    }
+
     pub(crate) fn take_changes(&mut self) -> Vec<FileId> {
         mem::take(&mut self.files_with_changed_diagnostics)
     }
@@ -36,7 +48,11 @@ impl DiagnosticsManager {
         &self,
         file_id: FileId,
     ) -> impl Iterator<Item = &lsp_types::Diagnostic> {
-        self.diagnostics.get(&file_id).into_iter().flatten()
+        self.diagnostics
+            .get(&file_id)
+            .into_iter()
+            .chain(self.buildifier_diagnostics.get(&file_id))
+            .flatten()
     }
 }
 
@@ -3,17 +3,18 @@ use std::panic;
 use starpls_ide::Cancelled;
 
 use crate::{
+    crash_report,
     event_loop::Task,
     server::{Server, ServerSnapshot},
 };
 
 pub(crate) struct RequestDispatcher<'a> {
     req: Option<lsp_server::Request>,
-    server: &'a Server,
+    server: &'a mut Server,
 }
 
 impl<'a> RequestDispatcher<'a> {
-    pub(crate) fn new(req: lsp_server::Request, server: &'a Server) -> Self {
+    pub(crate) fn new(req: lsp_server::Request, server: &'a mut Server) -> Self {
         Self {
             req: Some(req),
             server,
@@ -53,14 +54,31 @@ impl<'a> RequestDispatcher<'a> {
                         .downcast_ref::<String>()
                         .map(String::as_str)
                         .or_else(|| err.downcast_ref::<&str>().copied());
-                    lsp_server::Response::new_err(
-                        req.id,
-                        lsp_server::ErrorCode::RequestFailed as i32,
-                        format!(
-                            "request handler panicked: {}",
-                            panic_message.unwrap_or("unknown reason")
+                    let message = format!(
+                        "request handler panicked: {}",
+                        panic_message.unwrap_or("unknown reason")
+                    );
+                    let report_path = match snapshot.crash_reporter.write_report(
+                        &snapshot,
+                        &req,
+                        &message,
+                        crash_report::take_backtrace(),
+                    ) {
+                        Ok(path) => Some(path),
+                        Err(err) => {
+                            eprintln!("server: failed to write crash report: {}", err);
+                            None
+                        }
+                    };
+                    return Task::RequestPanicked {
+                        response: lsp_server::Response::new_err(
+                            req.id,
+                            lsp_server::ErrorCode::RequestFailed as i32,
+                            message,
                         ),
-                    )
+                        method: req.method,
+                        report_path,
+                    };
                 }
             };
 
@@ -70,6 +88,36 @@ impl<'a> RequestDispatcher<'a> {
         self
     }
 
+    /// Handles the request synchronously on the main thread. This is used for requests that need to
+    /// mutate the server's state.
+    pub(crate) fn on_sync_mut<R>(
+        &mut self,
+        f: fn(&mut Server, R::Params) -> anyhow::Result<R::Result>,
+    ) -> &mut Self
+    where
+        R: lsp_types::request::Request + 'static,
+        R::Params: serde::de::DeserializeOwned,
+    {
+        let (req, params) = match self.parse::<R>() {
+            Some(res) => res,
+            None => return self,
+        };
+
+        let response = match f(&mut *self.server, params) {
+            Ok(res) => lsp_server::Response::new_ok(req.id, res),
+            Err(err) => lsp_server::Response::new_err(
+                req.id,
+                lsp_server::ErrorCode::RequestFailed as i32,
+                err.to_string(),
+            ),
+        };
+        self.server
+            .task_pool_handle
+            .spawn(move || Task::ResponseReady(response));
+
+        self
+    }
+
     pub(crate) fn finish(&mut self) {
         let req = match self.req.take() {
             Some(req) => req,
@@ -2,6 +2,7 @@ use std::{
     collections::HashMap,
     fs,
     hash::BuildHasherDefault,
+    io::Read,
     mem,
     path::{Path, PathBuf, MAIN_SEPARATOR},
     sync::Arc,
@@ -14,15 +15,26 @@ use indexmap::IndexSet;
 use parking_lot::RwLock;
 use rustc_hash::FxHasher;
 use starpls_bazel::{
-    self,
+    self, buck2,
+    bzlmod::ModuleResolver,
     client::BazelClient,
     label::{PartialParse, RepoKind},
     APIContext, Label, ParseError,
 };
-use starpls_common::{Dialect, FileId, LoadItemCandidate, LoadItemCandidateKind, ResolvedPath};
+use starpls_common::{
+    normalize, read_source, Dialect, FileId, LineEndings, LoadItemCandidate, LoadItemCandidateKind,
+    ResolvedPath,
+};
 use starpls_ide::FileLoader;
 
-use crate::event_loop::{FetchExternalRepoRequest, Task};
+use crate::{
+    event_loop::{FetchExternalRepoRequest, Task},
+    load_index::LoadIndex,
+    project_config::ProjectConfigLoader,
+};
+
+/// The names of the files that declare Bazel packages, in the order that Bazel looks for them.
+const BAZEL_BUILD_FILE_NAMES: &[&str] = &["BUILD.bazel", "BUILD"];
 
 #[derive(Debug, Clone, Copy)]
 pub(crate) enum DocumentSource {
@@ -38,8 +50,13 @@ impl From<Option<i32>> for DocumentSource {
 
 /// Represents an active text document. Text documents may either be sourced from disk
 /// or from the editor.
+/// The contents of a document are normalized, i.e. they don't contain a byte order mark and
+/// always use `\n` line endings; the document's original line endings are restored in any edits
+/// sent back to the editor, and positions on its first line account for its byte order mark.
 pub(crate) struct Document {
     pub(crate) contents: String,
+    pub(crate) line_endings: LineEndings,
+    pub(crate) has_bom: bool,
     pub(crate) dialect: Dialect,
     pub(crate) api_context: Option<APIContext>,
     pub(crate) source: DocumentSource,
@@ -52,8 +69,11 @@ impl Document {
         api_context: Option<APIContext>,
         version: Option<i32>,
     ) -> Self {
+        let (contents, line_endings, has_bom) = normalize(contents);
         Self {
             contents,
+            line_endings,
+            has_bom,
             dialect,
             api_context,
             source: version.into(),
@@ -72,15 +92,20 @@ pub(crate) struct DocumentManager {
     has_closed_or_opened_documents: bool,
     changed_file_ids: Vec<(FileId, DocumentChangeKind)>,
     path_interner: Arc<PathInterner>,
+    project_configs: Arc<ProjectConfigLoader>,
 }
 
 impl DocumentManager {
-    pub(crate) fn new(path_interner: Arc<PathInterner>) -> Self {
+    pub(crate) fn new(
+        path_interner: Arc<PathInterner>,
+        project_configs: Arc<ProjectConfigLoader>,
+    ) -> Self {
         Self {
             documents: Default::default(),
             has_closed_or_opened_documents: false,
             changed_file_ids: Default::default(),
             path_interner,
+            project_configs,
         }
     }
 
@@ -91,6 +116,11 @@ impl DocumentManager {
             Some(res) => res,
             None => return,
         };
+        let dialect = self
+            .project_configs
+            .config_for_path(&path)
+            .dialect
+            .unwrap_or(dialect);
         let file_id = self.path_interner.intern_path(path);
         self.documents.insert(
             file_id,
@@ -111,6 +141,14 @@ impl DocumentManager {
 
     pub(crate) fn modify(&mut self, file_id: FileId, contents: String, version: Option<i32>) {
         if let Some(document) = self.documents.get_mut(&file_id) {
+            // Edits can introduce `\r\n` line endings, but usually don't contain line endings at
+            // all, so they never switch a document back to `\n` line endings. Likewise, edits are
+            // applied to contents without the byte order mark, so they never remove it.
+            let (contents, line_endings, has_bom) = normalize(contents);
+            if line_endings == LineEndings::Dos {
+                document.line_endings = line_endings;
+            }
+            document.has_bom |= has_bom;
             document.contents = contents;
             document.source = version.into();
             self.changed_file_ids
@@ -125,6 +163,27 @@ impl DocumentManager {
         (has_opened_or_closed_documents, changed_documents)
     }
 
+    pub(crate) fn file_ids(&self) -> Vec<FileId> {
+        self.documents.keys().copied().collect()
+    }
+
+    /// Returns the line endings of the given document, which should be applied to the new text of
+    /// any edits to it.
+    pub(crate) fn line_endings(&self, file_id: FileId) -> LineEndings {
+        self.documents
+            .get(&file_id)
+            .map(|document| document.line_endings)
+            .unwrap_or_default()
+    }
+
+    /// Returns whether the given document starts with a byte order mark, which the client counts
+    /// as a character on its first line.
+    pub(crate) fn has_bom(&self, file_id: FileId) -> bool {
+        self.documents
+            .get(&file_id)
+            .map_or(false, |document| document.has_bom)
+    }
+
     pub(crate) fn get(&self, file_id: FileId) -> Option<&Document> {
         self.documents.get(&file_id)
     }
@@ -136,6 +195,12 @@ impl DocumentManager {
     pub(crate) fn lookup_by_path_buf(&self, path: &PathBuf) -> Option<FileId> {
         self.path_interner.lookup_by_path_buf(path)
     }
+
+    /// Returns the paths of every file known to the server along with their file IDs, including
+    /// files that aren't open.
+    pub(crate) fn entries(&self) -> Vec<(FileId, PathBuf)> {
+        self.path_interner.entries()
+    }
 }
 
 #[derive(Default, Debug)]
@@ -163,6 +228,16 @@ impl PathInterner {
             .expect("unknown file_id")
             .clone()
     }
+
+    /// Returns all interned paths along with their file IDs.
+    pub(crate) fn entries(&self) -> Vec<(FileId, PathBuf)> {
+        self.map
+            .read()
+            .iter()
+            .enumerate()
+            .map(|(index, path)| (FileId(index as u32), path.clone()))
+            .collect()
+    }
 }
 
 pub(crate) struct DefaultFileLoader {
@@ -173,6 +248,11 @@ pub(crate) struct DefaultFileLoader {
     cached_load_results: DashMap<String, PathBuf>,
     fetch_repo_sender: Sender<Task>,
     bzlmod_enabled: bool,
+    /// Resolves repositories from `MODULE.bazel` files when Bazel can't provide the mapping.
+    module_resolver: Arc<ModuleResolver>,
+    /// Finds the files in the workspace that load a given file. Only the language server keeps
+    /// one up to date.
+    load_index: Option<Arc<LoadIndex>>,
 }
 
 impl DefaultFileLoader {
@@ -183,6 +263,8 @@ impl DefaultFileLoader {
         external_output_base: PathBuf,
         fetch_repo_sender: Sender<Task>,
         bzlmod_enabled: bool,
+        module_resolver: Arc<ModuleResolver>,
+        load_index: Option<Arc<LoadIndex>>,
     ) -> Self {
         Self {
             bazel_client,
@@ -192,9 +274,29 @@ impl DefaultFileLoader {
             cached_load_results: Default::default(),
             fetch_repo_sender,
             bzlmod_enabled,
+            module_resolver,
+            load_index,
+        }
+    }
+
+    /// Indexes the `load` statements of every file in the workspace, so that files loading a given
+    /// file are found even if they aren't open.
+    pub(crate) fn index_workspace_loads(&self) {
+        if let Some(load_index) = &self.load_index {
+            load_index.index_workspace(&|from, path| self.resolve_load_from_path(from, path));
         }
     }
 
+    /// Resolves a `load` in the file at the given path for the load index. Files that can't be
+    /// loaded, e.g. because their repository hasn't been fetched, are left out.
+    fn resolve_load_from_path(&self, from: &Path, path: &str) -> Option<PathBuf> {
+        let (dialect, _) = dialect_and_api_context_for_path(from)?;
+        self.resolve_load(path, dialect, from, None)
+            .ok()
+            .flatten()
+            .map(|(path, _, _)| path)
+    }
+
     fn make_cache_key(&self, repo_kind: &RepoKind, path: &str, from: FileId) -> String {
         format!("{:?}-{:?}-{:?}", repo_kind, path, from.0)
     }
@@ -231,12 +333,15 @@ impl DefaultFileLoader {
         from_path
     }
 
-    fn resolve_label(&self, label: &Label, from: FileId) -> anyhow::Result<Option<ResolvedLabel>> {
+    fn resolve_label(
+        &self,
+        label: &Label,
+        from_path: &Path,
+    ) -> anyhow::Result<Option<ResolvedLabel>> {
         let repo_kind = label.kind();
         let mut canonical_repo_res = None;
         let (root, package) = match &repo_kind {
             RepoKind::Apparent if self.bzlmod_enabled => {
-                let from_path = self.interner.lookup_by_file_id(from);
                 let from_repo = match from_path.strip_prefix(&self.external_output_base) {
                     Result::Ok(stripped) => match stripped
                         .components()
@@ -256,9 +361,13 @@ impl DefaultFileLoader {
                     }
                 };
 
-                let canonical_repo = self
+                let canonical_repo = match self
                     .bazel_client
-                    .resolve_repo_from_mapping(label.repo(), from_repo)?;
+                    .resolve_repo_from_mapping(label.repo(), from_repo)?
+                {
+                    Some(canonical_repo) => Some(canonical_repo),
+                    None => self.module_resolver.resolve_repo(label.repo(), from_repo),
+                };
                 match canonical_repo {
                     Some(canonical_repo) => (
                         if canonical_repo.is_empty() {
@@ -289,8 +398,7 @@ impl DefaultFileLoader {
             }
             RepoKind::Current => {
                 // Find the Bazel workspace root.
-                let from_path = self.interner.lookup_by_file_id(from);
-                match starpls_bazel::resolve_workspace(&from_path)? {
+                match starpls_bazel::resolve_workspace(from_path)? {
                     Some(root) => root,
                     None => {
                         bail!("not in a Bazel workspace")
@@ -313,6 +421,102 @@ impl DefaultFileLoader {
         }))
     }
 
+    /// Resolves a package, e.g. `//foo`, `@repo//foo`, or the empty string for the package
+    /// containing `from`, to its directory.
+    fn resolve_package_dir(
+        &self,
+        package: &str,
+        from: FileId,
+    ) -> anyhow::Result<Option<ResolvedLabel>> {
+        // Any target will do, since only the package's directory is needed.
+        let package = package
+            .strip_suffix('/')
+            .filter(|package| !package.ends_with('/'))
+            .unwrap_or(package);
+        let text = format!("{}:BUILD", package);
+        let label = match Label::parse(&text) {
+            Result::Ok(label) => label,
+            Err(_) => return Ok(None),
+        };
+        Ok(self
+            .resolve_label(&label, &self.interner.lookup_by_file_id(from))?
+            .and_then(|mut res| res.resolved_path.pop().then_some(res)))
+    }
+
+    /// Resolves a `load` of `path` to the file that it loads, along with the file's API context and
+    /// its canonical repository, if it's in one.
+    /// Results are cached by the loading file's ID, if it has one.
+    fn resolve_load(
+        &self,
+        path: &str,
+        dialect: Dialect,
+        from_path: &Path,
+        from: Option<FileId>,
+    ) -> anyhow::Result<Option<(PathBuf, Option<APIContext>, Option<String>)>> {
+        Ok(Some(match dialect {
+            Dialect::Standard => {
+                // Resolve the given path relative to the importing file's directory.
+                let from_dir = match from_path.parent() {
+                    Some(from_dir) => from_dir,
+                    None => return Ok(None),
+                };
+                (from_dir.join(path).canonicalize()?, None, None)
+            }
+            Dialect::Bazel => {
+                // Parse the load path as a Bazel label.
+                let label = match Label::parse(path) {
+                    Result::Ok(label) => label,
+                    Err(err) => return Err(anyhow!("error parsing label: {}", err.err)),
+                };
+
+                // Only .bzl files can be loaded.
+                if !label.target().ends_with(".bzl") {
+                    bail!("cannot load a non-bzl file");
+                }
+
+                let repo_kind = label.kind();
+                let (resolved_path, canonical_repo) =
+                    match from.and_then(|from| self.read_cache_result(&repo_kind, path, from)) {
+                        Some(path) => (path, None),
+                        None => {
+                            let res = match self.resolve_label(&label, from_path)? {
+                                Some(res) => res,
+                                None => return Ok(None),
+                            };
+                            if let Some(from) = from {
+                                self.record_cache_result(
+                                    &repo_kind,
+                                    path,
+                                    from,
+                                    res.resolved_path.clone(),
+                                );
+                            }
+                            (res.resolved_path, res.canonical_repo)
+                        }
+                    };
+
+                (resolved_path, Some(APIContext::Bzl), canonical_repo)
+            }
+            Dialect::Buck2 => {
+                let load_path = match buck2::LoadPath::parse(path) {
+                    Some(load_path) => load_path,
+                    None => bail!("error parsing load path {:?}", path),
+                };
+
+                // Only .bzl files can be loaded.
+                if !load_path.file.ends_with(".bzl") {
+                    bail!("cannot load a non-bzl file");
+                }
+
+                (
+                    resolve_buck2_load_path(&load_path, from_path)?,
+                    Some(APIContext::Bzl),
+                    None,
+                )
+            }
+        }))
+    }
+
     fn maybe_intern_file(
         &self,
         path: PathBuf,
@@ -323,8 +527,8 @@ impl DefaultFileLoader {
         let (file_id, contents) = match self.interner.lookup_by_path_buf(&path) {
             Some(file_id) => (file_id, None),
             None => {
-                let contents = match fs::read_to_string(&path) {
-                    Result::Ok(contents) => contents,
+                let contents = match read_source(&path) {
+                    Result::Ok(decoded) => decoded.text,
                     Err(err) => {
                         if let Some(canonical_repo) = fetch_repo_on_err {
                             if !self
@@ -371,10 +575,11 @@ impl FileLoader for DefaultFileLoader {
             Err(err) => return Err(anyhow!("error parsing label: {}", err.err)),
         };
 
-        let resolved_label = match self.resolve_label(&label, from)? {
-            Some(resolved_label) => resolved_label,
-            None => return Ok(None),
-        };
+        let resolved_label =
+            match self.resolve_label(&label, &self.interner.lookup_by_file_id(from))? {
+                Some(resolved_label) => resolved_label,
+                None => return Ok(None),
+            };
 
         let res = if fs::metadata(&resolved_label.resolved_path)
             .ok()
@@ -396,9 +601,11 @@ impl FileLoader for DefaultFileLoader {
             let build_file = match fs::read_dir(parent)
                 .into_iter()
                 .flat_map(|entries| entries.into_iter())
-                .find_map(|entry| match entry.ok()?.file_name().to_str()? {
-                    file_name @ ("BUILD" | "BUILD.bazel") => Some(file_name.to_string()),
-                    _ => None,
+                .find_map(|entry| {
+                    let file_name = entry.ok()?.file_name().to_str()?.to_string();
+                    build_file_names(dialect)
+                        .contains(&file_name.as_str())
+                        .then_some(file_name)
                 }) {
                 Some(build_file) => build_file,
                 None => return Ok(None),
@@ -425,46 +632,12 @@ impl FileLoader for DefaultFileLoader {
         dialect: Dialect,
         from: FileId,
     ) -> anyhow::Result<Option<(FileId, Dialect, Option<APIContext>, Option<String>)>> {
-        let (path, api_context, canonical_repo) = match dialect {
-            Dialect::Standard => {
-                // Find the importing file's directory.
-                let mut from_path = self.interner.lookup_by_file_id(from);
-                assert!(from_path.pop());
-
-                // Resolve the given path relative to the importing file's directory.
-                (from_path.join(path).canonicalize()?, None, None)
-            }
-            Dialect::Bazel => {
-                // Parse the load path as a Bazel label.
-                let label = match Label::parse(path) {
-                    Result::Ok(label) => label,
-                    Err(err) => return Err(anyhow!("error parsing label: {}", err.err)),
-                };
-
-                // Only .bzl files can be loaded.
-                if !label.target().ends_with(".bzl") {
-                    bail!("cannot load a non-bzl file");
-                }
-
-                let repo_kind = label.kind();
-                let (resolved_path, canonical_repo) = match self
-                    .read_cache_result(&repo_kind, path, from)
-                {
-                    Some(path) => (path, None),
-                    None => {
-                        let res = match self.resolve_label(&label, from)? {
-                            Some(res) => res,
-                            None => return Ok(None),
-                        };
-                        self.record_cache_result(&repo_kind, path, from, res.resolved_path.clone());
-                        (res.resolved_path, res.canonical_repo)
-                    }
-                };
-
-                (resolved_path, Some(APIContext::Bzl), canonical_repo)
-            }
-        };
-
+        let from_path = self.interner.lookup_by_file_id(from);
+        let (path, api_context, canonical_repo) =
+            match self.resolve_load(path, dialect, &from_path, Some(from))? {
+                Some(res) => res,
+                None => return Ok(None),
+            };
         let (file_id, contents) = self.maybe_intern_file(path, from, canonical_repo)?;
         Ok(Some((file_id, dialect, api_context, contents)))
     }
@@ -566,7 +739,126 @@ impl FileLoader for DefaultFileLoader {
                     }
                 }
             }
+            Dialect::Buck2 => {
+                let (cell_root, from_package) =
+                    match buck2::resolve_cell(self.interner.lookup_by_file_id(from))? {
+                        Some(res) => res,
+                        None => return Ok(None),
+                    };
+
+                // Only the files of the current package and of the packages in the current cell
+                // are offered, once their package has been typed.
+                match path.split_once(':') {
+                    Some(("", _)) => read_dir_targets(from_package).map(Some),
+                    Some((package, _)) => match package.strip_prefix("//") {
+                        Some(package) => read_dir_targets(cell_root.join(package)).map(Some),
+                        None => Ok(None),
+                    },
+                    None => Ok(None),
+                }
+            }
+        }
+    }
+
+    fn list_label_candidates(
+        &self,
+        path: &str,
+        dialect: Dialect,
+        from: FileId,
+    ) -> anyhow::Result<Option<(Vec<LoadItemCandidate>, Option<(FileId, Option<String>)>)>> {
+        if dialect != Dialect::Bazel {
+            return Ok(None);
+        }
+
+        // Labels without a package, e.g. `foo.cc` in `srcs`, refer to the current package.
+        let (package, target) = match path.split_once(':') {
+            Some((package, target)) => (package, Some(target)),
+            None if path.starts_with("//") || path.starts_with('@') => (path, None),
+            None => ("", Some(path)),
+        };
+
+        let target = match target {
+            Some(target) => target,
+            None => {
+                // The package is being typed, so offer the subdirectories of the part that's
+                // been typed so far, e.g. `//foo/` or `@repo//`.
+                let parent = match package.rfind('/') {
+                    Some(end) => &package[..end + 1],
+                    None => return Ok(None),
+                };
+                return match self.resolve_package_dir(parent, from)? {
+                    Some(res) => Ok(Some((read_dir_packages(res.resolved_path)?, None))),
+                    None => Ok(None),
+                };
+            }
+        };
+
+        let res = match self.resolve_package_dir(package, from)? {
+            Some(res) => res,
+            None => return Ok(None),
+        };
+        let dir = match target.rfind('/') {
+            Some(end) => res.resolved_path.join(&target[..end]),
+            None => res.resolved_path.clone(),
+        };
+        let candidates = read_dir_files(&dir, build_file_names(dialect))?;
+
+        // Only targets directly in the package can be offered, before a subdirectory is typed.
+        if target.contains('/') {
+            return Ok(Some((candidates, None)));
         }
+        let build_file = match build_file_names(dialect)
+            .iter()
+            .map(|name| dir.join(name))
+            .find(|path| path.is_file())
+        {
+            Some(path) => Some(self.maybe_intern_file(path, from, res.canonical_repo)?),
+            None => None,
+        };
+        Ok(Some((candidates, build_file)))
+    }
+
+    fn package_name(&self, file_id: FileId) -> Option<String> {
+        // Files in external repositories aren't in the workspace, and aren't edited anyway.
+        let dirname = self.dirname(file_id);
+        let package = dirname.strip_prefix(&self.workspace).ok()?;
+        let components = package
+            .components()
+            .map(|component| component.as_os_str().to_str())
+            .collect::<Option<Vec<_>>>()?;
+        Some(components.join("/"))
+    }
+
+    fn read_file_prefix(&self, path: &Path, max_len: usize) -> Option<(Vec<u8>, u64)> {
+        let file = fs::File::open(path).ok()?;
+        let size = file.metadata().ok()?.len();
+        let mut prefix = Vec::new();
+        file.take(max_len as u64).read_to_end(&mut prefix).ok()?;
+        Some((prefix, size))
+    }
+
+    fn workspace_loaders(&self, file_id: FileId) -> Option<Vec<FileId>> {
+        let path = self.interner.lookup_by_file_id(file_id);
+        let loaders = self
+            .load_index
+            .as_ref()?
+            .loaders(&path, &|from, path| self.resolve_load_from_path(from, path))?;
+        Some(
+            loaders
+                .into_iter()
+                .map(|loader| self.interner.intern_path(loader))
+                .collect(),
+        )
+    }
+
+    fn read_workspace_file(
+        &self,
+        file_id: FileId,
+    ) -> Option<(Dialect, Option<APIContext>, String)> {
+        let path = self.interner.lookup_by_file_id(file_id);
+        let (dialect, api_context) = dialect_and_api_context_for_path(&path)?;
+        let contents = read_source(&path).ok()?.text;
+        Some((dialect, api_context, contents))
     }
 }
 
@@ -594,6 +886,38 @@ fn read_dir_packages(path: impl AsRef<Path>) -> anyhow::Result<Vec<LoadItemCandi
         .collect())
 }
 
+/// Lists the files in a package, along with the subdirectories that aren't packages of their own,
+/// which files can be nested in.
+fn read_dir_files(
+    path: impl AsRef<Path>,
+    build_file_names: &[&str],
+) -> anyhow::Result<Vec<LoadItemCandidate>> {
+    Ok(fs::read_dir(path)?
+        .flat_map(|entry| entry)
+        .filter_map(|entry| {
+            let file_type = entry.file_type().ok()?;
+            let file_name = entry.file_name().to_str()?.to_string();
+            let kind = if file_type.is_dir() {
+                let is_package = build_file_names
+                    .iter()
+                    .any(|name| entry.path().join(name).is_file());
+                if is_package {
+                    return None;
+                }
+                LoadItemCandidateKind::Directory
+            } else if build_file_names.contains(&file_name.as_str()) {
+                return None;
+            } else {
+                LoadItemCandidateKind::File
+            };
+            Some(LoadItemCandidate {
+                kind,
+                path: file_name,
+            })
+        })
+        .collect())
+}
+
 fn read_dir_targets(path: impl AsRef<Path>) -> anyhow::Result<Vec<LoadItemCandidate>> {
     Ok(fs::read_dir(path)?
         .flat_map(|entry| entry)
@@ -620,6 +944,62 @@ fn read_dir_targets(path: impl AsRef<Path>) -> anyhow::Result<Vec<LoadItemCandid
         .collect())
 }
 
+/// Returns whether a file is owned by Bazel rather than by the workspace, i.e. whether it's in the
+/// output base, like the files of fetched external repositories and the generated files under
+/// `bazel-out`, or is reached through one of the `bazel-*` convenience symlinks in the
+/// workspace. Such files are still analyzed, but never edited, since Bazel overwrites them.
+pub(crate) fn is_read_only_path(path: &Path, workspace: &Path, output_base: &Path) -> bool {
+    if !output_base.as_os_str().is_empty() && path.starts_with(output_base) {
+        return true;
+    }
+    let in_convenience_symlink = path
+        .strip_prefix(workspace)
+        .ok()
+        .and_then(|path| path.components().next())
+        .and_then(|component| component.as_os_str().to_str())
+        .map_or(false, |name| name.starts_with("bazel-"));
+    in_convenience_symlink
+        || path
+            .components()
+            .any(|component| component.as_os_str() == "bazel-out")
+}
+
+/// Returns the names of the files that declare packages in the given dialect, in the order that
+/// they're looked for.
+pub(crate) fn build_file_names(dialect: Dialect) -> &'static [&'static str] {
+    match dialect {
+        Dialect::Buck2 => buck2::BUCK_FILE_NAMES,
+        Dialect::Standard | Dialect::Bazel => BAZEL_BUILD_FILE_NAMES,
+    }
+}
+
+/// Resolves a Buck2 load path to the file that it refers to. Cells other than the loading
+/// file's own, e.g. `prelude` in `@prelude//:rules.bzl`, are found through the `.buckconfig`
+/// files above the loading file.
+fn resolve_buck2_load_path(
+    load_path: &buck2::LoadPath,
+    from_path: &Path,
+) -> anyhow::Result<PathBuf> {
+    let (cell_root, from_package) = match buck2::resolve_cell(from_path)? {
+        Some(res) => res,
+        None => bail!("not in a Buck2 cell"),
+    };
+    let package = match (load_path.cell, load_path.package) {
+        (_, None) => from_package,
+        (None, Some(package)) => cell_root.join(package),
+        (Some(cell), Some(package)) => match buck2::resolve_named_cell(from_path, cell)? {
+            Some(cell_root) => cell_root.join(package),
+            None => bail!(
+                "Could not resolve cell \"{}\" from the cells declared in .buckconfig files",
+                cell
+            ),
+        },
+    };
+
+    // Cells are often declared relative to their `.buckconfig` file, e.g. as `root = .`.
+    Ok(package.join(load_path.file).canonicalize()?)
+}
+
 pub(crate) fn dialect_and_api_context_for_path(
     path: impl AsRef<Path>,
 ) -> Option<(Dialect, Option<APIContext>)> {
@@ -627,6 +1007,7 @@ pub(crate) fn dialect_and_api_context_for_path(
     let basename = path.file_name().and_then(|name| name.to_str())?;
     Some(match basename {
         "BUILD" | "BUILD.bazel" => (Dialect::Bazel, Some(APIContext::Build)),
+        "BUCK" | "BUCK.v2" => (Dialect::Buck2, Some(APIContext::Build)),
         "REPO.bazel" => (Dialect::Bazel, Some(APIContext::Repo)),
         "MODULE.bazel" => (Dialect::Bazel, Some(APIContext::Module)),
         "WORKSPACE" | "WORKSPACE.bazel" | "WORKSPACE.bzlmod" => {
@@ -634,8 +1015,111 @@ pub(crate) fn dialect_and_api_context_for_path(
         }
         _ => match path.extension().and_then(|ext| ext.to_str()) {
             Some("sky" | "star") => (Dialect::Standard, None),
+            Some("bxl") => (Dialect::Buck2, Some(APIContext::Bzl)),
+            // `.bzl` files are shared by Bazel and Buck2, so they're told apart by their project.
+            Some("bzl") if buck2::is_in_project(path) => (Dialect::Buck2, Some(APIContext::Bzl)),
             Some("bzl") => (Dialect::Bazel, Some(APIContext::Bzl)),
             _ => return None,
         },
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs,
+        path::{Path, PathBuf},
+    };
+
+    use starpls_bazel::{buck2, APIContext};
+    use starpls_common::Dialect;
+
+    use super::{build_file_names, dialect_and_api_context_for_path, resolve_buck2_load_path};
+
+    /// Creates a Buck2 project with a `prelude` cell for the given test, and returns its root.
+    fn buck2_project(test: &str) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("starpls-{}-{}", test, std::process::id()));
+        fs::create_dir_all(root.join("foo")).unwrap();
+        fs::create_dir_all(root.join("prelude")).unwrap();
+        fs::write(
+            root.join(".buckconfig"),
+            "[cells]\nroot = .\nprelude = prelude\n",
+        )
+        .unwrap();
+        fs::write(root.join("foo/BUCK"), "").unwrap();
+        fs::write(root.join("foo/defs.bzl"), "").unwrap();
+        fs::write(root.join("prelude/prelude.bzl"), "").unwrap();
+        root.canonicalize().unwrap()
+    }
+
+    #[test]
+    fn test_dialect_and_api_context_for_path() {
+        let check = |path: &Path, expected: Option<(Dialect, Option<APIContext>)>| {
+            assert_eq!(
+                dialect_and_api_context_for_path(path),
+                expected,
+                "{:?}",
+                path
+            );
+        };
+        let root = buck2_project("dialects");
+        check(
+            &root.join("foo/BUCK"),
+            Some((Dialect::Buck2, Some(APIContext::Build))),
+        );
+        check(
+            &root.join("foo/BUCK.v2"),
+            Some((Dialect::Buck2, Some(APIContext::Build))),
+        );
+        check(
+            &root.join("foo/defs.bzl"),
+            Some((Dialect::Buck2, Some(APIContext::Bzl))),
+        );
+        check(
+            Path::new("/ws/tools/query.bxl"),
+            Some((Dialect::Buck2, Some(APIContext::Bzl))),
+        );
+        check(
+            Path::new("/ws/BUILD.bazel"),
+            Some((Dialect::Bazel, Some(APIContext::Build))),
+        );
+        check(
+            Path::new("/ws/defs.bzl"),
+            Some((Dialect::Bazel, Some(APIContext::Bzl))),
+        );
+        check(
+            Path::new("/ws/MODULE.bazel"),
+            Some((Dialect::Bazel, Some(APIContext::Module))),
+        );
+        check(Path::new("/ws/lib.star"), Some((Dialect::Standard, None)));
+        check(Path::new("/ws/README.md"), None);
+    }
+
+    #[test]
+    fn test_build_file_names() {
+        assert_eq!(build_file_names(Dialect::Bazel), ["BUILD.bazel", "BUILD"]);
+        assert_eq!(build_file_names(Dialect::Buck2), buck2::BUCK_FILE_NAMES);
+    }
+
+    #[test]
+    fn test_resolve_buck2_load_path() {
+        let root = buck2_project("buck2-loads");
+        let from = root.join("foo/BUCK");
+        let resolve = |path: &str| {
+            resolve_buck2_load_path(&buck2::LoadPath::parse(path).unwrap(), &from).ok()
+        };
+
+        // Other cells are found through the `.buckconfig` file at the root of the project.
+        assert_eq!(
+            resolve("@prelude//:prelude.bzl"),
+            Some(root.join("prelude/prelude.bzl"))
+        );
+        assert_eq!(
+            resolve("prelude//prelude.bzl"),
+            Some(root.join("prelude/prelude.bzl"))
+        );
+        assert_eq!(resolve(":defs.bzl"), Some(root.join("foo/defs.bzl")));
+        assert_eq!(resolve("//foo:defs.bzl"), Some(root.join("foo/defs.bzl")));
+        assert_eq!(resolve("@missing//:defs.bzl"), None);
+    }
+}
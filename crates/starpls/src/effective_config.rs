@@ -0,0 +1,202 @@
+//! Reports the settings in effect for a document, along with where each one comes from and the
+//! values that it overrides. This is meant for debugging configuration, e.g. to find out why a
+//! diagnostic category that's turned off in a `starpls.toml` file is still reported in some
+//! subdirectory.
+
+use std::path::Path;
+
+use clap::ValueEnum;
+use serde_json::Value;
+use starpls_ide::DiagnosticCategory;
+
+use crate::{
+    extensions::{ConfigSource, EffectiveSetting, OverriddenSetting},
+    features::{Feature, InlayHintsMode},
+    server::Server,
+};
+
+/// Merges the settings from every source, in increasing order of precedence.
+pub(crate) fn effective_config(server: &Server, path: Option<&Path>) -> Vec<EffectiveSetting> {
+    let mut layers = vec![
+        (ConfigSource::Default, defaults()),
+        (ConfigSource::CommandLine, command_line(server)),
+        (ConfigSource::ClientSettings, client_settings(server)),
+    ];
+    if let Some(path) = path {
+        for file in server.project_configs.files_for_path(path) {
+            let uri = match lsp_types::Url::from_file_path(&file.path) {
+                Ok(uri) => uri,
+                Err(_) => continue,
+            };
+            layers.push((
+                ConfigSource::ProjectConfig { uri },
+                file.values
+                    .into_iter()
+                    .map(|(key, value)| (key, Value::String(value)))
+                    .collect(),
+            ));
+        }
+    }
+
+    let mut settings: Vec<EffectiveSetting> = Vec::new();
+    for (source, values) in layers {
+        for (key, value) in values {
+            match settings.iter_mut().find(|setting| setting.key == key) {
+                Some(setting) => {
+                    let prev_value = std::mem::replace(&mut setting.value, value);
+                    let prev_source = std::mem::replace(&mut setting.source, source.clone());
+                    setting.overridden.insert(
+                        0,
+                        OverriddenSetting {
+                            value: prev_value,
+                            source: prev_source,
+                        },
+                    );
+                }
+                None => settings.push(EffectiveSetting {
+                    key,
+                    value,
+                    source: source.clone(),
+                    overridden: Vec::new(),
+                }),
+            }
+        }
+    }
+    settings.sort_by(|a, b| a.key.cmp(&b.key));
+    settings
+}
+
+fn defaults() -> Vec<(String, Value)> {
+    let categories = DiagnosticCategory::ALL.iter().map(|category| {
+        (
+            format!("diagnostics.{}", category.name()),
+            Value::String("default".to_string()),
+        )
+    });
+    let features = Feature::ALL
+        .iter()
+        .map(|feature| (format!("features.{}", feature.key()), Value::Bool(true)));
+    let inlay_hints = (
+        "inlayHints".to_string(),
+        Value::String(InlayHintsMode::default().key().to_string()),
+    );
+    categories
+        .chain(features)
+        .chain(std::iter::once(inlay_hints))
+        .collect()
+}
+
+fn client_settings(server: &Server) -> Vec<(String, Value)> {
+    let mut settings = Feature::ALL
+        .iter()
+        .filter(|feature| !server.feature_settings.is_enabled(**feature))
+        .map(|feature| (format!("features.{}", feature.key()), Value::Bool(false)))
+        .collect::<Vec<_>>();
+    if server.feature_settings.inlay_hints != InlayHintsMode::default() {
+        settings.push((
+            "inlayHints".to_string(),
+            Value::String(server.feature_settings.inlay_hints.key().to_string()),
+        ));
+    }
+    settings
+}
+
+fn command_line(server: &Server) -> Vec<(String, Value)> {
+    let args = &server.config.args;
+    let flags = [
+        (
+            "buildifier",
+            args.buildifier.clone().map_or(Value::Null, Value::String),
+        ),
+        (
+            "buildifier_add_tables",
+            args.buildifier_add_tables
+                .clone()
+                .map_or(Value::Null, Value::String),
+        ),
+        (
+            "buildifier_args",
+            Value::Array(
+                args.buildifier_args
+                    .iter()
+                    .cloned()
+                    .map(Value::String)
+                    .collect(),
+            ),
+        ),
+        (
+            "buildifier_tables",
+            args.buildifier_tables
+                .clone()
+                .map_or(Value::Null, Value::String),
+        ),
+        (
+            "check_sidecar",
+            args.check_sidecar
+                .clone()
+                .map_or(Value::Null, Value::String),
+        ),
+        (
+            "check_sidecar_args",
+            Value::Array(
+                args.check_sidecar_args
+                    .iter()
+                    .cloned()
+                    .map(Value::String)
+                    .collect(),
+            ),
+        ),
+        (
+            "experimental_enable_spellcheck",
+            Value::Bool(args.experimental_enable_spellcheck),
+        ),
+        (
+            "experimental_enable_todos",
+            Value::Bool(args.experimental_enable_todos),
+        ),
+        (
+            "experimental_enable_workspace_diagnostics",
+            Value::Bool(args.experimental_enable_workspace_diagnostics),
+        ),
+        (
+            "experimental_infer_ctx_attributes",
+            Value::Bool(args.experimental_infer_ctx_attributes),
+        ),
+        (
+            "fetch_repo_mode",
+            args.fetch_repo_mode
+                .to_possible_value()
+                .map_or(Value::Null, |value| value.get_name().into()),
+        ),
+        (
+            "label_style",
+            args.label_style
+                .and_then(|style| style.to_possible_value())
+                .map_or(Value::Null, |value| value.get_name().into()),
+        ),
+        (
+            "required_header_file",
+            args.required_header_file
+                .clone()
+                .map_or(Value::Null, Value::String),
+        ),
+        (
+            "struct_merge_functions",
+            Value::Array(
+                args.struct_merge_functions
+                    .iter()
+                    .cloned()
+                    .map(Value::String)
+                    .collect(),
+            ),
+        ),
+        (
+            "todo_tags",
+            Value::Array(args.todo_tags.iter().cloned().map(Value::String).collect()),
+        ),
+    ];
+    flags
+        .into_iter()
+        .map(|(flag, value)| (format!("args.{}", flag), value))
+        .collect()
+}
@@ -1,8 +1,11 @@
+use std::path::PathBuf;
+
 use crossbeam_channel::select;
 use lsp_server::Connection;
 use lsp_types::{InitializeParams, WorkDoneProgressCreateParams};
 use rustc_hash::FxHashSet;
 use starpls_common::FileId;
+use starpls_ide::Cancellable;
 
 use crate::{
     config::ServerConfig,
@@ -10,8 +13,11 @@ use crate::{
     dispatcher::RequestDispatcher,
     document::DocumentSource,
     extensions,
+    features::Feature,
     handlers::{notifications, requests},
-    server::{Server, ServerSnapshot},
+    server::{format_repos, Server, ServerSnapshot},
+    sidecar::CheckedFile,
+    workspace_diagnostics::{self, Job, WorkspaceDiagnosticsProgress},
     ServerArgs,
 };
 
@@ -31,7 +37,11 @@ macro_rules! match_notification {
 #[derive(Debug)]
 pub(crate) enum FetchExternalReposProgress {
     Begin(FxHashSet<String>),
-    End(FxHashSet<FileId>),
+    End {
+        files: FxHashSet<FileId>,
+        /// The repositories that couldn't be fetched.
+        failed: FxHashSet<String>,
+    },
 }
 
 #[derive(Debug)]
@@ -47,12 +57,27 @@ pub(crate) enum Task {
     DiagnosticsReady(Vec<(FileId, Vec<lsp_types::Diagnostic>)>),
     /// A request has been evaluated and its response is ready.
     ResponseReady(lsp_server::Response),
+    /// A request handler panicked. The response is an error, and a crash report was written to
+    /// the given path if possible.
+    RequestPanicked {
+        response: lsp_server::Response,
+        method: String,
+        report_path: Option<PathBuf>,
+    },
     /// Retry a previously failed request (e.g. due to Salsa cancellation).
     Retry(lsp_server::Request),
     /// Events from fetching external repositories.
     FetchExternalRepos(FetchExternalReposProgress),
     /// A request to fetch an external repository.
     FetchExternalRepoRequest(FetchExternalRepoRequest),
+    /// Edits to the given files have settled, so the closed files affected by them can be checked.
+    WorkspaceDiagnosticsRequested(Vec<FileId>),
+    /// Events from checking closed files affected by edits.
+    WorkspaceDiagnostics(WorkspaceDiagnosticsProgress),
+    /// Edits to the given files have settled, so they can be linted with Buildifier.
+    BuildifierLintRequested(Vec<FileId>),
+    /// Buildifier's lint warnings for a set of files are ready for forwarding.
+    BuildifierDiagnosticsReady(Vec<(FileId, Vec<lsp_types::Diagnostic>)>),
 }
 
 #[derive(Debug)]
@@ -67,11 +92,19 @@ pub fn process_connection(
     initialize_params: InitializeParams,
 ) -> anyhow::Result<()> {
     eprintln!("server: initializing state and starting event loop");
+    let initialization_options = initialize_params.initialization_options;
     let config = ServerConfig {
         args,
         caps: initialize_params.capabilities,
     };
-    let server = Server::new(connection, config)?;
+    let mut server = Server::new(connection, config)?;
+
+    // Apply the feature settings from the initialization options, then also pull them from the
+    // client's settings if possible.
+    server.update_feature_settings(initialization_options.as_ref());
+    if server.config.has_configuration_support() {
+        server.request_configuration();
+    }
     server.run()
 }
 
@@ -106,6 +139,8 @@ impl Server {
                 self.handle_notification(not)?;
             }
             Event::Message(lsp_server::Message::Response(resp)) => {
+                self.crash_reporter
+                    .record(format!("response (id {})", resp.id));
                 self.complete_request(resp);
             }
             Event::Task(task) => {
@@ -117,7 +152,12 @@ impl Server {
             }
         };
 
-        if !self.pending_repos.is_empty() && !self.is_fetching_repos {
+        if !self.pending_repos.is_empty()
+            && !self.is_fetching_repos
+            && self
+                .feature_settings
+                .is_enabled(Feature::ExternalRepositories)
+        {
             self.fetch_bazel_external_repos();
         }
 
@@ -128,6 +168,20 @@ impl Server {
         let mut files_to_update = Vec::new();
         if should_request_analysis {
             self.analysis_requested_for_files = None;
+            if let Some(debouncer) = &self.workspace_diagnostics_debouncer {
+                if !changed_file_ids.is_empty()
+                    && self
+                        .feature_settings
+                        .is_enabled(Feature::WorkspaceDiagnostics)
+                {
+                    debouncer.sender.send(changed_file_ids.clone()).unwrap();
+                }
+            }
+            if let Some(debouncer) = &self.buildifier_debouncer {
+                if !changed_file_ids.is_empty() {
+                    debouncer.sender.send(changed_file_ids.clone()).unwrap();
+                }
+            }
             self.analysis_debouncer
                 .sender
                 .send(changed_file_ids)
@@ -139,6 +193,9 @@ impl Server {
         if !files_to_update.is_empty() {
             self.update_diagnostics(files_to_update);
         }
+        if let Some(job) = self.workspace_diagnostics.next_job() {
+            self.run_workspace_diagnostics_job(job);
+        }
 
         let changed_file_ids = self.diagnostics_manager.take_changes();
 
@@ -152,6 +209,7 @@ impl Server {
                 Some(DocumentSource::Editor(version)) => version,
                 _ => continue,
             };
+            self.workspace_diagnostics.remove(file_id);
             let diagnostics = self
                 .diagnostics_manager
                 .get_diagnostics(file_id)
@@ -176,14 +234,15 @@ impl Server {
 
     fn update_diagnostics(&mut self, file_ids: Vec<FileId>) {
         let snapshot = self.snapshot();
-        self.task_pool_handle.spawn(move || {
+        self.task_pool_handle.spawn_background(move || {
             let mut res = Vec::new();
 
             // Query the database for diagnostics for each file and convert them to an LSP-compatible format.
             for file_id in file_ids {
+                // If the operation was cancelled, simply continue to the next file.
                 let diagnostics = match collect_diagnostics(&snapshot, file_id) {
-                    Some(diagnositcs) => diagnositcs,
-                    None => continue,
+                    Ok(Some(diagnostics)) => diagnostics,
+                    _ => continue,
                 };
                 res.push((file_id, diagnostics));
             }
@@ -192,26 +251,145 @@ impl Server {
         });
     }
 
+    fn lint_with_buildifier(&mut self, file_ids: Vec<FileId>) {
+        let buildifier = match self.buildifier.clone() {
+            Some(buildifier) => buildifier,
+            None => return,
+        };
+        let snapshot = self.snapshot();
+        self.task_pool_handle.spawn_background(move || {
+            Task::BuildifierDiagnosticsReady(buildifier.lint_files(&snapshot, file_ids))
+        });
+    }
+
+    fn run_workspace_diagnostics_job(&mut self, job: Job) {
+        let snapshot = self.snapshot();
+        match job {
+            Job::FindAffected(file_ids) => {
+                let open_file_ids = self.open_file_ids();
+                self.task_pool_handle.spawn_background(move || {
+                    workspace_diagnostics::find_affected(
+                        &snapshot.analysis_snapshot,
+                        file_ids,
+                        &open_file_ids,
+                    )
+                });
+            }
+            Job::Check(file_ids) => self
+                .task_pool_handle
+                .spawn_background(move || workspace_diagnostics::check(&snapshot, file_ids)),
+        }
+    }
+
+    fn publish_workspace_diagnostics(
+        &mut self,
+        diagnostics: Vec<(FileId, Vec<lsp_types::Diagnostic>)>,
+    ) {
+        let document_manager = self.document_manager.read();
+        let mut notifications = Vec::new();
+        for (file_id, diagnostics) in diagnostics {
+            // Files that were opened while they were being checked publish their own diagnostics.
+            if matches!(
+                document_manager
+                    .get(file_id)
+                    .map(|document| document.source),
+                Some(DocumentSource::Editor(_))
+            ) || !self
+                .workspace_diagnostics
+                .set_diagnostics(file_id, &diagnostics)
+            {
+                continue;
+            }
+            let path = document_manager.lookup_by_file_id(file_id);
+            notifications.push(lsp_types::PublishDiagnosticsParams {
+                uri: lsp_types::Url::from_file_path(path).unwrap(),
+                diagnostics,
+                version: None,
+            });
+        }
+        drop(document_manager);
+
+        for params in notifications {
+            self.send_notification::<lsp_types::notification::PublishDiagnostics>(params);
+        }
+    }
+
+    fn open_file_ids(&self) -> FxHashSet<FileId> {
+        let document_manager = self.document_manager.read();
+        document_manager
+            .file_ids()
+            .into_iter()
+            .filter(|&file_id| {
+                matches!(
+                    document_manager
+                        .get(file_id)
+                        .map(|document| document.source),
+                    Some(DocumentSource::Editor(_))
+                )
+            })
+            .collect()
+    }
+
     fn register_and_handle_request(&mut self, req: lsp_server::Request) {
+        self.crash_reporter
+            .record(format!("request {} (id {})", req.method, req.id));
         self.req_queue.incoming.register(req.id.clone(), ());
         self.handle_request(req);
     }
 
     fn handle_request(&mut self, req: lsp_server::Request) {
+        // Clients that can't unregister a turned off feature still send requests for it.
+        if let Some(feature) = Feature::for_method(&req.method) {
+            if !self.feature_settings.is_enabled(feature) {
+                self.respond(lsp_server::Response::new_ok(
+                    req.id,
+                    serde_json::Value::Null,
+                ));
+                return;
+            }
+        }
+
         RequestDispatcher::new(req, self)
             .on::<extensions::ShowSyntaxTree>(requests::show_syntax_tree)
             .on::<extensions::ShowHir>(requests::show_hir)
+            .on::<extensions::LanguageConfiguration>(requests::language_configuration)
+            .on::<extensions::LoadGraph>(requests::load_graph)
+            .on::<extensions::Runnables>(requests::runnables)
+            .on::<extensions::Targets>(requests::targets)
+            .on::<extensions::Todos>(requests::todos)
+            .on_sync_mut::<extensions::EffectiveConfig>(requests::effective_config)
+            .on_sync_mut::<lsp_types::request::ExecuteCommand>(requests::execute_command)
+            .on::<lsp_types::request::CallHierarchyIncomingCalls>(requests::incoming_calls)
+            .on::<lsp_types::request::CallHierarchyOutgoingCalls>(requests::outgoing_calls)
+            .on::<lsp_types::request::CallHierarchyPrepare>(requests::prepare_call_hierarchy)
+            .on::<lsp_types::request::CodeActionRequest>(requests::code_action)
             .on::<lsp_types::request::Completion>(requests::completion)
             .on::<lsp_types::request::DocumentSymbolRequest>(requests::document_symbols)
+            .on::<lsp_types::request::FoldingRangeRequest>(requests::folding_range)
+            .on::<lsp_types::request::Formatting>(requests::formatting)
             .on::<lsp_types::request::GotoDefinition>(requests::goto_definition)
             .on::<lsp_types::request::HoverRequest>(requests::hover)
+            .on::<lsp_types::request::InlayHintRequest>(requests::inlay_hint)
+            .on::<lsp_types::request::InlineValueRequest>(requests::inline_value)
+            .on::<lsp_types::request::OnTypeFormatting>(requests::on_type_formatting)
+            .on::<lsp_types::request::RangeFormatting>(requests::range_formatting)
+            .on::<lsp_types::request::References>(requests::references)
+            .on::<lsp_types::request::Rename>(requests::rename)
+            .on::<lsp_types::request::SelectionRangeRequest>(requests::selection_range)
+            .on::<lsp_types::request::SemanticTokensFullRequest>(requests::semantic_tokens_full)
             .on::<lsp_types::request::SignatureHelpRequest>(requests::signature_help)
+            .on::<lsp_types::request::WillRenameFiles>(requests::will_rename_files)
+            .on::<lsp_types::request::WorkspaceSymbolRequest>(requests::workspace_symbol)
             .finish();
     }
 
     fn handle_notification(&mut self, not: lsp_server::Notification) -> anyhow::Result<()> {
+        self.crash_reporter
+            .record(format!("notification {}", not.method));
         match_notification! {
             match not {
+                if lsp_types::notification::Cancel as params => notifications::cancel_request(self, params),
+                if lsp_types::notification::DidChangeConfiguration as params => notifications::did_change_configuration(self, params),
                 if lsp_types::notification::DidOpenTextDocument as params => notifications::did_open_text_document(self, params),
                 if lsp_types::notification::DidCloseTextDocument as params => notifications::did_close_text_document(self, params),
                 if lsp_types::notification::DidChangeTextDocument as params => notifications::did_change_text_document(self, params),
@@ -233,7 +411,31 @@ impl Server {
             Task::ResponseReady(resp) => {
                 self.respond(resp);
             }
-            Task::Retry(req) => self.handle_request(req),
+            Task::RequestPanicked {
+                response,
+                method,
+                report_path,
+            } => {
+                self.respond(response);
+                let message = match report_path {
+                    Some(path) => format!(
+                        "The language server encountered an internal error while handling a {} request. A report was written to {}; please attach it when filing an issue.",
+                        method,
+                        path.display()
+                    ),
+                    None => format!(
+                        "The language server encountered an internal error while handling a {} request. Please check the language server logs for more details.",
+                        method
+                    ),
+                };
+                self.send_error_message(&message);
+            }
+            Task::Retry(req) => {
+                // Requests cancelled by the client while they were running aren't retried.
+                if !self.req_queue.incoming.is_completed(&req.id) {
+                    self.handle_request(req);
+                }
+            }
             Task::FetchExternalRepos(progress) => {
                 let token = "FetchExternalRepos".to_string();
                 let work_done = match progress {
@@ -244,30 +446,28 @@ impl Server {
                             },
                         );
 
-                        let mut repos = repos.into_iter().collect::<Vec<_>>();
-                        repos.sort();
-
-                        let mut title = "Fetching external repositories: ".to_string();
-                        for (i, repo) in repos.into_iter().enumerate() {
-                            if i > 0 {
-                                title.push_str(", ");
-                            }
-                            title.push('"');
-                            title.push_str(&repo);
-                            title.push('"');
-                        }
-
                         lsp_types::WorkDoneProgress::Begin(lsp_types::WorkDoneProgressBegin {
-                            title,
+                            title: format!(
+                                "Fetching external repositories: {}",
+                                format_repos(&repos)
+                            ),
                             ..Default::default()
                         })
                     }
-                    FetchExternalReposProgress::End(files) => {
+                    FetchExternalReposProgress::End { files, failed } => {
                         self.is_fetching_repos = false;
                         self.force_analysis_for_files.extend(files);
-                        lsp_types::WorkDoneProgress::End(lsp_types::WorkDoneProgressEnd {
-                            message: None,
-                        })
+                        let message = if failed.is_empty() {
+                            None
+                        } else {
+                            let message = format!(
+                                "Failed to fetch external repositories: {}",
+                                format_repos(&failed)
+                            );
+                            self.send_error_message(&message);
+                            Some(message)
+                        };
+                        lsp_types::WorkDoneProgress::End(lsp_types::WorkDoneProgressEnd { message })
                     }
                 };
 
@@ -284,6 +484,35 @@ impl Server {
                     self.pending_files.insert(file_id);
                 }
             }
+            Task::WorkspaceDiagnosticsRequested(file_ids) => {
+                if self
+                    .feature_settings
+                    .is_enabled(Feature::WorkspaceDiagnostics)
+                {
+                    self.workspace_diagnostics.request(file_ids);
+                }
+            }
+            Task::WorkspaceDiagnostics(progress) => {
+                self.workspace_diagnostics.handle_progress(&progress);
+                // Drop the results of a job that was running when the feature was turned off.
+                if !self
+                    .feature_settings
+                    .is_enabled(Feature::WorkspaceDiagnostics)
+                {
+                    self.workspace_diagnostics.clear();
+                    return;
+                }
+                if let WorkspaceDiagnosticsProgress::Report { diagnostics, .. } = progress {
+                    self.publish_workspace_diagnostics(diagnostics);
+                }
+            }
+            Task::BuildifierLintRequested(file_ids) => self.lint_with_buildifier(file_ids),
+            Task::BuildifierDiagnosticsReady(diagnostics) => {
+                for (file_id, diagnostics) in diagnostics {
+                    self.diagnostics_manager
+                        .set_buildifier_diagnostics(file_id, diagnostics);
+                }
+            }
         }
     }
 
@@ -307,20 +536,55 @@ where
     }
 }
 
-fn collect_diagnostics(
+pub(crate) fn collect_diagnostics(
     snapshot: &ServerSnapshot,
     file_id: FileId,
-) -> Option<Vec<lsp_types::Diagnostic>> {
-    let line_index = snapshot.analysis_snapshot.line_index(file_id).ok()??;
-
-    // Get the diagnostics for the current path. If the operation was cancelled, simply continue to the next file.
-    let diagnostics = snapshot.analysis_snapshot.diagnostics(file_id).ok()?;
+) -> Cancellable<Option<Vec<lsp_types::Diagnostic>>> {
+    let line_index = match snapshot.line_index(file_id)? {
+        Some(line_index) => line_index,
+        None => return Ok(None),
+    };
+    let encoding = snapshot.config.position_encoding();
+    let diagnostics = snapshot.analysis_snapshot.diagnostics(file_id)?;
 
     // Convert the diagnostics. This includes translating text offsets into `(line, column)` format.
-    Some(
-        diagnostics
-            .into_iter()
-            .flat_map(|diagnostic| convert::lsp_diagnostic_from_native(diagnostic, &line_index))
-            .collect::<Vec<_>>(),
-    )
+    let mut diagnostics = diagnostics
+        .into_iter()
+        .flat_map(|diagnostic| {
+            convert::lsp_diagnostic_from_native(diagnostic, line_index, encoding)
+        })
+        .collect::<Vec<_>>();
+
+    // Merge in the diagnostics from the check sidecar, if any. Files owned by Bazel aren't checked,
+    // since they can't be fixed anyway.
+    if let Some(sidecar) = &snapshot.check_sidecar {
+        let document_manager = snapshot.document_manager.read();
+        let path = document_manager.lookup_by_file_id(file_id);
+        let document = document_manager.get(file_id).map(|document| {
+            (
+                document.dialect,
+                document.api_context.clone(),
+                document.contents.clone(),
+            )
+        });
+        drop(document_manager);
+        if let Some((dialect, api_context, contents)) = document {
+            if !snapshot.is_read_only(file_id) {
+                diagnostics.extend(sidecar.diagnostics(
+                    &snapshot.analysis_snapshot,
+                    file_id,
+                    CheckedFile {
+                        path: &path,
+                        dialect,
+                        api_context,
+                        contents: &contents,
+                    },
+                    line_index,
+                    encoding,
+                )?);
+            }
+        }
+    }
+
+    Ok(Some(diagnostics))
 }
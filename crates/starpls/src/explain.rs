@@ -0,0 +1,43 @@
+use anyhow::anyhow;
+use starpls_common::DiagnosticCode;
+
+const DOCS_HEADER: &str = "Every diagnostic reported by `starpls` has a stable code, which editors display alongside the diagnostic and link to this page. Run `starpls --explain <code>` to print the documentation for a code, or `starpls --explain all` to regenerate this page.";
+
+/// Prints the documentation for the given diagnostic code. The special code `all` prints the
+/// documentation for every code as Markdown, in the format of `docs/diagnostics.md`.
+pub(crate) fn run_explain(code: &str) -> anyhow::Result<()> {
+    if code == "all" {
+        println!("# Diagnostics\n\n{}", DOCS_HEADER);
+        for code in DiagnosticCode::ALL {
+            println!(
+                "\n## {}\n\n{}\n\n{}\n\n**How to fix:** {}",
+                code.name(),
+                code.summary(),
+                code.description(),
+                code.remediation()
+            );
+        }
+        return Ok(());
+    }
+
+    let code = DiagnosticCode::from_name(code).ok_or_else(|| {
+        anyhow!(
+            "Unknown diagnostic code {:?}. Valid codes are: {}",
+            code,
+            DiagnosticCode::ALL
+                .iter()
+                .map(|code| code.name())
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+    })?;
+    println!(
+        "{}: {}\n\n{}\n\nHow to fix: {}\n\nSee {} for more information.",
+        code.name(),
+        code.summary(),
+        code.description(),
+        code.remediation(),
+        code.docs_url().unwrap_or_default()
+    );
+    Ok(())
+}
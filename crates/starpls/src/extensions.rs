@@ -1,6 +1,15 @@
-use lsp_types::{request::Request, TextDocumentIdentifier};
+use std::path::PathBuf;
+
+use lsp_types::{request::Request, Position, Range, TextDocumentIdentifier, Url};
 use serde::{Deserialize, Serialize};
 
+/// The command run by the "add to dictionary" code action. Its only argument is the word to add.
+pub const ADD_WORD_TO_DICTIONARY_COMMAND: &str = "starpls.addWordToDictionary";
+
+/// The command that updates every `bazel_dep` in a `MODULE.bazel` file to its latest version. Its
+/// optional argument is the URI of the file, which defaults to the workspace's root `MODULE.bazel`.
+pub const UPDATE_ALL_DEPENDENCIES_COMMAND: &str = "starpls.updateAllDependencies";
+
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct ShowSyntaxTreeParams {
@@ -30,3 +39,224 @@ impl Request for ShowHir {
     type Result = String;
     const METHOD: &'static str = "starpls/showHir";
 }
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct LoadGraphParams {
+    pub text_document: TextDocumentIdentifier,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct LoadGraphResult {
+    /// Files transitively loaded by the requested file.
+    pub loadees: Vec<Url>,
+    /// Files that transitively load the requested file.
+    pub loaders: Vec<Url>,
+    /// Direct `load` edges between the files above.
+    pub edges: Vec<LoadGraphEdge>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct LoadGraphEdge {
+    pub loader: Url,
+    pub loadee: Url,
+}
+
+#[derive(Debug)]
+pub enum LoadGraph {}
+
+impl Request for LoadGraph {
+    type Params = LoadGraphParams;
+    type Result = LoadGraphResult;
+    const METHOD: &'static str = "starpls/loadGraph";
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct TodosParams {
+    pub text_document: TextDocumentIdentifier,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Todo {
+    /// The tag that the comment starts with, e.g. `TODO`.
+    pub tag: String,
+    pub owner: Option<String>,
+    pub issue: Option<String>,
+    pub message: String,
+    pub range: Range,
+}
+
+#[derive(Debug)]
+pub enum Todos {}
+
+impl Request for Todos {
+    type Params = TodosParams;
+    type Result = Vec<Todo>;
+    const METHOD: &'static str = "starpls/todos";
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct TargetsParams {
+    pub text_document: TextDocumentIdentifier,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct TargetInfo {
+    pub name: String,
+    /// The rule or macro used to declare the target, e.g. `cc_library`.
+    pub kind: String,
+    /// Attributes with literal values, mapped to strings, lists of strings, booleans, or integers.
+    pub attributes: serde_json::Map<String, serde_json::Value>,
+    /// Whether the target is (heuristically) declared by a macro rather than a rule.
+    pub is_macro: bool,
+    pub range: Range,
+    pub selection_range: Range,
+}
+
+#[derive(Debug)]
+pub enum Targets {}
+
+impl Request for Targets {
+    type Params = TargetsParams;
+    type Result = Vec<TargetInfo>;
+    const METHOD: &'static str = "starpls/targets";
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct RunnablesParams {
+    pub text_document: TextDocumentIdentifier,
+    /// If set, only the runnables for the target at this position and for the whole package are
+    /// returned.
+    pub position: Option<Position>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Runnable {
+    /// A human-readable description of the command, e.g. `bazel test //pkg:foo`.
+    pub label: String,
+    /// The range of the target declaration, if the runnable is for a single target.
+    pub location: Option<Range>,
+    pub args: RunnableArgs,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct RunnableArgs {
+    /// The directory to run Bazel from.
+    pub workspace_root: PathBuf,
+    /// The arguments to pass to Bazel, e.g. `["test", "//pkg:foo"]`.
+    pub bazel_args: Vec<String>,
+}
+
+#[derive(Debug)]
+pub enum Runnables {}
+
+impl Request for Runnables {
+    type Params = RunnablesParams;
+    type Result = Vec<Runnable>;
+    const METHOD: &'static str = "starpls/runnables";
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct LanguageConfigurationResult {
+    pub indentation_rules: IndentationRules,
+    pub on_enter_rules: Vec<OnEnterRule>,
+}
+
+/// Regular expressions matched against single lines, as in VS Code's language configuration.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct IndentationRules {
+    pub increase_indent_pattern: String,
+    pub decrease_indent_pattern: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct OnEnterRule {
+    pub before_text: String,
+    pub action: OnEnterAction,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct OnEnterAction {
+    /// Either `indent` or `outdent`.
+    pub indent: String,
+}
+
+#[derive(Debug)]
+pub enum LanguageConfiguration {}
+
+impl Request for LanguageConfiguration {
+    type Params = ();
+    type Result = LanguageConfigurationResult;
+    const METHOD: &'static str = "starpls/languageConfiguration";
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct EffectiveConfigParams {
+    /// If set, the `starpls.toml` files that apply to this document are included.
+    pub text_document: Option<TextDocumentIdentifier>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct EffectiveConfigResult {
+    /// The settings, sorted by key.
+    pub settings: Vec<EffectiveSetting>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct EffectiveSetting {
+    /// The setting's key, e.g. `diagnostics.type` or `features.completion`.
+    pub key: String,
+    pub value: serde_json::Value,
+    pub source: ConfigSource,
+    /// The values from sources with lower precedence that this one overrides, from highest to
+    /// lowest precedence.
+    pub overridden: Vec<OverriddenSetting>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct OverriddenSetting {
+    pub value: serde_json::Value,
+    pub source: ConfigSource,
+}
+
+/// Where a setting comes from, in increasing order of precedence.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum ConfigSource {
+    Default,
+    /// The server's command-line flags, including their default values.
+    CommandLine,
+    /// The `starpls` section of the client's settings, or the initialization options.
+    ClientSettings,
+    /// A `starpls.toml` file. Files in nested directories take precedence over those in their
+    /// parent directories.
+    ProjectConfig {
+        uri: Url,
+    },
+}
+
+#[derive(Debug)]
+pub enum EffectiveConfig {}
+
+impl Request for EffectiveConfig {
+    type Params = EffectiveConfigParams;
+    type Result = EffectiveConfigResult;
+    const METHOD: &'static str = "starpls/effectiveConfig";
+}
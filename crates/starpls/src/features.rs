@@ -0,0 +1,246 @@
+//! Settings that turn off individual features at runtime, so that users of large repositories can
+//! trade features for latency without restarting the server. Features are enabled by default, and
+//! are configured through the `starpls` section of the client's settings, or through the
+//! initialization options. For example:
+//!
+//! ```json
+//! {
+//!     "features": {
+//!         "workspaceDiagnostics": false,
+//!         "externalRepositories": false,
+//!         "semanticTokens": false
+//!     },
+//!     "inlayHints": "params"
+//! }
+//! ```
+//!
+//! `inlayHints` picks which inferred types are shown as inlay hints: `"off"`, `"params"` for the
+//! types of parameters only, or `"all"` for parameter, return, and variable types. Turning off the
+//! `inlayHints` feature instead removes inlay hints from the client altogether.
+//!
+//! Features backed by an LSP capability are registered dynamically if the client supports it, so
+//! that turning them off removes them from the client entirely. Otherwise, their requests are
+//! answered with empty responses while they're turned off.
+
+use anyhow::{anyhow, bail};
+use lsp_types::{request::Request as _, ClientCapabilities};
+use rustc_hash::FxHashSet;
+use serde_json::Value;
+use starpls_ide::InlayHintsConfig;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub(crate) enum Feature {
+    CodeActions,
+    Completion,
+    DocumentSymbols,
+    InlayHints,
+    SemanticTokens,
+    /// Diagnostics for closed files that load an edited file.
+    WorkspaceDiagnostics,
+    /// Fetching external repositories that aren't in the output base yet.
+    ExternalRepositories,
+}
+
+impl Feature {
+    pub(crate) const ALL: &'static [Feature] = &[
+        Feature::CodeActions,
+        Feature::Completion,
+        Feature::DocumentSymbols,
+        Feature::InlayHints,
+        Feature::SemanticTokens,
+        Feature::WorkspaceDiagnostics,
+        Feature::ExternalRepositories,
+    ];
+
+    pub(crate) fn key(self) -> &'static str {
+        match self {
+            Feature::CodeActions => "codeActions",
+            Feature::Completion => "completion",
+            Feature::DocumentSymbols => "documentSymbols",
+            Feature::InlayHints => "inlayHints",
+            Feature::SemanticTokens => "semanticTokens",
+            Feature::WorkspaceDiagnostics => "workspaceDiagnostics",
+            Feature::ExternalRepositories => "externalRepositories",
+        }
+    }
+
+    fn from_key(key: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|feature| feature.key() == key)
+    }
+
+    /// The method that the feature's capability is registered for, if it has one. This is the
+    /// method of the feature's request, except for semantic tokens, whose requests, e.g.
+    /// `textDocument/semanticTokens/full`, are all registered as `textDocument/semanticTokens`.
+    pub(crate) fn method(self) -> Option<&'static str> {
+        match self {
+            Feature::CodeActions => Some(lsp_types::request::CodeActionRequest::METHOD),
+            Feature::Completion => Some(lsp_types::request::Completion::METHOD),
+            Feature::DocumentSymbols => Some(lsp_types::request::DocumentSymbolRequest::METHOD),
+            Feature::InlayHints => Some(lsp_types::request::InlayHintRequest::METHOD),
+            Feature::SemanticTokens => Some("textDocument/semanticTokens"),
+            Feature::WorkspaceDiagnostics | Feature::ExternalRepositories => None,
+        }
+    }
+
+    /// Returns the feature whose capability is registered for the given request.
+    pub(crate) fn for_method(method: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|feature| {
+            feature.method().map_or(false, |registered| {
+                method == registered
+                    || (*feature == Feature::SemanticTokens
+                        && method
+                            .strip_prefix(registered)
+                            .map_or(false, |rest| rest.starts_with('/')))
+            })
+        })
+    }
+
+    /// Returns whether the feature's capability is registered dynamically instead of being
+    /// advertised when the server is initialized.
+    pub(crate) fn is_dynamically_registered(self, caps: &ClientCapabilities) -> bool {
+        let text_document = match &caps.text_document {
+            Some(text_document) => text_document,
+            None => return false,
+        };
+        let dynamic_registration = match self {
+            Feature::CodeActions => text_document
+                .code_action
+                .as_ref()
+                .and_then(|code_action| code_action.dynamic_registration),
+            Feature::Completion => text_document
+                .completion
+                .as_ref()
+                .and_then(|completion| completion.dynamic_registration),
+            Feature::DocumentSymbols => text_document
+                .document_symbol
+                .as_ref()
+                .and_then(|document_symbol| document_symbol.dynamic_registration),
+            Feature::InlayHints => text_document
+                .inlay_hint
+                .as_ref()
+                .and_then(|inlay_hint| inlay_hint.dynamic_registration),
+            Feature::SemanticTokens => text_document
+                .semantic_tokens
+                .as_ref()
+                .and_then(|semantic_tokens| semantic_tokens.dynamic_registration),
+            Feature::WorkspaceDiagnostics | Feature::ExternalRepositories => None,
+        };
+        dynamic_registration.unwrap_or_default()
+    }
+
+    /// The options sent when registering the feature's capability. These are the same options
+    /// that are otherwise advertised when the server is initialized.
+    pub(crate) fn registration_options(self) -> Option<Value> {
+        let options = match self {
+            Feature::CodeActions => serde_json::to_value(crate::code_action_options()),
+            Feature::Completion => serde_json::to_value(crate::completion_options()),
+            Feature::DocumentSymbols => {
+                serde_json::to_value(lsp_types::DocumentSymbolOptions::default())
+            }
+            Feature::InlayHints => serde_json::to_value(lsp_types::InlayHintOptions::default()),
+            Feature::SemanticTokens => serde_json::to_value(crate::semantic_tokens_options()),
+            Feature::WorkspaceDiagnostics | Feature::ExternalRepositories => return None,
+        };
+        let mut options = options.ok()?;
+        // A `null` selector applies the registration to the documents that the client selects
+        // for the server.
+        options
+            .as_object_mut()?
+            .insert("documentSelector".to_string(), Value::Null);
+        Some(options)
+    }
+}
+
+/// Which inferred types are shown as inlay hints.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) enum InlayHintsMode {
+    Off,
+    ParamsOnly,
+    #[default]
+    All,
+}
+
+impl InlayHintsMode {
+    const ALL: &'static [InlayHintsMode] = &[
+        InlayHintsMode::Off,
+        InlayHintsMode::ParamsOnly,
+        InlayHintsMode::All,
+    ];
+
+    pub(crate) fn key(self) -> &'static str {
+        match self {
+            InlayHintsMode::Off => "off",
+            InlayHintsMode::ParamsOnly => "params",
+            InlayHintsMode::All => "all",
+        }
+    }
+
+    fn from_key(key: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|mode| mode.key() == key)
+    }
+
+    pub(crate) fn config(self) -> InlayHintsConfig {
+        InlayHintsConfig {
+            parameter_types: self != InlayHintsMode::Off,
+            return_types: self == InlayHintsMode::All,
+            variable_types: self == InlayHintsMode::All,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct FeatureSettings {
+    disabled: FxHashSet<Feature>,
+    pub(crate) inlay_hints: InlayHintsMode,
+}
+
+impl FeatureSettings {
+    /// Parses the settings from the `starpls` section of the client's settings.
+    pub(crate) fn from_section(section: &Value) -> anyhow::Result<Self> {
+        let mut settings = Self::default();
+        match section.get("inlayHints") {
+            None | Some(Value::Null) => {}
+            Some(Value::String(key)) => {
+                settings.inlay_hints = InlayHintsMode::from_key(key).ok_or_else(|| {
+                    anyhow!(
+                        "expected `inlayHints` to be \"off\", \"params\" or \"all\", got {:?}",
+                        key
+                    )
+                })?;
+            }
+            Some(_) => bail!("expected `inlayHints` to be a string"),
+        }
+        let features = match section.get("features") {
+            None | Some(Value::Null) => return Ok(settings),
+            Some(Value::Object(features)) => features,
+            Some(_) => bail!("expected `features` to be an object"),
+        };
+        for (key, value) in features {
+            let feature =
+                Feature::from_key(key).ok_or_else(|| anyhow!("unknown feature {:?}", key))?;
+            match value.as_bool() {
+                Some(true) => {}
+                Some(false) => {
+                    settings.disabled.insert(feature);
+                }
+                None => bail!("expected a boolean for feature {:?}", key),
+            }
+        }
+        Ok(settings)
+    }
+
+    pub(crate) fn is_enabled(&self, feature: Feature) -> bool {
+        !self.disabled.contains(&feature)
+    }
+
+    pub(crate) fn disabled_keys(&self) -> Vec<&'static str> {
+        Feature::ALL
+            .iter()
+            .filter(|feature| self.disabled.contains(feature))
+            .map(|feature| feature.key())
+            .collect()
+    }
+}
@@ -0,0 +1,185 @@
+//! Keeps `load` paths and labels pointing at the right place when files or package directories
+//! are renamed or moved in the editor.
+//!
+//! Only labels in files that the server already knows about are updated, which includes open
+//! documents, everything they load, and the files that load any known file that's being renamed.
+//! Other labels in files that aren't open, e.g. a closed `BUILD` file's `srcs`, aren't updated.
+
+use std::path::{Path, PathBuf};
+
+use starpls_bazel::{label::RepoKind, resolve_workspace, Label};
+use starpls_common::{FileId, ResolvedPath};
+use starpls_ide::{LabelReference, TextEdit};
+
+use crate::server::ServerSnapshot;
+
+/// Paths that are about to be renamed, as `(old, new)` pairs. Each path may be a file or a
+/// directory.
+pub(crate) struct Renames(pub(crate) Vec<(PathBuf, PathBuf)>);
+
+impl Renames {
+    /// Returns where the given path will be once the renames are applied.
+    fn apply(&self, path: &Path) -> PathBuf {
+        for (old, new) in &self.0 {
+            if let Ok(rest) = path.strip_prefix(old) {
+                return if rest.as_os_str().is_empty() {
+                    new.clone()
+                } else {
+                    new.join(rest)
+                };
+            }
+        }
+        path.to_path_buf()
+    }
+
+    fn affects(&self, path: &Path) -> bool {
+        self.0.iter().any(|(old, _)| path.starts_with(old))
+    }
+}
+
+/// The thing that a label refers to, before the renames are applied.
+enum Referent {
+    File(PathBuf),
+    Target { build_file: PathBuf, name: String },
+}
+
+/// Returns edits to the labels in known files that refer to a renamed path, or whose meaning
+/// would change because the file containing it is being moved. See the module docs for which
+/// files are known.
+pub(crate) fn label_edits(
+    snapshot: &ServerSnapshot,
+    renames: &Renames,
+) -> anyhow::Result<Vec<(FileId, Vec<TextEdit>)>> {
+    // Finding the loaders of the renamed files adds them to the database even if they aren't
+    // open, so that their `load` paths are updated too.
+    let renamed_file_ids = snapshot
+        .document_manager
+        .read()
+        .entries()
+        .into_iter()
+        .filter(|(_, path)| renames.affects(path))
+        .map(|(file_id, _)| file_id)
+        .collect::<Vec<_>>();
+    for file_id in renamed_file_ids {
+        snapshot.analysis_snapshot.load_graph(file_id)?;
+    }
+
+    let references = snapshot.analysis_snapshot.label_references()?;
+    let mut edits: Vec<(FileId, Vec<TextEdit>)> = Vec::new();
+    for LabelReference {
+        file_id,
+        range,
+        value,
+        resolved_path,
+    } in references
+    {
+        let (from, referent) = {
+            let document_manager = snapshot.document_manager.read();
+            let from = document_manager.lookup_by_file_id(file_id);
+            let referent = match resolved_path {
+                ResolvedPath::Source { path } => Referent::File(path),
+                ResolvedPath::BuildTarget {
+                    build_file, target, ..
+                } => Referent::Target {
+                    build_file: document_manager.lookup_by_file_id(build_file),
+                    name: target,
+                },
+            };
+            (from, referent)
+        };
+        if !from.starts_with(&snapshot.workspace) {
+            continue;
+        }
+
+        let new_text = match label_after_renames(renames, &value, &from, &referent) {
+            Some(new_text) if new_text != value => new_text,
+            _ => continue,
+        };
+        let edit = TextEdit { range, new_text };
+        match edits.iter_mut().find(|(other, _)| *other == file_id) {
+            Some((_, file_edits)) => file_edits.push(edit),
+            None => edits.push((file_id, vec![edit])),
+        }
+    }
+    Ok(edits)
+}
+
+/// Computes the text of a label once the renames are applied, keeping its form where possible,
+/// e.g. relative labels stay relative as long as the target stays in the same package as the
+/// file that refers to it.
+fn label_after_renames(
+    renames: &Renames,
+    value: &str,
+    from: &Path,
+    referent: &Referent,
+) -> Option<String> {
+    let label = Label::parse(value).ok()?;
+    if label.kind() != RepoKind::Current {
+        return None;
+    }
+
+    let referent_path = match referent {
+        Referent::File(path) => path,
+        Referent::Target { build_file, .. } => build_file,
+    };
+    if !renames.affects(referent_path) && !(label.is_relative() && renames.affects(from)) {
+        return None;
+    }
+
+    let (root, package) = package_after_renames(renames, referent_path)?;
+    let target = match referent {
+        Referent::File(path) => relative_path(&renames.apply(path), &package)?,
+        Referent::Target { name, .. } => name.clone(),
+    };
+
+    if label.is_relative() {
+        let (_, from_package) = package_after_renames(renames, from)?;
+        if from_package == package {
+            return Some(if value.starts_with(':') {
+                format!(":{}", target)
+            } else {
+                target
+            });
+        }
+    }
+
+    let package_name = relative_path(&package, &root)?;
+    let prefix = value.find("//").map_or("", |index| &value[..index]);
+    let is_shorthand =
+        label.has_target_shorthand() && package_name.rsplit('/').next() == Some(target.as_str());
+    Some(if is_shorthand {
+        format!("{}//{}", prefix, package_name)
+    } else {
+        format!("{}//{}:{}", prefix, package_name, target)
+    })
+}
+
+/// Returns the workspace root and the package directory that the given path will be in once the
+/// renames are applied.
+fn package_after_renames(renames: &Renames, path: &Path) -> Option<(PathBuf, PathBuf)> {
+    let (root, package) = resolve_workspace(path).ok()??;
+    let new_path = renames.apply(path);
+    let package = if renames.affects(&package) {
+        renames.apply(&package)
+    } else if new_path != path {
+        // The path is moving without its package, so it may end up in another one. The
+        // directories that it's moving into may not exist yet, so search from the closest one
+        // that does.
+        let existing = new_path.ancestors().skip(1).find(|dir| dir.is_dir())?;
+        resolve_workspace(existing).ok()??.1
+    } else {
+        package
+    };
+    Some((root, package))
+}
+
+/// Returns `path` relative to `base`, with `/` as the separator.
+fn relative_path(path: &Path, base: &Path) -> Option<String> {
+    let components = path
+        .strip_prefix(base)
+        .ok()?
+        .components()
+        .map(|component| component.as_os_str().to_str())
+        .collect::<Option<Vec<_>>>()?;
+    Some(components.join("/"))
+}
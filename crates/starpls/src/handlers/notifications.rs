@@ -1,4 +1,38 @@
-use crate::{convert, server::Server, utils::apply_document_content_changes};
+use crate::{
+    convert,
+    project_config::PROJECT_CONFIG_FILE,
+    server::{Server, SETTINGS_SECTION},
+    utils::apply_document_content_changes,
+};
+
+pub(crate) fn cancel_request(
+    server: &mut Server,
+    params: lsp_types::CancelParams,
+) -> anyhow::Result<()> {
+    let id = match params.id {
+        lsp_types::NumberOrString::Number(id) => id.into(),
+        lsp_types::NumberOrString::String(id) => id.into(),
+    };
+    // The request keeps running on its snapshot, but its response is dropped once it completes.
+    if let Some(resp) = server.req_queue.incoming.cancel(id) {
+        server.connection.sender.send(resp.into())?;
+    }
+    Ok(())
+}
+
+pub(crate) fn did_change_configuration(
+    server: &mut Server,
+    params: lsp_types::DidChangeConfigurationParams,
+) -> anyhow::Result<()> {
+    // Clients that support pulling settings may send a notification without them, in which case
+    // they're requested instead.
+    match params.settings.get(SETTINGS_SECTION) {
+        Some(section) => server.update_feature_settings(Some(section)),
+        None if server.config.has_configuration_support() => server.request_configuration(),
+        None => {}
+    }
+    Ok(())
+}
 
 pub(crate) fn did_open_text_document(
     server: &mut Server,
@@ -29,11 +63,16 @@ pub(crate) fn did_change_text_document(
     let mut document_manager = server.document_manager.write();
     let path = convert::path_buf_from_url(&params.text_document.uri)?;
     if let Some(file_id) = document_manager.lookup_by_path_buf(&path) {
-        let contents = document_manager
+        let (contents, has_bom) = document_manager
             .get(file_id)
-            .map(|document| document.contents.clone())
+            .map(|document| (document.contents.clone(), document.has_bom))
             .expect("lookup contents of non-existent file");
-        let contents = apply_document_content_changes(contents, params.content_changes);
+        let contents = apply_document_content_changes(
+            contents,
+            params.content_changes,
+            server.config.position_encoding(),
+            has_bom,
+        );
         document_manager.modify(file_id, contents, Some(params.text_document.version))
     }
     Ok(())
@@ -44,6 +83,16 @@ pub(crate) fn did_save_text_document(
     params: lsp_types::DidSaveTextDocumentParams,
 ) -> anyhow::Result<()> {
     let path = convert::path_buf_from_url(&params.text_document.uri)?;
+    if path.file_name().and_then(|file_name| file_name.to_str()) == Some(PROJECT_CONFIG_FILE) {
+        server.reload_project_configs();
+        return Ok(());
+    }
+    if let Some("MODULE.bazel" | "MODULE.bazel.lock") =
+        path.file_name().and_then(|file_name| file_name.to_str())
+    {
+        server.reload_module_lockfile();
+    }
+
     if server
         .document_manager
         .read()
@@ -1,17 +1,45 @@
-use anyhow::Ok;
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, bail, Ok};
+use starpls_common::{FileId, FileRange};
 use starpls_ide::{
-    CompletionItemKind,
+    indentation_rules, CallHierarchyItem, CodeActionCommand, CodeActionKind, CompletionItemKind,
     CompletionMode::{InsertText, TextEdit},
-    FilePosition,
+    EditTarget, FileEdit, FilePosition, FoldingRangeKind, IncomingCall, IndentAction,
+    InlayHintKind, RunnableKind, StringKind, TargetAttributeValue,
 };
+use starpls_syntax::TextRange;
 
 use crate::{
     convert::{self, path_buf_from_url},
-    extensions::{ShowHirParams, ShowSyntaxTreeParams},
-    server::ServerSnapshot,
+    effective_config,
+    extensions::{
+        EffectiveConfigParams, EffectiveConfigResult, IndentationRules,
+        LanguageConfigurationResult, LoadGraphEdge, LoadGraphParams, LoadGraphResult,
+        OnEnterAction, OnEnterRule, Runnable, RunnableArgs, RunnablesParams, ShowHirParams,
+        ShowSyntaxTreeParams, TargetInfo, TargetsParams, Todo, TodosParams,
+        ADD_WORD_TO_DICTIONARY_COMMAND, UPDATE_ALL_DEPENDENCIES_COMMAND,
+    },
+    features::InlayHintsMode,
+    file_renames::{self, Renames},
+    partial_results::PartialResults,
+    server::{Server, ServerSnapshot},
     utils::response_from_locations,
 };
 
+/// The modifiers of the `string` semantic tokens, in the order that they're listed in the legend
+/// sent to the client.
+pub(crate) const STRING_TOKEN_MODIFIERS: &[&str] =
+    &["label", "path", "glob", "format", "visibility"];
+
+/// How long a request that searches the workspace, e.g. for the references to a symbol, runs
+/// before giving up and returning the results found so far. Clients that accept partial results
+/// get every result instead, however long the search takes.
+const SEARCH_TIME_BUDGET: Duration = Duration::from_secs(2);
+
 macro_rules! try_opt {
     ($expr:expr) => {
         match { $expr } {
@@ -45,6 +73,490 @@ pub(crate) fn show_syntax_tree(
     Ok(rendered_syntax_tree.unwrap_or_else(|| "".to_string()))
 }
 
+pub(crate) fn language_configuration(
+    _snapshot: &ServerSnapshot,
+    _params: (),
+) -> anyhow::Result<LanguageConfigurationResult> {
+    let rules = indentation_rules();
+    Ok(LanguageConfigurationResult {
+        indentation_rules: IndentationRules {
+            increase_indent_pattern: rules.increase_indent_pattern,
+            decrease_indent_pattern: rules.decrease_indent_pattern,
+        },
+        on_enter_rules: rules
+            .on_enter_rules
+            .into_iter()
+            .map(|rule| OnEnterRule {
+                before_text: rule.before_text,
+                action: OnEnterAction {
+                    indent: match rule.action {
+                        IndentAction::Indent => "indent",
+                        IndentAction::Outdent => "outdent",
+                    }
+                    .to_string(),
+                },
+            })
+            .collect(),
+    })
+}
+
+pub(crate) fn load_graph(
+    snapshot: &ServerSnapshot,
+    params: LoadGraphParams,
+) -> anyhow::Result<LoadGraphResult> {
+    let path = path_buf_from_url(&params.text_document.uri)?;
+    let document_manager = snapshot.document_manager.read();
+    let file_id = match document_manager.lookup_by_path_buf(&path) {
+        Some(file_id) => file_id,
+        None => return Ok(Default::default()),
+    };
+    let graph = match snapshot.analysis_snapshot.load_graph(file_id)? {
+        Some(graph) => graph,
+        None => return Ok(Default::default()),
+    };
+    let to_url =
+        |file_id| lsp_types::Url::from_file_path(document_manager.lookup_by_file_id(file_id)).ok();
+    Ok(LoadGraphResult {
+        loadees: graph.loadees.into_iter().filter_map(to_url).collect(),
+        loaders: graph.loaders.into_iter().filter_map(to_url).collect(),
+        edges: graph
+            .edges
+            .into_iter()
+            .filter_map(|(loader, loadee)| {
+                Some(LoadGraphEdge {
+                    loader: to_url(loader)?,
+                    loadee: to_url(loadee)?,
+                })
+            })
+            .collect(),
+    })
+}
+
+pub(crate) fn runnables(
+    snapshot: &ServerSnapshot,
+    params: RunnablesParams,
+) -> anyhow::Result<Vec<Runnable>> {
+    let path = path_buf_from_url(&params.text_document.uri)?;
+    let file_id = match snapshot.document_manager.read().lookup_by_path_buf(&path) {
+        Some(file_id) => file_id,
+        None => return Ok(Vec::new()),
+    };
+    let encoding = snapshot.config.position_encoding();
+    let line_index = match snapshot.line_index(file_id)? {
+        Some(line_index) => line_index,
+        None => return Ok(Vec::new()),
+    };
+    let pos = match params.position {
+        Some(pos) => match convert::text_size_from_lsp_position(snapshot, file_id, pos)? {
+            Some(pos) => Some(pos),
+            None => return Ok(Vec::new()),
+        },
+        None => None,
+    };
+
+    // Determine the label of the file's package.
+    let (workspace_root, package) = match starpls_bazel::resolve_workspace(&path)? {
+        Some(res) => res,
+        None => return Ok(Vec::new()),
+    };
+    let package = package
+        .strip_prefix(&workspace_root)?
+        .to_string_lossy()
+        .replace('\\', "/");
+
+    Ok(snapshot
+        .analysis_snapshot
+        .runnables(file_id, pos)?
+        .unwrap_or_default()
+        .into_iter()
+        .map(|runnable| {
+            let command = match runnable.kind {
+                RunnableKind::Build => "build",
+                RunnableKind::Test => "test",
+                RunnableKind::Run => "run",
+            };
+            let label = format!(
+                "//{}:{}",
+                package,
+                runnable.target.as_deref().unwrap_or("all")
+            );
+            Runnable {
+                label: format!("bazel {} {}", command, label),
+                location: runnable.range.and_then(|range| {
+                    convert::lsp_range_from_text_range(range, line_index, encoding)
+                }),
+                args: RunnableArgs {
+                    workspace_root: workspace_root.clone(),
+                    bazel_args: vec![command.to_string(), label],
+                },
+            }
+        })
+        .collect())
+}
+
+pub(crate) fn targets(
+    snapshot: &ServerSnapshot,
+    params: TargetsParams,
+) -> anyhow::Result<Vec<TargetInfo>> {
+    let path = path_buf_from_url(&params.text_document.uri)?;
+    let file_id = match snapshot.document_manager.read().lookup_by_path_buf(&path) {
+        Some(file_id) => file_id,
+        None => return Ok(Vec::new()),
+    };
+    let encoding = snapshot.config.position_encoding();
+    let line_index = match snapshot.line_index(file_id)? {
+        Some(line_index) => line_index,
+        None => return Ok(Vec::new()),
+    };
+    Ok(snapshot
+        .analysis_snapshot
+        .targets(file_id)?
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|target| {
+            Some(TargetInfo {
+                range: convert::lsp_range_from_text_range(target.range, line_index, encoding)?,
+                selection_range: convert::lsp_range_from_text_range(
+                    target.name_range,
+                    line_index,
+                    encoding,
+                )?,
+                name: target.name,
+                kind: target.kind.to_string(),
+                attributes: target
+                    .attributes
+                    .into_iter()
+                    .filter_map(|attr| {
+                        let value = match attr.value {
+                            TargetAttributeValue::String(s) => {
+                                serde_json::Value::String(s.to_string())
+                            }
+                            TargetAttributeValue::StringList(values) => serde_json::Value::Array(
+                                values
+                                    .iter()
+                                    .map(|value| serde_json::Value::String(value.to_string()))
+                                    .collect(),
+                            ),
+                            TargetAttributeValue::Bool(b) => serde_json::Value::Bool(b),
+                            TargetAttributeValue::Int(i) => serde_json::Value::from(i),
+                            TargetAttributeValue::Other(_) => return None,
+                        };
+                        Some((attr.name.to_string(), value))
+                    })
+                    .collect(),
+                is_macro: target.is_macro,
+            })
+        })
+        .collect())
+}
+
+pub(crate) fn todos(snapshot: &ServerSnapshot, params: TodosParams) -> anyhow::Result<Vec<Todo>> {
+    let path = path_buf_from_url(&params.text_document.uri)?;
+    let file_id = match snapshot.document_manager.read().lookup_by_path_buf(&path) {
+        Some(file_id) => file_id,
+        None => return Ok(Vec::new()),
+    };
+    let encoding = snapshot.config.position_encoding();
+    let line_index = match snapshot.line_index(file_id)? {
+        Some(line_index) => line_index,
+        None => return Ok(Vec::new()),
+    };
+    Ok(snapshot
+        .analysis_snapshot
+        .todos(file_id)?
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|todo| {
+            Some(Todo {
+                range: convert::lsp_range_from_text_range(todo.range, line_index, encoding)?,
+                tag: todo.tag,
+                owner: todo.owner,
+                issue: todo.issue,
+                message: todo.message,
+            })
+        })
+        .collect())
+}
+
+pub(crate) fn code_action(
+    snapshot: &ServerSnapshot,
+    params: lsp_types::CodeActionParams,
+) -> anyhow::Result<Option<lsp_types::CodeActionResponse>> {
+    let uri = params.text_document.uri;
+    let path = path_buf_from_url(&uri)?;
+    let file_id = try_opt!(snapshot.document_manager.read().lookup_by_path_buf(&path));
+    let start = try_opt!(convert::text_size_from_lsp_position(
+        snapshot,
+        file_id,
+        params.range.start,
+    )?);
+    let end = try_opt!(convert::text_size_from_lsp_position(
+        snapshot,
+        file_id,
+        params.range.end,
+    )?);
+    let range = FileRange {
+        file_id,
+        range: TextRange::new(start, end),
+    };
+    let mut actions = try_opt!(snapshot.analysis_snapshot.code_actions(range.clone())?);
+
+    // Combining every fix in the file is expensive, so only do it when it was asked for.
+    if is_requested_kind(&params.context, &lsp_types::CodeActionKind::SOURCE_FIX_ALL) {
+        actions.extend(snapshot.analysis_snapshot.fix_all_action(file_id)?);
+    }
+
+    // Fixes that load undefined names look up the modules exporting them in the workspace index.
+    let names = snapshot
+        .analysis_snapshot
+        .unresolved_names(range.clone())?
+        .unwrap_or_default();
+    if !names.is_empty() {
+        let exports = snapshot.workspace_symbols.exported_symbols(&names, &path);
+        if let Some(load_actions) = snapshot
+            .analysis_snapshot
+            .add_load_actions(range.clone(), exports)?
+        {
+            actions.extend(load_actions);
+        }
+    }
+
+    // Actions that generate files next to the current one need to know its name.
+    if let Some(file_name) = path.file_name().and_then(|file_name| file_name.to_str()) {
+        if let Some(scaffold_actions) = snapshot
+            .analysis_snapshot
+            .test_scaffold_actions(range, file_name.to_string())?
+        {
+            actions.extend(scaffold_actions);
+        }
+    }
+
+    // Files owned by Bazel can't be edited, so drop the actions that would edit them. Actions
+    // that create files would create them next to the current file.
+    let is_read_only = snapshot.is_read_only(file_id);
+    actions.retain(|action| {
+        action
+            .file_edits
+            .iter()
+            .all(|file_edit| match file_edit.target {
+                EditTarget::File(target_file_id) => !snapshot.is_read_only(target_file_id),
+                EditTarget::NewFile(_) => !is_read_only,
+            })
+            && !(is_read_only && !action.edits.is_empty())
+    });
+
+    let mut res = Vec::with_capacity(actions.len());
+    for action in actions {
+        let kind = match action.kind {
+            CodeActionKind::QuickFix => lsp_types::CodeActionKind::QUICKFIX,
+            CodeActionKind::RefactorExtract => lsp_types::CodeActionKind::REFACTOR_EXTRACT,
+            CodeActionKind::RefactorRewrite => lsp_types::CodeActionKind::REFACTOR_REWRITE,
+            CodeActionKind::Source => lsp_types::CodeActionKind::SOURCE,
+            CodeActionKind::SourceFixAll => lsp_types::CodeActionKind::SOURCE_FIX_ALL,
+            CodeActionKind::SourceOrganizeImports => {
+                lsp_types::CodeActionKind::SOURCE_ORGANIZE_IMPORTS
+            }
+        };
+
+        if !is_requested_kind(&params.context, &kind) {
+            continue;
+        }
+
+        let edit = if action.edits.is_empty() && action.file_edits.is_empty() {
+            None
+        } else {
+            match code_action_edit(snapshot, &uri, file_id, action.edits, action.file_edits)? {
+                Some(edit) => Some(edit),
+                None => continue,
+            }
+        };
+        let command = action.command.map(|command| match command {
+            CodeActionCommand::AddWordToDictionary(word) => lsp_types::Command {
+                title: action.title.clone(),
+                command: ADD_WORD_TO_DICTIONARY_COMMAND.to_string(),
+                arguments: Some(vec![serde_json::Value::String(word)]),
+            },
+            CodeActionCommand::UpdateAllDependencies => lsp_types::Command {
+                title: action.title.clone(),
+                command: UPDATE_ALL_DEPENDENCIES_COMMAND.to_string(),
+                arguments: Some(vec![serde_json::Value::String(uri.to_string())]),
+            },
+        });
+        res.push(lsp_types::CodeActionOrCommand::CodeAction(
+            lsp_types::CodeAction {
+                title: action.title,
+                kind: Some(kind),
+                edit,
+                command,
+                is_preferred: Some(action.is_preferred),
+                ..Default::default()
+            },
+        ));
+    }
+
+    Ok(Some(res))
+}
+
+/// Returns whether a request for code actions asked for actions of the given kind. Clients request
+/// only some kinds of actions in some contexts, e.g. `source.fixAll` when saving a file. Kinds are
+/// hierarchical, so `source` includes `source.fixAll`.
+fn is_requested_kind(
+    context: &lsp_types::CodeActionContext,
+    kind: &lsp_types::CodeActionKind,
+) -> bool {
+    context.only.as_ref().map_or(true, |only| {
+        only.iter().any(|requested| {
+            kind.as_str() == requested.as_str()
+                || kind
+                    .as_str()
+                    .strip_prefix(requested.as_str())
+                    .map_or(false, |rest| rest.starts_with('.'))
+        })
+    })
+}
+
+/// Converts the edits made by a code action to a workspace edit. Returns `None` if the edits
+/// can't be converted, e.g. because they create a file and the client doesn't support that.
+fn code_action_edit(
+    snapshot: &ServerSnapshot,
+    uri: &lsp_types::Url,
+    file_id: FileId,
+    edits: Vec<starpls_ide::TextEdit>,
+    file_edits: Vec<FileEdit>,
+) -> anyhow::Result<Option<lsp_types::WorkspaceEdit>> {
+    let mut changes = vec![(
+        uri.clone(),
+        try_opt!(lsp_text_edits(snapshot, file_id, edits)?),
+    )];
+    let mut created_files = Vec::new();
+    for file_edit in file_edits {
+        match file_edit.target {
+            EditTarget::File(target_file_id) => {
+                let path = snapshot
+                    .document_manager
+                    .read()
+                    .lookup_by_file_id(target_file_id);
+                let target_uri = try_opt!(lsp_types::Url::from_file_path(path).ok());
+                let edits = try_opt!(lsp_text_edits(snapshot, target_file_id, file_edit.edits)?);
+                changes.push((target_uri, edits));
+            }
+            EditTarget::NewFile(name) => {
+                if !snapshot.config.has_create_file_support() {
+                    return Ok(None);
+                }
+                let path = path_buf_from_url(uri)?.with_file_name(name);
+                let target_uri = try_opt!(lsp_types::Url::from_file_path(path).ok());
+
+                // New files are empty, so every edit inserts text at the start of the file. They
+                // use the same line endings as the file that the code action was requested for.
+                let line_endings = snapshot.document_manager.read().line_endings(file_id);
+                let edits = file_edit
+                    .edits
+                    .into_iter()
+                    .map(|edit| lsp_types::TextEdit {
+                        range: Default::default(),
+                        new_text: line_endings.apply(&edit.new_text).into_owned(),
+                    })
+                    .collect();
+                created_files.push(target_uri.clone());
+                changes.push((target_uri, edits));
+            }
+        }
+    }
+
+    if created_files.is_empty() && !snapshot.config.has_document_changes_support() {
+        return Ok(Some(lsp_types::WorkspaceEdit {
+            changes: Some(changes.into_iter().collect()),
+            ..Default::default()
+        }));
+    }
+
+    let mut operations = created_files
+        .into_iter()
+        .map(|uri| {
+            lsp_types::DocumentChangeOperation::Op(lsp_types::ResourceOp::Create(
+                lsp_types::CreateFile {
+                    uri,
+                    options: None,
+                    annotation_id: None,
+                },
+            ))
+        })
+        .collect::<Vec<_>>();
+    operations.extend(changes.into_iter().map(|(uri, edits)| {
+        lsp_types::DocumentChangeOperation::Edit(lsp_types::TextDocumentEdit {
+            text_document: lsp_types::OptionalVersionedTextDocumentIdentifier {
+                uri,
+                version: None,
+            },
+            edits: edits.into_iter().map(lsp_types::OneOf::Left).collect(),
+        })
+    }));
+    Ok(Some(lsp_types::WorkspaceEdit {
+        document_changes: Some(lsp_types::DocumentChanges::Operations(operations)),
+        ..Default::default()
+    }))
+}
+
+fn lsp_text_edits(
+    snapshot: &ServerSnapshot,
+    file_id: FileId,
+    edits: Vec<starpls_ide::TextEdit>,
+) -> anyhow::Result<Option<Vec<lsp_types::TextEdit>>> {
+    let encoding = snapshot.config.position_encoding();
+    let line_index = try_opt!(snapshot.line_index(file_id)?);
+    let line_endings = snapshot.document_manager.read().line_endings(file_id);
+    Ok(edits
+        .into_iter()
+        .map(|edit| {
+            Some(lsp_types::TextEdit {
+                range: convert::lsp_range_from_text_range(edit.range, line_index, encoding)?,
+                new_text: line_endings.apply(&edit.new_text).into_owned(),
+            })
+        })
+        .collect())
+}
+
+/// Returns the merged settings, including those from the `starpls.toml` files that apply to the
+/// given document.
+pub(crate) fn effective_config(
+    server: &mut Server,
+    params: EffectiveConfigParams,
+) -> anyhow::Result<EffectiveConfigResult> {
+    let path = params
+        .text_document
+        .map(|text_document| path_buf_from_url(&text_document.uri))
+        .transpose()?;
+    Ok(EffectiveConfigResult {
+        settings: effective_config::effective_config(server, path.as_deref()),
+    })
+}
+
+pub(crate) fn execute_command(
+    server: &mut Server,
+    params: lsp_types::ExecuteCommandParams,
+) -> anyhow::Result<Option<serde_json::Value>> {
+    match params.command.as_str() {
+        ADD_WORD_TO_DICTIONARY_COMMAND => {
+            let word = params
+                .arguments
+                .first()
+                .and_then(|arg| arg.as_str())
+                .ok_or_else(|| anyhow!("expected a word to add to the dictionary"))?;
+            server.add_word_to_dictionary(word)?;
+        }
+        UPDATE_ALL_DEPENDENCIES_COMMAND => {
+            let path = match params.arguments.first().and_then(|arg| arg.as_str()) {
+                Some(uri) => path_buf_from_url(&lsp_types::Url::parse(uri)?)?,
+                None => server.workspace.join("MODULE.bazel"),
+            };
+            server.update_all_dependencies(path)?;
+        }
+        command => bail!("unknown command: {}", command),
+    }
+    Ok(None)
+}
+
 pub(crate) fn goto_definition(
     snapshot: &ServerSnapshot,
     params: lsp_types::GotoDefinitionParams,
@@ -68,19 +580,105 @@ pub(crate) fn goto_definition(
     Ok(Some(resp))
 }
 
+pub(crate) fn references(
+    snapshot: &ServerSnapshot,
+    params: lsp_types::ReferenceParams,
+) -> anyhow::Result<Option<Vec<lsp_types::Location>>> {
+    let path = path_buf_from_url(&params.text_document_position.text_document.uri)?;
+    let file_id = try_opt!(snapshot.document_manager.read().lookup_by_path_buf(&path));
+    let pos = try_opt!(convert::text_size_from_lsp_position(
+        snapshot,
+        file_id,
+        params.text_document_position.position,
+    )?);
+
+    // Clients that accept partial results get the references as soon as they're found, so the
+    // search can take as long as it needs. Everyone else gets whatever was found within the time
+    // budget.
+    let mut results =
+        PartialResults::new(snapshot, params.partial_result_params.partial_result_token);
+    let deadline = if results.is_streaming() {
+        None
+    } else {
+        Some(Instant::now() + SEARCH_TIME_BUDGET)
+    };
+    let mut err = None;
+    let is_complete = try_opt!(snapshot.analysis_snapshot.references_in_batches(
+        FilePosition { file_id, pos },
+        params.context.include_declaration,
+        deadline,
+        &mut |references| match locations_from_file_ranges(snapshot, references) {
+            // `Ok` is `anyhow::Ok` in this module, which can't be matched on.
+            Result::Ok(locations) => results.report(locations),
+            Err(e) => {
+                err.get_or_insert(e);
+            }
+        },
+    )?);
+    if let Some(err) = err {
+        return Err(err);
+    }
+
+    if !is_complete {
+        show_incomplete_search_message(snapshot, "references");
+    }
+    Ok(Some(results.finish()))
+}
+
+/// Tells the user that a search ran out of time, since LSP has no way to mark most responses as
+/// incomplete.
+fn show_incomplete_search_message(snapshot: &ServerSnapshot, what: &str) {
+    snapshot.send_notification::<lsp_types::notification::ShowMessage>(
+        lsp_types::ShowMessageParams {
+            typ: lsp_types::MessageType::INFO,
+            message: format!(
+                "Stopped searching for {} after {} seconds. The results may be incomplete.",
+                what,
+                SEARCH_TIME_BUDGET.as_secs()
+            ),
+        },
+    );
+}
+
+fn locations_from_file_ranges(
+    snapshot: &ServerSnapshot,
+    references: Vec<FileRange>,
+) -> anyhow::Result<Vec<lsp_types::Location>> {
+    let encoding = snapshot.config.position_encoding();
+    let mut locations = Vec::with_capacity(references.len());
+    for FileRange { file_id, range } in references {
+        let line_index = match snapshot.line_index(file_id)? {
+            Some(line_index) => line_index,
+            None => continue,
+        };
+        let path = snapshot.document_manager.read().lookup_by_file_id(file_id);
+        let (uri, range) = match (
+            lsp_types::Url::from_file_path(path).ok(),
+            convert::lsp_range_from_text_range(range, line_index, encoding),
+        ) {
+            (Some(uri), Some(range)) => (uri, range),
+            _ => continue,
+        };
+        locations.push(lsp_types::Location { uri, range });
+    }
+    Ok(locations)
+}
+
 pub(crate) fn completion(
     snapshot: &ServerSnapshot,
     params: lsp_types::CompletionParams,
 ) -> anyhow::Result<Option<lsp_types::CompletionResponse>> {
     let path = path_buf_from_url(&params.text_document_position.text_document.uri)?;
     let file_id = try_opt!(snapshot.document_manager.read().lookup_by_path_buf(&path));
-    let line_index = try_opt!(snapshot.analysis_snapshot.line_index(file_id)?);
+    let encoding = snapshot.config.position_encoding();
+    let line_index = try_opt!(snapshot.line_index(file_id)?);
     let pos = try_opt!(convert::text_size_from_lsp_position(
         snapshot,
         file_id,
         params.text_document_position.position,
     )?);
 
+    let line_endings = snapshot.document_manager.read().line_endings(file_id);
     Ok(Some(
         snapshot
             .analysis_snapshot
@@ -98,8 +696,10 @@ pub(crate) fn completion(
                         TextEdit(edit) => (
                             None,
                             Some(lsp_types::CompletionTextEdit::Edit(lsp_types::TextEdit {
-                                range: convert::lsp_range_from_text_range(edit.range, line_index)?,
-                                new_text: edit.new_text,
+                                range: convert::lsp_range_from_text_range(
+                                    edit.range, line_index, encoding,
+                                )?,
+                                new_text: line_endings.apply(&edit.new_text).into_owned(),
                             })),
                         ),
                     },
@@ -118,6 +718,7 @@ pub(crate) fn completion(
                         CompletionItemKind::Folder => lsp_types::CompletionItemKind::FOLDER,
                         CompletionItemKind::Constant => lsp_types::CompletionItemKind::CONSTANT,
                     }),
+                    detail: item.detail,
                     sort_text,
                     insert_text,
                     text_edit,
@@ -140,16 +741,301 @@ pub(crate) fn hover(
         file_id,
         params.text_document_position_params.position,
     )?);
-    Ok(snapshot
+    let hover = try_opt!(snapshot
         .analysis_snapshot
-        .hover(FilePosition { file_id, pos })?
-        .map(|hover| lsp_types::Hover {
-            contents: lsp_types::HoverContents::Markup(lsp_types::MarkupContent {
-                kind: lsp_types::MarkupKind::Markdown,
-                value: hover.contents.value,
-            }),
-            range: None,
-        }))
+        .hover(FilePosition { file_id, pos })?);
+    let mut value = hover.contents.value;
+    if let Some(FileRange { file_id, range }) = hover.definition {
+        let path = snapshot.document_manager.read().lookup_by_file_id(file_id);
+        if let (Some(uri), Some(line_index)) = (
+            lsp_types::Url::from_file_path(path).ok(),
+            snapshot.line_index(file_id)?,
+        ) {
+            // Editors jump to the line given by the `#L` fragment, which is 1-based.
+            let line = line_index.line_col(range.start()).line + 1;
+            value.push_str(&format!("\n[Go to definition]({}#L{})\n", uri, line));
+        }
+    }
+    Ok(Some(lsp_types::Hover {
+        contents: lsp_types::HoverContents::Markup(lsp_types::MarkupContent {
+            kind: lsp_types::MarkupKind::Markdown,
+            value,
+        }),
+        range: None,
+    }))
+}
+
+pub(crate) fn inlay_hint(
+    snapshot: &ServerSnapshot,
+    params: lsp_types::InlayHintParams,
+) -> anyhow::Result<Option<Vec<lsp_types::InlayHint>>> {
+    if snapshot.inlay_hints == InlayHintsMode::Off {
+        return Ok(None);
+    }
+    let path = path_buf_from_url(&params.text_document.uri)?;
+    let file_id = try_opt!(snapshot.document_manager.read().lookup_by_path_buf(&path));
+    let start = try_opt!(convert::text_size_from_lsp_position(
+        snapshot,
+        file_id,
+        params.range.start,
+    )?);
+    let end = try_opt!(convert::text_size_from_lsp_position(
+        snapshot,
+        file_id,
+        params.range.end,
+    )?);
+    let hints = try_opt!(snapshot.analysis_snapshot.inlay_hints(
+        FileRange {
+            file_id,
+            range: TextRange::new(start, end),
+        },
+        snapshot.inlay_hints.config(),
+    )?);
+    let encoding = snapshot.config.position_encoding();
+    let line_index = try_opt!(snapshot.line_index(file_id)?);
+    Ok(Some(
+        hints
+            .into_iter()
+            .filter_map(|hint| {
+                Some(lsp_types::InlayHint {
+                    position: convert::lsp_position_from_text_size(hint.pos, line_index, encoding)?,
+                    label: lsp_types::InlayHintLabel::String(hint.label),
+                    kind: Some(lsp_types::InlayHintKind::TYPE),
+                    text_edits: None,
+                    tooltip: None,
+                    padding_left: Some(hint.kind == InlayHintKind::ReturnType),
+                    padding_right: None,
+                    data: None,
+                })
+            })
+            .collect(),
+    ))
+}
+
+pub(crate) fn inline_value(
+    snapshot: &ServerSnapshot,
+    params: lsp_types::InlineValueParams,
+) -> anyhow::Result<Option<Vec<lsp_types::InlineValue>>> {
+    let path = path_buf_from_url(&params.text_document.uri)?;
+    let file_id = try_opt!(snapshot.document_manager.read().lookup_by_path_buf(&path));
+    let start = try_opt!(convert::text_size_from_lsp_position(
+        snapshot,
+        file_id,
+        params.range.start,
+    )?);
+    let end = try_opt!(convert::text_size_from_lsp_position(
+        snapshot,
+        file_id,
+        params.range.end,
+    )?);
+    let stopped_at = try_opt!(convert::text_size_from_lsp_position(
+        snapshot,
+        file_id,
+        params.context.stopped_location.start,
+    )?);
+    let values = try_opt!(snapshot.analysis_snapshot.inline_values(
+        FileRange {
+            file_id,
+            range: TextRange::new(start, end),
+        },
+        stopped_at,
+    )?);
+    let encoding = snapshot.config.position_encoding();
+    let line_index = try_opt!(snapshot.line_index(file_id)?);
+    Ok(Some(
+        values
+            .into_iter()
+            .filter_map(|value| {
+                Some(lsp_types::InlineValue::VariableLookup(
+                    lsp_types::InlineValueVariableLookup {
+                        range: convert::lsp_range_from_text_range(
+                            value.range,
+                            line_index,
+                            encoding,
+                        )?,
+                        variable_name: Some(value.name),
+                        case_sensitive_lookup: true,
+                    },
+                ))
+            })
+            .collect(),
+    ))
+}
+
+pub(crate) fn formatting(
+    snapshot: &ServerSnapshot,
+    params: lsp_types::DocumentFormattingParams,
+) -> anyhow::Result<Option<Vec<lsp_types::TextEdit>>> {
+    let path = path_buf_from_url(&params.text_document.uri)?;
+    let file_id = try_opt!(snapshot.document_manager.read().lookup_by_path_buf(&path));
+    if snapshot.is_read_only(file_id) {
+        return Ok(None);
+    }
+    if let Some(edits) = snapshot
+        .buildifier
+        .as_ref()
+        .and_then(|buildifier| buildifier.format_document(snapshot, file_id))
+    {
+        return lsp_text_edits(snapshot, file_id, edits);
+    }
+    let edits = try_opt!(snapshot.analysis_snapshot.format(file_id)?);
+    lsp_text_edits(snapshot, file_id, edits)
+}
+
+pub(crate) fn range_formatting(
+    snapshot: &ServerSnapshot,
+    params: lsp_types::DocumentRangeFormattingParams,
+) -> anyhow::Result<Option<Vec<lsp_types::TextEdit>>> {
+    let path = path_buf_from_url(&params.text_document.uri)?;
+    let file_id = try_opt!(snapshot.document_manager.read().lookup_by_path_buf(&path));
+    if snapshot.is_read_only(file_id) {
+        return Ok(None);
+    }
+    let start = try_opt!(convert::text_size_from_lsp_position(
+        snapshot,
+        file_id,
+        params.range.start,
+    )?);
+    let end = try_opt!(convert::text_size_from_lsp_position(
+        snapshot,
+        file_id,
+        params.range.end,
+    )?);
+    let edits = try_opt!(snapshot.analysis_snapshot.format_range(FileRange {
+        file_id,
+        range: TextRange::new(start, end),
+    })?);
+    lsp_text_edits(snapshot, file_id, edits)
+}
+
+pub(crate) fn on_type_formatting(
+    snapshot: &ServerSnapshot,
+    params: lsp_types::DocumentOnTypeFormattingParams,
+) -> anyhow::Result<Option<Vec<lsp_types::TextEdit>>> {
+    let path = path_buf_from_url(&params.text_document_position.text_document.uri)?;
+    let file_id = try_opt!(snapshot.document_manager.read().lookup_by_path_buf(&path));
+    if snapshot.is_read_only(file_id) {
+        return Ok(None);
+    }
+    let pos = try_opt!(convert::text_size_from_lsp_position(
+        snapshot,
+        file_id,
+        params.text_document_position.position,
+    )?);
+    let ch = try_opt!(params.ch.chars().next());
+    let default_indent = if params.options.insert_spaces {
+        " ".repeat(params.options.tab_size as usize)
+    } else {
+        "\t".to_string()
+    };
+    let edits = try_opt!(snapshot.analysis_snapshot.on_type_formatting(
+        FilePosition { file_id, pos },
+        ch,
+        &default_indent
+    )?);
+    lsp_text_edits(snapshot, file_id, edits)
+}
+
+pub(crate) fn rename(
+    snapshot: &ServerSnapshot,
+    params: lsp_types::RenameParams,
+) -> anyhow::Result<Option<lsp_types::WorkspaceEdit>> {
+    let path = path_buf_from_url(&params.text_document_position.text_document.uri)?;
+    let file_id = try_opt!(snapshot.document_manager.read().lookup_by_path_buf(&path));
+    let pos = try_opt!(convert::text_size_from_lsp_position(
+        snapshot,
+        file_id,
+        params.text_document_position.position,
+    )?);
+    let rename = try_opt!(snapshot
+        .analysis_snapshot
+        .rename(FilePosition { file_id, pos }, &params.new_name)??);
+
+    // Until the workspace's loads are indexed, only the files that the server already knows
+    // about are searched for call sites and conflicts, so a rename could miss some of them.
+    if rename.exported_from.is_some() && !snapshot.load_index.is_indexed() {
+        bail!("Can't rename yet, since the workspace is still being indexed. Try again shortly.");
+    }
+
+    let mut changes = Vec::with_capacity(rename.file_edits.len());
+    for file_edit in rename.file_edits {
+        let target_file_id = match file_edit.target {
+            EditTarget::File(target_file_id) => target_file_id,
+            EditTarget::NewFile(_) => continue,
+        };
+        let path = snapshot
+            .document_manager
+            .read()
+            .lookup_by_file_id(target_file_id);
+        if snapshot.is_read_only(target_file_id) {
+            bail!(
+                "Can't rename, since {} is owned by Bazel and can't be edited",
+                path.display()
+            );
+        }
+        let uri = try_opt!(lsp_types::Url::from_file_path(path).ok());
+        let edits = try_opt!(lsp_text_edits(snapshot, target_file_id, file_edit.edits)?);
+        changes.push((uri, edits));
+    }
+
+    // Files in external repositories are only analyzed once something loads them, so functions
+    // defined there may have callers in other external repositories that weren't updated. Ask
+    // the user to confirm the rename in this case, if the client supports it.
+    let is_external = rename.exported_from.map_or(false, |file_id| {
+        !snapshot
+            .document_manager
+            .read()
+            .lookup_by_file_id(file_id)
+            .starts_with(&snapshot.workspace)
+    });
+    let annotation_id = (is_external && snapshot.config.has_change_annotation_support())
+        .then(|| "external-call-sites".to_string());
+    if annotation_id.is_none() && !snapshot.config.has_document_changes_support() {
+        return Ok(Some(lsp_types::WorkspaceEdit {
+            changes: Some(changes.into_iter().collect()),
+            ..Default::default()
+        }));
+    }
+
+    let document_changes = changes
+        .into_iter()
+        .map(|(uri, edits)| lsp_types::TextDocumentEdit {
+            text_document: lsp_types::OptionalVersionedTextDocumentIdentifier {
+                uri,
+                version: None,
+            },
+            edits: edits
+                .into_iter()
+                .map(|text_edit| match &annotation_id {
+                    Some(annotation_id) => lsp_types::OneOf::Right(lsp_types::AnnotatedTextEdit {
+                        text_edit,
+                        annotation_id: annotation_id.clone(),
+                    }),
+                    None => lsp_types::OneOf::Left(text_edit),
+                })
+                .collect(),
+        })
+        .collect();
+    Ok(Some(lsp_types::WorkspaceEdit {
+        document_changes: Some(lsp_types::DocumentChanges::Edits(document_changes)),
+        change_annotations: annotation_id.map(|annotation_id| {
+            [(
+                annotation_id,
+                lsp_types::ChangeAnnotation {
+                    label: "Rename parameter".to_string(),
+                    needs_confirmation: Some(true),
+                    description: Some(
+                        "This function is defined in an external repository. Calls to it from \
+                         other external repositories haven't been analyzed and won't be updated."
+                            .to_string(),
+                    ),
+                },
+            )]
+            .into_iter()
+            .collect()
+        }),
+        ..Default::default()
+    }))
 }
 
 pub(crate) fn signature_help(
@@ -196,22 +1082,579 @@ pub(crate) fn document_symbols(
 ) -> anyhow::Result<Option<lsp_types::DocumentSymbolResponse>> {
     let path = path_buf_from_url(&params.text_document.uri)?;
     let file_id = try_opt!(snapshot.document_manager.read().lookup_by_path_buf(&path));
-    let line_index = try_opt!(snapshot.analysis_snapshot.line_index(file_id)?);
+    let encoding = snapshot.config.position_encoding();
+    let line_index = try_opt!(snapshot.line_index(file_id)?);
     Ok(snapshot
         .analysis_snapshot
         .document_symbols(file_id)?
         .map(|symbols| {
             symbols
                 .into_iter()
-                .filter_map(|symbol| convert::lsp_document_symbol_from_native(symbol, line_index))
+                .filter_map(|symbol| {
+                    convert::lsp_document_symbol_from_native(symbol, line_index, encoding)
+                })
                 .collect::<Vec<_>>()
                 .into()
         }))
 }
 
+pub(crate) fn folding_range(
+    snapshot: &ServerSnapshot,
+    params: lsp_types::FoldingRangeParams,
+) -> anyhow::Result<Option<Vec<lsp_types::FoldingRange>>> {
+    let path = path_buf_from_url(&params.text_document.uri)?;
+    let file_id = try_opt!(snapshot.document_manager.read().lookup_by_path_buf(&path));
+    let line_index = try_opt!(snapshot.line_index(file_id)?);
+    let ranges = try_opt!(snapshot.analysis_snapshot.folding_ranges(file_id)?);
+
+    // Ranges are folded by whole lines, which every client supports, and the first line of each
+    // is kept visible.
+    Ok(Some(
+        ranges
+            .into_iter()
+            .filter_map(|range| {
+                let start_line = line_index.line_col(range.range.start()).line;
+                let end_line = line_index.line_col(range.range.end()).line;
+                (start_line < end_line).then(|| lsp_types::FoldingRange {
+                    start_line,
+                    start_character: None,
+                    end_line,
+                    end_character: None,
+                    kind: match range.kind {
+                        FoldingRangeKind::Code => None,
+                        FoldingRangeKind::Comment => Some(lsp_types::FoldingRangeKind::Comment),
+                    },
+                    collapsed_text: None,
+                })
+            })
+            .collect(),
+    ))
+}
+
+pub(crate) fn selection_range(
+    snapshot: &ServerSnapshot,
+    params: lsp_types::SelectionRangeParams,
+) -> anyhow::Result<Option<Vec<lsp_types::SelectionRange>>> {
+    let path = path_buf_from_url(&params.text_document.uri)?;
+    let file_id = try_opt!(snapshot.document_manager.read().lookup_by_path_buf(&path));
+    let line_index = try_opt!(snapshot.line_index(file_id)?);
+    let encoding = snapshot.config.position_encoding();
+    let offsets = params
+        .positions
+        .iter()
+        .map(|pos| {
+            convert::text_size_from_lsp_position_with_index(line_index, encoding, *pos)
+                .ok_or_else(|| anyhow!("position {:?} is out of bounds", pos))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let ranges = try_opt!(snapshot
+        .analysis_snapshot
+        .selection_ranges(file_id, offsets)?);
+
+    // Each range links to the range containing it as its parent, so the chain is built from the
+    // whole file inwards.
+    Ok(Some(
+        ranges
+            .into_iter()
+            .zip(&params.positions)
+            .map(|(ranges, pos)| {
+                let mut selection_range: Option<lsp_types::SelectionRange> = None;
+                for range in ranges.into_iter().rev() {
+                    let range =
+                        match convert::lsp_range_from_text_range(range, line_index, encoding) {
+                            Some(range) => range,
+                            None => continue,
+                        };
+                    selection_range = Some(lsp_types::SelectionRange {
+                        range,
+                        parent: selection_range.map(Box::new),
+                    });
+                }
+                selection_range.unwrap_or(lsp_types::SelectionRange {
+                    range: lsp_types::Range {
+                        start: *pos,
+                        end: *pos,
+                    },
+                    parent: None,
+                })
+            })
+            .collect(),
+    ))
+}
+
+pub(crate) fn semantic_tokens_full(
+    snapshot: &ServerSnapshot,
+    params: lsp_types::SemanticTokensParams,
+) -> anyhow::Result<Option<lsp_types::SemanticTokensResult>> {
+    let path = path_buf_from_url(&params.text_document.uri)?;
+    let file_id = try_opt!(snapshot.document_manager.read().lookup_by_path_buf(&path));
+    let line_index = try_opt!(snapshot.line_index(file_id)?);
+    let encoding = snapshot.config.position_encoding();
+    let tokens = try_opt!(snapshot.analysis_snapshot.semantic_tokens(file_id)?);
+
+    // Each token is encoded relative to the start of the previous one. Strings that span several
+    // lines are skipped, since not every client supports multiline tokens.
+    let mut data = Vec::new();
+    let (mut prev_line, mut prev_start) = (0, 0);
+    for token in tokens {
+        let range = match convert::lsp_range_from_text_range(token.range, line_index, encoding) {
+            Some(range) if range.start.line == range.end.line => range,
+            _ => continue,
+        };
+        let modifier = match token.kind {
+            StringKind::Label => "label",
+            StringKind::FilePath => "path",
+            StringKind::GlobPattern => "glob",
+            StringKind::FormatString => "format",
+            StringKind::Visibility => "visibility",
+            StringKind::Plain => continue,
+        };
+        let modifier_index = match STRING_TOKEN_MODIFIERS
+            .iter()
+            .position(|name| *name == modifier)
+        {
+            Some(index) => index,
+            None => continue,
+        };
+        let delta_line = range.start.line - prev_line;
+        data.push(lsp_types::SemanticToken {
+            delta_line,
+            delta_start: if delta_line == 0 {
+                range.start.character - prev_start
+            } else {
+                range.start.character
+            },
+            length: range.end.character - range.start.character,
+            token_type: 0,
+            token_modifiers_bitset: 1 << modifier_index,
+        });
+        prev_line = range.start.line;
+        prev_start = range.start.character;
+    }
+    Ok(Some(lsp_types::SemanticTokensResult::Tokens(
+        lsp_types::SemanticTokens {
+            result_id: None,
+            data,
+        },
+    )))
+}
+
+pub(crate) fn prepare_call_hierarchy(
+    snapshot: &ServerSnapshot,
+    params: lsp_types::CallHierarchyPrepareParams,
+) -> anyhow::Result<Option<Vec<lsp_types::CallHierarchyItem>>> {
+    let path = path_buf_from_url(&params.text_document_position_params.text_document.uri)?;
+    let file_id = try_opt!(snapshot.document_manager.read().lookup_by_path_buf(&path));
+    let pos = try_opt!(convert::text_size_from_lsp_position(
+        snapshot,
+        file_id,
+        params.text_document_position_params.position,
+    )?);
+    let items = try_opt!(snapshot
+        .analysis_snapshot
+        .prepare_call_hierarchy(FilePosition { file_id, pos })?);
+    let mut lsp_items = Vec::with_capacity(items.len());
+    for item in items {
+        lsp_items.extend(lsp_call_hierarchy_item(snapshot, item)?);
+    }
+    Ok(Some(lsp_items))
+}
+
+pub(crate) fn incoming_calls(
+    snapshot: &ServerSnapshot,
+    params: lsp_types::CallHierarchyIncomingCallsParams,
+) -> anyhow::Result<Option<Vec<lsp_types::CallHierarchyIncomingCall>>> {
+    let item = try_opt!(call_hierarchy_item_range(snapshot, &params.item)?);
+
+    // Callers in the files that load the item are reported as each file is searched.
+    let mut results =
+        PartialResults::new(snapshot, params.partial_result_params.partial_result_token);
+    let mut err = None;
+    try_opt!(snapshot
+        .analysis_snapshot
+        .incoming_calls_in_batches(item, &mut |calls| {
+            match lsp_incoming_calls(snapshot, calls) {
+                // `Ok` is `anyhow::Ok` in this module, which can't be matched on.
+                Result::Ok(calls) => results.report(calls),
+                Err(e) => {
+                    err.get_or_insert(e);
+                }
+            }
+        })?);
+    if let Some(err) = err {
+        return Err(err);
+    }
+    Ok(Some(results.finish()))
+}
+
+fn lsp_incoming_calls(
+    snapshot: &ServerSnapshot,
+    calls: Vec<IncomingCall>,
+) -> anyhow::Result<Vec<lsp_types::CallHierarchyIncomingCall>> {
+    let encoding = snapshot.config.position_encoding();
+    let mut lsp_calls = Vec::with_capacity(calls.len());
+    for call in calls {
+        let line_index = match snapshot.line_index(call.from.file_id)? {
+            Some(line_index) => line_index,
+            None => continue,
+        };
+        let from_ranges = call
+            .from_ranges
+            .into_iter()
+            .filter_map(|range| convert::lsp_range_from_text_range(range, line_index, encoding))
+            .collect();
+        if let Some(from) = lsp_call_hierarchy_item(snapshot, call.from)? {
+            lsp_calls.push(lsp_types::CallHierarchyIncomingCall { from, from_ranges });
+        }
+    }
+    Ok(lsp_calls)
+}
+
+pub(crate) fn outgoing_calls(
+    snapshot: &ServerSnapshot,
+    params: lsp_types::CallHierarchyOutgoingCallsParams,
+) -> anyhow::Result<Option<Vec<lsp_types::CallHierarchyOutgoingCall>>> {
+    let item = try_opt!(call_hierarchy_item_range(snapshot, &params.item)?);
+    let line_index = try_opt!(snapshot.line_index(item.file_id)?);
+    let calls = try_opt!(snapshot.analysis_snapshot.outgoing_calls(item)?);
+    let encoding = snapshot.config.position_encoding();
+
+    // Outgoing calls are all in the item's own file, so they're found at once, but they're still
+    // reported as partial results if the client asked for them.
+    let mut results =
+        PartialResults::new(snapshot, params.partial_result_params.partial_result_token);
+    for call in calls {
+        let from_ranges = call
+            .from_ranges
+            .into_iter()
+            .filter_map(|range| convert::lsp_range_from_text_range(range, line_index, encoding))
+            .collect();
+        if let Some(to) = lsp_call_hierarchy_item(snapshot, call.to)? {
+            results.report([lsp_types::CallHierarchyOutgoingCall { to, from_ranges }]);
+        }
+    }
+    Ok(Some(results.finish()))
+}
+
+/// Finds the selection range of a call hierarchy item sent back by the client, which identifies
+/// the item to the analysis.
+fn call_hierarchy_item_range(
+    snapshot: &ServerSnapshot,
+    item: &lsp_types::CallHierarchyItem,
+) -> anyhow::Result<Option<FileRange>> {
+    let path = path_buf_from_url(&item.uri)?;
+    let file_id = try_opt!(snapshot.document_manager.read().lookup_by_path_buf(&path));
+    let start = try_opt!(convert::text_size_from_lsp_position(
+        snapshot,
+        file_id,
+        item.selection_range.start,
+    )?);
+    let end = try_opt!(convert::text_size_from_lsp_position(
+        snapshot,
+        file_id,
+        item.selection_range.end,
+    )?);
+    Ok(Some(FileRange {
+        file_id,
+        range: TextRange::new(start, end.max(start)),
+    }))
+}
+
+fn lsp_call_hierarchy_item(
+    snapshot: &ServerSnapshot,
+    item: CallHierarchyItem,
+) -> anyhow::Result<Option<lsp_types::CallHierarchyItem>> {
+    let encoding = snapshot.config.position_encoding();
+    let line_index = try_opt!(snapshot.line_index(item.file_id)?);
+    let path = snapshot
+        .document_manager
+        .read()
+        .lookup_by_file_id(item.file_id);
+    // Calls from the top level of a file are shown as calls from the file itself.
+    let name = if item.name.is_empty() {
+        path.file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default()
+    } else {
+        item.name
+    };
+    Ok(Some(lsp_types::CallHierarchyItem {
+        name,
+        kind: convert::lsp_symbol_kind_from_native(item.kind),
+        tags: None,
+        detail: item.detail,
+        uri: try_opt!(lsp_types::Url::from_file_path(&path).ok()),
+        range: try_opt!(convert::lsp_range_from_text_range(
+            item.range, line_index, encoding
+        )),
+        selection_range: try_opt!(convert::lsp_range_from_text_range(
+            item.selection_range,
+            line_index,
+            encoding
+        )),
+        data: None,
+    }))
+}
+
+pub(crate) fn will_rename_files(
+    snapshot: &ServerSnapshot,
+    params: lsp_types::RenameFilesParams,
+) -> anyhow::Result<Option<lsp_types::WorkspaceEdit>> {
+    let renames = params
+        .files
+        .iter()
+        .filter_map(|rename| {
+            let old_uri = lsp_types::Url::parse(&rename.old_uri).ok()?;
+            let new_uri = lsp_types::Url::parse(&rename.new_uri).ok()?;
+            Some((
+                path_buf_from_url(&old_uri).ok()?,
+                path_buf_from_url(&new_uri).ok()?,
+            ))
+        })
+        .collect::<Vec<_>>();
+    if renames.is_empty() {
+        return Ok(None);
+    }
+
+    let mut changes = HashMap::new();
+    for (file_id, edits) in file_renames::label_edits(snapshot, &Renames(renames))? {
+        if snapshot.is_read_only(file_id) {
+            continue;
+        }
+        let path = snapshot.document_manager.read().lookup_by_file_id(file_id);
+        let uri = match lsp_types::Url::from_file_path(path).ok() {
+            Some(uri) => uri,
+            None => continue,
+        };
+        if let Some(edits) = lsp_text_edits(snapshot, file_id, edits)? {
+            changes.insert(uri, edits);
+        }
+    }
+    if changes.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(lsp_types::WorkspaceEdit {
+        changes: Some(changes),
+        ..Default::default()
+    }))
+}
+
+pub(crate) fn workspace_symbol(
+    snapshot: &ServerSnapshot,
+    params: lsp_types::WorkspaceSymbolParams,
+) -> anyhow::Result<Option<lsp_types::WorkspaceSymbolResponse>> {
+    // Matches are ranked against each other, so they're only reported once every file has been
+    // searched, or once the time budget runs out.
+    let mut results =
+        PartialResults::new(snapshot, params.partial_result_params.partial_result_token);
+    let deadline = if results.is_streaming() {
+        None
+    } else {
+        Some(Instant::now() + SEARCH_TIME_BUDGET)
+    };
+    let (symbols, is_complete) = snapshot.workspace_symbols.search(&params.query, deadline);
+    results.report(symbols);
+    if !is_complete {
+        show_incomplete_search_message(snapshot, "symbols");
+    }
+    Ok(Some(lsp_types::WorkspaceSymbolResponse::Flat(
+        results.finish(),
+    )))
+}
+
 fn to_markup_doc(doc: String) -> lsp_types::Documentation {
     lsp_types::Documentation::MarkupContent(lsp_types::MarkupContent {
         kind: lsp_types::MarkupKind::Markdown,
         value: doc,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{path::PathBuf, sync::Arc};
+
+    use crossbeam_channel::Receiver;
+    use lsp_types::{
+        CallHierarchyIncomingCallsParams, CallHierarchyItem, CallHierarchyOutgoingCallsParams,
+        CallHierarchyPrepareParams, NumberOrString, PartialResultParams, Position,
+        TextDocumentIdentifier, TextDocumentPositionParams, Url, WorkspaceSymbolParams,
+        WorkspaceSymbolResponse,
+    };
+    use parking_lot::RwLock;
+    use starpls_common::Dialect;
+    use starpls_ide::Analysis;
+
+    use crate::{
+        convert::PositionEncoding,
+        crash_report::CrashReporter,
+        document::{DocumentManager, PathInterner},
+        load_index::LoadIndex,
+        project_config::ProjectConfigLoader,
+        server::ServerSnapshot,
+        workspace_symbols::WorkspaceSymbolIndex,
+    };
+
+    const DEFS: &str = "def helper():\n    pass\n\ndef my_macro():\n    helper()\n";
+
+    const USER: &str = "load(\"defs.star\", \"helper\")\n\ndef wrapper():\n    helper()\n";
+
+    const FILES: [(&str, &str); 2] = [("defs.star", DEFS), ("user.star", USER)];
+
+    /// Creates a snapshot of a server whose workspace at `/ws` contains the given files, along
+    /// with the receiving end of the messages that the server sends to the client.
+    fn snapshot(files: &[(&str, &str)]) -> (ServerSnapshot, Receiver<lsp_server::Message>) {
+        let workspace = PathBuf::from("/ws");
+        let interner = Arc::new(PathInterner::default());
+        let workspace_symbols = Arc::new(WorkspaceSymbolIndex::new(
+            workspace.clone(),
+            PositionEncoding::Utf16,
+        ));
+        for (path, contents) in files {
+            let path = workspace.join(path);
+            interner.intern_path(path.clone());
+            workspace_symbols.file_changed(path, contents.to_string());
+        }
+        let (sender, receiver) = crossbeam_channel::unbounded();
+        let snapshot = ServerSnapshot {
+            config: Default::default(),
+            analysis_snapshot: Analysis::from_files(files, Dialect::Standard).snapshot(),
+            document_manager: Arc::new(RwLock::new(DocumentManager::new(
+                interner,
+                Arc::new(ProjectConfigLoader::new(workspace.clone())),
+            ))),
+            crash_reporter: Arc::new(CrashReporter::new(false)),
+            workspace: workspace.clone(),
+            output_base: PathBuf::from("/output_base"),
+            inlay_hints: Default::default(),
+            workspace_symbols,
+            load_index: Arc::new(LoadIndex::new(workspace)),
+            check_sidecar: None,
+            buildifier: None,
+            sender,
+        };
+        (snapshot, receiver)
+    }
+
+    fn partial_result_params() -> PartialResultParams {
+        PartialResultParams {
+            partial_result_token: Some(NumberOrString::String("results".to_string())),
+        }
+    }
+
+    /// Returns the values of the `$/progress` notifications sent for partial results.
+    fn partial_results(receiver: &Receiver<lsp_server::Message>) -> Vec<serde_json::Value> {
+        receiver
+            .try_iter()
+            .filter_map(|message| match message {
+                lsp_server::Message::Notification(not) if not.method == "$/progress" => {
+                    assert_eq!(not.params["token"], "results");
+                    Some(not.params["value"].clone())
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn names(batch: &serde_json::Value, key: &str) -> Vec<String> {
+        batch
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|value| value[key]["name"].as_str().unwrap().to_string())
+            .collect()
+    }
+
+    fn call_hierarchy_item(
+        snapshot: &ServerSnapshot,
+        file: &str,
+        needle: &str,
+    ) -> CallHierarchyItem {
+        let text = FILES.iter().find(|(path, _)| *path == file).unwrap().1;
+        let offset = text.find(needle).unwrap();
+        let line = text[..offset].matches('\n').count();
+        let character = offset - text[..offset].rfind('\n').map_or(0, |index| index + 1);
+        super::prepare_call_hierarchy(
+            snapshot,
+            CallHierarchyPrepareParams {
+                text_document_position_params: TextDocumentPositionParams {
+                    text_document: TextDocumentIdentifier {
+                        uri: Url::from_file_path(PathBuf::from("/ws").join(file)).unwrap(),
+                    },
+                    position: Position::new(line as u32, character as u32),
+                },
+                work_done_progress_params: Default::default(),
+            },
+        )
+        .unwrap()
+        .unwrap()
+        .remove(0)
+    }
+
+    #[test]
+    fn test_workspace_symbol_partial_results() {
+        let (snapshot, receiver) = snapshot(&FILES);
+        let response = super::workspace_symbol(
+            &snapshot,
+            WorkspaceSymbolParams {
+                query: "my_mac".to_string(),
+                work_done_progress_params: Default::default(),
+                partial_result_params: partial_result_params(),
+            },
+        )
+        .unwrap()
+        .unwrap();
+        assert!(matches!(response, WorkspaceSymbolResponse::Flat(symbols) if symbols.is_empty()));
+
+        let batches = partial_results(&receiver);
+        assert_eq!(batches.len(), 1);
+        assert_eq!(batches[0][0]["name"], "my_macro");
+    }
+
+    #[test]
+    fn test_incoming_calls_partial_results() {
+        let (snapshot, receiver) = snapshot(&FILES);
+        let item = call_hierarchy_item(&snapshot, "defs.star", "helper():");
+        let calls = super::incoming_calls(
+            &snapshot,
+            CallHierarchyIncomingCallsParams {
+                item,
+                work_done_progress_params: Default::default(),
+                partial_result_params: partial_result_params(),
+            },
+        )
+        .unwrap()
+        .unwrap();
+        assert!(calls.is_empty());
+
+        // Calls are reported one file at a time, starting with the file defining the callee.
+        let batches = partial_results(&receiver);
+        assert_eq!(
+            batches
+                .iter()
+                .map(|batch| names(batch, "from"))
+                .collect::<Vec<_>>(),
+            vec![vec!["my_macro".to_string()], vec!["wrapper".to_string()]]
+        );
+    }
+
+    #[test]
+    fn test_outgoing_calls_partial_results() {
+        let (snapshot, receiver) = snapshot(&FILES);
+        let item = call_hierarchy_item(&snapshot, "user.star", "wrapper");
+        let calls = super::outgoing_calls(
+            &snapshot,
+            CallHierarchyOutgoingCallsParams {
+                item,
+                work_done_progress_params: Default::default(),
+                partial_result_params: partial_result_params(),
+            },
+        )
+        .unwrap()
+        .unwrap();
+        assert!(calls.is_empty());
+
+        let batches = partial_results(&receiver);
+        assert_eq!(batches.len(), 1);
+        assert_eq!(names(&batches[0], "to"), vec!["helper".to_string()]);
+    }
+}
@@ -0,0 +1,120 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::anyhow;
+use starpls_bazel::APIContext;
+use starpls_common::read_source;
+use starpls_ide::{Analysis, Change};
+
+use crate::{
+    check::create_analysis,
+    document::{self, PathInterner},
+};
+
+/// Lists the `BUILD` files in the current workspace that transitively load the given file,
+/// i.e. every package that may be affected by editing it.
+pub(crate) fn run_impact(file: String, output_base: Option<String>) -> anyhow::Result<()> {
+    let (mut analysis, interner, workspace) = create_analysis(output_base)?;
+    let workspace = workspace.canonicalize().unwrap_or(workspace);
+    let target = PathBuf::from(&file)
+        .canonicalize()
+        .map_err(|_| anyhow!("Could not resolve the path {:?} as a Starlark file.", file))?;
+
+    // Loaders can only be found among files that the analysis knows about, so add every
+    // Starlark file in the workspace up front.
+    add_workspace_files(&mut analysis, &interner, &workspace)?;
+    if interner.lookup_by_path_buf(&target).is_none() {
+        let (dialect, api_context) = document::dialect_and_api_context_for_path(&target)
+            .ok_or_else(|| anyhow!("Could not resolve the path {:?} as a Starlark file.", file))?;
+        let mut change = Change::default();
+        let file_id = interner.intern_path(target.clone());
+        change.create_file(file_id, dialect, api_context, read_source(&target)?.text);
+        analysis.apply_change(change);
+    }
+
+    let target_file_id = interner
+        .lookup_by_path_buf(&target)
+        .ok_or_else(|| anyhow!("Could not resolve the path {:?} as a Starlark file.", file))?;
+    let graph = analysis
+        .snapshot()
+        .load_graph(target_file_id)?
+        .unwrap_or_default();
+
+    let mut build_files = graph
+        .loaders
+        .into_iter()
+        .map(|file_id| interner.lookup_by_file_id(file_id))
+        .filter(|path| {
+            matches!(
+                document::dialect_and_api_context_for_path(path),
+                Some((_, Some(APIContext::Build)))
+            )
+        })
+        .map(|path| {
+            path.strip_prefix(&workspace)
+                .map(Path::to_path_buf)
+                .unwrap_or(path)
+        })
+        .collect::<Vec<_>>();
+    build_files.sort();
+
+    for path in build_files {
+        println!("{}", path.display());
+    }
+
+    Ok(())
+}
+
+/// Adds every Starlark file in the workspace that hasn't already been interned to the analysis.
+pub(crate) fn add_workspace_files(
+    analysis: &mut Analysis,
+    interner: &PathInterner,
+    workspace: &Path,
+) -> anyhow::Result<()> {
+    let mut paths = Vec::new();
+    collect_starlark_files(workspace, &mut paths)?;
+
+    let mut change = Change::default();
+    for path in paths {
+        if interner.lookup_by_path_buf(&path).is_some() {
+            continue;
+        }
+        let (dialect, api_context) = match document::dialect_and_api_context_for_path(&path) {
+            Some(res) => res,
+            None => continue,
+        };
+        let contents = match read_source(&path) {
+            Ok(decoded) => decoded.text,
+            Err(_) => continue,
+        };
+        let file_id = interner.intern_path(path);
+        change.create_file(file_id, dialect, api_context, contents);
+    }
+    analysis.apply_change(change);
+    Ok(())
+}
+
+pub(crate) fn collect_starlark_files(dir: &Path, paths: &mut Vec<PathBuf>) -> anyhow::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            // Skip hidden directories as well as Bazel's convenience symlinks.
+            let skip = entry
+                .file_name()
+                .to_str()
+                .map(|name| name.starts_with('.') || name.starts_with("bazel-"))
+                .unwrap_or(true);
+            if !skip {
+                collect_starlark_files(&path, paths)?;
+            }
+        } else if file_type.is_file() && document::dialect_and_api_context_for_path(&path).is_some()
+        {
+            paths.push(path);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,56 @@
+use std::{fs, path::PathBuf};
+
+use anyhow::{anyhow, bail};
+use starpls_bazel::starter_build::{starter_build_file, Language};
+
+/// The names that Bazel recognizes for `BUILD` files, in order of precedence.
+const BUILD_FILE_NAMES: &[&str] = &["BUILD.bazel", "BUILD"];
+
+/// Writes a starter `BUILD.bazel` file to a directory, declaring a library for the sources in the
+/// given language and a test for each test source. Directories that already have a `BUILD` file
+/// are left alone.
+pub(crate) fn run_init_build(dir: String, lang: String, dry_run: bool) -> anyhow::Result<()> {
+    let language =
+        Language::from_name(&lang).ok_or_else(|| anyhow!("Unknown language {:?}.", lang))?;
+    let path = PathBuf::from(&dir);
+    if !path.is_dir() {
+        bail!("{:?} is not a directory.", dir);
+    }
+    if let Some(existing) = BUILD_FILE_NAMES
+        .iter()
+        .map(|name| path.join(name))
+        .find(|path| path.is_file())
+    {
+        bail!("{} already exists.", existing.display());
+    }
+
+    let mut file_names = Vec::new();
+    for entry in fs::read_dir(&path)? {
+        let entry = entry?;
+        if entry.file_type()?.is_file() {
+            if let Some(file_name) = entry.file_name().to_str() {
+                file_names.push(file_name.to_string());
+            }
+        }
+    }
+    let package_name = path
+        .canonicalize()
+        .ok()
+        .and_then(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .map(|name| name.to_string())
+        })
+        .unwrap_or_else(|| "lib".to_string());
+    let contents = starter_build_file(language, &package_name, &file_names)
+        .ok_or_else(|| anyhow!("Found no {} sources in {:?}.", lang, dir))?;
+
+    if dry_run {
+        print!("{}", contents);
+    } else {
+        let build_file = path.join(BUILD_FILE_NAMES[0]);
+        fs::write(&build_file, contents)?;
+        println!("Wrote {}", build_file.display());
+    }
+    Ok(())
+}
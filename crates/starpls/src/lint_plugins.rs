@@ -0,0 +1,14 @@
+//! The custom lint rules compiled into the server when it's built with the `lint-plugins`
+//! feature.
+//!
+//! To add a rule, implement [`LintRule`] in a crate that depends on `starpls_ide`, add that crate
+//! as a dependency of this one, and return the rule from [`lint_rules`]. Both the language server
+//! and `starpls check` run the rules returned here.
+
+use std::sync::Arc;
+
+use starpls_ide::LintRule;
+
+pub(crate) fn lint_rules() -> Vec<Arc<dyn LintRule>> {
+    Vec::new()
+}
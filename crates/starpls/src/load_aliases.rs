@@ -0,0 +1,155 @@
+use std::path::Path;
+
+use serde::Serialize;
+use starpls_common::FileId;
+use starpls_syntax::edit::Edit;
+
+use crate::{
+    check::create_analysis, impact::add_workspace_files, package_move::write_edits,
+    symbols::OutputFormat,
+};
+
+#[derive(Serialize)]
+struct DriftOutput {
+    /// The file that exports the symbol, relative to the workspace.
+    module: String,
+    name: String,
+    /// The alias that `--fix` standardizes on.
+    preferred: String,
+    aliases: Vec<AliasOutput>,
+}
+
+#[derive(Serialize)]
+struct AliasOutput {
+    alias: String,
+    sites: Vec<SiteOutput>,
+}
+
+/// A `load` item, at a one-based line within a file.
+#[derive(Serialize)]
+struct SiteOutput {
+    path: String,
+    line: u32,
+}
+
+/// Reports the symbols that the Starlark files in the current workspace load under different
+/// local names, e.g. `"my_rule"` in one file and `rule = "my_rule"` in another. With `fix`, the
+/// `load` items and the references to them are rewritten to use each symbol's most common name,
+/// except in files where that name is already taken.
+pub(crate) fn run_load_aliases(
+    fix: bool,
+    format: OutputFormat,
+    output_base: Option<String>,
+) -> anyhow::Result<()> {
+    let (mut analysis, interner, workspace) = create_analysis(output_base)?;
+    let workspace = workspace.canonicalize().unwrap_or(workspace);
+    add_workspace_files(&mut analysis, &interner, &workspace)?;
+
+    let file_ids = interner
+        .entries()
+        .into_iter()
+        .filter(|(_, path)| path.starts_with(&workspace))
+        .map(|(file_id, _)| file_id)
+        .collect::<Vec<_>>();
+    let snap = analysis.snapshot();
+    let drifts = snap.load_alias_drift(&file_ids)?;
+    let relative_path = |path: &Path| {
+        path.strip_prefix(&workspace)
+            .unwrap_or(path)
+            .to_string_lossy()
+            .to_string()
+    };
+
+    let mut outputs = Vec::new();
+    for drift in &drifts {
+        let mut aliases = Vec::new();
+        for (alias, _) in &drift.aliases {
+            let mut sites = Vec::new();
+            for site in drift.sites.iter().filter(|site| site.alias == *alias) {
+                let line = match snap.line_index(site.file_id)? {
+                    Some(line_index) => line_index.line_col(site.range.start()).line + 1,
+                    None => continue,
+                };
+                sites.push(SiteOutput {
+                    path: relative_path(&interner.lookup_by_file_id(site.file_id)),
+                    line,
+                });
+            }
+            sites.sort_by(|a, b| (&a.path, a.line).cmp(&(&b.path, b.line)));
+            aliases.push(AliasOutput {
+                alias: alias.clone(),
+                sites,
+            });
+        }
+        outputs.push(DriftOutput {
+            module: relative_path(&interner.lookup_by_file_id(drift.module)),
+            name: drift.name.clone(),
+            preferred: drift.preferred_alias().to_string(),
+            aliases,
+        });
+    }
+
+    match format {
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&outputs)?),
+        OutputFormat::Text => {
+            if outputs.is_empty() {
+                println!("Every symbol is loaded under a single name.");
+            }
+            for output in &outputs {
+                println!("{}%{}:", output.module, output.name);
+                for AliasOutput { alias, sites } in &output.aliases {
+                    let marker = if *alias == output.preferred {
+                        " (most common)"
+                    } else {
+                        ""
+                    };
+                    println!("  {}: {} loads{}", alias, sites.len(), marker);
+                    for site in sites {
+                        println!("    {}:{}", site.path, site.line);
+                    }
+                }
+            }
+        }
+    }
+
+    if !fix {
+        return Ok(());
+    }
+
+    // Standardizing each symbol only touches its own `load` items and references, so the edits
+    // for every symbol in a file can be applied together.
+    let mut changes: Vec<(FileId, Vec<Edit>)> = Vec::new();
+    let mut conflicts = 0;
+    for drift in &drifts {
+        let standardization = snap.standardize_load_alias(drift, drift.preferred_alias())?;
+        conflicts += standardization.conflicts.len();
+        for site in &standardization.conflicts {
+            eprintln!(
+                "load_aliases: skipping {}, which already uses the name {:?}",
+                relative_path(&interner.lookup_by_file_id(site.file_id)),
+                drift.preferred_alias()
+            );
+        }
+        for (file_id, edits) in standardization.edits {
+            let edits = edits.into_iter().map(|edit| Edit {
+                range: edit.range,
+                new_text: edit.new_text,
+            });
+            match changes.iter_mut().find(|(id, _)| *id == file_id) {
+                Some((_, file_edits)) => file_edits.extend(edits),
+                None => changes.push((file_id, edits.collect())),
+            }
+        }
+    }
+
+    for (file_id, edits) in &mut changes {
+        edits.sort_by_key(|edit| edit.range.start());
+        write_edits(&interner.lookup_by_file_id(*file_id), edits)?;
+    }
+    println!(
+        "Rewrote {} files, skipped {} loads with conflicting names",
+        changes.len(),
+        conflicts
+    );
+    Ok(())
+}
@@ -0,0 +1,179 @@
+//! An index of the `load` statements of every Starlark file in the workspace, which finds the files
+//! that load a given file even if they aren't open and aren't loaded by any open file.
+//!
+//! Like the workspace symbol index, the workspace is indexed from disk in the background when the
+//! server starts. Afterwards, files are re-indexed from their contents in the editor whenever they
+//! change. Changed files are only re-indexed when the next lookup comes in, so that typing doesn't
+//! pay for it.
+
+use std::{
+    mem,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use parking_lot::RwLock;
+use rustc_hash::{FxHashMap, FxHashSet};
+use starpls_ide::index_load_paths;
+
+use crate::impact;
+
+/// Resolves a `load` in the file at the given path, e.g. `//foo:defs.bzl`, to the path of the file
+/// that it loads.
+pub(crate) type ResolveLoad<'a> = &'a dyn Fn(&Path, &str) -> Option<PathBuf>;
+
+#[derive(Default)]
+struct Edges {
+    /// The files loaded by each indexed file.
+    loadees: FxHashMap<PathBuf, Vec<PathBuf>>,
+    /// The indexed files that load each file.
+    loaders: FxHashMap<PathBuf, FxHashSet<PathBuf>>,
+}
+
+impl Edges {
+    fn set_loadees(&mut self, path: PathBuf, loadees: Vec<PathBuf>) {
+        for loadee in self.loadees.remove(&path).unwrap_or_default() {
+            if let Some(loaders) = self.loaders.get_mut(&loadee) {
+                loaders.remove(&path);
+            }
+        }
+        for loadee in &loadees {
+            self.loaders
+                .entry(loadee.clone())
+                .or_default()
+                .insert(path.clone());
+        }
+        self.loadees.insert(path, loadees);
+    }
+}
+
+pub(crate) struct LoadIndex {
+    workspace: PathBuf,
+    edges: RwLock<Edges>,
+    /// The contents of files that changed since they were last indexed.
+    pending: RwLock<FxHashMap<PathBuf, String>>,
+    /// Whether the whole workspace has been indexed from disk. Until then, lookups fail, since
+    /// they would silently miss loaders.
+    is_indexed: AtomicBool,
+}
+
+impl LoadIndex {
+    pub(crate) fn new(workspace: PathBuf) -> Self {
+        Self {
+            workspace,
+            edges: Default::default(),
+            pending: Default::default(),
+            is_indexed: AtomicBool::new(false),
+        }
+    }
+
+    /// Indexes every Starlark file in the workspace from disk. Files that changed in the editor
+    /// in the meantime are re-indexed from their newer contents by the next lookup.
+    pub(crate) fn index_workspace(&self, resolve: ResolveLoad) {
+        let mut paths = Vec::new();
+        if let Err(err) = impact::collect_starlark_files(&self.workspace, &mut paths) {
+            eprintln!("server: failed to index workspace loads: {}", err);
+        }
+        for path in paths {
+            let contents = match starpls_common::read_source(&path) {
+                Ok(decoded) => decoded.text,
+                Err(_) => continue,
+            };
+            let loadees = resolve_loads(&path, &contents, resolve);
+            self.edges.write().set_loadees(path, loadees);
+        }
+        self.is_indexed.store(true, Ordering::Release);
+        eprintln!(
+            "server: indexed loads in {} files",
+            self.edges.read().loadees.len()
+        );
+    }
+
+    /// Whether the whole workspace has been indexed, so that lookups find every loader.
+    pub(crate) fn is_indexed(&self) -> bool {
+        self.is_indexed.load(Ordering::Acquire)
+    }
+
+    /// Records that a file changed, so that it's re-indexed before the next lookup.
+    pub(crate) fn file_changed(&self, path: PathBuf, contents: String) {
+        self.pending.write().insert(path, contents);
+    }
+
+    /// Returns the files that directly load the file at the given path, or `None` if the
+    /// workspace hasn't been indexed yet.
+    pub(crate) fn loaders(&self, path: &Path, resolve: ResolveLoad) -> Option<Vec<PathBuf>> {
+        if !self.is_indexed() {
+            return None;
+        }
+        self.index_pending(resolve);
+        let mut loaders = self
+            .edges
+            .read()
+            .loaders
+            .get(path)
+            .map(|loaders| loaders.iter().cloned().collect::<Vec<_>>())
+            .unwrap_or_default();
+        loaders.sort();
+        Some(loaders)
+    }
+
+    fn index_pending(&self, resolve: ResolveLoad) {
+        let pending = mem::take(&mut *self.pending.write());
+        for (path, contents) in pending {
+            let loadees = resolve_loads(&path, &contents, resolve);
+            self.edges.write().set_loadees(path, loadees);
+        }
+    }
+}
+
+fn resolve_loads(path: &Path, contents: &str, resolve: ResolveLoad) -> Vec<PathBuf> {
+    index_load_paths(contents)
+        .iter()
+        .filter_map(|load| resolve(path, load))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use super::LoadIndex;
+
+    fn resolve(from: &Path, path: &str) -> Option<PathBuf> {
+        Some(from.parent()?.join(path))
+    }
+
+    #[test]
+    fn test_loaders_follow_edits() {
+        let index = LoadIndex::new(PathBuf::from("/nonexistent/ws"));
+        assert_eq!(index.loaders(Path::new("/ws/b.star"), &resolve), None);
+
+        index.index_workspace(&resolve);
+        index.file_changed(
+            PathBuf::from("/ws/a.star"),
+            "load(\"b.star\", \"b\")\n".to_string(),
+        );
+        index.file_changed(
+            PathBuf::from("/ws/c.star"),
+            "load(\"b.star\", \"b\")\nload(\"a.star\", \"a\")\n".to_string(),
+        );
+        assert_eq!(
+            index.loaders(Path::new("/ws/b.star"), &resolve),
+            Some(vec![
+                PathBuf::from("/ws/a.star"),
+                PathBuf::from("/ws/c.star")
+            ])
+        );
+
+        // Removing a `load` removes the file from the loaders.
+        index.file_changed(PathBuf::from("/ws/c.star"), "c = 1\n".to_string());
+        assert_eq!(
+            index.loaders(Path::new("/ws/b.star"), &resolve),
+            Some(vec![PathBuf::from("/ws/a.star")])
+        );
+        assert_eq!(
+            index.loaders(Path::new("/ws/a.star"), &resolve),
+            Some(vec![])
+        );
+    }
+}
@@ -1,44 +1,299 @@
+use bench::run_bench;
 use check::run_check;
 use clap::{Args, Parser, Subcommand};
+use convert::PositionEncoding;
+use crash_check::run_crash_check;
+use explain::run_explain;
+use features::Feature;
+use handlers::requests::STRING_TOKEN_MODIFIERS;
+use impact::run_impact;
+use init_build::run_init_build;
+use load_aliases::run_load_aliases;
 use lsp_server::Connection;
 use lsp_types::{
-    CompletionOptions, HoverProviderCapability, OneOf, ServerCapabilities, SignatureHelpOptions,
-    TextDocumentSyncCapability, TextDocumentSyncKind,
+    CallHierarchyServerCapability, CodeActionKind, CodeActionOptions, CodeActionProviderCapability,
+    CompletionOptions, DocumentOnTypeFormattingOptions, ExecuteCommandOptions, FileOperationFilter,
+    FileOperationPattern, FileOperationRegistrationOptions, FoldingRangeProviderCapability,
+    HoverProviderCapability, InitializeParams, OneOf, SelectionRangeProviderCapability,
+    SemanticTokenModifier, SemanticTokenType, SemanticTokensFullOptions, SemanticTokensLegend,
+    SemanticTokensOptions, SemanticTokensServerCapabilities, ServerCapabilities,
+    SignatureHelpOptions, TextDocumentSyncCapability, TextDocumentSyncKind,
+    WorkspaceFileOperationsServerCapabilities, WorkspaceServerCapabilities,
 };
+use package_move::run_move;
+use server::{FetchRepoMode, LabelStyleMode};
+use spec_test::run_spec_test;
+use stats::{run_load_hotspots, run_stats};
+use symbols::{run_attrs, run_symbols, OutputFormat};
+use targets::{run_query, run_targets};
+use usages::{run_usages, UsagesFormat};
 
+mod bench;
+mod buildifier;
 mod check;
 mod config;
 mod convert;
+mod crash_check;
+mod crash_report;
 mod debouncer;
 mod diagnostics;
 mod dispatcher;
 mod document;
+mod effective_config;
 mod event_loop;
+mod explain;
 mod extensions;
+mod features;
+mod file_renames;
 mod handlers;
+mod impact;
+mod init_build;
+#[cfg(feature = "lint-plugins")]
+mod lint_plugins;
+mod load_aliases;
+mod load_index;
+mod package_move;
+mod partial_results;
+mod project_config;
 mod server;
+mod sidecar;
+mod spec_test;
+mod stats;
+mod symbols;
+mod targets;
 mod task_pool;
+mod usages;
 mod utils;
+mod workspace_diagnostics;
+mod workspace_symbols;
 
 const COMPLETION_TRIGGER_CHARACTERS: &[char] = &['.', '"', '\'', '/', ':'];
 const SIGNATURE_HELP_TRIGGER_CHARACTERS: &[char] = &['(', ',', ')'];
 
 #[derive(Parser)]
 struct Cli {
+    /// Prints the documentation for a diagnostic code, e.g. `unresolved-name`, and exits. Pass
+    /// `all` to print the documentation for every code.
+    #[clap(long = "explain", value_name = "CODE")]
+    explain: Option<String>,
     #[command(subcommand)]
     command: Option<Commands>,
 }
 
 #[derive(Subcommand)]
 enum Commands {
-    Check {
-        /// Paths to typecheck.
-        paths: Vec<String>,
+    /// Prints the attribute schema of a rule.
+    Attrs {
+        /// Path to the Starlark file from which to resolve the rule.
+        path: String,
+        /// Name of the rule.
+        rule: String,
+        /// The output format.
+        #[clap(long = "format", value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+        /// Path to the Bazel output base.
+        #[clap(long = "output_base")]
+        output_base: Option<String>,
+    },
+    Bench(BenchArgs),
+    Check(CheckArgs),
+    /// Runs every analysis over the Starlark files in a directory, including hovers and
+    /// completions at sampled positions, and reports any that panic.
+    CrashCheck {
+        /// Path to the directory containing the files to check.
+        path: String,
+        /// The maximum number of positions per file to request hovers, completions, and other
+        /// position-based features at.
+        #[clap(long = "max_positions_per_file", default_value_t = 50)]
+        max_positions_per_file: usize,
+        /// Path to the Bazel output base.
+        #[clap(long = "output_base")]
+        output_base: Option<String>,
+    },
+    /// Lists the BUILD files that are affected by changes to the given file.
+    Impact {
+        /// Path to the Starlark file to analyze.
+        file: String,
+        /// Path to the Bazel output base.
+        #[clap(long = "output_base")]
+        output_base: Option<String>,
+    },
+    /// Generates a starter `BUILD.bazel` file for a directory of sources, with a library target
+    /// for the sources and a test target for each test source.
+    InitBuild {
+        /// Path to the directory containing the sources.
+        dir: String,
+        /// The language of the sources.
+        #[clap(long = "lang", value_parser = ["cc", "py", "java"])]
+        lang: String,
+        /// Print the generated file instead of writing it.
+        #[clap(long = "dry_run", default_value_t = false)]
+        dry_run: bool,
+    },
+    /// Reports the symbols that are loaded under different local names across the workspace, e.g.
+    /// `"my_rule"` in one file and `rule = "my_rule"` in another.
+    LoadAliases {
+        /// Rewrite the `load` items, along with the references to them, to use each symbol's most
+        /// common name.
+        #[clap(long = "fix", default_value_t = false)]
+        fix: bool,
+        /// The output format.
+        #[clap(long = "format", value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+        /// Path to the Bazel output base.
+        #[clap(long = "output_base")]
+        output_base: Option<String>,
+    },
+    /// Rewrites the labels across the workspace that refer to a package or its subpackages, e.g.
+    /// `load` paths, dependencies, and visibility entries, to reflect moving the package to
+    /// another directory. Comments and formatting are left as they are.
+    Move {
+        /// The package being moved, e.g. `//old/pkg`.
+        from: String,
+        /// The package's new location, e.g. `//new/pkg`.
+        to: String,
+        /// Report the labels that would be rewritten in each file without changing any files.
+        #[clap(long = "dry_run", default_value_t = false)]
+        dry_run: bool,
+        /// Path to the Bazel output base.
+        #[clap(long = "output_base")]
+        output_base: Option<String>,
+    },
+    /// Lists the targets in the workspace that match a query, without invoking Bazel. For example,
+    /// `kind(cc_library) and attr(copts, "-fno-exceptions")`.
+    Query {
+        /// The query to evaluate. Supports the `kind()`, `name()`, and `attr()` functions, combined
+        /// with `and`, `or`, and `except`.
+        query: String,
+        /// The output format.
+        #[clap(long = "format", value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
         /// Path to the Bazel output base.
         #[clap(long = "output_base")]
         output_base: Option<String>,
     },
     Server(ServerArgs),
+    /// Parses the test files of the Starlark spec's conformance suite, e.g. a checkout of
+    /// `starlark-go`'s `testdata` directory, and reports the test chunks that the parser gets
+    /// wrong. Chunks whose expected failures only happen at runtime aren't checked.
+    SpecTest {
+        /// Path to the directory containing the `.star` test files.
+        path: String,
+        /// The output format.
+        #[clap(long = "format", value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+    },
+    /// Reports statistics about the Starlark files in the workspace, e.g. the number of packages,
+    /// targets, and errors, along with how long each analysis phase took.
+    Stats {
+        /// The output format.
+        #[clap(long = "format", value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+        /// Report the `.bzl` files that many `BUILD` files load but that export rarely used
+        /// symbols instead, which are candidates for splitting to reduce loading-phase costs.
+        #[clap(
+            long = "load_hotspots",
+            alias = "load-hotspots",
+            default_value_t = false
+        )]
+        load_hotspots: bool,
+        /// The minimum number of `BUILD` files that must load a `.bzl` file, directly or through
+        /// other files, for `--load_hotspots` to report it.
+        #[clap(long = "min_loaders", default_value_t = 10)]
+        min_loaders: usize,
+        /// Path to the Bazel output base.
+        #[clap(long = "output_base")]
+        output_base: Option<String>,
+    },
+    /// Dumps the definitions in a file or package.
+    Symbols {
+        /// Path to a Starlark file, or to a package directory.
+        path: String,
+        /// The output format.
+        #[clap(long = "format", value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+        /// Path to the Bazel output base.
+        #[clap(long = "output_base")]
+        output_base: Option<String>,
+    },
+    /// Lists the targets in the workspace that are declared with the given rule or macro.
+    Targets {
+        /// Name of the rule or macro, e.g. `java_library`. If omitted, prints the number of
+        /// targets declared with each rule or macro instead.
+        kind: Option<String>,
+        /// Only list targets that set the given attribute. May be repeated.
+        #[clap(long = "with_attr")]
+        with_attrs: Vec<String>,
+        /// The output format.
+        #[clap(long = "format", value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+        /// Path to the Bazel output base.
+        #[clap(long = "output_base")]
+        output_base: Option<String>,
+    },
+    /// Reports the files in the workspace that use the given symbols from `.bzl` files, e.g. to plan
+    /// deprecations.
+    Usages {
+        /// Symbols to look for, in the form `//pkg:file.bzl%name`.
+        symbols: Vec<String>,
+        /// Path to a file listing additional symbols, one per line.
+        #[clap(long = "symbols_file")]
+        symbols_file: Option<String>,
+        /// The output format.
+        #[clap(long = "format", value_enum, default_value_t = UsagesFormat::Text)]
+        format: UsagesFormat,
+        /// Path to the Bazel output base.
+        #[clap(long = "output_base")]
+        output_base: Option<String>,
+    },
+}
+
+/// Benchmarks parsing, lowering, type inference, hover, and completion over the Starlark files in a
+/// directory, e.g. a checkout of a `rules_*` repository.
+#[derive(Args)]
+pub(crate) struct BenchArgs {
+    /// Path to the directory containing the files to benchmark. Defaults to the workspace root.
+    path: Option<String>,
+    /// The number of times to run each phase, each time with a fresh database.
+    #[clap(long = "iterations", default_value_t = 3)]
+    iterations: usize,
+    /// The maximum number of positions per file to request hovers and completions at.
+    #[clap(long = "max_positions_per_file", default_value_t = 10)]
+    max_positions_per_file: usize,
+    /// The output format. Reports in the JSON format can be used as baselines.
+    #[clap(long = "format", value_enum, default_value_t = OutputFormat::Text)]
+    format: OutputFormat,
+    /// Path to a report from a previous run, in the JSON format, to compare against.
+    #[clap(long = "baseline")]
+    baseline: Option<String>,
+    /// How much slower, in percent, a phase's median time may get compared to the baseline
+    /// before the benchmark fails.
+    #[clap(long = "threshold", default_value_t = 10.0)]
+    threshold: f64,
+    /// Path to the Bazel output base.
+    #[clap(long = "output_base")]
+    output_base: Option<String>,
+}
+
+#[derive(Args)]
+pub(crate) struct CheckArgs {
+    /// Paths to typecheck.
+    paths: Vec<String>,
+    /// Path to the Bazel output base.
+    #[clap(long = "output_base")]
+    output_base: Option<String>,
+    /// Path to a baseline file. Diagnostics recorded in the baseline are not reported.
+    #[clap(long = "baseline")]
+    baseline: Option<String>,
+    /// Record all current diagnostics to the baseline file instead of reporting them.
+    #[clap(long = "write_baseline", default_value_t = false)]
+    write_baseline: bool,
+    /// Keep running and re-check files whenever they change.
+    #[clap(long = "watch", default_value_t = false)]
+    watch: bool,
+    /// Also check files changed since the given git revision, along with the files that load them.
+    #[clap(long = "since")]
+    since: Option<String>,
 }
 
 #[derive(Args, Default)]
@@ -49,14 +304,176 @@ pub(crate) struct ServerArgs {
     /// Infer attributes on a rule implementation function's context parameter.
     #[clap(long = "experimental_infer_ctx_attributes", default_value_t = false)]
     experimental_infer_ctx_attributes: bool,
+    /// A comma-separated list of functions that merge structs, written as they're called, e.g.
+    /// `structs.merge`. Calls to them are inferred to return a struct with the fields of each
+    /// struct passed to them.
+    #[clap(long = "struct_merge_functions", value_delimiter = ',')]
+    struct_merge_functions: Vec<String>,
+    /// Report unknown words in docstrings, `doc` attributes, and comments.
+    #[clap(long = "experimental_enable_spellcheck", default_value_t = false)]
+    experimental_enable_spellcheck: bool,
+    /// Report comments starting with a tag, e.g. `# TODO: ...`, as hints.
+    #[clap(long = "experimental_enable_todos", default_value_t = false)]
+    experimental_enable_todos: bool,
+    /// Report diagnostics for files that aren't open, but that load an edited file, e.g. the
+    /// `BUILD` files that use an edited macro.
+    #[clap(
+        long = "experimental_enable_workspace_diagnostics",
+        default_value_t = false
+    )]
+    experimental_enable_workspace_diagnostics: bool,
+    /// A comma-separated list of tags to look for in comments. Defaults to TODO, FIXME, and
+    /// DEPRECATED.
+    #[clap(long = "todo_tags", value_delimiter = ',')]
+    todo_tags: Vec<String>,
+    /// Path to a file containing the header that `.bzl` files are required to begin with. Relative
+    /// paths are resolved against the workspace root.
+    #[clap(long = "required_header_file")]
+    required_header_file: Option<String>,
+    /// The style that labels in `BUILD` files are expected to be written in: `short` for relative
+    /// labels and the `//foo` shorthand wherever possible, or `full` for labels that always name
+    /// their package and target. Labels that don't match the style are reported as warnings.
+    #[clap(long = "label_style", value_enum)]
+    label_style: Option<LabelStyleMode>,
+    /// Path to a JSON file that replaces or augments the bundled builtin definitions, e.g. to add
+    /// attributes to native rules. May be repeated, in which case later files take precedence.
+    /// Relative paths are resolved against the workspace root.
+    #[clap(long = "builtin_overrides")]
+    builtin_overrides: Vec<String>,
+    /// Path to a directory of additional builtin packs, where each subdirectory is a pack
+    /// containing a `builtins.pb` file, a `rules.pb` file, or both. Relative paths are resolved
+    /// against the workspace root.
+    #[clap(long = "builtin_packs_dir")]
+    builtin_packs_dir: Option<String>,
+    /// The name of a builtin pack to disable, e.g. `rules_java`. May be repeated.
+    #[clap(long = "disabled_builtin_packs")]
+    disabled_builtin_packs: Vec<String>,
+    /// A program to run checks specific to the workspace, e.g. conventions for its `BUILD` files.
+    /// It's sent a summary of each file as JSON, and responds with diagnostics to report; see
+    /// `crates/starpls/src/sidecar.rs` for the protocol. Relative paths are resolved against the
+    /// workspace root.
+    #[clap(long = "check_sidecar")]
+    check_sidecar: Option<String>,
+    /// An argument to pass to the program given by `--check_sidecar`. May be repeated.
+    #[clap(long = "check_sidecar_arg", allow_hyphen_values = true)]
+    check_sidecar_args: Vec<String>,
+    /// A Buildifier binary to format documents with instead of the builtin formatter, e.g. to
+    /// match the output of the version that CI checks formatting with. Its `--lint=warn` warnings
+    /// are reported as diagnostics. Bare names are looked up on the `PATH`, while relative paths
+    /// are resolved against the workspace root.
+    #[clap(long = "buildifier")]
+    buildifier: Option<String>,
+    /// An argument to pass to the binary given by `--buildifier`, e.g.
+    /// `--warnings=-module-docstring`. May be repeated.
+    #[clap(long = "buildifier_arg", allow_hyphen_values = true)]
+    buildifier_args: Vec<String>,
+    /// Path to a Buildifier `tables.json` file that replaces Buildifier's default tables, as with
+    /// its `--tables` flag. When tables are given, the builtin formatter sorts the arguments of
+    /// calls in `BUILD` files and the string lists passed as sortable attributes, and the tables
+    /// decide which attributes hold labels. Relative paths are resolved against the workspace
+    /// root.
+    #[clap(long = "buildifier_tables")]
+    buildifier_tables: Option<String>,
+    /// Path to a Buildifier `tables.json` file that extends Buildifier's default tables, or the
+    /// ones given by `--buildifier_tables`, as with its `--add_tables` flag. Relative paths are
+    /// resolved against the workspace root.
+    #[clap(long = "buildifier_add_tables")]
+    buildifier_add_tables: Option<String>,
+    /// Path to a directory of cached registry metadata, laid out like the registry itself with
+    /// each module's metadata at `modules/<name>/metadata.json`, e.g. a local checkout of the
+    /// Bazel Central Registry. Used to offer updates for outdated `bazel_dep`s. Relative paths
+    /// are resolved against the workspace root.
+    #[clap(long = "registry_cache")]
+    registry_cache: Option<String>,
+    /// Path to the directory that the repository mappings of external repositories are cached in.
+    /// The cache is keyed by the definitions of the repositories and the workspace's dependency
+    /// resolution, so it can be shared by every workspace. Defaults to
+    /// `$XDG_CACHE_HOME/starpls/repos`, or `~/.cache/starpls/repos`.
+    #[clap(long = "repo_cache_dir")]
+    repo_cache_dir: Option<String>,
+    /// Don't cache the repository mappings of external repositories on disk.
+    #[clap(long = "disable_repo_cache", default_value_t = false)]
+    disable_repo_cache: bool,
+    /// How to obtain the sources of external repositories that aren't in the output base yet:
+    /// `bazel` queries the repository so that Bazel fetches it, `offline` only uses repositories
+    /// that are already on disk, and `fetch-on-demand` asks before running `bazel fetch`.
+    #[clap(long = "fetch_repo_mode", value_enum, default_value_t = FetchRepoMode::Bazel)]
+    fetch_repo_mode: FetchRepoMode,
+    /// Include an excerpt of the file that a failed request was made against in crash reports.
+    /// The contents of strings and comments in the excerpt are masked.
+    #[clap(long = "crash_report_include_source", default_value_t = false)]
+    crash_report_include_source: bool,
 }
 
 fn main() -> anyhow::Result<()> {
     let cli = Cli::parse();
+    if let Some(code) = cli.explain {
+        return run_explain(&code);
+    }
 
     match cli.command {
-        Some(Commands::Check { paths, output_base }) => run_check(paths, output_base),
+        Some(Commands::Attrs {
+            path,
+            rule,
+            format,
+            output_base,
+        }) => run_attrs(path, rule, format, output_base),
+        Some(Commands::Bench(args)) => run_bench(args),
+        Some(Commands::Check(args)) => run_check(args),
+        Some(Commands::CrashCheck {
+            path,
+            max_positions_per_file,
+            output_base,
+        }) => run_crash_check(path, max_positions_per_file, output_base),
+        Some(Commands::Impact { file, output_base }) => run_impact(file, output_base),
+        Some(Commands::InitBuild { dir, lang, dry_run }) => run_init_build(dir, lang, dry_run),
+        Some(Commands::LoadAliases {
+            fix,
+            format,
+            output_base,
+        }) => run_load_aliases(fix, format, output_base),
+        Some(Commands::Move {
+            from,
+            to,
+            dry_run,
+            output_base,
+        }) => run_move(from, to, dry_run, output_base),
+        Some(Commands::Query {
+            query,
+            format,
+            output_base,
+        }) => run_query(query, format, output_base),
         Some(Commands::Server(args)) => run_server(args),
+        Some(Commands::SpecTest { path, format }) => run_spec_test(path, format),
+        Some(Commands::Stats {
+            format,
+            load_hotspots,
+            min_loaders,
+            output_base,
+        }) => {
+            if load_hotspots {
+                run_load_hotspots(min_loaders, format, output_base)
+            } else {
+                run_stats(format, output_base)
+            }
+        }
+        Some(Commands::Symbols {
+            path,
+            format,
+            output_base,
+        }) => run_symbols(path, format, output_base),
+        Some(Commands::Targets {
+            kind,
+            with_attrs,
+            format,
+            output_base,
+        }) => run_targets(kind, with_attrs, format, output_base),
+        Some(Commands::Usages {
+            symbols,
+            symbols_file,
+            format,
+            output_base,
+        }) => run_usages(symbols, symbols_file, format, output_base),
         None => run_server(Default::default()),
     }
 }
@@ -67,16 +484,48 @@ fn run_server(args: ServerArgs) -> anyhow::Result<()> {
     // Create the transport over stdio.
     let (connection, io_threads) = Connection::stdio();
 
-    // Initialize the connection with server capabilities. For now, this consists
-    // only of `TextDocumentSyncKind.Full`.
+    // Initialize the connection with server capabilities. The position encoding depends on the
+    // encodings supported by the client, so we need to read its capabilities first.
+    let (initialize_id, initialize_params) = connection.initialize_start()?;
+    let initialize_params: InitializeParams = serde_json::from_value(initialize_params)?;
+    let position_encoding = PositionEncoding::negotiate(&initialize_params.capabilities);
+    eprintln!("server: using position encoding {:?}", position_encoding);
+
+    // Features that can be turned off at runtime are registered once the server has started
+    // instead, if the client supports registering them dynamically.
+    let is_static =
+        |feature: Feature| !feature.is_dynamically_registered(&initialize_params.capabilities);
     let server_capabilities = serde_json::to_value(&ServerCapabilities {
-        completion_provider: Some(CompletionOptions {
-            trigger_characters: Some(make_trigger_characters(COMPLETION_TRIGGER_CHARACTERS)),
+        call_hierarchy_provider: Some(CallHierarchyServerCapability::Simple(true)),
+        code_action_provider: is_static(Feature::CodeActions)
+            .then(|| CodeActionProviderCapability::Options(code_action_options())),
+        completion_provider: is_static(Feature::Completion).then(completion_options),
+        definition_provider: Some(OneOf::Left(true)),
+        document_formatting_provider: Some(OneOf::Left(true)),
+        document_on_type_formatting_provider: Some(DocumentOnTypeFormattingOptions {
+            first_trigger_character: "\n".to_string(),
+            more_trigger_character: Some(vec![":".to_string()]),
+        }),
+        document_range_formatting_provider: Some(OneOf::Left(true)),
+        document_symbol_provider: is_static(Feature::DocumentSymbols).then_some(OneOf::Left(true)),
+        execute_command_provider: Some(ExecuteCommandOptions {
+            commands: vec![
+                extensions::ADD_WORD_TO_DICTIONARY_COMMAND.to_string(),
+                extensions::UPDATE_ALL_DEPENDENCIES_COMMAND.to_string(),
+            ],
             ..Default::default()
         }),
-        definition_provider: Some(OneOf::Left(true)),
-        document_symbol_provider: Some(OneOf::Left(true)),
+        folding_range_provider: Some(FoldingRangeProviderCapability::Simple(true)),
         hover_provider: Some(HoverProviderCapability::Simple(true)),
+        inlay_hint_provider: is_static(Feature::InlayHints).then_some(OneOf::Left(true)),
+        inline_value_provider: Some(OneOf::Left(true)),
+        position_encoding: Some(position_encoding.kind()),
+        references_provider: Some(OneOf::Left(true)),
+        rename_provider: Some(OneOf::Left(true)),
+        selection_range_provider: Some(SelectionRangeProviderCapability::Simple(true)),
+        semantic_tokens_provider: is_static(Feature::SemanticTokens).then(|| {
+            SemanticTokensServerCapabilities::SemanticTokensOptions(semantic_tokens_options())
+        }),
         signature_help_provider: Some(SignatureHelpOptions {
             trigger_characters: Some(make_trigger_characters(SIGNATURE_HELP_TRIGGER_CHARACTERS)),
             ..Default::default()
@@ -84,9 +533,29 @@ fn run_server(args: ServerArgs) -> anyhow::Result<()> {
         text_document_sync: Some(TextDocumentSyncCapability::Kind(
             TextDocumentSyncKind::INCREMENTAL,
         )),
+        workspace: Some(WorkspaceServerCapabilities {
+            file_operations: Some(WorkspaceFileOperationsServerCapabilities {
+                will_rename: Some(FileOperationRegistrationOptions {
+                    filters: vec![FileOperationFilter {
+                        scheme: Some("file".to_string()),
+                        pattern: FileOperationPattern {
+                            glob: "**".to_string(),
+                            matches: None,
+                            options: None,
+                        },
+                    }],
+                }),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }),
+        workspace_symbol_provider: Some(OneOf::Left(true)),
         ..Default::default()
     })?;
-    let initialize_params = serde_json::from_value(connection.initialize(server_capabilities)?)?;
+    connection.initialize_finish(
+        initialize_id,
+        serde_json::json!({ "capabilities": server_capabilities }),
+    )?;
     event_loop::process_connection(connection, args, initialize_params)?;
 
     // Graceful shutdown.
@@ -96,6 +565,41 @@ fn run_server(args: ServerArgs) -> anyhow::Result<()> {
     Ok(())
 }
 
+pub(crate) fn code_action_options() -> CodeActionOptions {
+    CodeActionOptions {
+        code_action_kinds: Some(vec![
+            CodeActionKind::QUICKFIX,
+            CodeActionKind::REFACTOR_EXTRACT,
+            CodeActionKind::REFACTOR_REWRITE,
+            CodeActionKind::SOURCE,
+            CodeActionKind::SOURCE_FIX_ALL,
+            CodeActionKind::SOURCE_ORGANIZE_IMPORTS,
+        ]),
+        ..Default::default()
+    }
+}
+
+pub(crate) fn semantic_tokens_options() -> SemanticTokensOptions {
+    SemanticTokensOptions {
+        legend: SemanticTokensLegend {
+            token_types: vec![SemanticTokenType::STRING],
+            token_modifiers: STRING_TOKEN_MODIFIERS
+                .iter()
+                .map(|name| SemanticTokenModifier::new(*name))
+                .collect(),
+        },
+        full: Some(SemanticTokensFullOptions::Bool(true)),
+        ..Default::default()
+    }
+}
+
+pub(crate) fn completion_options() -> CompletionOptions {
+    CompletionOptions {
+        trigger_characters: Some(make_trigger_characters(COMPLETION_TRIGGER_CHARACTERS)),
+        ..Default::default()
+    }
+}
+
 fn make_trigger_characters(chars: &[char]) -> Vec<String> {
     chars.iter().map(|c| c.to_string()).collect()
 }
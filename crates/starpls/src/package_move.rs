@@ -0,0 +1,123 @@
+use std::{fs, path::Path};
+
+use anyhow::{anyhow, bail};
+use starpls_common::decode;
+use starpls_syntax::edit::{apply_edits, Edit};
+
+use crate::targets::index_workspace;
+
+const BOM: &str = "\u{feff}";
+
+/// Rewrites the labels across the current workspace that refer to the package `from` or its
+/// subpackages to refer to `to` instead, e.g. `load` paths, dependencies, and visibility entries.
+/// Only the labels are rewritten; the package directory itself is left for the caller to move,
+/// either before or after running this.
+pub(crate) fn run_move(
+    from: String,
+    to: String,
+    dry_run: bool,
+    output_base: Option<String>,
+) -> anyhow::Result<()> {
+    let from_package = parse_package(&from)?;
+    let to_package = parse_package(&to)?;
+    if from_package == to_package {
+        bail!("The packages {:?} and {:?} are the same.", from, to);
+    }
+    if to_package.starts_with(&format!("{}/", from_package)) {
+        bail!("Cannot move the package {:?} into itself.", from);
+    }
+
+    let (snap, interner, workspace, index) = index_workspace(output_base)?;
+    let from_dir = workspace.join(from_package);
+    if !from_dir.is_dir() && !workspace.join(to_package).is_dir() {
+        bail!("Could not find the package {:?} in the workspace.", from);
+    }
+
+    let mut moved_targets = index
+        .iter()
+        .filter(|indexed| {
+            interner.lookup_by_file_id(indexed.file_id).parent() == Some(from_dir.as_path())
+        })
+        .map(|indexed| indexed.target.name.clone())
+        .collect::<Vec<_>>();
+    moved_targets.sort();
+    for name in &moved_targets {
+        println!("//{}:{} -> //{}:{}", from_package, name, to_package, name);
+    }
+
+    let file_ids = interner
+        .entries()
+        .into_iter()
+        .filter(|(_, path)| path.starts_with(&workspace))
+        .map(|(file_id, _)| file_id)
+        .collect::<Vec<_>>();
+    let mut changes = snap
+        .package_move_edits(&file_ids, from_package, to_package)?
+        .into_iter()
+        .map(|(file_id, edits)| (interner.lookup_by_file_id(file_id), edits))
+        .collect::<Vec<_>>();
+    changes.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    let mut count = 0;
+    for (path, edits) in &changes {
+        let display_path = path.strip_prefix(&workspace).unwrap_or(path);
+        println!("{}: {} labels", display_path.display(), edits.len());
+        count += edits.len();
+        if !dry_run {
+            let edits = edits
+                .iter()
+                .map(|edit| Edit {
+                    range: edit.range,
+                    new_text: edit.new_text.clone(),
+                })
+                .collect::<Vec<_>>();
+            write_edits(path, &edits)?;
+        }
+    }
+
+    println!(
+        "{} {} labels in {} files",
+        if dry_run { "Would rewrite" } else { "Rewrote" },
+        count,
+        changes.len()
+    );
+    Ok(())
+}
+
+/// Parses a package given as `//pkg`, returning it without the leading slashes.
+fn parse_package(package: &str) -> anyhow::Result<&str> {
+    let err = || {
+        anyhow!(
+            "Invalid package {:?}, expected a package of the form \"//pkg\".",
+            package
+        )
+    };
+    let name = package
+        .trim_start_matches('@')
+        .strip_prefix("//")
+        .ok_or_else(err)?
+        .trim_end_matches('/');
+    if name.is_empty() || name.contains(':') || name.split('/').any(str::is_empty) {
+        return Err(err());
+    }
+    Ok(name)
+}
+
+/// Applies edits to a file on disk, keeping its byte order mark and line endings.
+pub(crate) fn write_edits(path: &Path, edits: &[Edit]) -> anyhow::Result<()> {
+    let bytes = fs::read(path).map_err(|err| anyhow!("Could not read {:?}: {}", path, err))?;
+    let has_bom = bytes.starts_with(BOM.as_bytes());
+    let decoded = decode(bytes);
+    if decoded.has_invalid_utf8 {
+        eprintln!("move: skipping {:?}, which isn't valid UTF-8", path);
+        return Ok(());
+    }
+
+    let text = apply_edits(&decoded.text, edits);
+    let mut contents = decoded.line_endings.apply(&text).into_owned();
+    if has_bom {
+        contents.insert_str(0, BOM);
+    }
+    fs::write(path, contents).map_err(|err| anyhow!("Could not write {:?}: {}", path, err))?;
+    Ok(())
+}
@@ -0,0 +1,74 @@
+//! Streams the results of requests that search many files, e.g. for references, to the client as
+//! they're found, rather than making it wait for the search to finish.
+//!
+//! Clients opt in by passing a partial result token with the request. Results are then reported in
+//! `$/progress` notifications for that token, and LSP requires the final response to be empty.
+//! Results found in quick succession are sent together, so that searching thousands of small files
+//! doesn't flood the client with notifications.
+
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+use crate::server::ServerSnapshot;
+
+/// How long results are held back to be sent along with the ones found after them.
+const FLUSH_INTERVAL: Duration = Duration::from_millis(100);
+
+pub(crate) struct PartialResults<'a, T> {
+    snapshot: &'a ServerSnapshot,
+    token: Option<lsp_types::ProgressToken>,
+    pending: Vec<T>,
+    /// When results were last sent. The first results are sent as soon as they're found.
+    last_flush: Option<Instant>,
+}
+
+impl<'a, T: Serialize> PartialResults<'a, T> {
+    pub(crate) fn new(
+        snapshot: &'a ServerSnapshot,
+        token: Option<lsp_types::ProgressToken>,
+    ) -> Self {
+        Self {
+            snapshot,
+            token,
+            pending: Vec::new(),
+            last_flush: None,
+        }
+    }
+
+    /// Whether the results are being streamed to the client.
+    pub(crate) fn is_streaming(&self) -> bool {
+        self.token.is_some()
+    }
+
+    pub(crate) fn report(&mut self, results: impl IntoIterator<Item = T>) {
+        self.pending.extend(results);
+        if self.is_streaming()
+            && !self.pending.is_empty()
+            && self
+                .last_flush
+                .map_or(true, |last_flush| last_flush.elapsed() >= FLUSH_INTERVAL)
+        {
+            self.flush();
+        }
+    }
+
+    /// Sends any results that are still held back, and returns the results for the final response,
+    /// which are empty if the results were streamed.
+    pub(crate) fn finish(mut self) -> Vec<T> {
+        if self.is_streaming() {
+            self.flush();
+        }
+        self.pending
+    }
+
+    fn flush(&mut self) {
+        if let Some(token) = &self.token {
+            if !self.pending.is_empty() {
+                self.snapshot
+                    .send_partial_result(token, std::mem::take(&mut self.pending));
+            }
+        }
+        self.last_flush = Some(Instant::now());
+    }
+}
@@ -0,0 +1,236 @@
+//! Support for `starpls.toml` files, which customize analysis for the directory they're in and all
+//! of its subdirectories. Settings from nested files take precedence over those from their parent
+//! directories. For example:
+//!
+//! ```toml
+//! # Analyze files in this subtree as plain Starlark. The other dialects are "bazel" and "buck2".
+//! dialect = "standard"
+//!
+//! [diagnostics]
+//! # Report type errors as warnings, and don't report unknown words at all.
+//! type = "warning"
+//! spelling = "off"
+//! ```
+//!
+//! Only the subset of TOML needed for the settings above is supported, namely tables and keys
+//! with string values.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, bail};
+use parking_lot::Mutex;
+use rustc_hash::FxHashMap;
+use starpls_common::{Dialect, Severity};
+use starpls_ide::{DiagnosticCategory, SeverityOverrides};
+
+pub(crate) const PROJECT_CONFIG_FILE: &str = "starpls.toml";
+
+/// The effective settings for a single file.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct ProjectConfig {
+    pub(crate) dialect: Option<Dialect>,
+    pub(crate) severity_overrides: SeverityOverrides,
+}
+
+impl ProjectConfig {
+    /// Applies a single setting from a `starpls.toml` file.
+    pub(crate) fn apply(&mut self, key: &str, value: &str) -> anyhow::Result<()> {
+        match key.split_once('.') {
+            None if key == "dialect" => {
+                self.dialect = Some(match value {
+                    "standard" => Dialect::Standard,
+                    "bazel" => Dialect::Bazel,
+                    "buck2" => Dialect::Buck2,
+                    dialect => bail!("unknown dialect {:?}", dialect),
+                })
+            }
+            Some(("diagnostics", category)) => {
+                let category = DiagnosticCategory::from_name(category)
+                    .ok_or_else(|| anyhow!("unknown diagnostic category {:?}", category))?;
+                let severity = match value {
+                    "off" => None,
+                    "hint" => Some(Severity::Hint),
+                    "warning" => Some(Severity::Warning),
+                    "error" => Some(Severity::Error),
+                    severity => bail!("unknown severity {:?}", severity),
+                };
+                self.severity_overrides.insert(category, severity);
+            }
+            _ => bail!("unknown key {:?}", key),
+        }
+        Ok(())
+    }
+}
+
+/// The settings from a single `starpls.toml` file.
+#[derive(Clone, Debug)]
+pub(crate) struct ProjectConfigFile {
+    pub(crate) path: PathBuf,
+    /// The valid settings in the file, sorted by key.
+    pub(crate) values: Vec<(String, String)>,
+}
+
+/// Finds and caches the `starpls.toml` files within a workspace.
+pub(crate) struct ProjectConfigLoader {
+    workspace: PathBuf,
+    cache: Mutex<FxHashMap<PathBuf, Option<ProjectConfigFile>>>,
+}
+
+impl ProjectConfigLoader {
+    pub(crate) fn new(workspace: PathBuf) -> Self {
+        Self {
+            workspace,
+            cache: Default::default(),
+        }
+    }
+
+    /// Returns the effective settings for the file at the given path, by merging all
+    /// `starpls.toml` files from the workspace root down to the file's directory.
+    pub(crate) fn config_for_path(&self, path: &Path) -> ProjectConfig {
+        let mut config = ProjectConfig::default();
+        for file in self.files_for_path(path) {
+            for (key, value) in &file.values {
+                // Settings are validated when the file is read.
+                let _ = config.apply(key, value);
+            }
+        }
+        config
+    }
+
+    /// Returns the `starpls.toml` files that apply to the file at the given path, from the
+    /// workspace root down to the file's directory, i.e. in increasing order of precedence.
+    pub(crate) fn files_for_path(&self, path: &Path) -> Vec<ProjectConfigFile> {
+        let dir = match path.parent() {
+            Some(dir) if dir.starts_with(&self.workspace) => dir,
+            _ => return Vec::new(),
+        };
+
+        let mut dirs = dir
+            .ancestors()
+            .take_while(|dir| dir.starts_with(&self.workspace))
+            .collect::<Vec<_>>();
+        dirs.reverse();
+
+        let mut cache = self.cache.lock();
+        dirs.into_iter()
+            .filter_map(|dir| {
+                cache
+                    .entry(dir.to_path_buf())
+                    .or_insert_with(|| read_config_file(&dir.join(PROJECT_CONFIG_FILE)))
+                    .clone()
+            })
+            .collect()
+    }
+
+    pub(crate) fn clear(&self) {
+        self.cache.lock().clear();
+    }
+}
+
+fn read_config_file(path: &Path) -> Option<ProjectConfigFile> {
+    let contents = fs::read_to_string(path).ok()?;
+    let values = match parse(&contents) {
+        Ok(values) => values,
+        Err(err) => {
+            eprintln!("server: failed to parse {:?}: {}", path, err);
+            return None;
+        }
+    };
+
+    // Invalid settings are skipped so that the rest of the file still applies.
+    let mut values = values
+        .into_iter()
+        .filter(
+            |(key, value)| match ProjectConfig::default().apply(key, value) {
+                Ok(()) => true,
+                Err(err) => {
+                    eprintln!("server: invalid {:?}: {}", path, err);
+                    false
+                }
+            },
+        )
+        .collect::<Vec<_>>();
+    values.sort();
+    for (key, value) in &values {
+        eprintln!("server: config {:?}: {} = {:?}", path, key, value);
+    }
+    Some(ProjectConfigFile {
+        path: path.to_path_buf(),
+        values,
+    })
+}
+
+/// Parses a TOML document into a map from dotted keys, e.g. `diagnostics.type`, to values.
+fn parse(contents: &str) -> anyhow::Result<FxHashMap<String, String>> {
+    let mut values = FxHashMap::default();
+    let mut table = String::new();
+
+    for (i, line) in contents.lines().enumerate() {
+        let line_number = i + 1;
+        let line = strip_comment(line).trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        if let Some(header) = line.strip_prefix('[') {
+            table = header
+                .strip_suffix(']')
+                .ok_or_else(|| anyhow!("line {}: unterminated table header", line_number))?
+                .trim()
+                .to_string();
+            continue;
+        }
+
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| anyhow!("line {}: expected `key = value`", line_number))?;
+        let key = key.trim().trim_matches('"');
+        let value = parse_value(value.trim())
+            .ok_or_else(|| anyhow!("line {}: invalid value {:?}", line_number, value.trim()))?;
+        let key = if table.is_empty() {
+            key.to_string()
+        } else {
+            format!("{}.{}", table, key)
+        };
+        values.insert(key, value);
+    }
+
+    Ok(values)
+}
+
+fn parse_value(value: &str) -> Option<String> {
+    if let Some(s) = value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+    {
+        Some(s.replace("\\\"", "\"").replace("\\\\", "\\"))
+    } else {
+        value
+            .strip_prefix('\'')
+            .and_then(|value| value.strip_suffix('\''))
+            .map(|s| s.to_string())
+    }
+}
+
+/// Strips a trailing `#` comment from a line, ignoring `#` characters inside strings.
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match (quote, c) {
+            (Some('"'), '\\') if !escaped => {
+                escaped = true;
+                continue;
+            }
+            (Some(q), c) if c == q && !escaped => quote = None,
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '#') => return &line[..i],
+            _ => {}
+        }
+        escaped = false;
+    }
+    line
+}
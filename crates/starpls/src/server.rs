@@ -1,59 +1,221 @@
-use std::{mem, panic, sync::Arc, time::Duration};
+use std::{
+    collections::HashMap,
+    fs::{self, OpenOptions},
+    io::Write,
+    mem, panic,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
 
+use anyhow::{anyhow, bail};
+use clap::ValueEnum;
+use crossbeam_channel::Sender;
 use lsp_server::{Connection, ReqQueue};
+use lsp_types::notification::Notification as _;
 use parking_lot::RwLock;
 use rustc_hash::FxHashSet;
 use starpls_bazel::{
     build_language::decode_rules,
+    bzlmod::ModuleResolver,
     client::{BazelCLI, BazelClient},
-    decode_builtins, Builtins,
+    decode_builtins,
+    lockfile::ModuleLockfile,
+    packs::{BuiltinPack, BuiltinPacks, BAZEL_CORE_PACK},
+    registry::RegistryMetadata,
+    repo_cache::RepoCache,
+    Builtins, BuiltinsOverride,
+};
+use starpls_common::{read_source, Dialect, FileId};
+use starpls_ide::{
+    parse_module_file, Analysis, AnalysisSnapshot, BuildifierTables, Cancellable, Change,
+    DiagnosticCategory, DiagnosticsOptions, InferenceOptions, LabelStyle, SeverityOverrides,
 };
-use starpls_common::FileId;
-use starpls_ide::{Analysis, AnalysisSnapshot, Change, InferenceOptions};
 
 use crate::{
+    buildifier::{self, Buildifier},
     config::ServerConfig,
+    convert::{self, FileLineIndex},
+    crash_report::{self, CrashReporter},
     debouncer::AnalysisDebouncer,
     diagnostics::DiagnosticsManager,
-    document::{DefaultFileLoader, DocumentChangeKind, DocumentManager, PathInterner},
+    document::{
+        is_read_only_path, DefaultFileLoader, DocumentChangeKind, DocumentManager, PathInterner,
+    },
     event_loop::{FetchExternalReposProgress, Task},
+    features::{Feature, FeatureSettings, InlayHintsMode},
+    load_index::LoadIndex,
+    project_config::ProjectConfigLoader,
+    sidecar::CheckSidecar,
     task_pool::{TaskPool, TaskPoolHandle},
+    workspace_diagnostics::{self, WorkspaceDiagnostics},
+    workspace_symbols::WorkspaceSymbolIndex,
 };
 
 const DEBOUNCE_INTERVAL: Duration = Duration::from_millis(250);
 
+/// The number of threads used for background work, i.e. computing diagnostics and fetching
+/// external repositories.
+const BACKGROUND_THREADS: usize = 2;
+
+/// The workspace-relative path of the word list used to extend the spell-checking dictionary.
+const WORKSPACE_WORDS_FILE: &str = ".starpls_words.txt";
+
+/// The lockfile that records the results of module resolution for the root `MODULE.bazel` file.
+const MODULE_LOCKFILE: &str = "MODULE.bazel.lock";
+
+/// The section of the client's settings that the server reads.
+pub(crate) const SETTINGS_SECTION: &str = "starpls";
+
+/// The action offered to the user when asking whether to fetch missing external repositories.
+const FETCH_REPOS_ACTION: &str = "Fetch";
+
 const BAZEL_INIT_ERR_MESSAGE: &str = "Failed to fetch Bazel configuration! Please check the language server logs for more details. Certain features may not work correctly until the underlying issue is fixed.";
 
+/// How the sources of external repositories that aren't on disk yet are obtained.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub(crate) enum FetchRepoMode {
+    /// Query the repository's targets, which makes Bazel fetch it into the output base.
+    #[default]
+    Bazel,
+    /// Only use repositories that are already in the output base.
+    Offline,
+    /// Ask the user before running `bazel fetch` for missing repositories.
+    FetchOnDemand,
+}
+
+/// The style that labels in `BUILD` files are expected to be written in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub(crate) enum LabelStyleMode {
+    /// Relative labels for targets in the same package, and `//foo` for `//foo:foo`.
+    Short,
+    /// Labels that always name their package and target, e.g. `//foo:foo`.
+    Full,
+}
+
+/// What to do with the client's response to a request sent by the server.
+#[derive(Debug, Default)]
+pub(crate) enum OnResponse {
+    #[default]
+    Ignore,
+    /// Fetch the given repositories if the user accepted, then re-analyze the given files.
+    FetchRepos {
+        repos: FxHashSet<String>,
+        files: FxHashSet<FileId>,
+    },
+    /// Apply the feature settings in the client's settings.
+    Configuration,
+}
+
 pub(crate) struct Server {
     pub(crate) config: Arc<ServerConfig>,
     pub(crate) connection: Connection,
-    pub(crate) req_queue: ReqQueue<(), ()>,
+    pub(crate) req_queue: ReqQueue<(), OnResponse>,
     pub(crate) task_pool_handle: TaskPoolHandle<Task>,
     pub(crate) document_manager: Arc<RwLock<DocumentManager>>,
     pub(crate) diagnostics_manager: DiagnosticsManager,
     pub(crate) analysis: Analysis,
+    pub(crate) diagnostics_options: DiagnosticsOptions,
     pub(crate) analysis_debouncer: AnalysisDebouncer,
     pub(crate) analysis_requested_for_files: Option<Vec<FileId>>,
+    /// Debounces edits before checking the closed files affected by them. Only set if workspace
+    /// diagnostics are enabled.
+    pub(crate) workspace_diagnostics_debouncer: Option<AnalysisDebouncer>,
+    pub(crate) workspace_diagnostics: WorkspaceDiagnostics,
     pub(crate) bazel_client: Arc<dyn BazelClient>,
+    pub(crate) module_resolver: Arc<ModuleResolver>,
     pub(crate) pending_repos: FxHashSet<String>,
     pub(crate) pending_files: FxHashSet<FileId>,
     pub(crate) force_analysis_for_files: FxHashSet<FileId>,
     pub(crate) fetched_repos: FxHashSet<String>,
     pub(crate) is_fetching_repos: bool,
+    pub(crate) workspace: PathBuf,
+    /// Bazel's output base. Files in it are read-only.
+    pub(crate) output_base: PathBuf,
+    pub(crate) project_configs: Arc<ProjectConfigLoader>,
+    pub(crate) feature_settings: FeatureSettings,
+    /// The features whose capabilities are currently registered with the client.
+    registered_features: FxHashSet<Feature>,
+    /// The Bazel builtins and rules, until the first Bazel file is opened.
+    pending_builtin_defs: Option<(Builtins, Builtins)>,
+    pub(crate) crash_reporter: Arc<CrashReporter>,
+    pub(crate) workspace_symbols: Arc<WorkspaceSymbolIndex>,
+    /// Finds the files that load a given file, including ones that aren't open.
+    pub(crate) load_index: Arc<LoadIndex>,
+    pub(crate) check_sidecar: Option<Arc<CheckSidecar>>,
+    pub(crate) buildifier: Option<Arc<Buildifier>>,
+    /// Debounces edits before linting open files with Buildifier. Only set if `--buildifier` is.
+    pub(crate) buildifier_debouncer: Option<AnalysisDebouncer>,
 }
 
 pub(crate) struct ServerSnapshot {
     pub(crate) config: Arc<ServerConfig>,
     pub(crate) analysis_snapshot: AnalysisSnapshot,
     pub(crate) document_manager: Arc<RwLock<DocumentManager>>,
+    pub(crate) crash_reporter: Arc<CrashReporter>,
+    pub(crate) workspace: PathBuf,
+    pub(crate) output_base: PathBuf,
+    pub(crate) inlay_hints: InlayHintsMode,
+    pub(crate) workspace_symbols: Arc<WorkspaceSymbolIndex>,
+    pub(crate) load_index: Arc<LoadIndex>,
+    pub(crate) check_sidecar: Option<Arc<CheckSidecar>>,
+    pub(crate) buildifier: Option<Arc<Buildifier>>,
+    /// Sends messages to the client, e.g. partial results while a request is being handled.
+    pub(crate) sender: Sender<lsp_server::Message>,
+}
+
+impl ServerSnapshot {
+    pub(crate) fn send_notification<N: lsp_types::notification::Notification>(
+        &self,
+        params: N::Params,
+    ) {
+        let not = lsp_server::Notification::new(N::METHOD.to_string(), params);
+        self.sender.send(not.into()).unwrap();
+    }
+
+    /// Reports part of a request's result to the client, which adds it to the parts reported
+    /// before. Once a request reports partial results, its final response must be empty.
+    pub(crate) fn send_partial_result(
+        &self,
+        token: &lsp_types::ProgressToken,
+        value: impl serde::Serialize,
+    ) {
+        let not = lsp_server::Notification::new(
+            lsp_types::notification::Progress::METHOD.to_string(),
+            serde_json::json!({ "token": token, "value": value }),
+        );
+        self.sender.send(not.into()).unwrap();
+    }
+
+    /// Returns whether a file is owned by Bazel, in which case edits to it are refused.
+    pub(crate) fn is_read_only(&self, file_id: FileId) -> bool {
+        let path = self.document_manager.read().lookup_by_file_id(file_id);
+        is_read_only_path(&path, &self.workspace, &self.output_base)
+    }
+
+    /// Returns the line index of a file, for converting between text offsets and LSP positions.
+    pub(crate) fn line_index(&self, file_id: FileId) -> Cancellable<Option<FileLineIndex<'_>>> {
+        let has_bom = self.document_manager.read().has_bom(file_id);
+        Ok(self
+            .analysis_snapshot
+            .line_index(file_id)?
+            .map(|index| FileLineIndex { index, has_bom }))
+    }
 }
 
 impl Server {
     pub(crate) fn new(connection: Connection, config: ServerConfig) -> anyhow::Result<Self> {
-        // Create the task pool for processin incoming requests.
+        // Create the task pools for processing incoming requests and background work. Requests
+        // get a thread per core, since each one only holds a read-only snapshot of the database.
         let (task_pool_sender, task_pool_receiver) = crossbeam_channel::unbounded();
-        let task_pool = TaskPool::with_num_threads(task_pool_sender.clone(), 4)?;
-        let task_pool_handle = TaskPoolHandle::new(task_pool_receiver, task_pool);
+        let request_threads = std::thread::available_parallelism()
+            .map(|threads| threads.get())
+            .unwrap_or(4)
+            .max(2);
+        let task_pool = TaskPool::with_num_threads(task_pool_sender.clone(), request_threads)?;
+        let background_pool =
+            TaskPool::with_num_threads(task_pool_sender.clone(), BACKGROUND_THREADS)?;
+        let task_pool_handle = TaskPoolHandle::new(task_pool_receiver, task_pool, background_pool);
         let mut has_bazel_init_err = false;
 
         // Load Bazel builtins from the specified file.
@@ -75,7 +237,7 @@ impl Server {
 
         eprintln!("server: using Bazel executable at {:?}", bazel_path);
 
-        let bazel_client = Arc::new(BazelCLI::new(&bazel_path));
+        let bazel_client = BazelCLI::new(&bazel_path);
         let info = match bazel_client.info() {
             Ok(info) => info,
             Err(err) => {
@@ -91,26 +253,46 @@ impl Server {
         let external_output_base = info.output_base.join("external");
 
         eprintln!("server: external output base: {:?}", external_output_base);
+
+        // Cache the repository mappings of external repositories in a user-level directory, so that
+        // they're shared across workspaces and survive restarts.
+        let repo_cache_dir = if config.args.disable_repo_cache {
+            None
+        } else {
+            config
+                .args
+                .repo_cache_dir
+                .as_ref()
+                .map(PathBuf::from)
+                .or_else(RepoCache::default_dir)
+        };
+        let bazel_client = Arc::new(match repo_cache_dir {
+            Some(dir) => {
+                eprintln!("server: caching external repository mappings in {:?}", dir);
+                bazel_client.with_repo_cache(RepoCache::new(
+                    dir,
+                    &external_output_base,
+                    &info.workspace,
+                ))
+            }
+            None => bazel_client,
+        });
         eprintln!("server: starlark-semantics: {:?}", info.starlark_semantics);
 
         // We determine whether to use bzlmod in two steps. First, we check if `MODULE.bazel` exists at all,
-        // and if so, whether the `bazel mod dump_repo_mapping` command is supported. If either of these
-        // checks fails, then we can't use bzlmod anyways.
+        // since we can't use bzlmod otherwise. Repositories are resolved with `bazel mod dump_repo_mapping`
+        // if it's supported, and from the `MODULE.bazel` files themselves if not.
         let bzlmod_capability = info
             .workspace
             .join("MODULE.bazel")
             .try_exists()
-            .unwrap_or(false)
-            && {
-                eprintln!("server: checking for `bazel mod dump_repo_mapping` capability");
-                match bazel_client.dump_repo_mapping("") {
-                    Ok(_) => true,
-                    Err(_) => {
-                        eprintln!("server: installed Bazel version doesn't support `bazel mod dump_repo_mapping`, disabling bzlmod support");
-                        false
-                    }
-                }
-            };
+            .unwrap_or(false);
+        if bzlmod_capability {
+            eprintln!("server: checking for `bazel mod dump_repo_mapping` capability");
+            if bazel_client.dump_repo_mapping("").is_err() {
+                eprintln!("server: installed Bazel version doesn't support `bazel mod dump_repo_mapping`, resolving repositories from MODULE.bazel files instead");
+            }
+        }
 
         let bzlmod_enabled = bzlmod_capability && {
             // Next, we check if bzlmod is enabled by default for the current Bazel version.
@@ -154,40 +336,213 @@ impl Server {
             }
         };
 
+        let workspace = info.workspace.clone();
+        let output_base = info.output_base.clone();
+
+        // Combine the bundled builtins, the native rules, and any additional packs.
+        let mut packs = BuiltinPacks::default();
+        packs.add(BuiltinPack {
+            name: BAZEL_CORE_PACK.to_string(),
+            builtins,
+            ..Default::default()
+        });
+        packs.add_native_rules(rules);
+        if let Some(dir) = &config.args.builtin_packs_dir {
+            if let Err(err) = packs.add_dir(workspace.join(dir)) {
+                eprintln!(
+                    "server: failed to load builtin packs from {:?}: {}",
+                    dir, err
+                );
+            }
+        }
+        eprintln!(
+            "server: enabled builtin packs: {:?}",
+            packs
+                .names()
+                .filter(|name| !config
+                    .args
+                    .disabled_builtin_packs
+                    .iter()
+                    .any(|disabled| disabled == name))
+                .collect::<Vec<_>>()
+        );
+        let (mut builtins, mut rules) = packs.combine(&config.args.disabled_builtin_packs);
+
+        // Apply any workspace-supplied overrides, in the order they were specified.
+        for path in &config.args.builtin_overrides {
+            match BuiltinsOverride::load(workspace.join(path)) {
+                Ok(overrides) => {
+                    eprintln!("server: applying builtin overrides from {:?}", path);
+                    overrides.apply(&mut builtins, &mut rules);
+                }
+                Err(err) => eprintln!(
+                    "server: failed to load builtin overrides from {:?}: {}",
+                    path, err
+                ),
+            }
+        }
+
         let path_interner = Arc::new(PathInterner::default());
-        let loader = DefaultFileLoader::new(
+        let module_resolver = Arc::new(ModuleResolver::new(
+            info.workspace.clone(),
+            external_output_base.clone(),
+            parse_module_file,
+        ));
+        let load_index = Arc::new(LoadIndex::new(workspace.clone()));
+        let loader = Arc::new(DefaultFileLoader::new(
             bazel_client.clone(),
             path_interner.clone(),
             info.workspace,
             external_output_base,
             task_pool_sender.clone(),
             bzlmod_enabled,
-        );
+            module_resolver.clone(),
+            Some(load_index.clone()),
+        ));
 
+        let project_configs = Arc::new(ProjectConfigLoader::new(workspace.clone()));
         let mut analysis = Analysis::new(
-            Arc::new(loader),
+            loader.clone(),
             InferenceOptions {
                 infer_ctx_attrs: config.args.experimental_infer_ctx_attributes,
+                struct_merge_functions: config.args.struct_merge_functions.clone(),
             },
         );
-        analysis.set_builtin_defs(builtins, rules);
+
+        let diagnostics_options = DiagnosticsOptions {
+            enable_spellcheck: config.args.experimental_enable_spellcheck,
+            spellcheck_words: if config.args.experimental_enable_spellcheck {
+                load_workspace_words(&workspace)
+            } else {
+                Vec::new()
+            },
+            enable_todos: config.args.experimental_enable_todos,
+            todo_tags: config.args.todo_tags.clone(),
+            required_header: config.args.required_header_file.as_ref().and_then(|path| {
+                match read_source(workspace.join(path)) {
+                    Ok(header) => Some(header.text),
+                    Err(err) => {
+                        eprintln!("server: failed to read required header file: {}", err);
+                        None
+                    }
+                }
+            }),
+            label_style: config.args.label_style.map(|style| match style {
+                LabelStyleMode::Short => LabelStyle::Short,
+                LabelStyleMode::Full => LabelStyle::Full,
+            }),
+        };
+        analysis.set_diagnostics_options(diagnostics_options.clone());
+        #[cfg(feature = "lint-plugins")]
+        analysis.set_lint_rules(crate::lint_plugins::lint_rules());
+        analysis.set_module_lockfile(
+            path_interner.intern_path(workspace.join("MODULE.bazel")),
+            load_module_lockfile(&workspace),
+        );
+        if let Some(dir) = &config.args.registry_cache {
+            match RegistryMetadata::load(workspace.join(dir)) {
+                Ok(metadata) => analysis.set_registry_metadata(metadata),
+                Err(err) => eprintln!(
+                    "server: failed to load registry metadata from {:?}: {}",
+                    dir, err
+                ),
+            }
+        }
+        analysis.set_buildifier_tables(load_buildifier_tables(
+            &workspace,
+            config.args.buildifier_tables.as_deref(),
+            config.args.buildifier_add_tables.as_deref(),
+        ));
+
+        crash_report::install_panic_hook();
+        let crash_reporter = Arc::new(CrashReporter::new(config.args.crash_report_include_source));
+
+        let workspace_diagnostics_debouncer = config
+            .args
+            .experimental_enable_workspace_diagnostics
+            .then(|| {
+                AnalysisDebouncer::new(
+                    workspace_diagnostics::DEBOUNCE_INTERVAL,
+                    task_pool_sender.clone(),
+                    Task::WorkspaceDiagnosticsRequested,
+                )
+            });
+
+        // Index the workspace's symbols and loads in the background, since walking a large
+        // workspace can take a while. Both are indexed by the same task, so that the other
+        // background thread stays free for diagnostics.
+        let workspace_symbols = Arc::new(WorkspaceSymbolIndex::new(
+            workspace.clone(),
+            config.position_encoding(),
+        ));
+        let index = workspace_symbols.clone();
+        task_pool_handle.spawn_background_with_sender(move |_| {
+            index.index_workspace();
+            loader.index_workspace_loads();
+        });
+
+        let check_sidecar = config.args.check_sidecar.as_ref().map(|program| {
+            Arc::new(CheckSidecar::new(
+                program,
+                config.args.check_sidecar_args.clone(),
+                workspace.clone(),
+            ))
+        });
+
+        let buildifier = config.args.buildifier.as_ref().map(|program| {
+            Arc::new(Buildifier::new(
+                program,
+                config.args.buildifier_args.clone(),
+                workspace.clone(),
+            ))
+        });
+        let buildifier_debouncer = buildifier.is_some().then(|| {
+            AnalysisDebouncer::new(
+                buildifier::DEBOUNCE_INTERVAL,
+                task_pool_sender.clone(),
+                Task::BuildifierLintRequested,
+            )
+        });
 
         let server = Server {
             config: Arc::new(config),
             connection,
             req_queue: Default::default(),
             task_pool_handle,
-            document_manager: Arc::new(RwLock::new(DocumentManager::new(path_interner))),
+            document_manager: Arc::new(RwLock::new(DocumentManager::new(
+                path_interner,
+                project_configs.clone(),
+            ))),
             diagnostics_manager: Default::default(),
             analysis,
-            analysis_debouncer: AnalysisDebouncer::new(DEBOUNCE_INTERVAL, task_pool_sender),
+            diagnostics_options,
+            analysis_debouncer: AnalysisDebouncer::new(
+                DEBOUNCE_INTERVAL,
+                task_pool_sender.clone(),
+                Task::AnalysisRequested,
+            ),
             analysis_requested_for_files: None,
+            workspace_diagnostics_debouncer,
+            workspace_diagnostics: Default::default(),
             bazel_client,
+            module_resolver,
             pending_repos: Default::default(),
             pending_files: Default::default(),
             force_analysis_for_files: Default::default(),
             fetched_repos: Default::default(),
             is_fetching_repos: false,
+            workspace,
+            output_base,
+            project_configs,
+            feature_settings: Default::default(),
+            registered_features: Default::default(),
+            pending_builtin_defs: Some((builtins, rules)),
+            crash_reporter,
+            workspace_symbols,
+            load_index,
+            check_sidecar,
+            buildifier,
+            buildifier_debouncer,
         };
 
         if has_bazel_init_err {
@@ -202,6 +557,15 @@ impl Server {
             config: self.config.clone(),
             analysis_snapshot: self.analysis.snapshot(),
             document_manager: Arc::clone(&self.document_manager),
+            crash_reporter: Arc::clone(&self.crash_reporter),
+            workspace: self.workspace.clone(),
+            output_base: self.output_base.clone(),
+            inlay_hints: self.feature_settings.inlay_hints,
+            workspace_symbols: Arc::clone(&self.workspace_symbols),
+            load_index: Arc::clone(&self.load_index),
+            check_sidecar: self.check_sidecar.clone(),
+            buildifier: self.buildifier.clone(),
+            sender: self.connection.sender.clone(),
         }
     }
 
@@ -220,8 +584,22 @@ impl Server {
                 Some(document) => document,
                 None => continue,
             };
+            let path = document_manager.lookup_by_file_id(file_id);
+            self.workspace_symbols
+                .file_changed(path.clone(), document.contents.clone());
+            self.load_index
+                .file_changed(path.clone(), document.contents.clone());
             match change_kind {
                 DocumentChangeKind::Create => {
+                    // Bazel builtins are only loaded once a Bazel file is opened.
+                    if document.dialect == Dialect::Bazel {
+                        if let Some((builtins, rules)) = self.pending_builtin_defs.take() {
+                            self.analysis.set_builtin_defs(builtins, rules);
+                        }
+                    }
+                    let severity_overrides = self.severity_overrides_for_path(&path);
+                    self.analysis
+                        .set_severity_overrides(file_id, severity_overrides);
                     change.create_file(
                         file_id,
                         document.dialect,
@@ -243,15 +621,55 @@ impl Server {
     }
 
     pub(crate) fn send_request<R: lsp_types::request::Request>(&mut self, params: R::Params) {
+        self.send_request_with::<R>(params, OnResponse::Ignore);
+    }
+
+    pub(crate) fn send_request_with<R: lsp_types::request::Request>(
+        &mut self,
+        params: R::Params,
+        on_response: OnResponse,
+    ) {
         let req = self
             .req_queue
             .outgoing
-            .register(R::METHOD.to_string(), params, ());
+            .register(R::METHOD.to_string(), params, on_response);
         self.send(req.into());
     }
 
     pub(crate) fn complete_request(&mut self, resp: lsp_server::Response) {
-        self.req_queue.outgoing.complete(resp.id);
+        match self.req_queue.outgoing.complete(resp.id) {
+            Some(OnResponse::FetchRepos { repos, files }) => {
+                let accepted = resp
+                    .result
+                    .and_then(|result| {
+                        serde_json::from_value::<Option<lsp_types::MessageActionItem>>(result).ok()
+                    })
+                    .flatten()
+                    .map_or(false, |item| item.title == FETCH_REPOS_ACTION);
+                if accepted {
+                    self.spawn_fetch_repos(repos, files, FetchRepoMode::FetchOnDemand);
+                } else {
+                    eprintln!(
+                        "server: not fetching external repositories {}",
+                        format_repos(&repos)
+                    );
+                    self.is_fetching_repos = false;
+                }
+            }
+            Some(OnResponse::Configuration) => {
+                let section = resp
+                    .result
+                    .and_then(|result| {
+                        serde_json::from_value::<Vec<serde_json::Value>>(result).ok()
+                    })
+                    .and_then(|mut sections| sections.pop())
+                    .filter(|section| !section.is_null());
+                if let Some(section) = section {
+                    self.update_feature_settings(Some(&section));
+                }
+            }
+            Some(OnResponse::Ignore) | None => {}
+        }
     }
 
     pub(crate) fn send_notification<N: lsp_types::notification::Notification>(
@@ -275,33 +693,339 @@ impl Server {
         )
     }
 
+    /// Adds a word to the workspace word list and re-checks all open documents.
+    pub(crate) fn add_word_to_dictionary(&mut self, word: &str) -> anyhow::Result<()> {
+        let word = word.trim().to_lowercase();
+        if self.diagnostics_options.spellcheck_words.contains(&word) {
+            return Ok(());
+        }
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.workspace.join(WORKSPACE_WORDS_FILE))?;
+        writeln!(file, "{}", word)?;
+
+        self.diagnostics_options.spellcheck_words.push(word);
+        self.analysis
+            .set_diagnostics_options(self.diagnostics_options.clone());
+        self.force_analysis_for_files
+            .extend(self.document_manager.read().file_ids());
+        Ok(())
+    }
+
+    /// Re-reads all `starpls.toml` files and re-checks all open documents. Dialect changes only
+    /// take effect once a document is reopened.
+    pub(crate) fn reload_project_configs(&mut self) {
+        self.project_configs.clear();
+        let document_manager = self.document_manager.read();
+        for file_id in document_manager.file_ids() {
+            let path = document_manager.lookup_by_file_id(file_id);
+            let severity_overrides = self.severity_overrides_for_path(&path);
+            self.analysis
+                .set_severity_overrides(file_id, severity_overrides);
+            self.force_analysis_for_files.insert(file_id);
+        }
+    }
+
+    /// Returns the severity overrides from the `starpls.toml` files that apply to the given path.
+    /// Style lints are suppressed for read-only files, since their issues can't be fixed.
+    fn severity_overrides_for_path(&self, path: &Path) -> SeverityOverrides {
+        let mut overrides = self
+            .project_configs
+            .config_for_path(path)
+            .severity_overrides;
+        if is_read_only_path(path, &self.workspace, &self.output_base) {
+            for category in DiagnosticCategory::ALL
+                .iter()
+                .filter(|category| category.is_style())
+            {
+                overrides.insert(*category, None);
+            }
+        }
+        overrides
+    }
+
+    /// Requests the `starpls` section of the client's settings.
+    pub(crate) fn request_configuration(&mut self) {
+        self.send_request_with::<lsp_types::request::WorkspaceConfiguration>(
+            lsp_types::ConfigurationParams {
+                items: vec![lsp_types::ConfigurationItem {
+                    scope_uri: None,
+                    section: Some(SETTINGS_SECTION.to_string()),
+                }],
+            },
+            OnResponse::Configuration,
+        );
+    }
+
+    /// Applies the feature settings in the `starpls` section of the client's settings. If the
+    /// settings are invalid, the current ones are kept.
+    pub(crate) fn update_feature_settings(&mut self, section: Option<&serde_json::Value>) {
+        let settings = match section.map_or_else(
+            || Ok(FeatureSettings::default()),
+            FeatureSettings::from_section,
+        ) {
+            Ok(settings) => settings,
+            Err(err) => {
+                eprintln!("server: invalid feature settings: {}", err);
+                self.feature_settings.clone()
+            }
+        };
+        self.set_feature_settings(settings);
+    }
+
+    /// Turns features on or off, registering or unregistering their capabilities with the client
+    /// as needed.
+    fn set_feature_settings(&mut self, settings: FeatureSettings) {
+        let mut registrations = Vec::new();
+        let mut unregistrations = Vec::new();
+        for &feature in Feature::ALL {
+            let method = match feature.method() {
+                Some(method) if feature.is_dynamically_registered(&self.config.caps) => method,
+                _ => continue,
+            };
+            let enabled = settings.is_enabled(feature);
+            if enabled && self.registered_features.insert(feature) {
+                registrations.push(lsp_types::Registration {
+                    id: method.to_string(),
+                    method: method.to_string(),
+                    register_options: feature.registration_options(),
+                });
+            } else if !enabled && self.registered_features.remove(&feature) {
+                unregistrations.push(lsp_types::Unregistration {
+                    id: method.to_string(),
+                    method: method.to_string(),
+                });
+            }
+        }
+        if !registrations.is_empty() {
+            self.send_request::<lsp_types::request::RegisterCapability>(
+                lsp_types::RegistrationParams { registrations },
+            );
+        }
+        if !unregistrations.is_empty() {
+            self.send_request::<lsp_types::request::UnregisterCapability>(
+                lsp_types::UnregistrationParams {
+                    unregisterations: unregistrations,
+                },
+            );
+        }
+
+        // Diagnostics for closed files would go stale once they're no longer updated.
+        if self
+            .feature_settings
+            .is_enabled(Feature::WorkspaceDiagnostics)
+            && !settings.is_enabled(Feature::WorkspaceDiagnostics)
+        {
+            let document_manager = self.document_manager.read();
+            let notifications = self
+                .workspace_diagnostics
+                .clear()
+                .into_iter()
+                .map(|file_id| lsp_types::PublishDiagnosticsParams {
+                    uri: lsp_types::Url::from_file_path(
+                        document_manager.lookup_by_file_id(file_id),
+                    )
+                    .unwrap(),
+                    diagnostics: Vec::new(),
+                    version: None,
+                })
+                .collect::<Vec<_>>();
+            drop(document_manager);
+            for params in notifications {
+                self.send_notification::<lsp_types::notification::PublishDiagnostics>(params);
+            }
+        }
+
+        // Hints that are already shown are only updated once the client asks for them again.
+        if settings.inlay_hints != self.feature_settings.inlay_hints
+            && self.config.has_inlay_hint_refresh_support()
+        {
+            self.send_request::<lsp_types::request::InlayHintRefreshRequest>(());
+        }
+
+        if settings != self.feature_settings {
+            eprintln!(
+                "server: disabled features: {:?}, inlay hints: {}",
+                settings.disabled_keys(),
+                settings.inlay_hints.key()
+            );
+        }
+        self.feature_settings = settings;
+    }
+
+    /// Re-reads the workspace's `MODULE.bazel.lock` file and re-checks the root `MODULE.bazel`
+    /// file if it is open. Repository mappings read from `MODULE.bazel` files are discarded too.
+    pub(crate) fn reload_module_lockfile(&mut self) {
+        self.module_resolver.clear();
+        let document_manager = self.document_manager.read();
+        if let Some(file_id) =
+            document_manager.lookup_by_path_buf(&self.workspace.join("MODULE.bazel"))
+        {
+            self.analysis
+                .set_module_lockfile(file_id, load_module_lockfile(&self.workspace));
+            if document_manager.get(file_id).is_some() {
+                self.force_analysis_for_files.insert(file_id);
+            }
+        }
+    }
+
+    /// Asks the client to update every `bazel_dep` in the given `MODULE.bazel` file to its latest
+    /// version.
+    pub(crate) fn update_all_dependencies(&mut self, path: PathBuf) -> anyhow::Result<()> {
+        let file_id = self
+            .document_manager
+            .read()
+            .lookup_by_path_buf(&path)
+            .ok_or_else(|| anyhow!("{:?} is not open", path))?;
+        if is_read_only_path(&path, &self.workspace, &self.output_base) {
+            bail!("{:?} is read-only", path);
+        }
+        let snapshot = self.analysis.snapshot();
+        let updates = snapshot
+            .dependency_updates(file_id)?
+            .ok_or_else(|| anyhow!("{:?} is not open", path))?;
+        if updates.is_empty() {
+            return Ok(());
+        }
+        let line_index = FileLineIndex {
+            index: snapshot
+                .line_index(file_id)?
+                .ok_or_else(|| anyhow!("{:?} is not open", path))?,
+            has_bom: self.document_manager.read().has_bom(file_id),
+        };
+        let encoding = self.config.position_encoding();
+        let line_endings = self.document_manager.read().line_endings(file_id);
+        let edits = updates
+            .into_iter()
+            .map(|update| {
+                Some(lsp_types::TextEdit {
+                    range: convert::lsp_range_from_text_range(
+                        update.edit.range,
+                        line_index,
+                        encoding,
+                    )?,
+                    new_text: line_endings.apply(&update.edit.new_text).into_owned(),
+                })
+            })
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| anyhow!("failed to convert dependency updates"))?;
+        let uri = lsp_types::Url::from_file_path(&path)
+            .map_err(|_| anyhow!("invalid path: {:?}", path))?;
+
+        self.send_request::<lsp_types::request::ApplyWorkspaceEdit>(
+            lsp_types::ApplyWorkspaceEditParams {
+                label: Some("Update all dependencies".to_string()),
+                edit: lsp_types::WorkspaceEdit {
+                    changes: Some(HashMap::from([(uri, edits)])),
+                    ..Default::default()
+                },
+            },
+        );
+        Ok(())
+    }
+
     pub(crate) fn fetch_bazel_external_repos(&mut self) {
         let repos = mem::take(&mut self.pending_repos);
         let files = mem::take(&mut self.pending_files);
-        let bazel_client = self.bazel_client.clone();
-        self.is_fetching_repos = true;
         self.fetched_repos.extend(repos.clone().into_iter());
-        self.task_pool_handle.spawn_with_sender(move |sender| {
-            sender
-                .send(Task::FetchExternalRepos(FetchExternalReposProgress::Begin(
-                    repos.clone(),
-                )))
-                .unwrap();
-
-            for repo in &repos {
-                eprintln!("server: fetching external repository \"@@{}\"", repo);
-                let _ = bazel_client.null_query_external_repo_targets(repo);
+        match self.config.args.fetch_repo_mode {
+            FetchRepoMode::Bazel => self.spawn_fetch_repos(repos, files, FetchRepoMode::Bazel),
+            FetchRepoMode::Offline => {
+                let message = format!(
+                    "External repositories are not available offline: {}",
+                    format_repos(&repos)
+                );
+                eprintln!("server: {}", message);
+                self.send_notification::<lsp_types::notification::ShowMessage>(
+                    lsp_types::ShowMessageParams {
+                        message,
+                        typ: lsp_types::MessageType::INFO,
+                    },
+                );
+            }
+            FetchRepoMode::FetchOnDemand => {
+                // Don't fetch anything else until the user has answered.
+                self.is_fetching_repos = true;
+                let message = format!(
+                    "External repositories are missing: {}. Fetch them with `bazel fetch`?",
+                    format_repos(&repos)
+                );
+                self.send_request_with::<lsp_types::request::ShowMessageRequest>(
+                    lsp_types::ShowMessageRequestParams {
+                        typ: lsp_types::MessageType::INFO,
+                        message,
+                        actions: Some(vec![
+                            lsp_types::MessageActionItem {
+                                title: FETCH_REPOS_ACTION.to_string(),
+                                properties: Default::default(),
+                            },
+                            lsp_types::MessageActionItem {
+                                title: "Not now".to_string(),
+                                properties: Default::default(),
+                            },
+                        ]),
+                    },
+                    OnResponse::FetchRepos { repos, files },
+                );
             }
+        }
+    }
 
-            sender
-                .send(Task::FetchExternalRepos(FetchExternalReposProgress::End(
-                    files,
-                )))
-                .unwrap();
-        });
+    fn spawn_fetch_repos(
+        &mut self,
+        repos: FxHashSet<String>,
+        files: FxHashSet<FileId>,
+        mode: FetchRepoMode,
+    ) {
+        let bazel_client = self.bazel_client.clone();
+        self.is_fetching_repos = true;
+        self.task_pool_handle
+            .spawn_background_with_sender(move |sender| {
+                sender
+                    .send(Task::FetchExternalRepos(FetchExternalReposProgress::Begin(
+                        repos.clone(),
+                    )))
+                    .unwrap();
+
+                let mut failed = FxHashSet::default();
+                for repo in &repos {
+                    eprintln!("server: fetching external repository \"@@{}\"", repo);
+                    let res = match mode {
+                        FetchRepoMode::FetchOnDemand => bazel_client.fetch_external_repo(repo),
+                        _ => bazel_client.null_query_external_repo_targets(repo),
+                    };
+                    if let Err(err) = res {
+                        eprintln!(
+                            "server: failed to fetch external repository \"@@{}\": {}",
+                            repo, err
+                        );
+                        failed.insert(repo.clone());
+                    }
+                }
+
+                sender
+                    .send(Task::FetchExternalRepos(FetchExternalReposProgress::End {
+                        files,
+                        failed,
+                    }))
+                    .unwrap();
+            });
     }
 }
 
+/// Formats a set of repository names as a sorted, comma-separated list of quoted names.
+pub(crate) fn format_repos(repos: &FxHashSet<String>) -> String {
+    let mut repos = repos.iter().collect::<Vec<_>>();
+    repos.sort();
+    repos
+        .into_iter()
+        .map(|repo| format!("\"{}\"", repo))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
 impl panic::RefUnwindSafe for ServerSnapshot {}
 
 pub(crate) fn load_bazel_builtins() -> anyhow::Result<Builtins> {
@@ -310,6 +1034,67 @@ pub(crate) fn load_bazel_builtins() -> anyhow::Result<Builtins> {
     Ok(builtins)
 }
 
+fn load_workspace_words(workspace: &Path) -> Vec<String> {
+    fs::read_to_string(workspace.join(WORKSPACE_WORDS_FILE))
+        .map(|contents| {
+            contents
+                .lines()
+                .map(|line| line.trim().to_lowercase())
+                .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Loads the Buildifier tables given by `--buildifier_tables` and `--buildifier_add_tables`, or
+/// returns `None` if neither was given. A file that can't be loaded is skipped, as if the flag
+/// naming it wasn't passed.
+fn load_buildifier_tables(
+    workspace: &Path,
+    tables_path: Option<&str>,
+    add_tables_path: Option<&str>,
+) -> Option<BuildifierTables> {
+    let load = |path: &str| {
+        let tables = fs::read_to_string(workspace.join(path))
+            .map_err(anyhow::Error::from)
+            .and_then(|contents| Ok(BuildifierTables::parse(&contents)?));
+        match tables {
+            Ok(tables) => Some(tables),
+            Err(err) => {
+                eprintln!(
+                    "server: failed to load Buildifier tables from {:?}: {}",
+                    path, err
+                );
+                None
+            }
+        }
+    };
+    match (tables_path.and_then(load), add_tables_path.and_then(load)) {
+        (None, None) => None,
+        (tables, add_tables) => {
+            let mut tables = tables.unwrap_or_default();
+            if let Some(add_tables) = add_tables {
+                tables.merge(add_tables);
+            }
+            Some(tables)
+        }
+    }
+}
+
+fn load_module_lockfile(workspace: &Path) -> Option<ModuleLockfile> {
+    let path = workspace.join(MODULE_LOCKFILE);
+    if !path.exists() {
+        return None;
+    }
+    match ModuleLockfile::load(&path) {
+        Ok(lockfile) => Some(lockfile),
+        Err(err) => {
+            eprintln!("server: failed to load {}: {}", MODULE_LOCKFILE, err);
+            None
+        }
+    }
+}
+
 pub(crate) fn load_bazel_build_language(client: &dyn BazelClient) -> anyhow::Result<Builtins> {
     let build_language_output = client.build_language()?;
     decode_rules(&build_language_output)
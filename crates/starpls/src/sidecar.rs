@@ -0,0 +1,478 @@
+//! Support for check sidecars, which let teams write their own checks, e.g. for conventions
+//! specific to their `BUILD` files, in any language. A sidecar is a subprocess, configured with
+//! `--check_sidecar`, that's sent a summary of each file whenever the file's diagnostics are
+//! computed, and whose diagnostics are reported alongside the server's own.
+//!
+//! The server talks to the sidecar with JSON-RPC 2.0 over the sidecar's stdin and stdout, one
+//! message per line. Files are sent as `starpls/check` requests:
+//!
+//! ```json
+//! {
+//!   "path": "/workspace/app/BUILD.bazel",
+//!   "dialect": "bazel",
+//!   "api_context": "build",
+//!   "contents": "go_binary(\n    name = \"server\",\n ...",
+//!   "symbols": [],
+//!   "targets": [
+//!     {
+//!       "name": "server",
+//!       "kind": "go_binary",
+//!       "is_macro": false,
+//!       "attributes": [{ "name": "srcs", "value": ["main.go"] }],
+//!       "span": { "start_line": 0, "start_col": 0, "end_line": 3, "end_col": 1 }
+//!     }
+//!   ]
+//! }
+//! ```
+//!
+//! `symbols` lists the top-level definitions of `.bzl` files, with their types as rendered in
+//! hovers. Attribute values that aren't literals, e.g. `select()`s, are sent as
+//! `{ "expr": "select(...)" }`. The sidecar responds with the problems it found:
+//!
+//! ```json
+//! {
+//!   "diagnostics": [
+//!     {
+//!       "span": { "start_line": 1, "start_col": 11, "end_line": 1, "end_col": 19 },
+//!       "severity": "warning",
+//!       "message": "Binaries must be named after their package",
+//!       "code": "acme-binary-name"
+//!     }
+//!   ]
+//! }
+//! ```
+//!
+//! Spans are zero-based, with columns in UTF-8 bytes. Since summaries come from the server's
+//! analysis, sidecars don't have to parse Starlark or resolve `load`s themselves, and files are
+//! only sent again when their summaries change. Sidecars should exit when their stdin is closed.
+
+use std::{
+    hash::{Hash, Hasher},
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    process::{Child, ChildStdin, Command, Stdio},
+    thread,
+    time::Duration,
+};
+
+use anyhow::{anyhow, bail};
+use crossbeam_channel::{Receiver, RecvTimeoutError};
+use line_index::{LineCol, LineIndex};
+use parking_lot::Mutex;
+use rustc_hash::{FxHashMap, FxHasher};
+use serde::{Deserialize, Serialize};
+use starpls_bazel::APIContext;
+use starpls_common::{Dialect, FileId};
+use starpls_ide::{AnalysisSnapshot, Cancellable, TargetAttributeValue};
+use starpls_syntax::TextRange;
+
+use crate::{
+    convert::{self, FileLineIndex, PositionEncoding},
+    symbols::{module_symbol_kind_name, Span},
+};
+
+/// How long to wait for the sidecar to check a file before restarting it.
+const CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How many times the sidecar is restarted after crashing or timing out before giving up on it.
+const MAX_RESTARTS: usize = 3;
+
+/// The file being checked, as sent to the sidecar.
+pub(crate) struct CheckedFile<'a> {
+    pub(crate) path: &'a Path,
+    pub(crate) dialect: Dialect,
+    pub(crate) api_context: Option<APIContext>,
+    pub(crate) contents: &'a str,
+}
+
+#[derive(Serialize)]
+struct CheckParams<'a> {
+    path: String,
+    dialect: &'static str,
+    api_context: Option<&'static str>,
+    contents: &'a str,
+    symbols: Vec<SymbolSummary>,
+    targets: Vec<TargetSummary>,
+}
+
+#[derive(Serialize)]
+struct SymbolSummary {
+    name: String,
+    kind: &'static str,
+    #[serde(rename = "type")]
+    ty: String,
+    span: Span,
+}
+
+#[derive(Serialize)]
+struct TargetSummary {
+    name: String,
+    kind: String,
+    is_macro: bool,
+    attributes: Vec<AttributeSummary>,
+    span: Span,
+}
+
+#[derive(Serialize)]
+struct AttributeSummary {
+    name: String,
+    value: serde_json::Value,
+}
+
+#[derive(Deserialize)]
+struct Response {
+    id: Option<u64>,
+    result: Option<CheckResult>,
+    error: Option<ResponseError>,
+}
+
+#[derive(Deserialize)]
+struct ResponseError {
+    message: String,
+}
+
+#[derive(Deserialize)]
+struct CheckResult {
+    #[serde(default)]
+    diagnostics: Vec<SidecarDiagnostic>,
+}
+
+#[derive(Clone, Deserialize)]
+struct SidecarDiagnostic {
+    span: Span,
+    #[serde(default)]
+    severity: SidecarSeverity,
+    message: String,
+    code: Option<String>,
+}
+
+#[derive(Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum SidecarSeverity {
+    Error,
+    #[default]
+    Warning,
+    Information,
+    Hint,
+}
+
+enum SidecarState {
+    NotStarted,
+    Running(SidecarProcess),
+    Failed,
+}
+
+struct SidecarProcess {
+    child: Child,
+    stdin: ChildStdin,
+    responses: Receiver<String>,
+    next_id: u64,
+}
+
+impl Drop for SidecarProcess {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+pub(crate) struct CheckSidecar {
+    program: PathBuf,
+    args: Vec<String>,
+    workspace: PathBuf,
+    state: Mutex<SidecarState>,
+    restarts: Mutex<usize>,
+    /// The diagnostics last reported for each file, along with the hash of the request they were
+    /// reported for.
+    cache: Mutex<FxHashMap<FileId, (u64, Vec<SidecarDiagnostic>)>>,
+}
+
+impl CheckSidecar {
+    pub(crate) fn new(program: &str, args: Vec<String>, workspace: PathBuf) -> Self {
+        // Programs given as paths are resolved against the workspace, while bare names are looked
+        // up on the `PATH`.
+        let program = if program.contains('/') {
+            workspace.join(program)
+        } else {
+            PathBuf::from(program)
+        };
+        Self {
+            program,
+            args,
+            workspace,
+            state: Mutex::new(SidecarState::NotStarted),
+            restarts: Mutex::new(0),
+            cache: Default::default(),
+        }
+    }
+
+    /// Checks a file with the sidecar. Failures are logged rather than reported, so that a broken
+    /// sidecar doesn't affect the server's own diagnostics.
+    pub(crate) fn diagnostics(
+        &self,
+        snapshot: &AnalysisSnapshot,
+        file_id: FileId,
+        file: CheckedFile,
+        line_index: FileLineIndex,
+        encoding: PositionEncoding,
+    ) -> Cancellable<Vec<lsp_types::Diagnostic>> {
+        let params = CheckParams {
+            path: file.path.to_string_lossy().to_string(),
+            dialect: match file.dialect {
+                Dialect::Standard => "standard",
+                Dialect::Bazel => "bazel",
+                Dialect::Buck2 => "buck2",
+            },
+            api_context: file.api_context.map(|api_context| match api_context {
+                APIContext::Bzl => "bzl",
+                APIContext::Build => "build",
+                APIContext::Module => "module",
+                APIContext::Repo => "repo",
+                APIContext::Workspace => "workspace",
+            }),
+            contents: file.contents,
+            symbols: snapshot
+                .module_symbols(file_id)?
+                .unwrap_or_default()
+                .into_iter()
+                .map(|symbol| SymbolSummary {
+                    name: symbol.name,
+                    kind: module_symbol_kind_name(symbol.kind),
+                    ty: symbol.ty,
+                    span: span_from_text_range(&line_index, symbol.range),
+                })
+                .collect(),
+            targets: snapshot
+                .targets(file_id)?
+                .unwrap_or_default()
+                .into_iter()
+                .map(|target| TargetSummary {
+                    name: target.name,
+                    kind: target.kind.to_string(),
+                    is_macro: target.is_macro,
+                    attributes: target
+                        .attributes
+                        .into_iter()
+                        .map(|attr| AttributeSummary {
+                            name: attr.name.to_string(),
+                            value: attribute_value_to_json(attr.value),
+                        })
+                        .collect(),
+                    span: span_from_text_range(&line_index, target.range),
+                })
+                .collect(),
+        };
+        let params = match serde_json::to_string(&params) {
+            Ok(params) => params,
+            Err(_) => return Ok(Vec::new()),
+        };
+
+        let mut hasher = FxHasher::default();
+        params.hash(&mut hasher);
+        let hash = hasher.finish();
+
+        let cached = self
+            .cache
+            .lock()
+            .get(&file_id)
+            .filter(|(cached_hash, _)| *cached_hash == hash)
+            .map(|(_, diagnostics)| diagnostics.clone());
+        let diagnostics = match cached {
+            Some(diagnostics) => diagnostics,
+            None => match self.check(&params) {
+                Ok(Some(diagnostics)) => {
+                    self.cache
+                        .lock()
+                        .insert(file_id, (hash, diagnostics.clone()));
+                    diagnostics
+                }
+                Ok(None) => Vec::new(),
+                Err(err) => {
+                    eprintln!(
+                        "server: check sidecar failed to check {:?}: {}",
+                        file.path, err
+                    );
+                    Vec::new()
+                }
+            },
+        };
+
+        let source = self
+            .program
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string());
+        Ok(diagnostics
+            .into_iter()
+            .filter_map(|diagnostic| {
+                let range = text_range_from_span(&line_index, &diagnostic.span)?;
+                Some(lsp_types::Diagnostic {
+                    range: convert::lsp_range_from_text_range(range, line_index, encoding)?,
+                    severity: Some(match diagnostic.severity {
+                        SidecarSeverity::Error => lsp_types::DiagnosticSeverity::ERROR,
+                        SidecarSeverity::Warning => lsp_types::DiagnosticSeverity::WARNING,
+                        SidecarSeverity::Information => lsp_types::DiagnosticSeverity::INFORMATION,
+                        SidecarSeverity::Hint => lsp_types::DiagnosticSeverity::HINT,
+                    }),
+                    code: diagnostic.code.map(lsp_types::NumberOrString::String),
+                    code_description: None,
+                    source: source.clone(),
+                    message: diagnostic.message,
+                    related_information: None,
+                    tags: None,
+                    data: None,
+                })
+            })
+            .collect())
+    }
+
+    /// Sends a file to the sidecar, starting it if needed. Returns `None` if the sidecar has
+    /// failed too many times to be used.
+    fn check(&self, params: &str) -> anyhow::Result<Option<Vec<SidecarDiagnostic>>> {
+        let mut state = self.state.lock();
+        if let SidecarState::NotStarted = *state {
+            *state = match self.spawn() {
+                Ok(process) => SidecarState::Running(process),
+                Err(err) => {
+                    eprintln!(
+                        "server: failed to start check sidecar {:?}: {}",
+                        self.program, err
+                    );
+                    SidecarState::Failed
+                }
+            };
+        }
+        let process = match &mut *state {
+            SidecarState::Running(process) => process,
+            _ => return Ok(None),
+        };
+
+        process.next_id += 1;
+        let id = process.next_id;
+        let res = writeln!(
+            process.stdin,
+            r#"{{"jsonrpc":"2.0","id":{},"method":"starpls/check","params":{}}}"#,
+            id, params
+        )
+        .and_then(|_| process.stdin.flush());
+        if let Err(err) = res {
+            self.restart(&mut state);
+            bail!("failed to send request: {}", err);
+        }
+
+        loop {
+            let line = match process.responses.recv_timeout(CHECK_TIMEOUT) {
+                Ok(line) => line,
+                Err(err) => {
+                    self.restart(&mut state);
+                    return Err(match err {
+                        RecvTimeoutError::Timeout => anyhow!("timed out"),
+                        RecvTimeoutError::Disconnected => anyhow!("sidecar exited"),
+                    });
+                }
+            };
+
+            // Skip anything that isn't the response to this request, e.g. logging that was
+            // mistakenly written to stdout.
+            let response = match serde_json::from_str::<Response>(&line) {
+                Ok(response) if response.id == Some(id) => response,
+                _ => continue,
+            };
+            if let Some(error) = response.error {
+                bail!("{}", error.message);
+            }
+            return Ok(Some(
+                response
+                    .result
+                    .map(|result| result.diagnostics)
+                    .unwrap_or_default(),
+            ));
+        }
+    }
+
+    fn restart(&self, state: &mut SidecarState) {
+        let mut restarts = self.restarts.lock();
+        *restarts += 1;
+        *state = if *restarts > MAX_RESTARTS {
+            eprintln!(
+                "server: check sidecar {:?} failed {} times, disabling it",
+                self.program, *restarts
+            );
+            SidecarState::Failed
+        } else {
+            SidecarState::NotStarted
+        };
+    }
+
+    fn spawn(&self) -> anyhow::Result<SidecarProcess> {
+        let mut child = Command::new(&self.program)
+            .args(&self.args)
+            .current_dir(&self.workspace)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()?;
+        let stdin = child
+            .stdin
+            .take()
+            .ok_or_else(|| anyhow!("failed to open stdin"))?;
+        let stdout = child
+            .stdout
+            .take()
+            .ok_or_else(|| anyhow!("failed to open stdout"))?;
+
+        // Read responses on a separate thread, so that requests can time out.
+        let (sender, responses) = crossbeam_channel::unbounded();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                match line {
+                    Ok(line) if sender.send(line).is_ok() => {}
+                    _ => break,
+                }
+            }
+        });
+
+        Ok(SidecarProcess {
+            child,
+            stdin,
+            responses,
+            next_id: 0,
+        })
+    }
+}
+
+fn attribute_value_to_json(value: TargetAttributeValue) -> serde_json::Value {
+    match value {
+        TargetAttributeValue::String(s) => s.to_string().into(),
+        TargetAttributeValue::StringList(values) => values
+            .into_iter()
+            .map(|value| value.to_string())
+            .collect::<Vec<_>>()
+            .into(),
+        TargetAttributeValue::Bool(b) => b.into(),
+        TargetAttributeValue::Int(n) => n.into(),
+        TargetAttributeValue::Other(expr) => serde_json::json!({ "expr": expr }),
+    }
+}
+
+fn span_from_text_range(line_index: &LineIndex, range: TextRange) -> Span {
+    let start = line_index.line_col(range.start());
+    let end = line_index.line_col(range.end());
+    Span {
+        start_line: start.line,
+        start_col: start.col,
+        end_line: end.line,
+        end_col: end.col,
+    }
+}
+
+fn text_range_from_span(line_index: &LineIndex, span: &Span) -> Option<TextRange> {
+    let start = line_index.offset(LineCol {
+        line: span.start_line,
+        col: span.start_col,
+    })?;
+    let end = line_index.offset(LineCol {
+        line: span.end_line,
+        col: span.end_col,
+    })?;
+    (start <= end).then(|| TextRange::new(start, end))
+}
@@ -0,0 +1,181 @@
+//! Runs the parser over the test files of the Starlark spec's conformance suite, e.g. the
+//! `testdata` directories of `starlark-go`, and reports how much of the suite it accepts.
+//!
+//! The test files are split into chunks by lines containing only `---`, and comments starting
+//! with `###` mark the lines on which a chunk is expected to fail. starpls doesn't bundle a
+//! Starlark interpreter, so only the static half of the suite is checked: chunks without expected
+//! failures must parse cleanly, and chunks with expected failures pass if a syntax error is
+//! reported on one of the marked lines. The remaining chunks are counted as unchecked, since
+//! their failures usually happen at runtime.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, bail};
+use serde::Serialize;
+use starpls_common::read_source;
+use starpls_syntax::{line_index, parse_module};
+
+use crate::symbols::OutputFormat;
+
+#[derive(Serialize)]
+struct FileOutput {
+    path: String,
+    passed: usize,
+    /// Chunks whose failures can only be observed by running them.
+    unchecked: usize,
+    failures: Vec<Failure>,
+}
+
+/// A syntax error reported in a chunk that doesn't expect one on that line, at a one-based line
+/// within its file.
+#[derive(Serialize)]
+struct Failure {
+    line: u32,
+    message: String,
+}
+
+enum Outcome {
+    Passed,
+    Unchecked,
+    Failed(Failure),
+}
+
+pub(crate) fn run_spec_test(path: String, format: OutputFormat) -> anyhow::Result<()> {
+    let dir = PathBuf::from(&path)
+        .canonicalize()
+        .map_err(|_| anyhow!("Could not resolve the directory {:?}.", path))?;
+    let mut paths = Vec::new();
+    collect_test_files(&dir, &mut paths)?;
+    paths.sort();
+
+    let mut outputs = Vec::new();
+    for path in &paths {
+        let contents = read_source(path)?.text;
+        let mut output = FileOutput {
+            path: path
+                .strip_prefix(&dir)
+                .unwrap_or(path)
+                .to_string_lossy()
+                .to_string(),
+            passed: 0,
+            unchecked: 0,
+            failures: Vec::new(),
+        };
+        for (start_line, chunk) in chunks(&contents) {
+            match check_chunk(&chunk) {
+                Outcome::Passed => output.passed += 1,
+                Outcome::Unchecked => output.unchecked += 1,
+                Outcome::Failed(failure) => output.failures.push(Failure {
+                    line: start_line + failure.line,
+                    message: failure.message,
+                }),
+            }
+        }
+        outputs.push(output);
+    }
+
+    let passed = outputs.iter().map(|output| output.passed).sum::<usize>();
+    let failed = outputs
+        .iter()
+        .map(|output| output.failures.len())
+        .sum::<usize>();
+    let unchecked = outputs.iter().map(|output| output.unchecked).sum::<usize>();
+    match format {
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&outputs)?),
+        OutputFormat::Text => {
+            for output in &outputs {
+                println!(
+                    "{}: {} passed, {} failed, {} unchecked",
+                    output.path,
+                    output.passed,
+                    output.failures.len(),
+                    output.unchecked
+                );
+                for failure in &output.failures {
+                    println!("  {}:{}: {}", output.path, failure.line, failure.message);
+                }
+            }
+            let checked = passed + failed;
+            println!(
+                "Checked {} chunks in {} files: {} passed ({:.1}%), {} failed, {} unchecked",
+                checked,
+                outputs.len(),
+                passed,
+                if checked == 0 {
+                    100.0
+                } else {
+                    passed as f64 * 100.0 / checked as f64
+                },
+                failed,
+                unchecked
+            );
+        }
+    }
+    if failed > 0 {
+        bail!("{} chunks failed", failed);
+    }
+    Ok(())
+}
+
+fn collect_test_files(dir: &Path, paths: &mut Vec<PathBuf>) -> anyhow::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_test_files(&path, paths)?;
+        } else if path.extension().map_or(false, |ext| ext == "star") {
+            paths.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Splits a test file into its chunks, along with the number of lines before each chunk.
+fn chunks(contents: &str) -> Vec<(u32, String)> {
+    let mut chunks = Vec::new();
+    let mut start_line = 0;
+    let mut chunk = String::new();
+    for (line_number, line) in contents.lines().enumerate() {
+        if line.trim_end() == "---" {
+            chunks.push((start_line, std::mem::take(&mut chunk)));
+            start_line = line_number as u32 + 1;
+        } else {
+            chunk.push_str(line);
+            chunk.push('\n');
+        }
+    }
+    chunks.push((start_line, chunk));
+    chunks
+}
+
+/// Parses a chunk and compares the syntax errors against the lines marked with `###`. The line of
+/// a failure is one-based, relative to the start of the chunk.
+fn check_chunk(chunk: &str) -> Outcome {
+    let mut errors = Vec::new();
+    parse_module(chunk, &mut |err| errors.push(err));
+    let line_index = line_index(chunk);
+    let errors = errors
+        .into_iter()
+        .map(|err| (line_index.line_col(err.range.start()).line, err.message))
+        .collect::<Vec<_>>();
+    let expected_lines = chunk
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| line.contains("###"))
+        .map(|(line_number, _)| line_number as u32)
+        .collect::<Vec<_>>();
+
+    if errors.iter().any(|(line, _)| expected_lines.contains(line)) {
+        return Outcome::Passed;
+    }
+    match errors.into_iter().next() {
+        Some((line, message)) => Outcome::Failed(Failure {
+            line: line + 1,
+            message,
+        }),
+        None if expected_lines.is_empty() => Outcome::Passed,
+        None => Outcome::Unchecked,
+    }
+}
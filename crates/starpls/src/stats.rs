@@ -0,0 +1,242 @@
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use starpls_bazel::APIContext;
+use starpls_common::Severity;
+
+use crate::{check::create_analysis, document, impact::add_workspace_files, symbols::OutputFormat};
+
+#[derive(Serialize)]
+struct Stats {
+    packages: usize,
+    bzl_files: usize,
+    other_files: usize,
+    targets: usize,
+    targets_by_kind: Vec<KindCount>,
+    load_edges: usize,
+    syntax_errors: usize,
+    files_with_syntax_errors: usize,
+    errors: usize,
+    warnings: usize,
+    timings: Timings,
+}
+
+#[derive(Serialize)]
+struct KindCount {
+    kind: String,
+    count: usize,
+}
+
+/// Wall-clock durations of each analysis phase, in milliseconds.
+#[derive(Serialize)]
+struct Timings {
+    load_ms: u128,
+    parse_ms: u128,
+    index_ms: u128,
+    typecheck_ms: u128,
+}
+
+/// Reports statistics about the Starlark files in the current workspace, e.g. the number of
+/// packages and targets, along with how long each analysis phase took.
+pub(crate) fn run_stats(format: OutputFormat, output_base: Option<String>) -> anyhow::Result<()> {
+    let (mut analysis, interner, workspace) = create_analysis(output_base)?;
+    let workspace = workspace.canonicalize().unwrap_or(workspace);
+
+    let start = Instant::now();
+    add_workspace_files(&mut analysis, &interner, &workspace)?;
+    let load_time = start.elapsed();
+
+    let mut entries = interner.entries();
+    entries.sort_by_key(|(file_id, _)| *file_id);
+    let mut build_file_ids = Vec::new();
+    let mut bzl_files = 0;
+    let mut other_files = 0;
+    for (file_id, path) in &entries {
+        match document::dialect_and_api_context_for_path(path) {
+            Some((_, Some(APIContext::Build))) => build_file_ids.push(*file_id),
+            Some((_, Some(APIContext::Bzl))) => bzl_files += 1,
+            _ => other_files += 1,
+        }
+    }
+
+    let snap = analysis.snapshot();
+    let start = Instant::now();
+    let mut load_edges = 0;
+    let mut syntax_errors = 0;
+    let mut files_with_syntax_errors = 0;
+    for (file_id, _) in &entries {
+        let stats = snap.file_stats(*file_id)?.unwrap_or_default();
+        load_edges += stats.resolved_load_count;
+        syntax_errors += stats.syntax_error_count;
+        if stats.syntax_error_count > 0 {
+            files_with_syntax_errors += 1;
+        }
+    }
+    let parse_time = start.elapsed();
+
+    let start = Instant::now();
+    let index = snap.target_index(&build_file_ids)?;
+    let index_time = start.elapsed();
+
+    let start = Instant::now();
+    let mut errors = 0;
+    let mut warnings = 0;
+    for (file_id, _) in &entries {
+        for diagnostic in snap.diagnostics(*file_id)? {
+            match diagnostic.severity {
+                Severity::Error => errors += 1,
+                Severity::Warning => warnings += 1,
+                Severity::Hint => {}
+            }
+        }
+    }
+    let typecheck_time = start.elapsed();
+
+    let targets_by_kind = index
+        .kinds()
+        .into_iter()
+        .map(|(kind, count)| KindCount {
+            kind: kind.to_string(),
+            count,
+        })
+        .collect::<Vec<_>>();
+    let stats = Stats {
+        packages: build_file_ids.len(),
+        bzl_files,
+        other_files,
+        targets: targets_by_kind.iter().map(|kind| kind.count).sum(),
+        targets_by_kind,
+        load_edges,
+        syntax_errors,
+        files_with_syntax_errors,
+        errors,
+        warnings,
+        timings: Timings {
+            load_ms: load_time.as_millis(),
+            parse_ms: parse_time.as_millis(),
+            index_ms: index_time.as_millis(),
+            typecheck_ms: typecheck_time.as_millis(),
+        },
+    };
+
+    match format {
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&stats)?),
+        OutputFormat::Text => {
+            println!("Packages: {}", stats.packages);
+            println!(".bzl files: {}", stats.bzl_files);
+            println!("Other Starlark files: {}", stats.other_files);
+            println!("Load edges: {}", stats.load_edges);
+            println!(
+                "Syntax errors: {} (in {} files)",
+                stats.syntax_errors, stats.files_with_syntax_errors
+            );
+            println!("Errors: {}", stats.errors);
+            println!("Warnings: {}", stats.warnings);
+            println!("Targets: {}", stats.targets);
+            for KindCount { kind, count } in &stats.targets_by_kind {
+                println!("  {} {}", count, kind);
+            }
+            println!("Timings:");
+            print_timing("Loading", load_time);
+            print_timing("Parsing", parse_time);
+            print_timing("Indexing", index_time);
+            print_timing("Type checking", typecheck_time);
+        }
+    }
+
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct LoadHotspotOutput {
+    path: String,
+    build_files: usize,
+    direct_loaders: usize,
+    symbols: Vec<SymbolLoads>,
+    rarely_used: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct SymbolLoads {
+    name: String,
+    loaders: usize,
+}
+
+/// Reports the `.bzl` files in the current workspace that at least `min_loaders` `BUILD` files
+/// load, but that export symbols few of those files use. Splitting the rarely used symbols into
+/// a separate file keeps the other packages from having to evaluate them.
+pub(crate) fn run_load_hotspots(
+    min_loaders: usize,
+    format: OutputFormat,
+    output_base: Option<String>,
+) -> anyhow::Result<()> {
+    let (mut analysis, interner, workspace) = create_analysis(output_base)?;
+    let workspace = workspace.canonicalize().unwrap_or(workspace);
+    add_workspace_files(&mut analysis, &interner, &workspace)?;
+
+    let file_ids = interner
+        .entries()
+        .into_iter()
+        .filter(|(_, path)| path.starts_with(&workspace))
+        .map(|(file_id, _)| file_id)
+        .collect::<Vec<_>>();
+    let outputs = analysis
+        .snapshot()
+        .load_hotspots(&file_ids, min_loaders)?
+        .into_iter()
+        .map(|hotspot| {
+            let path = interner.lookup_by_file_id(hotspot.file_id);
+            LoadHotspotOutput {
+                path: path
+                    .strip_prefix(&workspace)
+                    .unwrap_or(&path)
+                    .to_string_lossy()
+                    .to_string(),
+                build_files: hotspot.build_file_count,
+                direct_loaders: hotspot.direct_loader_count,
+                symbols: hotspot
+                    .symbols
+                    .into_iter()
+                    .map(|(name, loaders)| SymbolLoads { name, loaders })
+                    .collect(),
+                rarely_used: hotspot.rarely_used,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    match format {
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&outputs)?),
+        OutputFormat::Text => {
+            if outputs.is_empty() {
+                println!(
+                    "No .bzl files loaded by at least {} BUILD files export rarely used symbols.",
+                    min_loaders
+                );
+            }
+            for output in outputs {
+                println!(
+                    "{}: loaded by {} BUILD files ({} direct loaders)",
+                    output.path, output.build_files, output.direct_loaders
+                );
+                for SymbolLoads { name, loaders } in &output.symbols {
+                    let marker = if output.rarely_used.contains(name) {
+                        " (rarely used)"
+                    } else {
+                        ""
+                    };
+                    println!("  {} loaders: {}{}", loaders, name, marker);
+                }
+                println!(
+                    "  Consider moving {} into a separate file.",
+                    output.rarely_used.join(", ")
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn print_timing(phase: &str, duration: Duration) {
+    println!("  {}: {:.2?}", phase, duration);
+}
@@ -0,0 +1,237 @@
+use std::{fs, path::PathBuf};
+
+use anyhow::anyhow;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use starpls_common::read_source;
+use starpls_ide::{Change, ModuleSymbolKind, RuleSchema};
+
+use crate::{check::create_analysis, document};
+
+#[derive(Clone, Copy, Default, ValueEnum)]
+pub(crate) enum OutputFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+#[derive(Serialize)]
+struct FileSymbols {
+    path: String,
+    symbols: Vec<Symbol>,
+}
+
+#[derive(Serialize)]
+struct Symbol {
+    name: String,
+    kind: &'static str,
+    #[serde(rename = "type")]
+    ty: String,
+    doc: Option<String>,
+    span: Span,
+}
+
+/// A zero-based, UTF-8 span within a file.
+#[derive(Clone, Serialize, Deserialize)]
+pub(crate) struct Span {
+    pub(crate) start_line: u32,
+    pub(crate) start_col: u32,
+    pub(crate) end_line: u32,
+    pub(crate) end_col: u32,
+}
+
+pub(crate) fn module_symbol_kind_name(kind: ModuleSymbolKind) -> &'static str {
+    match kind {
+        ModuleSymbolKind::Function => "function",
+        ModuleSymbolKind::Provider => "provider",
+        ModuleSymbolKind::Rule => "rule",
+        ModuleSymbolKind::Variable => "variable",
+    }
+}
+
+/// Dumps all top-level definitions in the given file, or in all Starlark files directly
+/// contained by the given package directory.
+pub(crate) fn run_symbols(
+    path: String,
+    format: OutputFormat,
+    output_base: Option<String>,
+) -> anyhow::Result<()> {
+    let err = || anyhow!("Could not resolve the path {:?} as a Starlark file.", path);
+    let resolved = PathBuf::from(&path).canonicalize().map_err(|_| err())?;
+    let mut paths = if resolved.is_dir() {
+        fs::read_dir(&resolved)?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| {
+                path.is_file() && document::dialect_and_api_context_for_path(path).is_some()
+            })
+            .collect::<Vec<_>>()
+    } else {
+        vec![resolved]
+    };
+    paths.sort();
+
+    let (mut analysis, interner, workspace) = create_analysis(output_base)?;
+    let workspace = workspace.canonicalize().unwrap_or(workspace);
+    let mut change = Change::default();
+    let mut file_ids = Vec::new();
+
+    for path in paths {
+        let (dialect, api_context) =
+            document::dialect_and_api_context_for_path(&path).ok_or_else(err)?;
+        let contents = read_source(&path).map_err(|_| err())?.text;
+        let file_id = interner.intern_path(path);
+        change.create_file(file_id, dialect, api_context, contents);
+        file_ids.push(file_id);
+    }
+
+    analysis.apply_change(change);
+
+    let snap = analysis.snapshot();
+    let mut files = Vec::new();
+    for file_id in file_ids {
+        let line_index = match snap.line_index(file_id)? {
+            Some(line_index) => line_index,
+            None => continue,
+        };
+        let path = interner.lookup_by_file_id(file_id);
+        let path = path.strip_prefix(&workspace).unwrap_or(&path);
+        let symbols = snap
+            .module_symbols(file_id)?
+            .unwrap_or_default()
+            .into_iter()
+            .map(|symbol| {
+                let start = line_index.line_col(symbol.range.start());
+                let end = line_index.line_col(symbol.range.end());
+                Symbol {
+                    name: symbol.name,
+                    kind: module_symbol_kind_name(symbol.kind),
+                    ty: symbol.ty,
+                    doc: symbol.doc,
+                    span: Span {
+                        start_line: start.line,
+                        start_col: start.col,
+                        end_line: end.line,
+                        end_col: end.col,
+                    },
+                }
+            })
+            .collect();
+        files.push(FileSymbols {
+            path: path.to_string_lossy().to_string(),
+            symbols,
+        });
+    }
+
+    match format {
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&files)?),
+        OutputFormat::Text => {
+            for file in files {
+                for symbol in file.symbols {
+                    println!(
+                        "{}:{}:{} - {} {}: {}",
+                        file.path,
+                        symbol.span.start_line + 1,
+                        symbol.span.start_col + 1,
+                        symbol.kind,
+                        symbol.name,
+                        symbol.ty
+                    );
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct RuleAttributeOutput {
+    name: String,
+    #[serde(rename = "type")]
+    ty: String,
+    doc: Option<String>,
+    mandatory: bool,
+    default_value: Option<String>,
+}
+
+#[derive(Serialize)]
+struct RuleSchemaOutput {
+    name: String,
+    doc: Option<String>,
+    attributes: Vec<RuleAttributeOutput>,
+}
+
+impl From<RuleSchema> for RuleSchemaOutput {
+    fn from(schema: RuleSchema) -> Self {
+        Self {
+            name: schema.name,
+            doc: schema.doc,
+            attributes: schema
+                .attributes
+                .into_iter()
+                .map(|attr| RuleAttributeOutput {
+                    name: attr.name,
+                    ty: attr.ty,
+                    doc: attr.doc,
+                    mandatory: attr.mandatory,
+                    default_value: attr.default_value,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Prints the attribute schema of the rule with the given name, as visible from the given file.
+/// This works for both rules declared with `rule()` and native rules.
+pub(crate) fn run_attrs(
+    path: String,
+    rule: String,
+    format: OutputFormat,
+    output_base: Option<String>,
+) -> anyhow::Result<()> {
+    let err = || anyhow!("Could not resolve the path {:?} as a Starlark file.", path);
+    let resolved = PathBuf::from(&path).canonicalize().map_err(|_| err())?;
+    let (dialect, api_context) =
+        document::dialect_and_api_context_for_path(&resolved).ok_or_else(err)?;
+    let contents = read_source(&resolved).map_err(|_| err())?.text;
+
+    let (mut analysis, interner, _) = create_analysis(output_base)?;
+    let mut change = Change::default();
+    let file_id = interner.intern_path(resolved);
+    change.create_file(file_id, dialect, api_context, contents);
+    analysis.apply_change(change);
+
+    let schema: RuleSchemaOutput = analysis
+        .snapshot()
+        .rule_attributes(file_id, &rule)?
+        .ok_or_else(|| anyhow!("Could not find a rule named {:?} in {:?}.", rule, path))?
+        .into();
+
+    match format {
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&schema)?),
+        OutputFormat::Text => {
+            println!("{}", schema.name);
+            if let Some(doc) = &schema.doc {
+                println!("{}", doc);
+            }
+            for attr in schema.attributes {
+                print!("  {}: {}", attr.name, attr.ty);
+                if attr.mandatory {
+                    print!(" (mandatory)");
+                }
+                if let Some(default_value) = &attr.default_value {
+                    print!(" = {}", default_value);
+                }
+                println!();
+                if let Some(doc) = &attr.doc {
+                    for line in doc.lines() {
+                        println!("      {}", line.trim());
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,175 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use anyhow::anyhow;
+use serde::Serialize;
+use starpls_bazel::APIContext;
+use starpls_ide::{AnalysisSnapshot, IndexedTarget, TargetIndex, TargetQuery};
+
+use crate::{
+    check::create_analysis,
+    document::{self, PathInterner},
+    impact::add_workspace_files,
+    symbols::{OutputFormat, Span},
+};
+
+#[derive(Serialize)]
+struct TargetOutput {
+    label: String,
+    kind: String,
+    path: String,
+    is_macro: bool,
+    span: Span,
+}
+
+#[derive(Serialize)]
+struct KindOutput {
+    kind: String,
+    count: usize,
+}
+
+/// Lists the targets in the current workspace that are declared with the given rule or macro,
+/// optionally only those that set all of the given attributes. Without a kind, prints the number
+/// of targets declared with each kind instead.
+pub(crate) fn run_targets(
+    kind: Option<String>,
+    with_attrs: Vec<String>,
+    format: OutputFormat,
+    output_base: Option<String>,
+) -> anyhow::Result<()> {
+    let (snap, interner, workspace, index) = index_workspace(output_base)?;
+    let kind = match kind {
+        Some(kind) => kind,
+        None => {
+            let kinds = index
+                .kinds()
+                .into_iter()
+                .map(|(kind, count)| KindOutput {
+                    kind: kind.to_string(),
+                    count,
+                })
+                .collect::<Vec<_>>();
+            match format {
+                OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&kinds)?),
+                OutputFormat::Text => {
+                    for KindOutput { kind, count } in kinds {
+                        println!("{} {}", count, kind);
+                    }
+                }
+            }
+            return Ok(());
+        }
+    };
+
+    let targets = index.targets_of_kind(&kind).iter().filter(|indexed| {
+        with_attrs.iter().all(|name| {
+            indexed
+                .target
+                .attributes
+                .iter()
+                .any(|attr| attr.name == name.as_str())
+        })
+    });
+    print_targets(&snap, &interner, &workspace, targets, format)
+}
+
+/// Lists the targets in the current workspace that match the given query. See
+/// [`TargetQuery`] for the query syntax.
+pub(crate) fn run_query(
+    query: String,
+    format: OutputFormat,
+    output_base: Option<String>,
+) -> anyhow::Result<()> {
+    let query =
+        TargetQuery::parse(&query).map_err(|err| anyhow!("Invalid query {:?}: {}", query, err))?;
+    let (snap, interner, workspace, index) = index_workspace(output_base)?;
+    print_targets(&snap, &interner, &workspace, index.query(&query), format)
+}
+
+/// Indexes the targets declared in every `BUILD` file in the current workspace.
+pub(crate) fn index_workspace(
+    output_base: Option<String>,
+) -> anyhow::Result<(AnalysisSnapshot, Arc<PathInterner>, PathBuf, TargetIndex)> {
+    let (mut analysis, interner, workspace) = create_analysis(output_base)?;
+    let workspace = workspace.canonicalize().unwrap_or(workspace);
+    add_workspace_files(&mut analysis, &interner, &workspace)?;
+
+    let file_ids = interner
+        .entries()
+        .into_iter()
+        .filter(|(_, path)| {
+            matches!(
+                document::dialect_and_api_context_for_path(path),
+                Some((_, Some(APIContext::Build)))
+            )
+        })
+        .map(|(file_id, _)| file_id)
+        .collect::<Vec<_>>();
+    let snap = analysis.snapshot();
+    let index = snap.target_index(&file_ids)?;
+    Ok((snap, interner, workspace, index))
+}
+
+fn print_targets<'a>(
+    snap: &AnalysisSnapshot,
+    interner: &PathInterner,
+    workspace: &Path,
+    targets: impl IntoIterator<Item = &'a IndexedTarget>,
+    format: OutputFormat,
+) -> anyhow::Result<()> {
+    let mut outputs = Vec::new();
+    for IndexedTarget { file_id, target } in targets {
+        let line_index = match snap.line_index(*file_id)? {
+            Some(line_index) => line_index,
+            None => continue,
+        };
+        let path = interner.lookup_by_file_id(*file_id);
+        let path = path.strip_prefix(workspace).unwrap_or(&path);
+        let package = path.parent().unwrap_or(Path::new(""));
+        let start = line_index.line_col(target.range.start());
+        let end = line_index.line_col(target.range.end());
+        outputs.push(TargetOutput {
+            label: format!(
+                "//{}:{}",
+                package.to_string_lossy().replace('\\', "/"),
+                target.name
+            ),
+            kind: target.kind.to_string(),
+            path: path.to_string_lossy().to_string(),
+            is_macro: target.is_macro,
+            span: Span {
+                start_line: start.line,
+                start_col: start.col,
+                end_line: end.line,
+                end_col: end.col,
+            },
+        });
+    }
+    outputs.sort_by(|a, b| {
+        (&a.path, a.span.start_line, a.span.start_col).cmp(&(
+            &b.path,
+            b.span.start_line,
+            b.span.start_col,
+        ))
+    });
+
+    match format {
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&outputs)?),
+        OutputFormat::Text => {
+            for target in outputs {
+                println!(
+                    "{}:{}:{} - {} {}",
+                    target.path,
+                    target.span.start_line + 1,
+                    target.span.start_col + 1,
+                    target.kind,
+                    target.label
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -29,7 +29,6 @@ impl<T> TaskPool<T> {
         })
     }
 
-    #[allow(unused)]
     fn spawn_with_sender<F>(&self, f: F)
     where
         T: Send + 'static,
@@ -42,14 +41,29 @@ impl<T> TaskPool<T> {
     }
 }
 
+/// Owns the pools that tasks are run on, along with the receiving end of the channel that their
+/// results are sent to.
+///
+/// Requests run on their own pool so that they can be served concurrently against a snapshot of
+/// the database, without waiting behind slower background work like computing diagnostics or
+/// fetching external repositories.
 pub(crate) struct TaskPoolHandle<T> {
     pub(crate) receiver: Receiver<T>,
     pool: TaskPool<T>,
+    background_pool: TaskPool<T>,
 }
 
 impl<T> TaskPoolHandle<T> {
-    pub(crate) fn new(receiver: Receiver<T>, pool: TaskPool<T>) -> Self {
-        Self { receiver, pool }
+    pub(crate) fn new(
+        receiver: Receiver<T>,
+        pool: TaskPool<T>,
+        background_pool: TaskPool<T>,
+    ) -> Self {
+        Self {
+            receiver,
+            pool,
+            background_pool,
+        }
     }
 
     pub(crate) fn spawn<F>(&self, f: F)
@@ -60,12 +74,19 @@ impl<T> TaskPoolHandle<T> {
         self.pool.spawn(f)
     }
 
-    #[allow(unused)]
-    pub(crate) fn spawn_with_sender<F>(&self, f: F)
+    pub(crate) fn spawn_background<F>(&self, f: F)
+    where
+        T: Send + 'static,
+        F: FnOnce() -> T + Send + 'static,
+    {
+        self.background_pool.spawn(f)
+    }
+
+    pub(crate) fn spawn_background_with_sender<F>(&self, f: F)
     where
         T: Send + 'static,
         F: FnOnce(Sender<T>) + Send + 'static,
     {
-        self.pool.spawn_with_sender(f)
+        self.background_pool.spawn_with_sender(f)
     }
 }
@@ -0,0 +1,195 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, bail};
+use clap::ValueEnum;
+use serde::Serialize;
+
+use crate::{check::create_analysis, impact::add_workspace_files};
+
+#[derive(Clone, Copy, Default, ValueEnum)]
+pub(crate) enum UsagesFormat {
+    #[default]
+    Text,
+    Json,
+    Csv,
+}
+
+#[derive(Serialize)]
+struct SymbolOutput {
+    symbol: String,
+    files: Vec<FileOutput>,
+}
+
+#[derive(Serialize)]
+struct FileOutput {
+    path: String,
+    /// The number of references to the symbol, not including the `load` statement.
+    count: usize,
+    locations: Vec<Location>,
+}
+
+/// A one-based line and column within a file.
+#[derive(Serialize)]
+struct Location {
+    line: u32,
+    col: u32,
+}
+
+/// Reports every file in the current workspace that uses the given symbols, along with the
+/// number and locations of the uses. Symbols are given as `//pkg:file.bzl%name`, either directly
+/// or one per line in `symbols_file`.
+pub(crate) fn run_usages(
+    mut symbols: Vec<String>,
+    symbols_file: Option<String>,
+    format: UsagesFormat,
+    output_base: Option<String>,
+) -> anyhow::Result<()> {
+    if let Some(symbols_file) = symbols_file {
+        let contents = fs::read_to_string(&symbols_file)
+            .map_err(|err| anyhow!("Could not read {:?}: {}", symbols_file, err))?;
+        symbols.extend(
+            contents
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                .map(|line| line.to_string()),
+        );
+    }
+    if symbols.is_empty() {
+        bail!("No symbols given.");
+    }
+
+    let (mut analysis, interner, workspace) = create_analysis(output_base)?;
+    let workspace = workspace.canonicalize().unwrap_or(workspace);
+    add_workspace_files(&mut analysis, &interner, &workspace)?;
+
+    let mut entries = interner.entries();
+    entries.sort_by(|(_, a), (_, b)| a.cmp(b));
+    let snap = analysis.snapshot();
+
+    let mut outputs = Vec::new();
+    for symbol in symbols {
+        let (loaded_path, name) = parse_symbol(&workspace, &symbol)?;
+        let loaded_file_id = interner
+            .lookup_by_path_buf(&loaded_path)
+            .ok_or_else(|| anyhow!("Could not find the file for {:?}.", symbol))?;
+
+        let mut files = Vec::new();
+        for (file_id, path) in &entries {
+            if *file_id == loaded_file_id {
+                continue;
+            }
+            let usages = snap
+                .symbol_usages(*file_id, loaded_file_id, &[name.clone()])?
+                .unwrap_or_default();
+            if usages.is_empty() {
+                continue;
+            }
+            let line_index = match snap.line_index(*file_id)? {
+                Some(line_index) => line_index,
+                None => continue,
+            };
+            let mut locations = usages
+                .iter()
+                .flat_map(|usage| usage.reference_ranges.iter())
+                .map(|range| {
+                    let line_col = line_index.line_col(range.start());
+                    Location {
+                        line: line_col.line + 1,
+                        col: line_col.col + 1,
+                    }
+                })
+                .collect::<Vec<_>>();
+            locations.sort_by_key(|location| (location.line, location.col));
+            files.push(FileOutput {
+                path: path
+                    .strip_prefix(&workspace)
+                    .unwrap_or(path)
+                    .to_string_lossy()
+                    .to_string(),
+                count: locations.len(),
+                locations,
+            });
+        }
+        outputs.push(SymbolOutput { symbol, files });
+    }
+
+    match format {
+        UsagesFormat::Json => println!("{}", serde_json::to_string_pretty(&outputs)?),
+        UsagesFormat::Csv => {
+            println!("symbol,path,count,locations");
+            for output in outputs {
+                for file in output.files {
+                    println!(
+                        "{},{},{},{}",
+                        csv_field(&output.symbol),
+                        csv_field(&file.path),
+                        file.count,
+                        csv_field(&format_locations(&file.locations)),
+                    );
+                }
+            }
+        }
+        UsagesFormat::Text => {
+            for output in outputs {
+                let count = output.files.iter().map(|file| file.count).sum::<usize>();
+                println!(
+                    "{}: {} uses in {} files",
+                    output.symbol,
+                    count,
+                    output.files.len()
+                );
+                for file in output.files {
+                    println!(
+                        "  {} ({}): {}",
+                        file.path,
+                        file.count,
+                        format_locations(&file.locations)
+                    );
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Parses a symbol of the form `//pkg:file.bzl%name` into the path of the file and the name.
+fn parse_symbol(workspace: &Path, symbol: &str) -> anyhow::Result<(PathBuf, String)> {
+    let err = || {
+        anyhow!(
+            "Invalid symbol {:?}, expected a symbol of the form \"//pkg:file.bzl%name\".",
+            symbol
+        )
+    };
+    let (label, name) = symbol.split_once('%').ok_or_else(err)?;
+    let label = label.trim_start_matches('@');
+    let (package, target) = label
+        .strip_prefix("//")
+        .ok_or_else(err)?
+        .split_once(':')
+        .ok_or_else(err)?;
+    if name.is_empty() || target.is_empty() {
+        return Err(err());
+    }
+    Ok((workspace.join(package).join(target), name.to_string()))
+}
+
+fn format_locations(locations: &[Location]) -> String {
+    locations
+        .iter()
+        .map(|location| format!("{}:{}", location.line, location.col))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains(|c: char| matches!(c, ',' | '"' | '\n')) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
@@ -1,50 +1,61 @@
-use std::ops::Range;
+use std::{fs, ops::Range, path::PathBuf};
 
 use anyhow::format_err;
-use line_index::{LineIndex, WideEncoding, WideLineCol};
+use line_index::LineIndex;
 use starpls_common::FileId;
-use starpls_ide::LocationLink;
+use starpls_ide::{BuiltinStub, LocationLink};
 
-use crate::{convert, server::ServerSnapshot};
+use crate::{
+    convert::{self, FileLineIndex, PositionEncoding},
+    server::ServerSnapshot,
+};
 
 pub(crate) fn text_offset(
-    line_index: &LineIndex,
+    line_index: FileLineIndex,
+    encoding: PositionEncoding,
     pos: lsp_types::Position,
 ) -> anyhow::Result<usize> {
-    let line_col = line_index
-        .to_utf8(
-            WideEncoding::Utf16,
-            WideLineCol {
-                line: pos.line,
-                col: pos.character,
-            },
-        )
-        .ok_or_else(|| format_err!("error converting wide line col to utf-8"))?;
-    line_index
-        .offset(line_col)
+    convert::text_size_from_lsp_position_with_index(line_index, encoding, pos)
         .map(|offset| offset.into())
         .ok_or_else(|| format_err!("invalid offset"))
 }
 
 pub(crate) fn text_range(
-    line_index: &LineIndex,
+    line_index: FileLineIndex,
+    encoding: PositionEncoding,
     range: lsp_types::Range,
 ) -> anyhow::Result<Range<usize>> {
-    let start = text_offset(line_index, range.start)?;
-    let end = text_offset(line_index, range.end)?;
+    let start = text_offset(line_index, encoding, range.start)?;
+    let end = text_offset(line_index, encoding, range.end)?;
     Ok(start..end)
 }
 
+/// Applies incremental changes to the normalized contents of a document. `has_bom` is whether the
+/// client's copy of the document starts with a byte order mark, which the change positions count.
 pub(crate) fn apply_document_content_changes(
     mut current_document_contents: String,
     content_changes: Vec<lsp_types::TextDocumentContentChangeEvent>,
+    encoding: PositionEncoding,
+    has_bom: bool,
 ) -> String {
     let mut line_index = LineIndex::new(&current_document_contents);
     for change in content_changes {
         let Some(pos_range) = change.range else {
             continue;
         };
-        if let Ok(range) = text_range(&line_index, pos_range) {
+        let file_line_index = FileLineIndex {
+            index: &line_index,
+            has_bom,
+        };
+        if let Ok(range) = text_range(file_line_index, encoding, pos_range) {
+            // With UTF-8 positions, a misbehaving client could send a range that splits a
+            // character, which would otherwise cause a panic.
+            if range.start > range.end
+                || !current_document_contents.is_char_boundary(range.start)
+                || !current_document_contents.is_char_boundary(range.end)
+            {
+                continue;
+            }
             current_document_contents.replace_range(range.clone(), &change.text);
             line_index = LineIndex::new(&current_document_contents);
         }
@@ -62,7 +73,8 @@ where
     T: Iterator<Item = LocationLink>,
     U: From<Vec<lsp_types::Location>> + From<Vec<lsp_types::LocationLink>>,
 {
-    let source_line_index = match snapshot.analysis_snapshot.line_index(source_file_id) {
+    let encoding = snapshot.config.position_encoding();
+    let source_line_index = match snapshot.line_index(source_file_id) {
         Ok(Some(source_line_index)) => source_line_index,
         _ => return Vec::<lsp_types::Location>::new().into(),
     };
@@ -75,11 +87,9 @@ where
                 target_file_id,
                 ..
             } => {
-                let target_line_index = snapshot
-                    .analysis_snapshot
-                    .line_index(target_file_id)
-                    .ok()??;
-                let range = convert::lsp_range_from_text_range(target_range, target_line_index);
+                let target_line_index = snapshot.line_index(target_file_id).ok()??;
+                let range =
+                    convert::lsp_range_from_text_range(target_range, target_line_index, encoding);
                 lsp_types::Location {
                     uri: lsp_types::Url::from_file_path(
                         snapshot
@@ -95,6 +105,12 @@ where
                 uri: lsp_types::Url::from_file_path(target_path).ok()?,
                 range: Default::default(),
             },
+            LocationLink::Builtin {
+                stub, target_line, ..
+            } => lsp_types::Location {
+                uri: lsp_types::Url::from_file_path(write_builtin_stub(&stub)?).ok()?,
+                range: line_range(target_line),
+            },
         };
 
         Some(location)
@@ -108,14 +124,12 @@ where
                 target_file_id,
                 ..
             } => {
-                let target_line_index = snapshot
-                    .analysis_snapshot
-                    .line_index(target_file_id)
-                    .ok()??;
-                let range = convert::lsp_range_from_text_range(target_range, target_line_index);
+                let target_line_index = snapshot.line_index(target_file_id).ok()??;
+                let range =
+                    convert::lsp_range_from_text_range(target_range, target_line_index, encoding);
                 lsp_types::LocationLink {
                     origin_selection_range: origin_selection_range.and_then(|range| {
-                        convert::lsp_range_from_text_range(range, source_line_index)
+                        convert::lsp_range_from_text_range(range, source_line_index, encoding)
                     }),
                     target_range: range.clone()?,
                     target_selection_range: range?,
@@ -132,12 +146,25 @@ where
                 origin_selection_range,
                 target_path,
             } => lsp_types::LocationLink {
-                origin_selection_range: origin_selection_range
-                    .and_then(|range| convert::lsp_range_from_text_range(range, source_line_index)),
+                origin_selection_range: origin_selection_range.and_then(|range| {
+                    convert::lsp_range_from_text_range(range, source_line_index, encoding)
+                }),
                 target_range: Default::default(),
                 target_selection_range: Default::default(),
                 target_uri: lsp_types::Url::from_file_path(target_path).ok()?,
             },
+            LocationLink::Builtin {
+                origin_selection_range,
+                stub,
+                target_line,
+            } => lsp_types::LocationLink {
+                origin_selection_range: origin_selection_range.and_then(|range| {
+                    convert::lsp_range_from_text_range(range, source_line_index, encoding)
+                }),
+                target_range: line_range(target_line),
+                target_selection_range: line_range(target_line),
+                target_uri: lsp_types::Url::from_file_path(write_builtin_stub(&stub)?).ok()?,
+            },
         };
 
         Some(location_link)
@@ -155,3 +182,35 @@ where
             .into()
     }
 }
+
+fn line_range(line: u32) -> lsp_types::Range {
+    let pos = lsp_types::Position { line, character: 0 };
+    lsp_types::Range {
+        start: pos,
+        end: pos,
+    }
+}
+
+/// Writes a builtin stub to a temporary directory so that clients can open it like any other
+/// file. The stub is only rewritten if it changed, e.g. after a Bazel upgrade, and is otherwise
+/// left read-only.
+#[allow(clippy::permissions_set_readonly_false)]
+fn write_builtin_stub(stub: &BuiltinStub) -> Option<PathBuf> {
+    let dir = std::env::temp_dir().join("starpls").join("builtins");
+    fs::create_dir_all(&dir).ok()?;
+    let path = dir.join(&stub.name);
+    if fs::read_to_string(&path).ok().as_deref() == Some(stub.contents.as_str()) {
+        return Some(path);
+    }
+
+    if let Ok(metadata) = fs::metadata(&path) {
+        let mut permissions = metadata.permissions();
+        permissions.set_readonly(false);
+        fs::set_permissions(&path, permissions).ok()?;
+    }
+    fs::write(&path, &stub.contents).ok()?;
+    let mut permissions = fs::metadata(&path).ok()?.permissions();
+    permissions.set_readonly(true);
+    fs::set_permissions(&path, permissions).ok()?;
+    Some(path)
+}
@@ -0,0 +1,232 @@
+//! Diagnostics for files that aren't open in an editor, but that load a file that was edited and
+//! so may have been broken by the edit, e.g. the `BUILD` files that use a macro.
+//!
+//! Affected files are checked a batch at a time, with each batch running as its own background
+//! task against a fresh snapshot. This way, at most one background thread is ever busy with
+//! workspace diagnostics, and edits never have to wait for more than a single batch to be
+//! cancelled.
+
+use std::{collections::VecDeque, time::Duration};
+
+use rustc_hash::{FxHashMap, FxHashSet};
+use starpls_common::FileId;
+use starpls_ide::AnalysisSnapshot;
+
+use crate::{
+    event_loop::{collect_diagnostics, Task},
+    server::ServerSnapshot,
+};
+
+/// How long to wait after the last edit before checking the files affected by it. This is longer
+/// than the delay for open files, since checking every loader of a widely used file is expensive.
+pub(crate) const DEBOUNCE_INTERVAL: Duration = Duration::from_millis(1000);
+
+/// The number of files whose diagnostics are computed and published together.
+const BATCH_SIZE: usize = 20;
+
+/// The maximum number of affected files that are checked after a single round of edits.
+const MAX_FILES: usize = 1000;
+
+#[derive(Debug)]
+pub(crate) enum WorkspaceDiagnosticsProgress {
+    /// The files affected by a round of edits have been found. If finding them was cancelled,
+    /// the edited files are returned instead so that they can be retried.
+    Affected {
+        files: Vec<FileId>,
+        cancelled: Option<Vec<FileId>>,
+    },
+    /// Diagnostics for a batch of affected files, along with the files in the batch that weren't
+    /// checked because the batch was cancelled.
+    Report {
+        diagnostics: Vec<(FileId, Vec<lsp_types::Diagnostic>)>,
+        unchecked: Vec<FileId>,
+    },
+}
+
+pub(crate) enum Job {
+    /// Find the closed files that load any of the given edited files.
+    FindAffected(Vec<FileId>),
+    /// Compute diagnostics for the given files.
+    Check(Vec<FileId>),
+}
+
+#[derive(Default)]
+pub(crate) struct WorkspaceDiagnostics {
+    /// Edited files whose loaders still need to be found.
+    edited: FxHashSet<FileId>,
+    /// Affected files that still need to be checked.
+    queue: VecDeque<FileId>,
+    is_running: bool,
+    /// The diagnostics last published for each file that isn't open.
+    published: FxHashMap<FileId, Vec<lsp_types::Diagnostic>>,
+}
+
+impl WorkspaceDiagnostics {
+    pub(crate) fn request(&mut self, file_ids: impl IntoIterator<Item = FileId>) {
+        self.edited.extend(file_ids);
+    }
+
+    /// Returns the next job to run, unless one is already running. Newly edited files are handled
+    /// first, since they may affect files that are already queued.
+    pub(crate) fn next_job(&mut self) -> Option<Job> {
+        if self.is_running {
+            return None;
+        }
+        let job = if !self.edited.is_empty() {
+            Job::FindAffected(self.edited.drain().collect())
+        } else if !self.queue.is_empty() {
+            let len = self.queue.len().min(BATCH_SIZE);
+            Job::Check(self.queue.drain(..len).collect())
+        } else {
+            return None;
+        };
+        self.is_running = true;
+        Some(job)
+    }
+
+    pub(crate) fn handle_progress(&mut self, progress: &WorkspaceDiagnosticsProgress) {
+        self.is_running = false;
+        match progress {
+            WorkspaceDiagnosticsProgress::Affected { files, cancelled } => {
+                if let Some(edited) = cancelled {
+                    self.edited.extend(edited);
+                }
+                for &file_id in files {
+                    if self.queue.len() >= MAX_FILES {
+                        eprintln!(
+                            "server: only checking {} of the files affected by the latest changes",
+                            MAX_FILES
+                        );
+                        break;
+                    }
+                    if !self.queue.contains(&file_id) {
+                        self.queue.push_back(file_id);
+                    }
+                }
+            }
+            WorkspaceDiagnosticsProgress::Report { unchecked, .. } => {
+                for &file_id in unchecked.iter().rev() {
+                    self.queue.push_front(file_id);
+                }
+            }
+        }
+    }
+
+    /// Records the diagnostics for a file and returns whether they differ from those last
+    /// published for it.
+    pub(crate) fn set_diagnostics(
+        &mut self,
+        file_id: FileId,
+        diagnostics: &[lsp_types::Diagnostic],
+    ) -> bool {
+        match self.published.get(&file_id) {
+            Some(published) if published == diagnostics => false,
+            // There's no need to publish an empty set of diagnostics for a file that never had any.
+            None if diagnostics.is_empty() => false,
+            _ => {
+                self.published.insert(file_id, diagnostics.to_vec());
+                true
+            }
+        }
+    }
+
+    /// Drops all pending work and returns the files that diagnostics were published for, so that
+    /// they can be cleared. A job that's already running still reports its progress.
+    pub(crate) fn clear(&mut self) -> Vec<FileId> {
+        self.edited.clear();
+        self.queue.clear();
+        self.published.drain().map(|(file_id, _)| file_id).collect()
+    }
+
+    /// Forgets the diagnostics published for a file, e.g. because it was opened and its
+    /// diagnostics are now published along with its version.
+    pub(crate) fn remove(&mut self, file_id: FileId) {
+        self.published.remove(&file_id);
+    }
+}
+
+/// Finds the files that transitively load any of the given edited files, other than those that
+/// are open.
+pub(crate) fn find_affected(
+    snapshot: &AnalysisSnapshot,
+    edited_file_ids: Vec<FileId>,
+    open_file_ids: &FxHashSet<FileId>,
+) -> Task {
+    let mut seen = FxHashSet::default();
+    let mut files = Vec::new();
+    for &file_id in &edited_file_ids {
+        let graph = match snapshot.load_graph(file_id) {
+            Ok(graph) => graph.unwrap_or_default(),
+            Err(_) => {
+                return Task::WorkspaceDiagnostics(WorkspaceDiagnosticsProgress::Affected {
+                    files,
+                    cancelled: Some(edited_file_ids),
+                })
+            }
+        };
+        files.extend(
+            graph
+                .loaders
+                .into_iter()
+                .filter(|loader| !open_file_ids.contains(loader) && seen.insert(*loader)),
+        );
+    }
+    Task::WorkspaceDiagnostics(WorkspaceDiagnosticsProgress::Affected {
+        files,
+        cancelled: None,
+    })
+}
+
+pub(crate) fn check(snapshot: &ServerSnapshot, file_ids: Vec<FileId>) -> Task {
+    let mut diagnostics = Vec::with_capacity(file_ids.len());
+    let mut unchecked = Vec::new();
+    for (i, &file_id) in file_ids.iter().enumerate() {
+        match collect_diagnostics(snapshot, file_id) {
+            Ok(Some(file_diagnostics)) => diagnostics.push((file_id, file_diagnostics)),
+            Ok(None) => {}
+            Err(_) => {
+                unchecked.extend_from_slice(&file_ids[i..]);
+                break;
+            }
+        }
+    }
+    Task::WorkspaceDiagnostics(WorkspaceDiagnosticsProgress::Report {
+        diagnostics,
+        unchecked,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use rustc_hash::FxHashSet;
+    use starpls_common::{Dialect, FileId};
+    use starpls_ide::Analysis;
+
+    use super::{find_affected, WorkspaceDiagnosticsProgress};
+    use crate::event_loop::Task;
+
+    #[test]
+    fn test_find_affected_closed_loaders() {
+        // Only `defs.bzl` is open. `BUILD.bazel` was never opened and isn't loaded by any open
+        // file, so it's only found through the loader's index of the workspace.
+        let analysis = Analysis::from_workspace(
+            &[("defs.bzl", "def my_macro():\n    pass\n")],
+            &[
+                (
+                    "BUILD.bazel",
+                    "load(\"defs.bzl\", \"my_macro\")\n\nmy_macro()\n",
+                ),
+                ("other.bzl", "x = 1\n"),
+            ],
+            Dialect::Bazel,
+        );
+        let open_file_ids = FxHashSet::from_iter([FileId(0)]);
+        match find_affected(&analysis.snapshot(), vec![FileId(0)], &open_file_ids) {
+            Task::WorkspaceDiagnostics(WorkspaceDiagnosticsProgress::Affected {
+                files,
+                cancelled: None,
+            }) => assert_eq!(files, vec![FileId(1)]),
+            task => panic!("unexpected task: {:?}", task),
+        }
+    }
+}
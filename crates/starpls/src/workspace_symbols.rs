@@ -0,0 +1,283 @@
+//! An index of the symbols defined by every Starlark file in the workspace, which answers
+//! `workspace/symbol` requests.
+//!
+//! The workspace is indexed from disk in the background when the server starts. Afterwards, files
+//! are re-indexed from their contents in the editor whenever they change. Changed files are only
+//! re-indexed when the next search comes in, so that typing doesn't pay for it.
+//!
+//! The index also finds the `.bzl` files that export a name, for the quick fixes that load an
+//! undefined name.
+
+use std::{
+    mem,
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
+use line_index::LineIndex;
+use parking_lot::RwLock;
+use rustc_hash::FxHashMap;
+use starpls_ide::{
+    fuzzy_match, index_workspace_symbols, ExportedSymbol, WorkspaceSymbol, WorkspaceSymbolKind,
+};
+
+use crate::{
+    convert::{self, PositionEncoding},
+    document, impact,
+};
+
+/// The most symbols returned for a single search.
+const MAX_RESULTS: usize = 256;
+
+struct IndexedSymbol {
+    symbol: WorkspaceSymbol,
+    range: lsp_types::Range,
+}
+
+pub(crate) struct WorkspaceSymbolIndex {
+    workspace: PathBuf,
+    encoding: PositionEncoding,
+    files: RwLock<FxHashMap<PathBuf, Vec<IndexedSymbol>>>,
+    /// The contents of files that changed since they were last indexed.
+    pending: RwLock<FxHashMap<PathBuf, String>>,
+}
+
+impl WorkspaceSymbolIndex {
+    pub(crate) fn new(workspace: PathBuf, encoding: PositionEncoding) -> Self {
+        Self {
+            workspace,
+            encoding,
+            files: Default::default(),
+            pending: Default::default(),
+        }
+    }
+
+    /// Indexes every Starlark file in the workspace from disk. Files that were already indexed
+    /// from the editor are left alone, since the editor's contents are newer.
+    pub(crate) fn index_workspace(&self) {
+        let mut paths = Vec::new();
+        if let Err(err) = impact::collect_starlark_files(&self.workspace, &mut paths) {
+            eprintln!("server: failed to index workspace symbols: {}", err);
+        }
+        for path in paths {
+            let contents = match starpls_common::read_source(&path) {
+                Ok(decoded) => decoded.text,
+                Err(_) => continue,
+            };
+            if let Some(symbols) = self.index(&path, &contents) {
+                self.files.write().entry(path).or_insert(symbols);
+            }
+        }
+        eprintln!(
+            "server: indexed workspace symbols in {} files",
+            self.files.read().len()
+        );
+    }
+
+    /// Records that a file changed, so that it's re-indexed before the next search.
+    pub(crate) fn file_changed(&self, path: PathBuf, contents: String) {
+        self.pending.write().insert(path, contents);
+    }
+
+    /// Returns the best matches for the query, ranked by how well they match. If `deadline`
+    /// passes, the remaining files are skipped and only the matches found so far are ranked.
+    /// Also returns whether every file was searched.
+    pub(crate) fn search(
+        &self,
+        query: &str,
+        deadline: Option<Instant>,
+    ) -> (Vec<lsp_types::SymbolInformation>, bool) {
+        self.index_pending();
+        let files = self.files.read();
+        let mut matches = Vec::new();
+        let mut is_complete = true;
+        for (path, symbols) in files.iter() {
+            if deadline.map_or(false, |deadline| Instant::now() >= deadline) {
+                is_complete = false;
+                break;
+            }
+            for symbol in symbols {
+                // Targets can also be found by their rule, e.g. `go_bin` finds every `go_binary`.
+                let score = fuzzy_match(query, &symbol.symbol.name).max(
+                    symbol
+                        .symbol
+                        .detail
+                        .as_deref()
+                        .and_then(|detail| fuzzy_match(query, detail))
+                        .map(|score| score.saturating_sub(1)),
+                );
+                if let Some(score) = score {
+                    matches.push((score, path, symbol));
+                }
+            }
+        }
+        matches.sort_by(|(score1, path1, symbol1), (score2, path2, symbol2)| {
+            score2
+                .cmp(score1)
+                .then_with(|| symbol1.symbol.name.cmp(&symbol2.symbol.name))
+                .then_with(|| path1.cmp(path2))
+        });
+        let symbols = matches
+            .into_iter()
+            .take(MAX_RESULTS)
+            .filter_map(|(_, path, symbol)| self.symbol_information(path, symbol))
+            .collect();
+        (symbols, is_complete)
+    }
+
+    /// Finds the public functions, rules, providers, and macros with the given names, in the
+    /// `.bzl` files other than `from`. Files outside of any package are skipped, since they can't
+    /// be loaded.
+    pub(crate) fn exported_symbols(&self, names: &[String], from: &Path) -> Vec<ExportedSymbol> {
+        self.index_pending();
+        let files = self.files.read();
+        let mut exports = Vec::new();
+        for (path, symbols) in files.iter() {
+            if path == from || path.extension().map_or(true, |ext| ext != "bzl") {
+                continue;
+            }
+            let mut module = None;
+            for symbol in symbols {
+                if symbol.symbol.kind == WorkspaceSymbolKind::Target
+                    || symbol.symbol.name.starts_with('_')
+                    || !names.contains(&symbol.symbol.name)
+                {
+                    continue;
+                }
+                if module.is_none() {
+                    module = Some(self.module_label(path));
+                }
+                if let Some(Some(module)) = &module {
+                    exports.push(ExportedSymbol {
+                        name: symbol.symbol.name.clone(),
+                        module: module.clone(),
+                    });
+                }
+            }
+        }
+        exports
+    }
+
+    fn index_pending(&self) {
+        let pending = mem::take(&mut *self.pending.write());
+        for (path, contents) in pending {
+            if let Some(symbols) = self.index(&path, &contents) {
+                self.files.write().insert(path, symbols);
+            }
+        }
+    }
+
+    /// Returns the label of a file in the workspace, relative to the closest package containing
+    /// it, e.g. `//foo:bar/defs.bzl` for `foo/bar/defs.bzl` if `foo/bar` isn't a package.
+    fn module_label(&self, path: &Path) -> Option<String> {
+        let (dialect, _) = document::dialect_and_api_context_for_path(path)?;
+        let package_dir = path
+            .ancestors()
+            .skip(1)
+            .take_while(|dir| dir.starts_with(&self.workspace))
+            .find(|dir| {
+                document::build_file_names(dialect)
+                    .iter()
+                    .any(|name| dir.join(name).is_file())
+            })?;
+        let package = package_dir.strip_prefix(&self.workspace).ok()?;
+        let name = path.strip_prefix(package_dir).ok()?;
+        Some(format!(
+            "//{}:{}",
+            package.to_string_lossy().replace('\\', "/"),
+            name.to_string_lossy().replace('\\', "/")
+        ))
+    }
+
+    fn index(&self, path: &Path, contents: &str) -> Option<Vec<IndexedSymbol>> {
+        let (_, api_context) = document::dialect_and_api_context_for_path(path)?;
+        let line_index = LineIndex::new(contents);
+        Some(
+            index_workspace_symbols(contents, api_context)
+                .into_iter()
+                .filter_map(|symbol| {
+                    Some(IndexedSymbol {
+                        range: convert::lsp_range_from_text_range(
+                            symbol.range,
+                            &line_index,
+                            self.encoding,
+                        )?,
+                        symbol,
+                    })
+                })
+                .collect(),
+        )
+    }
+
+    #[allow(deprecated)]
+    fn symbol_information(
+        &self,
+        path: &Path,
+        symbol: &IndexedSymbol,
+    ) -> Option<lsp_types::SymbolInformation> {
+        let (name, kind) = match symbol.symbol.kind {
+            WorkspaceSymbolKind::Function | WorkspaceSymbolKind::Macro => {
+                (symbol.symbol.name.clone(), lsp_types::SymbolKind::FUNCTION)
+            }
+            WorkspaceSymbolKind::Provider => {
+                (symbol.symbol.name.clone(), lsp_types::SymbolKind::STRUCT)
+            }
+            WorkspaceSymbolKind::Rule => (symbol.symbol.name.clone(), lsp_types::SymbolKind::CLASS),
+            // Targets are shown by their labels.
+            WorkspaceSymbolKind::Target => {
+                let package = path
+                    .parent()
+                    .and_then(|dir| dir.strip_prefix(&self.workspace).ok())
+                    .map(|dir| dir.to_string_lossy().replace('\\', "/"))
+                    .unwrap_or_default();
+                (
+                    format!("//{}:{}", package, symbol.symbol.name),
+                    lsp_types::SymbolKind::VARIABLE,
+                )
+            }
+        };
+        Some(lsp_types::SymbolInformation {
+            name,
+            kind,
+            tags: None,
+            deprecated: None,
+            location: lsp_types::Location {
+                uri: lsp_types::Url::from_file_path(path).ok()?,
+                range: symbol.range,
+            },
+            container_name: symbol.symbol.detail.clone(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{path::PathBuf, time::Instant};
+
+    use super::WorkspaceSymbolIndex;
+    use crate::convert::PositionEncoding;
+
+    #[test]
+    fn test_search_stops_at_deadline() {
+        let index = WorkspaceSymbolIndex::new(PathBuf::from("/ws"), PositionEncoding::Utf16);
+        index.file_changed(
+            PathBuf::from("/ws/defs.star"),
+            "def my_macro():\n    pass\n".to_string(),
+        );
+
+        let (symbols, is_complete) = index.search("my_mac", None);
+        assert_eq!(
+            symbols
+                .iter()
+                .map(|symbol| symbol.name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["my_macro"]
+        );
+        assert!(is_complete);
+
+        // Files are skipped once the deadline has passed.
+        let (symbols, is_complete) = index.search("my_mac", Some(Instant::now()));
+        assert!(symbols.is_empty());
+        assert!(!is_complete);
+    }
+}
@@ -0,0 +1,235 @@
+//! Support for Buck2 projects. Buck2's packages are declared by `BUCK` files, and its cells, which
+//! play the part of Bazel's repositories, are declared in the `[cells]` sections of `.buckconfig`
+//! files, e.g.:
+//!
+//! ```ini
+//! [cells]
+//! root = .
+//! prelude = prelude
+//! ```
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// The names of the files that declare Buck2 packages.
+pub const BUCK_FILE_NAMES: &[&str] = &["BUCK", "BUCK.v2"];
+
+const BUCKCONFIG_FILE_NAME: &str = ".buckconfig";
+
+const BAZEL_WORKSPACE_FILE_NAMES: &[&str] =
+    &["WORKSPACE", "WORKSPACE.bazel", "MODULE.bazel", "REPO.bazel"];
+
+/// A path in a Buck2 `load` statement, e.g. `@prelude//cxx:cxx.bzl`, `prelude//cxx/cxx.bzl`,
+/// `//foo:defs.bzl` or `:defs.bzl`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LoadPath<'a> {
+    /// The cell that the file is in, or `None` for the cell of the loading file.
+    pub cell: Option<&'a str>,
+    /// The path of the file's package relative to the root of its cell, or `None` for the package
+    /// of the loading file, as in `:defs.bzl`.
+    pub package: Option<&'a str>,
+    /// The path of the file relative to its package.
+    pub file: &'a str,
+}
+
+impl<'a> LoadPath<'a> {
+    /// Parses a load path. Unlike Bazel labels, Buck2 load paths may omit the `@` before the cell
+    /// and the `:` before the file, in which case the file's package is its directory.
+    pub fn parse(path: &'a str) -> Option<Self> {
+        if let Some(file) = path.strip_prefix(':') {
+            return (!file.is_empty()).then_some(Self {
+                cell: None,
+                package: None,
+                file,
+            });
+        }
+
+        let (cell, rest) = path.split_once("//")?;
+        let cell = cell.strip_prefix('@').unwrap_or(cell);
+        if cell.contains([':', '/']) {
+            return None;
+        }
+        let (package, file) = match rest.split_once(':') {
+            Some(res) => res,
+            None => rest.rsplit_once('/').unwrap_or(("", rest)),
+        };
+        if file.is_empty() {
+            return None;
+        }
+        Some(Self {
+            cell: (!cell.is_empty()).then_some(cell),
+            package: Some(package),
+            file,
+        })
+    }
+}
+
+/// Parses the cells declared in a `.buckconfig` file, along with their paths relative to the
+/// file's directory. The `[repositories]` section of older `.buckconfig` files is supported too.
+pub fn parse_cells(contents: &str) -> Vec<(String, String)> {
+    let mut cells = Vec::new();
+    let mut in_cells = false;
+    for line in contents.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with(['#', ';']) {
+            continue;
+        }
+        if let Some(section) = line
+            .strip_prefix('[')
+            .and_then(|line| line.strip_suffix(']'))
+        {
+            in_cells = matches!(section.trim(), "cells" | "repositories");
+        } else if let (true, Some((name, path))) = (in_cells, line.split_once('=')) {
+            cells.push((name.trim().to_string(), path.trim().to_string()));
+        }
+    }
+    cells
+}
+
+/// Returns whether the given path is in a Buck2 project rather than a Bazel workspace, i.e.
+/// whether a `.buckconfig` file is closer to it than any of the files that mark the root of a
+/// Bazel workspace. Directories with both are treated as Bazel workspaces.
+pub fn is_in_project(from: impl AsRef<Path>) -> bool {
+    from.as_ref()
+        .ancestors()
+        .filter(|ancestor| ancestor.is_dir())
+        .find_map(|ancestor| {
+            if BAZEL_WORKSPACE_FILE_NAMES
+                .iter()
+                .any(|name| ancestor.join(name).is_file())
+            {
+                Some(false)
+            } else if ancestor.join(BUCKCONFIG_FILE_NAME).is_file() {
+                Some(true)
+            } else {
+                None
+            }
+        })
+        .unwrap_or_default()
+}
+
+/// Finds the root of the cell containing the given path, which is the closest directory with a
+/// `.buckconfig` file, along with the directory of the package containing the path. Paths that
+/// aren't in any package are considered to be in the cell's root package.
+pub fn resolve_cell(from: impl AsRef<Path>) -> io::Result<Option<(PathBuf, PathBuf)>> {
+    let mut package: Option<PathBuf> = None;
+    for ancestor in from
+        .as_ref()
+        .ancestors()
+        .filter(|ancestor| ancestor.is_dir())
+    {
+        if ancestor.join(BUCKCONFIG_FILE_NAME).is_file() {
+            return Ok(Some((
+                ancestor.to_path_buf(),
+                package.unwrap_or_else(|| ancestor.to_path_buf()),
+            )));
+        }
+        if BUCK_FILE_NAMES
+            .iter()
+            .any(|name| ancestor.join(name).is_file())
+        {
+            package.get_or_insert(ancestor.to_path_buf());
+        }
+    }
+
+    Ok(None)
+}
+
+/// Finds the root of the cell with the given name, e.g. `prelude`, as declared by the closest
+/// `.buckconfig` file above the given path that declares it.
+pub fn resolve_named_cell(from: impl AsRef<Path>, name: &str) -> io::Result<Option<PathBuf>> {
+    for ancestor in from
+        .as_ref()
+        .ancestors()
+        .filter(|ancestor| ancestor.is_dir())
+    {
+        let config = ancestor.join(BUCKCONFIG_FILE_NAME);
+        if !config.is_file() {
+            continue;
+        }
+        if let Some((_, path)) = parse_cells(&fs::read_to_string(config)?)
+            .into_iter()
+            .find(|(cell, _)| cell == name)
+        {
+            return Ok(Some(ancestor.join(path)));
+        }
+    }
+
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(input: &str, cell: Option<&str>, package: Option<&str>, file: &str) {
+        assert_eq!(
+            LoadPath::parse(input),
+            Some(LoadPath {
+                cell,
+                package,
+                file
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_load_paths() {
+        check(":defs.bzl", None, None, "defs.bzl");
+        check("//foo:defs.bzl", None, Some("foo"), "defs.bzl");
+        check("//foo/defs.bzl", None, Some("foo"), "defs.bzl");
+        check(
+            "@prelude//:prelude.bzl",
+            Some("prelude"),
+            Some(""),
+            "prelude.bzl",
+        );
+        check(
+            "prelude//cxx:cxx.bzl",
+            Some("prelude"),
+            Some("cxx"),
+            "cxx.bzl",
+        );
+        check(
+            "prelude//cxx/tools/defs.bzl",
+            Some("prelude"),
+            Some("cxx/tools"),
+            "defs.bzl",
+        );
+    }
+
+    #[test]
+    fn test_parse_invalid_load_paths() {
+        for input in ["", ":", "defs.bzl", "//foo:", "//foo/", "a/b//foo:defs.bzl"] {
+            assert_eq!(LoadPath::parse(input), None, "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn test_parse_cells() {
+        let contents = r#"
+# Cells are relative to this file.
+[cells]
+  root = .
+  prelude = prelude
+
+[cell_aliases]
+  config = prelude
+
+[repositories]
+toolchains = third-party/toolchains
+"#;
+        assert_eq!(
+            parse_cells(contents),
+            vec![
+                ("root".to_string(), ".".to_string()),
+                ("prelude".to_string(), "prelude".to_string()),
+                (
+                    "toolchains".to_string(),
+                    "third-party/toolchains".to_string()
+                ),
+            ]
+        );
+    }
+}
@@ -0,0 +1,314 @@
+//! Resolves the apparent repository names used in labels, e.g. the `rules_go` in
+//! `@rules_go//go:def.bzl`, to canonical repository names by reading `MODULE.bazel` files directly.
+//! This is used when Bazel can't be asked for the repository mapping, e.g. because the installed
+//! version doesn't support `bazel mod dump_repo_mapping`.
+//!
+//! Each module sees the repositories of its own `bazel_dep`s, along with the extension
+//! repositories that it imports with `use_repo`, under the names that it declares for them.
+//! Bazel's scheme for canonical names changed across versions, e.g. `rules_go~` in Bazel 7 and
+//! `rules_go+` in Bazel 8, so the names of the repositories already fetched into the external
+//! output base are used to pick the right one.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use parking_lot::RwLock;
+
+use crate::lockfile::ModuleLockfile;
+
+/// The declarations in a `MODULE.bazel` file that determine its repository mapping.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ModuleFile {
+    /// The `name` passed to `module`.
+    pub name: Option<String>,
+    /// The `repo_name` passed to `module`.
+    pub repo_name: Option<String>,
+    pub deps: Vec<BazelDep>,
+    pub extension_repos: Vec<ExtensionRepo>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BazelDep {
+    pub name: String,
+    pub repo_name: Option<String>,
+}
+
+/// A repository imported from a module extension with `use_repo`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExtensionRepo {
+    /// The label of the `.bzl` file that defines the extension, as passed to `use_extension`.
+    pub extension_file: String,
+    pub extension_name: String,
+    /// The name that the repository is imported under.
+    pub apparent_name: String,
+    /// The name that the extension gives the repository.
+    pub name: String,
+}
+
+/// The canonical names of the repositories in the external output base.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ExternalRepos {
+    names: HashSet<String>,
+}
+
+impl ExternalRepos {
+    pub fn new(names: impl IntoIterator<Item = String>) -> Self {
+        Self {
+            names: names.into_iter().collect(),
+        }
+    }
+
+    pub fn load(dir: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let mut names = HashSet::new();
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            if entry.file_type()?.is_dir() {
+                if let Ok(name) = entry.file_name().into_string() {
+                    names.insert(name);
+                }
+            }
+        }
+        Ok(Self { names })
+    }
+
+    /// The separator used in canonical names, which is `+` as of Bazel 8 and `~` before that.
+    fn separator(&self) -> char {
+        if self.names.iter().any(|name| name.contains('+')) {
+            '+'
+        } else {
+            '~'
+        }
+    }
+
+    /// Returns the first of the given names that has been fetched, or else the first name that
+    /// uses the current separator.
+    fn pick(&self, candidates: Vec<String>) -> Option<String> {
+        let separator = self.separator();
+        candidates
+            .iter()
+            .find(|name| self.names.contains(*name))
+            .or_else(|| {
+                candidates
+                    .iter()
+                    .find(|name| !name.contains(if separator == '+' { '~' } else { '+' }))
+            })
+            .cloned()
+    }
+
+    /// Returns the canonical name of a module's repository. Older versions of Bazel include the
+    /// module's version in the name, e.g. `rules_go~0.46.0`.
+    fn module_repo(&self, module: &str, version: Option<&str>) -> String {
+        let mut candidates = Vec::new();
+        for separator in ['~', '+'] {
+            candidates.push(format!("{}{}", module, separator));
+            if let Some(version) = version {
+                candidates.push(format!("{}{}{}", module, separator, version));
+            }
+        }
+        self.pick(candidates)
+            .unwrap_or_else(|| format!("{}{}", module, self.separator()))
+    }
+
+    /// Returns the canonical name of a repository generated by a module extension, e.g.
+    /// `rules_go~~go_sdk~go_default_sdk` in Bazel 7 or `rules_go++go_sdk+go_default_sdk` in Bazel 8.
+    /// Extensions defined by the root module live under `_main`.
+    fn extension_repo(&self, host_repo: &str, extension_name: &str, name: &str) -> String {
+        let host_repo = if host_repo.is_empty() {
+            "_main"
+        } else {
+            host_repo
+        };
+        let candidates = ['~', '+']
+            .iter()
+            .map(|separator| {
+                format!(
+                    "{}{}{}{}{}",
+                    host_repo, separator, extension_name, separator, name
+                )
+            })
+            .collect();
+        self.pick(candidates).unwrap_or_default()
+    }
+}
+
+/// Computes the repository mapping of a module, i.e. the canonical names of the repositories that
+/// it can refer to, keyed by their apparent names. `module_repo` is the canonical name of the
+/// module's own repository, which is the empty string for the root module. The lockfile, if any,
+/// provides the resolved versions of the dependencies.
+pub fn repo_mapping(
+    module: &ModuleFile,
+    module_repo: &str,
+    repos: &ExternalRepos,
+    lockfile: Option<&ModuleLockfile>,
+) -> HashMap<String, String> {
+    let mut mapping = HashMap::new();
+    mapping.insert("bazel_tools".to_string(), "bazel_tools".to_string());
+    for name in module.name.iter().chain(module.repo_name.iter()) {
+        mapping.insert(name.clone(), module_repo.to_string());
+    }
+    for dep in &module.deps {
+        let version = lockfile
+            .and_then(|lockfile| lockfile.module(&dep.name))
+            .and_then(|module| module.version.as_deref());
+        mapping.insert(
+            dep.repo_name.clone().unwrap_or_else(|| dep.name.clone()),
+            repos.module_repo(&dep.name, version),
+        );
+    }
+
+    let mut extension_repos = Vec::new();
+    for repo in &module.extension_repos {
+        // Extensions are hosted by the module that defines them, which is the current module if
+        // the label doesn't name a repository.
+        let repo_part = |label: &str| label.split("//").next().unwrap_or_default().to_string();
+        let host_repo = if let Some(rest) = repo.extension_file.strip_prefix("@@") {
+            repo_part(rest)
+        } else if let Some(rest) = repo.extension_file.strip_prefix('@') {
+            match mapping.get(&repo_part(rest)) {
+                Some(host_repo) => host_repo.clone(),
+                None => continue,
+            }
+        } else {
+            module_repo.to_string()
+        };
+        let canonical_repo = repos.extension_repo(&host_repo, &repo.extension_name, &repo.name);
+        if !canonical_repo.is_empty() {
+            extension_repos.push((repo.apparent_name.clone(), canonical_repo));
+        }
+    }
+    mapping.extend(extension_repos);
+    mapping
+}
+
+/// Resolves repositories from the `MODULE.bazel` files of the workspace and of the modules
+/// fetched into the external output base, caching the mapping of each module.
+pub struct ModuleResolver {
+    workspace: PathBuf,
+    external_output_base: PathBuf,
+    parse: fn(&str) -> ModuleFile,
+    mappings: RwLock<HashMap<String, HashMap<String, String>>>,
+}
+
+impl ModuleResolver {
+    /// Creates a resolver that uses `parse` to read the declarations in `MODULE.bazel` files.
+    pub fn new(
+        workspace: PathBuf,
+        external_output_base: PathBuf,
+        parse: fn(&str) -> ModuleFile,
+    ) -> Self {
+        Self {
+            workspace,
+            external_output_base,
+            parse,
+            mappings: Default::default(),
+        }
+    }
+
+    /// Returns the canonical name of the repository that `from_repo` refers to as
+    /// `apparent_repo`. `from_repo` is the canonical name of the referring repository, which is
+    /// the empty string for the main repository.
+    pub fn resolve_repo(&self, apparent_repo: &str, from_repo: &str) -> Option<String> {
+        if let Some(mapping) = self.mappings.read().get(from_repo) {
+            return mapping.get(apparent_repo).cloned();
+        }
+
+        let module_dir = if from_repo.is_empty() {
+            self.workspace.clone()
+        } else {
+            self.external_output_base.join(from_repo)
+        };
+        let module = fs::read_to_string(module_dir.join("MODULE.bazel"))
+            .map(|contents| (self.parse)(&contents))
+            .unwrap_or_default();
+        let repos = ExternalRepos::load(&self.external_output_base).unwrap_or_default();
+        let lockfile = ModuleLockfile::load(self.workspace.join("MODULE.bazel.lock")).ok();
+        let mapping = repo_mapping(&module, from_repo, &repos, lockfile.as_ref());
+        let canonical_repo = mapping.get(apparent_repo).cloned();
+        self.mappings.write().insert(from_repo.to_string(), mapping);
+        canonical_repo
+    }
+
+    /// Discards the cached mappings, e.g. after a `MODULE.bazel` file changed.
+    pub fn clear(&self) {
+        self.mappings.write().clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{repo_mapping, BazelDep, ExtensionRepo, ExternalRepos, ModuleFile};
+    use crate::lockfile::ModuleLockfile;
+
+    fn module() -> ModuleFile {
+        ModuleFile {
+            name: Some("my_project".to_string()),
+            repo_name: None,
+            deps: vec![
+                BazelDep {
+                    name: "rules_go".to_string(),
+                    repo_name: Some("io_bazel_rules_go".to_string()),
+                },
+                BazelDep {
+                    name: "platforms".to_string(),
+                    repo_name: None,
+                },
+            ],
+            extension_repos: vec![
+                ExtensionRepo {
+                    extension_file: "@io_bazel_rules_go//go:extensions.bzl".to_string(),
+                    extension_name: "go_sdk".to_string(),
+                    apparent_name: "go_sdk".to_string(),
+                    name: "go_default_sdk".to_string(),
+                },
+                ExtensionRepo {
+                    extension_file: "//:extensions.bzl".to_string(),
+                    extension_name: "tools".to_string(),
+                    apparent_name: "tools".to_string(),
+                    name: "tools".to_string(),
+                },
+            ],
+        }
+    }
+
+    fn repos(names: &[&str]) -> ExternalRepos {
+        ExternalRepos::new(names.iter().map(|name| name.to_string()))
+    }
+
+    #[test]
+    fn test_fetched_repos() {
+        let repos = repos(&[
+            "rules_go+",
+            "platforms+",
+            "rules_go++go_sdk+go_default_sdk",
+            "_main+tools+tools",
+        ]);
+        let mapping = repo_mapping(&module(), "", &repos, None);
+        assert_eq!(mapping["my_project"], "");
+        assert_eq!(mapping["io_bazel_rules_go"], "rules_go+");
+        assert_eq!(mapping["platforms"], "platforms+");
+        assert_eq!(mapping["go_sdk"], "rules_go++go_sdk+go_default_sdk");
+        assert_eq!(mapping["tools"], "_main+tools+tools");
+        assert_eq!(mapping["bazel_tools"], "bazel_tools");
+        assert!(!mapping.contains_key("rules_go"));
+    }
+
+    #[test]
+    fn test_versioned_repos() {
+        let lockfile = ModuleLockfile::parse(
+            r#"{
+                "registryFileHashes": {
+                    "https://bcr.bazel.build/modules/rules_go/0.46.0/MODULE.bazel": "abc"
+                }
+            }"#,
+        )
+        .unwrap();
+        let repos = repos(&["rules_go~0.46.0"]);
+        let mapping = repo_mapping(&module(), "", &repos, Some(&lockfile));
+        assert_eq!(mapping["io_bazel_rules_go"], "rules_go~0.46.0");
+        assert_eq!(mapping["platforms"], "platforms~");
+        assert_eq!(mapping["go_sdk"], "rules_go~0.46.0~go_sdk~go_default_sdk");
+    }
+}
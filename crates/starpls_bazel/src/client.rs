@@ -9,6 +9,8 @@ use anyhow::{anyhow, bail};
 use parking_lot::RwLock;
 use serde_json::Deserializer;
 
+use crate::repo_cache::RepoCache;
+
 #[derive(Default)]
 pub struct BazelInfo {
     pub output_base: PathBuf,
@@ -27,11 +29,14 @@ pub trait BazelClient: Send + Sync + 'static {
     ) -> anyhow::Result<Option<String>>;
     fn clear_repo_mappings(&self);
     fn null_query_external_repo_targets(&self, repo: &str) -> anyhow::Result<()>;
+    /// Downloads an external repository with `bazel fetch`, without building anything in it.
+    fn fetch_external_repo(&self, repo: &str) -> anyhow::Result<()>;
 }
 
 pub struct BazelCLI {
     executable: PathBuf,
     repo_mappings: RwLock<HashMap<String, HashMap<String, String>>>,
+    repo_cache: Option<RepoCache>,
 }
 
 impl BazelCLI {
@@ -42,6 +47,13 @@ impl BazelCLI {
         }
     }
 
+    /// Persists the repository mappings of external repositories in the given cache, so that
+    /// they don't have to be dumped again for as long as the repositories stay the same.
+    pub fn with_repo_cache(mut self, repo_cache: RepoCache) -> Self {
+        self.repo_cache = Some(repo_cache);
+        self
+    }
+
     fn run_command(&self, args: &[&str]) -> anyhow::Result<Vec<u8>> {
         let output = Command::new(&self.executable).args(args).output()?;
         if !output.status.success() {
@@ -125,8 +137,33 @@ impl BazelClient for BazelCLI {
         }
         drop(mappings);
 
-        // Otherwise, fetch the repo mapping and cache it. For now, we always cache the result, even if the call failed.
-        let mapping = self.dump_repo_mapping(from_repo).unwrap_or_default();
+        // Next, check the on-disk cache. The main repository's mapping isn't cached there, since
+        // it changes along with the workspace's `MODULE.bazel` file.
+        let cached = self
+            .repo_cache
+            .as_ref()
+            .filter(|_| !from_repo.is_empty())
+            .and_then(|repo_cache| Some((repo_cache, repo_cache.repo_key(from_repo)?)));
+        let mapping = match cached
+            .as_ref()
+            .and_then(|(repo_cache, key)| repo_cache.read_repo_mapping(key))
+        {
+            Some(mapping) => mapping,
+            // Otherwise, fetch the repo mapping and cache it. For now, we always cache the result
+            // in memory, even if the call failed.
+            None => {
+                let mapping = self.dump_repo_mapping(from_repo);
+                if let (Ok(mapping), Some((repo_cache, key))) = (&mapping, &cached) {
+                    if let Err(err) = repo_cache.write_repo_mapping(key, mapping) {
+                        eprintln!(
+                            "bazel: failed to cache repo mapping for {:?}: {}",
+                            from_repo, err
+                        );
+                    }
+                }
+                mapping.unwrap_or_default()
+            }
+        };
         let canonical_repo = mapping.get(apparent_repo).cloned();
         self.repo_mappings
             .write()
@@ -146,6 +183,11 @@ impl BazelClient for BazelCLI {
             .status()?;
         Ok(())
     }
+
+    fn fetch_external_repo(&self, repo: &str) -> anyhow::Result<()> {
+        self.run_command(&["fetch", &format!("@@{}//...", repo)])?;
+        Ok(())
+    }
 }
 
 impl Default for BazelCLI {
@@ -153,6 +195,7 @@ impl Default for BazelCLI {
         Self {
             executable: "bazel".into(),
             repo_mappings: Default::default(),
+            repo_cache: None,
         }
     }
 }
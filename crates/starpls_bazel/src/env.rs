@@ -82,6 +82,14 @@ pub fn make_bzl_builtins() -> Builtins {
         .into()
 }
 
+/// The globals of Buck2's `BUCK`, `.bzl` and `.bxl` files, which replace Bazel's builtins for
+/// files of the `Buck2` dialect.
+pub fn make_buck2_builtins() -> Builtins {
+    serde_json::from_str::<BuiltinsJson>(include_str!("../data/buck2.builtins.json"))
+        .expect("bug: invalid buck2.builtins.json")
+        .into()
+}
+
 pub fn make_build_builtins() -> Builtins {
     serde_json::from_str::<BuiltinsJson>(include_str!("../data/build.builtins.json"))
         .expect("bug: invalid build.builtins.json")
@@ -0,0 +1,46 @@
+//! The implicit output targets of native rules, e.g. the `lib<name>.so` target that is declared
+//! alongside every `cc_library`.
+
+/// The attributes whose values name output files that are declared as targets in the same
+/// package, e.g. the `outs` attribute of `genrule`.
+pub const OUTPUT_ATTRIBUTES: &[&str] = &["out", "outs"];
+
+/// Returns the names of the implicit output targets declared by a target of the given rule.
+pub fn implicit_outputs(rule: &str, name: &str) -> Vec<String> {
+    let patterns: &[&str] = match rule {
+        "cc_binary" => &["{name}.stripped", "{name}.dwp"],
+        "cc_library" => &[
+            "lib{name}.a",
+            "lib{name}.pic.a",
+            "lib{name}.lo",
+            "lib{name}.pic.lo",
+            "lib{name}.so",
+            "lib{name}.ifso",
+        ],
+        "java_binary" | "java_test" => &[
+            "{name}.jar",
+            "{name}-src.jar",
+            "{name}_deploy.jar",
+            "{name}_deploy-src.jar",
+        ],
+        "java_library" => &["lib{name}.jar", "lib{name}-src.jar"],
+        "py_binary" | "py_test" => &["{name}.zip"],
+        _ => &[],
+    };
+    patterns
+        .iter()
+        .map(|pattern| pattern.replace("{name}", name))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::implicit_outputs;
+
+    #[test]
+    fn test_implicit_outputs() {
+        assert!(implicit_outputs("cc_library", "foo").contains(&"libfoo.so".to_string()));
+        assert!(implicit_outputs("java_binary", "app").contains(&"app_deploy.jar".to_string()));
+        assert!(implicit_outputs("filegroup", "srcs").is_empty());
+    }
+}
@@ -7,13 +7,23 @@ use prost::Message;
 pub use crate::{
     builtin::Builtins,
     label::{Label, ParseError},
+    overrides::BuiltinsOverride,
 };
 
 pub mod attr;
+pub mod buck2;
 pub mod build_language;
+pub mod bzlmod;
 pub mod client;
 pub mod env;
+pub mod implicit_outputs;
 pub mod label;
+pub mod lockfile;
+pub mod overrides;
+pub mod packs;
+pub mod registry;
+pub mod repo_cache;
+pub mod starter_build;
 
 #[cfg(bazel)]
 pub mod builtin {
@@ -0,0 +1,212 @@
+//! Parsing for `MODULE.bazel.lock` files, which record the result of Bazel's module resolution.
+
+use std::{cmp::Ordering, collections::HashMap, fs, path::Path};
+
+use serde_json::Value;
+
+/// A module selected by Bazel's module resolution.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ResolvedModule {
+    /// The resolved version, or `None` if the module's version was overridden by a
+    /// non-registry override, e.g. `local_path_override`.
+    pub version: Option<String>,
+    /// The URL of the registry that the module was fetched from, if any.
+    pub registry: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ModuleLockfile {
+    modules: HashMap<String, ResolvedModule>,
+}
+
+impl ModuleLockfile {
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let contents = fs::read_to_string(path)?;
+        Self::parse(&contents)
+    }
+
+    /// Parses a lockfile. Older lockfiles record the resolved dependency graph directly, under
+    /// `moduleDepGraph`. Newer lockfiles only record the registry files that were fetched during
+    /// resolution, under `registryFileHashes`; since resolution selects the highest requested
+    /// version of each module, that version is used for modules missing from the graph.
+    pub fn parse(contents: &str) -> anyhow::Result<Self> {
+        let lockfile: Value = serde_json::from_str(contents)?;
+        let mut modules: HashMap<String, ResolvedModule> = HashMap::new();
+
+        if let Some(hashes) = lockfile
+            .get("registryFileHashes")
+            .and_then(Value::as_object)
+        {
+            for url in hashes.keys() {
+                let (registry, name, version) = match parse_registry_url(url) {
+                    Some(res) => res,
+                    None => continue,
+                };
+                let module = modules
+                    .entry(name.to_string())
+                    .or_insert_with(|| ResolvedModule {
+                        version: None,
+                        registry: None,
+                    });
+                let is_newer = module.version.as_deref().map_or(true, |current| {
+                    compare_versions(version, current) == Ordering::Greater
+                });
+                if is_newer {
+                    module.version = Some(version.to_string());
+                    module.registry = Some(registry.to_string());
+                }
+            }
+        }
+
+        if let Some(graph) = lockfile.get("moduleDepGraph").and_then(Value::as_object) {
+            for (key, node) in graph {
+                if key == "<root>" {
+                    continue;
+                }
+                let name = match node.get("name").and_then(Value::as_str) {
+                    Some(name) => name,
+                    None => continue,
+                };
+                let version = node
+                    .get("version")
+                    .and_then(Value::as_str)
+                    .filter(|version| !version.is_empty() && !key.ends_with("@_"));
+                let registry = modules
+                    .get(name)
+                    .and_then(|module| module.registry.clone())
+                    .filter(|_| version.is_some());
+                modules.insert(
+                    name.to_string(),
+                    ResolvedModule {
+                        version: version.map(|version| version.to_string()),
+                        registry,
+                    },
+                );
+            }
+        }
+
+        Ok(Self { modules })
+    }
+
+    pub fn module(&self, name: &str) -> Option<&ResolvedModule> {
+        self.modules.get(name)
+    }
+}
+
+/// Splits a URL of the form `<registry>/modules/<name>/<version>/MODULE.bazel`.
+fn parse_registry_url(url: &str) -> Option<(&str, &str, &str)> {
+    let rest = url.strip_suffix("/MODULE.bazel")?;
+    let (rest, version) = rest.rsplit_once('/')?;
+    let (rest, name) = rest.rsplit_once('/')?;
+    let registry = rest.strip_suffix("/modules")?;
+    Some((registry, name, version))
+}
+
+/// Compares two module versions as Bazel does. Versions have the form
+/// `RELEASE[-PRERELEASE][+BUILD]`, where the release and prerelease parts are dot-separated
+/// identifiers. Numeric identifiers are compared numerically and sort before alphanumeric ones,
+/// and a version with a prerelease sorts before the same version without one.
+pub fn compare_versions(a: &str, b: &str) -> Ordering {
+    fn split(version: &str) -> (&str, Option<&str>) {
+        let version = version
+            .split_once('+')
+            .map_or(version, |(version, _)| version);
+        match version.split_once('-') {
+            Some((release, prerelease)) => (release, Some(prerelease)),
+            None => (version, None),
+        }
+    }
+
+    fn compare_identifiers(a: &str, b: &str) -> Ordering {
+        let mut a_parts = a.split('.');
+        let mut b_parts = b.split('.');
+        loop {
+            match (a_parts.next(), b_parts.next()) {
+                (Some(a), Some(b)) => {
+                    let ordering = match (a.parse::<u64>(), b.parse::<u64>()) {
+                        (Ok(a), Ok(b)) => a.cmp(&b),
+                        (Ok(_), Err(_)) => Ordering::Less,
+                        (Err(_), Ok(_)) => Ordering::Greater,
+                        (Err(_), Err(_)) => a.cmp(b),
+                    };
+                    if ordering != Ordering::Equal {
+                        return ordering;
+                    }
+                }
+                (Some(_), None) => return Ordering::Greater,
+                (None, Some(_)) => return Ordering::Less,
+                (None, None) => return Ordering::Equal,
+            }
+        }
+    }
+
+    let (a_release, a_prerelease) = split(a);
+    let (b_release, b_prerelease) = split(b);
+    compare_identifiers(a_release, b_release).then_with(|| match (a_prerelease, b_prerelease) {
+        (Some(a), Some(b)) => compare_identifiers(a, b),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cmp::Ordering;
+
+    use super::{compare_versions, ModuleLockfile, ResolvedModule};
+
+    #[test]
+    fn test_compare_versions() {
+        assert_eq!(compare_versions("1.2.3", "1.2.3"), Ordering::Equal);
+        assert_eq!(compare_versions("1.10.0", "1.9.0"), Ordering::Greater);
+        assert_eq!(compare_versions("1.2", "1.2.1"), Ordering::Less);
+        assert_eq!(compare_versions("1.0.0-rc1", "1.0.0"), Ordering::Less);
+        assert_eq!(compare_versions("1.0.0.bcr.1", "1.0.0"), Ordering::Greater);
+        assert_eq!(compare_versions("1.0.0+build", "1.0.0"), Ordering::Equal);
+    }
+
+    #[test]
+    fn test_parse() {
+        let lockfile = ModuleLockfile::parse(
+            r#"{
+                "lockFileVersion": 11,
+                "registryFileHashes": {
+                    "https://bcr.bazel.build/bazel_registry.json": "abc",
+                    "https://bcr.bazel.build/modules/rules_go/0.41.0/MODULE.bazel": "abc",
+                    "https://bcr.bazel.build/modules/rules_go/0.46.0/MODULE.bazel": "abc",
+                    "https://bcr.bazel.build/modules/rules_go/0.9.0/MODULE.bazel": "abc",
+                    "https://example.com/registry/modules/internal/1.0.0/MODULE.bazel": "abc"
+                },
+                "moduleDepGraph": {
+                    "<root>": { "name": "", "version": "" },
+                    "local_lib@_": { "name": "local_lib", "version": "" }
+                }
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            lockfile.module("rules_go"),
+            Some(&ResolvedModule {
+                version: Some("0.46.0".to_string()),
+                registry: Some("https://bcr.bazel.build".to_string()),
+            })
+        );
+        assert_eq!(
+            lockfile.module("internal"),
+            Some(&ResolvedModule {
+                version: Some("1.0.0".to_string()),
+                registry: Some("https://example.com/registry".to_string()),
+            })
+        );
+        assert_eq!(
+            lockfile.module("local_lib"),
+            Some(&ResolvedModule {
+                version: None,
+                registry: None,
+            })
+        );
+        assert_eq!(lockfile.module("missing"), None);
+    }
+}
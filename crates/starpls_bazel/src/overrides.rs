@@ -0,0 +1,329 @@
+//! Workspace-supplied overrides for the bundled builtin definitions, e.g. to model in-house
+//! forks of native rules that accept extra attributes.
+//!
+//! An override file is a JSON document of the following form, where every key is optional:
+//!
+//! ```json
+//! {
+//!     "globals": [{ "name": "glob", "doc": "..." }],
+//!     "types": [{ "name": "ctx", "fields": [{ "name": "my_field", "type": "string" }] }],
+//!     "rules": [
+//!         {
+//!             "name": "cc_library",
+//!             "callable": { "params": [{ "name": "my_attr", "type": "string" }] }
+//!         }
+//!     ]
+//! }
+//! ```
+//!
+//! By default, an override is patched onto the existing definition with the same name: any
+//! properties it sets take precedence, and parameters and fields are merged by name. An override
+//! with `"replace": true` instead replaces the existing definition entirely. Definitions that
+//! don't exist yet are added. When several override files are applied, later files take
+//! precedence over earlier ones.
+
+use std::{fs, path::Path};
+
+use serde::Deserialize;
+
+use crate::{
+    builtin::{Callable, Param, Type, Value},
+    Builtins,
+};
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BuiltinsOverride {
+    globals: Vec<ValueOverride>,
+    types: Vec<TypeOverride>,
+    rules: Vec<ValueOverride>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct TypeOverride {
+    name: String,
+    doc: Option<String>,
+    fields: Vec<ValueOverride>,
+    replace: bool,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ValueOverride {
+    name: String,
+    r#type: Option<String>,
+    doc: Option<String>,
+    callable: Option<CallableOverride>,
+    replace: bool,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct CallableOverride {
+    params: Vec<ParamOverride>,
+    return_type: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ParamOverride {
+    name: String,
+    r#type: Option<String>,
+    doc: Option<String>,
+    default_value: Option<String>,
+    is_mandatory: Option<bool>,
+    is_star_arg: Option<bool>,
+    is_star_star_arg: Option<bool>,
+}
+
+impl BuiltinsOverride {
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let contents = fs::read_to_string(path)?;
+        Self::parse(&contents)
+    }
+
+    pub fn parse(contents: &str) -> anyhow::Result<Self> {
+        Ok(serde_json::from_str(contents)?)
+    }
+
+    /// Applies the overrides to the builtin globals and types, as well as to the native rules
+    /// reported by `bazel info build-language`.
+    pub fn apply(self, builtins: &mut Builtins, rules: &mut Builtins) {
+        apply_values(&mut builtins.global, self.globals);
+        apply_values(&mut rules.global, self.rules);
+        for type_override in self.types {
+            match builtins
+                .r#type
+                .iter_mut()
+                .find(|ty| ty.name == type_override.name)
+            {
+                Some(ty) if !type_override.replace => {
+                    if let Some(doc) = type_override.doc {
+                        ty.doc = doc;
+                    }
+                    apply_values(&mut ty.field, type_override.fields);
+                }
+                Some(ty) => *ty = type_override.into_type(),
+                None => builtins.r#type.push(type_override.into_type()),
+            }
+        }
+    }
+}
+
+impl TypeOverride {
+    fn into_type(self) -> Type {
+        let mut ty = Type {
+            name: self.name,
+            doc: self.doc.unwrap_or_default(),
+            ..Default::default()
+        };
+        apply_values(&mut ty.field, self.fields);
+        ty
+    }
+}
+
+fn apply_values(values: &mut Vec<Value>, overrides: Vec<ValueOverride>) {
+    for value_override in overrides {
+        match values
+            .iter_mut()
+            .find(|value| value.name == value_override.name)
+        {
+            Some(value) if !value_override.replace => value_override.patch(value),
+            Some(value) => {
+                *value = Default::default();
+                value_override.patch(value);
+            }
+            None => {
+                let mut value = Value::default();
+                value_override.patch(&mut value);
+                values.push(value);
+            }
+        }
+    }
+}
+
+impl ValueOverride {
+    fn patch(self, value: &mut Value) {
+        value.name = self.name;
+        if let Some(r#type) = self.r#type {
+            value.r#type = r#type;
+        }
+        if let Some(doc) = self.doc {
+            value.doc = doc;
+        }
+        if let Some(callable_override) = self.callable {
+            let callable = value.callable.get_or_insert_with(|| Callable {
+                return_type: "None".to_string(),
+                ..Default::default()
+            });
+            if let Some(return_type) = callable_override.return_type {
+                callable.return_type = return_type;
+            }
+            for param_override in callable_override.params {
+                match callable
+                    .param
+                    .iter_mut()
+                    .find(|param| param.name == param_override.name)
+                {
+                    Some(param) => param_override.patch(param),
+                    None => {
+                        let mut param = Param {
+                            r#type: "Unknown".to_string(),
+                            ..Default::default()
+                        };
+                        param_override.patch(&mut param);
+                        insert_param(&mut callable.param, param);
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl ParamOverride {
+    fn patch(self, param: &mut Param) {
+        param.name = self.name;
+        if let Some(r#type) = self.r#type {
+            param.r#type = r#type;
+        }
+        if let Some(doc) = self.doc {
+            param.doc = doc;
+        }
+        if let Some(default_value) = self.default_value {
+            param.default_value = default_value;
+        }
+        if let Some(is_mandatory) = self.is_mandatory {
+            param.is_mandatory = is_mandatory;
+        }
+        if let Some(is_star_arg) = self.is_star_arg {
+            param.is_star_arg = is_star_arg;
+        }
+        if let Some(is_star_star_arg) = self.is_star_star_arg {
+            param.is_star_star_arg = is_star_star_arg;
+        }
+    }
+}
+
+/// Inserts a new parameter before any `*args` and `**kwargs` parameters, so that it can still be
+/// passed by keyword.
+fn insert_param(params: &mut Vec<Param>, param: Param) {
+    let pos = if param.is_star_arg || param.is_star_star_arg {
+        params.len()
+    } else {
+        params
+            .iter()
+            .position(|param| param.is_star_arg || param.is_star_star_arg)
+            .unwrap_or(params.len())
+    };
+    params.insert(pos, param);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BuiltinsOverride;
+    use crate::{
+        builtin::{Callable, Param, Type, Value},
+        Builtins,
+    };
+
+    fn param(name: &str) -> Param {
+        Param {
+            name: name.to_string(),
+            r#type: "string".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_apply() {
+        let mut builtins = Builtins {
+            global: vec![Value {
+                name: "glob".to_string(),
+                doc: "Bundled doc.".to_string(),
+                ..Default::default()
+            }],
+            r#type: vec![Type {
+                name: "ctx".to_string(),
+                doc: "Bundled doc.".to_string(),
+                field: vec![Value {
+                    name: "attr".to_string(),
+                    ..Default::default()
+                }],
+            }],
+        };
+        let mut rules = Builtins {
+            global: vec![Value {
+                name: "cc_library".to_string(),
+                doc: "Bundled doc.".to_string(),
+                callable: Some(Callable {
+                    param: vec![param("name"), param("srcs")],
+                    return_type: "None".to_string(),
+                }),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        BuiltinsOverride::parse(
+            r#"{
+                "globals": [{ "name": "glob", "replace": true }, { "name": "my_global", "doc": "Added." }],
+                "types": [{ "name": "ctx", "fields": [{ "name": "my_field" }] }],
+                "rules": [
+                    {
+                        "name": "cc_library",
+                        "callable": {
+                            "params": [
+                                { "name": "srcs", "doc": "Patched doc." },
+                                { "name": "my_attr", "type": "boolean" }
+                            ]
+                        }
+                    }
+                ]
+            }"#,
+        )
+        .unwrap()
+        .apply(&mut builtins, &mut rules);
+
+        let globals = builtins
+            .global
+            .iter()
+            .map(|value| (value.name.as_str(), value.doc.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(globals, vec![("glob", ""), ("my_global", "Added.")]);
+
+        let ctx = &builtins.r#type[0];
+        assert_eq!(ctx.doc, "Bundled doc.");
+        assert_eq!(
+            ctx.field
+                .iter()
+                .map(|field| field.name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["attr", "my_field"]
+        );
+
+        let cc_library = &rules.global[0];
+        assert_eq!(cc_library.doc, "Bundled doc.");
+        let params = &cc_library.callable.as_ref().unwrap().param;
+        assert_eq!(
+            params
+                .iter()
+                .map(|param| (
+                    param.name.as_str(),
+                    param.r#type.as_str(),
+                    param.doc.as_str()
+                ))
+                .collect::<Vec<_>>(),
+            vec![
+                ("name", "string", ""),
+                ("srcs", "string", "Patched doc."),
+                ("my_attr", "boolean", ""),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_rejects_unknown_fields() {
+        assert!(BuiltinsOverride::parse(r#"{ "global": [] }"#).is_err());
+    }
+}
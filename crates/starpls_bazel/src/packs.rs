@@ -0,0 +1,206 @@
+//! Builtin definitions packaged into separately-loadable packs, e.g. the Bazel core
+//! definitions or the native rules of a single language, which are combined at runtime.
+//!
+//! Additional packs can be loaded from a directory without recompiling the language server.
+//! Each subdirectory of such a directory is a pack named after the subdirectory, containing an
+//! encoded `Builtins` proto with globals and types at `builtins.pb`, and one with rules at
+//! `rules.pb`. Either file may be omitted.
+
+use std::{fs, path::Path};
+
+use crate::{builtin::Value, load_builtins, Builtins};
+
+/// The name of the pack containing the bundled Bazel globals and types.
+pub const BAZEL_CORE_PACK: &str = "bazel";
+
+/// The name of the pack containing native rules that don't belong to a language pack.
+pub const NATIVE_RULES_PACK: &str = "native_rules";
+
+/// The language packs that native rules are split into, along with the prefixes of the names of
+/// the rules they contain.
+const LANGUAGE_PACKS: &[(&str, &[&str])] = &[
+    ("rules_cc", &["cc_", "objc_", "fdo_", "propeller_"]),
+    ("rules_java", &["java_"]),
+    ("rules_py", &["py_"]),
+];
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BuiltinPack {
+    pub name: String,
+    pub builtins: Builtins,
+    pub rules: Builtins,
+}
+
+impl BuiltinPack {
+    /// Loads a pack from the given directory.
+    pub fn load(dir: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let dir = dir.as_ref();
+        let name = dir
+            .file_name()
+            .and_then(|name| name.to_str())
+            .ok_or_else(|| anyhow::anyhow!("invalid pack directory: {:?}", dir))?
+            .to_string();
+        let load = |file_name: &str| -> anyhow::Result<Builtins> {
+            let path = dir.join(file_name);
+            if path.exists() {
+                load_builtins(path)
+            } else {
+                Ok(Builtins::default())
+            }
+        };
+        Ok(Self {
+            name,
+            builtins: load("builtins.pb")?,
+            rules: load("rules.pb")?,
+        })
+    }
+}
+
+/// An ordered collection of builtin packs. When several packs define the same global, type, or
+/// rule, the definition from the pack added last takes precedence.
+#[derive(Clone, Debug, Default)]
+pub struct BuiltinPacks {
+    packs: Vec<BuiltinPack>,
+}
+
+impl BuiltinPacks {
+    pub fn add(&mut self, pack: BuiltinPack) {
+        self.packs.push(pack);
+    }
+
+    /// Splits native rules, e.g. those reported by `bazel info build-language`, into language
+    /// packs and adds them.
+    pub fn add_native_rules(&mut self, rules: Builtins) {
+        let mut packs = LANGUAGE_PACKS
+            .iter()
+            .map(|(name, _)| BuiltinPack {
+                name: name.to_string(),
+                ..Default::default()
+            })
+            .collect::<Vec<_>>();
+        let mut native_rules = BuiltinPack {
+            name: NATIVE_RULES_PACK.to_string(),
+            ..Default::default()
+        };
+        for rule in rules.global {
+            let pack = match LANGUAGE_PACKS.iter().position(|(_, prefixes)| {
+                prefixes.iter().any(|prefix| rule.name.starts_with(prefix))
+            }) {
+                Some(pos) => &mut packs[pos],
+                None => &mut native_rules,
+            };
+            pack.rules.global.push(rule);
+        }
+        self.packs.push(native_rules);
+        self.packs.extend(packs);
+    }
+
+    /// Loads every pack in the given directory, in order of name.
+    pub fn add_dir(&mut self, dir: impl AsRef<Path>) -> anyhow::Result<()> {
+        let mut pack_dirs = fs::read_dir(dir)?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.is_dir())
+            .collect::<Vec<_>>();
+        pack_dirs.sort();
+        for pack_dir in pack_dirs {
+            self.add(BuiltinPack::load(pack_dir)?);
+        }
+        Ok(())
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.packs.iter().map(|pack| pack.name.as_str())
+    }
+
+    /// Combines the packs that aren't disabled into a single set of builtins and a single set of
+    /// rules.
+    pub fn combine(self, disabled: &[String]) -> (Builtins, Builtins) {
+        let mut builtins = Builtins::default();
+        let mut rules = Builtins::default();
+        for pack in self
+            .packs
+            .into_iter()
+            .filter(|pack| !disabled.contains(&pack.name))
+        {
+            merge_values(&mut builtins.global, pack.builtins.global);
+            for ty in pack.builtins.r#type {
+                match builtins
+                    .r#type
+                    .iter_mut()
+                    .find(|existing| existing.name == ty.name)
+                {
+                    Some(existing) => *existing = ty,
+                    None => builtins.r#type.push(ty),
+                }
+            }
+            merge_values(&mut rules.global, pack.rules.global);
+        }
+        (builtins, rules)
+    }
+}
+
+fn merge_values(values: &mut Vec<Value>, new_values: Vec<Value>) {
+    for value in new_values {
+        match values
+            .iter_mut()
+            .find(|existing| existing.name == value.name)
+        {
+            Some(existing) => *existing = value,
+            None => values.push(value),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BuiltinPack, BuiltinPacks};
+    use crate::{builtin::Value, Builtins};
+
+    fn values(names: &[&str]) -> Vec<Value> {
+        names
+            .iter()
+            .map(|name| Value {
+                name: name.to_string(),
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    fn names(values: &[Value]) -> Vec<&str> {
+        values.iter().map(|value| value.name.as_str()).collect()
+    }
+
+    #[test]
+    fn test_combine() {
+        let mut packs = BuiltinPacks::default();
+        packs.add(BuiltinPack {
+            name: "bazel".to_string(),
+            builtins: Builtins {
+                global: values(&["glob", "select"]),
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+        packs.add_native_rules(Builtins {
+            global: values(&["cc_library", "filegroup", "java_library", "py_binary"]),
+            ..Default::default()
+        });
+        assert_eq!(
+            packs.names().collect::<Vec<_>>(),
+            vec![
+                "bazel",
+                "native_rules",
+                "rules_cc",
+                "rules_java",
+                "rules_py"
+            ]
+        );
+
+        let (builtins, rules) = packs.combine(&["rules_java".to_string()]);
+        assert_eq!(names(&builtins.global), vec!["glob", "select"]);
+        assert_eq!(
+            names(&rules.global),
+            vec!["filegroup", "cc_library", "py_binary"]
+        );
+    }
+}
@@ -0,0 +1,90 @@
+//! Cached module metadata from a Bazel registry, such as the Bazel Central Registry (BCR).
+//!
+//! The cache is a directory laid out like the registry itself, e.g. a local checkout of the
+//! BCR, where each module's metadata is stored at `modules/<name>/metadata.json`.
+
+use std::{collections::HashMap, fs, path::Path};
+
+use serde::Deserialize;
+
+use crate::lockfile::compare_versions;
+
+/// The contents of a module's `metadata.json` file.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct ModuleMetadata {
+    pub versions: Vec<String>,
+    /// Maps yanked versions to the reason they were yanked.
+    pub yanked_versions: HashMap<String, String>,
+}
+
+impl ModuleMetadata {
+    pub fn parse(contents: &str) -> anyhow::Result<Self> {
+        Ok(serde_json::from_str(contents)?)
+    }
+
+    /// Returns the highest version of the module that hasn't been yanked.
+    pub fn latest_version(&self) -> Option<&str> {
+        self.versions
+            .iter()
+            .filter(|version| !self.yanked_versions.contains_key(*version))
+            .max_by(|a, b| compare_versions(a, b))
+            .map(|version| version.as_str())
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RegistryMetadata {
+    modules: HashMap<String, ModuleMetadata>,
+}
+
+impl RegistryMetadata {
+    /// Loads the metadata for every module in the given cache directory. Modules whose metadata
+    /// can't be read or parsed are skipped.
+    pub fn load(dir: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let mut modules = HashMap::new();
+        for entry in fs::read_dir(dir.as_ref().join("modules"))? {
+            let entry = entry?;
+            let name = match entry.file_name().into_string() {
+                Ok(name) => name,
+                Err(_) => continue,
+            };
+            let metadata = match fs::read_to_string(entry.path().join("metadata.json"))
+                .ok()
+                .and_then(|contents| ModuleMetadata::parse(&contents).ok())
+            {
+                Some(metadata) => metadata,
+                None => continue,
+            };
+            modules.insert(name, metadata);
+        }
+        Ok(Self { modules })
+    }
+
+    pub fn insert(&mut self, name: String, metadata: ModuleMetadata) {
+        self.modules.insert(name, metadata);
+    }
+
+    pub fn module(&self, name: &str) -> Option<&ModuleMetadata> {
+        self.modules.get(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ModuleMetadata;
+
+    #[test]
+    fn test_latest_version() {
+        let metadata = ModuleMetadata::parse(
+            r#"{
+                "homepage": "https://github.com/bazelbuild/rules_go",
+                "versions": ["0.9.0", "0.41.0", "0.46.0", "0.47.0"],
+                "yanked_versions": { "0.47.0": "Broken release." }
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(metadata.latest_version(), Some("0.46.0"));
+        assert_eq!(ModuleMetadata::default().latest_version(), None);
+    }
+}
@@ -0,0 +1,170 @@
+//! A cache of the repository mappings of external repositories, which are expensive to compute
+//! since each one takes a Bazel invocation. Only repository mappings are cached: the files of
+//! external repositories are parsed and analyzed like any other file, which is cheap in
+//! comparison, and their results live in the analysis database rather than on disk.
+//!
+//! Entries are keyed by the hash that Bazel records in the marker file of each fetched repository,
+//! which changes whenever the repository's definition does, e.g. when it's upgraded to a new
+//! version or commit, along with a fingerprint of the workspace's dependency resolution. A
+//! repository's mapping depends on how the root module resolved its dependencies, e.g. through
+//! overrides, so the fingerprint covers the workspace's `MODULE.bazel.lock` file, or its
+//! `MODULE.bazel` file if it has no lockfile. Switching branches therefore reuses the entries of
+//! the repositories that didn't change as long as the resolution didn't either. The cache lives in
+//! a user-level directory, so that it's shared by the workspaces that resolve the same
+//! repositories in the same way.
+
+use std::{
+    collections::HashMap,
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+pub struct RepoCache {
+    dir: PathBuf,
+    external_output_base: PathBuf,
+    workspace: PathBuf,
+}
+
+impl RepoCache {
+    pub fn new(
+        dir: impl Into<PathBuf>,
+        external_output_base: impl Into<PathBuf>,
+        workspace: impl Into<PathBuf>,
+    ) -> Self {
+        Self {
+            dir: dir.into(),
+            external_output_base: external_output_base.into(),
+            workspace: workspace.into(),
+        }
+    }
+
+    /// The default cache directory, `$XDG_CACHE_HOME/starpls/repos` or `~/.cache/starpls/repos`.
+    pub fn default_dir() -> Option<PathBuf> {
+        let cache_home = env::var_os("XDG_CACHE_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".cache")))?;
+        Some(cache_home.join("starpls").join("repos"))
+    }
+
+    /// Returns the key identifying the current definition of a fetched repository and the
+    /// workspace's current dependency resolution, or `None` if the repository hasn't been fetched.
+    pub fn repo_key(&self, repo: &str) -> Option<String> {
+        // Bazel 7 and later prefix marker files with `@`, while earlier versions don't.
+        let marker = ["@", ""].iter().find_map(|prefix| {
+            fs::read_to_string(
+                self.external_output_base
+                    .join(format!("{}{}.marker", prefix, repo)),
+            )
+            .ok()
+        })?;
+        let hash = marker.lines().next()?.trim();
+        if hash.is_empty() || !hash.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return None;
+        }
+        Some(format!(
+            "{}-{}-{}",
+            repo,
+            hash,
+            self.resolution_fingerprint()
+        ))
+    }
+
+    /// Returns a fingerprint of the workspace's dependency resolution, which is read on every call
+    /// since the lockfile changes whenever module resolution is re-run.
+    fn resolution_fingerprint(&self) -> String {
+        let contents = ["MODULE.bazel.lock", "MODULE.bazel"]
+            .iter()
+            .find_map(|name| fs::read(self.workspace.join(name)).ok())
+            .unwrap_or_default();
+        format!("{:016x}", fnv1a(&contents))
+    }
+
+    pub fn read_repo_mapping(&self, key: &str) -> Option<HashMap<String, String>> {
+        let contents = fs::read_to_string(self.repo_mapping_path(key)).ok()?;
+        serde_json::from_str(&contents).ok()
+    }
+
+    pub fn write_repo_mapping(
+        &self,
+        key: &str,
+        mapping: &HashMap<String, String>,
+    ) -> anyhow::Result<()> {
+        let path = self.repo_mapping_path(key);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        // Write to a temporary file first, so that other servers sharing the cache never read a
+        // partially written entry.
+        let tmp_path = path.with_extension(format!("json.{}.tmp", std::process::id()));
+        fs::write(&tmp_path, serde_json::to_string(mapping)?)?;
+        fs::rename(&tmp_path, &path)?;
+        Ok(())
+    }
+
+    fn repo_mapping_path(&self, key: &str) -> PathBuf {
+        self.dir.join("repo_mappings").join(format!("{}.json", key))
+    }
+}
+
+/// The 64-bit FNV-1a hash, which unlike the standard library's hashers is stable across Rust
+/// versions, so that the cache's keys are too.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x100000001b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, fs};
+
+    use super::RepoCache;
+
+    #[test]
+    fn test_repo_mapping_round_trip() {
+        let root = std::env::temp_dir().join(format!("starpls-repo-cache-{}", std::process::id()));
+        let external = root.join("external");
+        fs::create_dir_all(&external).unwrap();
+        fs::write(
+            external.join("@rules_go+.marker"),
+            "4e3c1f0a9b\nFILE:@@rules_go+//:MODULE.bazel 123\n",
+        )
+        .unwrap();
+        let workspace = root.join("workspace");
+        fs::create_dir_all(&workspace).unwrap();
+        fs::write(workspace.join("MODULE.bazel.lock"), "{}").unwrap();
+        let cache = RepoCache::new(root.join("cache"), &external, &workspace);
+
+        assert_eq!(cache.repo_key("platforms+"), None);
+        let key = cache.repo_key("rules_go+").unwrap();
+        assert!(key.starts_with("rules_go+-4e3c1f0a9b-"), "{}", key);
+        assert_eq!(cache.read_repo_mapping(&key), None);
+
+        let mapping = HashMap::from([("bazel_skylib".to_string(), "bazel_skylib+".to_string())]);
+        cache.write_repo_mapping(&key, &mapping).unwrap();
+        assert_eq!(cache.read_repo_mapping(&key), Some(mapping));
+
+        // Other workspaces that resolve their dependencies differently, e.g. with an override,
+        // don't share the entry.
+        let other_workspace = root.join("other_workspace");
+        fs::create_dir_all(&other_workspace).unwrap();
+        fs::write(
+            other_workspace.join("MODULE.bazel.lock"),
+            r#"{"moduleDepGraph": {}}"#,
+        )
+        .unwrap();
+        let other_cache = RepoCache::new(root.join("cache"), &external, &other_workspace);
+        let other_key = other_cache.repo_key("rules_go+").unwrap();
+        assert_ne!(other_key, key);
+        assert_eq!(other_cache.read_repo_mapping(&other_key), None);
+
+        // Upgrading the repository changes its marker, and hence its key.
+        fs::write(external.join("@rules_go+.marker"), "77aa01\n").unwrap();
+        let new_key = cache.repo_key("rules_go+").unwrap();
+        assert_ne!(new_key, key);
+        assert_eq!(cache.read_repo_mapping(&new_key), None);
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,371 @@
+//! Generates starter `BUILD` files for directories of sources that aren't built with Bazel yet.
+//!
+//! The sources are split into tests and everything else by their file names, following each
+//! language's usual conventions, e.g. `foo_test.cc`, `test_foo.py`, or `FooTest.java`. Everything
+//! else goes into a single library named after the directory, found with a `glob` so that new
+//! files are picked up, and each test gets its own target that depends on the library.
+
+use starpls_fmt::FormatMode;
+
+/// A language that starter `BUILD` files can be generated for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Language {
+    Cc,
+    Py,
+    Java,
+}
+
+impl Language {
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "cc" => Language::Cc,
+            "py" => Language::Py,
+            "java" => Language::Java,
+            _ => return None,
+        })
+    }
+
+    fn rules_module(self) -> &'static str {
+        match self {
+            Language::Cc => "@rules_cc//cc:defs.bzl",
+            Language::Py => "@rules_python//python:defs.bzl",
+            Language::Java => "@rules_java//java:defs.bzl",
+        }
+    }
+
+    fn library_rule(self) -> &'static str {
+        match self {
+            Language::Cc => "cc_library",
+            Language::Py => "py_library",
+            Language::Java => "java_library",
+        }
+    }
+
+    fn test_rule(self) -> &'static str {
+        match self {
+            Language::Cc => "cc_test",
+            Language::Py => "py_test",
+            Language::Java => "java_test",
+        }
+    }
+
+    fn source_extensions(self) -> &'static [&'static str] {
+        match self {
+            Language::Cc => &["c", "cc", "cpp", "cxx"],
+            Language::Py => &["py"],
+            Language::Java => &["java"],
+        }
+    }
+
+    fn header_extensions(self) -> &'static [&'static str] {
+        match self {
+            Language::Cc => &["h", "hh", "hpp", "hxx", "inc"],
+            Language::Py | Language::Java => &[],
+        }
+    }
+
+    /// The `glob` patterns, without the extension, that match test sources.
+    fn test_patterns(self) -> &'static [&'static str] {
+        match self {
+            Language::Cc => &["*_test", "*_unittest"],
+            Language::Py => &["*_test", "test_*"],
+            Language::Java => &["*Test"],
+        }
+    }
+
+    fn is_test(self, stem: &str) -> bool {
+        self.test_patterns().iter().any(|pattern| {
+            match (pattern.strip_prefix('*'), pattern.strip_suffix('*')) {
+                (Some(suffix), _) => stem.len() > suffix.len() && stem.ends_with(suffix),
+                (_, Some(prefix)) => stem.len() > prefix.len() && stem.starts_with(prefix),
+                _ => stem == *pattern,
+            }
+        })
+    }
+}
+
+enum Value {
+    String(String),
+    List(Vec<String>),
+    Glob {
+        include: Vec<String>,
+        exclude: Vec<String>,
+    },
+}
+
+/// Returns the contents of a starter `BUILD` file for a directory containing the given files, or
+/// `None` if none of the files are sources in the given language. `package_name` is the name of
+/// the directory, which is used as the library's name.
+pub fn starter_build_file(
+    language: Language,
+    package_name: &str,
+    file_names: &[String],
+) -> Option<String> {
+    let mut extensions = Vec::new();
+    let mut headers = Vec::new();
+    let mut tests = Vec::new();
+    let mut has_library_sources = false;
+    for file_name in file_names {
+        let (stem, extension) = match file_name.rsplit_once('.') {
+            Some((stem, extension)) if !stem.is_empty() => (stem, extension),
+            _ => continue,
+        };
+        if language.header_extensions().contains(&extension) {
+            if !headers.contains(&extension) {
+                headers.push(extension);
+            }
+        } else if language.source_extensions().contains(&extension) {
+            if !extensions.contains(&extension) {
+                extensions.push(extension);
+            }
+            if language.is_test(stem) {
+                tests.push((stem, file_name.as_str()));
+            } else {
+                has_library_sources = true;
+            }
+        }
+    }
+    if extensions.is_empty() && headers.is_empty() {
+        return None;
+    }
+    extensions.sort();
+    headers.sort();
+    tests.sort();
+
+    let library_name = target_name(package_name);
+    let has_library = has_library_sources || !headers.is_empty();
+    let mut rules = Vec::new();
+    let mut calls = Vec::new();
+    if has_library {
+        let mut attrs = vec![("name", Value::String(library_name.clone()))];
+        if has_library_sources {
+            let exclude = if tests.is_empty() {
+                Vec::new()
+            } else {
+                extensions
+                    .iter()
+                    .flat_map(|extension| {
+                        language
+                            .test_patterns()
+                            .iter()
+                            .map(move |pattern| format!("{}.{}", pattern, extension))
+                    })
+                    .collect()
+            };
+            attrs.push((
+                "srcs",
+                Value::Glob {
+                    include: patterns(&extensions),
+                    exclude,
+                },
+            ));
+        }
+        if !headers.is_empty() {
+            attrs.push((
+                "hdrs",
+                Value::Glob {
+                    include: patterns(&headers),
+                    exclude: Vec::new(),
+                },
+            ));
+        }
+        rules.push(language.library_rule());
+        calls.push(render_call(language.library_rule(), attrs));
+    }
+    for (stem, file_name) in &tests {
+        let mut attrs = vec![
+            ("name", Value::String(target_name(stem))),
+            ("srcs", Value::List(vec![file_name.to_string()])),
+        ];
+        if has_library {
+            attrs.push(("deps", Value::List(vec![format!(":{}", library_name)])));
+        }
+        calls.push(render_call(language.test_rule(), attrs));
+    }
+    if !tests.is_empty() {
+        rules.push(language.test_rule());
+    }
+
+    let mut text = format!("load(\"{}\"", language.rules_module());
+    for rule in &rules {
+        text.push_str(&format!(", \"{}\"", rule));
+    }
+    text.push_str(")\n");
+    for call in calls {
+        text.push('\n');
+        text.push_str(&call);
+    }
+
+    // The file is run through the formatter, so that it's formatted like any other `BUILD` file
+    // that `starpls` formats even where the rendering above falls short.
+    Some(starpls_fmt::format(&text, FormatMode::Build).unwrap_or(text))
+}
+
+/// Turns a file or directory name into a valid target name.
+fn target_name(name: &str) -> String {
+    let name = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>();
+    if name.is_empty() || name.chars().all(|c| c == '.') {
+        "lib".to_string()
+    } else {
+        name
+    }
+}
+
+fn patterns(extensions: &[&str]) -> Vec<String> {
+    extensions
+        .iter()
+        .map(|extension| format!("*.{}", extension))
+        .collect()
+}
+
+/// Renders a call in the style that Buildifier formats `BUILD` files with: one attribute per line
+/// and lists with more than one element split over several lines, with trailing commas. The
+/// formatter keeps the line breaks of nested lists as written, so they're laid out here.
+fn render_call(rule: &str, attrs: Vec<(&str, Value)>) -> String {
+    let mut text = format!("{}(\n", rule);
+    for (name, value) in attrs {
+        let value = match value {
+            Value::String(value) => quote(&value),
+            Value::List(values) => render_list(&values, 1),
+            Value::Glob { include, exclude } if exclude.is_empty() => {
+                format!("glob({})", render_list(&include, 1))
+            }
+            Value::Glob { include, exclude } => format!(
+                "glob(\n{indent}{indent}{},\n{indent}{indent}exclude = {},\n{indent})",
+                render_list(&include, 2),
+                render_list(&exclude, 2),
+                indent = INDENT,
+            ),
+        };
+        text.push_str(&format!("{}{} = {},\n", INDENT, name, value));
+    }
+    text.push_str(")\n");
+    text
+}
+
+const INDENT: &str = "    ";
+
+fn render_list(values: &[String], depth: usize) -> String {
+    match values {
+        [value] => format!("[{}]", quote(value)),
+        _ => {
+            let mut text = "[\n".to_string();
+            for value in values {
+                text.push_str(&format!("{}{},\n", INDENT.repeat(depth + 1), quote(value)));
+            }
+            text.push_str(&INDENT.repeat(depth));
+            text.push(']');
+            text
+        }
+    }
+}
+
+fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{starter_build_file, Language};
+
+    fn check(language: Language, file_names: &[&str], expected: Option<&str>) {
+        let file_names = file_names
+            .iter()
+            .map(|file_name| file_name.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            starter_build_file(language, "widgets", &file_names).as_deref(),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_cc() {
+        check(
+            Language::Cc,
+            &["widget.cc", "widget.h", "widget_test.cc", "README.md"],
+            Some(
+                r#"load("@rules_cc//cc:defs.bzl", "cc_library", "cc_test")
+
+cc_library(
+    name = "widgets",
+    srcs = glob(
+        ["*.cc"],
+        exclude = [
+            "*_test.cc",
+            "*_unittest.cc",
+        ],
+    ),
+    hdrs = glob(["*.h"]),
+)
+
+cc_test(
+    name = "widget_test",
+    srcs = ["widget_test.cc"],
+    deps = [":widgets"],
+)
+"#,
+            ),
+        );
+    }
+
+    #[test]
+    fn test_py() {
+        check(
+            Language::Py,
+            &["__init__.py", "widget.py", "test_widget.py"],
+            Some(
+                r#"load("@rules_python//python:defs.bzl", "py_library", "py_test")
+
+py_library(
+    name = "widgets",
+    srcs = glob(
+        ["*.py"],
+        exclude = [
+            "*_test.py",
+            "test_*.py",
+        ],
+    ),
+)
+
+py_test(
+    name = "test_widget",
+    srcs = ["test_widget.py"],
+    deps = [":widgets"],
+)
+"#,
+            ),
+        );
+    }
+
+    #[test]
+    fn test_java_without_tests() {
+        check(
+            Language::Java,
+            &["Widget.java", "Gadget.java"],
+            Some(
+                r#"load("@rules_java//java:defs.bzl", "java_library")
+
+java_library(
+    name = "widgets",
+    srcs = glob(["*.java"]),
+)
+"#,
+            ),
+        );
+    }
+
+    #[test]
+    fn test_no_sources() {
+        check(Language::Java, &["widget.py", "Test.java.orig"], None);
+    }
+}
@@ -8,6 +8,7 @@ pub struct Diagnostic {
     pub message: String,
     pub severity: Severity,
     pub range: FileRange,
+    pub code: DiagnosticCode,
 }
 
 #[derive(Clone, Debug)]
@@ -19,9 +20,275 @@ pub struct FileRange {
 /// A severity level for diagnostic messages.
 #[derive(Clone, Debug)]
 pub enum Severity {
+    Hint,
     Warning,
     Error,
 }
 
 #[salsa::accumulator]
 pub struct Diagnostics(Diagnostic);
+
+/// The base URL of the documentation for diagnostic codes. Each code is documented under an
+/// anchor named after it.
+pub const DIAGNOSTIC_DOCS_URL: &str =
+    "https://github.com/withered-magic/starpls/blob/main/docs/diagnostics.md";
+
+/// A stable identifier for the check that produced a diagnostic.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum DiagnosticCode {
+    SyntaxError,
+    TopLevelStatement,
+    InvalidAssignment,
+    UnresolvedName,
+    UnknownField,
+    InvalidIndex,
+    InvalidArgument,
+    ArgumentTypeMismatch,
+    MissingArgument,
+    NotCallable,
+    InvalidSlice,
+    UnsupportedOperator,
+    NotIterable,
+    TypeMismatch,
+    TupleSizeMismatch,
+    InvalidTypeAnnotation,
+    UnresolvedLoad,
+    UnresolvedSymbol,
+    LoadCycle,
+    MissingHeader,
+    Misspelling,
+    Todo,
+    ModuleVersionMismatch,
+    InvalidUtf8,
+    MissingNativePrefix,
+    UnnecessaryNativePrefix,
+    InvalidAttributeValue,
+    DuplicateKey,
+    DuplicateLoadSymbol,
+    UnusedLoad,
+    UnusedVariable,
+    MergeablePlatformTargets,
+    NonCanonicalLabel,
+    /// A diagnostic reported by a custom lint rule, with the rule's code.
+    Lint(&'static str),
+}
+
+impl DiagnosticCode {
+    pub const ALL: &'static [DiagnosticCode] = &[
+        Self::SyntaxError,
+        Self::TopLevelStatement,
+        Self::InvalidAssignment,
+        Self::UnresolvedName,
+        Self::UnknownField,
+        Self::InvalidIndex,
+        Self::InvalidArgument,
+        Self::ArgumentTypeMismatch,
+        Self::MissingArgument,
+        Self::NotCallable,
+        Self::InvalidSlice,
+        Self::UnsupportedOperator,
+        Self::NotIterable,
+        Self::TypeMismatch,
+        Self::TupleSizeMismatch,
+        Self::InvalidTypeAnnotation,
+        Self::UnresolvedLoad,
+        Self::UnresolvedSymbol,
+        Self::LoadCycle,
+        Self::MissingHeader,
+        Self::Misspelling,
+        Self::Todo,
+        Self::ModuleVersionMismatch,
+        Self::InvalidUtf8,
+        Self::MissingNativePrefix,
+        Self::UnnecessaryNativePrefix,
+        Self::InvalidAttributeValue,
+        Self::DuplicateKey,
+        Self::DuplicateLoadSymbol,
+        Self::UnusedLoad,
+        Self::UnusedVariable,
+        Self::MergeablePlatformTargets,
+        Self::NonCanonicalLabel,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::SyntaxError => "syntax-error",
+            Self::TopLevelStatement => "top-level-statement",
+            Self::InvalidAssignment => "invalid-assignment",
+            Self::UnresolvedName => "unresolved-name",
+            Self::UnknownField => "unknown-field",
+            Self::InvalidIndex => "invalid-index",
+            Self::InvalidArgument => "invalid-argument",
+            Self::ArgumentTypeMismatch => "argument-type-mismatch",
+            Self::MissingArgument => "missing-argument",
+            Self::NotCallable => "not-callable",
+            Self::InvalidSlice => "invalid-slice",
+            Self::UnsupportedOperator => "unsupported-operator",
+            Self::NotIterable => "not-iterable",
+            Self::TypeMismatch => "type-mismatch",
+            Self::TupleSizeMismatch => "tuple-size-mismatch",
+            Self::InvalidTypeAnnotation => "invalid-type-annotation",
+            Self::UnresolvedLoad => "unresolved-load",
+            Self::UnresolvedSymbol => "unresolved-symbol",
+            Self::LoadCycle => "load-cycle",
+            Self::MissingHeader => "missing-header",
+            Self::Misspelling => "misspelling",
+            Self::Todo => "todo",
+            Self::ModuleVersionMismatch => "module-version-mismatch",
+            Self::InvalidUtf8 => "invalid-utf8",
+            Self::MissingNativePrefix => "missing-native-prefix",
+            Self::UnnecessaryNativePrefix => "unnecessary-native-prefix",
+            Self::InvalidAttributeValue => "invalid-attribute-value",
+            Self::DuplicateKey => "duplicate-key",
+            Self::DuplicateLoadSymbol => "duplicate-load-symbol",
+            Self::UnusedLoad => "unused-load",
+            Self::UnusedVariable => "unused-variable",
+            Self::MergeablePlatformTargets => "mergeable-platform-targets",
+            Self::NonCanonicalLabel => "non-canonical-label",
+            Self::Lint(code) => code,
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|code| code.name() == name)
+    }
+
+    /// A one-line summary of the check.
+    pub fn summary(self) -> &'static str {
+        match self {
+            Self::SyntaxError => "The file could not be parsed.",
+            Self::TopLevelStatement => "A statement isn't allowed at the top level of a file.",
+            Self::InvalidAssignment => "The target of an assignment is not assignable.",
+            Self::UnresolvedName => "A name is not defined.",
+            Self::UnknownField => "A field doesn't exist on a value's type.",
+            Self::InvalidIndex => {
+                "A value can't be indexed, or can't be indexed with the given type."
+            }
+            Self::InvalidArgument => "A call passes arguments the callee doesn't accept.",
+            Self::ArgumentTypeMismatch => "An argument has the wrong type.",
+            Self::MissingArgument => "A call doesn't pass a required argument.",
+            Self::NotCallable => "A value that isn't callable is called.",
+            Self::InvalidSlice => "A slice expression is invalid.",
+            Self::UnsupportedOperator => "An operator isn't supported for the given types.",
+            Self::NotIterable => "A value that isn't iterable is iterated over.",
+            Self::TypeMismatch => "A value has the wrong type.",
+            Self::TupleSizeMismatch => "An unpacking assignment has the wrong number of targets.",
+            Self::InvalidTypeAnnotation => "A type annotation can't be resolved.",
+            Self::UnresolvedLoad => "A `load` statement refers to a file that can't be found.",
+            Self::UnresolvedSymbol => "A loaded symbol isn't exported by the loaded file.",
+            Self::LoadCycle => "Files load each other in a cycle.",
+            Self::MissingHeader => "The file doesn't start with the required header.",
+            Self::Misspelling => "A word in a comment or string may be misspelled.",
+            Self::Todo => "A comment contains a TODO-style tag.",
+            Self::ModuleVersionMismatch => {
+                "A `bazel_dep` resolves to a different version than it declares."
+            }
+            Self::InvalidUtf8 => "The file contains invalid UTF-8.",
+            Self::MissingNativePrefix => {
+                "A native rule or function is called without `native.` in a `.bzl` file."
+            }
+            Self::UnnecessaryNativePrefix => "A `BUILD` file calls a function through `native.`.",
+            Self::InvalidAttributeValue => {
+                "An argument isn't one of the values its parameter accepts."
+            }
+            Self::DuplicateKey => "A dict literal has the same key more than once.",
+            Self::DuplicateLoadSymbol => "A `load` statement binds the same name more than once.",
+            Self::UnusedLoad => "A loaded symbol is never used.",
+            Self::UnusedVariable => "A local variable is assigned but never read.",
+            Self::MergeablePlatformTargets => {
+                "Targets for different platforms only differ in some of their attributes."
+            }
+            Self::NonCanonicalLabel => "A label isn't written in the workspace's label style.",
+            Self::Lint(_) => "A custom lint rule reported a problem.",
+        }
+    }
+
+    /// What the check looks for.
+    pub fn description(self) -> &'static str {
+        match self {
+            Self::SyntaxError => "The parser encountered a token it didn't expect, e.g. an unterminated string, a missing closing bracket, or inconsistent indentation.",
+            Self::TopLevelStatement => "Starlark doesn't allow `if` and `for` statements at the top level of a file.",
+            Self::InvalidAssignment => "Only names, attributes, index expressions, and lists or tuples of these can be assigned to.",
+            Self::UnresolvedName => "The name isn't defined in the current scope, isn't a builtin, and isn't loaded from another file.",
+            Self::UnknownField => "The type of the value being accessed has no field with the given name.",
+            Self::InvalidIndex => "The value being indexed doesn't support indexing, the index has the wrong type, or the index is out of range for a tuple.",
+            Self::InvalidArgument => "The call passes an unexpected positional or keyword argument, passes the same argument more than once, or passes arguments in an invalid order, e.g. a positional argument after a keyword argument.",
+            Self::ArgumentTypeMismatch => "The type of an argument isn't assignable to the type of the parameter it's passed to.",
+            Self::MissingArgument => "A mandatory parameter, or a mandatory attribute of a rule, is missing from a call.",
+            Self::NotCallable => "The type of the called value doesn't support calls.",
+            Self::InvalidSlice => "The value being sliced doesn't support slicing, or the `start`, `stop`, or `step` operands aren't integers or `None`.",
+            Self::UnsupportedOperator => "The operand types of a unary or binary operator don't support the operator, e.g. adding a string to an integer.",
+            Self::NotIterable => "The value used in a `for` loop, comprehension, or unpacking assignment doesn't support iteration.",
+            Self::TypeMismatch => "The type of a value doesn't match the type required by its context, e.g. a type annotation.",
+            Self::TupleSizeMismatch => "The number of targets on the left-hand side of an assignment doesn't match the number of elements in the tuple on the right-hand side.",
+            Self::InvalidTypeAnnotation => "A type comment refers to an unknown type, or is malformed.",
+            Self::UnresolvedLoad => "The label or path in a `load` statement couldn't be resolved to a file, e.g. because the file or the repository doesn't exist.",
+            Self::UnresolvedSymbol => "The file loaded by a `load` statement has no top-level definition with the given name, or the name is private.",
+            Self::LoadCycle => "A file loads itself, either directly or through a chain of other files. Bazel rejects such cycles.",
+            Self::MissingHeader => "The workspace requires every file to start with a header, e.g. a license notice.",
+            Self::Misspelling => "The spell checker didn't find the word in its dictionary or the workspace word list.",
+            Self::Todo => "The comment contains one of the configured tags, e.g. `TODO` or `FIXME`.",
+            Self::ModuleVersionMismatch => "Bazel's module resolution selected a different version of the module than the one declared in `MODULE.bazel`, usually because another module depends on a newer version.",
+            Self::InvalidUtf8 => "The file isn't valid UTF-8, e.g. because it was written by a tool that uses a different encoding. Invalid byte sequences are replaced with U+FFFD for analysis, so names and strings containing them may not resolve as expected.",
+            Self::MissingNativePrefix => "Native rules and functions like `cc_library` and `glob` aren't global in `.bzl` files, where they must be accessed through the `native` module, e.g. as `native.cc_library`.",
+            Self::UnnecessaryNativePrefix => "The `native` module is only available in `.bzl` files. In `BUILD` files, native rules and functions are called directly.",
+            Self::InvalidAttributeValue => "Some attributes and parameters only accept a fixed set of values, e.g. the attributes of a rule declared with `attr.string(values = [...])`, or `cfg` of `attr.label()`. Only literal values are checked.",
+            Self::DuplicateKey => "Bazel rejects dict literals with duplicate keys, while other Starlark dialects silently keep the value of the last one. Only literal keys are checked, and keys are compared by value, so e.g. `\"a\"` and `'a'` are the same key.",
+            Self::DuplicateLoadSymbol => "Bazel rejects `load` statements that bind the same name more than once, either by loading a symbol twice or by using the same alias for two symbols.",
+            Self::UnusedLoad => "No name in the file refers to the symbol bound by a `load` item. Unused loads make Bazel load and evaluate files that aren't needed.",
+            Self::UnusedVariable => "A variable assigned inside a function is never read afterwards, which often points to a typo or leftover code. Variables whose names start with an underscore, and top-level variables, which other files may load, aren't checked.",
+            Self::MergeablePlatformTargets => "Targets of the same rule whose names only differ in a platform suffix, e.g. `foo_linux` and `foo_macos`, set the same attributes, and only some of the attribute values differ. Such copies are easier to maintain as a single target that picks the platform-specific values with `select()`.",
+            Self::NonCanonicalLabel => "The workspace requires labels in `BUILD` files to be written either in full, e.g. `//foo/bar:bar` and `//foo/bar:baz`, or in their shortest form, e.g. `//foo/bar` for the target named after its package and `:baz` for targets in the same package. Labels in `load` statements and package groups like `:__pkg__` aren't checked.",
+            Self::Lint(_) => "The lint rule is compiled into the server by the organization that built it, rather than being part of `starpls`.",
+        }
+    }
+
+    /// How to fix the diagnostics reported by the check.
+    pub fn remediation(self) -> &'static str {
+        match self {
+            Self::SyntaxError => "Fix the syntax at the reported location. Later errors in the same file are often caused by the first one.",
+            Self::TopLevelStatement => "Move the statement into a function, or use a comprehension or conditional expression instead.",
+            Self::InvalidAssignment => "Assign to a variable, field, or element instead.",
+            Self::UnresolvedName => "Check the name for typos, define it, or load it with a `load` statement.",
+            Self::UnknownField => "Check the field name for typos, or check that the value has the expected type.",
+            Self::InvalidIndex => "Check the types of the value and the index.",
+            Self::InvalidArgument => "Check the signature of the callee and adjust the arguments to match it.",
+            Self::ArgumentTypeMismatch => "Pass a value of the expected type, or convert the value first.",
+            Self::MissingArgument => "Pass a value for every mandatory parameter or attribute.",
+            Self::NotCallable => "Check that the called value is a function, rule, or provider.",
+            Self::InvalidSlice => "Check the types of the value and the slice operands.",
+            Self::UnsupportedOperator => "Convert the operands to compatible types.",
+            Self::NotIterable => "Check that the value is a list, tuple, dict, string, or other iterable.",
+            Self::TypeMismatch => "Use a value of the expected type, or fix the annotation.",
+            Self::TupleSizeMismatch => "Add or remove targets so that both sides have the same size.",
+            Self::InvalidTypeAnnotation => "Check the annotation for typos and refer only to known types.",
+            Self::UnresolvedLoad => "Check the label for typos, and check that any external repository is declared and fetched.",
+            Self::UnresolvedSymbol => "Check the symbol name for typos, or load it from the file that defines it.",
+            Self::LoadCycle => "Move the shared definitions into a separate file that both files load.",
+            Self::MissingHeader => "Insert the header, e.g. with the \"Insert required header\" code action.",
+            Self::Misspelling => "Fix the spelling, or add the word to the workspace word list with the \"Add to dictionary\" code action.",
+            Self::Todo => "Resolve the TODO, or disable the check.",
+            Self::ModuleVersionMismatch => "Update the declared version to the resolved one, so that `MODULE.bazel` reflects what is actually built.",
+            Self::InvalidUtf8 => "Re-save the file as UTF-8.",
+            Self::MissingNativePrefix => "Add the `native.` prefix, e.g. with the \"Qualify with `native.`\" code action.",
+            Self::UnnecessaryNativePrefix => "Remove the `native.` prefix, e.g. with the \"Remove `native.` prefix\" code action.",
+            Self::InvalidAttributeValue => "Pass one of the accepted values, which are listed in the diagnostic and offered as completions.",
+            Self::DuplicateKey => "Remove all but one of the entries, e.g. with the \"Remove duplicates of key\" code action, which keeps the last one.",
+            Self::DuplicateLoadSymbol => "Remove the extra symbols, e.g. with the \"Remove duplicate loads\" code action.",
+            Self::UnusedLoad => "Remove the item, e.g. with the \"Remove unused load\" code action, which removes the whole `load` statement if none of its items are used.",
+            Self::UnusedVariable => "Remove the assignment, or rename the variable to start with an underscore. The \"Remove unused variable\" code action keeps the assigned value if it contains a call, in case the call has side effects.",
+            Self::MergeablePlatformTargets => "Merge the targets, e.g. with the \"Merge into `foo` with `select()`\" code action, which reuses the `config_setting`s the workspace already selects on for each platform. Update any references from other packages to the merged target.",
+            Self::NonCanonicalLabel => "Rewrite the label, e.g. with the \"Expand label\" or \"Shorten label\" code action, or rewrite every label in the file with \"Canonicalize all labels in file\".",
+            Self::Lint(_) => "See the documentation of the lint rule, or ask its maintainers.",
+        }
+    }
+
+    /// The URL of the code's documentation. Custom lint rules aren't documented by `starpls`, so
+    /// they have none.
+    pub fn docs_url(self) -> Option<String> {
+        match self {
+            Self::Lint(_) => None,
+            _ => Some(format!("{}#{}", DIAGNOSTIC_DOCS_URL, self.name())),
+        }
+    }
+}
@@ -0,0 +1,103 @@
+//! Decoding of source files, which may have been written by tools that add a byte order mark,
+//! use Windows line endings, or don't produce valid UTF-8.
+//!
+//! Analysis always operates on normalized text, i.e. text without a byte order mark and with `\n`
+//! line endings. Since normalization only removes characters at the end of lines, line and column
+//! positions in the normalized text are the same as in the original text, except on the first line
+//! of files with a byte order mark, whose columns are shifted by the byte order mark. Whether the
+//! text had one is recorded along with its line endings, so that positions can be converted.
+
+use std::{borrow::Cow, fs, io, path::Path};
+
+const BOM: &str = "\u{feff}";
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LineEndings {
+    /// Lines end with `\n`.
+    #[default]
+    Unix,
+    /// Lines end with `\r\n`.
+    Dos,
+}
+
+impl LineEndings {
+    /// Converts normalized text, e.g. the new text of an edit, to use these line endings.
+    pub fn apply<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        match self {
+            LineEndings::Unix => Cow::Borrowed(text),
+            LineEndings::Dos => Cow::Owned(text.replace('\n', "\r\n")),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DecodedText {
+    pub text: String,
+    pub line_endings: LineEndings,
+    /// Whether the original text started with a byte order mark.
+    pub has_bom: bool,
+    /// Whether the original text contained invalid UTF-8, which was replaced with U+FFFD.
+    pub has_invalid_utf8: bool,
+}
+
+/// Decodes the given bytes as UTF-8, replacing invalid sequences with U+FFFD, and normalizes the
+/// resulting text.
+pub fn decode(bytes: Vec<u8>) -> DecodedText {
+    let (text, has_invalid_utf8) = match String::from_utf8(bytes) {
+        Ok(text) => (text, false),
+        Err(err) => (String::from_utf8_lossy(err.as_bytes()).into_owned(), true),
+    };
+    let (text, line_endings, has_bom) = normalize(text);
+    DecodedText {
+        text,
+        line_endings,
+        has_bom,
+        has_invalid_utf8,
+    }
+}
+
+/// Removes the byte order mark from the given text, if any, and converts its line endings to `\n`.
+/// The line endings of the text are considered to be `\r\n` if any line ends with `\r\n`. Also
+/// returns whether the text started with a byte order mark.
+pub fn normalize(mut text: String) -> (String, LineEndings, bool) {
+    let has_bom = text.starts_with(BOM);
+    if has_bom {
+        text.drain(..BOM.len());
+    }
+    if !text.contains("\r\n") {
+        return (text, LineEndings::Unix, has_bom);
+    }
+    (text.replace("\r\n", "\n"), LineEndings::Dos, has_bom)
+}
+
+/// Reads and decodes the file at the given path.
+pub fn read_source(path: impl AsRef<Path>) -> io::Result<DecodedText> {
+    fs::read(path).map(decode)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode, normalize, LineEndings};
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(
+            normalize("\u{feff}x = 1\r\ny = 2\r\n".to_string()),
+            ("x = 1\ny = 2\n".to_string(), LineEndings::Dos, true)
+        );
+        assert_eq!(
+            normalize("x = 1\ny = 2\n".to_string()),
+            ("x = 1\ny = 2\n".to_string(), LineEndings::Unix, false)
+        );
+        assert_eq!(LineEndings::Dos.apply("a\nb"), "a\r\nb");
+    }
+
+    #[test]
+    fn test_decode_invalid_utf8() {
+        let decoded = decode(b"s = \"caf\xe9\"\r\n".to_vec());
+        assert_eq!(decoded.text, "s = \"caf\u{fffd}\"\n");
+        assert_eq!(decoded.line_endings, LineEndings::Dos);
+        assert!(!decoded.has_bom);
+        assert!(decoded.has_invalid_utf8);
+    }
+}
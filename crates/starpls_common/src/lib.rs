@@ -5,9 +5,17 @@ use starpls_syntax::{
     line_index as syntax_line_index, parse_module, LineIndex, Module, ParseTree, SyntaxNode,
 };
 
-pub use crate::diagnostics::{Diagnostic, Diagnostics, FileRange, Severity};
+pub use crate::{
+    diagnostics::{
+        Diagnostic, DiagnosticCode, Diagnostics, FileRange, Severity, DIAGNOSTIC_DOCS_URL,
+    },
+    encoding::{decode, normalize, read_source, DecodedText, LineEndings},
+    symbol::Symbol,
+};
 
 mod diagnostics;
+mod encoding;
+mod symbol;
 mod util;
 
 #[salsa::jar(db = Db)]
@@ -24,6 +32,9 @@ pub struct Jar(
 pub enum Dialect {
     Standard,
     Bazel,
+    /// Buck2's Starlark, used by `BUCK` and `.bxl` files, and by the `.bzl` files of Buck2
+    /// projects.
+    Buck2,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -38,10 +49,22 @@ pub struct LoadItemCandidate {
     pub path: String,
 }
 
+/// The candidates for completing a label in a `BUILD` file, e.g. one of the `deps` of a target.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LabelCandidates {
+    /// The packages that the label can be completed with if its package is being typed, or the
+    /// files in its package otherwise.
+    pub candidates: Vec<LoadItemCandidate>,
+    /// The `BUILD` file of the label's package if its target is being typed, whose targets the
+    /// label can also be completed with.
+    pub build_file: Option<FileId>,
+}
+
 /// A Key corresponding to an interned file path. Use these instead of `Path`s to refer to files.
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct FileId(pub u32);
 
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum ResolvedPath {
     Source {
         path: PathBuf,
@@ -82,6 +105,14 @@ pub trait Db: salsa::DbWithJar<Jar> {
         from: FileId,
     ) -> anyhow::Result<Option<Vec<LoadItemCandidate>>>;
 
+    /// Lists the candidates for completing the label typed so far in `path`. The `BUILD` file
+    /// of the label's package, if any, is loaded into the database.
+    fn list_label_candidates(
+        &self,
+        path: &str,
+        from: FileId,
+    ) -> anyhow::Result<Option<LabelCandidates>>;
+
     fn resolve_path(
         &self,
         path: &str,
@@ -127,6 +158,7 @@ pub fn parse(db: &dyn Db, file: File) -> Parse {
                     range: err.range,
                 },
                 severity: Severity::Error,
+                code: DiagnosticCode::SyntaxError,
             },
         )
     });
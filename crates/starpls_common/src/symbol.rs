@@ -0,0 +1,107 @@
+//! Interned strings for identifiers and labels.
+//!
+//! The same names and labels show up across thousands of files in large repositories, e.g. the
+//! labels of common dependencies. A `Symbol` stores a single shared copy of its text, which is
+//! freed once the last `Symbol` referring to it is dropped. Symbols are cheap to clone, and
+//! compare and hash by pointer.
+
+use std::{
+    cmp::Ordering,
+    fmt::{self, Debug, Display},
+    ops::Deref,
+};
+
+use starpls_intern::Interned;
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct Symbol(Interned<str>);
+
+impl Symbol {
+    pub fn new(text: &str) -> Self {
+        Self(Interned::new_str(text))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Deref for Symbol {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl AsRef<str> for Symbol {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl From<&str> for Symbol {
+    fn from(text: &str) -> Self {
+        Self::new(text)
+    }
+}
+
+impl From<String> for Symbol {
+    fn from(text: String) -> Self {
+        Self::new(&text)
+    }
+}
+
+impl PartialEq<str> for Symbol {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for Symbol {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+/// Symbols are ordered by their text, rather than by pointer, so that sorting them is
+/// deterministic.
+impl PartialOrd for Symbol {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Symbol {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.as_str().cmp(other.as_str())
+    }
+}
+
+impl Debug for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Display::fmt(self.as_str(), f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Symbol;
+
+    #[test]
+    fn test_symbol() {
+        let a = Symbol::new("//foo:bar");
+        let b = Symbol::from("//foo:bar".to_string());
+        assert_eq!(a, b);
+        assert_eq!(a.as_str().as_ptr(), b.as_str().as_ptr());
+        assert_ne!(a, Symbol::new("//foo:baz"));
+        assert_eq!(a, "//foo:bar");
+        assert!(a < Symbol::new("//foo:baz"));
+    }
+}
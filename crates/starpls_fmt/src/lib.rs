@@ -0,0 +1,383 @@
+//! A formatter for Starlark files whose output matches Buildifier's.
+//!
+//! The formatter works on the lossless syntax tree, so comments and the grouping of statements
+//! into blocks separated by blank lines are preserved. Like Buildifier, it takes line breaks from
+//! the input rather than fitting code into a maximum width: in `BUILD` files, calls and collections
+//! with more than one element are broken into one element per line, while in `.bzl` files, they're
+//! kept on one line if they were written on one line. Files with syntax errors aren't formatted.
+
+use starpls_syntax::{TextRange, TextSize};
+
+pub use crate::tables::Tables;
+
+mod printer;
+mod tables;
+
+/// The rules used to format a file, which depend on its kind.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FormatMode {
+    /// `BUILD` files, along with `WORKSPACE`, `MODULE.bazel`, and `REPO.bazel` files, which are
+    /// mostly lists of calls.
+    Build,
+    /// `.bzl` files and other Starlark files.
+    Bzl,
+}
+
+/// Formats a file, returning `None` if it has syntax errors.
+pub fn format(text: &str, mode: FormatMode) -> Option<String> {
+    printer::print(text, mode, None).map(|output| output.text)
+}
+
+/// Formats a file like [`format`], and also rewrites `BUILD` files like Buildifier does with the
+/// given tables: keyword arguments are sorted by priority, and sortable string lists are sorted.
+pub fn format_with_tables(text: &str, mode: FormatMode, tables: &Tables) -> Option<String> {
+    printer::print(text, mode, Some(tables)).map(|output| output.text)
+}
+
+/// Formats the top-level statements that intersect `range`, returning the range of the text to
+/// replace along with its replacement. The range to replace covers whole statements, including
+/// their comments. Returns `None` if the file has syntax errors or if `range` doesn't intersect any
+/// statements.
+pub fn format_range(text: &str, range: TextRange, mode: FormatMode) -> Option<(TextRange, String)> {
+    print_range(text, range, mode, None)
+}
+
+/// Formats the top-level statements that intersect `range` like [`format_range`], rewriting them
+/// with the given tables like [`format_with_tables`].
+pub fn format_range_with_tables(
+    text: &str,
+    range: TextRange,
+    mode: FormatMode,
+    tables: &Tables,
+) -> Option<(TextRange, String)> {
+    print_range(text, range, mode, Some(tables))
+}
+
+/// Formats a file like [`format`], but returns the formatted text piece by piece: each top-level
+/// statement along with its comments, and the whitespace between them. Each piece comes with the
+/// range of the original text that it replaces, and together the ranges cover the whole file in
+/// order, so that callers can edit only the statements that changed.
+pub fn format_statements(text: &str, mode: FormatMode) -> Option<Vec<(TextRange, String)>> {
+    print_statements(text, mode, None)
+}
+
+/// Formats a file piece by piece like [`format_statements`], rewriting it with the given tables
+/// like [`format_with_tables`].
+pub fn format_statements_with_tables(
+    text: &str,
+    mode: FormatMode,
+    tables: &Tables,
+) -> Option<Vec<(TextRange, String)>> {
+    print_statements(text, mode, Some(tables))
+}
+
+fn print_range(
+    text: &str,
+    range: TextRange,
+    mode: FormatMode,
+    tables: Option<&Tables>,
+) -> Option<(TextRange, String)> {
+    let output = printer::print(text, mode, tables)?;
+    let mut chunks = output
+        .chunks
+        .iter()
+        .filter(|chunk| chunk.source.intersect(range).is_some());
+    let first = chunks.next()?;
+    let last = chunks.last().unwrap_or(first);
+    Some((
+        first.source.cover(last.source),
+        output.text[first.output.start..last.output.end].to_string(),
+    ))
+}
+
+fn print_statements(
+    text: &str,
+    mode: FormatMode,
+    tables: Option<&Tables>,
+) -> Option<Vec<(TextRange, String)>> {
+    let output = printer::print(text, mode, tables)?;
+    let mut pieces = Vec::with_capacity(output.chunks.len() * 2 + 1);
+    let mut source_end = TextSize::from(0);
+    let mut output_end = 0;
+    for chunk in &output.chunks {
+        pieces.push((
+            TextRange::new(source_end, chunk.source.start()),
+            output.text[output_end..chunk.output.start].to_string(),
+        ));
+        pieces.push((chunk.source, output.text[chunk.output.clone()].to_string()));
+        source_end = chunk.source.end();
+        output_end = chunk.output.end;
+    }
+    pieces.push((
+        TextRange::new(source_end, TextSize::of(text)),
+        output.text[output_end..].to_string(),
+    ));
+    Some(pieces)
+}
+
+#[cfg(test)]
+mod tests {
+    use starpls_syntax::{TextRange, TextSize};
+
+    use super::{format, format_range, format_statements, format_with_tables, FormatMode, Tables};
+
+    fn check(mode: FormatMode, input: &str, expected: &str) {
+        let actual = format(input, mode).expect("input should have no syntax errors");
+        assert_eq!(actual, expected);
+        assert_eq!(format(&actual, mode).as_deref(), Some(expected));
+    }
+
+    #[test]
+    fn test_build_calls() {
+        check(
+            FormatMode::Build,
+            r#"cc_library(name='foo',srcs=["a.cc","b.cc"],deps=[":bar"])
+exports_files(["a.txt"])
+"#,
+            r#"cc_library(
+    name = "foo",
+    srcs = [
+        "a.cc",
+        "b.cc",
+    ],
+    deps = [":bar"],
+)
+
+exports_files(["a.txt"])
+"#,
+        );
+    }
+
+    #[test]
+    fn test_comments() {
+        check(
+            FormatMode::Build,
+            r#"# Leading comment.
+filegroup(
+  name = "srcs",  # The name.
+  srcs = [
+    # The first file.
+    "a.txt",
+    "b.txt",  # The second file.
+    # The end.
+  ],
+)
+"#,
+            r#"# Leading comment.
+filegroup(
+    name = "srcs",  # The name.
+    srcs = [
+        # The first file.
+        "a.txt",
+        "b.txt",  # The second file.
+        # The end.
+    ],
+)
+"#,
+        );
+    }
+
+    #[test]
+    fn test_bzl_blank_lines() {
+        check(
+            FormatMode::Bzl,
+            r#"load("//:defs.bzl", "a")
+load("//:other.bzl", b = "c")
+# Comment about x.
+x = 1  # Trailing comment.
+
+
+y = [1,
+  2]
+z = (1,)
+def f(a,b=1, *args, **kwargs):
+  """Docstring."""
+  if a and not b:
+    return a+b
+  elif a:
+    pass
+  else:
+    return [x for x in args if x]
+"#,
+            r#"load("//:defs.bzl", "a")
+load("//:other.bzl", b = "c")
+
+# Comment about x.
+x = 1  # Trailing comment.
+
+y = [
+    1,
+    2,
+]
+z = (1,)
+
+def f(a, b = 1, *args, **kwargs):
+    """Docstring."""
+    if a and not b:
+        return a + b
+    elif a:
+        pass
+    else:
+        return [x for x in args if x]
+"#,
+        );
+    }
+
+    #[test]
+    fn test_expressions() {
+        check(
+            FormatMode::Bzl,
+            r#"a = x[1:2]+y[::2]
+b = {'k':v for k,v in d.items()}
+c = lambda x,y: -x if x else ~y
+d = (
+  1)
+e = f(*args,**kwargs)[0].attr
+"#,
+            r#"a = x[1:2] + y[::2]
+b = {"k": v for k, v in d.items()}
+c = lambda x, y: -x if x else ~y
+d = (
+    1
+)
+e = f(*args, **kwargs)[0].attr
+"#,
+        );
+    }
+
+    #[test]
+    fn test_syntax_errors() {
+        assert_eq!(format("foo(\n", FormatMode::Bzl), None);
+    }
+
+    #[test]
+    fn test_format_range() {
+        let input = "a=1\n\nb=[1,\n2]\nc=3\n";
+        let start = TextSize::from(input.find('b').unwrap() as u32);
+        let (range, text) = format_range(input, TextRange::empty(start), FormatMode::Bzl).unwrap();
+        assert_eq!(&input[range], "b=[1,\n2]");
+        assert_eq!(text, "b = [\n    1,\n    2,\n]");
+    }
+
+    #[test]
+    fn test_format_statements() {
+        let input = "# Header.\na=1\n\n\n\nb=2\n";
+        let pieces = format_statements(input, FormatMode::Bzl).unwrap();
+        assert_eq!(
+            pieces
+                .iter()
+                .map(|(range, text)| (&input[*range], text.as_str()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("", ""),
+                ("# Header.\na=1", "# Header.\na = 1"),
+                ("\n\n\n\n", "\n\n"),
+                ("b=2", "b = 2"),
+                ("\n", "\n"),
+            ]
+        );
+        assert_eq!(
+            pieces.into_iter().map(|(_, text)| text).collect::<String>(),
+            format(input, FormatMode::Bzl).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_sort_with_tables() {
+        let input = r#"cc_library(
+    deps = ["@foo//:foo", "//b:b", ":a"],
+    copts = ["-Wall", "-O2"],
+    srcs = [
+        "b.cc",
+        "a.cc",  # Comes first.
+        # Generated sources.
+        "gen_a.cc",
+        "gen_c.cc",
+        "gen_b.cc",
+    ],
+    name = "lib",
+)
+"#;
+        let expected = r#"cc_library(
+    name = "lib",
+    srcs = [
+        "a.cc",  # Comes first.
+        "b.cc",
+        # Generated sources.
+        "gen_a.cc",
+        "gen_b.cc",
+        "gen_c.cc",
+    ],
+    copts = [
+        "-Wall",
+        "-O2",
+    ],
+    deps = [
+        ":a",
+        "//b:b",
+        "@foo//:foo",
+    ],
+)
+"#;
+        let tables = Tables::default();
+        assert_eq!(
+            format_with_tables(input, FormatMode::Build, &tables).as_deref(),
+            Some(expected)
+        );
+        assert_eq!(
+            format_with_tables(expected, FormatMode::Build, &tables).as_deref(),
+            Some(expected)
+        );
+
+        // Without tables, and in `.bzl` files, nothing is reordered.
+        assert!(format(input, FormatMode::Build)
+            .unwrap()
+            .starts_with("cc_library(\n    deps = [\n        \"@foo//:foo\","));
+        assert_eq!(
+            format_with_tables(input, FormatMode::Bzl, &tables),
+            format(input, FormatMode::Bzl)
+        );
+    }
+
+    #[test]
+    fn test_sort_with_custom_tables() {
+        let tables = Tables::parse(
+            r#"{
+    "IsSortableListArg": {"copts": true},
+    "NamePriority": {"name": -99, "copts": -1}
+}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            format_with_tables(
+                "cc_library(srcs = [\"b.cc\", \"a.cc\"], copts = [\"-b\", \"-a\"], name = \"lib\")\n",
+                FormatMode::Build,
+                &tables,
+            )
+            .as_deref(),
+            Some(
+                r#"cc_library(
+    name = "lib",
+    copts = [
+        "-a",
+        "-b",
+    ],
+    srcs = [
+        "b.cc",
+        "a.cc",
+    ],
+)
+"#
+            )
+        );
+    }
+
+    #[test]
+    fn test_do_not_sort() {
+        let input = "filegroup(\n    name = \"a\",\n    srcs = [\n        # do not sort\n        \"b\",\n        \"a\",\n    ],\n)\n";
+        assert_eq!(
+            format_with_tables(input, FormatMode::Build, &Tables::default()).as_deref(),
+            Some(input)
+        );
+    }
+}
@@ -0,0 +1,1020 @@
+//! Prints a syntax tree in Buildifier's format.
+//!
+//! Printing a node fails if it has comments in places that the printer doesn't know how to keep,
+//! e.g. between the operands of a binary expression. The top-level statements containing those
+//! nodes are printed as written instead, so that comments are never dropped.
+//!
+//! Given Buildifier's [`Tables`], calls in `BUILD` files are also rewritten like Buildifier
+//! rewrites them: their keyword arguments are sorted by priority, and the string lists passed as
+//! sortable attributes are sorted, with their comments moving along with them.
+
+use std::{mem, ops::Range};
+
+use starpls_syntax::{
+    ast::{self, AstNode, AstToken},
+    parse_module, SyntaxElement, SyntaxKind,
+    SyntaxKind::*,
+    SyntaxNode, TextRange, TextSize, T,
+};
+
+use crate::{FormatMode, Tables};
+
+const INDENT_WIDTH: usize = 4;
+
+pub(crate) struct Output {
+    pub(crate) text: String,
+    /// The top-level statements of the file, along with their comments.
+    pub(crate) chunks: Vec<Chunk>,
+}
+
+pub(crate) struct Chunk {
+    /// The range of the statement in the original text.
+    pub(crate) source: TextRange,
+    /// The range of the formatted statement in [`Output::text`].
+    pub(crate) output: Range<usize>,
+}
+
+pub(crate) fn print(text: &str, mode: FormatMode, tables: Option<&Tables>) -> Option<Output> {
+    let root = parse(text)?;
+    let tables = tables.filter(|_| mode == FormatMode::Build);
+    let mut printer = Printer {
+        source: text,
+        mode,
+        tables,
+        out: String::new(),
+        indent: 0,
+        level: 0,
+        chunks: Vec::new(),
+        rule: String::new(),
+        sort_strings: false,
+    };
+    printer.statements(&root, false)?;
+    if !printer.out.is_empty() {
+        printer.out.push('\n');
+    }
+
+    // As a last line of defense, make sure that formatting didn't change anything but whitespace
+    // and commas, or just the order of tokens if arguments and lists may have been sorted.
+    let mut before = significant_tokens(&root);
+    let mut after = significant_tokens(&parse(&printer.out)?);
+    if tables.is_some() {
+        before.sort();
+        after.sort();
+    }
+    if before != after {
+        return None;
+    }
+
+    Some(Output {
+        text: printer.out,
+        chunks: printer.chunks,
+    })
+}
+
+fn parse(text: &str) -> Option<SyntaxNode> {
+    let mut has_errors = false;
+    let root = parse_module(text, &mut |_| has_errors = true).syntax();
+    let has_error_nodes = root
+        .descendants_with_tokens()
+        .any(|element| element.kind() == ERROR);
+    (!has_errors && !has_error_nodes).then_some(root)
+}
+
+fn significant_tokens(root: &SyntaxNode) -> Vec<(SyntaxKind, String)> {
+    root.descendants_with_tokens()
+        .filter_map(|element| element.into_token())
+        .filter(|token| !matches!(token.kind(), WHITESPACE | NEWLINE | INDENT | DEDENT | COMMA))
+        .map(|token| {
+            let text = match token.kind() {
+                STRING => string_literal(token.text()),
+                _ => token.text().trim_end().to_string(),
+            };
+            (token.kind(), text)
+        })
+        .collect()
+}
+
+/// A statement, along with the comments on the lines before it and at the end of its line.
+/// Comments that are separated from the next statement by a blank line form a group of their own.
+struct Group {
+    /// Whether the group is separated from the previous one by a blank line.
+    blank_before: bool,
+    comments: Vec<String>,
+    stmt: Option<SyntaxNode>,
+    trailing_comment: Option<String>,
+    range: TextRange,
+}
+
+/// An element of a bracketed sequence, along with the comments on the lines before it and at the
+/// end of its line.
+struct SeqItem {
+    node: SyntaxNode,
+    comments: Vec<String>,
+    suffix_comment: Option<String>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum SeqKind {
+    Call,
+    Def,
+    Dict,
+    List,
+    Load,
+    Tuple,
+}
+
+struct Printer<'a> {
+    source: &'a str,
+    mode: FormatMode,
+    /// The tables used to sort arguments and lists, if they should be sorted.
+    tables: Option<&'a Tables>,
+    out: String,
+    indent: usize,
+    /// How deeply the current statement is nested in blocks.
+    level: usize,
+    chunks: Vec<Chunk>,
+    /// The name of the function called by the innermost call being printed, or an empty string if
+    /// the callee isn't a plain name.
+    rule: String,
+    /// Whether the list about to be printed is the value of a sortable attribute.
+    sort_strings: bool,
+}
+
+impl Printer<'_> {
+    fn statements(&mut self, parent: &SyntaxNode, in_suite: bool) -> Option<()> {
+        let groups = self.groups(parent);
+        for (i, group) in groups.iter().enumerate() {
+            if i > 0 && !self.compact(&groups[i - 1], group) {
+                self.out.push('\n');
+            }
+            if i > 0 || in_suite {
+                self.newline();
+            }
+
+            let start = self.out.len();
+            for (j, comment) in group.comments.iter().enumerate() {
+                if j > 0 {
+                    self.newline();
+                }
+                self.out.push_str(comment);
+            }
+            if let Some(stmt) = &group.stmt {
+                if !group.comments.is_empty() {
+                    self.newline();
+                }
+                let checkpoint = self.out.len();
+                if self.statement(stmt).is_none() {
+                    if self.level > 0 {
+                        return None;
+                    }
+                    self.out.truncate(checkpoint);
+                    self.indent = 0;
+                    let range = TextRange::new(stmt.text_range().start(), content_end(stmt));
+                    self.out.push_str(&self.source[range]);
+                }
+            }
+            if let Some(comment) = &group.trailing_comment {
+                self.out.push_str("  ");
+                self.out.push_str(comment);
+            }
+            if self.level == 0 {
+                self.chunks.push(Chunk {
+                    source: group.range,
+                    output: start..self.out.len(),
+                });
+            }
+        }
+        Some(())
+    }
+
+    fn groups(&self, parent: &SyntaxNode) -> Vec<Group> {
+        let mut groups: Vec<Group> = Vec::new();
+        let mut pending = Vec::new();
+        let mut pending_blank_before = false;
+        let mut pending_start = None;
+        let mut prev_end: Option<TextSize> = None;
+        let mut can_trail = false;
+
+        for element in parent.children_with_tokens() {
+            let is_comment = is_comment(element.kind());
+            if !is_comment && !ast::Statement::can_cast(element.kind()) {
+                continue;
+            }
+            let range = element.text_range();
+            if is_comment && can_trail {
+                if let (Some(group), Some(end)) = (groups.last_mut(), prev_end) {
+                    if !self.has_newline(end, range.start()) {
+                        group.trailing_comment = Some(comment_text(&element));
+                        group.range = group.range.cover(range);
+                        prev_end = Some(range.end());
+                        can_trail = false;
+                        continue;
+                    }
+                }
+            }
+
+            let blank_before =
+                prev_end.map_or(false, |end| self.has_blank_line(end, range.start()));
+            if blank_before && !pending.is_empty() {
+                groups.push(Group {
+                    blank_before: pending_blank_before,
+                    comments: mem::take(&mut pending),
+                    stmt: None,
+                    trailing_comment: None,
+                    range: TextRange::new(
+                        pending_start.unwrap_or(range.start()),
+                        prev_end.unwrap(),
+                    ),
+                });
+            }
+            if pending.is_empty() {
+                pending_blank_before = blank_before;
+                pending_start = Some(range.start());
+            }
+
+            match element.into_node() {
+                Some(stmt) if !is_comment => {
+                    let end = content_end(&stmt);
+                    can_trail = !matches!(stmt.kind(), DEF_STMT | IF_STMT | FOR_STMT);
+                    groups.push(Group {
+                        blank_before: pending_blank_before,
+                        comments: mem::take(&mut pending),
+                        stmt: Some(stmt),
+                        trailing_comment: None,
+                        range: TextRange::new(pending_start.unwrap_or(range.start()), end),
+                    });
+                    prev_end = Some(end);
+                }
+                element => {
+                    let text = match element {
+                        Some(node) => node.text().to_string(),
+                        None => self.source[range].to_string(),
+                    };
+                    pending.push(text.trim_end().to_string());
+                    prev_end = Some(range.end());
+                    can_trail = false;
+                }
+            }
+        }
+
+        if let (Some(start), Some(end)) = (pending_start, prev_end) {
+            if !pending.is_empty() {
+                groups.push(Group {
+                    blank_before: pending_blank_before,
+                    comments: pending,
+                    stmt: None,
+                    trailing_comment: None,
+                    range: TextRange::new(start, end),
+                });
+            }
+        }
+        groups
+    }
+
+    /// Returns whether two consecutive groups are printed without a blank line between them,
+    /// following Buildifier's rules.
+    fn compact(&self, prev: &Group, next: &Group) -> bool {
+        if !next.comments.is_empty() {
+            return false;
+        }
+        let (prev_kind, next_kind) = match (&prev.stmt, &next.stmt) {
+            (Some(prev), Some(next)) => (prev.kind(), next.kind()),
+            _ => return false,
+        };
+        if prev_kind == LOAD_STMT && next_kind == LOAD_STMT {
+            return true;
+        }
+        if prev_kind == LOAD_STMT || next_kind == LOAD_STMT {
+            return false;
+        }
+        if self.mode == FormatMode::Build && self.level == 0 {
+            return false;
+        }
+        if prev_kind == DEF_STMT || next_kind == DEF_STMT {
+            return false;
+        }
+        !next.blank_before
+    }
+
+    fn statement(&mut self, node: &SyntaxNode) -> Option<()> {
+        match node.kind() {
+            DEF_STMT => self.def_stmt(node),
+            IF_STMT => self.if_stmt(node, "if"),
+            FOR_STMT => self.for_stmt(node),
+            LOAD_STMT => {
+                self.out.push_str("load");
+                self.seq(node, SeqKind::Load)
+            }
+            RETURN_STMT => {
+                no_comments(node)?;
+                self.out.push_str("return");
+                if let Some(expr) = expressions(node).next() {
+                    self.out.push(' ');
+                    self.expr(&expr)?;
+                }
+                Some(())
+            }
+            BREAK_STMT => self.keyword(node, "break"),
+            CONTINUE_STMT => self.keyword(node, "continue"),
+            PASS_STMT => self.keyword(node, "pass"),
+            ASSIGN_STMT => {
+                no_comments(node)?;
+                let mut exprs = expressions(node);
+                let lhs = exprs.next()?;
+                let rhs = exprs.next()?;
+                let op = ast::AssignStmt::cast(node.clone())?.assign_op_info()?.0;
+                self.expr(&lhs)?;
+                self.out.push(' ');
+                self.out.push_str(op.text());
+                self.out.push(' ');
+                self.expr(&rhs)
+            }
+            _ => self.expr(node),
+        }
+    }
+
+    fn keyword(&mut self, node: &SyntaxNode, keyword: &str) -> Option<()> {
+        no_comments(node)?;
+        self.out.push_str(keyword);
+        Some(())
+    }
+
+    fn def_stmt(&mut self, node: &SyntaxNode) -> Option<()> {
+        let name = child(node, NAME)?;
+        self.out.push_str("def ");
+        self.out.push_str(&name.text().to_string());
+        self.seq(&child(node, PARAMETERS)?, SeqKind::Def)?;
+        self.out.push(':');
+        self.header_comments(node);
+        self.suite(&child(node, SUITE)?)
+    }
+
+    fn if_stmt(&mut self, node: &SyntaxNode, keyword: &str) -> Option<()> {
+        self.out.push_str(keyword);
+        self.out.push(' ');
+        self.expr(&expressions(node).next()?)?;
+        self.out.push(':');
+
+        // Comments before the first block are at the end of the `if` line, while comments after a
+        // block are on the lines before the `elif` or `else`.
+        let mut in_header = true;
+        for element in node.children_with_tokens() {
+            match element.kind() {
+                kind if is_comment(kind) => {
+                    if in_header {
+                        self.out.push_str("  ");
+                    } else {
+                        self.newline();
+                    }
+                    self.out.push_str(&comment_text(&element));
+                }
+                SUITE => {
+                    self.suite(element.as_node()?)?;
+                    in_header = false;
+                }
+                IF_STMT => {
+                    self.newline();
+                    self.if_stmt(element.as_node()?, "elif")?;
+                }
+                ELSE => {
+                    self.newline();
+                    self.out.push_str("else:");
+                    in_header = true;
+                }
+                _ => {}
+            }
+        }
+        Some(())
+    }
+
+    fn for_stmt(&mut self, node: &SyntaxNode) -> Option<()> {
+        self.out.push_str("for ");
+        self.loop_variables(&child(node, LOOP_VARIABLES)?)?;
+        self.out.push_str(" in ");
+        self.expr(&expressions(node).next()?)?;
+        self.out.push(':');
+        self.header_comments(node);
+        self.suite(&child(node, SUITE)?)
+    }
+
+    /// Prints the comments at the end of the first line of a compound statement.
+    fn header_comments(&mut self, node: &SyntaxNode) {
+        for element in node.children_with_tokens() {
+            if is_comment(element.kind()) {
+                self.out.push_str("  ");
+                self.out.push_str(&comment_text(&element));
+            }
+        }
+    }
+
+    fn suite(&mut self, suite: &SyntaxNode) -> Option<()> {
+        self.indent += INDENT_WIDTH;
+        self.level += 1;
+        let res = self.statements(suite, true);
+        self.indent -= INDENT_WIDTH;
+        self.level -= 1;
+        res
+    }
+
+    fn loop_variables(&mut self, node: &SyntaxNode) -> Option<()> {
+        no_comments(node)?;
+        for (i, expr) in expressions(node).enumerate() {
+            if i > 0 {
+                self.out.push_str(", ");
+            }
+            self.expr(&expr)?;
+        }
+        Some(())
+    }
+
+    fn expr(&mut self, node: &SyntaxNode) -> Option<()> {
+        // Only lists that are the value of a sortable attribute, or concatenated to form it, are
+        // sorted.
+        let sort_strings = mem::take(&mut self.sort_strings);
+        match node.kind() {
+            LIST_EXPR => {
+                self.sort_strings = sort_strings;
+                return self.seq(node, SeqKind::List);
+            }
+            DICT_EXPR => return self.seq(node, SeqKind::Dict),
+            TUPLE_EXPR if child_token(node, T!['(']).is_some() => {
+                return self.seq(node, SeqKind::Tuple)
+            }
+            _ => no_comments(node)?,
+        }
+
+        match node.kind() {
+            NAME_REF => self.out.push_str(&node.text().to_string()),
+            LITERAL_EXPR => {
+                let token = node.first_token()?;
+                match token.kind() {
+                    STRING => self.out.push_str(&string_literal(token.text())),
+                    _ => self.out.push_str(token.text()),
+                }
+            }
+            UNARY_EXPR => {
+                let op = node.first_token()?;
+                self.out.push_str(op.text());
+                if op.kind() == T![not] {
+                    self.out.push(' ');
+                }
+                self.expr(&expressions(node).next()?)?;
+            }
+            BINARY_EXPR => self.binary_expr(node, sort_strings)?,
+            IF_EXPR => {
+                let mut exprs = expressions(node);
+                self.expr(&exprs.next()?)?;
+                self.out.push_str(" if ");
+                self.expr(&exprs.next()?)?;
+                self.out.push_str(" else ");
+                self.expr(&exprs.next()?)?;
+            }
+            LAMBDA_EXPR => {
+                self.out.push_str("lambda");
+                if let Some(params) = child(node, PARAMETERS) {
+                    no_comments(&params)?;
+                    for (i, param) in params.children().enumerate() {
+                        self.out.push_str(if i > 0 { ", " } else { " " });
+                        self.seq_item(&param)?;
+                    }
+                }
+                self.out.push_str(": ");
+                self.expr(&expressions(node).next()?)?;
+            }
+            LIST_COMP | DICT_COMP => self.comprehension(node)?,
+            TUPLE_EXPR => {
+                let mut count = 0;
+                for (i, expr) in expressions(node).enumerate() {
+                    if i > 0 {
+                        self.out.push_str(", ");
+                    }
+                    self.expr(&expr)?;
+                    count += 1;
+                }
+                if count == 1 {
+                    self.out.push(',');
+                }
+            }
+            PAREN_EXPR => {
+                let open = child_token(node, T!['('])?;
+                let inner = expressions(node).next()?;
+                let multi_line = self.has_newline(open.end(), inner.text_range().start());
+                self.out.push('(');
+                if multi_line {
+                    self.indent += INDENT_WIDTH;
+                    self.newline();
+                    self.expr(&inner)?;
+                    self.indent -= INDENT_WIDTH;
+                    self.newline();
+                } else {
+                    self.expr(&inner)?;
+                }
+                self.out.push(')');
+            }
+            DOT_EXPR => {
+                self.expr(&expressions(node).next()?)?;
+                self.out.push('.');
+                self.out.push_str(&child(node, NAME)?.text().to_string());
+            }
+            CALL_EXPR => {
+                let callee = expressions(node).next()?;
+                let args = child(node, ARGUMENTS)?;
+                self.expr(&callee)?;
+                let rule = match callee.kind() {
+                    NAME_REF => callee.text().to_string(),
+                    _ => String::new(),
+                };
+                let outer_rule = mem::replace(&mut self.rule, rule);
+                let res = self.seq(&args, SeqKind::Call);
+                self.rule = outer_rule;
+                res?;
+            }
+            INDEX_EXPR | SLICE_EXPR => {
+                let mut elements = node.children_with_tokens();
+                self.expr(elements.next()?.as_node()?)?;
+                for element in elements {
+                    match element.kind() {
+                        T!['['] | T![']'] | T![:] => self.out.push_str(&element_text(&element)),
+                        _ => {
+                            if let Some(node) = element.as_node() {
+                                self.expr(node)?;
+                            }
+                        }
+                    }
+                }
+            }
+            _ => return None,
+        }
+        Some(())
+    }
+
+    fn binary_expr(&mut self, node: &SyntaxNode, sort_strings: bool) -> Option<()> {
+        let mut exprs = expressions(node);
+        let lhs = exprs.next()?;
+        let rhs = exprs.next()?;
+        let ops = node
+            .children_with_tokens()
+            .filter_map(|element| element.into_token())
+            .filter(|token| token.kind() != WHITESPACE)
+            .collect::<Vec<_>>();
+        let op = ops
+            .iter()
+            .map(|token| token.text())
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        // Keep line breaks after operators, aligning the continuation lines with the start of the
+        // expression.
+        let line_break = self.has_newline(ops.last()?.text_range().end(), rhs.text_range().start());
+        let indent = self.indent;
+        if line_break {
+            self.indent = self.column();
+        }
+        let sort_strings = sort_strings && op == "+";
+        self.sort_strings = sort_strings;
+        self.expr(&lhs)?;
+        self.out.push(' ');
+        self.out.push_str(&op);
+        if line_break {
+            self.newline();
+        } else {
+            self.out.push(' ');
+        }
+        self.sort_strings = sort_strings;
+        self.expr(&rhs)?;
+        self.indent = indent;
+        Some(())
+    }
+
+    fn comprehension(&mut self, node: &SyntaxNode) -> Option<()> {
+        let open = node.first_token()?;
+        let close = node.last_token()?;
+        let body = node.first_child()?;
+        let multi_line = self.has_newline(open.text_range().end(), body.text_range().start());
+        self.out.push_str(open.text());
+        if multi_line {
+            self.indent += INDENT_WIDTH;
+            self.newline();
+        }
+        if body.kind() == DICT_ENTRY {
+            self.seq_item(&body)?;
+        } else {
+            self.expr(&body)?;
+        }
+        for clause in node.children().skip(1) {
+            no_comments(&clause)?;
+            if multi_line {
+                self.newline();
+            } else {
+                self.out.push(' ');
+            }
+            match clause.kind() {
+                COMP_CLAUSE_FOR => {
+                    self.out.push_str("for ");
+                    self.loop_variables(&child(&clause, LOOP_VARIABLES)?)?;
+                    self.out.push_str(" in ");
+                    self.expr(&expressions(&clause).next()?)?;
+                }
+                COMP_CLAUSE_IF => {
+                    self.out.push_str("if ");
+                    self.expr(&expressions(&clause).next()?)?;
+                }
+                _ => return None,
+            }
+        }
+        if multi_line {
+            self.indent -= INDENT_WIDTH;
+            self.newline();
+        }
+        self.out.push_str(close.text());
+        Some(())
+    }
+
+    /// Prints a bracketed, comma-separated sequence, e.g. the arguments of a call or the elements
+    /// of a list.
+    fn seq(&mut self, node: &SyntaxNode, kind: SeqKind) -> Option<()> {
+        let sort_strings = mem::take(&mut self.sort_strings);
+        let mut open = None;
+        let mut close = None;
+        let mut items: Vec<SeqItem> = Vec::new();
+        let mut pending = Vec::new();
+        let mut prev_end = None;
+
+        for element in node.children_with_tokens() {
+            let range = element.text_range();
+            match element.kind() {
+                T!['('] | T!['['] | T!['{'] if open.is_none() => {
+                    open = Some(element);
+                    prev_end = Some(range.end());
+                }
+                T![')'] | T![']'] | T!['}'] => close = Some(element),
+                T![,] => prev_end = Some(range.end()),
+                kind if is_comment(kind) => {
+                    let text = comment_text(&element);
+                    let same_line =
+                        prev_end.map_or(false, |end| !self.has_newline(end, range.start()));
+                    match items.last_mut() {
+                        Some(item)
+                            if same_line && pending.is_empty() && item.suffix_comment.is_none() =>
+                        {
+                            item.suffix_comment = Some(text)
+                        }
+                        _ => pending.push(text),
+                    }
+                }
+                _ => {
+                    if let Some(node) = element.into_node() {
+                        prev_end = Some(range.end());
+                        items.push(SeqItem {
+                            node,
+                            comments: mem::take(&mut pending),
+                            suffix_comment: None,
+                        });
+                    }
+                }
+            }
+        }
+
+        let (open, close) = (open?, close?);
+        let end_comments = pending;
+        let compact = self.use_compact(
+            kind,
+            open.text_range(),
+            close.text_range(),
+            &items,
+            &end_comments,
+        );
+        if let Some(tables) = self.tables {
+            match kind {
+                SeqKind::Call => sort_args(tables, &self.rule, &mut items),
+                SeqKind::List if sort_strings && !has_do_not_sort(&items, &end_comments) => {
+                    sort_strings_in_chunks(tables, &mut items)
+                }
+                _ => {}
+            }
+        }
+
+        self.out.push_str(&element_text(&open));
+        if compact {
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    self.out.push_str(", ");
+                }
+                self.seq_item(&item.node)?;
+            }
+            if kind == SeqKind::Tuple && items.len() == 1 {
+                self.out.push(',');
+            }
+        } else {
+            self.indent += INDENT_WIDTH;
+            for item in &items {
+                for comment in &item.comments {
+                    self.newline();
+                    self.out.push_str(comment);
+                }
+                self.newline();
+                self.seq_item(&item.node)?;
+                self.out.push(',');
+                if let Some(comment) = &item.suffix_comment {
+                    self.out.push_str("  ");
+                    self.out.push_str(comment);
+                }
+            }
+            for comment in &end_comments {
+                self.newline();
+                self.out.push_str(comment);
+            }
+            self.indent -= INDENT_WIDTH;
+            self.newline();
+        }
+        self.out.push_str(&element_text(&close));
+        Some(())
+    }
+
+    /// Returns whether a sequence is printed on one line, following Buildifier's rules.
+    fn use_compact(
+        &self,
+        kind: SeqKind,
+        open: TextRange,
+        close: TextRange,
+        items: &[SeqItem],
+        end_comments: &[String],
+    ) -> bool {
+        let has_comments = !end_comments.is_empty()
+            || items
+                .iter()
+                .any(|item| !item.comments.is_empty() || item.suffix_comment.is_some());
+        if has_comments {
+            return false;
+        }
+
+        // Whether each item starts on the line where the previous one ends.
+        let mut prev_end = open.end();
+        let mut same_lines = true;
+        for item in items {
+            let range = item.node.text_range();
+            same_lines &= !self.has_newline(prev_end, range.start());
+            prev_end = range.end();
+        }
+        same_lines &= !self.has_newline(prev_end, close.start());
+
+        // Outside of top-level statements in `BUILD` files, line breaks are kept as written.
+        let keep_line_breaks = self.mode == FormatMode::Bzl || self.level > 0;
+        if kind == SeqKind::Def || (kind != SeqKind::Load && keep_line_breaks) {
+            return same_lines;
+        }
+
+        let force_multi_line = match items {
+            [] => self.has_newline(open.end(), close.start()),
+            [item] => {
+                let range = item.node.text_range();
+                self.has_newline(open.end(), range.start())
+                    || self.has_newline(range.end(), close.start())
+            }
+            _ => false,
+        };
+        if force_multi_line {
+            return false;
+        }
+        let force_compact = match kind {
+            SeqKind::Load => same_lines,
+            SeqKind::Call | SeqKind::Tuple => {
+                items.len() > 1 && same_lines && items.iter().all(|item| is_simple(&item.node))
+            }
+            _ => false,
+        };
+        force_compact || items.len() <= 1
+    }
+
+    fn seq_item(&mut self, node: &SyntaxNode) -> Option<()> {
+        if ast::Expression::can_cast(node.kind()) {
+            return self.expr(node);
+        }
+        no_comments(node)?;
+        match node.kind() {
+            SIMPLE_ARGUMENT => self.expr(&expressions(node).next()?),
+            UNPACKED_LIST_ARGUMENT => {
+                self.out.push('*');
+                self.expr(&expressions(node).next()?)
+            }
+            UNPACKED_DICT_ARGUMENT => {
+                self.out.push_str("**");
+                self.expr(&expressions(node).next()?)
+            }
+            KEYWORD_ARGUMENT | SIMPLE_PARAMETER => {
+                let name = child(node, NAME)?.text().to_string();
+                self.out.push_str(&name);
+                if let Some(value) = expressions(node).next() {
+                    self.out.push_str(" = ");
+                    self.sort_strings = node.kind() == KEYWORD_ARGUMENT
+                        && self.tables.map_or(false, |tables| {
+                            tables.is_sortable_list_arg(&self.rule, &name)
+                        });
+                    self.expr(&value)?;
+                }
+                Some(())
+            }
+            ARGS_LIST_PARAMETER | KWARGS_DICT_PARAMETER => {
+                self.out.push_str(if node.kind() == ARGS_LIST_PARAMETER {
+                    "*"
+                } else {
+                    "**"
+                });
+                if let Some(name) = child(node, NAME) {
+                    self.out.push_str(&name.text().to_string());
+                }
+                Some(())
+            }
+            DICT_ENTRY => {
+                let mut exprs = expressions(node);
+                self.expr(&exprs.next()?)?;
+                self.out.push_str(": ");
+                self.expr(&exprs.next()?)
+            }
+            LOAD_MODULE | DIRECT_LOAD_ITEM => {
+                self.out
+                    .push_str(&string_literal(&child_text(node, STRING)?));
+                Some(())
+            }
+            ALIASED_LOAD_ITEM => {
+                self.out.push_str(&child(node, NAME)?.text().to_string());
+                self.out.push_str(" = ");
+                self.out
+                    .push_str(&string_literal(&child_text(node, STRING)?));
+                Some(())
+            }
+            _ => None,
+        }
+    }
+
+    fn newline(&mut self) {
+        self.out.push('\n');
+        self.out.extend(std::iter::repeat(' ').take(self.indent));
+    }
+
+    fn column(&self) -> usize {
+        let line_start = self.out.rfind('\n').map_or(0, |pos| pos + 1);
+        self.out[line_start..].chars().count()
+    }
+
+    fn has_newline(&self, start: TextSize, end: TextSize) -> bool {
+        start < end && self.source[TextRange::new(start, end)].contains('\n')
+    }
+
+    fn has_blank_line(&self, start: TextSize, end: TextSize) -> bool {
+        start < end
+            && self.source[TextRange::new(start, end)]
+                .matches('\n')
+                .count()
+                > 1
+    }
+}
+
+/// Sorts the keyword arguments of a call by their priorities and then by their names. Only the
+/// first run of keyword arguments is sorted, so that positional and unpacked arguments keep their
+/// places.
+fn sort_args(tables: &Tables, rule: &str, items: &mut [SeqItem]) {
+    let is_keyword = |item: &SeqItem| item.node.kind() == KEYWORD_ARGUMENT;
+    let start = match items.iter().position(is_keyword) {
+        Some(start) => start,
+        None => return,
+    };
+    let end = items[start..]
+        .iter()
+        .position(|item| !is_keyword(item))
+        .map_or(items.len(), |len| start + len);
+    items[start..end].sort_by_cached_key(|item| {
+        let name = child(&item.node, NAME)
+            .map(|name| name.text().to_string())
+            .unwrap_or_default();
+        (tables.name_priority(rule, &name), name)
+    });
+}
+
+/// Sorts each run of string literals in a list. Runs are broken by other elements and by comments
+/// on lines of their own, so that comments describing a group of elements keep describing them.
+fn sort_strings_in_chunks(tables: &Tables, items: &mut [SeqItem]) {
+    let mut start = 0;
+    while start < items.len() {
+        if string_value(&items[start].node).is_none() {
+            start += 1;
+            continue;
+        }
+        let mut end = start + 1;
+        while end < items.len()
+            && items[end].comments.is_empty()
+            && string_value(&items[end].node).is_some()
+        {
+            end += 1;
+        }
+        items[start..end].sort_by_cached_key(|item| {
+            tables.sort_key(&string_value(&item.node).unwrap_or_default())
+        });
+        start = end;
+    }
+}
+
+/// Returns whether a list is marked with a `# do not sort` comment, which Buildifier respects.
+fn has_do_not_sort(items: &[SeqItem], end_comments: &[String]) -> bool {
+    items
+        .iter()
+        .flat_map(|item| item.comments.iter().chain(&item.suffix_comment))
+        .chain(end_comments)
+        .any(|comment| comment.contains("do not sort"))
+}
+
+fn string_value(node: &SyntaxNode) -> Option<String> {
+    if node.kind() != LITERAL_EXPR {
+        return None;
+    }
+    let token = node.first_token().filter(|token| token.kind() == STRING)?;
+    Some(ast::String::cast(token)?.value()?.to_string())
+}
+
+/// Returns the end of a statement's last token, excluding the newlines and dedents that end its
+/// block.
+fn content_end(node: &SyntaxNode) -> TextSize {
+    node.descendants_with_tokens()
+        .filter_map(|element| element.into_token())
+        .filter(|token| !matches!(token.kind(), WHITESPACE | NEWLINE | INDENT | DEDENT))
+        .last()
+        .map_or(node.text_range().end(), |token| token.text_range().end())
+}
+
+/// Returns whether an argument is a literal, as Buildifier keeps calls with only literal arguments
+/// on one line.
+fn is_simple(node: &SyntaxNode) -> bool {
+    let expr = match node.kind() {
+        SIMPLE_ARGUMENT => match expressions(node).next() {
+            Some(expr) => expr,
+            None => return false,
+        },
+        _ => node.clone(),
+    };
+    matches!(expr.kind(), LITERAL_EXPR | UNARY_EXPR)
+}
+
+/// Converts single-quoted strings to double-quoted ones when that doesn't require escaping
+/// anything.
+fn string_literal(text: &str) -> String {
+    let quote = match text.find(['\'', '"']) {
+        Some(quote) => quote,
+        None => return text.to_string(),
+    };
+    let (prefix, rest) = text.split_at(quote);
+    if rest.starts_with("'''") || !rest.starts_with('\'') || rest.len() < 2 || !rest.ends_with('\'')
+    {
+        return text.to_string();
+    }
+    let contents = &rest[1..rest.len() - 1];
+    if contents.contains(['"', '\\']) {
+        return text.to_string();
+    }
+    format!("{}\"{}\"", prefix, contents)
+}
+
+fn is_comment(kind: SyntaxKind) -> bool {
+    matches!(kind, COMMENT | TYPE_COMMENT)
+}
+
+fn comment_text(element: &SyntaxElement) -> String {
+    element_text(element).trim_end().to_string()
+}
+
+fn element_text(element: &SyntaxElement) -> String {
+    match element.as_token() {
+        Some(token) => token.text().to_string(),
+        None => element
+            .as_node()
+            .map(|node| node.text().to_string())
+            .unwrap_or_default(),
+    }
+}
+
+fn no_comments(node: &SyntaxNode) -> Option<()> {
+    node.children_with_tokens()
+        .all(|element| !is_comment(element.kind()))
+        .then_some(())
+}
+
+fn expressions(node: &SyntaxNode) -> impl Iterator<Item = SyntaxNode> {
+    node.children()
+        .filter(|child| ast::Expression::can_cast(child.kind()))
+}
+
+fn child(node: &SyntaxNode, kind: SyntaxKind) -> Option<SyntaxNode> {
+    node.children().find(|child| child.kind() == kind)
+}
+
+fn child_token(node: &SyntaxNode, kind: SyntaxKind) -> Option<TextRange> {
+    node.children_with_tokens()
+        .find(|element| element.kind() == kind)
+        .map(|element| element.text_range())
+}
+
+fn child_text(node: &SyntaxNode, kind: SyntaxKind) -> Option<String> {
+    node.children_with_tokens()
+        .filter_map(|element| element.into_token())
+        .find(|token| token.kind() == kind)
+        .map(|token| token.text().to_string())
+}
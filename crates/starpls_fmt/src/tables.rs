@@ -0,0 +1,352 @@
+//! Buildifier's tables of attribute names, which control how calls in `BUILD` files are rewritten:
+//! the order of keyword arguments, which string lists are sorted, and which attributes hold
+//! labels. Organizations customize them with a `tables.json` file, passed to Buildifier with
+//! `--tables` to replace the tables or with `--add_tables` to extend them.
+
+use std::{cmp::Ordering, collections::HashMap};
+
+use serde::Deserialize;
+
+/// The priorities of the attributes that Buildifier sorts away from the others. Attributes that
+/// aren't listed have a priority of 0, and attributes with the same priority are sorted by name.
+const NAME_PRIORITY: &[(&str, i32)] = &[
+    ("name", -99),
+    ("archive_override.module_name", -99),
+    ("git_override.module_name", -99),
+    ("local_path_override.module_name", -99),
+    ("multiple_version_override.module_name", -99),
+    ("single_version_override.module_name", -99),
+    ("gwt_name", -98),
+    ("package_name", -97),
+    ("visible_node_name", -96),
+    ("size", -95),
+    ("timeout", -94),
+    ("testonly", -93),
+    ("src", -92),
+    ("srcdir", -91),
+    ("srcs", -90),
+    ("out", -89),
+    ("outs", -88),
+    ("hdrs", -87),
+    ("has_services", -86),
+    ("include", -85),
+    ("of", -84),
+    ("baseline", -83),
+    ("destdir", 1),
+    ("exports", 2),
+    ("runtime_deps", 3),
+    ("deps", 4),
+    ("implementation", 5),
+    ("implements", 6),
+    ("alwayslink", 7),
+];
+
+/// The attributes whose values are lists of strings that Buildifier keeps sorted.
+const SORTABLE_LIST_ARGS: &[&str] = &[
+    "cc_deps",
+    "common_deps",
+    "compatible_with",
+    "configs",
+    "constraints",
+    "data",
+    "default_visibility",
+    "deps",
+    "deps_java",
+    "exported_deps",
+    "exports",
+    "filegroups",
+    "files",
+    "hdrs",
+    "implementation_deps",
+    "imports",
+    "includes",
+    "inherits",
+    "javadeps",
+    "lib_deps",
+    "module_deps",
+    "out",
+    "outs",
+    "packages",
+    "plugin_modules",
+    "proto_deps",
+    "protos",
+    "pubs",
+    "resources",
+    "runtime_deps",
+    "shared_deps",
+    "similar_deps",
+    "srcs",
+    "swig_includes",
+    "swigdeps",
+    "tags",
+    "tests",
+    "to_start_extensions",
+    "tools",
+    "visibility",
+];
+
+/// The attributes of specific rules that aren't sorted even though their names are in
+/// [`SORTABLE_LIST_ARGS`], given as `rule.attribute`.
+const SORTABLE_DENYLIST: &[&str] = &["genrule.srcs"];
+
+/// The attributes whose values are labels, or lists of labels.
+const LABEL_ARGS: &[&str] = &[
+    "app_target",
+    "appdir",
+    "base_package",
+    "build_deps",
+    "cc_deps",
+    "ccdeps",
+    "common_deps",
+    "compile_deps",
+    "compiler",
+    "data",
+    "default_visibility",
+    "dep",
+    "deps",
+    "deps_java",
+    "dont_depend_on",
+    "env_deps",
+    "envscripts",
+    "exported_deps",
+    "exports",
+    "externs_list",
+    "files",
+    "globals",
+    "implementation",
+    "implementation_deps",
+    "implements",
+    "includes",
+    "interface",
+    "jar",
+    "jars",
+    "javadeps",
+    "lib_deps",
+    "library",
+    "malloc",
+    "model",
+    "mods",
+    "module_deps",
+    "module_target",
+    "of",
+    "plugins",
+    "private_deps",
+    "proto",
+    "proto_deps",
+    "proto_target",
+    "protos",
+    "resource",
+    "resources",
+    "runtime_deps",
+    "scope",
+    "shared_deps",
+    "similar_deps",
+    "source_jar",
+    "src",
+    "srcs",
+    "stripped_targets",
+    "suites",
+    "swigdeps",
+    "target",
+    "target_devices",
+    "target_platforms",
+    "template",
+    "test",
+    "tests",
+    "tests_deps",
+    "tool",
+    "tools",
+    "visibility",
+];
+
+/// The tables used to rewrite calls in `BUILD` files. The default tables are Buildifier's own.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Tables {
+    /// The attributes whose values are labels.
+    pub label_args: Vec<String>,
+    /// The attributes of specific rules whose values aren't labels, given as `rule.attribute`.
+    pub label_denylist: Vec<String>,
+    /// The attributes whose values are sorted string lists.
+    pub sortable_list_args: Vec<String>,
+    /// The attributes of specific rules that aren't sorted, given as `rule.attribute`.
+    pub sortable_denylist: Vec<String>,
+    /// The attributes of specific rules that are sorted even though their names aren't in
+    /// `sortable_list_args`, given as `rule.attribute`.
+    pub sortable_allowlist: Vec<String>,
+    /// The priorities of keyword arguments, given either as `attribute` or as `rule.attribute`.
+    /// Arguments are sorted by priority, and then by name.
+    pub name_priority: HashMap<String, i32>,
+    /// Whether labels in the current repository are sorted as if they had leading slashes.
+    pub strip_label_leading_slashes: bool,
+}
+
+impl Default for Tables {
+    fn default() -> Self {
+        let strings =
+            |names: &[&str]| -> Vec<String> { names.iter().map(|name| name.to_string()).collect() };
+        Self {
+            label_args: strings(LABEL_ARGS),
+            label_denylist: Vec::new(),
+            sortable_list_args: strings(SORTABLE_LIST_ARGS),
+            sortable_denylist: strings(SORTABLE_DENYLIST),
+            sortable_allowlist: Vec::new(),
+            name_priority: NAME_PRIORITY
+                .iter()
+                .map(|(name, priority)| (name.to_string(), *priority))
+                .collect(),
+            strip_label_leading_slashes: false,
+        }
+    }
+}
+
+/// The contents of a `tables.json` file. Like Buildifier, only the entries of the boolean tables
+/// that are set to `true` are used.
+#[derive(Default, Deserialize)]
+#[serde(default, rename_all = "PascalCase")]
+struct Definitions {
+    is_label_arg: HashMap<String, bool>,
+    label_denylist: HashMap<String, bool>,
+    is_sortable_list_arg: HashMap<String, bool>,
+    sortable_denylist: HashMap<String, bool>,
+    sortable_allowlist: HashMap<String, bool>,
+    name_priority: HashMap<String, i32>,
+    strip_label_leading_slashes: bool,
+}
+
+impl Tables {
+    /// Parses the contents of a `tables.json` file. As with Buildifier's `--tables`, the parsed
+    /// tables replace the default ones entirely, so tables missing from the file are empty.
+    pub fn parse(json: &str) -> Result<Self, serde_json::Error> {
+        let definitions: Definitions = serde_json::from_str(json)?;
+        let enabled = |table: HashMap<String, bool>| {
+            let mut names = table
+                .into_iter()
+                .filter(|(_, enabled)| *enabled)
+                .map(|(name, _)| name)
+                .collect::<Vec<_>>();
+            names.sort();
+            names
+        };
+        Ok(Self {
+            label_args: enabled(definitions.is_label_arg),
+            label_denylist: enabled(definitions.label_denylist),
+            sortable_list_args: enabled(definitions.is_sortable_list_arg),
+            sortable_denylist: enabled(definitions.sortable_denylist),
+            sortable_allowlist: enabled(definitions.sortable_allowlist),
+            name_priority: definitions.name_priority,
+            strip_label_leading_slashes: definitions.strip_label_leading_slashes,
+        })
+    }
+
+    /// Adds the entries of `other` to these tables, as with Buildifier's `--add_tables`. Priorities
+    /// in `other` take precedence.
+    pub fn merge(&mut self, other: Tables) {
+        for (tables, others) in [
+            (&mut self.label_args, other.label_args),
+            (&mut self.label_denylist, other.label_denylist),
+            (&mut self.sortable_list_args, other.sortable_list_args),
+            (&mut self.sortable_denylist, other.sortable_denylist),
+            (&mut self.sortable_allowlist, other.sortable_allowlist),
+        ] {
+            for name in others {
+                if !tables.contains(&name) {
+                    tables.push(name);
+                }
+            }
+        }
+        self.name_priority.extend(other.name_priority);
+        self.strip_label_leading_slashes |= other.strip_label_leading_slashes;
+    }
+
+    /// Returns the priority of a keyword argument in a call to `rule`.
+    pub fn name_priority(&self, rule: &str, arg: &str) -> i32 {
+        self.name_priority
+            .get(&format!("{}.{}", rule, arg))
+            .or_else(|| self.name_priority.get(arg))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// Returns whether the string lists passed as `arg` to `rule` are kept sorted.
+    pub fn is_sortable_list_arg(&self, rule: &str, arg: &str) -> bool {
+        let key = format!("{}.{}", rule, arg);
+        (self.sortable_list_args.iter().any(|name| name == arg)
+            && !self.sortable_denylist.contains(&key))
+            || self.sortable_allowlist.contains(&key)
+    }
+
+    /// Returns whether the values passed as `arg` to `rule` are labels.
+    pub fn is_label_arg(&self, rule: &str, arg: &str) -> bool {
+        self.label_args.iter().any(|name| name == arg)
+            && !self.label_denylist.contains(&format!("{}.{}", rule, arg))
+    }
+
+    /// Compares two strings in a sorted list the way Buildifier does: plain strings come first,
+    /// then relative labels, then labels in the current repository, and then labels in other
+    /// repositories. Within each of these groups, strings are compared by their parts between `.`
+    /// and `:` characters, so that e.g. `:a` sorts before `:a.b` and `:a_b`.
+    pub fn compare_strings(&self, a: &str, b: &str) -> Ordering {
+        self.sort_key(a).cmp(&self.sort_key(b))
+    }
+
+    pub(crate) fn sort_key(&self, value: &str) -> (u8, Vec<String>, String) {
+        let phase = if value.starts_with(':') {
+            1
+        } else if value.starts_with("//")
+            || (self.strip_label_leading_slashes && !value.starts_with('@'))
+        {
+            2
+        } else if value.starts_with('@') {
+            3
+        } else {
+            0
+        };
+        let parts = value.split([':', '.']).map(str::to_string).collect();
+        (phase, parts, value.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cmp::Ordering;
+
+    use super::Tables;
+
+    #[test]
+    fn test_compare_strings() {
+        let tables = Tables::default();
+        let mut values = vec!["@foo//:bar", "//a:b", ":a_b", "b.cc", ":a.b", ":a", "a.cc"];
+        values.sort_by(|a, b| tables.compare_strings(a, b));
+        assert_eq!(
+            values,
+            vec!["a.cc", "b.cc", ":a", ":a.b", ":a_b", "//a:b", "@foo//:bar"]
+        );
+        assert_eq!(tables.compare_strings(":a", ":a"), Ordering::Equal);
+    }
+
+    #[test]
+    fn test_parse_and_merge() {
+        let parsed = Tables::parse(
+            r#"{
+    "IsSortableListArg": {"srcs": true, "copts": true, "deps": false},
+    "SortableAllowlist": {"my_rule.args": true},
+    "NamePriority": {"name": -99, "my_rule.config": -50}
+}"#,
+        )
+        .unwrap();
+        assert_eq!(parsed.sortable_list_args, vec!["copts", "srcs"]);
+        assert!(parsed.label_args.is_empty());
+        assert!(!parsed.is_sortable_list_arg("cc_library", "deps"));
+        assert!(parsed.is_sortable_list_arg("my_rule", "args"));
+        assert_eq!(parsed.name_priority("my_rule", "config"), -50);
+        assert_eq!(parsed.name_priority("other_rule", "config"), 0);
+
+        let mut tables = Tables::default();
+        tables.merge(parsed);
+        assert!(tables.is_sortable_list_arg("cc_library", "deps"));
+        assert!(tables.is_sortable_list_arg("cc_library", "copts"));
+        assert!(!tables.is_sortable_list_arg("genrule", "srcs"));
+        assert!(tables.is_label_arg("cc_library", "deps"));
+        assert_eq!(tables.name_priority("cc_library", "srcs"), -90);
+    }
+}
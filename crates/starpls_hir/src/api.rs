@@ -2,7 +2,7 @@ use std::sync::Arc;
 
 use starpls_common::{parse, Diagnostic, Diagnostics, File, InFile};
 use starpls_syntax::{
-    ast::{self, AstNode, AstPtr, SyntaxNodePtr},
+    ast::{self, AstNode, AstPtr, AstToken, SyntaxNodePtr},
     TextSize,
 };
 
@@ -14,7 +14,7 @@ use crate::{
         scope::{self, module_scopes, ParameterDef},
         Function as HirDefFunction, LoadItemId, Stmt,
     },
-    module, source_map,
+    display, module, source_map,
     typeck::{
         self, builtins::BuiltinFunction, intrinsics::IntrinsicFunction, resolve_type_ref,
         FieldInner, ParamInner, Provider, Struct as DefStruct, Substitution, TagClass, Tuple, Ty,
@@ -146,6 +146,191 @@ impl<'a> Semantics<'a> {
                 value: def,
             })
     }
+
+    /// Classifies a string literal by where it's used, e.g. as the value of an attribute that
+    /// takes labels or as the pattern passed to `glob`.
+    pub fn classify_string(&self, file: File, string: &ast::String) -> StringKind {
+        let parent = match string.syntax().parent() {
+            Some(parent) => parent,
+            None => return StringKind::Plain,
+        };
+        if ast::LoadModule::can_cast(parent.kind()) {
+            return StringKind::Label;
+        }
+        if !ast::LiteralExpr::can_cast(parent.kind()) {
+            return StringKind::Plain;
+        }
+        let has_label_prefix = string.value().map_or(false, |value| {
+            value.starts_with(':') || value.starts_with("//") || value.starts_with('@')
+        });
+        let fallback = if has_label_prefix {
+            StringKind::Label
+        } else {
+            StringKind::Plain
+        };
+
+        // Strings that are formatted, e.g. `"%s.cc" % name` or `"{}.cc".format(name)`.
+        match parent.parent() {
+            Some(outer) => {
+                if let Some(binary) = ast::BinaryExpr::cast(outer.clone()) {
+                    let is_lhs = binary.lhs().map_or(false, |lhs| *lhs.syntax() == parent);
+                    if is_lhs
+                        && matches!(
+                            binary.binary_op_info(),
+                            Some((_, ast::BinaryOp::Arith(ast::ArithOp::Mod)))
+                        )
+                    {
+                        return StringKind::FormatString;
+                    }
+                } else if let Some(dot) = ast::DotExpr::cast(outer) {
+                    if dot
+                        .field()
+                        .and_then(|field| field.name())
+                        .map_or(false, |name| name.text() == "format")
+                    {
+                        return StringKind::FormatString;
+                    }
+                }
+            }
+            None => return fallback,
+        }
+
+        // Find the argument that the string is part of, looking through the lists that hold it,
+        // the concatenations of those lists, and the branches of `select` expressions.
+        let mut node = parent;
+        let arg = loop {
+            let outer = match node.parent() {
+                Some(outer) => outer,
+                None => return fallback,
+            };
+            if ast::ListExpr::can_cast(outer.kind()) {
+                node = outer;
+            } else if let Some(binary) = ast::BinaryExpr::cast(outer.clone()) {
+                if !matches!(
+                    binary.binary_op_info(),
+                    Some((_, ast::BinaryOp::Arith(ast::ArithOp::Add)))
+                ) {
+                    return fallback;
+                }
+                node = outer;
+            } else if let Some(entry) = ast::DictEntry::cast(outer.clone()) {
+                let is_value = entry.value().map_or(false, |value| *value.syntax() == node);
+                let call = outer
+                    .parent()
+                    .and_then(|dict| dict.parent())
+                    .and_then(|arg| arg.parent())
+                    .and_then(|args| args.parent())
+                    .and_then(ast::CallExpr::cast);
+                match call {
+                    Some(call) if is_value && callee_name(&call).as_deref() == Some("select") => {
+                        node = call.syntax().clone();
+                    }
+                    _ => return fallback,
+                }
+            } else {
+                break outer;
+            }
+        };
+        let call = match arg
+            .parent()
+            .and_then(|args| args.parent())
+            .and_then(ast::CallExpr::cast)
+        {
+            Some(call) => call,
+            None => return fallback,
+        };
+        let callee = callee_name(&call);
+
+        if ast::SimpleArgument::can_cast(arg.kind()) {
+            return match callee.as_deref() {
+                Some("glob") => StringKind::GlobPattern,
+                Some("exports_files") => StringKind::FilePath,
+                _ => fallback,
+            };
+        }
+        let name = match ast::KeywordArgument::cast(arg).and_then(|arg| arg.name()?.name()) {
+            Some(name) => name.text().to_string(),
+            None => return fallback,
+        };
+        if callee.as_deref() == Some("glob") && matches!(name.as_str(), "include" | "exclude") {
+            return StringKind::GlobPattern;
+        }
+        if matches!(name.as_str(), "visibility" | "default_visibility") {
+            return StringKind::Visibility;
+        }
+
+        // Prefer the kind of the attribute being set, and fall back to the attributes that
+        // commonly take labels for calls that can't be resolved, e.g. to macros.
+        let kind = self.resolve_call_expr(file, &call).and_then(|callable| {
+            callable
+                .params(self.db)
+                .into_iter()
+                .find(|(param, _)| {
+                    param
+                        .name(self.db)
+                        .map_or(false, |param_name| param_name.as_str() == name)
+                })
+                .and_then(|(param, _)| param.attribute_kind(self.db))
+        });
+        let takes_labels = match kind {
+            Some(typeck::AttributeKind::Label | typeck::AttributeKind::LabelList) => true,
+            Some(typeck::AttributeKind::Output | typeck::AttributeKind::OutputList) => {
+                return StringKind::FilePath;
+            }
+            Some(_) => false,
+            None => LABEL_ATTRIBUTES.contains(&name.as_str()),
+        };
+        match (takes_labels, has_label_prefix) {
+            (true, true) => StringKind::Label,
+            (true, false) => StringKind::FilePath,
+            (false, _) => fallback,
+        }
+    }
+}
+
+/// How a string literal is used, judging by where it appears. IDE features use this to agree on
+/// which strings are labels, paths, and patterns.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StringKind {
+    /// A label, e.g. `"//foo:bar"`, or the module of a `load` statement.
+    Label,
+    /// The name of a file in the current package, e.g. `"foo.cc"` in `srcs`.
+    FilePath,
+    /// A pattern passed to `glob`, e.g. `"**/*.cc"`.
+    GlobPattern,
+    /// A string that is formatted with `%` or `format`.
+    FormatString,
+    /// A visibility specification, e.g. `"//visibility:public"`.
+    Visibility,
+    /// Any other string.
+    Plain,
+}
+
+/// Attributes that commonly take labels, whose values are treated as labels when the attributes
+/// of the call can't be determined.
+const LABEL_ATTRIBUTES: &[&str] = &[
+    "actual",
+    "data",
+    "deps",
+    "exports",
+    "hdrs",
+    "implementation_deps",
+    "resources",
+    "runtime_deps",
+    "src",
+    "srcs",
+    "textual_hdrs",
+    "tools",
+];
+
+/// Returns the name of the function being called, e.g. `glob` for both `glob(...)` and
+/// `native.glob(...)`.
+fn callee_name(call: &ast::CallExpr) -> Option<String> {
+    match call.callee()? {
+        ast::Expression::Name(name_ref) => Some(name_ref.name()?.text().to_string()),
+        ast::Expression::Dot(dot) => Some(dot.field()?.name()?.text().to_string()),
+        _ => None,
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -306,10 +491,33 @@ impl Type {
         self.ty.kind() == &TyKind::Unknown
     }
 
+    /// Returns the type in the syntax of type comments. See [`Type::param_annotation`] for
+    /// parameter types.
+    pub fn annotation(&self, db: &dyn Db) -> String {
+        display::annotation(db, &self.ty)
+    }
+
+    /// Returns the type in the syntax of type comments, for use as the type of a parameter in a
+    /// function type comment.
+    pub fn param_annotation(&self, db: &dyn Db) -> String {
+        display::non_leading_annotation(db, &self.ty)
+    }
+
     pub fn is_user_defined_function(&self) -> bool {
         matches!(self.ty.kind(), TyKind::Function(_))
     }
 
+    pub fn is_rule(&self) -> bool {
+        matches!(self.ty.kind(), TyKind::Rule(_))
+    }
+
+    pub fn is_provider(&self) -> bool {
+        matches!(
+            self.ty.kind(),
+            TyKind::Provider(_) | TyKind::ProviderRawConstructor(_, _)
+        )
+    }
+
     pub fn params(&self, db: &dyn Db) -> Vec<(Param, Type)> {
         match self.ty.params(db) {
             Some(params) => params.map(|(param, ty)| (param, ty.into())).collect(),
@@ -367,21 +575,51 @@ impl Type {
                     .fields
                     .as_ref()
                     .and_then(|fields| fields.0.clone())?;
-                source_map(db, dict_expr.file)
-                    .expr_map_back
-                    .get(&dict_expr.value)
-                    .and_then(|ptr| ptr.clone().cast::<ast::DictExpr>())
-                    .and_then(|ptr| {
-                        Some(InFile {
-                            file: dict_expr.file,
-                            value: ptr.try_to_node(&parse(db, dict_expr.file).syntax(db))?,
-                        })
-                    })
+                dict_expr_source(db, &dict_expr)
             }
             _ => None,
         }
     }
 
+    /// Returns the dict literal that declares the attributes of a rule, e.g. the
+    /// `{"srcs": attr.label_list()}` in `rule(attrs = {"srcs": attr.label_list()})`.
+    pub fn rule_attrs_source(&self, db: &dyn Db) -> Option<InFile<ast::DictExpr>> {
+        match self.ty.kind() {
+            TyKind::Rule(rule) => dict_expr_source(db, rule.attrs_source.as_ref()?),
+            _ => None,
+        }
+    }
+
+    /// Returns the expression that declares the fields of a provider or of a struct created with
+    /// `struct(...)`, i.e. the provider's `fields` dict or list, or the call to `struct`. Fields of
+    /// unrelated types can share a name, but not a declaration.
+    pub fn fields_declaration(&self, db: &dyn Db) -> Option<InFile<SyntaxNodePtr>> {
+        let expr = match self.ty.kind() {
+            TyKind::Provider(provider)
+            | TyKind::ProviderInstance(provider)
+            | TyKind::ProviderRawConstructor(_, provider) => provider.fields.as_ref()?.0.clone()?,
+            TyKind::Struct(strukt) => match strukt.as_ref()? {
+                typeck::Struct::Inline { call_expr, .. } => call_expr.clone(),
+                _ => return None,
+            },
+            _ => return None,
+        };
+        let ptr = source_map(db, expr.file)
+            .expr_map_back
+            .get(&expr.value)?
+            .syntax_node_ptr();
+        Some(InFile {
+            file: expr.file,
+            value: ptr,
+        })
+    }
+
+    /// Returns whether this is a provider with an `init` callback, whose calls take the
+    /// callback's parameters instead of the provider's fields.
+    pub fn has_provider_init(&self) -> bool {
+        matches!(self.ty.kind(), TyKind::Provider(provider) if provider.has_init)
+    }
+
     pub fn known_keys(&self, db: &dyn Db) -> Option<Vec<String>> {
         self.ty.known_keys().map(|known_keys| {
             known_keys
@@ -405,6 +643,11 @@ impl Type {
         }
     }
 
+    /// Returns the type with any literal value dropped, e.g. `string` for `Literal["abc"]`.
+    pub fn without_literal(&self) -> Type {
+        self.ty.clone().normalize().into()
+    }
+
     pub fn try_as_inline_struct(&self) -> Option<Struct> {
         match self.ty.kind() {
             TyKind::Struct(strukt) => strukt.as_ref().and_then(|strukt| match strukt {
@@ -498,6 +741,17 @@ impl Callable {
         matches!(self.0, CallableInner::HirDef(_))
     }
 
+    /// Returns the `def` statement of a user-defined function.
+    pub fn source(&self, db: &dyn Db) -> Option<InFile<SyntaxNodePtr>> {
+        match self.0 {
+            CallableInner::HirDef(func) => Some(InFile {
+                file: func.file(db),
+                value: func.ptr(db),
+            }),
+            _ => None,
+        }
+    }
+
     pub fn is_rule(&self) -> bool {
         matches!(self.0, CallableInner::Rule(_))
     }
@@ -519,6 +773,19 @@ impl From<BuiltinFunction> for Callable {
     }
 }
 
+fn dict_expr_source(db: &dyn Db, dict_expr: &InFile<ExprId>) -> Option<InFile<ast::DictExpr>> {
+    source_map(db, dict_expr.file)
+        .expr_map_back
+        .get(&dict_expr.value)
+        .and_then(|ptr| ptr.clone().cast::<ast::DictExpr>())
+        .and_then(|ptr| {
+            Some(InFile {
+                file: dict_expr.file,
+                value: ptr.try_to_node(&parse(db, dict_expr.file).syntax(db))?,
+            })
+        })
+}
+
 #[derive(Clone, Debug)]
 enum CallableInner {
     HirDef(HirDefFunction),
@@ -2,8 +2,7 @@ use std::{collections::HashSet, ops::Index};
 
 use id_arena::{Arena, Id};
 use rustc_hash::FxHashMap;
-use smol_str::SmolStr;
-use starpls_common::File;
+use starpls_common::{File, Symbol};
 use starpls_syntax::{
     ast::{self, AssignOp, AstPtr, BinaryOp, SyntaxNodePtr, UnaryOp},
     TextRange,
@@ -11,6 +10,7 @@ use starpls_syntax::{
 
 use crate::{typeck::TypeRef, Db, Ty, TyKind};
 
+mod interface;
 mod lower;
 pub(crate) mod resolver;
 pub(crate) mod scope;
@@ -18,6 +18,8 @@ pub(crate) mod scope;
 #[cfg(test)]
 mod tests;
 
+pub use interface::ModuleInterface;
+
 pub type ModulePtr = AstPtr<ast::Module>;
 
 pub type ExprId = Id<Expr>;
@@ -422,19 +424,19 @@ impl Literal {
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
-pub struct Name(SmolStr);
+pub struct Name(Symbol);
 
 impl Name {
     pub(crate) fn from_str(name: &str) -> Self {
-        Self::new(SmolStr::new(name))
+        Self::new(Symbol::new(name))
     }
 
     pub(crate) fn missing() -> Self {
-        Self::new(SmolStr::new_inline("[missing name]"))
+        Self::new(Symbol::new("[missing name]"))
     }
 
     pub fn is_missing(&self) -> bool {
-        &self.0 == "[missing name]"
+        self.0 == "[missing name]"
     }
 
     pub fn from_ast_node(name: ast::NameRef) -> Self {
@@ -450,14 +452,20 @@ impl Name {
     }
 
     pub(crate) fn new_inline(name: &'static str) -> Self {
-        Self::new(SmolStr::new_inline(name))
+        Self::new(Symbol::new(name))
     }
 
-    fn new(repr: SmolStr) -> Self {
+    fn new(repr: Symbol) -> Self {
         Self(repr)
     }
 }
 
+impl From<&str> for Name {
+    fn from(name: &str) -> Self {
+        Self::from_str(name)
+    }
+}
+
 #[salsa::interned]
 pub(crate) struct LiteralString {
     pub(crate) value: Box<str>,
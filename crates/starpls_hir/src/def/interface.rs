@@ -0,0 +1,65 @@
+use std::hash::{Hash, Hasher};
+
+use rustc_hash::FxHasher;
+use starpls_common::{parse, File};
+use starpls_syntax::{
+    ast::{self, AstNode, AstToken},
+    SyntaxKind, SyntaxNode,
+};
+
+use crate::Db;
+
+/// A fingerprint of the parts of a module that other modules can observe by loading it.
+///
+/// This covers everything in the module except for the bodies of its functions, whose
+/// docstrings and type comments are still included since they make up part of the functions'
+/// signatures. Function bodies are lowered after the rest of the module, so editing a body
+/// leaves the IDs of every expression, parameter, and function outside of it unchanged. As a
+/// result, inference results for modules that load this one remain valid as long as its
+/// interface stays the same.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ModuleInterface(u64);
+
+impl ModuleInterface {
+    pub fn new(db: &dyn Db, file: File) -> Self {
+        let mut hasher = FxHasher::default();
+        hash_node(&parse(db, file).syntax(db), &mut hasher);
+        Self(hasher.finish())
+    }
+}
+
+fn hash_node(node: &SyntaxNode, hasher: &mut FxHasher) {
+    node.kind().hash(hasher);
+    if let Some(def) = node.parent().and_then(ast::DefStmt::cast) {
+        if node.kind() == SyntaxKind::SUITE {
+            def.doc()
+                .map(|doc| doc.syntax().text().to_string())
+                .hash(hasher);
+            def.suite()
+                .and_then(|suite| suite.type_comment())
+                .map(|type_comment| type_comment.syntax().text().to_string())
+                .hash(hasher);
+            return;
+        }
+    }
+
+    for element in node.children_with_tokens() {
+        if let Some(child) = element.as_node() {
+            hash_node(child, hasher);
+        } else if let Some(token) = element.as_token() {
+            // Trivia doesn't affect lowering. This includes plain comments, but not type comments,
+            // which are parsed into their own nodes.
+            if !matches!(
+                token.kind(),
+                SyntaxKind::WHITESPACE
+                    | SyntaxKind::COMMENT
+                    | SyntaxKind::NEWLINE
+                    | SyntaxKind::INDENT
+                    | SyntaxKind::DEDENT
+            ) {
+                token.kind().hash(hasher);
+                token.text().hash(hasher);
+            }
+        }
+    }
+}
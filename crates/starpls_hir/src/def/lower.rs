@@ -1,7 +1,11 @@
-use starpls_common::{line_index, Diagnostic, Diagnostics, File, FileRange, Severity};
+use std::mem;
+
+use starpls_common::{
+    line_index, Diagnostic, DiagnosticCode, Diagnostics, File, FileRange, Severity,
+};
 use starpls_syntax::{
     ast::{self, AstNode, AstPtr, AstToken, SyntaxNodePtr},
-    SyntaxToken, TextRange,
+    SyntaxKind, SyntaxNode, SyntaxToken, TextRange,
 };
 
 use crate::{
@@ -35,6 +39,7 @@ pub(super) fn lower_module(
             load_item_map: Default::default(),
             load_item_map_back: Default::default(),
         },
+        deferred_bodies: Vec::new(),
     }
     .lower(syntax)
 }
@@ -44,6 +49,8 @@ struct LoweringContext<'a> {
     file: File,
     module: Module,
     source_map: ModuleSourceMap,
+    /// The `def` statements whose bodies haven't been lowered yet, along with those bodies.
+    deferred_bodies: Vec<(StmtId, Option<ast::Suite>)>,
 }
 
 impl<'a> LoweringContext<'a> {
@@ -55,13 +62,14 @@ impl<'a> LoweringContext<'a> {
             .collect();
 
         let mut top_level = Vec::new();
-        for statement in syntax.statements() {
+        for statement in statements_with_recovered(syntax.syntax()) {
             let stmt = self.lower_stmt(statement.clone());
             top_level.push(stmt);
             match &self.module.stmts[stmt] {
                 Stmt::If { .. } => Diagnostics::push(
                     self.db,
                     Diagnostic {
+                        code: DiagnosticCode::TopLevelStatement,
                         message: "Starlark does not allow top-level if statements".to_string(),
                         severity: Severity::Error,
                         range: FileRange {
@@ -73,6 +81,7 @@ impl<'a> LoweringContext<'a> {
                 Stmt::For { .. } => Diagnostics::push(
                     self.db,
                     Diagnostic {
+                        code: DiagnosticCode::TopLevelStatement,
                         message: "Starlark does not allow top-level for statements".to_string(),
                         severity: Severity::Error,
                         range: FileRange {
@@ -85,6 +94,18 @@ impl<'a> LoweringContext<'a> {
             }
         }
         self.module.top_level = top_level.into_boxed_slice();
+
+        // Function bodies are lowered last, so that editing one doesn't change the IDs of anything
+        // declared outside of it. See `ModuleInterface` for why this matters.
+        while !self.deferred_bodies.is_empty() {
+            for (stmt, suite) in mem::take(&mut self.deferred_bodies) {
+                let body = self.lower_suite_opt(suite);
+                if let Stmt::Def { stmts, .. } = &mut self.module.stmts[stmt] {
+                    *stmts = body;
+                }
+            }
+        }
+
         (self.module, self.source_map)
     }
 
@@ -100,7 +121,6 @@ impl<'a> LoweringContext<'a> {
                     spec.as_ref().map(|spec| &spec.0[..]).unwrap_or(&[]),
                     &doc,
                 );
-                let stmts = self.lower_suite_opt(node.suite());
                 let func = Function::new(
                     self.db,
                     self.file,
@@ -110,10 +130,17 @@ impl<'a> LoweringContext<'a> {
                     ptr.syntax_node_ptr(),
                     params,
                 );
-                let stmt = self.alloc_stmt(Stmt::Def { func, stmts }, ptr);
+                let stmt = self.alloc_stmt(
+                    Stmt::Def {
+                        func,
+                        stmts: Box::new([]),
+                    },
+                    ptr,
+                );
                 for param in func.params(self.db).iter() {
                     self.module.param_to_def_stmt.insert(*param, stmt);
                 }
+                self.deferred_bodies.push((stmt, node.suite()));
                 return stmt;
             }
             ast::Statement::If(stmt) => {
@@ -332,17 +359,18 @@ impl<'a> LoweringContext<'a> {
         //     x: The first argument
         //     y: The second argument
         //
+        // Each name may be followed by a type in parentheses, e.g. `x (int): The first argument`.
         // This may be extended to support other styles in the future.
         let find_doc = |name: &str| {
-            let prefix = format!("{}:", name);
             doc.as_ref().and_then(|doc| {
                 doc.lines().find_map(|line| {
-                    let line = line.trim().trim_start_matches('*');
-                    if line.starts_with(&prefix) {
-                        Some(line[prefix.len()..].to_string().into_boxed_str())
-                    } else {
-                        None
-                    }
+                    let rest = line.trim().trim_start_matches('*').strip_prefix(name)?;
+                    let rest = match rest.trim_start().strip_prefix('(') {
+                        Some(type_and_rest) => type_and_rest.split_once(')')?.1,
+                        None => rest,
+                    };
+                    rest.strip_prefix(':')
+                        .map(|doc| doc.to_string().into_boxed_str())
                 })
             })
         };
@@ -439,7 +467,7 @@ impl<'a> LoweringContext<'a> {
     fn lower_suite_opt(&mut self, syntax: Option<ast::Suite>) -> Box<[StmtId]> {
         syntax
             .iter()
-            .flat_map(|suite| suite.statements())
+            .flat_map(|suite| statements_with_recovered(suite.syntax()))
             .map(|statement| self.lower_stmt(statement))
             .collect::<Vec<_>>()
             .into_boxed_slice()
@@ -609,3 +637,18 @@ impl<'a> LoweringContext<'a> {
         id
     }
 }
+
+/// Returns the statements that are children of the given node, including those nested in error
+/// nodes, e.g. an unexpectedly indented block. Lowering these lets us keep providing IDE features
+/// for code that is being edited and doesn't parse cleanly.
+fn statements_with_recovered(parent: &SyntaxNode) -> Vec<ast::Statement> {
+    let mut statements = Vec::new();
+    for child in parent.children() {
+        if child.kind() == SyntaxKind::ERROR {
+            statements.extend(statements_with_recovered(&child));
+        } else if let Some(statement) = ast::Statement::cast(child) {
+            statements.push(statement);
+        }
+    }
+    statements
+}
@@ -2,7 +2,7 @@ use std::collections::{hash_map::Entry, VecDeque};
 
 use id_arena::{Arena, Id};
 use rustc_hash::FxHashMap;
-use starpls_common::{Diagnostic, Diagnostics, File, FileRange, Severity};
+use starpls_common::{Diagnostic, DiagnosticCode, Diagnostics, File, FileRange, Severity};
 
 use crate::{
     def::{CompClause, Expr, ExprId, Function, LoadItem, LoadItemId, Param, ParamId, Stmt, StmtId},
@@ -333,6 +333,7 @@ impl ScopeCollector<'_> {
                 _ => Diagnostics::push(
                     self.db,
                     Diagnostic {
+                        code: DiagnosticCode::InvalidAssignment,
                         message: "Expression is not assignable".to_string(),
                         severity: Severity::Error,
                         range: FileRange {
@@ -1,8 +1,11 @@
 use starpls_bazel::APIContext;
-use starpls_common::{Dialect, File, FileId};
+use starpls_common::{Db as _, Dialect, File, FileId};
 use starpls_test_util::parse_fixture;
 
-use crate::{def::resolver::Resolver, test_database::TestDatabase};
+use crate::{
+    def::{resolver::Resolver, ModuleInterface},
+    test_database::TestDatabase,
+};
 
 fn check_scope(fixture: &str, expected: &[&str]) {
     let test_db: TestDatabase = Default::default();
@@ -162,3 +165,49 @@ def ts_project(tsc = _t$0sc):
         &["_tsc"],
     )
 }
+
+#[test]
+fn test_module_interface() {
+    let mut test_db: TestDatabase = Default::default();
+    let file_id = FileId(0);
+    let file = test_db.create_file(
+        file_id,
+        Dialect::Bazel,
+        Some(APIContext::Bzl),
+        r#"
+FOO = 1
+
+def foo(x = FOO):
+    """Does foo."""
+    return x
+"#
+        .to_string(),
+    );
+    let interface = ModuleInterface::new(&test_db, file);
+
+    // Edits to function bodies and comments don't change the interface.
+    test_db.update_file(
+        file_id,
+        r#"
+# Comment.
+FOO = 1
+
+def foo(x = FOO):
+    """Does foo."""
+    y = x + 1
+    return y
+"#
+        .to_string(),
+    );
+    assert_eq!(ModuleInterface::new(&test_db, file), interface);
+
+    // Edits to docstrings, signatures, and top-level statements do.
+    for contents in [
+        "\nFOO = 1\n\ndef foo(x = FOO):\n    \"\"\"Does bar.\"\"\"\n    return x\n",
+        "\nFOO = 1\n\ndef foo(x = FOO, y = 2):\n    \"\"\"Does foo.\"\"\"\n    return x\n",
+        "\nFOO = 2\n\ndef foo(x = FOO):\n    \"\"\"Does foo.\"\"\"\n    return x\n",
+    ] {
+        test_db.update_file(file_id, contents.to_string());
+        assert_ne!(ModuleInterface::new(&test_db, file), interface);
+    }
+}
@@ -5,7 +5,7 @@ use crate::{
     module,
     typeck::{
         builtins::BuiltinFunctionParam, intrinsics::IntrinsicFunctionParam, resolve_type_ref,
-        Protocol, RuleKind, Tuple, TyKind, TypeRef,
+        Protocol, RuleKind, Tuple, TyData, TyKind, TypeRef,
     },
     Db, Ty, Type,
 };
@@ -256,6 +256,9 @@ impl DisplayWithDb for TyKind {
                 f.write_str(") -> ")?;
                 return func.ret_type_ref(db).fmt(f);
             }
+            TyKind::BuiltinType(ty, Some(TyData::Element(elem))) => {
+                return write!(f, "{}[{}]", ty.name(db).as_str(), elem.display(db).alt())
+            }
             TyKind::BuiltinType(ty, _) => return f.write_str(ty.name(db).as_str()),
             TyKind::BoundVar(index) => return write!(f, "'{}", index),
             TyKind::Protocol(proto) => {
@@ -305,3 +308,65 @@ impl DisplayWithDb for TyKind {
         }
     }
 }
+
+/// Formats a type in the syntax of type comments, e.g. for inserting an inferred type into a
+/// file. Literal types are widened, and types that can't be spelled in a type comment, such as
+/// functions and providers, are written as `Unknown`.
+pub(crate) fn annotation(db: &dyn Db, ty: &Ty) -> String {
+    let text = match ty.kind() {
+        TyKind::Any => "Any",
+        TyKind::None => "None",
+        TyKind::Bool(_) => "bool",
+        TyKind::Int(_) => "int",
+        TyKind::Float => "float",
+        TyKind::String(_) => "string",
+        TyKind::StringElems => "Iterable[string]",
+        TyKind::Bytes => "bytes",
+        TyKind::BytesElems => "Iterable[int]",
+        TyKind::Range => "range",
+        TyKind::List(ty) => return format!("list[{}]", annotation(db, ty)),
+        TyKind::Tuple(Tuple::Variable(ty)) => return format!("Sequence[{}]", annotation(db, ty)),
+        TyKind::Dict(key_ty, value_ty, _) => {
+            return format!(
+                "dict[{}, {}]",
+                annotation(db, key_ty),
+                non_leading_annotation(db, value_ty)
+            )
+        }
+        TyKind::Protocol(Protocol::Iterable(ty)) => {
+            return format!("Iterable[{}]", annotation(db, ty))
+        }
+        TyKind::Protocol(Protocol::Sequence(ty)) => {
+            return format!("Sequence[{}]", annotation(db, ty))
+        }
+        TyKind::Union(tys) => {
+            // Widening literals may leave duplicates, e.g. for `Literal[1] | Literal[2]`.
+            let mut parts: Vec<String> = Vec::with_capacity(tys.len());
+            for ty in tys.iter() {
+                let part = annotation(db, ty);
+                if !parts.contains(&part) {
+                    parts.push(part);
+                }
+            }
+            return parts.join(" | ");
+        }
+        TyKind::Struct(_) => "struct",
+        TyKind::BuiltinType(ty, Some(TyData::Element(elem))) => {
+            return format!("{}[{}]", ty.name(db).as_str(), annotation(db, elem))
+        }
+        TyKind::BuiltinType(ty, _) => return ty.name(db).as_str().to_string(),
+        _ => "Unknown",
+    };
+    text.to_string()
+}
+
+/// Like [`annotation`], but for positions in a type comment that can't start with the `None`
+/// keyword, e.g. parameter types and type arguments after the first. `NoneType` is accepted
+/// everywhere instead.
+pub(crate) fn non_leading_annotation(db: &dyn Db, ty: &Ty) -> String {
+    let text = annotation(db, ty);
+    match text.strip_prefix("None") {
+        Some(rest) if rest.is_empty() || rest.starts_with(' ') => format!("NoneType{}", rest),
+        _ => text,
+    }
+}
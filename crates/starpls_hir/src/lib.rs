@@ -5,7 +5,7 @@ use starpls_common::{parse, Dialect, File, Parse};
 // Ideally, we should find a way to avoid needing this, as it breaks the API boundary of this crate.
 pub use crate::{
     api::*,
-    def::{ExprId, LoadItemId, LoadStmt, Module, Name, ParamId},
+    def::{ExprId, LoadItemId, LoadStmt, Module, ModuleInterface, Name, ParamId},
     display::{DisplayWithDb, DisplayWithDbWrapper},
     typeck::{builtins::BuiltinDefs, Cancelled, GlobalCtxt, InferenceOptions, Ty, TyCtxt},
 };
@@ -45,6 +45,7 @@ pub struct Jar(
     typeck::builtins::BuiltinGlobals,
     typeck::builtins::BuiltinType,
     typeck::builtins::BuiltinTypes,
+    typeck::builtins::buck2_builtin_globals_query,
     typeck::builtins::builtin_globals_query,
     typeck::builtins::builtin_types_query,
     typeck::builtins::CommonAttributes,
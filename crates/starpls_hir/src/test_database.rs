@@ -2,7 +2,7 @@ use std::sync::Arc;
 
 use dashmap::{mapref::entry::Entry, DashMap};
 use starpls_bazel::{APIContext, Builtins};
-use starpls_common::{File, FileId, LoadItemCandidate, ResolvedPath};
+use starpls_common::{File, FileId, LabelCandidates, LoadItemCandidate, ResolvedPath};
 use starpls_test_util::{make_test_builtins, FixtureType};
 
 use crate::{
@@ -78,6 +78,14 @@ impl starpls_common::Db for TestDatabase {
         Ok(None)
     }
 
+    fn list_label_candidates(
+        &self,
+        _path: &str,
+        _from: FileId,
+    ) -> anyhow::Result<Option<LabelCandidates>> {
+        Ok(None)
+    }
+
     fn resolve_path(
         &self,
         _path: &str,
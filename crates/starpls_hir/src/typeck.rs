@@ -8,11 +8,11 @@ use std::{
 use crossbeam::atomic::AtomicCell;
 use either::Either;
 use parking_lot::Mutex;
-use rustc_hash::FxHashMap;
+use rustc_hash::{FxHashMap, FxHashSet};
 use smallvec::{smallvec, SmallVec};
-use starpls_common::{parse, Diagnostic, Dialect, File, InFile};
+use starpls_common::{parse, Diagnostic, Dialect, File, FileId, InFile};
 use starpls_intern::{impl_internable, Interned};
-use starpls_syntax::ast::SyntaxNodePtr;
+use starpls_syntax::ast::{self, SyntaxNodePtr};
 
 use crate::{
     def::{ExprId, Function, LiteralString, LoadItemId, LoadStmt, Param as HirDefParam, ParamId},
@@ -32,6 +32,7 @@ use crate::{
 
 mod call;
 mod infer;
+mod json;
 
 #[cfg(test)]
 mod tests;
@@ -142,6 +143,9 @@ impl std::error::Error for Cancelled {}
 #[derive(Clone, Debug, Default)]
 pub struct InferenceOptions {
     pub infer_ctx_attrs: bool,
+    /// The functions that merge structs, e.g. `structs.merge`, written as they're called. Calls to
+    /// them return a struct with the fields of each of their positional arguments.
+    pub struct_merge_functions: Vec<String>,
 }
 
 #[derive(Default)]
@@ -233,7 +237,7 @@ impl Ty {
                     .map(move |(index, field)| {
                         let resolved = resolve_type_ref(db, &field.type_ref).0;
                         let resolved = match (resolved.kind(), data) {
-                            // If `TyData` is set, this means the current type is either `ctx` or `repository_ctx`.
+                            // If `TyData::Attributes` is set, this means the current type is either `ctx` or `repository_ctx`.
                             // Override the `attr` field for both of these types.
                             (TyKind::Struct(_), Some(TyData::Attributes(attrs)))
                                 if field.name.as_str() == "attr" =>
@@ -243,6 +247,29 @@ impl Ty {
                                 }))
                                 .intern()
                             }
+                            // If `TyData` is set to a module extension, this means the current type is
+                            // either `module_ctx` or `bazel_module`. Thread the extension through
+                            // `module_ctx.modules` so that `bazel_module.tags` can be shaped from its
+                            // tag classes.
+                            (TyKind::List(elem), Some(data @ TyData::ModuleExtension(_)))
+                                if field.name.as_str() == "modules" =>
+                            {
+                                match elem.kind() {
+                                    TyKind::BuiltinType(elem, None) => TyKind::List(
+                                        TyKind::BuiltinType(*elem, Some(data.clone())).intern(),
+                                    )
+                                    .intern(),
+                                    _ => resolved.clone(),
+                                }
+                            }
+                            (_, Some(TyData::ModuleExtension(module_extension)))
+                                if field.name.as_str() == "tags" =>
+                            {
+                                TyKind::Struct(Some(Struct::Tags {
+                                    module_extension: module_extension.clone(),
+                                }))
+                                .intern()
+                            }
                             _ => resolved,
                         };
                         let field = Field(FieldInner::BuiltinField { parent: *ty, index });
@@ -271,23 +298,37 @@ impl Ty {
                 Fields::Union(acc.into_iter())
             }
             TyKind::Struct(strukt) => Fields::Struct(
-                strukt
-                    .as_ref()
-                    .and_then(|strukt| match strukt {
-                        Struct::Inline { fields, .. } => Some(fields),
-                        _ => None,
-                    })
-                    .into_iter()
-                    .flat_map(|fields| fields.iter())
-                    .map(|(name, ty)| {
-                        (
-                            Field(FieldInner::StructField {
-                                name: name.clone(),
-                                doc: None,
-                            }),
-                            ty.clone(),
-                        )
-                    }),
+                match strukt {
+                    Some(Struct::Inline { fields, .. }) => fields
+                        .iter()
+                        .map(|(name, ty)| {
+                            (
+                                Field(FieldInner::StructField {
+                                    name: name.clone(),
+                                    doc: None,
+                                }),
+                                ty.clone(),
+                            )
+                        })
+                        .collect::<Vec<_>>(),
+                    // Each tag class is a list of the tags that a module declares with it.
+                    Some(Struct::Tags { module_extension }) => module_extension
+                        .tag_classes
+                        .iter()
+                        .flat_map(|tag_classes| tag_classes.iter())
+                        .map(|(name, tag_class)| {
+                            (
+                                Field(FieldInner::StructField {
+                                    name: name.clone(),
+                                    doc: tag_class.doc.as_ref().map(|doc| doc.to_string()),
+                                }),
+                                TyKind::List(tag_class.instance_ty()).intern(),
+                            )
+                        })
+                        .collect(),
+                    _ => Vec::new(),
+                }
+                .into_iter(),
             ),
             TyKind::ProviderInstance(provider) => {
                 Fields::Provider(provider.fields.as_ref()?.1.iter().enumerate().map(
@@ -635,7 +676,7 @@ impl Ty {
         }
     }
 
-    fn normalize(self) -> Ty {
+    pub(crate) fn normalize(self) -> Ty {
         match self.kind() {
             TyKind::Bool(_) => Ty::bool(),
             TyKind::Int(_) => Ty::int(),
@@ -881,9 +922,12 @@ impl Param {
             _ => return None,
         };
 
-        attr.default_text_range.as_ref().and_then(|e| {
+        attr.default_value.as_ref().and_then(|e| {
             Some(match e {
-                Either::Left((file, ptr)) => ptr
+                Either::Left((file, expr)) => source_map(db, *file)
+                    .expr_map_back
+                    .get(expr)?
+                    .syntax_node_ptr()
                     .try_to_node(&parse(db, *file).syntax(db))?
                     .text()
                     .to_string(),
@@ -891,6 +935,67 @@ impl Param {
             })
         })
     }
+
+    /// Returns the list of values accepted by a rule or tag class attribute that restricts them
+    /// with `values`. The list is only returned if it's written out as a list expression.
+    pub fn allowed_values_source(&self, db: &dyn Db) -> Option<InFile<ast::ListExpr>> {
+        let (file, expr) = match &self.0 {
+            ParamInner::RuleParam(RuleParam::Keyword { attr, .. })
+            | ParamInner::TagParam(TagParam::Keyword { attr, .. }) => attr.values?,
+            _ => return None,
+        };
+        let ptr = source_map(db, file)
+            .expr_map_back
+            .get(&expr)?
+            .clone()
+            .cast::<ast::ListExpr>()?;
+        Some(InFile {
+            file,
+            value: ptr.try_to_node(&parse(db, file).syntax(db))?,
+        })
+    }
+
+    pub fn is_mandatory(&self, db: &dyn Db) -> bool {
+        match &self.0 {
+            ParamInner::Param { parent, index } => parent.as_ref().map_or(false, |parent| {
+                let module = module(db, parent.file(db));
+                !module[parent.params(db)[*index]].is_optional()
+            }),
+            ParamInner::IntrinsicParam { parent, index } => matches!(
+                parent.params(db)[*index],
+                IntrinsicFunctionParam::Positional {
+                    optional: false,
+                    ..
+                }
+            ),
+            ParamInner::BuiltinParam { parent, index } => parent.params(db)[*index].is_mandatory(),
+            ParamInner::RuleParam(RuleParam::Keyword { attr, .. })
+            | ParamInner::TagParam(TagParam::Keyword { attr, .. }) => attr.mandatory,
+            ParamInner::RuleParam(RuleParam::BuiltinKeyword(kind, index)) => {
+                common_attributes_query(db)
+                    .get(db, kind.clone(), *index)
+                    .1
+                    .mandatory
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns the kind of the rule or tag class attribute that this parameter sets.
+    pub(crate) fn attribute_kind(&self, db: &dyn Db) -> Option<AttributeKind> {
+        match &self.0 {
+            ParamInner::RuleParam(RuleParam::Keyword { attr, .. })
+            | ParamInner::TagParam(TagParam::Keyword { attr, .. }) => Some(attr.kind.clone()),
+            ParamInner::RuleParam(RuleParam::BuiltinKeyword(kind, index)) => Some(
+                common_attributes_query(db)
+                    .get(db, kind.clone(), *index)
+                    .1
+                    .kind
+                    .clone(),
+            ),
+            _ => None,
+        }
+    }
 }
 
 enum Params<I1, I2, I3, I4, I5, I6> {
@@ -990,6 +1095,17 @@ impl Field {
             FieldInner::StaticField { doc, .. } => doc.unwrap_or_default().to_string(),
         }
     }
+
+    /// Whether the field comes from the builtin definitions, rather than from a struct, provider or
+    /// module extension defined in source.
+    pub fn is_builtin(&self) -> bool {
+        matches!(
+            self.0,
+            FieldInner::BuiltinField { .. }
+                | FieldInner::BuiltinMethod { .. }
+                | FieldInner::IntrinsicField { .. }
+        )
+    }
 }
 
 pub(crate) enum FieldInner {
@@ -1060,6 +1176,9 @@ where
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub(crate) enum TyData {
     Attributes(Arc<Vec<(Name, Arc<Attribute>)>>),
+    ModuleExtension(Arc<ModuleExtension>),
+    /// The type of the elements of a `depset`.
+    Element(Ty),
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
@@ -1173,7 +1292,11 @@ pub struct Attribute {
     pub kind: AttributeKind,
     pub doc: Option<Box<str>>,
     pub mandatory: bool,
-    pub default_text_range: Option<Either<(File, SyntaxNodePtr), String>>,
+    /// The expression for the attribute's default value, or its text for builtin attributes.
+    pub default_value: Option<Either<(File, ExprId), String>>,
+    /// The expression for the values accepted by the attribute, if it restricts them with
+    /// `values`, e.g. `attr.string(values = ["a", "b"])`.
+    pub values: Option<(File, ExprId)>,
 }
 
 impl Attribute {
@@ -1181,13 +1304,15 @@ impl Attribute {
         kind: AttributeKind,
         doc: Option<Box<str>>,
         mandatory: bool,
-        default_text_range: Option<Either<(File, SyntaxNodePtr), String>>,
+        default_value: Option<Either<(File, ExprId), String>>,
+        values: Option<(File, ExprId)>,
     ) -> Self {
         Self {
             kind,
             doc,
             mandatory,
-            default_text_range,
+            default_value,
+            values,
         }
     }
 
@@ -1237,6 +1362,11 @@ pub(crate) struct Rule {
     pub(crate) kind: RuleKind,
     pub(crate) doc: Option<Box<str>>,
     pub(crate) attrs: Arc<Vec<(Name, Arc<Attribute>)>>,
+    /// The dict literal passed as `attrs`, which declares the rule's attributes.
+    pub(crate) attrs_source: Option<InFile<ExprId>>,
+    /// Whether `attrs` may be missing some of the rule's attributes, e.g. because they're built
+    /// from a dict that isn't a literal. Calls to such rules aren't checked for unknown attributes.
+    pub(crate) has_unknown_attrs: bool,
 }
 
 impl Rule {
@@ -1262,7 +1392,11 @@ impl Rule {
 pub(crate) struct Provider {
     pub(crate) name: Option<Name>,
     pub(crate) doc: Option<LiteralString>,
+    /// The declared fields, along with the dict or list literal that declares them.
     pub(crate) fields: Option<(Option<InFile<ExprId>>, Box<[ProviderField]>)>,
+    /// Whether the provider has an `init` callback, whose parameters are taken by calls to the
+    /// provider instead of its fields.
+    pub(crate) has_init: bool,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
@@ -1270,6 +1404,9 @@ pub(crate) enum Struct {
     Inline {
         call_expr: InFile<ExprId>,
         fields: Box<[(Name, Ty)]>,
+        /// Whether the struct may have fields besides `fields`, e.g. when it was created from an
+        /// unpacked dict.
+        has_unknown_fields: bool,
     },
     FieldSignature {
         ty: Ty,
@@ -1277,6 +1414,11 @@ pub(crate) enum Struct {
     Attributes {
         attrs: Arc<Vec<(Name, Arc<Attribute>)>>,
     },
+    /// The `tags` of a `bazel_module` passed to a module extension's implementation, with a
+    /// field for each of the extension's tag classes.
+    Tags {
+        module_extension: Arc<ModuleExtension>,
+    },
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
@@ -1291,6 +1433,22 @@ pub(crate) struct TagClass {
     pub(crate) doc: Option<Box<str>>,
 }
 
+impl TagClass {
+    /// The type of a single tag declared with this tag class, e.g. `deps.tags.install[0]`.
+    pub(crate) fn instance_ty(&self) -> Ty {
+        TyKind::Struct(Some(Struct::Attributes {
+            attrs: Arc::new(
+                self.attrs
+                    .iter()
+                    .flat_map(|attrs| attrs.iter())
+                    .cloned()
+                    .collect(),
+            ),
+        }))
+        .intern()
+    }
+}
+
 impl TyKind {
     pub fn intern(self) -> Ty {
         Ty(Interned::new(self))
@@ -1318,6 +1476,8 @@ pub(crate) struct ProviderField {
 pub(crate) struct DictLiteral {
     pub(crate) expr: Option<InFile<ExprId>>,
     pub(crate) known_keys: Box<[(LiteralString, Ty)]>,
+    /// Whether `known_keys` contains every key of the dict, i.e. none of its keys are computed.
+    pub(crate) all_keys_known: bool,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -1417,15 +1577,82 @@ pub(crate) struct InferenceCtxt {
     pub(crate) type_of_param: FxHashMap<FileParamId, Ty>,
 }
 
+impl InferenceCtxt {
+    fn invalidate(&mut self, invalidated: &InvalidatedFiles) {
+        self.diagnostics
+            .retain(|diagnostic| !invalidated.file_ids.contains(&diagnostic.range.file_id));
+        self.resolved_load_stmts
+            .retain(|id, _| !invalidated.files.contains(&id.file));
+        self.load_resolution_stack.clear();
+        self.type_of_expr
+            .retain(|id, _| !invalidated.files.contains(&id.file));
+        self.type_of_load_item
+            .retain(|id, _| !invalidated.files.contains(&id.file));
+        self.type_of_param
+            .retain(|id, _| !invalidated.files.contains(&id.file));
+    }
+}
+
+struct InvalidatedFiles {
+    files: FxHashSet<File>,
+    file_ids: FxHashSet<FileId>,
+}
+
 pub struct CancelGuard<'a> {
     gcx: &'a GlobalCtxt,
     cx: &'a Mutex<InferenceCtxt>,
+    /// The files whose inference results are discarded when the guard is dropped. If this is
+    /// `None`, all results are discarded.
+    invalidated: Option<InvalidatedFiles>,
 }
 
 impl<'a> CancelGuard<'a> {
     fn new(gcx: &'a GlobalCtxt) -> Self {
         gcx.shared_state.cancelled.store(true);
-        Self { gcx, cx: &gcx.cx }
+        Self {
+            gcx,
+            cx: &gcx.cx,
+            invalidated: None,
+        }
+    }
+
+    /// Limits the inference results that are discarded when the guard is dropped to those for the
+    /// given files, each paired with whether its `ModuleInterface` changed. The results for files
+    /// that load a file whose interface changed, directly or through other files, are discarded
+    /// as well.
+    pub fn invalidate_files(
+        &mut self,
+        db: &dyn Db,
+        changed_files: impl IntoIterator<Item = (File, bool)>,
+    ) {
+        let cx = self.cx.lock();
+        let mut loaded_by: FxHashMap<File, Vec<File>> = FxHashMap::default();
+        for (id, loaded_file) in cx.resolved_load_stmts.iter() {
+            if let Some(loaded_file) = loaded_file {
+                loaded_by.entry(*loaded_file).or_default().push(id.file);
+            }
+        }
+
+        let mut files = FxHashSet::default();
+        let mut stack = Vec::new();
+        for (file, interface_changed) in changed_files {
+            if interface_changed {
+                stack.push(file);
+            } else {
+                files.insert(file);
+            }
+        }
+
+        let mut affected = FxHashSet::default();
+        while let Some(file) = stack.pop() {
+            if affected.insert(file) {
+                stack.extend(loaded_by.get(&file).into_iter().flatten().copied());
+            }
+        }
+        files.extend(affected);
+
+        let file_ids = files.iter().map(|file| file.id(db)).collect();
+        self.invalidated = Some(InvalidatedFiles { files, file_ids });
     }
 }
 
@@ -1433,7 +1660,10 @@ impl Drop for CancelGuard<'_> {
     fn drop(&mut self) {
         let mut cx = self.cx.lock();
         self.gcx.shared_state.cancelled.store(false);
-        *cx = Default::default();
+        match self.invalidated.take() {
+            Some(invalidated) => cx.invalidate(&invalidated),
+            None => *cx = Default::default(),
+        }
     }
 }
 
@@ -1504,6 +1734,16 @@ impl<'a> TypeRefResolver<'a> {
                     TyKind::Struct(Some(Struct::FieldSignature { ty }))
                 }),
                 name => match builtin_types.types(self.db).get(name).cloned() {
+                    // Depsets may be annotated with the type of their elements, e.g. `depset[File]`.
+                    Some(ty) if name == "depset" && args.is_some() => match ty.kind() {
+                        TyKind::BuiltinType(depset, _) => {
+                            let depset = *depset;
+                            self.resolve_single_arg_type_constructor(args, |elem| {
+                                TyKind::BuiltinType(depset, Some(TyData::Element(elem)))
+                            })
+                        }
+                        _ => ty,
+                    },
                     Some(ty) => ty,
                     None => {
                         self.errors.push(format!("Unknown type \"{}\"", name));
@@ -1607,6 +1847,17 @@ pub(crate) fn assign_tys(db: &dyn Db, source: &Ty, target: &Ty) -> bool {
         {
             true
         }
+        // Depsets whose element types aren't known are compatible with any other depset.
+        (
+            TyKind::BuiltinType(source_ty, source_data),
+            TyKind::BuiltinType(target_ty, target_data),
+        ) if source_ty == target_ty => match (source_data, target_data) {
+            (Some(TyData::Element(source)), Some(TyData::Element(target))) => {
+                assign_tys(db, source, target)
+            }
+            (Some(TyData::Element(_)), None) | (None, Some(TyData::Element(_))) => true,
+            _ => source_data == target_data,
+        },
         (TyKind::Union(source_tys), TyKind::Union(target_tys)) => {
             source_tys.iter().all(|source_ty| {
                 target_tys
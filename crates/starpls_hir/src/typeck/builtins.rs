@@ -8,20 +8,21 @@ use starpls_bazel::{
     builtin::{Callable, Value},
     env, Builtins, BUILTINS_TYPES_DENY_LIST, BUILTINS_VALUES_DENY_LIST,
 };
-use starpls_common::{parse, Dialect, File, InFile};
+use starpls_common::{parse, DiagnosticCode, Dialect, File, InFile};
 use starpls_syntax::ast::{self, AstNode};
 
 use crate::{
     def::{
         resolver::{Export, Resolver},
-        Argument,
+        Argument, Expr, Literal,
     },
-    source_map,
+    module, source_map,
     typeck::{
-        Attribute, AttributeKind, ModuleExtension, Provider, ProviderField, Rule as TyRule,
-        RuleKind, Struct, TagClass, Tuple,
+        assign_tys, json::infer_json_type, resolve_type_ref, Attribute, AttributeKind,
+        ModuleExtension, Provider, ProviderField, Rule as TyRule, RuleKind, Struct, TagClass,
+        Tuple, TyData,
     },
-    Db, ExprId, Name, Ty, TyCtxt, TyKind, TypeRef,
+    Db, DisplayWithDb, ExprId, Name, Ty, TyCtxt, TyKind, TypeRef,
 };
 
 const DEFAULT_DOC: &str = "See the [Bazel Build Encyclopedia](https://bazel.build/reference/be/overview) for more details.";
@@ -132,19 +133,22 @@ impl BuiltinFunction {
             self.name(db).as_str(),
         ) {
             (None, "struct") => {
-                let fields = args
-                    .filter_map(|(arg, ty)| match arg {
-                        Argument::Keyword { name, .. } => Some((name.clone(), ty.clone())),
-                        _ => None,
-                    })
-                    .collect::<Vec<_>>()
-                    .into_boxed_slice();
+                let mut fields = Vec::new();
+                let mut has_unknown_fields = false;
+                for (arg, ty) in args {
+                    match arg {
+                        Argument::Keyword { name, .. } => fields.push((name.clone(), ty.clone())),
+                        Argument::UnpackedDict { .. } => has_unknown_fields = true,
+                        _ => {}
+                    }
+                }
                 TyKind::Struct(Some(Struct::Inline {
                     call_expr: InFile {
                         file,
                         value: call_expr,
                     },
-                    fields,
+                    fields: fields.into_boxed_slice(),
+                    has_unknown_fields,
                 }))
             }
             (None, "provider") => {
@@ -152,7 +156,7 @@ impl BuiltinFunction {
                 let mut doc = None;
                 let mut has_init = false;
                 for (arg, ty) in args {
-                    if let Argument::Keyword { name, .. } = arg {
+                    if let Argument::Keyword { name, expr } = arg {
                         match name.as_str() {
                             "doc" => {
                                 if let TyKind::String(Some(s)) = ty.kind() {
@@ -160,7 +164,9 @@ impl BuiltinFunction {
                                 }
                             }
                             "fields" => {
-                                if let TyKind::Dict(_, _, Some(lit)) = ty.kind() {
+                                if let TyKind::List(_) = ty.kind() {
+                                    fields = provider_list_fields(db, file, *expr);
+                                } else if let TyKind::Dict(_, _, Some(lit)) = ty.kind() {
                                     fields = Some((
                                         lit.expr.clone(),
                                         lit.known_keys
@@ -237,6 +243,7 @@ impl BuiltinFunction {
                         name: provider_name,
                         doc,
                         fields,
+                        has_init,
                     });
 
                     TyKind::Tuple(Tuple::Simple(smallvec![
@@ -256,12 +263,19 @@ impl BuiltinFunction {
                         .and_then(|name_ref| name_ref.name())
                         .as_ref()
                         .map(|name| Name::from_str(name.text()));
-                    TyKind::Provider(Arc::new(Provider { name, doc, fields }))
+                    TyKind::Provider(Arc::new(Provider {
+                        name,
+                        doc,
+                        fields,
+                        has_init,
+                    }))
                 }
             }
 
             (None, name @ ("rule" | "repository_rule")) => {
                 let mut attrs = None;
+                let mut attrs_source = None;
+                let mut has_unknown_attrs = false;
                 let mut doc = None;
                 for (arg, ty) in args {
                     if let Argument::Keyword { name, .. } = arg {
@@ -272,7 +286,12 @@ impl BuiltinFunction {
                                 }
                             }
                             "attrs" => {
+                                has_unknown_attrs = true;
                                 if let TyKind::Dict(_, _, Some(lit)) = ty.kind() {
+                                    has_unknown_attrs = !lit.all_keys_known
+                                        || lit.known_keys.iter().any(|(_, ty)| {
+                                            !matches!(ty.kind(), TyKind::Attribute(_))
+                                        });
                                     attrs = Some(
                                         lit.known_keys
                                             .iter()
@@ -284,7 +303,8 @@ impl BuiltinFunction {
                                                 _ => None,
                                             })
                                             .collect::<Vec<_>>(),
-                                    )
+                                    );
+                                    attrs_source = lit.expr.clone();
                                 }
                             }
                             _ => {}
@@ -300,13 +320,16 @@ impl BuiltinFunction {
                     },
                     doc: doc.map(|doc| doc.value(db).clone()),
                     attrs: Arc::new(attrs.unwrap_or_default()),
+                    attrs_source,
+                    has_unknown_attrs,
                 })
             }
 
             (Some("attr"), attr) => {
                 let mut doc: Option<Box<str>> = None;
                 let mut mandatory = false;
-                let mut default_ptr = None;
+                let mut default_expr = None;
+                let mut values_expr = None;
                 for (arg, ty) in args {
                     if let Argument::Keyword { name, expr } = arg {
                         match name.as_str() {
@@ -320,11 +343,8 @@ impl BuiltinFunction {
                                     mandatory = *b;
                                 }
                             }
-                            "default" => {
-                                if let Some(ptr) = source_map(db, file).expr_map_back.get(expr) {
-                                    default_ptr = Some(ptr.syntax_node_ptr());
-                                }
-                            }
+                            "default" => default_expr = Some(*expr),
+                            "values" => values_expr = Some(*expr),
                             _ => {}
                         }
                     }
@@ -348,18 +368,25 @@ impl BuiltinFunction {
                     },
                     doc,
                     mandatory,
-                    default_ptr.map(|text_range| Either::Left((file, text_range))),
+                    default_expr.map(|expr| Either::Left((file, expr))),
+                    values_expr.map(|expr| (file, expr)),
                 )))
             }
 
             (None, "tag_class") => {
                 let mut attrs = None;
+                let mut has_unknown_attrs = false;
                 let mut doc = None;
                 for (arg, ty) in args {
                     if let Argument::Keyword { name, .. } = arg {
                         match name.as_str() {
                             "attrs" => {
+                                has_unknown_attrs = true;
                                 if let TyKind::Dict(_, _, Some(lit)) = ty.kind() {
+                                    has_unknown_attrs = !lit.all_keys_known
+                                        || lit.known_keys.iter().any(|(_, ty)| {
+                                            !matches!(ty.kind(), TyKind::Attribute(_))
+                                        });
                                     attrs = Some(
                                         lit.known_keys
                                             .iter()
@@ -427,6 +454,42 @@ impl BuiltinFunction {
                 TyKind::ModuleExtension(Arc::new(ModuleExtension { doc, tag_classes }))
             }
 
+            (Some("json"), "decode") => {
+                let mut text = None;
+                let mut default = None;
+                for (arg, ty) in args {
+                    match arg {
+                        Argument::Simple { .. } if text.is_none() => text = Some(ty),
+                        Argument::Keyword { name, .. } if name.as_str() == "x" => text = Some(ty),
+                        Argument::Keyword { name, .. } if name.as_str() == "default" => {
+                            default = Some(ty)
+                        }
+                        _ => {}
+                    }
+                }
+
+                // Constant strings are decoded directly. Otherwise, a dict or list passed as the
+                // `default`, which is returned if decoding fails, describes the expected shape.
+                if let Some(TyKind::String(Some(s))) = text.map(|ty| ty.kind()) {
+                    if let Some(ty) = infer_json_type(db, &s.value(db)) {
+                        return Some(ty);
+                    }
+                }
+                return match default.map(|ty| ty.kind()) {
+                    Some(TyKind::Dict(..) | TyKind::List(_)) => default.cloned(),
+                    _ => None,
+                };
+            }
+
+            (None, "select") => {
+                let dict_ty = args.find_map(|(arg, ty)| match arg {
+                    Argument::Simple { .. } => Some(ty),
+                    Argument::Keyword { name, .. } if name.as_str() == "x" => Some(ty),
+                    _ => None,
+                })?;
+                return select_ty(tcx, dict_ty);
+            }
+
             (None, "use_extension") => {
                 let mut next_string_arg = || {
                     args.next().and_then(|(arg, ty)| match (arg, ty.kind()) {
@@ -455,6 +518,20 @@ impl BuiltinFunction {
                 TyKind::ModuleExtensionProxy(module_extension)
             }
 
+            (None, "depset") => return depset_ty(tcx, file, args),
+
+            (Some("depset"), "to_list") => {
+                let module = module(db, file);
+                let receiver = match &module[call_expr] {
+                    Expr::Call { callee, .. } => match &module[*callee] {
+                        Expr::Dot { expr, .. } => *expr,
+                        _ => return None,
+                    },
+                    _ => return None,
+                };
+                TyKind::List(depset_elem_ty(&tcx.infer_expr(file, receiver))?)
+            }
+
             _ => return None,
         };
 
@@ -462,6 +539,152 @@ impl BuiltinFunction {
     }
 }
 
+/// Returns the type of a `depset()` call, whose elements have the type shared by its direct
+/// elements and the elements of the depsets in `transitive`. Arguments whose elements have a
+/// different type than the ones before them are reported.
+fn depset_ty<'a, I>(tcx: &mut TyCtxt, file: File, args: I) -> Option<Ty>
+where
+    I: Iterator<Item = (&'a Argument, &'a Ty)>,
+{
+    let db = tcx.db;
+    let mut elem_ty: Option<Ty> = None;
+    for (index, (arg, ty)) in args.enumerate() {
+        let (expr, tys) = match arg {
+            Argument::Simple { expr } if index == 0 => {
+                (*expr, known_list_elem_ty(ty).into_iter().collect())
+            }
+            Argument::Keyword { name, expr } if name.as_str() == "direct" => {
+                (*expr, known_list_elem_ty(ty).into_iter().collect())
+            }
+            Argument::Keyword { name, expr } if name.as_str() == "transitive" => {
+                let tys = match ty.kind() {
+                    TyKind::List(depset_ty) => match depset_ty.kind() {
+                        TyKind::Union(depset_tys) => {
+                            depset_tys.iter().filter_map(depset_elem_ty).collect()
+                        }
+                        _ => depset_elem_ty(depset_ty).into_iter().collect(),
+                    },
+                    _ => Vec::new(),
+                };
+                (*expr, tys)
+            }
+            _ => continue,
+        };
+        for ty in tys {
+            match &elem_ty {
+                Some(expected)
+                    if !assign_tys(db, &ty, expected) && !assign_tys(db, expected, &ty) =>
+                {
+                    tcx.add_expr_diagnostic_error(
+                        file,
+                        expr,
+                        DiagnosticCode::TypeMismatch,
+                        format!(
+                            "Depset elements of type \"{}\" cannot be combined with elements of type \"{}\"",
+                            ty.display(db),
+                            expected.display(db)
+                        ),
+                    );
+                    break;
+                }
+                Some(_) => {}
+                None => elem_ty = Some(ty),
+            }
+        }
+    }
+
+    let depset = match resolve_type_ref(db, &TypeRef::from_str_opt("depset"))
+        .0
+        .kind()
+    {
+        TyKind::BuiltinType(depset, _) => *depset,
+        _ => return None,
+    };
+    Some(TyKind::BuiltinType(depset, Some(TyData::Element(elem_ty?))).intern())
+}
+
+/// Returns the element type of a list, unless it's unknown, e.g. for an empty list literal.
+fn known_list_elem_ty(ty: &Ty) -> Option<Ty> {
+    match ty.kind() {
+        TyKind::List(elem_ty)
+            if !matches!(
+                elem_ty.kind(),
+                TyKind::Unknown | TyKind::Any | TyKind::Unbound
+            ) =>
+        {
+            Some(elem_ty.clone().normalize())
+        }
+        _ => None,
+    }
+}
+
+fn depset_elem_ty(ty: &Ty) -> Option<Ty> {
+    match ty.kind() {
+        TyKind::BuiltinType(_, Some(TyData::Element(elem_ty))) => Some(elem_ty.clone()),
+        _ => None,
+    }
+}
+
+/// Returns the fields of a provider declared with a list of names, e.g. `fields = ["a", "b"]`, if
+/// every name is a string literal.
+fn provider_list_fields(
+    db: &dyn Db,
+    file: File,
+    expr: ExprId,
+) -> Option<(Option<InFile<ExprId>>, Box<[ProviderField]>)> {
+    let module = module(db, file);
+    let exprs = match &module[expr] {
+        Expr::List { exprs } => exprs,
+        _ => return None,
+    };
+    let fields = exprs
+        .iter()
+        .map(|expr| match &module[*expr] {
+            Expr::Literal {
+                literal: Literal::String(name),
+            } if !name.value(db).is_empty() => Some(ProviderField {
+                name: Name::from_str(&name.value(db)),
+                doc: None,
+            }),
+            _ => None,
+        })
+        .collect::<Option<_>>()?;
+    Some((Some(InFile { file, value: expr }), fields))
+}
+
+/// Returns the type of a `select()` call, which is the type shared by the values that it selects
+/// between. Empty lists, e.g. in the usual `"//conditions:default": []` branch, are compatible with
+/// any other list, so `[...] + select({...})` keeps the element type of its lists.
+fn select_ty(tcx: &mut TyCtxt, dict_ty: &Ty) -> Option<Ty> {
+    let (value_ty, lit) = match dict_ty.kind() {
+        TyKind::Dict(_, value_ty, lit) => (value_ty, lit),
+        _ => return None,
+    };
+    if !matches!(value_ty.kind(), TyKind::Unknown) {
+        return Some(value_ty.clone());
+    }
+
+    let InFile { file, value: expr } = lit.as_ref()?.expr.clone()?;
+    let module = module(tcx.db, file);
+    let entries = match &module[expr] {
+        Expr::Dict { entries } => entries,
+        _ => return None,
+    };
+    let mut element_tys = Vec::new();
+    for entry in entries.iter() {
+        if matches!(&module[entry.value], Expr::List { exprs } if exprs.is_empty()) {
+            continue;
+        }
+        match tcx.infer_expr(file, entry.value).kind() {
+            TyKind::List(ty) if !matches!(ty.kind(), TyKind::Unknown | TyKind::Any) => {
+                element_tys.push(ty.clone())
+            }
+            _ => return None,
+        }
+    }
+    Some(Ty::list(Ty::union(element_tys.into_iter())))
+}
+
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub enum BuiltinFunctionParam {
     Simple {
@@ -518,7 +741,35 @@ pub struct BuiltinDefs {
 
 pub(crate) fn builtin_globals(db: &dyn Db, dialect: Dialect) -> BuiltinGlobals {
     let defs = db.get_builtin_defs(&dialect);
-    builtin_globals_query(db, defs)
+    match dialect {
+        Dialect::Buck2 => buck2_builtin_globals_query(db, defs),
+        _ => builtin_globals_query(db, defs),
+    }
+}
+
+/// Buck2 files use Buck2's globals instead of Bazel's, and have no counterparts of `MODULE.bazel`,
+/// `REPO.bazel` or `WORKSPACE` files.
+#[salsa::tracked]
+pub(crate) fn buck2_builtin_globals_query(db: &dyn Db, defs: BuiltinDefs) -> BuiltinGlobals {
+    let builtins = defs.builtins(db);
+    let rules = defs.rules(db);
+    let bzl_globals = APIGlobals::from_values(
+        db,
+        env::make_buck2_builtins()
+            .global
+            .iter()
+            .chain(builtins.global.iter())
+            .chain(rules.global.iter()),
+    );
+    let empty_globals = || APIGlobals::from_values(db, std::iter::empty());
+
+    BuiltinGlobals::new(
+        db,
+        bzl_globals,
+        empty_globals(),
+        empty_globals(),
+        empty_globals(),
+    )
 }
 
 #[salsa::tracked]
@@ -747,7 +998,8 @@ pub(crate) fn common_attributes_query(db: &dyn Db) -> CommonAttributes {
                         },
                         doc: Some(attr.doc.into_boxed_str()),
                         mandatory: attr.is_mandatory,
-                        default_text_range: Some(Either::Right(attr.default_value)),
+                        default_value: Some(Either::Right(attr.default_value)),
+                        values: None,
                     },
                 )
             })
@@ -883,6 +1135,11 @@ fn maybe_field_type_ref_override(typ: &str, field: &str) -> Option<TypeRef> {
                 .into_boxed_slice(),
             ),
         ),
+        // `modules` is documented as a plain `list`, but is always a list of `bazel_module`s.
+        ("module_ctx", "modules") => TypeRef::Name(
+            Name::new_inline("list"),
+            Some(vec![TypeRef::Name(Name::new_inline("bazel_module"), None)].into_boxed_slice()),
+        ),
         _ => return None,
     };
 
@@ -3,17 +3,39 @@
 //! but with a couple of modifications for handling "*args" and "**kwargs" arguments.
 use std::iter;
 
+use rustc_hash::FxHashSet;
 use smallvec::{smallvec, SmallVec};
 
 use crate::{
     def::{Argument, Param},
     typeck::{
         builtins::BuiltinFunctionParam, intrinsics::IntrinsicFunctionParam, Provider, Rule,
-        TagClass,
+        RuleKind, TagClass,
     },
     Db, ExprId, Name,
 };
 
+/// Attributes that Bazel adds to rules depending on how they're declared, e.g. `size` for test
+/// rules, on top of the common attributes.
+const IMPLICIT_BUILD_ATTRS: &[&str] = &[
+    "applicable_licenses",
+    "args",
+    "aspect_hints",
+    "env",
+    "env_inherit",
+    "exec_group_compatible_with",
+    "flaky",
+    "licenses",
+    "local",
+    "output_licenses",
+    "package_metadata",
+    "shard_count",
+    "size",
+    "timeout",
+];
+
+const IMPLICIT_REPOSITORY_ATTRS: &[&str] = &["repo_mapping"];
+
 pub(crate) struct ArgError {
     pub(crate) expr: ExprId,
     pub(crate) message: String,
@@ -107,17 +129,48 @@ impl Slots {
         }
 
         // Keyword arguments are assigned next, i.e. `Argument::Keyword` and `Argument::UnpackedDict`.
+        let mut seen_keywords = FxHashSet::default();
         'outer: for (arg_index, arg) in args.iter().enumerate() {
             match arg {
                 Argument::Keyword {
                     name: ref arg_name,
                     expr,
                 } => {
+                    // Repeating a keyword argument is always an error, even for calls whose
+                    // parameters aren't fully known.
+                    if !seen_keywords.insert(arg_name) {
+                        errors.push(ArgError {
+                            expr: *expr,
+                            message: format!(
+                                "Duplicate keyword argument \"{}\"",
+                                arg_name.as_str()
+                            ),
+                        });
+                        continue;
+                    }
+
                     // Look for either a keyword parameter matching this argument's
                     // name, or for the "**kwargs" parameter.
                     let provider = SlotProvider::Single(*expr, arg_index);
                     for (slot_index, slot) in self.slots.iter_mut().enumerate() {
                         match slot {
+                            // The parameter was already provided by a positional argument.
+                            Slot::Keyword {
+                                name,
+                                provider: SlotProvider::Single(_, _),
+                                ..
+                            } if arg_name == name => {
+                                if !self.disable_errors {
+                                    errors.push(ArgError {
+                                        expr: *expr,
+                                        message: format!(
+                                            "Multiple values for parameter \"{}\"",
+                                            arg_name.as_str()
+                                        ),
+                                    });
+                                }
+                                continue 'outer;
+                            }
                             Slot::Keyword {
                                 name,
                                 provider:
@@ -198,19 +251,38 @@ impl Slots {
     }
 
     pub(crate) fn from_rule(db: &dyn Db, rule: &Rule) -> Self {
+        let slots = rule.attrs(db).map(|(name, _)| Slot::Keyword {
+            name: name.clone(),
+            provider: SlotProvider::Missing,
+            positional: false,
+        });
+
+        // Unknown attributes can only be reported when all of the rule's attributes are known.
+        // Otherwise, accept any keyword argument.
+        if rule.has_unknown_attrs {
+            return Self {
+                slots: slots
+                    .chain(iter::once(Slot::KwargsDict {
+                        providers: smallvec![],
+                    }))
+                    .collect(),
+                disable_errors: true,
+            };
+        }
+
+        let implicit_attrs = match rule.kind {
+            RuleKind::Build => IMPLICIT_BUILD_ATTRS,
+            RuleKind::Repository => IMPLICIT_REPOSITORY_ATTRS,
+        };
         Self {
-            slots: rule
-                .attrs(db)
-                .map(|(name, _)| Slot::Keyword {
-                    name: name.clone(),
+            slots: slots
+                .chain(implicit_attrs.iter().copied().map(|name| Slot::Keyword {
+                    name: Name::new_inline(name),
                     provider: SlotProvider::Missing,
                     positional: false,
-                })
-                .chain(iter::once(Slot::KwargsDict {
-                    providers: smallvec![],
                 }))
                 .collect(),
-            disable_errors: true,
+            disable_errors: false,
         }
     }
 
@@ -246,7 +318,8 @@ impl Slots {
                     })
                 })
                 .collect(),
-            disable_errors: true,
+            // Providers without declared fields accept any keyword arguments.
+            disable_errors: provider.fields.is_none(),
         }
     }
 }
@@ -1,6 +1,8 @@
 use std::sync::Arc;
 
-use starpls_common::{line_index, parse, Diagnostic, File, FileRange, InFile, Severity};
+use starpls_common::{
+    line_index, parse, Diagnostic, DiagnosticCode, File, FileRange, InFile, Severity,
+};
 use starpls_syntax::{
     ast::{self, ArithOp, AstNode, AstPtr, BinaryOp, BitwiseOp, UnaryOp},
     TextRange,
@@ -132,6 +134,7 @@ impl TyCtxt<'_> {
                         self.add_expr_diagnostic_error(
                             file,
                             expr,
+                            DiagnosticCode::UnresolvedName,
                             format!("\"{}\" is not defined", name.as_str()),
                         );
                         self.unbound_ty()
@@ -171,6 +174,7 @@ impl TyCtxt<'_> {
                         _ => None,
                     })
                     .collect::<Vec<_>>();
+                let all_keys_known = known_keys.len() == entries.len();
 
                 TyKind::Dict(
                     key_ty,
@@ -178,6 +182,7 @@ impl TyCtxt<'_> {
                     Some(Arc::new(DictLiteral {
                         expr: Some(InFile { file, value: expr }),
                         known_keys: known_keys.into_boxed_slice(),
+                        all_keys_known,
                     })),
                 )
                 .intern()
@@ -212,10 +217,11 @@ impl TyCtxt<'_> {
             } => {
                 let receiver_ty = self.infer_expr(file, *dot_expr);
                 match receiver_ty.kind() {
-                    TyKind::Unknown
-                    | TyKind::ProviderInstance(_)
-                    | TyKind::Unbound
-                    | TyKind::Any => self.unknown_ty(),
+                    TyKind::Unknown | TyKind::Unbound | TyKind::Any => self.unknown_ty(),
+                    // Instances of providers without declared fields may have any fields.
+                    TyKind::ProviderInstance(provider) if provider.fields.is_none() => {
+                        self.unknown_ty()
+                    }
                     _ => {
                         if field.is_missing() {
                             return self.unknown_ty();
@@ -249,6 +255,12 @@ impl TyCtxt<'_> {
                                             })
                                             .unwrap_or_else(|| self.unknown_ty());
                                     }
+                                    // The fields of inline structs are known exactly, unless
+                                    // some of them were unpacked from a dict.
+                                    TyKind::Struct(Some(Struct::Inline {
+                                        has_unknown_fields: false,
+                                        ..
+                                    })) => {}
                                     TyKind::Struct(_) => return self.unknown_ty(),
                                     _ => {}
                                 }
@@ -256,6 +268,7 @@ impl TyCtxt<'_> {
                                 self.add_expr_diagnostic_warning_ty(
                                     file,
                                     expr,
+                                    DiagnosticCode::UnknownField,
                                     format!(
                                         "Cannot access field \"{}\" for type \"{}\"",
                                         field.as_str(),
@@ -283,6 +296,7 @@ impl TyCtxt<'_> {
                                 None => self.add_expr_diagnostic_error_ty(
                                     file,
                                     expr,
+                                    DiagnosticCode::InvalidIndex,
                                     format!(
                                         "Index {} is out of range for type {}",
                                         x,
@@ -294,6 +308,7 @@ impl TyCtxt<'_> {
                             _ => self.add_expr_diagnostic_error_ty(
                                 file,
                                 expr,
+                                DiagnosticCode::InvalidIndex,
                                 format!(
                                     "Cannot index tuple with type \"{}\"",
                                     index_ty.display(db).alt()
@@ -304,7 +319,23 @@ impl TyCtxt<'_> {
                         return self.set_expr_type(file, expr, return_ty);
                     }
                     TyKind::List(ty) => (&int_ty, ty, "list"),
-                    TyKind::Dict(key_ty, value_ty, _) => (key_ty, value_ty, "dict"),
+                    TyKind::Dict(key_ty, value_ty, known_keys) => {
+                        // Lookups of known keys, e.g. in dicts decoded from constant JSON
+                        // strings, have the type of the entry for that key.
+                        let known_ty = match (known_keys, index_ty.kind()) {
+                            (Some(lit), TyKind::String(Some(key))) => lit
+                                .known_keys
+                                .iter()
+                                .rev()
+                                .find(|(other, _)| other == key)
+                                .map(|(_, ty)| ty.clone().normalize()),
+                            _ => None,
+                        };
+                        if let Some(ty) = known_ty {
+                            return self.set_expr_type(file, expr, ty);
+                        }
+                        (key_ty, value_ty, "dict")
+                    }
                     TyKind::String(_) => (&int_ty, &string_ty, "string"),
                     TyKind::Bytes => (&int_ty, &int_ty, "bytes"),
                     TyKind::Range => (&int_ty, &int_ty, "range"),
@@ -321,6 +352,7 @@ impl TyCtxt<'_> {
                             self.add_expr_diagnostic_warning_ty(
                                 file,
                                 expr,
+                                DiagnosticCode::InvalidIndex,
                                 format!("Type \"{}\" is not indexable", lhs_ty.display(db).alt()),
                             )
                         });
@@ -335,6 +367,7 @@ impl TyCtxt<'_> {
                     self.add_expr_diagnostic_warning_ty(
                         file,
                         *lhs,
+                        DiagnosticCode::InvalidIndex,
                         format!(
                             "Cannot index {} with type \"{}\"",
                             name,
@@ -355,6 +388,7 @@ impl TyCtxt<'_> {
                                 self.add_expr_diagnostic_error(
                                     file,
                                     *expr,
+                                    DiagnosticCode::InvalidArgument,
                                     String::from(
                                         "Positional argument cannot follow keyword arguments",
                                     ),
@@ -364,6 +398,7 @@ impl TyCtxt<'_> {
                                 self.add_expr_diagnostic_error(
                                     file,
                                     *expr,
+                                    DiagnosticCode::InvalidArgument,
                                     String::from(
                                         "Positional argument cannot follow keyword argument unpacking",
                                     ),
@@ -380,6 +415,7 @@ impl TyCtxt<'_> {
                                 self.add_expr_diagnostic_error(
                                     file,
                                     *expr,
+                                    DiagnosticCode::InvalidArgument,
                                     String::from(
                                         "Unpacked iterable argument cannot follow keyword arguments",
                                     ),
@@ -389,6 +425,7 @@ impl TyCtxt<'_> {
                                 self.add_expr_diagnostic_error(
                                     file,
                                     *expr,
+                                    DiagnosticCode::InvalidArgument,
                                     String::from(
                                         "Unpacked iterable argument cannot follow keyword argument unpacking",
                                     ),
@@ -405,6 +442,9 @@ impl TyCtxt<'_> {
                 let args_with_ty = args.iter().zip(arg_tys.iter());
 
                 match callee_ty.kind() {
+                    _ if self.is_struct_merge_function(file, *callee) => {
+                        self.merge_struct_args(file, expr, args_with_ty)
+                    }
                     TyKind::Function(func) => {
                         let module = module(db, func.file(db));
                         let params = func.params(db).iter().copied();
@@ -416,7 +456,12 @@ impl TyCtxt<'_> {
                         let errors = slots.assign_args(&args, None).0;
 
                         for error in errors {
-                            self.add_expr_diagnostic_error(file, error.expr, error.message);
+                            self.add_expr_diagnostic_error(
+                                file,
+                                error.expr,
+                                DiagnosticCode::InvalidArgument,
+                                error.message,
+                            );
                         }
 
                         let mut missing_params = Vec::new();
@@ -442,7 +487,7 @@ impl TyCtxt<'_> {
                                 SlotProvider::Single(expr, index) => {
                                     let ty = &arg_tys[index];
                                     if !assign_tys(db, ty, &param_ty) {
-                                        self.add_expr_diagnostic_error(file, expr, format!("Argument of type \"{}\" cannot be assigned to parameter of type \"{}\"", ty.display(self.db).alt(), param_ty.display(self.db).alt()));
+                                        self.add_expr_diagnostic_error(file, expr, DiagnosticCode::ArgumentTypeMismatch, format!("Argument of type \"{}\" cannot be assigned to parameter of type \"{}\"", ty.display(self.db).alt(), param_ty.display(self.db).alt()));
                                     }
                                 }
                                 _ => {}
@@ -471,7 +516,12 @@ impl TyCtxt<'_> {
                                 message.push('"');
                             }
 
-                            self.add_expr_diagnostic_error(file, expr, message);
+                            self.add_expr_diagnostic_error(
+                                file,
+                                expr,
+                                DiagnosticCode::MissingArgument,
+                                message,
+                            );
                         }
 
                         func.ret_type_ref(db)
@@ -484,7 +534,12 @@ impl TyCtxt<'_> {
                         let errors = slots.assign_args(&args, None).0;
 
                         for error in errors {
-                            self.add_expr_diagnostic_error(file, error.expr, error.message);
+                            self.add_expr_diagnostic_error(
+                                file,
+                                error.expr,
+                                DiagnosticCode::InvalidArgument,
+                                error.message,
+                            );
                         }
 
                         // Validate argument types.
@@ -503,6 +558,7 @@ impl TyCtxt<'_> {
                                         self.add_expr_diagnostic_error(
                                             file,
                                             expr,
+                                            DiagnosticCode::MissingArgument,
                                             format!(
                                                 "Missing expected argument of type \"{}\"",
                                                 param_ty.display(db)
@@ -513,7 +569,7 @@ impl TyCtxt<'_> {
                                 SlotProvider::Single(expr, index) => {
                                     let ty = &arg_tys[index];
                                     if !assign_tys(db, ty, &param_ty) {
-                                        self.add_expr_diagnostic_error(file, expr, format!("Argument of type \"{}\" cannot be assigned to parameter of type \"{}\"", ty.display(self.db).alt(), param_ty.display(self.db).alt()));
+                                        self.add_expr_diagnostic_error(file, expr, DiagnosticCode::ArgumentTypeMismatch, format!("Argument of type \"{}\" cannot be assigned to parameter of type \"{}\"", ty.display(self.db).alt(), param_ty.display(self.db).alt()));
                                     }
                                 }
                                 _ => {}
@@ -539,7 +595,12 @@ impl TyCtxt<'_> {
                         let errors = slots.assign_args(&args, None).0;
 
                         for error in errors {
-                            self.add_expr_diagnostic_error(file, error.expr, error.message);
+                            self.add_expr_diagnostic_error(
+                                file,
+                                error.expr,
+                                DiagnosticCode::InvalidArgument,
+                                error.message,
+                            );
                         }
 
                         let mut missing_params = Vec::new();
@@ -559,7 +620,7 @@ impl TyCtxt<'_> {
                                 SlotProvider::Single(expr, index) => {
                                     let ty = &arg_tys[index];
                                     if !assign_tys(db, ty, &param_ty) {
-                                        self.add_expr_diagnostic_error(file, expr, format!("Argument of type \"{}\" cannot be assigned to parameter of type \"{}\"", ty.display(self.db).alt(), param_ty.display(self.db).alt()));
+                                        self.add_expr_diagnostic_error(file, expr, DiagnosticCode::ArgumentTypeMismatch, format!("Argument of type \"{}\" cannot be assigned to parameter of type \"{}\"", ty.display(self.db).alt(), param_ty.display(self.db).alt()));
                                     }
                                 }
                                 _ => {}
@@ -588,7 +649,12 @@ impl TyCtxt<'_> {
                                 message.push('"');
                             }
 
-                            self.add_expr_diagnostic_error(file, expr, message);
+                            self.add_expr_diagnostic_error(
+                                file,
+                                expr,
+                                DiagnosticCode::MissingArgument,
+                                message,
+                            );
                         }
 
                         func.maybe_unique_ret_type(self, file, expr, args_with_ty)
@@ -596,7 +662,16 @@ impl TyCtxt<'_> {
                     }
                     TyKind::Rule(rule) => {
                         let mut slots = Slots::from_rule(db, rule);
-                        slots.assign_args(&args, None);
+                        let errors = slots.assign_args(&args, None).0;
+
+                        for error in errors {
+                            self.add_expr_diagnostic_error(
+                                file,
+                                error.expr,
+                                DiagnosticCode::InvalidArgument,
+                                error.message,
+                            );
+                        }
 
                         let mut missing_attrs = Vec::new();
 
@@ -608,7 +683,7 @@ impl TyCtxt<'_> {
                                     SlotProvider::Single(expr, index) => {
                                         let ty = &arg_tys[index];
                                         if !assign_tys(db, ty, &expected_ty) {
-                                            self.add_expr_diagnostic_error(file, expr, format!("Argument of type \"{}\" cannot be assigned to parameter of type \"{}\"", ty.display(self.db).alt(), expected_ty.display(self.db).alt()));
+                                            self.add_expr_diagnostic_error(file, expr, DiagnosticCode::ArgumentTypeMismatch, format!("Argument of type \"{}\" cannot be assigned to parameter of type \"{}\"", ty.display(self.db).alt(), expected_ty.display(self.db).alt()));
                                         }
                                     }
                                     SlotProvider::Missing => {
@@ -634,18 +709,47 @@ impl TyCtxt<'_> {
                                 message.push('"');
                             }
 
-                            self.add_expr_diagnostic_error(file, expr, message);
+                            self.add_expr_diagnostic_error(
+                                file,
+                                expr,
+                                DiagnosticCode::MissingArgument,
+                                message,
+                            );
                         }
 
                         self.none_ty()
                     }
                     TyKind::Provider(provider) | TyKind::ProviderRawConstructor(_, provider) => {
+                        // Arguments set the declared fields, unless they're passed to the
+                        // provider's `init` callback instead.
+                        let is_init =
+                            provider.has_init && matches!(callee_ty.kind(), TyKind::Provider(_));
+                        if !is_init {
+                            let errors = Slots::from_provider(provider).assign_args(&args, None).0;
+                            for error in errors {
+                                self.add_expr_diagnostic_error(
+                                    file,
+                                    error.expr,
+                                    DiagnosticCode::InvalidArgument,
+                                    error.message,
+                                );
+                            }
+                        }
                         TyKind::ProviderInstance(provider.clone()).intern()
                     }
                     TyKind::Tag(tag_class) => {
                         // TODO(withered-magic): Much of this logic is duplicated from handling `TyKind::Rule` above.
                         let mut slots = Slots::from_tag_class(tag_class);
-                        slots.assign_args(&args, None);
+                        let errors = slots.assign_args(&args, None).0;
+
+                        for error in errors {
+                            self.add_expr_diagnostic_error(
+                                file,
+                                error.expr,
+                                DiagnosticCode::InvalidArgument,
+                                error.message,
+                            );
+                        }
 
                         let mut missing_attrs = Vec::new();
 
@@ -662,7 +766,7 @@ impl TyCtxt<'_> {
                                     SlotProvider::Single(expr, index) => {
                                         let ty = &arg_tys[index];
                                         if !assign_tys(db, ty, &expected_ty) {
-                                            self.add_expr_diagnostic_error(file, expr, format!("Argument of type \"{}\" cannot be assigned to parameter of type \"{}\"", ty.display(self.db).alt(), expected_ty.display(self.db).alt()));
+                                            self.add_expr_diagnostic_error(file, expr, DiagnosticCode::ArgumentTypeMismatch, format!("Argument of type \"{}\" cannot be assigned to parameter of type \"{}\"", ty.display(self.db).alt(), expected_ty.display(self.db).alt()));
                                         }
                                     }
                                     SlotProvider::Missing => {
@@ -688,7 +792,12 @@ impl TyCtxt<'_> {
                                 message.push('"');
                             }
 
-                            self.add_expr_diagnostic_error(file, expr, message);
+                            self.add_expr_diagnostic_error(
+                                file,
+                                expr,
+                                DiagnosticCode::MissingArgument,
+                                message,
+                            );
                         }
 
                         self.none_ty()
@@ -697,6 +806,7 @@ impl TyCtxt<'_> {
                     _ => self.add_expr_diagnostic_warning_ty(
                         file,
                         expr,
+                        DiagnosticCode::NotCallable,
                         format!("Type \"{}\" is not callable", callee_ty.display(db).alt()),
                     ),
                 }
@@ -735,6 +845,7 @@ impl TyCtxt<'_> {
                         self.add_expr_diagnostic_error(
                             file,
                             expr,
+                            DiagnosticCode::InvalidSlice,
                             "`start`, `stop`, and `step` operands must be integers or `None`",
                         )
                     }
@@ -758,6 +869,7 @@ impl TyCtxt<'_> {
                     _ => self.add_expr_diagnostic_warning_ty(
                         file,
                         expr,
+                        DiagnosticCode::InvalidSlice,
                         format!("Cannot slice expression of type \"{}\"", lhs_ty.display(db)),
                     ),
                 }
@@ -768,6 +880,71 @@ impl TyCtxt<'_> {
         self.set_expr_type(file, expr, ty)
     }
 
+    /// Whether the callee is one of the configured struct-merging functions, which are matched by
+    /// how they're called, e.g. `structs.merge`.
+    fn is_struct_merge_function(&self, file: File, callee: ExprId) -> bool {
+        let options = &self.shared_state.options;
+        !options.struct_merge_functions.is_empty()
+            && self.dotted_name(file, callee).map_or(false, |name| {
+                options
+                    .struct_merge_functions
+                    .iter()
+                    .any(|function| function == &name)
+            })
+    }
+
+    fn dotted_name(&self, file: File, expr: ExprId) -> Option<String> {
+        match &module(self.db, file)[expr] {
+            Expr::Name { name } => Some(name.as_str().to_string()),
+            Expr::Dot { expr, field } if !field.is_missing() => {
+                let mut name = self.dotted_name(file, *expr)?;
+                name.push('.');
+                name.push_str(field.as_str());
+                Some(name)
+            }
+            _ => None,
+        }
+    }
+
+    /// Merges the fields of the structs passed to a struct-merging function, with the fields of
+    /// later structs taking precedence. Arguments that aren't inline structs may contribute any
+    /// fields.
+    fn merge_struct_args<'a, I>(&mut self, file: File, call_expr: ExprId, args: I) -> Ty
+    where
+        I: Iterator<Item = (&'a Argument, &'a Ty)>,
+    {
+        let mut fields: Vec<(Name, Ty)> = Vec::new();
+        let mut has_unknown_fields = false;
+        for (arg, ty) in args {
+            match (arg, ty.kind()) {
+                (
+                    Argument::Simple { .. },
+                    TyKind::Struct(Some(Struct::Inline {
+                        fields: arg_fields,
+                        has_unknown_fields: arg_has_unknown_fields,
+                        ..
+                    })),
+                ) => {
+                    for (name, ty) in arg_fields.iter() {
+                        fields.retain(|(other, _)| other != name);
+                        fields.push((name.clone(), ty.clone()));
+                    }
+                    has_unknown_fields |= *arg_has_unknown_fields;
+                }
+                _ => has_unknown_fields = true,
+            }
+        }
+        TyKind::Struct(Some(Struct::Inline {
+            call_expr: InFile {
+                file,
+                value: call_expr,
+            },
+            fields: fields.into_boxed_slice(),
+            has_unknown_fields,
+        }))
+        .intern()
+    }
+
     fn infer_unary_expr(&mut self, file: File, parent: ExprId, expr: ExprId, op: UnaryOp) -> Ty {
         let ty = self.infer_expr(file, expr);
         match self.check_unary_expr(&ty, op) {
@@ -775,6 +952,7 @@ impl TyCtxt<'_> {
             Err(()) => self.add_expr_diagnostic_error_ty(
                 file,
                 parent,
+                DiagnosticCode::UnsupportedOperator,
                 format!(
                     "Operator \"{}\" is not supported for type \"{}\"",
                     op,
@@ -818,6 +996,7 @@ impl TyCtxt<'_> {
             self.add_expr_diagnostic_warning_ty(
                 file,
                 parent,
+                DiagnosticCode::UnsupportedOperator,
                 format!(
                     "Operator \"{}\" not supported for types \"{}\" and \"{}\"",
                     op,
@@ -892,6 +1071,7 @@ impl TyCtxt<'_> {
                     self.add_expr_diagnostic_warning(
                         file,
                         parent,
+                        DiagnosticCode::UnsupportedOperator,
                         format!(
                             "Operator \"{}\" not supported for types \"{}\" and \"{}\"",
                             op,
@@ -938,7 +1118,13 @@ impl TyCtxt<'_> {
                     } else {
                         // Add TypeRef resolution errors.
                         for error in errors.iter() {
-                            self.add_diagnostic_for_range(file, Severity::Error, type_ref.1, error);
+                            self.add_diagnostic_for_range(
+                                file,
+                                Severity::Error,
+                                type_ref.1,
+                                DiagnosticCode::InvalidTypeAnnotation,
+                                error,
+                            );
                         }
                         None
                     }
@@ -983,6 +1169,7 @@ impl TyCtxt<'_> {
                 self.add_expr_diagnostic_warning(
                     file,
                     source,
+                    DiagnosticCode::NotIterable,
                     format!("Type \"{}\" is not iterable", source_ty.display(db)),
                 );
                 for expr in targets.iter() {
@@ -1015,6 +1202,7 @@ impl TyCtxt<'_> {
                         self.add_expr_diagnostic_error(
                             file,
                             root,
+                            DiagnosticCode::TypeMismatch,
                             format!(
                                 "Expected value of type \"{}\"",
                                 expected_ty.display(self.db)
@@ -1061,6 +1249,7 @@ impl TyCtxt<'_> {
                     self.add_expr_diagnostic_error(
                         file,
                         root,
+                        DiagnosticCode::TupleSizeMismatch,
                         format!(
                             "Tuple size mismatch, {} on left-hand side and {} on right-hand side",
                             exprs.len(),
@@ -1078,6 +1267,7 @@ impl TyCtxt<'_> {
                 self.add_expr_diagnostic_warning(
                     file,
                     root,
+                    DiagnosticCode::NotIterable,
                     format!("Type \"{}\" is not iterable", source_ty.display(self.db)),
                 );
                 for expr in exprs.iter() {
@@ -1125,13 +1315,20 @@ impl TyCtxt<'_> {
         &mut self,
         file: File,
         expr: ExprId,
+        code: DiagnosticCode,
         message: T,
     ) {
-        self.add_expr_diagnostic_with_severity(file, expr, Severity::Warning, message)
+        self.add_expr_diagnostic_with_severity(file, expr, Severity::Warning, code, message)
     }
 
-    fn add_expr_diagnostic_error<T: Into<String>>(&mut self, file: File, expr: ExprId, message: T) {
-        self.add_expr_diagnostic_with_severity(file, expr, Severity::Error, message)
+    pub(crate) fn add_expr_diagnostic_error<T: Into<String>>(
+        &mut self,
+        file: File,
+        expr: ExprId,
+        code: DiagnosticCode,
+        message: T,
+    ) {
+        self.add_expr_diagnostic_with_severity(file, expr, Severity::Error, code, message)
     }
 
     fn add_expr_diagnostic_with_severity<T: Into<String>>(
@@ -1139,22 +1336,24 @@ impl TyCtxt<'_> {
         file: File,
         expr: ExprId,
         severity: Severity,
+        code: DiagnosticCode,
         message: T,
     ) {
         let range = match source_map(self.db, file).expr_map_back.get(&expr) {
             Some(ptr) => ptr.syntax_node_ptr().text_range(),
             None => return,
         };
-        self.add_diagnostic_for_range(file, severity, range, message);
+        self.add_diagnostic_for_range(file, severity, range, code, message);
     }
 
     fn add_expr_diagnostic_error_ty<T: Into<String>>(
         &mut self,
         file: File,
         expr: ExprId,
+        code: DiagnosticCode,
         message: T,
     ) -> Ty {
-        self.add_expr_diagnostic_error(file, expr, message);
+        self.add_expr_diagnostic_error(file, expr, code, message);
         self.unknown_ty()
     }
 
@@ -1162,9 +1361,10 @@ impl TyCtxt<'_> {
         &mut self,
         file: File,
         expr: ExprId,
+        code: DiagnosticCode,
         message: T,
     ) -> Ty {
-        self.add_expr_diagnostic_warning(file, expr, message);
+        self.add_expr_diagnostic_warning(file, expr, code, message);
         self.unknown_ty()
     }
 
@@ -1173,11 +1373,13 @@ impl TyCtxt<'_> {
         file: File,
         severity: Severity,
         range: TextRange,
+        code: DiagnosticCode,
         message: T,
     ) {
         self.cx.diagnostics.push(Diagnostic {
             message: message.into(),
             severity,
+            code,
             range: FileRange {
                 file_id: file.id(self.db),
                 range,
@@ -1250,6 +1452,21 @@ impl TyCtxt<'_> {
                     _ => None,
                 }
             }
+            TyKind::ModuleExtension(module_extension) => {
+                let ty = builtin_types(self.db, file.dialect(self.db))
+                    .types(self.db)
+                    .get("module_ctx")?;
+                match ty.kind() {
+                    TyKind::BuiltinType(ty, _) => Some(
+                        TyKind::BuiltinType(
+                            *ty,
+                            Some(TyData::ModuleExtension(module_extension.clone())),
+                        )
+                        .intern(),
+                    ),
+                    _ => None,
+                }
+            }
             _ => None,
         }
     }
@@ -1265,6 +1482,7 @@ impl TyCtxt<'_> {
                     file,
                     Severity::Warning,
                     ptr.syntax_node_ptr().text_range(),
+                    DiagnosticCode::InvalidTypeAnnotation,
                     error,
                 );
             }
@@ -1304,6 +1522,7 @@ impl TyCtxt<'_> {
                                 file,
                                 Severity::Warning,
                                 range(),
+                                DiagnosticCode::LoadCycle,
                                 "Cannot load the current file",
                             );
                             return self.unknown_ty();
@@ -1333,6 +1552,7 @@ impl TyCtxt<'_> {
                                     file,
                                     Severity::Warning,
                                     load_stmt.ptr(db).text_range(),
+                                    DiagnosticCode::LoadCycle,
                                     message.clone(),
                                 )
                             }
@@ -1342,6 +1562,7 @@ impl TyCtxt<'_> {
                                 file,
                                 Severity::Warning,
                                 load_stmt.ptr(db).text_range(),
+                                DiagnosticCode::LoadCycle,
                                 message,
                             );
 
@@ -1365,6 +1586,7 @@ impl TyCtxt<'_> {
                                         file,
                                         Severity::Warning,
                                         range(),
+                                        DiagnosticCode::UnresolvedSymbol,
                                         format!(
                                             "Could not resolve symbol \"{}\" in module \"{}\"",
                                             name,
@@ -1405,6 +1627,7 @@ impl TyCtxt<'_> {
                     file,
                     Severity::Warning,
                     load_stmt.ptr(self.db).text_range(),
+                    DiagnosticCode::UnresolvedLoad,
                     format!(
                         "Could not resolve module \"{}\": {}",
                         load_stmt.module(self.db),
@@ -125,13 +125,18 @@ impl IntrinsicFunction {
             return None;
         }
 
+        // Keys from positional arguments, e.g. `dict(other, key = value)`, aren't known.
+        let mut all_keys_known = true;
         let known_keys = args
             .filter_map(|(arg, ty)| match arg {
                 Argument::Keyword { name, .. } => Some((
                     LiteralString::new(db, name.as_str().to_string().into_boxed_str()),
                     ty.clone(),
                 )),
-                _ => None,
+                _ => {
+                    all_keys_known = false;
+                    None
+                }
             })
             .collect::<Vec<_>>();
 
@@ -162,6 +167,7 @@ impl IntrinsicFunction {
                 Some(Arc::new(DictLiteral {
                     expr: None,
                     known_keys: known_keys.into_boxed_slice(),
+                    all_keys_known,
                 })),
             )
             .intern(),
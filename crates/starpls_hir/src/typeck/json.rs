@@ -0,0 +1,193 @@
+//! Infers the type of the value that `json.decode()` returns for a constant string.
+//!
+//! Objects become dicts whose keys are known, so that e.g. `config["deps"]` has the type of the
+//! `"deps"` entry and the keys can be completed. Only as much of the JSON grammar as is needed to
+//! find the shape of the value is checked; if the string isn't valid JSON, no type is inferred.
+
+use std::{iter::Peekable, str::Chars, sync::Arc};
+
+use crate::{def::LiteralString, typeck::DictLiteral, Db, Ty, TyKind};
+
+/// Objects and arrays nested deeper than this aren't inferred, to keep pathological inputs from
+/// overflowing the stack.
+const MAX_DEPTH: usize = 32;
+
+pub(crate) fn infer_json_type(db: &dyn Db, text: &str) -> Option<Ty> {
+    let mut parser = Parser {
+        db,
+        chars: text.chars().peekable(),
+    };
+    let ty = parser.value(0)?;
+    parser.skip_whitespace();
+    parser.chars.peek().is_none().then_some(ty)
+}
+
+struct Parser<'a> {
+    db: &'a dyn Db,
+    chars: Peekable<Chars<'a>>,
+}
+
+impl Parser<'_> {
+    fn value(&mut self, depth: usize) -> Option<Ty> {
+        if depth > MAX_DEPTH {
+            return None;
+        }
+        self.skip_whitespace();
+        Some(match *self.chars.peek()? {
+            '{' => self.object(depth)?,
+            '[' => self.array(depth)?,
+            '"' => {
+                self.string()?;
+                Ty::string()
+            }
+            't' => self.keyword("true", Ty::bool())?,
+            'f' => self.keyword("false", Ty::bool())?,
+            'n' => self.keyword("null", Ty::none())?,
+            '-' | '0'..='9' => self.number()?,
+            _ => return None,
+        })
+    }
+
+    fn object(&mut self, depth: usize) -> Option<Ty> {
+        self.chars.next();
+        let mut known_keys: Vec<(LiteralString, Ty)> = Vec::new();
+        self.skip_whitespace();
+        if self.eat('}') {
+            return Some(Ty::dict(Ty::string(), Ty::unknown(), None));
+        }
+        loop {
+            self.skip_whitespace();
+            let key = self.string()?;
+            self.skip_whitespace();
+            if !self.eat(':') {
+                return None;
+            }
+            let ty = self.value(depth + 1)?;
+            // As with dict literals, the last entry for a key is the one that's used.
+            known_keys.retain(|(other, _)| &*other.value(self.db) != key.as_str());
+            known_keys.push((LiteralString::new(self.db, key.into_boxed_str()), ty));
+            self.skip_whitespace();
+            if self.eat('}') {
+                break;
+            }
+            if !self.eat(',') {
+                return None;
+            }
+        }
+
+        let value_ty = common_type(known_keys.iter().map(|(_, ty)| ty.clone()));
+        Some(Ty::dict(
+            Ty::string(),
+            value_ty,
+            Some(Arc::new(DictLiteral {
+                expr: None,
+                known_keys: known_keys.into_boxed_slice(),
+                all_keys_known: true,
+            })),
+        ))
+    }
+
+    fn array(&mut self, depth: usize) -> Option<Ty> {
+        self.chars.next();
+        let mut tys = Vec::new();
+        self.skip_whitespace();
+        if !self.eat(']') {
+            loop {
+                tys.push(self.value(depth + 1)?);
+                self.skip_whitespace();
+                if self.eat(']') {
+                    break;
+                }
+                if !self.eat(',') {
+                    return None;
+                }
+            }
+        }
+        Some(Ty::list(common_type(tys.into_iter())))
+    }
+
+    fn string(&mut self) -> Option<String> {
+        if !self.eat('"') {
+            return None;
+        }
+        let mut value = String::new();
+        loop {
+            match self.chars.next()? {
+                '"' => return Some(value),
+                '\\' => match self.chars.next()? {
+                    'n' => value.push('\n'),
+                    't' => value.push('\t'),
+                    'r' => value.push('\r'),
+                    'b' => value.push('\u{8}'),
+                    'f' => value.push('\u{c}'),
+                    'u' => {
+                        let mut code = 0;
+                        for _ in 0..4 {
+                            code = code * 16 + self.chars.next()?.to_digit(16)?;
+                        }
+                        value.push(char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER));
+                    }
+                    c @ ('"' | '\\' | '/') => value.push(c),
+                    _ => return None,
+                },
+                c if c < ' ' => return None,
+                c => value.push(c),
+            }
+        }
+    }
+
+    fn number(&mut self) -> Option<Ty> {
+        let mut is_float = false;
+        let mut saw_digit = false;
+        while let Some(&c) = self.chars.peek() {
+            match c {
+                '0'..='9' => saw_digit = true,
+                '-' | '+' => {}
+                '.' | 'e' | 'E' => is_float = true,
+                _ => break,
+            }
+            self.chars.next();
+        }
+        saw_digit.then(|| {
+            if is_float {
+                TyKind::Float.intern()
+            } else {
+                Ty::int()
+            }
+        })
+    }
+
+    fn keyword(&mut self, keyword: &str, ty: Ty) -> Option<Ty> {
+        for expected in keyword.chars() {
+            if self.chars.next()? != expected {
+                return None;
+            }
+        }
+        Some(ty)
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        self.chars.next_if(|&next| next == c).is_some()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self
+            .chars
+            .next_if(|&c| matches!(c, ' ' | '\t' | '\n' | '\r'))
+            .is_some()
+        {}
+    }
+}
+
+/// Returns the type shared by all of the given types, or `Unknown` if they differ.
+fn common_type(mut tys: impl Iterator<Item = Ty>) -> Ty {
+    let first = match tys.next() {
+        Some(first) => first,
+        None => return Ty::unknown(),
+    };
+    if tys.all(|ty| ty == first) {
+        first
+    } else {
+        Ty::unknown()
+    }
+}
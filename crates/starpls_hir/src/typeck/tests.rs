@@ -17,10 +17,13 @@ fn check_infer(input: &str, expect: Expect) {
 
 fn check_infer_with_options(input: &str, expect: Expect, options: InferenceOptions) {
     let mut builder = TestDatabaseBuilder::default();
+    builder.add_function("depset");
     builder.add_function("provider");
     builder.add_function("rule");
+    builder.add_function("select");
     builder.add_function("struct");
     builder.add_type(FixtureType::new("File", vec![], vec![]));
+    builder.add_type(FixtureType::new("depset", vec![], vec!["to_list"]));
     builder.add_type(FixtureType::new(
         "ctx",
         vec![
@@ -37,8 +40,24 @@ fn check_infer_with_options(input: &str, expect: Expect, options: InferenceOptio
         vec![("attr", "struct")],
         vec![],
     ));
-    builder.add_type(FixtureType::new("attr", vec![], vec!["label_list"]));
+    builder.add_type(FixtureType::new(
+        "module_ctx",
+        vec![("modules", "list")],
+        vec![],
+    ));
+    builder.add_type(FixtureType::new(
+        "bazel_module",
+        vec![("name", "string"), ("tags", "bazel_module_tags")],
+        vec![],
+    ));
+    builder.add_type(FixtureType::new(
+        "attr",
+        vec![],
+        vec!["label_list", "string"],
+    ));
     builder.add_global("attr", "attr");
+    builder.add_type(FixtureType::new("json", vec![], vec!["decode"]));
+    builder.add_global("json", "json");
     builder.set_inference_options(options);
 
     let mut db = builder.build();
@@ -412,6 +431,88 @@ foo(baz=1)
     );
 }
 
+#[test]
+fn test_call_repeated_argument() {
+    check_infer(
+        r#"
+def foo(bar, baz):
+    pass
+
+foo(1, bar=2, baz=3)
+foo(bar=1, bar=2, baz=3)
+"#,
+        expect![[r#"
+            30..33 "foo": def foo(bar, baz) -> Unknown
+            34..35 "1": Literal[1]
+            41..42 "2": Literal[2]
+            48..49 "3": Literal[3]
+            30..50 "foo(1, bar=2, baz=3)": Unknown
+            51..54 "foo": def foo(bar, baz) -> Unknown
+            59..60 "1": Literal[1]
+            66..67 "2": Literal[2]
+            73..74 "3": Literal[3]
+            51..75 "foo(bar=1, bar=2, baz=3)": Unknown
+
+            41..42 Multiple values for parameter "bar"
+            66..67 Duplicate keyword argument "bar"
+        "#]],
+    );
+}
+
+#[test]
+fn test_call_rule_unknown_attribute() {
+    check_infer(
+        r#"
+_SRCS = "srcs"
+
+foo_rule = rule(attrs = {"srcs": attr.label_list()})
+bar_rule = rule(attrs = {_SRCS: attr.label_list()})
+
+foo_rule(name = "foo", deps = [])
+foo_rule("foo", size = "small")
+bar_rule(name = "bar", deps = [], name = "baz")
+"#,
+        expect![[r#"
+            1..6 "_SRCS": Literal["srcs"]
+            9..15 "\"srcs\"": Literal["srcs"]
+            17..25 "foo_rule": rule
+            28..32 "rule": def rule(*args, **kwargs) -> Unknown
+            42..48 "\"srcs\"": Literal["srcs"]
+            50..54 "attr": attr
+            50..65 "attr.label_list": def label_list(*args, **kwargs) -> Unknown
+            50..67 "attr.label_list()": Attribute
+            41..68 "{\"srcs\": attr.label_list()}": dict[string, Attribute]
+            28..69 "rule(attrs = {\"srcs\": attr.label_list()})": rule
+            70..78 "bar_rule": rule
+            81..85 "rule": def rule(*args, **kwargs) -> Unknown
+            95..100 "_SRCS": Literal["srcs"]
+            102..106 "attr": attr
+            102..117 "attr.label_list": def label_list(*args, **kwargs) -> Unknown
+            102..119 "attr.label_list()": Attribute
+            94..120 "{_SRCS: attr.label_list()}": dict[string, Attribute]
+            81..121 "rule(attrs = {_SRCS: attr.label_list()})": rule
+            123..131 "foo_rule": rule
+            139..144 "\"foo\"": Literal["foo"]
+            153..155 "[]": list[Unknown]
+            123..156 "foo_rule(name = \"foo\", deps = [])": None
+            157..165 "foo_rule": rule
+            166..171 "\"foo\"": Literal["foo"]
+            180..187 "\"small\"": Literal["small"]
+            157..188 "foo_rule(\"foo\", size = \"small\")": None
+            189..197 "bar_rule": rule
+            205..210 "\"bar\"": Literal["bar"]
+            219..221 "[]": list[Unknown]
+            230..235 "\"baz\"": Literal["baz"]
+            189..236 "bar_rule(name = \"bar\", deps = [], name = \"baz\")": None
+
+            153..155 Unexpected keyword argument "deps"
+            157..188 Argument missing for attribute(s) "name"
+            166..171 Unexpected positional argument
+            230..235 Duplicate keyword argument "name"
+        "#]],
+    );
+}
+
 #[test]
 fn test_call_keyword_only() {
     check_infer(
@@ -645,6 +746,119 @@ foo.c
             38..43 "foo.b": Literal["bar"]
             44..47 "foo": struct
             44..49 "foo.c": Unknown
+
+            44..49 Cannot access field "c" for type "struct"
+        "#]],
+    )
+}
+
+#[test]
+fn test_struct_unpacked_kwargs() {
+    check_infer(
+        r#"
+foo = struct(a = 1, **{"b": 2})
+foo.b
+"#,
+        expect![[r#"
+            1..4 "foo": struct
+            7..13 "struct": def struct(*args, **kwargs) -> Unknown
+            18..19 "1": Literal[1]
+            24..27 "\"b\"": Literal["b"]
+            29..30 "2": Literal[2]
+            23..31 "{\"b\": 2}": dict[string, int]
+            7..32 "struct(a = 1, **{\"b\": 2})": struct
+            33..36 "foo": struct
+            33..38 "foo.b": Unknown
+        "#]],
+    )
+}
+
+#[test]
+fn test_struct_merge_functions() {
+    check_infer_with_options(
+        r#"
+def _merge(*structs):
+    pass
+
+structs = struct(merge = _merge)
+a = struct(x = 1)
+b = struct(x = "x", y = True)
+c = structs.merge(a, b)
+c.x
+c.y
+c.z
+"#,
+        expect![[r#"
+            33..40 "structs": struct
+            43..49 "struct": def struct(*args, **kwargs) -> Unknown
+            58..64 "_merge": def _merge(*structs: Unknown) -> Unknown
+            43..65 "struct(merge = _merge)": struct
+            66..67 "a": struct
+            70..76 "struct": def struct(*args, **kwargs) -> Unknown
+            81..82 "1": Literal[1]
+            70..83 "struct(x = 1)": struct
+            84..85 "b": struct
+            88..94 "struct": def struct(*args, **kwargs) -> Unknown
+            99..102 "\"x\"": Literal["x"]
+            108..112 "True": Literal[True]
+            88..113 "struct(x = \"x\", y = True)": struct
+            114..115 "c": struct
+            118..125 "structs": struct
+            118..131 "structs.merge": def _merge(*structs: Unknown) -> Unknown
+            132..133 "a": struct
+            135..136 "b": struct
+            118..137 "structs.merge(a, b)": struct
+            138..139 "c": struct
+            138..141 "c.x": Literal["x"]
+            142..143 "c": struct
+            142..145 "c.y": Literal[True]
+            146..147 "c": struct
+            146..149 "c.z": Unknown
+
+            146..149 Cannot access field "z" for type "struct"
+        "#]],
+        InferenceOptions {
+            struct_merge_functions: vec!["structs.merge".to_string()],
+            ..Default::default()
+        },
+    );
+}
+
+#[test]
+fn test_depset() {
+    check_infer(
+        r#"
+a = depset(["a"])
+b = depset(["b"], transitive = [a])
+c = b.to_list()
+d = depset([1], transitive = [a])
+"#,
+        expect![[r#"
+            1..2 "a": depset[string]
+            5..11 "depset": def depset(*args, **kwargs) -> Unknown
+            12..15 "\"a\"": Literal["a"]
+            11..16 "[\"a\"]": list[string]
+            5..17 "depset([\"a\"])": depset[string]
+            19..20 "b": depset[string]
+            23..29 "depset": def depset(*args, **kwargs) -> Unknown
+            30..33 "\"b\"": Literal["b"]
+            29..34 "[\"b\"]": list[string]
+            51..52 "a": depset[string]
+            50..53 "[a]": list[depset[string]]
+            23..54 "depset([\"b\"], transitive = [a])": depset[string]
+            55..56 "c": list[string]
+            59..60 "b": depset[string]
+            59..68 "b.to_list": def to_list(*args, **kwargs) -> Unknown
+            59..70 "b.to_list()": list[string]
+            71..72 "d": depset[int]
+            75..81 "depset": def depset(*args, **kwargs) -> Unknown
+            82..83 "1": Literal[1]
+            81..84 "[1]": list[int]
+            101..102 "a": depset[string]
+            100..103 "[a]": list[depset[string]]
+            75..104 "depset([1], transitive = [a])": depset[int]
+
+            100..103 Depset elements of type "string" cannot be combined with elements of type "int"
         "#]],
     )
 }
@@ -680,6 +894,41 @@ info = DataInfo(foo = "foo", bar = "bar")
     )
 }
 
+#[test]
+fn test_provider_fields() {
+    check_infer(
+        r#"
+DataInfo = provider(fields = ["foo", "bar"])
+
+info = DataInfo(foo = 1, baz = 2)
+x = info.foo
+y = info.qux
+"#,
+        expect![[r#"
+            1..9 "DataInfo": Provider[DataInfo]
+            12..20 "provider": def provider(*args, **kwargs) -> Unknown
+            31..36 "\"foo\"": Literal["foo"]
+            38..43 "\"bar\"": Literal["bar"]
+            30..44 "[\"foo\", \"bar\"]": list[string]
+            12..45 "provider(fields = [\"foo\", \"bar\"])": Provider[DataInfo]
+            47..51 "info": DataInfo
+            54..62 "DataInfo": Provider[DataInfo]
+            69..70 "1": Literal[1]
+            78..79 "2": Literal[2]
+            54..80 "DataInfo(foo = 1, baz = 2)": DataInfo
+            81..82 "x": Unknown
+            85..89 "info": DataInfo
+            85..93 "info.foo": Unknown
+            94..95 "y": Unknown
+            98..102 "info": DataInfo
+            98..106 "info.qux": Unknown
+
+            78..79 Unexpected keyword argument "baz"
+            98..106 Cannot access field "qux" for type "DataInfo"
+        "#]],
+    )
+}
+
 #[test]
 fn test_provider_constructor() {
     check_infer(
@@ -1137,6 +1386,61 @@ my_rule = repository_rule(
         "#]],
         InferenceOptions {
             infer_ctx_attrs: true,
+            ..Default::default()
+        },
+    );
+}
+
+#[test]
+fn test_infer_module_ctx_tags() {
+    check_infer_with_options(
+        r#"
+def _impl(module_ctx):
+    mod = module_ctx.modules[0]
+    tag = mod.tags.install[0]
+    name = tag.name
+
+_install = tag_class(attrs = {"name": attr.string()})
+
+deps = module_extension(
+    implementation = _impl,
+    tag_classes = {"install": _install},
+)
+"#,
+        expect![[r#"
+            28..31 "mod": bazel_module
+            34..44 "module_ctx": module_ctx
+            34..52 "module_ctx.modules": list[bazel_module]
+            53..54 "0": Literal[0]
+            34..55 "module_ctx.modules[0]": bazel_module
+            60..63 "tag": struct
+            66..69 "mod": bazel_module
+            66..74 "mod.tags": struct
+            66..82 "mod.tags.install": list[struct]
+            83..84 "0": Literal[0]
+            66..85 "mod.tags.install[0]": struct
+            90..94 "name": string
+            97..100 "tag": struct
+            97..105 "tag.name": string
+            107..115 "_install": tag_class
+            118..127 "tag_class": def tag_class(attrs: dict[string, Unknown] = {}, doc: string | None = None) -> tag_class
+            137..143 "\"name\"": Literal["name"]
+            145..149 "attr": attr
+            145..156 "attr.string": def string(*args, **kwargs) -> Unknown
+            145..158 "attr.string()": Attribute
+            136..159 "{\"name\": attr.string()}": dict[string, Attribute]
+            118..160 "tag_class(attrs = {\"name\": attr.string()})": tag_class
+            162..166 "deps": module_extension
+            169..185 "module_extension": def module_extension(implementation: Unknown, tag_classes: Unknown = {}, doc: string | None = None, environ: Sequence[string] = [], os_dependent: bool = False, arch_dependent: bool = False) -> Unknown
+            208..213 "_impl": def _impl(module_ctx) -> Unknown
+            234..243 "\"install\"": Literal["install"]
+            245..253 "_install": tag_class
+            233..254 "{\"install\": _install}": dict[string, tag_class]
+            169..257 "module_extension(\n    implementation = _impl,\n    tag_classes = {\"install\": _install},\n)": module_extension
+        "#]],
+        InferenceOptions {
+            infer_ctx_attrs: true,
+            ..Default::default()
         },
     );
 }
@@ -1177,3 +1481,86 @@ my_rule = rule(
         "#]],
     );
 }
+
+#[test]
+fn test_json_decode() {
+    check_infer(
+        r#"
+config = json.decode('{"name": "foo", "deps": ["a"]}')
+deps = config["deps"]
+fallback = json.decode(deps[0], default = {"enabled": False})
+enabled = fallback["enabled"]
+invalid = json.decode("{")
+"#,
+        expect![[r#"
+            1..7 "config": dict[string, Unknown]
+            10..14 "json": json
+            10..21 "json.decode": def decode(*args, **kwargs) -> Unknown
+            22..54 "'{\"name\": \"foo\", \"deps\": [\"a\"]}'": Literal["{\"name\": \"foo\", \"deps\": [\"a\"]}"]
+            10..55 "json.decode('{\"name\": \"foo\", \"deps\": [\"a\"]}')": dict[string, Unknown]
+            56..60 "deps": list[string]
+            63..69 "config": dict[string, Unknown]
+            70..76 "\"deps\"": Literal["deps"]
+            63..77 "config[\"deps\"]": list[string]
+            78..86 "fallback": dict[string, bool]
+            89..93 "json": json
+            89..100 "json.decode": def decode(*args, **kwargs) -> Unknown
+            101..105 "deps": list[string]
+            106..107 "0": Literal[0]
+            101..108 "deps[0]": string
+            121..130 "\"enabled\"": Literal["enabled"]
+            132..137 "False": Literal[False]
+            120..138 "{\"enabled\": False}": dict[string, bool]
+            89..139 "json.decode(deps[0], default = {\"enabled\": False})": dict[string, bool]
+            140..147 "enabled": bool
+            150..158 "fallback": dict[string, bool]
+            159..168 "\"enabled\"": Literal["enabled"]
+            150..169 "fallback[\"enabled\"]": bool
+            170..177 "invalid": Unknown
+            180..184 "json": json
+            180..191 "json.decode": def decode(*args, **kwargs) -> Unknown
+            192..195 "\"{\"": Literal["{"]
+            180..196 "json.decode(\"{\")": Unknown
+        "#]],
+    );
+}
+
+#[test]
+fn test_select_concatenation() {
+    check_infer(
+        r#"
+srcs = ["a.cc"] + select({"//x": ["b.cc"], "//conditions:default": []})
+copts = select({"//x": ["-DX"]}) + select({"//y": ["-DY"], "//conditions:default": []})
+"#,
+        expect![[r#"
+            1..5 "srcs": list[string]
+            9..15 "\"a.cc\"": Literal["a.cc"]
+            8..16 "[\"a.cc\"]": list[string]
+            19..25 "select": def select(*args, **kwargs) -> Unknown
+            27..32 "\"//x\"": Literal["//x"]
+            35..41 "\"b.cc\"": Literal["b.cc"]
+            34..42 "[\"b.cc\"]": list[string]
+            44..66 "\"//conditions:default\"": Literal["//conditions:default"]
+            68..70 "[]": list[Unknown]
+            26..71 "{\"//x\": [\"b.cc\"], \"//conditions:default\": []}": dict[string, Unknown]
+            19..72 "select({\"//x\": [\"b.cc\"], \"//conditions:default\": []})": list[string]
+            8..72 "[\"a.cc\"] + select({\"//x\": [\"b.cc\"], \"//conditions:default\": []})": list[string]
+            73..78 "copts": list[string]
+            81..87 "select": def select(*args, **kwargs) -> Unknown
+            89..94 "\"//x\"": Literal["//x"]
+            97..102 "\"-DX\"": Literal["-DX"]
+            96..103 "[\"-DX\"]": list[string]
+            88..104 "{\"//x\": [\"-DX\"]}": dict[string, list[string]]
+            81..105 "select({\"//x\": [\"-DX\"]})": list[string]
+            108..114 "select": def select(*args, **kwargs) -> Unknown
+            116..121 "\"//y\"": Literal["//y"]
+            124..129 "\"-DY\"": Literal["-DY"]
+            123..130 "[\"-DY\"]": list[string]
+            132..154 "\"//conditions:default\"": Literal["//conditions:default"]
+            156..158 "[]": list[Unknown]
+            115..159 "{\"//y\": [\"-DY\"], \"//conditions:default\": []}": dict[string, Unknown]
+            108..160 "select({\"//y\": [\"-DY\"], \"//conditions:default\": []})": list[string]
+            81..160 "select({\"//x\": [\"-DX\"]}) + select({\"//y\": [\"-DY\"], \"//conditions:default\": []})": list[string]
+        "#]],
+    );
+}
@@ -0,0 +1,277 @@
+//! Completion and validation of the values of rule attributes and builtin parameters that only
+//! accept a fixed set of values.
+//!
+//! The values of user-defined rule attributes come from their declarations, e.g.
+//! `attr.string(values = ["a", "b"])`. A few builtin parameters, like `cfg` of `attr.label()`,
+//! have closed sets of values that aren't part of the builtin definitions, so they're listed
+//! here instead. Only literal values are checked; variables, `select()`s, and transitions
+//! are left alone.
+
+use starpls_common::{parse, Diagnostic, DiagnosticCode, File, FileRange, Severity};
+use starpls_hir::{Callable, Db, Semantics};
+use starpls_syntax::ast::{self, AstNode};
+
+/// The values accepted by `cfg` for the label attributes created by `attr.label()`,
+/// `attr.label_list()`, and `attr.label_keyed_string_dict()`.
+const CFG_VALUES: &[&str] = &["exec", "target"];
+
+/// The values accepted by the `stamp` attribute of native binary and test rules.
+const STAMP_VALUES: &[i64] = &[-1, 0, 1];
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum AllowedValue {
+    String(String),
+    Int(i64),
+}
+
+impl AllowedValue {
+    fn from_expr(expr: &ast::Expression) -> Option<Self> {
+        match expr {
+            ast::Expression::Literal(lit) => match lit.kind() {
+                ast::LiteralKind::String(s) => Some(Self::String(s.value()?.to_string())),
+                ast::LiteralKind::Int(i) => Some(Self::Int(i.value()?.try_into().ok()?)),
+                _ => None,
+            },
+            ast::Expression::Unary(unary_expr) => match unary_expr.unary_op_info()? {
+                (_, ast::UnaryOp::Arith(ast::UnaryArithOp::Sub)) => {
+                    match Self::from_expr(&unary_expr.expr()?)? {
+                        Self::Int(i) => Some(Self::Int(-i)),
+                        _ => None,
+                    }
+                }
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Renders the value as a Starlark literal.
+    pub(crate) fn to_literal(&self) -> String {
+        match self {
+            Self::String(s) => format!("{:?}", s),
+            Self::Int(i) => i.to_string(),
+        }
+    }
+}
+
+/// Returns the values accepted by the argument with the given name in a call, if they're
+/// restricted to a fixed set.
+pub(crate) fn allowed_values(
+    db: &dyn Db,
+    file: File,
+    call: &ast::CallExpr,
+    name: &str,
+) -> Option<Vec<AllowedValue>> {
+    let sema = Semantics::new(db);
+    let callable = sema.resolve_call_expr(file, call)?;
+    allowed_values_for_callable(db, call, &callable, name)
+}
+
+fn allowed_values_for_callable(
+    db: &dyn Db,
+    call: &ast::CallExpr,
+    callable: &Callable,
+    name: &str,
+) -> Option<Vec<AllowedValue>> {
+    let param = callable
+        .params(db)
+        .into_iter()
+        .map(|(param, _)| param)
+        .find(|param| {
+            param
+                .name(db)
+                .map_or(false, |param_name| param_name.as_str() == name)
+        })?;
+
+    if let Some(list) = param.allowed_values_source(db) {
+        // Skip lists with non-literal elements, since their values aren't known.
+        return list
+            .value
+            .elements()
+            .map(|element| AllowedValue::from_expr(&element))
+            .collect();
+    }
+
+    if callable.is_user_defined() || callable.is_rule() {
+        return None;
+    }
+    match name {
+        "cfg" if is_label_attr_constructor(call) => Some(
+            CFG_VALUES
+                .iter()
+                .map(|value| AllowedValue::String(value.to_string()))
+                .collect(),
+        ),
+        "stamp" => Some(
+            STAMP_VALUES
+                .iter()
+                .copied()
+                .map(AllowedValue::Int)
+                .collect(),
+        ),
+        _ => None,
+    }
+}
+
+fn is_label_attr_constructor(call: &ast::CallExpr) -> bool {
+    let dot_expr = match call.callee() {
+        Some(ast::Expression::Dot(dot_expr)) => dot_expr,
+        _ => return false,
+    };
+    let is_attr_module = matches!(
+        dot_expr.expr(),
+        Some(ast::Expression::Name(name_ref))
+            if name_ref.name().map_or(false, |name| name.text() == "attr")
+    );
+    is_attr_module
+        && dot_expr
+            .field()
+            .and_then(|field| field.name())
+            .map_or(false, |field| {
+                matches!(
+                    field.text(),
+                    "label" | "label_list" | "label_keyed_string_dict"
+                )
+            })
+}
+
+/// Reports literal arguments that aren't among the values accepted by their parameters.
+pub(crate) fn invalid_value_diagnostics(db: &dyn Db, file: File) -> Vec<Diagnostic> {
+    let sema = Semantics::new(db);
+    let mut diagnostics = Vec::new();
+    for call in parse(db, file)
+        .syntax(db)
+        .descendants()
+        .filter_map(ast::CallExpr::cast)
+    {
+        let args = call
+            .arguments()
+            .into_iter()
+            .flat_map(|args| args.arguments())
+            .filter_map(|arg| {
+                let arg = match arg {
+                    ast::Argument::Keyword(arg) => arg,
+                    _ => return None,
+                };
+                let name = arg.name()?.name()?.text().to_string();
+                let expr = arg.expr()?;
+                let value = AllowedValue::from_expr(&expr)?;
+                Some((name, expr, value))
+            })
+            .collect::<Vec<_>>();
+        if args.is_empty() {
+            continue;
+        }
+
+        // Only resolve the callee once there's something to check.
+        let callable = match sema.resolve_call_expr(file, &call) {
+            Some(callable) => callable,
+            None => continue,
+        };
+        for (name, expr, value) in args {
+            let values = match allowed_values_for_callable(db, &call, &callable, &name) {
+                Some(values) if !values.is_empty() => values,
+                _ => continue,
+            };
+            if values.contains(&value) {
+                continue;
+            }
+            diagnostics.push(Diagnostic {
+                message: format!(
+                    "Invalid value {} for \"{}\", expected one of {}",
+                    value.to_literal(),
+                    name,
+                    values
+                        .iter()
+                        .map(AllowedValue::to_literal)
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+                code: DiagnosticCode::InvalidAttributeValue,
+                severity: Severity::Error,
+                range: FileRange {
+                    file_id: file.id(db),
+                    range: expr.syntax().text_range(),
+                },
+            });
+        }
+    }
+    diagnostics
+}
+
+#[cfg(test)]
+mod tests {
+    use starpls_bazel::Builtins;
+    use starpls_common::{Db as _, DiagnosticCode, Dialect, FileId};
+    use starpls_hir::Db as _;
+    use starpls_test_util::{make_test_builtins, FixtureType};
+
+    use super::invalid_value_diagnostics;
+    use crate::{Analysis, FilePosition};
+
+    #[test]
+    fn test_invalid_values() {
+        let contents = r#"
+def _impl(ctx):
+    pass
+
+my_rule = rule(
+    implementation = _impl,
+    attrs = {
+        "mode": attr.string(values = ["fast", "slow"]),
+        "level": attr.int(values = [-1, 0, 1]),
+        "other": attr.string(),
+    },
+)
+
+my_rule(name = "a", mode = "fast", level = -1)
+my_rule(name = "b", mode = "medium", level = 2, other = "anything")
+"#;
+        let file_id = FileId(0);
+        let mut analysis = Analysis::from_files(&[("defs.bzl", contents)], Dialect::Bazel);
+        analysis.db.set_builtin_defs(
+            Dialect::Bazel,
+            make_test_builtins(
+                vec!["rule".to_string()],
+                vec![("attr".to_string(), "attr".to_string())],
+                vec![FixtureType::new("attr", vec![], vec!["int", "string"])],
+            ),
+            Builtins::default(),
+        );
+        let snap = analysis.snapshot();
+
+        let file = snap.db.get_file(file_id).unwrap();
+        let diagnostics = invalid_value_diagnostics(&*snap.db, file);
+        assert_eq!(
+            diagnostics
+                .iter()
+                .map(|diagnostic| (diagnostic.code, &contents[diagnostic.range.range]))
+                .collect::<Vec<_>>(),
+            vec![
+                (DiagnosticCode::InvalidAttributeValue, "\"medium\""),
+                (DiagnosticCode::InvalidAttributeValue, "2"),
+            ]
+        );
+        assert_eq!(
+            diagnostics[0].message,
+            "Invalid value \"medium\" for \"mode\", expected one of \"fast\", \"slow\""
+        );
+
+        // The accepted values are also offered as completions.
+        let pos = contents.find("\"fast\", level").unwrap() + 1;
+        let labels = snap
+            .completion(
+                FilePosition {
+                    file_id,
+                    pos: (pos as u32).into(),
+                },
+                None,
+            )
+            .unwrap()
+            .unwrap()
+            .into_iter()
+            .map(|item| item.label)
+            .collect::<Vec<_>>();
+        assert_eq!(labels, vec!["fast", "slow"]);
+    }
+}
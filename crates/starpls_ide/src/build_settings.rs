@@ -0,0 +1,398 @@
+//! Recognizes build settings, i.e. the targets whose values configure the build, like the
+//! `string_flag` and `bool_flag` rules from Skylib, or rules declared with
+//! `rule(build_setting = config.string(flag = True))`.
+//!
+//! Build settings are mostly referred to by label from other places: the `flag_values` of
+//! `config_setting`s, whose labels are in turn the keys of `select()`s, and the `inputs` and
+//! `outputs` of transitions. Those labels already resolve like any other label, so this module
+//! adds what's specific to build settings: describing them on hover, finding the labels that
+//! refer to one, and describing the builtin `//command_line_option` settings, which don't
+//! belong to any package.
+
+use starpls_common::{parse, Db as _, File, FileRange, ResolvedPath};
+use starpls_hir::{Name, ScopeDef, Semantics};
+use starpls_syntax::{
+    ast::{self, AstNode, AstToken},
+    SyntaxKind, SyntaxToken,
+};
+
+use crate::{
+    label_references::label_references,
+    labels::{self, LabelTarget},
+    macro_targets::keyword_arg,
+    module_deps::callee_name,
+    targets::{rule_name, targets, Target},
+    util::pick_best_token,
+    Database, FilePosition, Hover, Markup,
+};
+
+/// The package of the pseudo-targets that stand for Bazel's own options in transitions, e.g.
+/// `//command_line_option:cpu`.
+const COMMAND_LINE_OPTION_PACKAGE: &str = "//command_line_option:";
+
+/// The Skylib rules that declare build settings, along with the types of their values and
+/// whether they can be set on the command line.
+const SKYLIB_BUILD_SETTINGS: &[(&str, &str, bool)] = &[
+    ("bool_flag", "bool", true),
+    ("bool_setting", "bool", false),
+    ("int_flag", "int", true),
+    ("int_setting", "int", false),
+    ("label_flag", "label", true),
+    ("label_setting", "label", false),
+    ("repeatable_string_flag", "string_list", true),
+    ("string_flag", "string", true),
+    ("string_list_flag", "string_list", true),
+    ("string_list_setting", "string_list", false),
+    ("string_setting", "string", false),
+];
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct BuildSetting {
+    /// The type of the setting's value, e.g. `string` for `config.string()`.
+    pub(crate) ty: String,
+    /// Whether the setting can be set on the command line, e.g. with `--//flags:foo=bar`.
+    pub(crate) is_flag: bool,
+}
+
+impl BuildSetting {
+    pub(crate) fn describe(&self) -> String {
+        if self.is_flag {
+            format!(
+                "Build setting of type `{}`, which can be set on the command line.\n",
+                self.ty
+            )
+        } else {
+            format!("Build setting of type `{}`.\n", self.ty)
+        }
+    }
+}
+
+/// Returns the build setting declared by a target in the given `BUILD` file, if its rule is one
+/// of Skylib's build setting rules, or a rule with a `build_setting`.
+pub(crate) fn build_setting(
+    db: &Database,
+    build_file: File,
+    target: &Target,
+) -> Option<BuildSetting> {
+    let kind = rule_name(&target.kind);
+    if let Some((_, ty, is_flag)) = SKYLIB_BUILD_SETTINGS
+        .iter()
+        .find(|(name, _, _)| *name == kind)
+    {
+        return Some(BuildSetting {
+            ty: ty.to_string(),
+            is_flag: *is_flag,
+        });
+    }
+
+    // Otherwise, find the `rule()` call that declared the target's rule.
+    if target.is_macro || kind != target.kind.as_str() {
+        return None;
+    }
+    let sema = Semantics::new(db);
+    let def = sema
+        .scope_for_module(build_file)
+        .resolve_name(&Name::from(kind))?
+        .pop()?;
+    let (file, def) = match def {
+        ScopeDef::LoadItem(load_item) => {
+            let def = sema.def_for_load_item(&load_item)?;
+            (def.file, def.value)
+        }
+        def => (build_file, def),
+    };
+    if !matches!(def, ScopeDef::Variable(_)) {
+        return None;
+    }
+    let node = def
+        .syntax_node_ptr(db, file)?
+        .to_node(&parse(db, file).syntax(db));
+    let assign_stmt = ast::AssignStmt::cast(node.parent()?)?;
+    let rule_call = match assign_stmt.rhs()? {
+        ast::Expression::Call(call_expr) if callee_name(&call_expr)? == "rule" => call_expr,
+        _ => return None,
+    };
+
+    // The setting's type is given by the function of the `config` module that's called, e.g.
+    // `config.bool(flag = True)`.
+    let config_call = match keyword_arg(&rule_call, "build_setting")? {
+        ast::Expression::Call(call_expr) => call_expr,
+        _ => return None,
+    };
+    let dot_expr = match config_call.callee()? {
+        ast::Expression::Dot(dot_expr) => dot_expr,
+        _ => return None,
+    };
+    match dot_expr.expr()? {
+        ast::Expression::Name(name_ref) if name_ref.name()?.text() == "config" => {}
+        _ => return None,
+    }
+    let is_flag = match keyword_arg(&config_call, "flag") {
+        Some(ast::Expression::Literal(lit)) => matches!(lit.kind(), ast::LiteralKind::Bool(true)),
+        _ => false,
+    };
+    Some(BuildSetting {
+        ty: dot_expr.field()?.name()?.text().to_string(),
+        is_flag,
+    })
+}
+
+/// Describes a reference to one of Bazel's own options, e.g. `"//command_line_option:cpu"` in
+/// the `outputs` of a transition.
+pub(crate) fn hover(token: &SyntaxToken) -> Option<Hover> {
+    let value = ast::String::cast(token.clone())?.value()?;
+    let option = value
+        .strip_prefix('@')
+        .unwrap_or(&*value)
+        .strip_prefix(COMMAND_LINE_OPTION_PACKAGE)?;
+    if option.is_empty() {
+        return None;
+    }
+    Some(Hover {
+        contents: Markup {
+            value: format!("```python\n(command-line option) --{}\n```\n", option),
+        },
+        range: Some(token.text_range()),
+        definition: None,
+    })
+}
+
+/// Finds the labels that refer to a build setting, from either the `name` of the target declaring
+/// it or one of the labels.
+pub(crate) fn references(
+    db: &Database,
+    FilePosition { file_id, pos }: FilePosition,
+    include_declaration: bool,
+) -> Option<Vec<FileRange>> {
+    let file = db.get_file(file_id)?;
+    let token = pick_best_token(
+        parse(db, file).syntax(db).token_at_offset(pos),
+        |kind| match kind {
+            SyntaxKind::STRING => 2,
+            kind if kind.is_trivia_token() => 0,
+            _ => 1,
+        },
+    )?;
+    if token.kind() != SyntaxKind::STRING {
+        return None;
+    }
+    let declared_target = targets(db, file_id)?
+        .into_iter()
+        .find(|target| target.name_range.contains_range(token.text_range()));
+    let (build_file, target) = match declared_target {
+        Some(target) => (file_id, target),
+        None => match labels::resolve_label(db, file, &token)?.target {
+            LabelTarget::Target {
+                build_file,
+                name,
+                target,
+            } if name == target.name => (build_file, target),
+            _ => return None,
+        },
+    };
+    build_setting(db, db.get_file(build_file)?, &target)?;
+
+    let mut references = Vec::new();
+    if include_declaration {
+        references.push(FileRange {
+            file_id: build_file,
+            range: target.name_range,
+        });
+    }
+    references.extend(
+        label_references(db)
+            .into_iter()
+            .filter(|reference| match &reference.resolved_path {
+                ResolvedPath::BuildTarget {
+                    build_file: reference_build_file,
+                    target: name,
+                    ..
+                } => *reference_build_file == build_file && *name == target.name,
+                _ => false,
+            })
+            .map(|reference| FileRange {
+                file_id: reference.file_id,
+                range: reference.range,
+            }),
+    );
+    Some(references)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use starpls_bazel::APIContext;
+    use starpls_common::{Dialect, FileId, LoadItemCandidate, ResolvedPath};
+    use starpls_syntax::{TextRange, TextSize};
+
+    use crate::{Analysis, AnalysisSnapshot, Change, FileLoader, FilePosition, FileRange};
+
+    /// Resolves relative labels to targets in the package of the file with ID 0.
+    struct PackageLoader;
+
+    impl FileLoader for PackageLoader {
+        fn resolve_path(
+            &self,
+            path: &str,
+            _dialect: Dialect,
+            _from: FileId,
+        ) -> anyhow::Result<Option<ResolvedPath>> {
+            Ok(path
+                .strip_prefix(':')
+                .map(|target| ResolvedPath::BuildTarget {
+                    build_file: FileId(0),
+                    target: target.to_string(),
+                    contents: None,
+                }))
+        }
+
+        fn load_file(
+            &self,
+            _path: &str,
+            _dialect: Dialect,
+            _from: FileId,
+        ) -> anyhow::Result<Option<(FileId, Dialect, Option<APIContext>, Option<String>)>> {
+            Ok(None)
+        }
+
+        fn list_load_candidates(
+            &self,
+            _path: &str,
+            _dialect: Dialect,
+            _from: FileId,
+        ) -> anyhow::Result<Option<Vec<LoadItemCandidate>>> {
+            Ok(None)
+        }
+
+        fn list_label_candidates(
+            &self,
+            _path: &str,
+            _dialect: Dialect,
+            _from: FileId,
+        ) -> anyhow::Result<Option<(Vec<LoadItemCandidate>, Option<(FileId, Option<String>)>)>>
+        {
+            Ok(None)
+        }
+    }
+
+    const BUILD: &str = r#"
+_my_setting = rule(
+    implementation = _impl,
+    build_setting = config.int(flag = True),
+)
+
+string_flag(
+    name = "mode",
+    build_setting_default = "fast",
+)
+
+_my_setting(
+    name = "jobs",
+    build_setting_default = 1,
+)
+
+config_setting(
+    name = "fast",
+    flag_values = {
+        ":mode": "fast",
+        ":jobs": "2",
+    },
+)
+"#;
+
+    fn snapshot(contents: &str) -> AnalysisSnapshot {
+        let mut change = Change::default();
+        change.create_file(
+            FileId(0),
+            Dialect::Bazel,
+            Some(APIContext::Build),
+            contents.to_string(),
+        );
+        let mut analysis = Analysis::new(Arc::new(PackageLoader), Default::default());
+        analysis.apply_change(change);
+        analysis.snapshot()
+    }
+
+    fn pos(contents: &str, needle: &str) -> FilePosition {
+        FilePosition {
+            file_id: FileId(0),
+            pos: TextSize::from(contents.find(needle).unwrap() as u32 + 1),
+        }
+    }
+
+    fn range(contents: &str, needle: &str) -> TextRange {
+        let start = contents.find(needle).unwrap() as u32;
+        TextRange::at(TextSize::from(start), TextSize::of(needle))
+    }
+
+    fn hover(contents: &str, needle: &str) -> String {
+        snapshot(contents)
+            .hover(pos(contents, needle))
+            .unwrap()
+            .unwrap()
+            .contents
+            .value
+    }
+
+    #[test]
+    fn test_hover_skylib_flag() {
+        assert!(hover(BUILD, "\":mode\"").ends_with(
+            "```\nBuild setting of type `string`, which can be set on the command line.\n"
+        ));
+    }
+
+    #[test]
+    fn test_hover_custom_build_setting() {
+        assert!(hover(BUILD, "\":jobs\"").ends_with(
+            "```\nBuild setting of type `int`, which can be set on the command line.\n"
+        ));
+    }
+
+    #[test]
+    fn test_hover_command_line_option() {
+        let contents = r#"x = ["//command_line_option:cpu"]"#;
+        assert_eq!(
+            hover(contents, "\"//command_line_option"),
+            "```python\n(command-line option) --cpu\n```\n"
+        );
+    }
+
+    #[test]
+    fn test_references_from_declaration() {
+        let snap = snapshot(BUILD);
+        let references = snap
+            .references(pos(BUILD, "\"mode\""), true)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            references,
+            vec![
+                FileRange {
+                    file_id: FileId(0),
+                    range: range(BUILD, "\"mode\""),
+                },
+                FileRange {
+                    file_id: FileId(0),
+                    range: range(BUILD, ":mode"),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_references_from_label() {
+        let snap = snapshot(BUILD);
+        let references = snap
+            .references(pos(BUILD, "\":jobs\""), false)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            references,
+            vec![FileRange {
+                file_id: FileId(0),
+                range: range(BUILD, ":jobs"),
+            }]
+        );
+    }
+}
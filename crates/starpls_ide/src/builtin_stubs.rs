@@ -0,0 +1,270 @@
+//! Renders builtins as Starlark stubs, so that goto definition has somewhere to go for names like
+//! `depset` or `ctx.actions.run` that aren't defined in any source file.
+//!
+//! Each stub lists the signature and documentation of every builtin in a set, either the globals
+//! available to a file or the fields of a builtin type. The parameters in a stub's `def`s are
+//! left untyped and their defaults are replaced with `None`, so that the stub itself parses; the
+//! full signature is included in the docstring instead.
+
+use std::fmt::Write;
+
+use starpls_common::File;
+use starpls_hir::{DisplayWithDb, Name, ScopeDef, Semantics, Type};
+
+use crate::{util::unindent_doc, Database};
+
+const HEADER: &str =
+    "# Generated by starpls from the builtin definitions. This file is read-only.\n";
+
+/// A generated source file describing a set of builtins.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BuiltinStub {
+    /// A file name for the stub, unique to the set of builtins that it describes.
+    pub name: String,
+    pub contents: String,
+}
+
+/// Renders the builtin functions available to the given file, returning the stub along with the
+/// zero-based line that the function with the given name is defined on.
+pub(crate) fn global_stub(db: &Database, file: File, name: &Name) -> Option<(BuiltinStub, u32)> {
+    let sema = Semantics::new(db);
+    let mut callables = sema
+        .scope_for_module(file)
+        .names()
+        .filter_map(|(name, def)| match def {
+            ScopeDef::Callable(callable) if !callable.is_user_defined() => Some((name, callable)),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    callables.sort_by(|(a, _), (b, _)| a.as_str().cmp(b.as_str()));
+
+    let mut contents = String::from(HEADER);
+    let mut target_line = None;
+    for (callable_name, callable) in callables {
+        contents.push('\n');
+        if &callable_name == name {
+            target_line = Some(line_count(&contents));
+        }
+        render_function(
+            db,
+            &mut contents,
+            callable_name.as_str(),
+            &callable.ty(db),
+            callable.doc(db).unwrap_or_default(),
+        );
+    }
+
+    let context = match file.api_context(db) {
+        Some(api_context) => format!("{:?}", api_context).to_lowercase(),
+        None => "standard".to_string(),
+    };
+    Some((
+        BuiltinStub {
+            name: format!("builtins_{}.bzl", context),
+            contents,
+        },
+        target_line?,
+    ))
+}
+
+/// Renders the builtin fields and methods of the given type, returning the stub along with the
+/// zero-based line that the field with the given name is defined on. If no field name is given,
+/// the stub is opened at its first line.
+pub(crate) fn type_stub(
+    db: &Database,
+    ty: &Type,
+    field_name: Option<&str>,
+) -> Option<(BuiltinStub, u32)> {
+    let fields = ty
+        .fields(db)
+        .into_iter()
+        .filter(|(field, _)| field.is_builtin())
+        .collect::<Vec<_>>();
+    if fields.is_empty() {
+        return None;
+    }
+
+    let type_name = ty.without_literal().display(db).to_string();
+    let mut contents = String::from(HEADER);
+    if let Some(doc) = ty.doc(db).filter(|doc| !doc.is_empty()) {
+        contents.push('\n');
+        render_doc(&mut contents, "", &format!("{}\n\n{}", type_name, doc));
+    }
+
+    let mut target_line = field_name.is_none().then_some(0);
+    for (field, field_ty) in fields {
+        let name = field.name(db);
+        contents.push('\n');
+        if Some(name.as_str()) == field_name {
+            target_line = Some(line_count(&contents));
+        }
+        if field_ty.is_function() {
+            render_function(db, &mut contents, name.as_str(), &field_ty, field.doc(db));
+        } else {
+            writeln!(
+                contents,
+                "{} = None  # type: {}",
+                name.as_str(),
+                field_ty.display(db)
+            )
+            .unwrap();
+            let doc = field.doc(db);
+            if !doc.is_empty() {
+                render_doc(&mut contents, "", &doc);
+            }
+        }
+    }
+
+    let file_name = type_name
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>();
+    let file_name = file_name.trim_matches('_');
+    Some((
+        BuiltinStub {
+            name: format!("{}.bzl", file_name),
+            contents,
+        },
+        target_line?,
+    ))
+}
+
+fn render_function(db: &Database, contents: &mut String, name: &str, ty: &Type, doc: String) {
+    let params = ty
+        .params(db)
+        .into_iter()
+        .enumerate()
+        .map(|(index, (param, _))| {
+            let name = param
+                .name(db)
+                .map(|name| name.as_str().trim_start_matches('*').to_string())
+                .unwrap_or_else(|| format!("_{}", index));
+            if param.is_args_list(db) {
+                format!("*{}", name)
+            } else if param.is_kwargs_dict(db) {
+                format!("**{}", name)
+            } else if param.is_mandatory(db) {
+                name
+            } else {
+                format!("{} = None", name)
+            }
+        })
+        .collect::<Vec<_>>();
+    writeln!(contents, "def {}({}):", name, params.join(", ")).unwrap();
+
+    let signature = ty.display(db).to_string();
+    let doc = if doc.is_empty() {
+        signature
+    } else {
+        format!("{}\n\n{}", signature, doc)
+    };
+    render_doc(contents, "    ", &doc);
+    writeln!(contents, "    pass").unwrap();
+}
+
+fn render_doc(contents: &mut String, indent: &str, doc: &str) {
+    writeln!(contents, "{}\"\"\"", indent).unwrap();
+    for line in unindent_doc(doc).lines() {
+        if line.is_empty() {
+            contents.push('\n');
+        } else {
+            // Escape anything that would end the docstring early.
+            let line = line.replace('\\', "\\\\").replace("\"\"\"", "\\\"\\\"\\\"");
+            writeln!(contents, "{}{}", indent, line).unwrap();
+        }
+    }
+    writeln!(contents, "{}\"\"\"", indent).unwrap();
+}
+
+fn line_count(text: &str) -> u32 {
+    text.matches('\n').count() as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use starpls_bazel::APIContext;
+    use starpls_common::Dialect;
+    use starpls_test_util::parse_fixture;
+
+    use crate::{AnalysisSnapshot, FilePosition, LocationLink};
+
+    fn check_goto_builtin(fixture: &str, expected_name: &str, expected_line: &str) {
+        let (contents, pos, _) = parse_fixture(fixture);
+        let (snap, file_id) =
+            AnalysisSnapshot::from_single_file(&contents, Dialect::Bazel, Some(APIContext::Bzl));
+        let links = snap
+            .goto_definition(FilePosition { file_id, pos })
+            .unwrap()
+            .unwrap();
+        match &links[..] {
+            [LocationLink::Builtin {
+                stub, target_line, ..
+            }] => {
+                assert_eq!(stub.name, expected_name);
+                assert_eq!(
+                    stub.contents.lines().nth(*target_line as usize),
+                    Some(expected_line)
+                );
+            }
+            _ => panic!("expected a single builtin location, got {:?}", links),
+        }
+    }
+
+    #[test]
+    fn test_builtin_function() {
+        check_goto_builtin(
+            r#"
+s = st$0ruct(a = 1)
+"#,
+            "builtins_bzl.bzl",
+            "def struct(*args, **kwargs):",
+        );
+    }
+
+    #[test]
+    fn test_intrinsic_function() {
+        check_goto_builtin(
+            r#"
+n = le$0n([1, 2])
+"#,
+            "builtins_bzl.bzl",
+            "def len(x0):",
+        );
+    }
+
+    #[test]
+    fn test_intrinsic_method() {
+        check_goto_builtin(
+            r#"
+"abc".starts$0with("a")
+"#,
+            "string.bzl",
+            "def startswith(x0, x1 = None, x2 = None):",
+        );
+    }
+
+    #[test]
+    fn test_user_defined_shadows_builtin() {
+        let (contents, pos, _) = parse_fixture(
+            r#"
+def len(x):
+    pass
+
+le$0n([])
+"#,
+        );
+        let (snap, file_id) =
+            AnalysisSnapshot::from_single_file(&contents, Dialect::Bazel, Some(APIContext::Bzl));
+        let links = snap
+            .goto_definition(FilePosition { file_id, pos })
+            .unwrap()
+            .unwrap();
+        assert!(matches!(&links[..], [LocationLink::Local { .. }]));
+    }
+}
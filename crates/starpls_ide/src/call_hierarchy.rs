@@ -0,0 +1,439 @@
+//! Builds the call hierarchy of Starlark functions, i.e. which functions call a given function
+//! and which functions it calls in turn, following calls across `load` statements.
+//!
+//! Calls are resolved the same way as references: a call's callee must be a name that resolves to
+//! a `def` statement, or to a rule, macro, or aspect assigned to a global. Calls to builtins and to
+//! values that aren't known statically, e.g. parameters or struct fields, are left out. Calls made
+//! at the top level of a file, e.g. macro invocations in `BUILD` files, are reported as calls from
+//! the file itself.
+
+use starpls_common::{parse, Db as _, File, FileId, FileRange};
+use starpls_hir::Semantics;
+use starpls_syntax::{
+    ast::{self, AstNode},
+    SyntaxNode, TextRange, TextSize,
+};
+
+use crate::{
+    document_symbols::SymbolKind,
+    module_deps::callee_name,
+    references::{find_references, resolve_symbol, symbol_at, Symbol},
+    Database, FilePosition,
+};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CallHierarchyItem {
+    /// The function's name. Items for the top level of a file have an empty name, and their
+    /// selection range is empty.
+    pub name: String,
+    pub kind: SymbolKind,
+    /// The function declaring a rule or the like, e.g. `rule` or `macro`.
+    pub detail: Option<String>,
+    pub file_id: FileId,
+    /// The range of the whole definition, e.g. a `def` statement.
+    pub range: TextRange,
+    /// The range of the definition's name.
+    pub selection_range: TextRange,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IncomingCall {
+    pub from: CallHierarchyItem,
+    /// The ranges of the callee's name at each call, within the file of `from`.
+    pub from_ranges: Vec<TextRange>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OutgoingCall {
+    pub to: CallHierarchyItem,
+    /// The ranges of the callee's name at each call, within the caller's file.
+    pub from_ranges: Vec<TextRange>,
+}
+
+/// Returns the item for the function at the given position, either at its definition or at a
+/// reference to it.
+pub(crate) fn prepare_call_hierarchy(
+    db: &Database,
+    pos: FilePosition,
+) -> Option<Vec<CallHierarchyItem>> {
+    let sema = Semantics::new(db);
+    let item = item_for_symbol(db, &symbol_at(db, &sema, pos)?)?;
+    Some(vec![item])
+}
+
+/// Returns the calls to the item with the given selection range, grouped by their callers.
+pub(crate) fn incoming_calls(db: &Database, item: FileRange) -> Option<Vec<IncomingCall>> {
+    let mut calls = Vec::new();
+    find_incoming_calls(db, item, &mut |batch| calls.extend(batch))?;
+    calls.sort_by_key(|call| (call.from.file_id, call.from.selection_range.start()));
+    Some(calls)
+}
+
+/// Finds the calls like [`incoming_calls`], but passes them to `sink` in batches as they're
+/// found, one batch for each file containing calls. Calls are searched for the same way as
+/// references, so each caller's calls are all in a single batch.
+pub(crate) fn find_incoming_calls(
+    db: &Database,
+    item: FileRange,
+    sink: &mut dyn FnMut(Vec<IncomingCall>),
+) -> Option<()> {
+    if item.range.is_empty() {
+        return Some(());
+    }
+    find_references(
+        db,
+        FilePosition {
+            file_id: item.file_id,
+            pos: item.range.start(),
+        },
+        false,
+        None,
+        &mut |references| {
+            let mut calls = calls_from_references(db, references);
+            if !calls.is_empty() {
+                calls.sort_by_key(|call| (call.from.file_id, call.from.selection_range.start()));
+                sink(calls);
+            }
+        },
+    )?;
+    Some(())
+}
+
+/// Groups the references that are calls by the items that they're made from.
+fn calls_from_references(db: &Database, references: Vec<FileRange>) -> Vec<IncomingCall> {
+    let mut calls: Vec<IncomingCall> = Vec::new();
+    for reference in references {
+        let file = match db.get_file(reference.file_id) {
+            Some(file) => file,
+            None => continue,
+        };
+        let root = parse(db, file).syntax(db);
+        let callee = match callee_at(&root, reference.range) {
+            Some(callee) => callee,
+            None => continue,
+        };
+        let from = callee
+            .syntax()
+            .ancestors()
+            .find_map(ast::DefStmt::cast)
+            .and_then(|def_stmt| def_item(db, file, &def_stmt))
+            .unwrap_or_else(|| module_item(db, file, &root));
+        match calls
+            .iter_mut()
+            .find(|call| is_same_item(&call.from, &from))
+        {
+            Some(call) => call.from_ranges.push(reference.range),
+            None => calls.push(IncomingCall {
+                from,
+                from_ranges: vec![reference.range],
+            }),
+        }
+    }
+    calls
+}
+
+/// Returns the calls made by the item with the given selection range, grouped by their callees.
+pub(crate) fn outgoing_calls(db: &Database, item: FileRange) -> Option<Vec<OutgoingCall>> {
+    let file = db.get_file(item.file_id)?;
+    let root = parse(db, file).syntax(db);
+    let caller = if item.range.is_empty() {
+        root.clone()
+    } else {
+        match root
+            .descendants()
+            .filter_map(ast::DefStmt::cast)
+            .find(|def_stmt| {
+                def_stmt.name().map(|name| name.syntax().text_range()) == Some(item.range)
+            }) {
+            Some(def_stmt) => def_stmt.syntax().clone(),
+            // Rules and the like don't call anything themselves.
+            None => return Some(Vec::new()),
+        }
+    };
+
+    let sema = Semantics::new(db);
+    let mut callees = Vec::new();
+    collect_callees(&caller, &mut callees);
+    let mut calls: Vec<OutgoingCall> = Vec::new();
+    for name_ref in callees {
+        let token = match name_ref.name() {
+            Some(token) => token,
+            None => continue,
+        };
+        let to = match resolve_symbol(db, &sema, file, &token)
+            .and_then(|symbol| item_for_symbol(db, &symbol))
+        {
+            Some(to) => to,
+            None => continue,
+        };
+        let range = name_ref.syntax().text_range();
+        match calls.iter_mut().find(|call| is_same_item(&call.to, &to)) {
+            Some(call) => call.from_ranges.push(range),
+            None => calls.push(OutgoingCall {
+                to,
+                from_ranges: vec![range],
+            }),
+        }
+    }
+    Some(calls)
+}
+
+/// Collects the names called within the given node, not including those called by the functions
+/// nested in it.
+fn collect_callees(node: &SyntaxNode, callees: &mut Vec<ast::NameRef>) {
+    for child in node.children() {
+        if ast::DefStmt::can_cast(child.kind()) {
+            continue;
+        }
+        if let Some(ast::Expression::Name(name_ref)) =
+            ast::CallExpr::cast(child.clone()).and_then(|call_expr| call_expr.callee())
+        {
+            callees.push(name_ref);
+        }
+        collect_callees(&child, callees);
+    }
+}
+
+/// Returns the name at the given range, if it's the callee of a call.
+fn callee_at(root: &SyntaxNode, range: TextRange) -> Option<ast::NameRef> {
+    let name_ref = ast::NameRef::cast(
+        root.token_at_offset(range.start())
+            .right_biased()?
+            .parent()?,
+    )?;
+    let call_expr = ast::CallExpr::cast(name_ref.syntax().parent()?)?;
+    (name_ref.syntax().text_range() == range && call_expr.callee()?.syntax() == name_ref.syntax())
+        .then_some(name_ref)
+}
+
+fn item_for_symbol(db: &Database, symbol: &Symbol) -> Option<CallHierarchyItem> {
+    let root = parse(db, symbol.file).syntax(db);
+    symbol.defs.iter().find_map(|ptr| {
+        let node = ptr.to_node(&root);
+        if let Some(def_stmt) = ast::DefStmt::cast(node.clone()) {
+            return def_item(db, symbol.file, &def_stmt);
+        }
+        let assign_stmt = ast::AssignStmt::cast(node.parent()?)?;
+        let declarer = match assign_stmt.rhs()? {
+            ast::Expression::Call(call_expr) => callee_name(&call_expr)?,
+            _ => return None,
+        };
+        if !matches!(
+            declarer.as_str(),
+            "aspect" | "macro" | "repository_rule" | "rule"
+        ) {
+            return None;
+        }
+        Some(CallHierarchyItem {
+            name: symbol.name.to_string(),
+            kind: SymbolKind::Class,
+            detail: Some(declarer),
+            file_id: symbol.file.id(db),
+            range: assign_stmt.syntax().text_range(),
+            selection_range: node.text_range(),
+        })
+    })
+}
+
+fn def_item(db: &Database, file: File, def_stmt: &ast::DefStmt) -> Option<CallHierarchyItem> {
+    let name = def_stmt.name()?;
+    Some(CallHierarchyItem {
+        name: name.name()?.text().to_string(),
+        kind: SymbolKind::Function,
+        detail: None,
+        file_id: file.id(db),
+        range: def_stmt.syntax().text_range(),
+        selection_range: name.syntax().text_range(),
+    })
+}
+
+fn module_item(db: &Database, file: File, root: &SyntaxNode) -> CallHierarchyItem {
+    CallHierarchyItem {
+        name: String::new(),
+        kind: SymbolKind::File,
+        detail: None,
+        file_id: file.id(db),
+        range: root.text_range(),
+        selection_range: TextRange::empty(TextSize::from(0)),
+    }
+}
+
+fn is_same_item(item1: &CallHierarchyItem, item2: &CallHierarchyItem) -> bool {
+    item1.file_id == item2.file_id && item1.selection_range == item2.selection_range
+}
+
+#[cfg(test)]
+mod tests {
+    use starpls_common::{Dialect, FileId, FileRange};
+    use starpls_syntax::{TextRange, TextSize};
+
+    use crate::{Analysis, AnalysisSnapshot, FilePosition, SymbolKind};
+
+    const DEFS: &str = r#"def _impl(ctx):
+    pass
+
+my_rule = rule(implementation = _impl)
+
+def my_macro(name):
+    my_rule(name = name)
+    helper()
+
+def helper():
+    pass
+"#;
+
+    const USER: &str = r#"load("defs.star", "my_macro", "my_rule")
+
+def wrapper(name):
+    my_macro(name = name)
+    my_rule(name = name + "_rule")
+    len(name)
+
+my_macro(name = "top")
+"#;
+
+    const FILES: [(&str, &str); 2] = [("defs.star", DEFS), ("user.star", USER)];
+
+    fn snapshot() -> AnalysisSnapshot {
+        AnalysisSnapshot::from_files(&FILES, Dialect::Standard)
+    }
+
+    fn name_range(file_id: u32, needle: &str) -> FileRange {
+        let text = FILES[file_id as usize].1;
+        let start = text.find(needle).unwrap() as u32;
+        let end = start
+            + needle
+                .find(|c: char| !c.is_alphanumeric() && c != '_')
+                .unwrap() as u32;
+        FileRange {
+            file_id: FileId(file_id),
+            range: TextRange::new(TextSize::from(start), TextSize::from(end)),
+        }
+    }
+
+    #[test]
+    fn test_prepare() {
+        let snap = snapshot();
+        let pos = USER.rfind("my_macro").unwrap() as u32;
+        let items = snap
+            .prepare_call_hierarchy(FilePosition {
+                file_id: FileId(1),
+                pos: TextSize::from(pos),
+            })
+            .unwrap()
+            .unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].name, "my_macro");
+        assert_eq!(items[0].kind, SymbolKind::Function);
+        assert_eq!(items[0].file_id, FileId(0));
+        assert_eq!(
+            items[0].selection_range,
+            name_range(0, "my_macro(name)").range
+        );
+
+        let pos = DEFS.find("my_rule =").unwrap() as u32;
+        let items = snap
+            .prepare_call_hierarchy(FilePosition {
+                file_id: FileId(0),
+                pos: TextSize::from(pos),
+            })
+            .unwrap()
+            .unwrap();
+        assert_eq!(items[0].kind, SymbolKind::Class);
+        assert_eq!(items[0].detail.as_deref(), Some("rule"));
+    }
+
+    #[test]
+    fn test_incoming_calls() {
+        let calls = snapshot()
+            .incoming_calls(name_range(0, "my_rule ="))
+            .unwrap()
+            .unwrap()
+            .into_iter()
+            .map(|call| {
+                let text = FILES[call.from.file_id.0 as usize].1;
+                (
+                    call.from.name,
+                    call.from_ranges
+                        .into_iter()
+                        .map(|range| &text[range])
+                        .collect::<Vec<_>>(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            calls,
+            vec![
+                ("my_macro".to_string(), vec!["my_rule"]),
+                ("wrapper".to_string(), vec!["my_rule"]),
+            ]
+        );
+
+        let calls = snapshot()
+            .incoming_calls(name_range(0, "my_macro(name)"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(calls.len(), 2);
+        assert_eq!(calls[0].from.name, "");
+        assert_eq!(calls[0].from.kind, SymbolKind::File);
+        assert_eq!(calls[0].from.file_id, FileId(1));
+        assert_eq!(calls[1].from.name, "wrapper");
+    }
+
+    #[test]
+    fn test_incoming_calls_from_closed_file() {
+        // `user.star` isn't open and isn't loaded by `defs.star`, so its calls are only found
+        // through the loader's index of the workspace.
+        let snap = Analysis::from_workspace(&FILES[..1], &FILES[1..], Dialect::Standard).snapshot();
+        let calls = snap
+            .incoming_calls(name_range(0, "my_macro(name)"))
+            .unwrap()
+            .unwrap()
+            .into_iter()
+            .map(|call| (call.from.name, call.from.file_id, call.from_ranges.len()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            calls,
+            vec![
+                ("".to_string(), FileId(1), 1),
+                ("wrapper".to_string(), FileId(1), 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_outgoing_calls() {
+        let calls = snapshot()
+            .outgoing_calls(name_range(1, "wrapper"))
+            .unwrap()
+            .unwrap()
+            .into_iter()
+            .map(|call| (call.to.name, call.to.file_id, call.from_ranges.len()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            calls,
+            vec![
+                ("my_macro".to_string(), FileId(0), 1),
+                ("my_rule".to_string(), FileId(0), 1),
+            ]
+        );
+
+        let calls = snapshot()
+            .outgoing_calls(name_range(0, "my_macro(name)"))
+            .unwrap()
+            .unwrap()
+            .into_iter()
+            .map(|call| call.to.name)
+            .collect::<Vec<_>>();
+        assert_eq!(calls, vec!["my_rule", "helper"]);
+
+        // Rules don't call anything.
+        assert_eq!(
+            snapshot()
+                .outgoing_calls(name_range(0, "my_rule ="))
+                .unwrap()
+                .unwrap(),
+            Vec::new()
+        );
+    }
+}
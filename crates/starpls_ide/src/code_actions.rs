@@ -0,0 +1,242 @@
+//! Partially replicates the "code actions" API in the LSP specification.
+
+use starpls_bazel::APIContext;
+use starpls_common::{Db as _, File, FileId, FileRange};
+use starpls_syntax::{TextRange, TextSize};
+
+use crate::{
+    duplicates, extract_macro, header, label_style, legacy_providers, load_aliases, module_deps,
+    native_prefix, organize_loads, platform_targets, sort_lists, spellcheck, type_annotations,
+    unused, Database, TextEdit,
+};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CodeActionKind {
+    QuickFix,
+    RefactorExtract,
+    RefactorRewrite,
+    /// Generates code that isn't tied to the selected range, e.g. new test files.
+    Source,
+    /// Applies every fix in a file that is safe to apply without user input, e.g. on save.
+    SourceFixAll,
+    /// Sorts and merges `load` statements.
+    SourceOrganizeImports,
+}
+
+/// A command to be run by the language server when a code action is selected.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CodeActionCommand {
+    /// Adds a word to the workspace's spell-checking dictionary.
+    AddWordToDictionary(String),
+    /// Updates every `bazel_dep` in the current `MODULE.bazel` file to its latest version.
+    UpdateAllDependencies,
+}
+
+/// The file that a [`FileEdit`] applies to.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EditTarget {
+    File(FileId),
+    /// A new file with the given name, in the same directory as the file that the code action
+    /// was requested for.
+    NewFile(String),
+}
+
+/// Edits to a file other than the one that the code action was requested for.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FileEdit {
+    pub target: EditTarget,
+    pub edits: Vec<TextEdit>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CodeAction {
+    pub title: String,
+    pub kind: CodeActionKind,
+    /// Edits to the file that the code action was requested for.
+    pub edits: Vec<TextEdit>,
+    pub file_edits: Vec<FileEdit>,
+    pub command: Option<CodeActionCommand>,
+    pub is_preferred: bool,
+}
+
+pub(crate) fn code_actions(db: &Database, range: FileRange) -> Option<Vec<CodeAction>> {
+    let file = db.get_file(range.file_id)?;
+    let mut actions = Vec::new();
+    actions.extend(header::insert_header_action(db, file, range.range));
+    if file.api_context(db) == Some(APIContext::Module) {
+        actions.extend(module_deps::update_actions(db, file, range.range));
+    }
+    actions.extend(native_prefix::native_prefix_actions(db, file, range.range));
+    actions.extend(duplicates::duplicate_actions(db, file, range.range));
+    actions.extend(label_style::label_style_actions(db, file, range.range));
+    actions.extend(sort_lists::sort_list_actions(db, file, range.range));
+    actions.extend(unused::unused_actions(db, file, range.range));
+    actions.extend(load_aliases::load_alias_actions(db, file, range.range));
+    actions.extend(organize_loads::organize_loads_actions(
+        db,
+        file,
+        range.range,
+    ));
+    actions.extend(platform_targets::platform_target_actions(
+        db,
+        file,
+        range.range,
+    ));
+    actions.extend(extract_macro::extract_macro_actions(db, file, range.range));
+    actions.extend(legacy_providers::legacy_provider_actions(
+        db,
+        file,
+        range.range,
+    ));
+    actions.extend(type_annotations::type_annotation_actions(
+        db,
+        file,
+        range.range,
+    ));
+    #[cfg(feature = "lint-plugins")]
+    actions.extend(crate::lint_rules::lint_actions(db, file, range.range));
+
+    if db.diagnostics_options.enable_spellcheck {
+        for misspelling in spellcheck::misspellings(db, file)
+            .into_iter()
+            .filter(|misspelling| misspelling.range.intersect(range.range).is_some())
+        {
+            for (i, suggestion) in spellcheck::suggestions(db, &misspelling.word)
+                .into_iter()
+                .enumerate()
+            {
+                actions.push(CodeAction {
+                    title: format!("Change to \"{}\"", suggestion),
+                    kind: CodeActionKind::QuickFix,
+                    edits: vec![TextEdit {
+                        range: misspelling.range,
+                        new_text: suggestion,
+                    }],
+                    file_edits: Vec::new(),
+                    command: None,
+                    is_preferred: i == 0,
+                });
+            }
+
+            actions.push(CodeAction {
+                title: format!("Add \"{}\" to dictionary", misspelling.word),
+                kind: CodeActionKind::QuickFix,
+                edits: Vec::new(),
+                file_edits: Vec::new(),
+                command: Some(CodeActionCommand::AddWordToDictionary(
+                    misspelling.word.to_lowercase(),
+                )),
+                is_preferred: false,
+            });
+        }
+    }
+
+    Some(actions)
+}
+
+/// Combines every safe fix in the file into a single action. Only fixes that have exactly one
+/// possible outcome are included, so that the action can be applied automatically. Unused local
+/// variables are left alone, since they're usually about to be used when the file is saved.
+pub(crate) fn fix_all_action(db: &Database, file: File) -> Option<CodeAction> {
+    let mut edits = Vec::new();
+
+    // Headers are inserted at the start of the file, so their edits go first in case another
+    // fix inserts text at the same offset.
+    let full_range = TextRange::up_to(TextSize::of(file.contents(db).as_str()));
+    if let Some(action) = header::insert_header_action(db, file, full_range) {
+        edits.extend(action.edits);
+    }
+    edits.extend(native_prefix::native_prefix_edits(db, file));
+    edits.extend(duplicates::duplicate_edits(db, file));
+
+    // Later fixes may touch the same text as earlier ones, e.g. a label may be part of a dict
+    // entry that's removed as a duplicate, so only apply the ones that don't overlap with the
+    // fixes before them. Removing unused loads and sorting lists take several edits each, which
+    // are kept or dropped together.
+    for group in unused::unused_load_edits(db, file)
+        .into_iter()
+        .chain(sort_lists::sort_list_edits(db, file))
+        .chain(
+            label_style::label_style_edits(db, file)
+                .into_iter()
+                .map(|edit| vec![edit]),
+        )
+    {
+        let overlaps = group.iter().any(|edit| {
+            edits
+                .iter()
+                .any(|other| other.range.intersect(edit.range).is_some())
+        });
+        if !overlaps {
+            edits.extend(group);
+        }
+    }
+
+    if edits.is_empty() {
+        return None;
+    }
+    Some(CodeAction {
+        title: "Fix all auto-fixable problems".to_string(),
+        kind: CodeActionKind::SourceFixAll,
+        edits,
+        file_edits: Vec::new(),
+        command: None,
+        is_preferred: false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use starpls_common::{Dialect, FileId, FileRange};
+    use starpls_syntax::{TextRange, TextSize};
+
+    use crate::{Analysis, CodeActionKind, TextEdit};
+
+    fn apply(text: &str, edits: &[TextEdit]) -> String {
+        let mut edits = edits.to_vec();
+        edits.sort_by_key(|edit| std::cmp::Reverse(edit.range.start()));
+        let mut text = text.to_string();
+        for edit in edits {
+            text.replace_range(std::ops::Range::<usize>::from(edit.range), &edit.new_text);
+        }
+        text
+    }
+
+    #[test]
+    fn test_fix_all() {
+        let contents = r#"load("//:defs.bzl", "my_macro", "unused_macro")
+
+my_macro(
+    name = "lib",
+    srcs = ["b.cc", "a.cc"],
+    deps = [":z", ":y"],
+)
+"#;
+        let snap = Analysis::from_files(&[("BUILD.bazel", contents)], Dialect::Bazel).snapshot();
+        let action = snap.fix_all_action(FileId(0)).unwrap().unwrap();
+        assert_eq!(action.kind, CodeActionKind::SourceFixAll);
+        assert_eq!(
+            apply(contents, &action.edits),
+            r#"load("//:defs.bzl", "my_macro")
+
+my_macro(
+    name = "lib",
+    srcs = ["a.cc", "b.cc"],
+    deps = [":y", ":z"],
+)
+"#
+        );
+
+        // The combined action is only computed when it's asked for.
+        let actions = snap
+            .code_actions(FileRange {
+                file_id: FileId(0),
+                range: TextRange::up_to(TextSize::of(contents)),
+            })
+            .unwrap()
+            .unwrap();
+        assert!(actions
+            .iter()
+            .all(|action| action.kind != CodeActionKind::SourceFixAll));
+    }
+}
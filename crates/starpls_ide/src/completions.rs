@@ -1,16 +1,19 @@
 //! Partially replicates the "completions" API in the LSP specification.
 
 use rustc_hash::FxHashMap;
-use starpls_common::{parse, FileId, LoadItemCandidateKind};
-use starpls_hir::{Db, Name, Param, ScopeDef, Semantics, Type};
+use starpls_common::{parse, Db as _, File, FileId, LoadItemCandidateKind};
+use starpls_hir::{Db, Name, Param, ScopeDef, Semantics, StringKind, Type};
 use starpls_syntax::{
     ast::{self, AstNode, AstToken},
-    parse_module,
+    edit, parse_module,
     SyntaxKind::*,
     SyntaxNode, TextRange, TextSize,
 };
 
-use crate::FilePosition;
+use crate::{
+    attr_values::{self, AllowedValue},
+    labels, Database, FilePosition,
+};
 
 const COMPLETION_MARKER: &'static str = "__STARPLS_COMPLETION_MARKER";
 
@@ -23,6 +26,8 @@ pub struct CompletionItem {
     pub label: String,
     pub kind: CompletionItemKind,
     pub mode: Option<CompletionMode>,
+    /// Shown next to the label, e.g. the type of a parameter.
+    pub detail: Option<String>,
     relevance: CompletionRelevance,
 }
 
@@ -32,12 +37,21 @@ impl CompletionItem {
     }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct TextEdit {
     pub range: TextRange,
     pub new_text: String,
 }
 
+impl From<edit::Edit> for TextEdit {
+    fn from(edit: edit::Edit) -> Self {
+        Self {
+            range: edit.range,
+            new_text: edit.new_text,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum CompletionMode {
     InsertText(String),
@@ -79,7 +93,9 @@ enum NameContext {
 
 struct NameRefContext {
     names: FxHashMap<Name, ScopeDef>,
-    params: Vec<Param>,
+    params: Vec<(Param, Type)>,
+    /// The values accepted by the keyword argument being completed, if restricted to a fixed set.
+    allowed_values: Vec<AllowedValue>,
     is_in_def: bool,
     is_in_for: bool,
     is_lone_expr: bool,
@@ -99,6 +115,18 @@ enum StringContext {
         file_id: FileId,
         lhs: ast::Expression,
     },
+    ArgumentValue {
+        file_id: FileId,
+        call: ast::CallExpr,
+        name: String,
+        text: ast::String,
+    },
+    /// A label, e.g. `"//foo:bar"`, or a string that's used as one, e.g. one of the `srcs` of a
+    /// target.
+    Label {
+        file_id: FileId,
+        text: ast::String,
+    },
 }
 
 struct CompletionContext {
@@ -106,7 +134,7 @@ struct CompletionContext {
 }
 
 pub(crate) fn completions(
-    db: &dyn Db,
+    db: &Database,
     pos: FilePosition,
     trigger_character: Option<String>,
 ) -> Option<Vec<CompletionItem>> {
@@ -117,25 +145,38 @@ pub(crate) fn completions(
         CompletionAnalysis::NameRef(NameRefContext {
             names,
             params,
+            allowed_values,
             is_lone_expr,
             is_in_def,
             is_in_for,
             is_loop_variable,
         }) => {
-            // Add completions for parameter names (excluding arg list and kwarg dict parameters).
-            for name in params
+            // Add completions for parameter names (excluding arg list and kwarg dict parameters),
+            // which for rules are their attributes, including common ones like `visibility`.
+            for (name, ty) in params
                 .iter()
-                .filter(|param| {
+                .filter(|(param, _)| {
                     !param.is_args_list(db)
                         && !param.is_kwargs_dict(db)
                         && !param.is_positional_only(db)
                 })
-                .filter_map(|param| param.name(db))
+                .filter_map(|(param, ty)| Some((param.name(db)?, ty)))
             {
                 items.push(CompletionItem {
                     label: format!("{}=", name.as_str()),
                     kind: CompletionItemKind::Variable,
                     mode: Some(CompletionMode::InsertText(format!("{} = ", name.as_str()))),
+                    detail: Some(ty.display(db).to_string()),
+                    relevance: CompletionRelevance::Parameter,
+                });
+            }
+
+            for value in allowed_values {
+                items.push(CompletionItem {
+                    label: value.to_literal(),
+                    kind: CompletionItemKind::Constant,
+                    mode: None,
+                    detail: None,
                     relevance: CompletionRelevance::Parameter,
                 });
             }
@@ -155,6 +196,7 @@ pub(crate) fn completions(
                             _ => CompletionItemKind::Variable,
                         },
                         mode: None,
+                        detail: None,
                         relevance: if decl.is_user_defined() {
                             CompletionRelevance::VariableOrKeyword
                         } else {
@@ -178,6 +220,7 @@ pub(crate) fn completions(
                         CompletionItemKind::Field
                     },
                     mode: None,
+                    detail: None,
                     relevance: CompletionRelevance::VariableOrKeyword,
                 })
             }
@@ -188,6 +231,7 @@ pub(crate) fn completions(
                     label: name.to_string(),
                     kind: CompletionItemKind::Class,
                     mode: None,
+                    detail: None,
                     relevance: CompletionRelevance::VariableOrKeyword,
                 })
             }
@@ -212,6 +256,7 @@ pub(crate) fn completions(
                         ),
                         new_text: candidate.path,
                     })),
+                    detail: None,
                     relevance: CompletionRelevance::VariableOrKeyword,
                 });
             }
@@ -241,6 +286,7 @@ pub(crate) fn completions(
                         _ => continue,
                     },
                     mode: None,
+                    detail: None,
                     relevance: CompletionRelevance::VariableOrKeyword,
                 });
             }
@@ -255,6 +301,80 @@ pub(crate) fn completions(
                     label: key,
                     kind: CompletionItemKind::Constant,
                     mode: None,
+                    detail: None,
+                    relevance: CompletionRelevance::VariableOrKeyword,
+                });
+            }
+        }
+        CompletionAnalysis::String(StringContext::ArgumentValue {
+            file_id,
+            call,
+            name,
+            text,
+        }) => {
+            let file = db.get_file(file_id)?;
+            let (value, offset) = text.value_and_offset()?;
+            let start = text.syntax().text_range().start() + TextSize::from(offset);
+            let range = TextRange::at(start, TextSize::from(value.len() as u32));
+            for value in attr_values::allowed_values(db, file, &call, &name)? {
+                if let AllowedValue::String(value) = value {
+                    items.push(CompletionItem {
+                        label: value.clone(),
+                        kind: CompletionItemKind::Constant,
+                        mode: Some(CompletionMode::TextEdit(TextEdit {
+                            range,
+                            new_text: value,
+                        })),
+                        detail: None,
+                        relevance: CompletionRelevance::Parameter,
+                    });
+                }
+            }
+        }
+        CompletionAnalysis::String(StringContext::Label { file_id, text }) => {
+            let (value, offset) = text.value_and_offset()?;
+            let start = text.syntax().text_range().start() + TextSize::from(offset);
+            let range = TextRange::at(start, TextSize::from(value.len() as u32));
+            let candidates = db.list_label_candidates(&value, file_id).ok()??;
+
+            // Packages and files are completed one segment at a time, like `load` paths.
+            let segment_offset = value.rfind(&['/', ':']).map_or(0, |start| start + 1);
+            let segment_start = start + TextSize::from(segment_offset as u32);
+            for candidate in candidates.candidates {
+                items.push(CompletionItem {
+                    label: candidate.path.clone(),
+                    kind: match candidate.kind {
+                        LoadItemCandidateKind::Directory => CompletionItemKind::Folder,
+                        LoadItemCandidateKind::File => CompletionItemKind::File,
+                    },
+                    mode: Some(CompletionMode::TextEdit(TextEdit {
+                        range: TextRange::new(segment_start, range.end()),
+                        new_text: candidate.path,
+                    })),
+                    detail: None,
+                    relevance: CompletionRelevance::VariableOrKeyword,
+                });
+            }
+
+            let build_file = match candidates.build_file.and_then(|id| db.get_file(id)) {
+                Some(build_file) => build_file,
+                None => return Some(items),
+            };
+            let package = value.rsplit_once(':').map_or("", |(package, _)| package);
+            for (name, target_range) in labels::target_names(db, build_file) {
+                // Targets can't depend on themselves.
+                if build_file.id(db) == file_id && target_range.contains_range(range) {
+                    continue;
+                }
+                let label = format!("{}:{}", package, name);
+                items.push(CompletionItem {
+                    label: label.clone(),
+                    kind: CompletionItemKind::Constant,
+                    mode: Some(CompletionMode::TextEdit(TextEdit {
+                        range,
+                        new_text: label,
+                    })),
+                    detail: None,
                     relevance: CompletionRelevance::VariableOrKeyword,
                 });
             }
@@ -271,6 +391,7 @@ pub(crate) fn add_globals(items: &mut Vec<CompletionItem>) {
             label: global.to_string(),
             kind: CompletionItemKind::Keyword,
             mode: None,
+            detail: None,
             relevance: CompletionRelevance::VariableOrKeyword,
         })
     };
@@ -285,6 +406,7 @@ fn add_keywords(items: &mut Vec<CompletionItem>, is_in_def: bool, is_in_for: boo
             label: keyword.to_string(),
             kind: CompletionItemKind::Keyword,
             mode: None,
+            detail: None,
             relevance: CompletionRelevance::VariableOrKeyword,
         })
     };
@@ -304,7 +426,13 @@ fn add_keywords(items: &mut Vec<CompletionItem>, is_in_def: bool, is_in_for: boo
     }
 }
 
-fn maybe_str_context(file_id: FileId, root: &SyntaxNode, pos: TextSize) -> Option<StringContext> {
+fn maybe_str_context(
+    db: &dyn Db,
+    file: File,
+    file_id: FileId,
+    root: &SyntaxNode,
+    pos: TextSize,
+) -> Option<StringContext> {
     let token = root.token_at_offset(pos).right_biased()?;
     let text = ast::String::cast(token.clone())?;
     let parent = token.parent()?;
@@ -315,19 +443,40 @@ fn maybe_str_context(file_id: FileId, root: &SyntaxNode, pos: TextSize) -> Optio
         let load_stmt = ast::LoadStmt::cast(parent.parent()?)?;
         return Some(StringContext::LoadItem { file_id, load_stmt });
     } else if let Some(expr) = ast::LiteralExpr::cast(parent) {
-        if let Some(index_expr) = ast::IndexExpr::cast(expr.syntax().parent()?) {
+        if matches!(
+            Semantics::new(db).classify_string(file, &text),
+            StringKind::Label | StringKind::FilePath | StringKind::Visibility
+        ) {
+            return Some(StringContext::Label { file_id, text });
+        }
+        let expr_parent = expr.syntax().parent()?;
+        if let Some(index_expr) = ast::IndexExpr::cast(expr_parent.clone()) {
             if index_expr.index() == Some(ast::Expression::Literal(expr)) {
                 return Some(StringContext::DictKey {
                     file_id,
                     lhs: index_expr.lhs()?,
                 });
             }
+        } else if let Some(arg) = ast::KeywordArgument::cast(expr_parent) {
+            return Some(StringContext::ArgumentValue {
+                file_id,
+                call: enclosing_call(arg.syntax())?,
+                name: arg.name()?.name()?.text().to_string(),
+                text,
+            });
         }
     }
 
     None
 }
 
+/// Returns the call whose arguments contain the given keyword argument.
+fn enclosing_call(arg: &SyntaxNode) -> Option<ast::CallExpr> {
+    ast::Arguments::cast(arg.parent()?)
+        .and_then(|args| args.syntax().parent())
+        .and_then(ast::CallExpr::cast)
+}
+
 impl CompletionContext {
     fn new(
         db: &dyn Db,
@@ -339,7 +488,7 @@ impl CompletionContext {
         let file = db.get_file(file_id)?;
         let parse = parse(db, file);
 
-        if let Some(cx) = maybe_str_context(file_id, &parse.syntax(db), pos) {
+        if let Some(cx) = maybe_str_context(db, file, file_id, &parse.syntax(db), pos) {
             return Some(CompletionContext {
                 analysis: CompletionAnalysis::String(cx),
             });
@@ -399,11 +548,11 @@ impl CompletionContext {
                 .map(|ty| {
                     ty.params(db)
                         .into_iter()
-                        .filter_map(|(param, _)| match param.name(db) {
+                        .filter_map(|(param, ty)| match param.name(db) {
                             Some(name)
                                 if keyword_args.iter().all(|kwarg| kwarg != name.as_str()) =>
                             {
-                                Some(param)
+                                Some((param, ty))
                             }
                             _ => None,
                         })
@@ -411,6 +560,20 @@ impl CompletionContext {
                 })
                 .unwrap_or_else(|| vec![]);
 
+            // The values of a keyword argument, e.g. `stamp = |`. The call is looked up in the
+            // original parse tree, since nodes from the modified one can't be resolved.
+            let allowed_values = name_ref
+                .syntax()
+                .parent()
+                .and_then(ast::KeywordArgument::cast)
+                .and_then(|arg| {
+                    let name = arg.name()?.name()?.text().to_string();
+                    let token = parse.syntax(db).token_at_offset(pos).left_biased()?;
+                    let call = token.parent_ancestors().find_map(ast::CallExpr::cast)?;
+                    attr_values::allowed_values(db, file, &call, &name)
+                })
+                .unwrap_or_default();
+
             let scope = sema.scope_for_offset(file, pos);
 
             let (is_in_def, is_in_for, is_loop_variable) =
@@ -432,6 +595,7 @@ impl CompletionContext {
             CompletionAnalysis::NameRef(NameRefContext {
                 names: scope.names().collect(),
                 params,
+                allowed_values,
                 is_in_def,
                 is_in_for,
                 is_lone_expr,
@@ -455,3 +619,104 @@ impl CompletionContext {
         Some(Self { analysis })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use rustc_hash::FxHashMap;
+    use starpls_bazel::{APIContext, Builtins};
+    use starpls_common::{Dialect, FileId};
+    use starpls_hir::Db as _;
+    use starpls_test_util::{make_test_builtins, FixtureType};
+
+    use crate::{Analysis, AnalysisSnapshot, Change, FilePosition, SimpleFileLoader};
+
+    #[test]
+    fn test_rule_attributes() {
+        let contents = r#"
+def _impl(ctx):
+    pass
+
+my_rule = rule(
+    implementation = _impl,
+    attrs = {
+        "count": attr.int(),
+    },
+)
+
+my_rule(name = "a", )
+"#;
+        let file_id = FileId(0);
+        let mut change = Change::default();
+        change.create_file(
+            file_id,
+            Dialect::Bazel,
+            Some(APIContext::Bzl),
+            contents.to_string(),
+        );
+        let mut analysis = Analysis::new(
+            Arc::new(SimpleFileLoader::from_file_set(FxHashMap::default())),
+            Default::default(),
+        );
+        analysis.db.set_builtin_defs(
+            Dialect::Bazel,
+            make_test_builtins(
+                vec!["rule".to_string()],
+                vec![("attr".to_string(), "attr".to_string())],
+                vec![FixtureType::new("attr", vec![], vec!["int"])],
+            ),
+            Builtins::default(),
+        );
+        analysis.apply_change(change);
+
+        let pos = contents.rfind(')').unwrap();
+        let items = analysis
+            .snapshot()
+            .completion(
+                FilePosition {
+                    file_id,
+                    pos: (pos as u32).into(),
+                },
+                None,
+            )
+            .unwrap()
+            .unwrap();
+        let detail = |label: &str| {
+            items
+                .iter()
+                .find(|item| item.label == label)
+                .map(|item| item.detail.as_deref())
+        };
+
+        // Attributes that are already set aren't offered again.
+        assert_eq!(detail("name="), None);
+        assert_eq!(detail("count="), Some(Some("int")));
+        assert_eq!(detail("testonly="), Some(Some("bool")));
+        assert!(detail("visibility=").is_some());
+    }
+
+    #[test]
+    fn test_buck2_globals() {
+        let contents = "config = re";
+        let (snap, file_id) =
+            AnalysisSnapshot::from_single_file(contents, Dialect::Buck2, Some(APIContext::Bzl));
+        let labels = snap
+            .completion(
+                FilePosition {
+                    file_id,
+                    pos: (contents.len() as u32).into(),
+                },
+                None,
+            )
+            .unwrap()
+            .unwrap()
+            .into_iter()
+            .map(|item| item.label)
+            .collect::<Vec<_>>();
+
+        // Buck2's globals replace Bazel's.
+        assert!(labels.iter().any(|label| label == "read_config"));
+        assert!(!labels.iter().any(|label| label == "repository_rule"));
+    }
+}
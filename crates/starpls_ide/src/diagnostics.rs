@@ -1,7 +1,121 @@
-use starpls_common::{Db, Diagnostic, FileId};
+use rustc_hash::FxHashMap;
+use starpls_common::{Db, Diagnostic, DiagnosticCode, FileId, Severity};
 use starpls_hir::diagnostics_for_file;
 
-use crate::Database;
+use crate::{
+    attr_values, duplicates, encoding, header, label_style, module_deps, native_prefix,
+    platform_targets, spellcheck, todos, unused, Database, LabelStyle,
+};
+
+/// Options for the opt-in diagnostics passes that run in addition to type checking.
+#[derive(Clone, Debug, Default)]
+pub struct DiagnosticsOptions {
+    /// Whether to report unknown words in docstrings, `doc` attributes, and comments.
+    pub enable_spellcheck: bool,
+    /// Additional words to accept when spell-checking, e.g. from a workspace word list.
+    pub spellcheck_words: Vec<String>,
+    /// Whether to report tagged comments, e.g. `# TODO: ...`, as hints.
+    pub enable_todos: bool,
+    /// The tags to look for in comments. If empty, [`DEFAULT_TODO_TAGS`](crate::DEFAULT_TODO_TAGS)
+    /// are used.
+    pub todo_tags: Vec<String>,
+    /// The header that `.bzl` files are required to begin with, if any.
+    pub required_header: Option<String>,
+    /// The style that labels in `BUILD` files are expected to be written in, if any.
+    pub label_style: Option<LabelStyle>,
+}
+
+/// The kind of check that produced a diagnostic. Severities can be overridden per category.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum DiagnosticCategory {
+    Syntax,
+    Type,
+    /// Type errors in the arguments of calls, e.g. missing or unknown arguments. These are split
+    /// from other type errors so that their severity can be tuned separately.
+    CallArguments,
+    Spelling,
+    Todo,
+    Header,
+    ModuleVersion,
+    Encoding,
+    NativePrefix,
+    Duplicate,
+    UnusedLoad,
+    UnusedVariable,
+    PlatformTargets,
+    LabelStyle,
+    /// Diagnostics reported by custom lint rules.
+    Lint,
+}
+
+impl DiagnosticCategory {
+    pub const ALL: &'static [DiagnosticCategory] = &[
+        Self::Syntax,
+        Self::Type,
+        Self::CallArguments,
+        Self::Spelling,
+        Self::Todo,
+        Self::Header,
+        Self::ModuleVersion,
+        Self::Encoding,
+        Self::NativePrefix,
+        Self::Duplicate,
+        Self::UnusedLoad,
+        Self::UnusedVariable,
+        Self::PlatformTargets,
+        Self::LabelStyle,
+        Self::Lint,
+    ];
+
+    /// The name of the category, as used in `starpls.toml` files.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Syntax => "syntax",
+            Self::Type => "type",
+            Self::CallArguments => "call_arguments",
+            Self::Spelling => "spelling",
+            Self::Todo => "todo",
+            Self::Header => "header",
+            Self::ModuleVersion => "module_version",
+            Self::Encoding => "encoding",
+            Self::NativePrefix => "native_prefix",
+            Self::Duplicate => "duplicate",
+            Self::UnusedLoad => "unused_load",
+            Self::UnusedVariable => "unused_variable",
+            Self::PlatformTargets => "platform_targets",
+            Self::LabelStyle => "label_style",
+            Self::Lint => "lint",
+        }
+    }
+
+    /// Whether the category only reports style issues, rather than problems that break the
+    /// build. Style issues aren't reported for files that can't be edited.
+    pub fn is_style(self) -> bool {
+        matches!(
+            self,
+            Self::Spelling
+                | Self::Todo
+                | Self::Header
+                | Self::NativePrefix
+                | Self::UnusedLoad
+                | Self::UnusedVariable
+                | Self::PlatformTargets
+                | Self::LabelStyle
+                | Self::Lint
+        )
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|category| category.name() == name)
+    }
+}
+
+/// Per-category severity overrides for a file. A value of `None` suppresses diagnostics of that
+/// category entirely.
+pub type SeverityOverrides = FxHashMap<DiagnosticCategory, Option<Severity>>;
 
 pub(crate) fn diagnostics(db: &Database, file_id: FileId) -> Vec<Diagnostic> {
     let file = match db.get_file(file_id) {
@@ -15,11 +129,99 @@ pub(crate) fn diagnostics(db: &Database, file_id: FileId) -> Vec<Diagnostic> {
         tcx.infer_all_load_items(file);
         tcx.diagnostics_for_file(file)
     });
+    let (call_argument_diagnostics, diagnostics) =
+        diagnostics.into_iter().partition(|diagnostic| {
+            matches!(
+                diagnostic.code,
+                DiagnosticCode::ArgumentTypeMismatch
+                    | DiagnosticCode::InvalidArgument
+                    | DiagnosticCode::MissingArgument
+            )
+        });
 
     // Limit the amount of syntax errors we send, as this many syntax errors probably means something
     // is really wrong with the file being analyzed.
-    diagnostics_for_file(db, file)
-        .take(128)
-        .chain(diagnostics.into_iter())
+    let mut categorized = vec![
+        (
+            DiagnosticCategory::Syntax,
+            diagnostics_for_file(db, file).take(128).collect(),
+        ),
+        (DiagnosticCategory::Type, diagnostics),
+        (DiagnosticCategory::CallArguments, call_argument_diagnostics),
+        (
+            DiagnosticCategory::Type,
+            attr_values::invalid_value_diagnostics(db, file),
+        ),
+        (
+            DiagnosticCategory::Header,
+            header::header_diagnostics(db, file),
+        ),
+        (
+            DiagnosticCategory::ModuleVersion,
+            module_deps::module_dep_diagnostics(db, file),
+        ),
+        (
+            DiagnosticCategory::Encoding,
+            encoding::encoding_diagnostics(db, file),
+        ),
+        (
+            DiagnosticCategory::NativePrefix,
+            native_prefix::native_prefix_diagnostics(db, file),
+        ),
+        (
+            DiagnosticCategory::Duplicate,
+            duplicates::duplicate_diagnostics(db, file),
+        ),
+        (
+            DiagnosticCategory::PlatformTargets,
+            platform_targets::platform_target_diagnostics(db, file),
+        ),
+        (
+            DiagnosticCategory::LabelStyle,
+            label_style::label_style_diagnostics(db, file),
+        ),
+    ];
+
+    let (unused_loads, unused_variables) = unused::unused_diagnostics(db, file)
+        .into_iter()
+        .partition(|diagnostic| diagnostic.code == DiagnosticCode::UnusedLoad);
+    categorized.push((DiagnosticCategory::UnusedLoad, unused_loads));
+    categorized.push((DiagnosticCategory::UnusedVariable, unused_variables));
+
+    if db.diagnostics_options.enable_spellcheck {
+        categorized.push((
+            DiagnosticCategory::Spelling,
+            spellcheck::spellcheck_diagnostics(db, file),
+        ));
+    }
+
+    if db.diagnostics_options.enable_todos {
+        categorized.push((DiagnosticCategory::Todo, todos::todo_diagnostics(db, file)));
+    }
+
+    #[cfg(feature = "lint-plugins")]
+    categorized.push((
+        DiagnosticCategory::Lint,
+        crate::lint_rules::lint_diagnostics(db, file),
+    ));
+
+    let overrides = db.severity_overrides.get(&file_id);
+    categorized
+        .into_iter()
+        .flat_map(|(category, diagnostics)| {
+            let severity = overrides
+                .as_ref()
+                .and_then(|overrides| overrides.get(&category).cloned());
+            diagnostics
+                .into_iter()
+                .filter_map(move |mut diagnostic| match &severity {
+                    Some(Some(severity)) => {
+                        diagnostic.severity = severity.clone();
+                        Some(diagnostic)
+                    }
+                    Some(None) => None,
+                    None => Some(diagnostic),
+                })
+        })
         .collect()
 }
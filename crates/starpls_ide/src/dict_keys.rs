@@ -0,0 +1,301 @@
+//! Navigation between the string keys of a dict literal and the places that look them up, e.g.
+//! from `TOOLCHAINS["linux_x86"]` or `TOOLCHAINS.get("linux_x86")` to the `"linux_x86"` entry of
+//! `TOOLCHAINS = {...}`. This is a common way of writing enums in configuration-style `.bzl`
+//! files.
+//!
+//! Only dicts that are assigned directly to a variable, either in the same file or in a file
+//! that's loaded, are handled.
+
+use starpls_common::{parse, Db as _, File, FileRange, InFile};
+use starpls_hir::{Name, ScopeDef, Semantics};
+use starpls_syntax::{
+    ast::{self, AstNode},
+    SyntaxKind, SyntaxToken, TextRange,
+};
+
+use crate::{load_graph::load_graph, util::pick_best_token, Database, FilePosition, LocationLink};
+
+/// A lookup of a string key, i.e. the `"key"` in `DICT["key"]` or `DICT.get("key")`.
+struct KeyUsage {
+    /// The expression that evaluates to the dict.
+    dict: ast::Expression,
+    key: String,
+    range: TextRange,
+}
+
+impl KeyUsage {
+    fn from_literal(lit: &ast::LiteralExpr) -> Option<Self> {
+        let key = match lit.kind() {
+            ast::LiteralKind::String(s) => s.value()?.to_string(),
+            _ => return None,
+        };
+        let parent = lit.syntax().parent()?;
+        let dict = if let Some(index_expr) = ast::IndexExpr::cast(parent.clone()) {
+            if index_expr.index()?.syntax() != lit.syntax() {
+                return None;
+            }
+            index_expr.lhs()?
+        } else {
+            // The key must be the first argument to `.get()`.
+            let arg = ast::SimpleArgument::cast(parent)?;
+            let args = ast::Arguments::cast(arg.syntax().parent()?)?;
+            if args.arguments().next()?.syntax() != arg.syntax() {
+                return None;
+            }
+            let call_expr = ast::CallExpr::cast(args.syntax().parent()?)?;
+            let dot_expr = match call_expr.callee()? {
+                ast::Expression::Dot(dot_expr) => dot_expr,
+                _ => return None,
+            };
+            if dot_expr.field()?.name()?.text() != "get" {
+                return None;
+            }
+            dot_expr.expr()?
+        };
+        Some(Self {
+            dict,
+            key,
+            range: lit.syntax().text_range(),
+        })
+    }
+}
+
+/// Returns the dict literal assigned to the variable that the given expression refers to.
+fn resolve_dict(
+    db: &Database,
+    sema: &Semantics,
+    file: File,
+    expr: &ast::Expression,
+) -> Option<InFile<ast::DictExpr>> {
+    let name_ref = match expr {
+        ast::Expression::Name(name_ref) => name_ref,
+        _ => return None,
+    };
+    let name = Name::from_ast_node(name_ref.clone());
+    let def = sema
+        .scope_for_expr(file, expr)?
+        .resolve_name(&name)?
+        .into_iter()
+        .last()?;
+    let (file, def) = match def {
+        ScopeDef::LoadItem(load_item) => {
+            let def = sema.def_for_load_item(&load_item)?;
+            (def.file, def.value)
+        }
+        def => (file, def),
+    };
+    if !matches!(def, ScopeDef::Variable(_)) {
+        return None;
+    }
+
+    let node = def
+        .syntax_node_ptr(db, file)?
+        .to_node(&parse(db, file).syntax(db));
+    let assign_stmt = ast::AssignStmt::cast(node.parent()?)?;
+    if assign_stmt.lhs()?.syntax() != &node {
+        return None;
+    }
+    match assign_stmt.rhs()? {
+        ast::Expression::Dict(dict_expr) => Some(InFile {
+            file,
+            value: dict_expr,
+        }),
+        _ => None,
+    }
+}
+
+/// Returns the range of the entry for the given key. If the key appears more than once, the
+/// last entry is the one that's used.
+fn find_key(dict_expr: &ast::DictExpr, key: &str) -> Option<TextRange> {
+    dict_expr
+        .entries()
+        .filter_map(|entry| match entry.key()? {
+            ast::Expression::Literal(lit) => match lit.kind() {
+                ast::LiteralKind::String(s) if &*s.value()? == key => {
+                    Some(lit.syntax().text_range())
+                }
+                _ => None,
+            },
+            _ => None,
+        })
+        .last()
+}
+
+/// If the token is a string key of a dict literal assigned to a variable, returns the dict
+/// and the key.
+fn key_definition(token: &SyntaxToken) -> Option<(ast::DictExpr, String)> {
+    let lit = ast::LiteralExpr::cast(token.parent()?)?;
+    let key = match lit.kind() {
+        ast::LiteralKind::String(s) => s.value()?.to_string(),
+        _ => return None,
+    };
+    let entry = ast::DictEntry::cast(lit.syntax().parent()?)?;
+    if entry.key()?.syntax() != lit.syntax() {
+        return None;
+    }
+    let dict_expr = ast::DictExpr::cast(entry.syntax().parent()?)?;
+    let assign_stmt = ast::AssignStmt::cast(dict_expr.syntax().parent()?)?;
+    if !matches!(assign_stmt.lhs()?, ast::Expression::Name(_)) {
+        return None;
+    }
+    Some((dict_expr, key))
+}
+
+pub(crate) fn goto_definition(
+    db: &Database,
+    file: File,
+    token: &SyntaxToken,
+) -> Option<Vec<LocationLink>> {
+    let sema = Semantics::new(db);
+    let usage = KeyUsage::from_literal(&ast::LiteralExpr::cast(token.parent()?)?)?;
+    let dict = resolve_dict(db, &sema, file, &usage.dict)?;
+    let range = find_key(&dict.value, &usage.key)?;
+    Some(vec![LocationLink::Local {
+        origin_selection_range: Some(usage.range),
+        target_range: range,
+        target_selection_range: range,
+        target_file_id: dict.file.id(db),
+    }])
+}
+
+/// Finds the lookups of a dict key, starting from either the key's entry in the dict literal or
+/// one of its lookups. Lookups are searched for in the file defining the dict and in the files
+/// that load it.
+pub(crate) fn references(
+    db: &Database,
+    FilePosition { file_id, pos }: FilePosition,
+    include_declaration: bool,
+) -> Option<Vec<FileRange>> {
+    let sema = Semantics::new(db);
+    let file = db.get_file(file_id)?;
+    let token = pick_best_token(
+        parse(db, file).syntax(db).token_at_offset(pos),
+        |kind| match kind {
+            SyntaxKind::STRING => 2,
+            kind if kind.is_trivia_token() => 0,
+            _ => 1,
+        },
+    )?;
+    let (dict, key) = match key_definition(&token) {
+        Some((dict_expr, key)) => (
+            InFile {
+                file,
+                value: dict_expr,
+            },
+            key,
+        ),
+        None => {
+            let usage = KeyUsage::from_literal(&ast::LiteralExpr::cast(token.parent()?)?)?;
+            (resolve_dict(db, &sema, file, &usage.dict)?, usage.key)
+        }
+    };
+    let dict_range = dict.value.syntax().text_range();
+
+    let mut references = Vec::new();
+    if include_declaration {
+        references.push(FileRange {
+            file_id: dict.file.id(db),
+            range: find_key(&dict.value, &key)?,
+        });
+    }
+    let loaders = load_graph(db, dict.file.id(db))
+        .map(|graph| graph.loaders)
+        .unwrap_or_default();
+    for file in std::iter::once(dict.file).chain(
+        loaders
+            .into_iter()
+            .filter_map(|file_id| db.get_file(file_id)),
+    ) {
+        for lit in parse(db, file)
+            .syntax(db)
+            .descendants()
+            .filter_map(ast::LiteralExpr::cast)
+        {
+            let usage = match KeyUsage::from_literal(&lit) {
+                Some(usage) if usage.key == key => usage,
+                _ => continue,
+            };
+            let resolves_to_dict = resolve_dict(db, &sema, file, &usage.dict)
+                .map_or(false, |other| {
+                    other.file == dict.file && other.value.syntax().text_range() == dict_range
+                });
+            if resolves_to_dict {
+                references.push(FileRange {
+                    file_id: file.id(db),
+                    range: usage.range,
+                });
+            }
+        }
+    }
+    Some(references)
+}
+
+#[cfg(test)]
+mod tests {
+    use starpls_bazel::APIContext;
+    use starpls_common::{Dialect, FileId};
+    use starpls_syntax::TextSize;
+
+    use crate::{AnalysisSnapshot, FilePosition, LocationLink};
+
+    const CONTENTS: &str = r#"
+TOOLCHAINS = {
+    "linux_x86": "//toolchains:linux",
+    "darwin_arm64": "//toolchains:darwin",
+}
+
+def toolchain(platform):
+    return TOOLCHAINS.get("linux_x86", TOOLCHAINS[platform])
+
+DEFAULT = TOOLCHAINS["linux_x86"]
+OTHER = {"linux_x86": 1}["linux_x86"]
+"#;
+
+    fn pos(needle: &str, nth: usize) -> FilePosition {
+        let offset = CONTENTS.match_indices(needle).nth(nth).unwrap().0 + 1;
+        FilePosition {
+            file_id: FileId(0),
+            pos: TextSize::from(offset as u32),
+        }
+    }
+
+    #[test]
+    fn test_goto_definition() {
+        let (snap, _) =
+            AnalysisSnapshot::from_single_file(CONTENTS, Dialect::Bazel, Some(APIContext::Bzl));
+        for nth in [1, 2] {
+            let targets = snap
+                .goto_definition(pos("\"linux_x86\"", nth))
+                .unwrap()
+                .unwrap()
+                .into_iter()
+                .map(|loc| match loc {
+                    LocationLink::Local { target_range, .. } => &CONTENTS[target_range],
+                    _ => panic!("expected local location"),
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(targets, vec!["\"linux_x86\""]);
+        }
+    }
+
+    #[test]
+    fn test_references() {
+        let (snap, _) =
+            AnalysisSnapshot::from_single_file(CONTENTS, Dialect::Bazel, Some(APIContext::Bzl));
+        let expected = vec![
+            CONTENTS.match_indices("\"linux_x86\"").nth(0).unwrap().0,
+            CONTENTS.match_indices("\"linux_x86\"").nth(1).unwrap().0,
+            CONTENTS.match_indices("\"linux_x86\"").nth(2).unwrap().0,
+        ];
+        for nth in [0, 1, 2] {
+            let actual = snap
+                .references(pos("\"linux_x86\"", nth), true)
+                .unwrap()
+                .unwrap()
+                .into_iter()
+                .map(|range| usize::from(range.range.start()))
+                .collect::<Vec<_>>();
+            assert_eq!(actual, expected);
+        }
+    }
+}
@@ -1,12 +1,12 @@
 use starpls_bazel::APIContext;
-use starpls_common::{parse, Db, File, FileId};
+use starpls_common::{parse, Db, FileId};
 use starpls_hir::{ScopeDef, Semantics};
 use starpls_syntax::{
     ast::{self, AstNode},
     TextRange,
 };
 
-use crate::Database;
+use crate::{targets::targets, Database};
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum SymbolKind {
@@ -54,15 +54,19 @@ pub struct DocumentSymbol {
     pub children: Option<Vec<DocumentSymbol>>,
 }
 
+/// Returns the outline of a file: its functions and global variables, with rules and providers
+/// listing their attributes and fields, and for `BUILD` files, the targets that it declares.
 pub(crate) fn document_symbols(db: &Database, file_id: FileId) -> Option<Vec<DocumentSymbol>> {
     let sema = Semantics::new(db);
     let file = db.get_file(file_id)?;
+    let root = parse(db, file).syntax(db);
     let scope = sema.scope_for_module(file);
     let mut symbols = scope
         .names()
         .filter_map(|(name, def)| {
-            let range = def.syntax_node_ptr(db, file)?.text_range();
-            Some(DocumentSymbol {
+            let ptr = def.syntax_node_ptr(db, file)?;
+            let range = ptr.text_range();
+            let mut symbol = DocumentSymbol {
                 name: name.as_str().to_string(),
                 detail: None,
                 kind: match def {
@@ -74,62 +78,121 @@ pub(crate) fn document_symbols(db: &Database, file_id: FileId) -> Option<Vec<Doc
                 range: range.clone(),
                 selection_range: range,
                 children: None,
-            })
+            };
+            if let ScopeDef::Variable(_) = def {
+                let ty = def.ty(db);
+                let (kind, detail, arg_name, child_kind) = if ty.is_rule() {
+                    (SymbolKind::Class, "rule", "attrs", SymbolKind::Property)
+                } else if ty.is_provider() {
+                    (SymbolKind::Struct, "provider", "fields", SymbolKind::Field)
+                } else {
+                    return Some(symbol);
+                };
+                symbol.kind = kind;
+                symbol.detail = Some(detail.to_string());
+                if let Some(assign_stmt) = ptr
+                    .to_node(&root)
+                    .ancestors()
+                    .find_map(ast::AssignStmt::cast)
+                {
+                    symbol.range = assign_stmt.syntax().text_range();
+                    symbol.children = match assign_stmt.rhs() {
+                        Some(ast::Expression::Call(call)) => {
+                            Some(keyword_symbols(&call, arg_name, child_kind))
+                        }
+                        _ => None,
+                    };
+                }
+            }
+            Some(symbol)
         })
         .collect();
     if file.api_context(db) == Some(APIContext::Build) {
-        add_target_symbols(db, file, &mut symbols);
+        add_target_symbols(db, file_id, &mut symbols);
     }
 
     symbols.sort_by(|s1, s2| s1.range.start().cmp(&s2.range.start()));
     Some(symbols)
 }
 
-fn add_target_symbols(db: &Database, file: File, acc: &mut Vec<DocumentSymbol>) {
-    let root = parse(db, file).syntax(db);
-    let targets = root.children().filter_map(|child| {
-        let expr = ast::CallExpr::cast(child)?;
-        let range = expr.syntax().text_range();
-        let name = expr
-            .arguments()
-            .into_iter()
-            .flat_map(|args| args.arguments())
-            .find_map(|arg| match arg {
-                ast::Argument::Keyword(arg) => {
-                    if arg.name()?.name()?.text() != "name" {
-                        return None;
-                    }
-                    let lit = match arg.expr()? {
-                        ast::Expression::Literal(lit) => lit,
-                        _ => return None,
-                    };
-                    match lit.kind() {
-                        ast::LiteralKind::String(s) => s.value(),
-                        _ => None,
-                    }
-                }
-                _ => None,
-            })?;
-        Some(DocumentSymbol {
-            name: format!(":{}", name),
-            detail: None,
-            kind: SymbolKind::Variable,
-            tags: None,
-            range: range.clone(),
-            selection_range: range,
-            children: None,
+/// Returns a symbol for each name listed by the given keyword argument of a call, e.g. the
+/// attributes in the `attrs` dict of a `rule()` or the fields of a `provider()`, which may be
+/// either a dict or a list.
+fn keyword_symbols(call: &ast::CallExpr, arg_name: &str, kind: SymbolKind) -> Vec<DocumentSymbol> {
+    let expr = call
+        .arguments()
+        .into_iter()
+        .flat_map(|args| args.arguments())
+        .find_map(|arg| match arg {
+            ast::Argument::Keyword(arg) if arg.name()?.name()?.text() == arg_name => arg.expr(),
+            _ => None,
+        });
+    let names: Vec<(ast::Expression, TextRange)> = match expr {
+        Some(ast::Expression::Dict(dict)) => dict
+            .entries()
+            .filter_map(|entry| Some((entry.key()?, entry.syntax().text_range())))
+            .collect(),
+        Some(ast::Expression::List(list)) => list
+            .elements()
+            .map(|element| {
+                let range = element.syntax().text_range();
+                (element, range)
+            })
+            .collect(),
+        _ => Vec::new(),
+    };
+    names
+        .into_iter()
+        .filter_map(|(name, range)| {
+            let name = match name {
+                ast::Expression::Literal(lit) => match lit.kind() {
+                    ast::LiteralKind::String(s) => s.value()?,
+                    _ => return None,
+                },
+                _ => return None,
+            };
+            Some(DocumentSymbol {
+                name: name.to_string(),
+                detail: None,
+                kind: kind.clone(),
+                tags: None,
+                range,
+                selection_range: range,
+                children: None,
+            })
         })
-    });
-    acc.extend(targets);
+        .collect()
+}
+
+fn add_target_symbols(db: &Database, file_id: FileId, acc: &mut Vec<DocumentSymbol>) {
+    acc.extend(
+        targets(db, file_id)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|target| DocumentSymbol {
+                name: format!(":{}", target.name),
+                detail: Some(target.kind.to_string()),
+                kind: SymbolKind::Variable,
+                tags: None,
+                range: target.range,
+                selection_range: target.name_range,
+                children: None,
+            }),
+    );
 }
 
 #[cfg(test)]
 mod tests {
+    use std::sync::Arc;
+
     use expect_test::{expect, Expect};
-    use starpls_bazel::APIContext;
-    use starpls_common::Dialect;
+    use rustc_hash::FxHashMap;
+    use starpls_bazel::{APIContext, Builtins};
+    use starpls_common::{Dialect, FileId};
+    use starpls_test_util::make_test_builtins;
 
-    use crate::AnalysisSnapshot;
+    use super::DocumentSymbol;
+    use crate::{Analysis, AnalysisSnapshot, Change, SimpleFileLoader};
 
     fn check(input: &str, expect: Expect) {
         let (snap, file_id) =
@@ -143,6 +206,57 @@ mod tests {
         expect.assert_eq(&actual);
     }
 
+    /// Checks the outline of a `.bzl` file, rendering each symbol's selection range as the text
+    /// that it covers.
+    fn check_bzl(input: &str, expect: Expect) {
+        let file_id = FileId(0);
+        let mut change = Change::default();
+        change.create_file(
+            file_id,
+            Dialect::Bazel,
+            Some(APIContext::Bzl),
+            input.to_string(),
+        );
+        let mut analysis = Analysis::new(
+            Arc::new(SimpleFileLoader::from_file_set(FxHashMap::default())),
+            Default::default(),
+        );
+        analysis.db.set_builtin_defs(
+            Dialect::Bazel,
+            make_test_builtins(
+                vec!["provider".to_string(), "rule".to_string()],
+                vec![],
+                vec![],
+            ),
+            Builtins::default(),
+        );
+        analysis.apply_change(change);
+
+        fn render(input: &str, symbol: &DocumentSymbol, depth: usize, acc: &mut String) {
+            acc.push_str(&format!(
+                "{}{:?} {} {:?} {:?}\n",
+                "  ".repeat(depth),
+                symbol.kind,
+                symbol.name,
+                symbol.detail,
+                &input[symbol.selection_range],
+            ));
+            for child in symbol.children.iter().flatten() {
+                render(input, child, depth + 1, acc);
+            }
+        }
+        let mut actual = String::new();
+        for symbol in analysis
+            .snapshot()
+            .document_symbols(file_id)
+            .unwrap()
+            .unwrap()
+        {
+            render(input, &symbol, 0, &mut actual);
+        }
+        expect.assert_eq(&actual);
+    }
+
     #[test]
     fn test_none() {
         check(r#""#, expect![]);
@@ -210,9 +324,42 @@ rust_library_test(
 "#,
             expect![[r#"
                 DocumentSymbol { name: "NUMS", detail: None, kind: Variable, tags: None, range: 1..5, selection_range: 1..5, children: None }
-                DocumentSymbol { name: ":starpls_ide", detail: None, kind: Variable, tags: None, range: 19..94, selection_range: 19..94, children: None }
-                DocumentSymbol { name: ":starpls_ide_test", detail: None, kind: Variable, tags: None, range: 96..176, selection_range: 96..176, children: None }
+                DocumentSymbol { name: ":starpls_ide", detail: Some("rust_library"), kind: Variable, tags: None, range: 19..94, selection_range: 44..57, children: None }
+                DocumentSymbol { name: ":starpls_ide_test", detail: Some("rust_library_test"), kind: Variable, tags: None, range: 96..176, selection_range: 126..144, children: None }
             "#]],
         )
     }
+
+    #[test]
+    fn test_rules_and_providers() {
+        check_bzl(
+            r#"
+MyInfo = provider(fields = ["srcs", "deps"])
+
+OtherInfo = provider(fields = {"value": "The value."})
+
+my_rule = rule(
+    implementation = _impl,
+    attrs = {
+        "srcs": attr.label_list(),
+        "out": attr.output(),
+    },
+)
+
+def _impl(ctx):
+    pass
+"#,
+            expect![[r#"
+                Struct MyInfo Some("provider") "MyInfo"
+                  Field srcs None "\"srcs\""
+                  Field deps None "\"deps\""
+                Struct OtherInfo Some("provider") "OtherInfo"
+                  Field value None "\"value\": \"The value.\""
+                Class my_rule Some("rule") "my_rule"
+                  Property srcs None "\"srcs\": attr.label_list()"
+                  Property out None "\"out\": attr.output()"
+                Function _impl None "def _impl(ctx):\n    pass\n"
+            "#]],
+        );
+    }
 }
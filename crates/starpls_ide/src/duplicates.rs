@@ -0,0 +1,328 @@
+//! Checks for keys that appear more than once in a dict literal, and for symbols that are bound
+//! more than once by the same `load` statement.
+//!
+//! Bazel rejects both, so they're reported as errors in Bazel files. Other dialects let the
+//! later entry win, so they're only warnings there. The quick fix keeps the last entry of each
+//! set of duplicates, which leaves the value unchanged wherever the later entry wins.
+
+use std::fmt::Write;
+
+use starpls_common::{
+    line_index, parse, Db as _, Diagnostic, DiagnosticCode, Dialect, File, FileRange, Severity,
+};
+use starpls_syntax::{
+    ast::{self, AstNode},
+    edit::EditBuilder,
+    SyntaxNode, SyntaxToken, TextRange,
+};
+
+use crate::{util::local_name, CodeAction, CodeActionKind, Database, Hover, TextEdit};
+
+/// A key or symbol that appears more than once in the same dict literal or `load` statement.
+struct Duplicate {
+    code: DiagnosticCode,
+    /// The key or symbol, as it's shown in messages.
+    name: String,
+    /// The node containing the entries, i.e. a `DICT_EXPR` or `LOAD_STMT` node.
+    list: SyntaxNode,
+    /// The entries with the key or symbol, in order.
+    entries: Vec<SyntaxNode>,
+    /// The ranges of the key or symbol in each entry.
+    ranges: Vec<TextRange>,
+}
+
+impl Duplicate {
+    /// Returns whether all of the entries are the same, ignoring whitespace, in which case it
+    /// doesn't matter which one is kept.
+    fn is_identical(&self) -> bool {
+        let normalize = |node: &SyntaxNode| {
+            node.text()
+                .to_string()
+                .split_whitespace()
+                .collect::<String>()
+        };
+        let first = normalize(&self.entries[0]);
+        self.entries[1..]
+            .iter()
+            .all(|entry| normalize(entry) == first)
+    }
+
+    fn edits(&self) -> Vec<TextEdit> {
+        let mut builder = EditBuilder::new();
+        for entry in &self.entries[..self.entries.len() - 1] {
+            builder.remove_list_item(&self.list, entry);
+        }
+        builder.finish().into_iter().map(TextEdit::from).collect()
+    }
+}
+
+fn duplicates(db: &Database, file: File) -> Vec<Duplicate> {
+    let root = parse(db, file).syntax(db);
+    let mut duplicates = Vec::new();
+    for node in root.descendants() {
+        let (code, keys) = if let Some(dict_expr) = ast::DictExpr::cast(node.clone()) {
+            let keys = dict_expr
+                .entries()
+                .filter_map(|entry| {
+                    let key = entry.key()?;
+                    let name = literal_key(&key)?;
+                    Some((name, entry.syntax().clone(), key.syntax().text_range()))
+                })
+                .collect::<Vec<_>>();
+            (DiagnosticCode::DuplicateKey, keys)
+        } else if let Some(load_stmt) = ast::LoadStmt::cast(node.clone()) {
+            let keys = load_stmt
+                .items()
+                .filter_map(|item| {
+                    let name = local_name(&item)?;
+                    Some((name, item.syntax().clone(), item.syntax().text_range()))
+                })
+                .collect::<Vec<_>>();
+            (DiagnosticCode::DuplicateLoadSymbol, keys)
+        } else {
+            continue;
+        };
+
+        let mut groups: Vec<Duplicate> = Vec::new();
+        for (name, entry, range) in keys {
+            match groups.iter_mut().find(|group| group.name == name) {
+                Some(group) => {
+                    group.entries.push(entry);
+                    group.ranges.push(range);
+                }
+                None => groups.push(Duplicate {
+                    code,
+                    name,
+                    list: node.clone(),
+                    entries: vec![entry],
+                    ranges: vec![range],
+                }),
+            }
+        }
+        duplicates.extend(groups.into_iter().filter(|group| group.entries.len() > 1));
+    }
+    duplicates
+}
+
+/// Returns the key of a dict entry if it's a literal, normalized so that e.g. `"a"` and `'a'`,
+/// or `16` and `0x10`, are the same key.
+fn literal_key(expr: &ast::Expression) -> Option<String> {
+    let lit = match expr {
+        ast::Expression::Literal(lit) => lit,
+        _ => return None,
+    };
+    match lit.kind() {
+        ast::LiteralKind::String(s) => Some(format!("{:?}", s.value()?)),
+        ast::LiteralKind::Int(i) => Some(i.value()?.to_string()),
+        ast::LiteralKind::Bool(b) => Some(if b { "True" } else { "False" }.to_string()),
+        ast::LiteralKind::None => Some("None".to_string()),
+        _ => None,
+    }
+}
+
+pub(crate) fn duplicate_diagnostics(db: &Database, file: File) -> Vec<Diagnostic> {
+    let severity = match file.dialect(db) {
+        Dialect::Bazel | Dialect::Buck2 => Severity::Error,
+        Dialect::Standard => Severity::Warning,
+    };
+    let mut diagnostics = Vec::new();
+    for duplicate in duplicates(db, file) {
+        let (first, repeated) = match duplicate.code {
+            DiagnosticCode::DuplicateKey => (
+                format!("Key {} is repeated later in this dict", duplicate.name),
+                format!("Duplicate key {} in dict", duplicate.name),
+            ),
+            _ => (
+                format!(
+                    "\"{}\" is loaded again later in this load statement",
+                    duplicate.name
+                ),
+                format!(
+                    "\"{}\" is loaded more than once in this load statement",
+                    duplicate.name
+                ),
+            ),
+        };
+
+        // The first entry is pointed out as well, since either one may be the one to remove.
+        diagnostics.push(Diagnostic {
+            message: first,
+            code: duplicate.code,
+            severity: Severity::Hint,
+            range: FileRange {
+                file_id: file.id(db),
+                range: duplicate.ranges[0],
+            },
+        });
+        for &range in &duplicate.ranges[1..] {
+            diagnostics.push(Diagnostic {
+                message: repeated.clone(),
+                code: duplicate.code,
+                severity: severity.clone(),
+                range: FileRange {
+                    file_id: file.id(db),
+                    range,
+                },
+            });
+        }
+    }
+    diagnostics
+}
+
+pub(crate) fn duplicate_actions(db: &Database, file: File, range: TextRange) -> Vec<CodeAction> {
+    duplicates(db, file)
+        .into_iter()
+        .filter(|duplicate| {
+            duplicate
+                .ranges
+                .iter()
+                .any(|duplicate_range| duplicate_range.intersect(range).is_some())
+        })
+        .map(|duplicate| CodeAction {
+            title: match duplicate.code {
+                DiagnosticCode::DuplicateKey => {
+                    format!("Remove duplicates of key {}", duplicate.name)
+                }
+                _ => format!("Remove duplicate loads of `{}`", duplicate.name),
+            },
+            kind: CodeActionKind::QuickFix,
+            edits: duplicate.edits(),
+            file_edits: Vec::new(),
+            command: None,
+            is_preferred: true,
+        })
+        .collect()
+}
+
+/// Returns the edits that remove duplicate entries for the "Fix all" action. Only sets of
+/// identical entries are included, since otherwise it isn't clear which entry was meant.
+pub(crate) fn duplicate_edits(db: &Database, file: File) -> Vec<TextEdit> {
+    let mut edits = duplicates(db, file)
+        .into_iter()
+        .filter(Duplicate::is_identical)
+        .flat_map(|duplicate| duplicate.edits())
+        .collect::<Vec<_>>();
+
+    // Nested dicts may be removed along with the entries containing them.
+    edits.sort_by_key(|edit| edit.range.start());
+    edits.dedup_by(|edit, prev| prev.range.contains_range(edit.range));
+    edits
+}
+
+/// Explains which of the entries is used when hovering over a duplicate key or symbol.
+pub(crate) fn hover(db: &Database, file: File, token: &SyntaxToken) -> Option<Hover> {
+    let duplicate = duplicates(db, file).into_iter().find(|duplicate| {
+        duplicate
+            .ranges
+            .iter()
+            .any(|range| range.contains_range(token.text_range()))
+    })?;
+    let line_index = line_index(db, file);
+    let lines = duplicate
+        .ranges
+        .iter()
+        .map(|range| (line_index.line_col(range.start()).line + 1).to_string())
+        .collect::<Vec<_>>();
+
+    let mut text = match duplicate.code {
+        DiagnosticCode::DuplicateKey => format!(
+            "Key {} appears {} times in this dict, on lines {}.",
+            duplicate.name,
+            lines.len(),
+            lines.join(", ")
+        ),
+        _ => format!(
+            "`{}` is loaded {} times by this load statement, on lines {}.",
+            duplicate.name,
+            lines.len(),
+            lines.join(", ")
+        ),
+    };
+    write!(
+        text,
+        " Bazel rejects this; other dialects use the last entry, on line {}.",
+        lines.last()?
+    )
+    .ok()?;
+    Some(text.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use starpls_common::{Db as _, DiagnosticCode, Dialect, FileRange, Severity};
+    use starpls_syntax::{TextRange, TextSize};
+
+    use super::duplicate_diagnostics;
+    use crate::{AnalysisSnapshot, TextEdit};
+
+    fn apply(text: &str, edits: &[TextEdit]) -> String {
+        let mut edits = edits.to_vec();
+        edits.sort_by_key(|edit| std::cmp::Reverse(edit.range.start()));
+        let mut text = text.to_string();
+        for edit in edits {
+            text.replace_range(std::ops::Range::<usize>::from(edit.range), &edit.new_text);
+        }
+        text
+    }
+
+    fn check_fix(contents: &str, offset: usize, title: &str, expected: &str) {
+        let (snap, file_id) = AnalysisSnapshot::from_single_file(contents, Dialect::Standard, None);
+        let actions = snap
+            .code_actions(FileRange {
+                file_id,
+                range: TextRange::empty(TextSize::from(offset as u32)),
+            })
+            .unwrap()
+            .unwrap();
+        let action = actions.iter().find(|action| action.title == title).unwrap();
+        assert_eq!(apply(contents, &action.edits), expected);
+    }
+
+    #[test]
+    fn test_duplicate_keys() {
+        let contents = r#"x = {"a": 1, 'a': 2, 16: 3, 0x10: 4, "b": 5}
+"#;
+        let (snap, file_id) = AnalysisSnapshot::from_single_file(contents, Dialect::Bazel, None);
+        let file = snap.db.get_file(file_id).unwrap();
+        let diagnostics = duplicate_diagnostics(&snap.db, file);
+        assert_eq!(
+            diagnostics
+                .iter()
+                .filter(|diagnostic| matches!(diagnostic.severity, Severity::Error))
+                .map(|diagnostic| (diagnostic.code, &contents[diagnostic.range.range]))
+                .collect::<Vec<_>>(),
+            vec![
+                (DiagnosticCode::DuplicateKey, "'a'"),
+                (DiagnosticCode::DuplicateKey, "0x10"),
+            ]
+        );
+        assert_eq!(
+            diagnostics
+                .iter()
+                .filter(|diagnostic| matches!(diagnostic.severity, Severity::Hint))
+                .map(|diagnostic| &contents[diagnostic.range.range])
+                .collect::<Vec<_>>(),
+            vec!["\"a\"", "16"]
+        );
+
+        check_fix(
+            contents,
+            6,
+            "Remove duplicates of key \"a\"",
+            r#"x = {'a': 2, 16: 3, 0x10: 4, "b": 5}
+"#,
+        );
+    }
+
+    #[test]
+    fn test_duplicate_load_symbols() {
+        check_fix(
+            r#"load(":a.bzl", "x", "y", z = "x", "x")
+"#,
+            16,
+            "Remove duplicate loads of `x`",
+            r#"load(":a.bzl", "y", z = "x", "x")
+"#,
+        );
+    }
+}
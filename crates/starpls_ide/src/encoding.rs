@@ -0,0 +1,60 @@
+//! Reports text that was replaced while decoding a file that isn't valid UTF-8.
+
+use starpls_common::{Db as _, Diagnostic, DiagnosticCode, File, FileRange, Severity};
+use starpls_syntax::{TextRange, TextSize};
+
+use crate::Database;
+
+pub(crate) fn encoding_diagnostics(db: &Database, file: File) -> Vec<Diagnostic> {
+    let contents = file.contents(db);
+    let mut diagnostics = Vec::new();
+    let mut chars = contents.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        if c != char::REPLACEMENT_CHARACTER {
+            continue;
+        }
+
+        // Report runs of replacement characters as a single diagnostic.
+        let mut end = start + c.len_utf8();
+        while let Some((pos, _)) = chars.next_if(|(_, c)| *c == char::REPLACEMENT_CHARACTER) {
+            end = pos + char::REPLACEMENT_CHARACTER.len_utf8();
+        }
+        diagnostics.push(Diagnostic {
+            code: DiagnosticCode::InvalidUtf8,
+            message: "Invalid UTF-8 was replaced with U+FFFD".to_string(),
+            severity: Severity::Warning,
+            range: FileRange {
+                file_id: file.id(db),
+                range: TextRange::new(TextSize::new(start as u32), TextSize::new(end as u32)),
+            },
+        });
+    }
+    diagnostics
+}
+
+#[cfg(test)]
+mod tests {
+    use starpls_common::{Db as _, Dialect};
+    use starpls_syntax::{TextRange, TextSize};
+
+    use super::encoding_diagnostics;
+    use crate::AnalysisSnapshot;
+
+    #[test]
+    fn test_encoding_diagnostics() {
+        let (snap, file_id) = AnalysisSnapshot::from_single_file(
+            "s = \"caf\u{fffd}\u{fffd}\"\nt = 1\n",
+            Dialect::Standard,
+            None,
+        );
+        let file = snap.db.get_file(file_id).unwrap();
+        let ranges = encoding_diagnostics(&snap.db, file)
+            .into_iter()
+            .map(|diagnostic| diagnostic.range.range)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            ranges,
+            vec![TextRange::new(TextSize::new(8), TextSize::new(14))]
+        );
+    }
+}
@@ -0,0 +1,516 @@
+//! A refactoring that replaces several similar targets in a `BUILD` file with calls to a new
+//! macro.
+//!
+//! The selected targets must be declared with the same rule, using only keyword arguments.
+//! Attributes that are set to the same value on every selected target are written directly in
+//! the body of the macro, while `name` and the attributes whose values vary between targets
+//! become parameters of the macro, defaulting to `None` if they aren't set on every target. The
+//! macro can be added to any `.bzl` file in the workspace that the `BUILD` file already loads, or
+//! to a new `macros.bzl` file next to it.
+
+use rustc_hash::FxHashSet;
+use starpls_bazel::APIContext;
+use starpls_common::{parse, Db as _, Dialect, File, FileId};
+use starpls_hir::Semantics;
+use starpls_syntax::{
+    ast::{self, AstNode},
+    SyntaxKind, SyntaxNode, TextRange,
+};
+
+use crate::{
+    util::{append_to_file, insert_load, load_stmts, loaded_name, local_name, merge_insertions},
+    CodeAction, CodeActionKind, Database, EditTarget, FileEdit, TextEdit,
+};
+
+/// The name of the file that is offered as a destination for the macro if the `BUILD` file
+/// doesn't already load it.
+const NEW_MACROS_FILE: &str = "macros.bzl";
+
+struct SelectedTarget {
+    range: TextRange,
+    /// The target's keyword arguments, along with their values as written and as compared.
+    attributes: Vec<(String, String, String)>,
+}
+
+impl SelectedTarget {
+    fn value(&self, name: &str) -> Option<&(String, String, String)> {
+        self.attributes.iter().find(|(attr, _, _)| attr == name)
+    }
+}
+
+enum Attribute {
+    /// Set to the same value on every target.
+    Constant(String),
+    /// A parameter of the macro. Parameters that aren't set on every target default to `None`.
+    Parameter { is_optional: bool },
+}
+
+/// Where the rule is loaded from, for rules that aren't native.
+struct RuleLoad {
+    module: String,
+    name: String,
+}
+
+struct Destination {
+    label: String,
+    target: EditTarget,
+    file: Option<File>,
+}
+
+pub(crate) fn extract_macro_actions(
+    db: &Database,
+    file: File,
+    range: TextRange,
+) -> Vec<CodeAction> {
+    if file.api_context(db) != Some(APIContext::Build) || range.is_empty() {
+        return Vec::new();
+    }
+    let root = parse(db, file).syntax(db);
+    let (rule, targets) = match selected_targets(&root, range) {
+        Some(res) => res,
+        None => return Vec::new(),
+    };
+    let attributes = macro_attributes(&targets);
+
+    // Native rules must be accessed through the `native` module in `.bzl` files, while other
+    // rules have to be loaded.
+    let loads = load_stmts(&root);
+    let rule_load = loads.iter().find_map(|(load_stmt, module)| {
+        load_stmt
+            .items()
+            .find(|item| local_name(item).as_deref() == Some(rule.as_str()))
+            .and_then(|item| loaded_name(&item))
+            .map(|name| RuleLoad {
+                module: module.clone(),
+                name,
+            })
+    });
+
+    destinations(db, file, &loads)
+        .into_iter()
+        .filter(|dest| {
+            // Package-relative labels only resolve to the same file from within the same package.
+            rule_load.as_ref().map_or(true, |rule_load| {
+                !is_relative_label(&rule_load.module) || is_relative_label(&dest.label)
+            })
+        })
+        .map(|dest| {
+            let dest_root = dest.file.map(|dest_file| parse(db, dest_file).syntax(db));
+            let macro_name = macro_name(&rule, &root, dest_root.as_ref());
+            let mut dest_edits = Vec::new();
+
+            // Load the rule into the destination file if it isn't already.
+            let rule_ref = match &rule_load {
+                Some(rule_load) => {
+                    let existing = dest_root.as_ref().and_then(|dest_root| {
+                        load_stmts(dest_root)
+                            .into_iter()
+                            .filter(|(_, module)| module == &rule_load.module)
+                            .flat_map(|(load_stmt, _)| load_stmt.items())
+                            .find(|item| {
+                                loaded_name(item).as_deref() == Some(rule_load.name.as_str())
+                            })
+                            .and_then(|item| local_name(&item))
+                    });
+                    match existing {
+                        Some(local_name) => local_name,
+                        None => {
+                            dest_edits.push(insert_load(
+                                dest_root.as_ref(),
+                                &rule_load.module,
+                                &[&rule_load.name],
+                            ));
+                            rule_load.name.clone()
+                        }
+                    }
+                }
+                None => format!("native.{}", rule),
+            };
+
+            // Append the macro to the end of the destination file.
+            let macro_def = macro_def(&macro_name, &rule_ref, &attributes);
+            dest_edits.push(match &dest_root {
+                Some(dest_root) => append_to_file(dest_root, &macro_def),
+                None => TextEdit {
+                    range: TextRange::empty(0.into()),
+                    new_text: macro_def,
+                },
+            });
+            merge_insertions(&mut dest_edits);
+
+            // Replace each target with a call to the macro, and load the macro.
+            let mut edits = vec![
+                match loads.iter().find(|(_, module)| module == &dest.label) {
+                    Some((load_stmt, _)) => TextEdit {
+                        range: TextRange::empty(
+                            load_stmt
+                                .items()
+                                .last()
+                                .map(|item| item.syntax().text_range().end())
+                                .or_else(|| {
+                                    load_stmt
+                                        .module()
+                                        .map(|module| module.syntax().text_range().end())
+                                })
+                                .unwrap_or_else(|| load_stmt.syntax().text_range().end()),
+                        ),
+                        new_text: format!(", \"{}\"", macro_name),
+                    },
+                    None => insert_load(Some(&root), &dest.label, &[&macro_name]),
+                },
+            ];
+            edits.extend(targets.iter().map(|target| TextEdit {
+                range: target.range,
+                new_text: macro_call(&macro_name, target, &attributes),
+            }));
+
+            CodeAction {
+                title: format!("Extract targets into a macro in {}", dest.label),
+                kind: CodeActionKind::RefactorExtract,
+                edits,
+                file_edits: vec![FileEdit {
+                    target: dest.target,
+                    edits: dest_edits,
+                }],
+                command: None,
+                is_preferred: false,
+            }
+        })
+        .collect()
+}
+
+/// Returns the rule that the targets intersecting the given range are declared with, along with
+/// the targets themselves, if there are at least two of them and they can all be extracted.
+fn selected_targets(root: &SyntaxNode, range: TextRange) -> Option<(String, Vec<SelectedTarget>)> {
+    let mut rule = None;
+    let mut targets = Vec::new();
+    for call_expr in root
+        .children()
+        .filter_map(ast::CallExpr::cast)
+        .filter(|call_expr| {
+            call_expr
+                .syntax()
+                .text_range()
+                .intersect(range)
+                .map_or(false, |intersection| !intersection.is_empty())
+        })
+    {
+        let callee = match call_expr.callee()? {
+            ast::Expression::Name(name_ref) => name_ref.name()?.text().to_string(),
+            _ => return None,
+        };
+        if *rule.get_or_insert_with(|| callee.clone()) != callee {
+            return None;
+        }
+
+        let mut attributes: Vec<(String, String, String)> = Vec::new();
+        for arg in call_expr.arguments()?.arguments() {
+            let arg = match arg {
+                ast::Argument::Keyword(arg) => arg,
+                _ => return None,
+            };
+            let name = arg.name()?.name()?.text().to_string();
+            let expr = arg.expr()?;
+            if attributes.iter().any(|(attr, _, _)| attr == &name) {
+                return None;
+            }
+            attributes.push((
+                name,
+                expr.syntax().text().to_string(),
+                normalize(expr.syntax()),
+            ));
+        }
+        if !attributes.iter().any(|(attr, _, _)| attr == "name") {
+            return None;
+        }
+        targets.push(SelectedTarget {
+            range: call_expr.syntax().text_range(),
+            attributes,
+        });
+    }
+
+    if targets.len() < 2 {
+        return None;
+    }
+    Some((rule?, targets))
+}
+
+/// Returns the attributes of the macro's target in the order they're first written.
+fn macro_attributes(targets: &[SelectedTarget]) -> Vec<(String, Attribute)> {
+    let mut names: Vec<&str> = Vec::new();
+    for target in targets {
+        for (name, _, _) in &target.attributes {
+            if !names.contains(&name.as_str()) {
+                names.push(name);
+            }
+        }
+    }
+
+    names
+        .into_iter()
+        .map(|name| {
+            let values = targets
+                .iter()
+                .map(|target| target.value(name))
+                .collect::<Vec<_>>();
+            let attribute = if name == "name" {
+                Attribute::Parameter { is_optional: false }
+            } else if values.iter().any(Option::is_none) {
+                Attribute::Parameter { is_optional: true }
+            } else if values.windows(2).all(|pair| {
+                pair[0].map(|(_, _, value)| value) == pair[1].map(|(_, _, value)| value)
+            }) {
+                Attribute::Constant(
+                    values[0]
+                        .map(|(_, text, _)| text.clone())
+                        .unwrap_or_default(),
+                )
+            } else {
+                Attribute::Parameter { is_optional: false }
+            };
+            (name.to_string(), attribute)
+        })
+        .collect()
+}
+
+fn macro_def(name: &str, rule_ref: &str, attributes: &[(String, Attribute)]) -> String {
+    let required = attributes
+        .iter()
+        .filter(|(_, attr)| matches!(attr, Attribute::Parameter { is_optional: false }))
+        .map(|(name, _)| name.clone());
+    let optional = attributes
+        .iter()
+        .filter(|(_, attr)| matches!(attr, Attribute::Parameter { is_optional: true }))
+        .map(|(name, _)| format!("{} = None", name));
+    let params = required.chain(optional).collect::<Vec<_>>().join(", ");
+
+    let mut text = format!("def {}({}):\n    {}(\n", name, params, rule_ref);
+    for (name, attr) in attributes {
+        let value = match attr {
+            // Values are written one level of indentation deeper than they were in the `BUILD`
+            // file.
+            Attribute::Constant(value) => value.replace('\n', "\n    "),
+            Attribute::Parameter { .. } => name.clone(),
+        };
+        text.push_str(&format!("        {} = {},\n", name, value));
+    }
+    text.push_str("    )\n");
+    text
+}
+
+fn macro_call(name: &str, target: &SelectedTarget, attributes: &[(String, Attribute)]) -> String {
+    let mut text = format!("{}(\n", name);
+    for (attr_name, attr) in attributes {
+        if let Attribute::Parameter { .. } = attr {
+            if let Some((_, value, _)) = target.value(attr_name) {
+                text.push_str(&format!("    {} = {},\n", attr_name, value));
+            }
+        }
+    }
+    text.push(')');
+    text
+}
+
+/// Picks a name for the macro that isn't already used by the `BUILD` file or the destination.
+fn macro_name(rule: &str, root: &SyntaxNode, dest_root: Option<&SyntaxNode>) -> String {
+    let mut used = load_stmts(root)
+        .iter()
+        .flat_map(|(load_stmt, _)| load_stmt.items())
+        .filter_map(|item| local_name(&item))
+        .collect::<FxHashSet<_>>();
+    if let Some(dest_root) = dest_root {
+        used.extend(
+            dest_root
+                .children()
+                .filter_map(ast::DefStmt::cast)
+                .filter_map(|def_stmt| Some(def_stmt.name()?.name()?.text().to_string())),
+        );
+        used.extend(
+            load_stmts(dest_root)
+                .iter()
+                .flat_map(|(load_stmt, _)| load_stmt.items())
+                .filter_map(|item| local_name(&item)),
+        );
+    }
+
+    let base = format!("{}_macro", rule);
+    let mut name = base.clone();
+    let mut i = 2;
+    while used.contains(&name) {
+        name = format!("{}_{}", base, i);
+        i += 1;
+    }
+    name
+}
+
+/// Returns the `.bzl` files that the macro can be added to, i.e. those in the current workspace
+/// that are loaded by the given file, as well as `macros.bzl` in the same package.
+fn destinations(db: &Database, file: File, loads: &[(ast::LoadStmt, String)]) -> Vec<Destination> {
+    let sema = Semantics::new(db);
+    let mut seen = FxHashSet::<FileId>::default();
+    let mut dests = Vec::new();
+    for (load_stmt, module) in loads {
+        if module.starts_with('@') || !module.ends_with(".bzl") {
+            continue;
+        }
+        if let Some(loaded_file) = sema.resolve_load_stmt(file, load_stmt) {
+            if seen.insert(loaded_file.id(db)) {
+                dests.push(Destination {
+                    label: module.clone(),
+                    target: EditTarget::File(loaded_file.id(db)),
+                    file: Some(loaded_file),
+                });
+            }
+        }
+    }
+
+    let new_label = format!(":{}", NEW_MACROS_FILE);
+    if !loads.iter().any(|(_, module)| module == &new_label) {
+        match db.load_file(&new_label, Dialect::Bazel, file.id(db)) {
+            Ok(Some(existing)) => {
+                if seen.insert(existing.id(db)) {
+                    dests.push(Destination {
+                        label: new_label,
+                        target: EditTarget::File(existing.id(db)),
+                        file: Some(existing),
+                    });
+                }
+            }
+            Ok(None) => dests.push(Destination {
+                label: new_label,
+                target: EditTarget::NewFile(NEW_MACROS_FILE.to_string()),
+                file: None,
+            }),
+            Err(_) => {}
+        }
+    }
+    dests
+}
+
+fn is_relative_label(label: &str) -> bool {
+    !label.starts_with("//") && !label.starts_with('@')
+}
+
+/// Returns the text of an expression without any whitespace or comments, for comparing values.
+fn normalize(node: &SyntaxNode) -> String {
+    node.descendants_with_tokens()
+        .filter_map(|element| element.into_token())
+        .filter(|token| {
+            !matches!(
+                token.kind(),
+                SyntaxKind::WHITESPACE | SyntaxKind::COMMENT | SyntaxKind::NEWLINE
+            )
+        })
+        .map(|token| token.text().to_string())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use starpls_common::{Dialect, FileId, FileRange};
+    use starpls_syntax::{TextRange, TextSize};
+
+    use crate::{Analysis, EditTarget, TextEdit};
+
+    fn apply(text: &str, edits: &[TextEdit]) -> String {
+        let mut edits = edits.to_vec();
+        edits.sort_by_key(|edit| std::cmp::Reverse(edit.range.start()));
+        let mut text = text.to_string();
+        for edit in edits {
+            text.replace_range(std::ops::Range::<usize>::from(edit.range), &edit.new_text);
+        }
+        text
+    }
+
+    #[test]
+    fn test_extract_macro() {
+        let build = r#"load(":defs.bzl", "helper")
+
+cc_library(
+    name = "foo",
+    srcs = ["foo.cc"],
+    linkstatic = True,
+)
+
+cc_library(
+    name = "bar",
+    srcs = ["bar.cc"],
+    deps = [":foo"],
+    linkstatic = True,
+)
+"#;
+        let defs = "def helper():\n    pass\n";
+        let build_id = FileId(0);
+        let defs_id = FileId(1);
+        // The loaded file is only found through the loader.
+        let analysis =
+            Analysis::from_workspace(&[("BUILD", build)], &[(":defs.bzl", defs)], Dialect::Bazel);
+
+        let actions = analysis
+            .snapshot()
+            .code_actions(FileRange {
+                file_id: build_id,
+                range: TextRange::new(TextSize::from(0), TextSize::of(build)),
+            })
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            actions
+                .iter()
+                .map(|action| action.title.as_str())
+                .collect::<Vec<_>>(),
+            vec![
+                "Extract targets into a macro in :defs.bzl",
+                "Extract targets into a macro in :macros.bzl",
+            ]
+        );
+
+        let calls = r#"
+
+cc_library_macro(
+    name = "foo",
+    srcs = ["foo.cc"],
+)
+
+cc_library_macro(
+    name = "bar",
+    srcs = ["bar.cc"],
+    deps = [":foo"],
+)
+"#;
+        let macro_def = r#"def cc_library_macro(name, srcs, deps = None):
+    native.cc_library(
+        name = name,
+        srcs = srcs,
+        linkstatic = True,
+        deps = deps,
+    )
+"#;
+        assert_eq!(
+            apply(build, &actions[0].edits),
+            format!(
+                "load(\":defs.bzl\", \"helper\", \"cc_library_macro\"){}",
+                calls
+            )
+        );
+        assert_eq!(actions[0].file_edits[0].target, EditTarget::File(defs_id));
+        assert_eq!(
+            apply(defs, &actions[0].file_edits[0].edits),
+            format!("{}\n\n{}", defs, macro_def)
+        );
+
+        assert_eq!(
+            apply(build, &actions[1].edits),
+            format!(
+                "load(\":defs.bzl\", \"helper\")\nload(\":macros.bzl\", \"cc_library_macro\"){}",
+                calls
+            )
+        );
+        assert_eq!(
+            actions[1].file_edits[0].target,
+            EditTarget::NewFile("macros.bzl".to_string())
+        );
+        assert_eq!(apply("", &actions[1].file_edits[0].edits), macro_def);
+    }
+}
@@ -0,0 +1,292 @@
+//! Finds the references to the fields of providers and structs, e.g. every `info.srcs` where
+//! `info` is a `MyInfo`. Fields are matched by the declaration of the provider or struct that
+//! they belong to rather than by name, so that the fields of unrelated providers that happen to
+//! share a name are left alone.
+//!
+//! A field is referred to by its entry in a provider's `fields`, by the keyword arguments of the
+//! calls that create instances, e.g. `MyInfo(srcs = ...)` or `struct(srcs = ...)`, and by field
+//! accesses. Only the file that declares the fields and the files that load it are searched.
+
+use starpls_common::{parse, Db as _, File, FileRange, InFile};
+use starpls_hir::Semantics;
+use starpls_syntax::{
+    ast::{self, AstNode, AstToken, SyntaxNodePtr},
+    SyntaxKind, SyntaxToken, T,
+};
+
+use crate::{
+    load_graph::load_graph, module_deps::callee_name, util::pick_best_token, Database, FilePosition,
+};
+
+/// The references to a field of a provider or struct.
+pub(crate) struct FieldReferences {
+    /// The string literal that declares the field in the provider's `fields`, if any. Struct
+    /// fields are only declared by the keyword arguments that set them.
+    pub(crate) declaration: Option<FileRange>,
+    /// The names of the keyword arguments that set the field and of the accesses to it.
+    pub(crate) uses: Vec<FileRange>,
+}
+
+pub(crate) fn references(
+    db: &Database,
+    pos: FilePosition,
+    include_declaration: bool,
+) -> Option<Vec<FileRange>> {
+    let references = field_references(db, pos)?;
+    Some(
+        references
+            .declaration
+            .filter(|_| include_declaration)
+            .into_iter()
+            .chain(references.uses)
+            .collect(),
+    )
+}
+
+/// Finds the references to the field at the given position, which may be any of its references.
+pub(crate) fn field_references(db: &Database, pos: FilePosition) -> Option<FieldReferences> {
+    let file = db.get_file(pos.file_id)?;
+    let sema = Semantics::new(db);
+    let token = pick_best_token(
+        parse(db, file).syntax(db).token_at_offset(pos.pos),
+        |kind| match kind {
+            T![ident] | SyntaxKind::STRING => 2,
+            kind if kind.is_trivia_token() => 0,
+            _ => 1,
+        },
+    )?;
+    let (declaration, name) = field_at(db, &sema, file, &token)?;
+
+    let loaders = load_graph(db, declaration.file.id(db))
+        .map(|graph| graph.loaders)
+        .unwrap_or_default();
+    let files = std::iter::once(declaration.file).chain(
+        loaders
+            .into_iter()
+            .filter_map(|file_id| db.get_file(file_id)),
+    );
+    let mut references = FieldReferences {
+        declaration: None,
+        uses: Vec::new(),
+    };
+    for file in files {
+        let root = parse(db, file).syntax(db);
+        for token in root
+            .descendants_with_tokens()
+            .filter_map(|element| element.into_token())
+        {
+            let has_name = match token.kind() {
+                T![ident] => token.text() == name,
+                SyntaxKind::STRING => {
+                    ast::String::cast(token.clone())
+                        .and_then(|string| string.value())
+                        .as_deref()
+                        == Some(name.as_str())
+                }
+                _ => false,
+            };
+            if !has_name
+                || field_at(db, &sema, file, &token).map_or(true, |(other, _)| other != declaration)
+            {
+                continue;
+            }
+            let range = FileRange {
+                file_id: file.id(db),
+                range: token.text_range(),
+            };
+            if token.kind() == SyntaxKind::STRING {
+                references.declaration = Some(range);
+            } else {
+                references.uses.push(range);
+            }
+        }
+    }
+    Some(references)
+}
+
+/// Returns the declaration of the fields that the field at the given token belongs to, along with
+/// the field's name.
+fn field_at(
+    db: &Database,
+    sema: &Semantics,
+    file: File,
+    token: &SyntaxToken,
+) -> Option<(InFile<SyntaxNodePtr>, String)> {
+    let parent = token.parent()?;
+    if let Some(name) = ast::Name::cast(parent.clone()) {
+        let owner = name.syntax().parent()?;
+        let ty = if let Some(dot_expr) = ast::DotExpr::cast(owner.clone()) {
+            sema.type_of_expr(file, &dot_expr.expr()?)?
+        } else {
+            let call = ast::KeywordArgument::cast(owner)?
+                .syntax()
+                .parent()
+                .and_then(|args| args.parent())
+                .and_then(ast::CallExpr::cast)?;
+            // Calls to functions that return structs take parameters, not fields.
+            let callee_ty = sema.type_of_expr(file, &call.callee()?)?;
+            let creates_instance = callee_name(&call).as_deref() == Some("struct")
+                || (callee_ty.fields_declaration(db).is_some() && !callee_ty.has_provider_init());
+            if !creates_instance {
+                return None;
+            }
+            sema.type_of_expr(file, &ast::Expression::Call(call))?
+        };
+        return Some((ty.fields_declaration(db)?, token.text().to_string()));
+    }
+
+    // The entries of a provider's `fields`, e.g. `"srcs"` in `fields = {"srcs": "..."}` or in
+    // `fields = ["srcs"]`.
+    let lit = ast::LiteralExpr::cast(parent)?;
+    let mut fields = lit.syntax().parent()?;
+    if let Some(entry) = ast::DictEntry::cast(fields.clone()) {
+        if entry.key()?.syntax() != lit.syntax() {
+            return None;
+        }
+        fields = entry.syntax().parent()?;
+    }
+    if !matches!(fields.kind(), SyntaxKind::DICT_EXPR | SyntaxKind::LIST_EXPR) {
+        return None;
+    }
+    let arg = ast::KeywordArgument::cast(fields.parent()?)?;
+    let call = arg
+        .syntax()
+        .parent()
+        .and_then(|args| args.parent())
+        .and_then(ast::CallExpr::cast)?;
+    if arg.name()?.name()?.text() != "fields" || callee_name(&call).as_deref() != Some("provider") {
+        return None;
+    }
+    Some((
+        InFile {
+            file,
+            value: SyntaxNodePtr::new(&fields),
+        },
+        ast::String::cast(token.clone())?.value()?.to_string(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use starpls_common::{Dialect, FileId};
+    use starpls_syntax::TextSize;
+
+    use crate::{AnalysisSnapshot, EditTarget, FilePosition};
+
+    const DEFS: &str = r#"FooInfo = provider(fields = {"srcs": "The sources.", "deps": "The deps."})
+BarInfo = provider(fields = ["srcs"])
+
+def make_foo():
+    return FooInfo(srcs = [], deps = [])
+"#;
+
+    const USER: &str = r#"load("defs.bzl", "BarInfo", "FooInfo")
+
+def merge():
+    foo = FooInfo(srcs = [], deps = [])
+    bar = BarInfo(srcs = [])
+    return foo.srcs + bar.srcs
+
+config = struct(srcs = [])
+config_srcs = config.srcs
+"#;
+
+    const FILES: [(&str, &str); 2] = [("defs.bzl", DEFS), ("user.bzl", USER)];
+
+    fn snapshot() -> AnalysisSnapshot {
+        AnalysisSnapshot::from_files(&FILES, Dialect::Bazel)
+    }
+
+    fn pos(file_id: u32, needle: &str) -> FilePosition {
+        let text = FILES[file_id as usize].1;
+        FilePosition {
+            file_id: FileId(file_id),
+            pos: TextSize::from(text.find(needle).unwrap() as u32 + 1),
+        }
+    }
+
+    /// Returns the line and text of each reference, along with the index of its file.
+    fn references(pos: FilePosition) -> Vec<(u32, usize, &'static str)> {
+        snapshot()
+            .references(pos, true)
+            .unwrap()
+            .unwrap()
+            .into_iter()
+            .map(|reference| {
+                let text = FILES[reference.file_id.0 as usize].1;
+                let start = usize::from(reference.range.start());
+                (
+                    reference.file_id.0,
+                    text[..start].matches('\n').count() + 1,
+                    &text[reference.range],
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_provider_field_references() {
+        let expected = vec![
+            (0, 1, "\"srcs\""),
+            (0, 5, "srcs"),
+            (1, 4, "srcs"),
+            (1, 6, "srcs"),
+        ];
+        assert_eq!(references(pos(1, "rcs = [], deps")), expected);
+        assert_eq!(references(pos(0, "srcs\": ")), expected);
+        assert_eq!(references(pos(1, "rcs + bar")), expected);
+    }
+
+    #[test]
+    fn test_provider_field_from_list() {
+        assert_eq!(
+            references(pos(1, "rcs = [])")),
+            vec![(0, 2, "\"srcs\""), (1, 5, "srcs"), (1, 6, "srcs")]
+        );
+    }
+
+    #[test]
+    fn test_struct_field_references() {
+        assert_eq!(
+            references(pos(1, "rcs\n")),
+            vec![(1, 8, "srcs"), (1, 9, "srcs")]
+        );
+    }
+
+    #[test]
+    fn test_rename_provider_field() {
+        let rename = snapshot()
+            .rename(pos(1, "rcs + bar"), "sources")
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        assert_eq!(rename.exported_from, Some(FileId(0)));
+        let renamed = rename
+            .file_edits
+            .iter()
+            .map(|file_edit| {
+                let file_id = match file_edit.target {
+                    EditTarget::File(file_id) => file_id,
+                    _ => panic!("expected a file edit"),
+                };
+                let mut text = FILES[file_id.0 as usize].1.to_string();
+                for edit in file_edit.edits.iter().rev() {
+                    text.replace_range(std::ops::Range::<usize>::from(edit.range), &edit.new_text);
+                }
+                text
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            renamed,
+            vec![
+                DEFS.replacen("\"srcs\"", "\"sources\"", 1).replacen(
+                    "srcs = []",
+                    "sources = []",
+                    1
+                ),
+                USER.replacen("FooInfo(srcs", "FooInfo(sources", 1)
+                    .replacen("foo.srcs", "foo.sources", 1),
+            ]
+        );
+    }
+}
@@ -0,0 +1,51 @@
+use starpls_common::{Db as _, FileId};
+use starpls_hir::diagnostics_for_file;
+
+use crate::{load_graph, Database};
+
+/// Summary statistics about a single file, used to track the health of a workspace.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FileStats {
+    /// The number of `load` statements in the file that resolve to another file.
+    pub resolved_load_count: usize,
+    /// The number of syntax errors in the file.
+    pub syntax_error_count: usize,
+}
+
+pub(crate) fn file_stats(db: &Database, file_id: FileId) -> Option<FileStats> {
+    let file = db.get_file(file_id)?;
+    Some(FileStats {
+        resolved_load_count: load_graph::direct_loadees(db, file).len(),
+        syntax_error_count: diagnostics_for_file(db, file).count(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use starpls_common::{Dialect, FileId};
+
+    use crate::{AnalysisSnapshot, FileStats};
+
+    #[test]
+    fn test_file_stats() {
+        let files = [
+            (
+                "a.star",
+                "load(\"b.star\", \"b\")\nload(\"missing.star\", \"c\")\nx = (\n",
+            ),
+            ("b.star", "b = 1\n"),
+        ];
+        let snap = AnalysisSnapshot::from_files(&files, Dialect::Standard);
+
+        let stats = snap.file_stats(FileId(0)).unwrap().unwrap();
+        assert_eq!(stats.resolved_load_count, 1);
+        assert!(stats.syntax_error_count > 0);
+        assert_eq!(
+            snap.file_stats(FileId(1)).unwrap().unwrap(),
+            FileStats {
+                resolved_load_count: 0,
+                syntax_error_count: 0,
+            }
+        );
+    }
+}
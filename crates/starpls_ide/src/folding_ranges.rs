@@ -0,0 +1,198 @@
+//! Finds the regions of a file that editors can fold: `def` statements, the arguments of calls
+//! (so that long `deps = [...]` lists collapse along with their call), list and dict literals,
+//! and blocks of consecutive comment lines. Only regions spanning more than one line are
+//! returned.
+//!
+//! Folding ranges only depend on the syntax tree, so they're still available in files that fail
+//! analysis or have syntax errors.
+
+use starpls_common::{parse, Db as _, FileId};
+use starpls_syntax::{SyntaxKind, SyntaxNode, SyntaxToken, TextRange};
+
+use crate::Database;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FoldingRangeKind {
+    Code,
+    Comment,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FoldingRange {
+    pub range: TextRange,
+    pub kind: FoldingRangeKind,
+}
+
+pub(crate) fn folding_ranges(db: &Database, file_id: FileId) -> Option<Vec<FoldingRange>> {
+    let file = db.get_file(file_id)?;
+    let root = parse(db, file).syntax(db);
+    let text = root.text().to_string();
+    let mut ranges = Vec::new();
+    for node in root.descendants() {
+        if !matches!(
+            node.kind(),
+            SyntaxKind::DEF_STMT
+                | SyntaxKind::ARGUMENTS
+                | SyntaxKind::LIST_EXPR
+                | SyntaxKind::LIST_COMP
+                | SyntaxKind::DICT_EXPR
+                | SyntaxKind::DICT_COMP
+        ) {
+            continue;
+        }
+        if let Some(range) = code_range(&node).filter(|range| text[*range].contains('\n')) {
+            ranges.push(FoldingRange {
+                range,
+                kind: FoldingRangeKind::Code,
+            });
+        }
+    }
+    ranges.extend(
+        comment_blocks(&root, &text)
+            .into_iter()
+            .map(|range| FoldingRange {
+                range,
+                kind: FoldingRangeKind::Comment,
+            }),
+    );
+    ranges.sort_by_key(|range| (range.range.start(), std::cmp::Reverse(range.range.end())));
+    Some(ranges)
+}
+
+/// Returns the range of a node from its first token to its last, excluding comments and line
+/// breaks, which may belong to the node without being part of the code it contains.
+pub(crate) fn code_range(node: &SyntaxNode) -> Option<TextRange> {
+    let mut tokens = node
+        .descendants_with_tokens()
+        .filter_map(|element| element.into_token())
+        .filter(|token| {
+            !token.kind().is_trivia_token()
+                && !matches!(
+                    token.kind(),
+                    SyntaxKind::NEWLINE | SyntaxKind::INDENT | SyntaxKind::DEDENT
+                )
+        });
+    let first = tokens.next()?;
+    let last = tokens.last().unwrap_or_else(|| first.clone());
+    Some(first.text_range().cover(last.text_range()))
+}
+
+/// Groups the comments that are on lines of their own into blocks of consecutive lines.
+fn comment_blocks(root: &SyntaxNode, text: &str) -> Vec<TextRange> {
+    let mut blocks: Vec<(TextRange, usize)> = Vec::new();
+    let mut prev: Option<SyntaxToken> = None;
+    for comment in root
+        .descendants_with_tokens()
+        .filter_map(|element| element.into_token())
+        .filter(|token| token.kind() == SyntaxKind::COMMENT)
+    {
+        let start = usize::from(comment.text_range().start());
+        let line_start = text[..start].rfind('\n').map_or(0, |pos| pos + 1);
+        if !text[line_start..start].trim().is_empty() {
+            prev = None;
+            continue;
+        }
+        let continues_block = prev.as_ref().map_or(false, |prev| {
+            let between = &text[usize::from(prev.text_range().end())..start];
+            between.trim().is_empty() && between.matches('\n').count() == 1
+        });
+        match blocks.last_mut() {
+            Some((range, count)) if continues_block => {
+                *range = range.cover(comment.text_range());
+                *count += 1;
+            }
+            _ => blocks.push((comment.text_range(), 1)),
+        }
+        prev = Some(comment);
+    }
+    blocks
+        .into_iter()
+        .filter(|(_, count)| *count > 1)
+        .map(|(range, _)| range)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use starpls_bazel::APIContext;
+    use starpls_common::Dialect;
+
+    use super::FoldingRangeKind;
+    use crate::AnalysisSnapshot;
+
+    fn check(contents: &str, expected: &[(FoldingRangeKind, &str)]) {
+        let (snap, file_id) =
+            AnalysisSnapshot::from_single_file(contents, Dialect::Bazel, Some(APIContext::Bzl));
+        let actual = snap
+            .folding_ranges(file_id)
+            .unwrap()
+            .unwrap()
+            .into_iter()
+            .map(|range| (range.kind, &contents[range.range]))
+            .collect::<Vec<_>>();
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_def_and_comments() {
+        check(
+            r#"# First line.
+# Second line.
+
+x = 1  # Trailing comment.
+# Lone comment.
+
+def f(a):
+    if a:
+        return [
+            a,
+        ]
+    return None
+"#,
+            &[
+                (FoldingRangeKind::Comment, "# First line.\n# Second line."),
+                (
+                    FoldingRangeKind::Code,
+                    "def f(a):\n    if a:\n        return [\n            a,\n        ]\n    return None",
+                ),
+                (FoldingRangeKind::Code, "[\n            a,\n        ]"),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_calls() {
+        check(
+            r#"cc_library(
+    name = "foo",
+    deps = [
+        ":bar",
+        ":baz",
+    ],
+    copts = ["-O2"],
+)
+"#,
+            &[
+                (
+                    FoldingRangeKind::Code,
+                    "(\n    name = \"foo\",\n    deps = [\n        \":bar\",\n        \":baz\",\n    ],\n    copts = [\"-O2\"],\n)",
+                ),
+                (
+                    FoldingRangeKind::Code,
+                    "[\n        \":bar\",\n        \":baz\",\n    ]",
+                ),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_analysis_errors() {
+        check(
+            "def f():\n    return undefined + \"a\" + 1\n",
+            &[(
+                FoldingRangeKind::Code,
+                "def f():\n    return undefined + \"a\" + 1",
+            )],
+        );
+    }
+}
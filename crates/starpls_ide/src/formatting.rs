@@ -0,0 +1,177 @@
+//! Formats documents with `starpls_fmt`, using Buildifier's `BUILD` file rules for `BUILD`,
+//! `WORKSPACE`, `MODULE.bazel`, and `REPO.bazel` files, and its `.bzl` rules for everything else.
+//!
+//! Rather than replacing the whole document, there's an edit for each top-level statement that
+//! changed, covering the text between its first and last changed characters. This way, editors
+//! keep the undo history, cursors, and folds of the rest of the file in place, which matters for
+//! format-on-save in large `BUILD` files.
+
+use starpls_bazel::APIContext;
+use starpls_common::{Db as _, File, FileId, FileRange};
+use starpls_fmt::FormatMode;
+use starpls_syntax::{TextRange, TextSize};
+
+use crate::{Database, TextEdit};
+
+pub(crate) fn format(db: &Database, file_id: FileId) -> Option<Vec<TextEdit>> {
+    let file = db.get_file(file_id)?;
+    let contents: &str = file.contents(db);
+    let pieces = match db.buildifier_tables.as_deref() {
+        Some(tables) => {
+            starpls_fmt::format_statements_with_tables(contents, mode(db, file), tables)?
+        }
+        None => starpls_fmt::format_statements(contents, mode(db, file))?,
+    };
+    Some(
+        pieces
+            .into_iter()
+            .filter_map(|(range, formatted)| minimal_edit(contents, range, &formatted))
+            .collect(),
+    )
+}
+
+pub(crate) fn format_range(
+    db: &Database,
+    FileRange { file_id, range }: FileRange,
+) -> Option<Vec<TextEdit>> {
+    let file = db.get_file(file_id)?;
+    let contents: &str = file.contents(db);
+    let (range, formatted) = match db.buildifier_tables.as_deref() {
+        Some(tables) => {
+            starpls_fmt::format_range_with_tables(contents, range, mode(db, file), tables)?
+        }
+        None => starpls_fmt::format_range(contents, range, mode(db, file))?,
+    };
+    Some(
+        minimal_edit(contents, range, &formatted)
+            .into_iter()
+            .collect(),
+    )
+}
+
+fn mode(db: &Database, file: File) -> FormatMode {
+    match file.api_context(db) {
+        Some(APIContext::Build | APIContext::Module | APIContext::Repo | APIContext::Workspace) => {
+            FormatMode::Build
+        }
+        _ => FormatMode::Bzl,
+    }
+}
+
+/// Returns an edit that replaces `range` of `contents` with `new_text`, minus their common prefix
+/// and suffix.
+pub fn minimal_edit(contents: &str, range: TextRange, new_text: &str) -> Option<TextEdit> {
+    let old_text = &contents[range];
+    let prefix = old_text
+        .char_indices()
+        .zip(new_text.chars())
+        .find(|((_, a), b)| a != b)
+        .map_or(old_text.len().min(new_text.len()), |((pos, _), _)| pos);
+    let suffix = old_text[prefix..]
+        .chars()
+        .rev()
+        .zip(new_text[prefix..].chars().rev())
+        .take_while(|(a, b)| a == b)
+        .map(|(a, _)| a.len_utf8())
+        .sum::<usize>();
+    if prefix == old_text.len() && prefix == new_text.len() {
+        return None;
+    }
+    let start = range.start() + TextSize::from(prefix as u32);
+    let end = range.end() - TextSize::from(suffix as u32);
+    Some(TextEdit {
+        range: TextRange::new(start, end),
+        new_text: new_text[prefix..new_text.len() - suffix].to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use starpls_bazel::APIContext;
+    use starpls_common::{Dialect, FileRange};
+    use starpls_syntax::{TextRange, TextSize};
+
+    use crate::{AnalysisSnapshot, TextEdit};
+
+    fn range(start: u32, end: u32) -> TextRange {
+        TextRange::new(TextSize::from(start), TextSize::from(end))
+    }
+
+    #[test]
+    fn test_format_build_file() {
+        let (snap, file_id) = AnalysisSnapshot::from_single_file(
+            "foo(name = 'a', srcs = ['a', 'b'])\n",
+            Dialect::Bazel,
+            Some(APIContext::Build),
+        );
+        assert_eq!(
+            snap.format(file_id).unwrap(),
+            Some(vec![TextEdit {
+                range: range(4, 33),
+                new_text:
+                    "\n    name = \"a\",\n    srcs = [\n        \"a\",\n        \"b\",\n    ],\n"
+                        .to_string(),
+            }])
+        );
+    }
+
+    #[test]
+    fn test_format_bzl_file() {
+        let (snap, file_id) = AnalysisSnapshot::from_single_file(
+            "foo(name = 'a', srcs = ['a', 'b'])\n",
+            Dialect::Bazel,
+            Some(APIContext::Bzl),
+        );
+        assert_eq!(
+            snap.format(file_id).unwrap(),
+            Some(vec![TextEdit {
+                range: range(11, 32),
+                new_text: "\"a\", srcs = [\"a\", \"b\"".to_string(),
+            }])
+        );
+    }
+
+    #[test]
+    fn test_format_changed_statements() {
+        let contents = "a=1\n\nb = 2\n\nc=3\n";
+        let (snap, file_id) =
+            AnalysisSnapshot::from_single_file(contents, Dialect::Bazel, Some(APIContext::Bzl));
+        assert_eq!(
+            snap.format(file_id).unwrap(),
+            Some(vec![
+                TextEdit {
+                    range: range(1, 2),
+                    new_text: " = ".to_string(),
+                },
+                TextEdit {
+                    range: range(13, 14),
+                    new_text: " = ".to_string(),
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn test_format_formatted_file() {
+        let (snap, file_id) =
+            AnalysisSnapshot::from_single_file("x = 1\n", Dialect::Bazel, Some(APIContext::Bzl));
+        assert_eq!(snap.format(file_id).unwrap(), Some(Vec::new()));
+    }
+
+    #[test]
+    fn test_format_range() {
+        let (snap, file_id) =
+            AnalysisSnapshot::from_single_file("a=1\nb=2\n", Dialect::Bazel, Some(APIContext::Bzl));
+        assert_eq!(
+            snap.format_range(FileRange {
+                file_id,
+                range: range(4, 4),
+            })
+            .unwrap(),
+            Some(vec![TextEdit {
+                range: range(5, 6),
+                new_text: " = ".to_string(),
+            }])
+        );
+    }
+}
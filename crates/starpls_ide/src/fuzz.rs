@@ -0,0 +1,135 @@
+//! An entry point for fuzzing the analysis with arbitrary input.
+
+use starpls_bazel::APIContext;
+use starpls_common::Dialect;
+use starpls_syntax::parse_any;
+
+use crate::AnalysisSnapshot;
+
+/// Parses, lowers, and type checks arbitrary bytes as a single file in each dialect, discarding
+/// the results. Invalid UTF-8 is replaced before analysis. This must not panic on any input, so
+/// any panic that escapes it is a bug.
+pub fn analyze_arbitrary_input(input: &[u8]) {
+    parse_any(input);
+    let text = String::from_utf8_lossy(input);
+    for (dialect, api_context) in [
+        (Dialect::Standard, None),
+        (Dialect::Bazel, Some(APIContext::Bzl)),
+        (Dialect::Bazel, Some(APIContext::Build)),
+    ] {
+        let (snap, file_id) = AnalysisSnapshot::from_single_file(&text, dialect, api_context);
+        let _ = snap.lower(file_id);
+        let _ = snap.diagnostics(file_id);
+        let _ = snap.document_symbols(file_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::analyze_arbitrary_input;
+
+    /// Fragments that inputs are built from, so that most inputs get past the lexer and exercise
+    /// the parser and lowering instead.
+    const FRAGMENTS: &[&str] = &[
+        "def ",
+        "f",
+        "x",
+        "(",
+        ")",
+        "[",
+        "]",
+        "{",
+        "}",
+        ":",
+        ",",
+        "=",
+        "+=",
+        "*",
+        "**",
+        ".",
+        "\n",
+        "    ",
+        "\t",
+        "if ",
+        "elif ",
+        "else",
+        "for ",
+        " in ",
+        "return",
+        "pass",
+        "lambda ",
+        "load(",
+        "\"a.bzl\"",
+        "'s'",
+        "\"\"\"",
+        "r\"\\",
+        "1",
+        "0x",
+        "1.5e",
+        "# type: ",
+        "->",
+        "not ",
+        "and",
+        "struct(",
+        "provider(",
+        "\\",
+        "#",
+        "\u{fffd}",
+        "\u{00e9}",
+    ];
+
+    /// A xorshift generator, so that failures can be reproduced from the seed alone.
+    struct Rng(u64);
+
+    impl Rng {
+        fn next_u64(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+    }
+
+    fn env_or(key: &str, default: u64) -> u64 {
+        std::env::var(key)
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(default)
+    }
+
+    /// Analyzes randomly generated inputs. Set `STARPLS_FUZZ_SEED` and `STARPLS_FUZZ_ITERATIONS`
+    /// to run against other or more inputs.
+    #[test]
+    fn test_arbitrary_input() {
+        let seed = env_or("STARPLS_FUZZ_SEED", 0x5eed).max(1);
+        let iterations = env_or("STARPLS_FUZZ_ITERATIONS", 100);
+        let mut rng = Rng(seed);
+        for iteration in 0..iterations {
+            let mut input = Vec::new();
+            for _ in 0..rng.next_u64() % 64 {
+                // Mix in raw bytes, which may not be valid UTF-8, every so often.
+                if rng.next_u64() % 8 == 0 {
+                    input.push(rng.next_u64() as u8);
+                } else {
+                    let fragment = FRAGMENTS[rng.next_u64() as usize % FRAGMENTS.len()];
+                    input.extend_from_slice(fragment.as_bytes());
+                }
+            }
+            let res = std::panic::catch_unwind(|| analyze_arbitrary_input(&input));
+            assert!(
+                res.is_ok(),
+                "panicked on iteration {} with seed {}, input: {:?}",
+                iteration,
+                seed,
+                String::from_utf8_lossy(&input)
+            );
+        }
+    }
+
+    #[test]
+    fn test_invalid_utf8() {
+        analyze_arbitrary_input(b"x = \"\xff\xfe\"\ndef \xc3(");
+        analyze_arbitrary_input(b"");
+        analyze_arbitrary_input(b"\x00\x00\n\t\t)\n");
+    }
+}
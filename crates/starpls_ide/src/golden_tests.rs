@@ -0,0 +1,199 @@
+//! Golden tests driven by markers in fixture files.
+//!
+//! Each file in `test_data/golden` is analyzed as a single file, and every comment of the form
+//! `# ^hover`, `# ^complete <prefix>` or `# ^goto` requests that feature at the column of the
+//! caret, on the closest line above the marker that isn't itself a marker. The results are
+//! rendered as text and compared against the file with the same name plus a `.golden` suffix.
+//! Running the tests with `UPDATE_EXPECT=1` rewrites the golden files instead.
+//!
+//! Since the `#` comes first, a caret can't point at the first column of a line. Pointing at the
+//! end of a name works just as well for hovers, and is where the cursor is for completions.
+//!
+//! Files ending in `.bzl` are analyzed as Bazel `.bzl` files, and all others as standard
+//! Starlark.
+
+use std::{fmt::Write, fs, path::PathBuf};
+
+use expect_test::expect_file;
+use starpls_bazel::APIContext;
+use starpls_common::Dialect;
+use starpls_syntax::TextSize;
+
+use crate::{AnalysisSnapshot, FilePosition, LocationLink};
+
+const GOLDEN_SUFFIX: &str = ".golden";
+
+struct Marker {
+    kind: String,
+    arg: String,
+    line: usize,
+    column: usize,
+}
+
+/// Finds the markers in the given text, along with the positions that they point at.
+fn parse_markers(text: &str) -> Vec<Marker> {
+    let mut markers = Vec::new();
+    let mut target_line = None;
+    for (line_number, line) in text.lines().enumerate() {
+        let trimmed = line.trim_start();
+        let rest = match trimmed.strip_prefix('#') {
+            Some(rest) if rest.trim_start().starts_with('^') => rest,
+            _ => {
+                target_line = Some(line_number);
+                continue;
+            }
+        };
+        let column = line.len() - trimmed.len() + 1 + (rest.len() - rest.trim_start().len());
+        let mut parts = rest.trim_start()[1..].splitn(2, char::is_whitespace);
+        let kind = parts.next().unwrap_or_default().to_string();
+        let arg = parts.next().unwrap_or_default().trim().to_string();
+        let line = target_line
+            .unwrap_or_else(|| panic!("marker on line {} has no line above it", line_number + 1));
+        markers.push(Marker {
+            kind,
+            arg,
+            line,
+            column,
+        });
+    }
+    markers
+}
+
+fn offset(text: &str, line: usize, column: usize) -> TextSize {
+    let line_start = text
+        .split_inclusive('\n')
+        .take(line)
+        .map(|line| line.len())
+        .sum::<usize>();
+    TextSize::from((line_start + column) as u32)
+}
+
+fn render(text: &str, file_name: &str) -> String {
+    let (dialect, api_context) = if file_name.ends_with(".bzl") {
+        (Dialect::Bazel, Some(APIContext::Bzl))
+    } else {
+        (Dialect::Standard, None)
+    };
+    let (snap, file_id) = AnalysisSnapshot::from_single_file(text, dialect, api_context);
+
+    let mut actual = String::new();
+    for marker in parse_markers(text) {
+        let pos = FilePosition {
+            file_id,
+            pos: offset(text, marker.line, marker.column),
+        };
+        write!(
+            actual,
+            "=== {} {}:{}",
+            marker.kind,
+            marker.line + 1,
+            marker.column + 1
+        )
+        .unwrap();
+        if !marker.arg.is_empty() {
+            write!(actual, " {}", marker.arg).unwrap();
+        }
+        actual.push('\n');
+
+        match marker.kind.as_str() {
+            "hover" => match snap.hover(pos).unwrap() {
+                Some(hover) => actual.push_str(&hover.contents.value),
+                None => actual.push_str("(none)\n"),
+            },
+            "complete" => {
+                let mut items = snap
+                    .completion(pos, None)
+                    .unwrap()
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter(|item| item.label.starts_with(&marker.arg))
+                    .collect::<Vec<_>>();
+                items.sort_by_key(|item| (item.sort_text(), item.label.clone()));
+                for item in items {
+                    writeln!(actual, "{} ({:?})", item.label, item.kind).unwrap();
+                }
+            }
+            "goto" => {
+                let mut targets = snap
+                    .goto_definition(pos)
+                    .unwrap()
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|target| match target {
+                        LocationLink::Local { target_range, .. } => {
+                            format!("{:?} {}", target_range, &text[target_range])
+                        }
+                        LocationLink::External { target_path, .. } => {
+                            target_path.display().to_string()
+                        }
+                        LocationLink::Builtin {
+                            stub, target_line, ..
+                        } => format!(
+                            "{}:{} {}",
+                            stub.name,
+                            target_line + 1,
+                            stub.contents
+                                .lines()
+                                .nth(target_line as usize)
+                                .unwrap_or_default()
+                        ),
+                    })
+                    .collect::<Vec<_>>();
+                targets.sort();
+                if targets.is_empty() {
+                    actual.push_str("(none)\n");
+                }
+                for target in targets {
+                    writeln!(actual, "{}", target).unwrap();
+                }
+            }
+            kind => panic!("{}: unknown marker kind {:?}", file_name, kind),
+        }
+        actual.push('\n');
+    }
+    actual
+}
+
+#[test]
+fn test_golden_files() {
+    let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("test_data/golden");
+    let mut paths = fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.is_file() && !path.to_string_lossy().ends_with(GOLDEN_SUFFIX))
+        .collect::<Vec<_>>();
+    paths.sort();
+    assert!(!paths.is_empty(), "no fixtures found in {}", dir.display());
+
+    for path in paths {
+        let file_name = path.file_name().unwrap().to_string_lossy().to_string();
+        let text = fs::read_to_string(&path).unwrap();
+        let golden = dir.join(format!("{}{}", file_name, GOLDEN_SUFFIX));
+        expect_file![golden].assert_eq(&render(&text, &file_name));
+    }
+}
+
+#[test]
+fn test_parse_markers() {
+    let markers =
+        parse_markers("x = 1\n# ^hover\n#    ^complete fo\ndef f():\n    y\n    #^goto\n");
+    let actual = markers
+        .iter()
+        .map(|marker| {
+            (
+                marker.kind.as_str(),
+                marker.arg.as_str(),
+                marker.line,
+                marker.column,
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        actual,
+        vec![
+            ("hover", "", 0, 2),
+            ("complete", "fo", 0, 5),
+            ("goto", "", 4, 5),
+        ]
+    );
+}
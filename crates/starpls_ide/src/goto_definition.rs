@@ -1,11 +1,17 @@
-use starpls_common::{parse as parse_query, Db};
+use starpls_common::{parse as parse_query, Db, FileRange};
 use starpls_hir::{Name, ScopeDef, Semantics};
 use starpls_syntax::{
     ast::{self, AstNode},
     T,
 };
 
-use crate::{util::pick_best_token, Database, FilePosition, LocationLink, ResolvedPath};
+use crate::{
+    builtin_stubs, dict_keys,
+    labels::{self, LabelTarget},
+    rule_attributes,
+    util::pick_best_token,
+    Database, FilePosition, LocationLink,
+};
 
 pub(crate) fn goto_definition(
     db: &Database,
@@ -24,37 +30,62 @@ pub(crate) fn goto_definition(
 
     if let Some(name_ref) = ast::NameRef::cast(parent.clone()) {
         let name = Name::from_ast_node(name_ref.clone());
-        let scope =
-            sema.scope_for_expr(file, &ast::Expression::cast(name_ref.syntax().clone())?)?;
-        return Some(
-            scope
-                .resolve_name(&name)?
-                .into_iter()
-                .flat_map(|def| match def {
-                    ScopeDef::LoadItem(load_item) => {
-                        let def = sema.def_for_load_item(&load_item)?;
-                        let range = def.value.syntax_node_ptr(db, def.file)?.text_range();
-                        Some(LocationLink::Local {
-                            origin_selection_range: None,
-                            target_range: range.clone(),
-                            target_selection_range: range.clone(),
-                            target_file_id: def.file.id(db),
-                        })
-                    }
-                    _ => def
-                        .syntax_node_ptr(db, file)
-                        .map(|ptr| LocationLink::Local {
-                            origin_selection_range: None,
-                            target_range: ptr.text_range(),
-                            target_selection_range: ptr.text_range(),
-                            target_file_id: file_id,
-                        }),
-                })
-                .collect(),
-        );
+        let expr = ast::Expression::cast(name_ref.syntax().clone())?;
+        let scope = sema.scope_for_expr(file, &expr)?;
+        let defs = scope.resolve_name(&name)?;
+        let links = defs
+            .iter()
+            .flat_map(|def| match def {
+                ScopeDef::LoadItem(load_item) => {
+                    let def = sema.def_for_load_item(load_item)?;
+                    let range = def.value.syntax_node_ptr(db, def.file)?.text_range();
+                    Some(LocationLink::Local {
+                        origin_selection_range: None,
+                        target_range: range.clone(),
+                        target_selection_range: range.clone(),
+                        target_file_id: def.file.id(db),
+                    })
+                }
+                _ => def
+                    .syntax_node_ptr(db, file)
+                    .map(|ptr| LocationLink::Local {
+                        origin_selection_range: None,
+                        target_range: ptr.text_range(),
+                        target_selection_range: ptr.text_range(),
+                        target_file_id: file_id,
+                    }),
+            })
+            .collect::<Vec<_>>();
+        if !links.is_empty() || defs.iter().any(|def| def.is_user_defined()) {
+            return Some(links);
+        }
+
+        // Builtin functions are found in the stub listing every global, and builtin variables
+        // (e.g. `native`) in the stub of their type.
+        let (stub, target_line) = match defs.first()? {
+            ScopeDef::Callable(_) => builtin_stubs::global_stub(db, file, &name)?,
+            _ => builtin_stubs::type_stub(db, &sema.type_of_expr(file, &expr)?, None)?,
+        };
+        return Some(vec![LocationLink::Builtin {
+            origin_selection_range: None,
+            stub,
+            target_line,
+        }]);
     }
 
     if let Some(name) = ast::Name::cast(parent.clone()) {
+        // Keyword arguments of rules link to the declarations of the attributes they set.
+        if let Some(FileRange { file_id, range }) =
+            rule_attributes::attr_definition(db, file, &name)
+        {
+            return Some(vec![LocationLink::Local {
+                origin_selection_range: None,
+                target_range: range,
+                target_selection_range: range,
+                target_file_id: file_id,
+            }]);
+        }
+
         let dot_expr = ast::DotExpr::cast(name.syntax().parent()?)?;
         let ty = sema.type_of_expr(file, &dot_expr.expr()?)?;
 
@@ -109,6 +140,14 @@ pub(crate) fn goto_definition(
                     })
             });
         }
+
+        // Otherwise, the field may be a builtin, e.g. `ctx.actions.run`.
+        let (stub, target_line) = builtin_stubs::type_stub(db, &ty, Some(token.text()))?;
+        return Some(vec![LocationLink::Builtin {
+            origin_selection_range: None,
+            stub,
+            target_line,
+        }]);
     }
 
     if let Some(load_module) = ast::LoadModule::cast(parent.clone()) {
@@ -134,82 +173,76 @@ pub(crate) fn goto_definition(
         }]);
     }
 
-    if let Some(lit) = ast::LiteralExpr::cast(parent) {
-        let value = match lit.kind() {
-            ast::LiteralKind::String(s) => s.value()?,
-            _ => return None,
-        };
-        let resolved_path = db.resolve_path(&value, file.dialect(db), file_id).ok()??;
-        return match resolved_path {
-            ResolvedPath::Source { path } => path.try_exists().ok()?.then(|| {
-                vec![LocationLink::External {
-                    origin_selection_range: Some(token.text_range()),
-                    target_path: path,
-                }]
-            }),
-            ResolvedPath::BuildTarget {
-                build_file: build_file_id,
-                target,
-                ..
-            } => {
-                let build_file = db.get_file(build_file_id)?;
-                let parse = parse_query(db, build_file).syntax(db);
-                let call_expr = parse
-                    .children()
-                    .filter_map(ast::CallExpr::cast)
-                    .find(|expr| {
-                        expr.arguments()
-                            .into_iter()
-                            .flat_map(|args| args.arguments())
-                            .any(|arg| match arg {
-                                ast::Argument::Keyword(arg) => {
-                                    arg.name()
-                                        .and_then(|name| name.name())
-                                        .map(|name| name.text() == "name")
-                                        .unwrap_or_default()
-                                        && arg
-                                            .expr()
-                                            .and_then(|expr| match expr {
-                                                ast::Expression::Literal(expr) => Some(expr),
-                                                _ => None,
-                                            })
-                                            .and_then(|expr| match expr.kind() {
-                                                ast::LiteralKind::String(s) => {
-                                                    s.value().map(|value| &*value == target)
-                                                }
-                                                _ => None,
-                                            })
-                                            .unwrap_or_default()
-                                }
-                                _ => false,
-                            })
-                    })?;
-                let range = call_expr.syntax().text_range();
-                Some(vec![LocationLink::Local {
-                    origin_selection_range: Some(token.text_range()),
-                    target_range: range.clone(),
-                    target_selection_range: range,
-                    target_file_id: build_file_id,
-                }])
-            }
-        };
+    // String keys used as enum values take precedence over labels and paths.
+    if let Some(links) = dict_keys::goto_definition(db, file, &token) {
+        return Some(links);
     }
 
-    None
+    let resolved = labels::resolve_label(db, file, &token)?;
+    match resolved.target {
+        LabelTarget::File(path) => path.try_exists().ok()?.then(|| {
+            vec![LocationLink::External {
+                origin_selection_range: Some(resolved.range),
+                target_path: path,
+            }]
+        }),
+        LabelTarget::Target {
+            build_file, target, ..
+        } => Some(vec![LocationLink::Local {
+            origin_selection_range: Some(resolved.range),
+            target_range: target.range,
+            target_selection_range: target.name_range,
+            target_file_id: build_file,
+        }]),
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use starpls_bazel::APIContext;
-    use starpls_common::Dialect;
-    use starpls_test_util::parse_fixture;
+    use std::sync::Arc;
+
+    use starpls_bazel::{APIContext, Builtins};
+    use starpls_common::{Dialect, FileId};
+    use starpls_syntax::{TextRange, TextSize};
+    use starpls_test_util::{make_test_builtins, parse_fixture, FixtureType};
 
-    use crate::{AnalysisSnapshot, FilePosition, LocationLink};
+    use crate::{Analysis, AnalysisSnapshot, Change, FilePosition, LocationLink, SimpleFileLoader};
 
     fn check_goto_definition(fixture: &str) {
         let (contents, pos, expected) = parse_fixture(fixture);
         let (snap, file_id) =
             AnalysisSnapshot::from_single_file(&contents, Dialect::Bazel, Some(APIContext::Bzl));
+        check(&snap, file_id, pos, expected);
+    }
+
+    /// Like `check_goto_definition`, but with the `rule()` and `attr` builtins defined.
+    fn check_rule_goto_definition(fixture: &str) {
+        let (contents, pos, expected) = parse_fixture(fixture);
+        let file_id = FileId(0);
+        let mut change = Change::default();
+        change.create_file(file_id, Dialect::Bazel, Some(APIContext::Bzl), contents);
+        let mut analysis = Analysis::new(
+            Arc::new(SimpleFileLoader::from_file_set(Default::default())),
+            Default::default(),
+        );
+        analysis.db.set_builtin_defs(
+            Dialect::Bazel,
+            make_test_builtins(
+                vec!["rule".to_string()],
+                vec![("attr".to_string(), "attr".to_string())],
+                vec![FixtureType::new(
+                    "attr",
+                    vec![],
+                    vec!["label_list", "string"],
+                )],
+            ),
+            Builtins::default(),
+        );
+        analysis.apply_change(change);
+        check(&analysis.snapshot(), file_id, pos, expected);
+    }
+
+    fn check(snap: &AnalysisSnapshot, file_id: FileId, pos: TextSize, expected: Vec<TextRange>) {
         let actual = snap
             .goto_definition(FilePosition { file_id, pos })
             .unwrap()
@@ -257,6 +290,18 @@ def f(abc):
         )
     }
 
+    #[test]
+    fn test_param_with_syntax_error() {
+        check_goto_definition(
+            r#"
+def f(abc):
+      #^^
+      if abc
+          print(a$0bc)
+"#,
+        )
+    }
+
     #[test]
     fn test_struct_field() {
         check_goto_definition(
@@ -269,6 +314,46 @@ s.f$0oo
         )
     }
 
+    #[test]
+    fn test_rule_attribute() {
+        check_rule_goto_definition(
+            r#"
+def _impl(ctx):
+    pass
+
+my_rule = rule(
+    implementation = _impl,
+    attrs = {
+        "foo_srcs": attr.label_list(),
+        #^^^^^^^^^
+        "bar": attr.string(),
+    },
+)
+
+my_rule(name = "x", fo$0o_srcs = [])
+"#,
+        )
+    }
+
+    #[test]
+    fn test_rule_attribute_from_variable() {
+        check_rule_goto_definition(
+            r#"
+_ATTRS = {
+    "bar": attr.string(),
+    #^^^^
+}
+
+def _impl(ctx):
+    pass
+
+my_rule = rule(implementation = _impl, attrs = _ATTRS)
+
+my_rule(name = "x", b$0ar = "")
+"#,
+        )
+    }
+
     #[test]
     fn test_provider_field() {
         check_goto_definition(
@@ -0,0 +1,165 @@
+//! Checks that `.bzl` files begin with a required header, e.g. a license comment or a module
+//! docstring template.
+//!
+//! The header is matched line by line, ignoring trailing whitespace and a leading shebang line.
+//! The `{year}` placeholder matches any year, and is filled in with the current year when
+//! inserting the header.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use starpls_bazel::APIContext;
+use starpls_common::{Db as _, Diagnostic, DiagnosticCode, File, FileRange, Severity};
+use starpls_syntax::{TextRange, TextSize};
+
+use crate::{CodeAction, CodeActionKind, Database, TextEdit};
+
+const YEAR_PLACEHOLDER: &str = "{year}";
+
+pub(crate) fn header_diagnostics(db: &Database, file: File) -> Vec<Diagnostic> {
+    match missing_header_range(db, file) {
+        Some(range) => vec![Diagnostic {
+            code: DiagnosticCode::MissingHeader,
+            message: "File is missing the required header".to_string(),
+            severity: Severity::Warning,
+            range: FileRange {
+                file_id: file.id(db),
+                range,
+            },
+        }],
+        None => Vec::new(),
+    }
+}
+
+pub(crate) fn insert_header_action(
+    db: &Database,
+    file: File,
+    range: TextRange,
+) -> Option<CodeAction> {
+    let header = db.diagnostics_options.required_header.as_ref()?;
+    missing_header_range(db, file)?.intersect(range)?;
+
+    // Insert the header after the shebang line, if there is one.
+    let contents = file.contents(db);
+    let offset = if contents.starts_with("#!") {
+        contents.find('\n').map_or(contents.len(), |pos| pos + 1)
+    } else {
+        0
+    };
+    let mut new_text = header
+        .trim_end()
+        .replace(YEAR_PLACEHOLDER, &current_year().to_string());
+    new_text.push('\n');
+    if offset < contents.len() {
+        new_text.push('\n');
+    }
+
+    Some(CodeAction {
+        title: "Insert required header".to_string(),
+        kind: CodeActionKind::QuickFix,
+        edits: vec![TextEdit {
+            range: TextRange::empty(TextSize::from(offset as u32)),
+            new_text,
+        }],
+        file_edits: Vec::new(),
+        command: None,
+        is_preferred: true,
+    })
+}
+
+/// Returns the range to report the missing header at, i.e. the first line of the file, if the
+/// file is expected to have a header but doesn't.
+fn missing_header_range(db: &Database, file: File) -> Option<TextRange> {
+    let header = db.diagnostics_options.required_header.as_ref()?;
+    if !matches!(file.api_context(db), Some(APIContext::Bzl)) {
+        return None;
+    }
+
+    let contents = file.contents(db);
+    if matches_header(contents, header) {
+        return None;
+    }
+
+    let first_line_len = contents.find('\n').unwrap_or(contents.len());
+    Some(TextRange::up_to(TextSize::from(first_line_len as u32)))
+}
+
+fn matches_header(contents: &str, header: &str) -> bool {
+    let mut lines = contents.lines().peekable();
+    if lines.peek().map_or(false, |line| line.starts_with("#!")) {
+        lines.next();
+    }
+
+    let mut header_lines = header.trim_end().lines();
+    header_lines.all(|header_line| {
+        lines.next().map_or(false, |line| {
+            matches_header_line(line.trim_end(), header_line.trim_end())
+        })
+    })
+}
+
+fn matches_header_line(line: &str, header_line: &str) -> bool {
+    let mut parts = header_line.split(YEAR_PLACEHOLDER);
+    let mut rest = match parts.next().and_then(|first| line.strip_prefix(first)) {
+        Some(rest) => rest,
+        None => return false,
+    };
+
+    for part in parts {
+        let digits = rest.len() - rest.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+        if digits == 0 {
+            return false;
+        }
+        rest = match rest[digits..].strip_prefix(part) {
+            Some(rest) => rest,
+            None => return false,
+        };
+    }
+
+    rest.is_empty()
+}
+
+fn current_year() -> i64 {
+    let days = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs() / 86400) as i64;
+
+    // Convert the number of days since the Unix epoch to a year in the proleptic Gregorian
+    // calendar, see http://howardhinnant.github.io/date_algorithms.html#civil_from_days.
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let year = yoe + era * 400;
+    if mp >= 10 {
+        year + 1
+    } else {
+        year
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::matches_header;
+
+    const HEADER: &str = "# Copyright {year} Example Inc.\n# SPDX-License-Identifier: Apache-2.0\n";
+
+    #[test]
+    fn test_matches_header() {
+        assert!(matches_header(
+            "# Copyright 2024 Example Inc.\n# SPDX-License-Identifier: Apache-2.0\n\nx = 1\n",
+            HEADER
+        ));
+        assert!(matches_header(
+            "#!/usr/bin/env starlark\n# Copyright 2019 Example Inc.  \n# SPDX-License-Identifier: Apache-2.0",
+            HEADER
+        ));
+        assert!(!matches_header(
+            "# Copyright Example Inc.\n# SPDX-License-Identifier: Apache-2.0\n",
+            HEADER
+        ));
+        assert!(!matches_header("# Copyright 2024 Example Inc.\n", HEADER));
+        assert!(!matches_header("x = 1\n", HEADER));
+    }
+}
@@ -1,14 +1,18 @@
 use std::fmt::Write;
 
-use starpls_common::{parse, Db as _};
-use starpls_hir::{DisplayWithDb, Semantics, Type};
+use starpls_bazel::APIContext;
+use starpls_common::{parse, Db as _, FileRange};
+use starpls_hir::{DisplayWithDb, Semantics, StringKind, Type};
 use starpls_syntax::{
-    ast::{self, AstNode},
+    ast::{self, AstNode, AstToken},
     SyntaxKind::*,
     TextRange, T,
 };
 
 use crate::{
+    build_settings, duplicates, labels,
+    macro_targets::macro_targets,
+    module_deps, rule_attributes,
     util::{pick_best_token, unindent_doc},
     Database, FilePosition,
 };
@@ -22,6 +26,8 @@ pub struct Markup {
 pub struct Hover {
     pub contents: Markup,
     pub range: Option<TextRange>,
+    /// Where the hovered symbol is defined, if it's worth linking to from the hover.
+    pub definition: Option<FileRange>,
 }
 
 impl From<String> for Hover {
@@ -29,6 +35,7 @@ impl From<String> for Hover {
         Self {
             contents: Markup { value },
             range: None,
+            definition: None,
         }
     }
 }
@@ -44,6 +51,41 @@ pub(crate) fn hover(db: &Database, FilePosition { file_id, pos }: FilePosition)
         _ => 1,
     })?;
 
+    // Check for hovers over the names and versions of `bazel_dep`s.
+    if file.api_context(db) == Some(APIContext::Module) {
+        if let Some(hover) = module_deps::hover(db, file, &token) {
+            return Some(hover);
+        }
+    }
+
+    if let Some(hover) = duplicates::hover(db, file, &token) {
+        return Some(hover);
+    }
+
+    if let Some(hover) = build_settings::hover(&token) {
+        return Some(hover);
+    }
+
+    if let Some(hover) = labels::hover(db, file, &token) {
+        return Some(hover);
+    }
+
+    // Strings that don't resolve to anything are still described by how they're used, e.g. as
+    // glob patterns or format strings.
+    if let Some(string) = ast::String::cast(token.clone()) {
+        let description = match sema.classify_string(file, &string) {
+            StringKind::Label => Some("label"),
+            StringKind::FilePath => Some("file"),
+            StringKind::GlobPattern => Some("glob pattern"),
+            StringKind::FormatString => Some("format string"),
+            StringKind::Visibility => Some("visibility"),
+            StringKind::Plain => None,
+        };
+        if let Some(description) = description {
+            return Some(format!("```python\n({}) {}\n```\n", description, token.text()).into());
+        }
+    }
+
     // Check for keyword hovers first.
     if token.kind().is_keyword() {
         let text = match token.kind() {
@@ -64,7 +106,26 @@ pub(crate) fn hover(db: &Database, FilePosition { file_id, pos }: FilePosition)
     let parent = token.parent()?;
     if let Some(expr) = ast::NameRef::cast(parent.clone()) {
         let ty = sema.type_of_expr(file, &expr.clone().into())?;
-        return Some(format_for_name(db, expr.name()?.text(), &ty).into());
+        let mut text = format_for_name(db, expr.name()?.text(), &ty);
+
+        // List the targets that macro calls in `BUILD` files declare.
+        let call = expr
+            .syntax()
+            .parent()
+            .and_then(ast::CallExpr::cast)
+            .filter(|call| {
+                file.api_context(db) == Some(APIContext::Build)
+                    && call
+                        .callee()
+                        .map_or(false, |callee| callee.syntax() == expr.syntax())
+            });
+        if let Some(targets) = call.and_then(|call| macro_targets(db, &sema, file, &call)) {
+            text.push_str("\nDeclares the targets:\n");
+            for target in targets {
+                writeln!(text, "- `:{}`", target).ok()?;
+            }
+        }
+        return Some(text.into());
     } else if let Some(name) = ast::Name::cast(parent.clone()) {
         let parent = name.syntax().parent()?;
         let name_token = name.name()?;
@@ -126,6 +187,7 @@ pub(crate) fn hover(db: &Database, FilePosition { file_id, pos }: FilePosition)
                 .and_then(|args| args.syntax().parent())
                 .and_then(|parent| ast::CallExpr::cast(parent))?;
             let func = sema.resolve_call_expr(file, &call)?;
+            let definition = rule_attributes::attr_definition(db, file, &name);
             let (name, param, ty) = func.params(db).into_iter().find_map(|(param, ty)| {
                 let name = param.name(db)?;
                 if name.as_str() == name_text {
@@ -147,7 +209,10 @@ pub(crate) fn hover(db: &Database, FilePosition { file_id, pos }: FilePosition)
                     text.push('\n');
                 }
             }
-            return Some(text.into());
+            return Some(Hover {
+                definition,
+                ..Hover::from(text)
+            });
         }
     } else if let Some(type_) = ast::NamedType::cast(parent.clone()) {
         let ty = sema.resolve_type(&type_)?;
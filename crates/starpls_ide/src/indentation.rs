@@ -0,0 +1,307 @@
+//! Automatic indentation, for editors that don't have a formatter for Starlark or that format on
+//! save only.
+//!
+//! The rules returned by [`indentation_rules`] are regular expressions in the shape that VS Code's
+//! language configuration expects, so that clients can indent lines without a round trip to the
+//! server. They can only look at one line at a time, so on-type formatting uses the syntax tree to
+//! fix up the indentation of a new line once it's been entered, e.g. inside brackets that were
+//! opened a few lines earlier.
+
+use starpls_common::{parse, Db as _, File};
+use starpls_syntax::{SyntaxKind, TextRange, TextSize, T};
+
+use crate::{Database, FilePosition, TextEdit};
+
+/// Keywords that start a block when the line ends with a colon.
+const BLOCK_KEYWORDS: &[&str] = &["def", "elif", "else", "for", "if"];
+
+/// Keywords that continue the block started by an `if` or `elif` at the same indentation.
+const CONTINUATION_KEYWORDS: &[&str] = &["elif", "else"];
+
+/// Keywords that end the current block, so that the next line is dedented.
+const TERMINATOR_KEYWORDS: &[&str] = &["break", "continue", "pass", "return"];
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IndentationRules {
+    /// Matches lines after which the next line is indented.
+    pub increase_indent_pattern: String,
+    /// Matches lines that are dedented as they're typed.
+    pub decrease_indent_pattern: String,
+    pub on_enter_rules: Vec<OnEnterRule>,
+}
+
+/// Adjusts the indentation of the line entered after a line that matches `before_text`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OnEnterRule {
+    pub before_text: String,
+    pub action: IndentAction,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IndentAction {
+    Indent,
+    Outdent,
+}
+
+pub fn indentation_rules() -> IndentationRules {
+    let block_keywords = BLOCK_KEYWORDS.join("|");
+    let continuation_keywords = CONTINUATION_KEYWORDS.join("|");
+    IndentationRules {
+        increase_indent_pattern: format!(r"^(\s*({})\b.*:|.*[\(\[\{{])\s*(#.*)?$", block_keywords),
+        decrease_indent_pattern: format!(r"^\s*(({})\b.*:|[\)\]\}}])", continuation_keywords),
+        on_enter_rules: vec![OnEnterRule {
+            before_text: format!(r"^\s*({})\b.*$", TERMINATOR_KEYWORDS.join("|")),
+            action: IndentAction::Outdent,
+        }],
+    }
+}
+
+/// Reindents the line containing the given position after `ch` is typed. A newline reindents the
+/// new line based on the lines before it, and a colon dedents an `else` or `elif` to the `if` that
+/// it belongs to. `default_indent` is used for a single level of indentation if the file doesn't
+/// have any indented lines yet.
+pub(crate) fn on_type_formatting(
+    db: &Database,
+    FilePosition { file_id, pos }: FilePosition,
+    ch: char,
+    default_indent: &str,
+) -> Option<Vec<TextEdit>> {
+    let file = db.get_file(file_id)?;
+    let contents = file.contents(db);
+    let pos = usize::from(pos).min(contents.len());
+    let line_start = contents[..pos].rfind('\n').map_or(0, |offset| offset + 1);
+    let current_indent = indentation(&contents[line_start..]);
+    let unit = indent_unit(contents).unwrap_or(default_indent);
+
+    let new_indent = match ch {
+        '\n' => indent_for_line(db, file, contents, line_start, unit)?,
+        ':' => dedent_continuation(contents, line_start)?,
+        _ => return None,
+    };
+    if new_indent == current_indent {
+        return Some(Vec::new());
+    }
+    Some(vec![TextEdit {
+        range: TextRange::at(
+            TextSize::from(line_start as u32),
+            TextSize::of(current_indent),
+        ),
+        new_text: new_indent,
+    }])
+}
+
+/// Computes the indentation of the line starting at `line_start` from the tokens before it.
+fn indent_for_line(
+    db: &Database,
+    file: File,
+    contents: &str,
+    line_start: usize,
+    unit: &str,
+) -> Option<String> {
+    let root = parse(db, file).syntax(db);
+    let line_start_size = TextSize::from(line_start as u32);
+
+    // The open brackets before the line, and the indentation of the logical line that the last
+    // token before the line belongs to.
+    let mut open_brackets = Vec::new();
+    let mut logical_indent = "";
+    let mut first_kind = None;
+    let mut last_kind = None;
+    for token in root
+        .descendants_with_tokens()
+        .filter_map(|element| element.into_token())
+    {
+        let range = token.text_range();
+        if range.start() >= line_start_size {
+            break;
+        }
+        // Don't touch lines inside multi-line strings.
+        if token.kind() == SyntaxKind::STRING && range.end() > line_start_size {
+            return None;
+        }
+        if token.kind().is_trivia_token()
+            || matches!(
+                token.kind(),
+                SyntaxKind::NEWLINE | SyntaxKind::INDENT | SyntaxKind::DEDENT
+            )
+        {
+            continue;
+        }
+
+        let start = usize::from(range.start());
+        let token_line_start = contents[..start].rfind('\n').map_or(0, |offset| offset + 1);
+        if open_brackets.is_empty() && contents[token_line_start..start].trim().is_empty() {
+            logical_indent = indentation(&contents[token_line_start..]);
+            first_kind = Some(token.kind());
+        }
+        match token.kind() {
+            T!['('] | T!['['] | T!['{'] => open_brackets.push(start),
+            T![')'] | T![']'] | T!['}'] => {
+                open_brackets.pop();
+            }
+            _ => {}
+        }
+        last_kind = Some(token.kind());
+    }
+
+    if let Some(&open) = open_brackets.last() {
+        let open_line_start = contents[..open].rfind('\n').map_or(0, |offset| offset + 1);
+        let open_indent = indentation(&contents[open_line_start..]);
+        // A closing bracket at the start of the line lines up with the line that opened it.
+        if contents[line_start..]
+            .trim_start_matches([' ', '\t'])
+            .starts_with([')', ']', '}'])
+        {
+            return Some(open_indent.to_string());
+        }
+        // Otherwise, line up with the first element if it's on the same line as the bracket.
+        let open_line_end = contents[open..]
+            .find('\n')
+            .map_or(contents.len(), |offset| open + offset);
+        let after_open = &contents[open + 1..open_line_end];
+        let rest = after_open.trim_start();
+        if !rest.is_empty() && !rest.starts_with('#') {
+            let column = open + 1 + (after_open.len() - rest.len()) - open_line_start;
+            return Some(" ".repeat(column));
+        }
+        return Some(format!("{}{}", open_indent, unit));
+    }
+
+    let indent = match (first_kind, last_kind) {
+        (_, Some(T![:])) => format!("{}{}", logical_indent, unit),
+        (Some(T![return] | T![pass] | T![break] | T![continue]), _) => logical_indent
+            .strip_suffix(unit)
+            .unwrap_or_default()
+            .to_string(),
+        _ => logical_indent.to_string(),
+    };
+    Some(indent)
+}
+
+/// Dedents an `else:` or `elif ...:` line to the indentation of the `if` or `elif` before it.
+fn dedent_continuation(contents: &str, line_start: usize) -> Option<String> {
+    let line = contents[line_start..].lines().next()?;
+    let current_indent = indentation(line);
+    if !CONTINUATION_KEYWORDS.contains(&first_word(line)) || !line.trim_end().ends_with(':') {
+        return None;
+    }
+
+    for prev_line in contents[..line_start].lines().rev() {
+        let trimmed = prev_line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        let prev_indent = indentation(prev_line);
+        if prev_indent.len() > current_indent.len() {
+            continue;
+        }
+        if matches!(first_word(prev_line), "if" | "elif") {
+            return Some(prev_indent.to_string());
+        }
+        if prev_indent.len() < current_indent.len() {
+            return None;
+        }
+    }
+    None
+}
+
+/// Returns the whitespace that a single level of indentation consists of in the given file.
+fn indent_unit(contents: &str) -> Option<&str> {
+    contents.lines().find_map(|line| {
+        let indent = indentation(line);
+        let rest = &line[indent.len()..];
+        (!indent.is_empty() && !rest.is_empty() && !rest.starts_with('#')).then_some(indent)
+    })
+}
+
+fn indentation(line: &str) -> &str {
+    let rest = line.trim_start_matches([' ', '\t']);
+    &line[..line.len() - rest.len()]
+}
+
+fn first_word(line: &str) -> &str {
+    let line = line.trim_start();
+    let end = line
+        .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+        .unwrap_or(line.len());
+    &line[..end]
+}
+
+#[cfg(test)]
+mod tests {
+    use starpls_common::Dialect;
+    use starpls_syntax::TextSize;
+
+    use crate::{AnalysisSnapshot, FilePosition};
+
+    /// Applies on-type formatting after `ch` is typed at the `$0` marker.
+    fn check(fixture: &str, ch: char, expected: &str) {
+        let offset = fixture.find("$0").unwrap();
+        let contents = fixture.replace("$0", "");
+        let (snap, file_id) = AnalysisSnapshot::from_single_file(&contents, Dialect::Bazel, None);
+        let edits = snap
+            .on_type_formatting(
+                FilePosition {
+                    file_id,
+                    pos: TextSize::from(offset as u32),
+                },
+                ch,
+                "    ",
+            )
+            .unwrap()
+            .unwrap_or_default();
+        let mut actual = contents.clone();
+        for edit in edits.iter().rev() {
+            actual.replace_range(std::ops::Range::<usize>::from(edit.range), &edit.new_text);
+        }
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_indent_after_colon() {
+        check(
+            "def f(x):\n    if x:\n$0",
+            '\n',
+            "def f(x):\n    if x:\n        ",
+        );
+    }
+
+    #[test]
+    fn test_dedent_after_return() {
+        check(
+            "def f(x):\n    return x\n    $0",
+            '\n',
+            "def f(x):\n    return x\n",
+        );
+    }
+
+    #[test]
+    fn test_indent_in_brackets() {
+        check(
+            "foo(\n    name = \"a\",\n    srcs = [\n$0",
+            '\n',
+            "foo(\n    name = \"a\",\n    srcs = [\n        ",
+        );
+        check("foo(name = \"a\",\n$0", '\n', "foo(name = \"a\",\n    ");
+        check("x = [\n    1,\n    $0]", '\n', "x = [\n    1,\n]");
+    }
+
+    #[test]
+    fn test_continuation_after_brackets() {
+        check(
+            "def f():\n    x = foo(\n        1,\n    )\n$0",
+            '\n',
+            "def f():\n    x = foo(\n        1,\n    )\n    ",
+        );
+    }
+
+    #[test]
+    fn test_dedent_else() {
+        check(
+            "if a:\n    if b:\n        x = 1\n        else:$0",
+            ':',
+            "if a:\n    if b:\n        x = 1\n    else:",
+        );
+        check("if a:\n    x = 1\nelse:$0", ':', "if a:\n    x = 1\nelse:");
+    }
+}
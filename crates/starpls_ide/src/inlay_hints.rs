@@ -0,0 +1,276 @@
+//! Shows inferred types inline: the types of parameters without a type comment, after their
+//! names, the return types of functions, after their parameter lists, and the types of variables
+//! at the sites that assign them.
+//!
+//! Variables assigned a literal aren't hinted, since their type is already obvious.
+
+use starpls_common::{parse, Db as _, File, FileRange};
+use starpls_hir::Semantics;
+use starpls_syntax::{
+    ast::{self, AssignOp, AstNode},
+    TextRange, TextSize, T,
+};
+
+use crate::{
+    type_annotations::{param_type, return_type, UNKNOWN},
+    Database,
+};
+
+/// Which kinds of inlay hints to show.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct InlayHintsConfig {
+    pub parameter_types: bool,
+    pub return_types: bool,
+    pub variable_types: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InlayHintKind {
+    ParameterType,
+    ReturnType,
+    VariableType,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InlayHint {
+    pub pos: TextSize,
+    pub label: String,
+    pub kind: InlayHintKind,
+}
+
+pub(crate) fn inlay_hints(
+    db: &Database,
+    FileRange { file_id, range }: FileRange,
+    config: InlayHintsConfig,
+) -> Option<Vec<InlayHint>> {
+    let file = db.get_file(file_id)?;
+    let sema = Semantics::new(db);
+    let root = parse(db, file).syntax(db);
+    let mut hints = Vec::new();
+    for node in root.descendants() {
+        if node.text_range().intersect(range).is_none() {
+            continue;
+        }
+        if let Some(def_stmt) = ast::DefStmt::cast(node.clone()) {
+            def_stmt_hints(db, &sema, file, &def_stmt, config, &mut hints);
+        } else if let Some(assign_stmt) = ast::AssignStmt::cast(node) {
+            if config.variable_types {
+                assign_stmt_hints(db, &sema, file, &assign_stmt, &mut hints);
+            }
+        }
+    }
+
+    hints.retain(|hint| range.contains_inclusive(hint.pos));
+    hints.sort_by_key(|hint| hint.pos);
+    Some(hints)
+}
+
+fn def_stmt_hints(
+    db: &Database,
+    sema: &Semantics,
+    file: File,
+    def_stmt: &ast::DefStmt,
+    config: InlayHintsConfig,
+    hints: &mut Vec<InlayHint>,
+) {
+    // A function type comment already spells out every type.
+    if def_stmt.spec().is_some() {
+        return;
+    }
+    let parameters = match def_stmt.parameters() {
+        Some(parameters) => parameters,
+        None => return,
+    };
+
+    if config.parameter_types {
+        for param in parameters.parameters() {
+            let name = match &param {
+                ast::Parameter::Simple(simple) if param.type_comment().is_none() => {
+                    match simple.name() {
+                        Some(name) => name,
+                        None => continue,
+                    }
+                }
+                _ => continue,
+            };
+            if let Some(ty) = param_type(db, sema, file, &param) {
+                hints.push(InlayHint {
+                    pos: name.syntax().text_range().end(),
+                    label: format!(": {}", ty.annotation(db)),
+                    kind: InlayHintKind::ParameterType,
+                });
+            }
+        }
+    }
+
+    // The hint goes right after the closing parenthesis, so skip incomplete parameter lists.
+    let is_closed = parameters
+        .syntax()
+        .last_token()
+        .map_or(false, |token| token.kind() == T![')']);
+    if config.return_types && is_closed && def_stmt.suite().is_some() {
+        let ret_type = return_type(db, sema, file, def_stmt);
+        if ret_type != UNKNOWN {
+            hints.push(InlayHint {
+                pos: parameters.syntax().text_range().end(),
+                label: format!("-> {}", ret_type),
+                kind: InlayHintKind::ReturnType,
+            });
+        }
+    }
+}
+
+fn assign_stmt_hints(
+    db: &Database,
+    sema: &Semantics,
+    file: File,
+    assign_stmt: &ast::AssignStmt,
+    hints: &mut Vec<InlayHint>,
+) {
+    if !matches!(assign_stmt.assign_op_info(), Some((_, AssignOp::Normal)))
+        || assign_stmt.type_comment().is_some()
+        || matches!(assign_stmt.rhs(), None | Some(ast::Expression::Literal(_)))
+    {
+        return;
+    }
+
+    let mut names = Vec::new();
+    if let Some(lhs) = assign_stmt.lhs() {
+        collect_assigned_names(lhs, &mut names);
+    }
+    for name_ref in names {
+        let pos = name_ref.syntax().text_range().end();
+        let ty = match sema.type_of_expr(file, &name_ref.into()) {
+            Some(ty) if !ty.is_unknown() => ty,
+            _ => continue,
+        };
+        hints.push(InlayHint {
+            pos,
+            label: format!(": {}", ty.annotation(db)),
+            kind: InlayHintKind::VariableType,
+        });
+    }
+}
+
+/// Collects the names bound by an assignment target, including the ones in tuples and lists,
+/// e.g. `a` and `b` in `a, [b] = ...`. Targets like `x[0]` and `x.y` don't bind names.
+pub(crate) fn collect_assigned_names(expr: ast::Expression, names: &mut Vec<ast::NameRef>) {
+    match expr {
+        ast::Expression::Name(name_ref) => names.push(name_ref),
+        ast::Expression::Tuple(tuple) => {
+            for element in tuple.elements() {
+                collect_assigned_names(element, names);
+            }
+        }
+        ast::Expression::List(list) => {
+            for element in list.elements() {
+                collect_assigned_names(element, names);
+            }
+        }
+        ast::Expression::Paren(paren) => {
+            if let Some(expr) = paren.expr() {
+                collect_assigned_names(expr, names);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use starpls_common::{Dialect, FileRange};
+    use starpls_syntax::{TextRange, TextSize};
+
+    use crate::{AnalysisSnapshot, InlayHintKind, InlayHintsConfig};
+
+    const ALL: InlayHintsConfig = InlayHintsConfig {
+        parameter_types: true,
+        return_types: true,
+        variable_types: true,
+    };
+
+    fn check(contents: &str, config: InlayHintsConfig, expected: &str) {
+        let (snap, file_id) = AnalysisSnapshot::from_single_file(contents, Dialect::Standard, None);
+        let hints = snap
+            .inlay_hints(
+                FileRange {
+                    file_id,
+                    range: TextRange::up_to(TextSize::of(contents)),
+                },
+                config,
+            )
+            .unwrap()
+            .unwrap();
+
+        // Render the hints into the text, the way an editor would.
+        let mut actual = contents.to_string();
+        for hint in hints.iter().rev() {
+            let label = match hint.kind {
+                InlayHintKind::ReturnType => format!(" {}", hint.label),
+                _ => hint.label.clone(),
+            };
+            actual.insert_str(usize::from(hint.pos), &label);
+        }
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_inlay_hints() {
+        check(
+            r#"def f(x, y = "a", *args, **kwargs):
+    return ["a"]
+
+z = [1, 2]
+a, (b, c) = 1, ("a", None)
+d = "literal"
+"#,
+            ALL,
+            r#"def f(x, y: string = "a", *args, **kwargs) -> list[string]:
+    return ["a"]
+
+z: list[int] = [1, 2]
+a: int, (b: string, c: None) = 1, ("a", None)
+d = "literal"
+"#,
+        );
+    }
+
+    #[test]
+    fn test_inlay_hints_parameters_only() {
+        check(
+            r#"def f(x = 1):
+    return x
+
+y = [f()]
+"#,
+            InlayHintsConfig {
+                parameter_types: true,
+                ..Default::default()
+            },
+            r#"def f(x: int = 1):
+    return x
+
+y = [f()]
+"#,
+        );
+    }
+
+    #[test]
+    fn test_inlay_hints_skip_type_comments() {
+        check(
+            r#"def f(x):
+    # type: (int) -> int
+    return x
+
+y = f(1)  # type: int
+"#,
+            ALL,
+            r#"def f(x):
+    # type: (int) -> int
+    return x
+
+y = f(1)  # type: int
+"#,
+        );
+    }
+}
@@ -0,0 +1,158 @@
+//! Finds the variables whose values a debugger can show inline while execution is stopped, i.e.
+//! the names bound by assignments, `for` loops, and parameters in the function that execution is
+//! stopped in, or at the top level of the module if it's stopped outside of any function.
+//!
+//! Only the names are found here. The debugger looks up their values in the stopped frame.
+
+use starpls_common::{parse, Db as _, FileRange};
+use starpls_syntax::{
+    ast::{self, AstNode},
+    SyntaxNode, TextRange, TextSize,
+};
+
+use crate::{inlay_hints::collect_assigned_names, Database};
+
+/// A variable to show the value of, along with where to show it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InlineValue {
+    pub range: TextRange,
+    pub name: String,
+}
+
+/// Returns the variables bound within the given range, before the location that execution is
+/// stopped at.
+pub(crate) fn inline_values(
+    db: &Database,
+    FileRange { file_id, range }: FileRange,
+    stopped_at: TextSize,
+) -> Option<Vec<InlineValue>> {
+    let file = db.get_file(file_id)?;
+    let root = parse(db, file).syntax(db);
+    let scope = root
+        .descendants()
+        .filter_map(ast::DefStmt::cast)
+        .filter(|def_stmt| def_stmt.syntax().text_range().contains(stopped_at))
+        .last();
+    let scope_node = scope
+        .as_ref()
+        .map_or_else(|| root.clone(), |def_stmt| def_stmt.syntax().clone());
+
+    let mut names = Vec::new();
+    if let Some(def_stmt) = &scope {
+        names.extend(
+            def_stmt
+                .parameters()
+                .into_iter()
+                .flat_map(|params| params.parameters())
+                .filter_map(|param| match param {
+                    ast::Parameter::Simple(param) => param.name(),
+                    ast::Parameter::ArgsList(param) => param.name(),
+                    ast::Parameter::KwargsDict(param) => param.name(),
+                })
+                .map(|name| name.syntax().clone()),
+        );
+    }
+    for node in scope_node.descendants() {
+        if !is_in_scope(&node, &scope_node) {
+            continue;
+        }
+        let mut name_refs = Vec::new();
+        if let Some(assign_stmt) = ast::AssignStmt::cast(node.clone()) {
+            if let Some(lhs) = assign_stmt.lhs() {
+                collect_assigned_names(lhs, &mut name_refs);
+            }
+        } else if let Some(for_stmt) = ast::ForStmt::cast(node) {
+            for expr in for_stmt
+                .targets()
+                .into_iter()
+                .flat_map(|targets| targets.exprs())
+            {
+                collect_assigned_names(expr, &mut name_refs);
+            }
+        }
+        names.extend(
+            name_refs
+                .into_iter()
+                .map(|name_ref| name_ref.syntax().clone()),
+        );
+    }
+
+    let mut values = names
+        .into_iter()
+        .filter(|name| {
+            let name_range = name.text_range();
+            range.contains_range(name_range) && name_range.end() <= stopped_at
+        })
+        .map(|name| InlineValue {
+            range: name.text_range(),
+            name: name.text().to_string(),
+        })
+        .collect::<Vec<_>>();
+    values.sort_by_key(|value| value.range.start());
+    Some(values)
+}
+
+/// Returns whether a node belongs to the given scope rather than to a nested function.
+fn is_in_scope(node: &SyntaxNode, scope: &SyntaxNode) -> bool {
+    node.ancestors()
+        .skip(1)
+        .find(|ancestor| ancestor == scope || ast::DefStmt::can_cast(ancestor.kind()))
+        .map_or(false, |ancestor| ancestor == *scope)
+}
+
+#[cfg(test)]
+mod tests {
+    use starpls_common::{Dialect, FileRange};
+    use starpls_syntax::{TextRange, TextSize};
+
+    use crate::AnalysisSnapshot;
+
+    fn check(contents: &str, stopped_at: &str, expected: &[&str]) {
+        let (snap, file_id) = AnalysisSnapshot::from_single_file(contents, Dialect::Standard, None);
+        let values = snap
+            .inline_values(
+                FileRange {
+                    file_id,
+                    range: TextRange::up_to(TextSize::of(contents)),
+                },
+                TextSize::from(contents.find(stopped_at).unwrap() as u32),
+            )
+            .unwrap()
+            .unwrap();
+        let names = values
+            .iter()
+            .map(|value| {
+                assert_eq!(&contents[value.range], value.name);
+                value.name.as_str()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(names, expected);
+    }
+
+    const CONTENTS: &str = r#"x = 1
+
+def f(a, *args, **kwargs):
+    b, [c] = a, [2]
+    for i, j in []:
+        def g(d):
+            e = d
+    print(b)
+    after = 3
+
+y = f(x)
+"#;
+
+    #[test]
+    fn test_inline_values_in_function() {
+        check(
+            CONTENTS,
+            "print(b)",
+            &["a", "args", "kwargs", "b", "c", "i", "j"],
+        );
+    }
+
+    #[test]
+    fn test_inline_values_at_top_level() {
+        check(CONTENTS, "y = f(x)", &["x"]);
+    }
+}
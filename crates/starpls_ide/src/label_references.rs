@@ -0,0 +1,228 @@
+use starpls_common::{parse, Db as _, FileId, ResolvedPath};
+use starpls_syntax::{ast, SyntaxKind, SyntaxNode, TextRange, TextSize};
+
+use crate::Database;
+
+/// A string literal that resolves to a file or target, e.g. the path in a `load` statement or a
+/// label in a `BUILD` file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LabelReference {
+    pub file_id: FileId,
+    /// The range of the string's value, excluding its prefix and quotes.
+    pub range: TextRange,
+    pub value: String,
+    pub resolved_path: ResolvedPath,
+}
+
+/// Finds the labels in every file known to the database that resolve to something. Strings
+/// containing escape sequences are skipped, since their values can't be edited in place.
+pub(crate) fn label_references(db: &Database) -> Vec<LabelReference> {
+    // Resolving a label may insert new files into the map, so copy the files out first.
+    let mut files = db
+        .files
+        .iter()
+        .map(|entry| *entry.value())
+        .collect::<Vec<_>>();
+    files.sort_by_key(|file| file.id(db));
+
+    let mut references = Vec::new();
+    for file in files {
+        let file_id = file.id(db);
+        let dialect = file.dialect(db);
+        for (value, range) in string_values(&parse(db, file).syntax(db)) {
+            let resolved_path = match db.resolve_path(&value, dialect, file_id) {
+                Ok(Some(resolved_path)) => resolved_path,
+                _ => continue,
+            };
+            references.push(LabelReference {
+                file_id,
+                range,
+                value,
+                resolved_path,
+            });
+        }
+    }
+    references
+}
+
+/// Returns the values of the non-empty string literals in a syntax tree, along with the ranges
+/// of the values, excluding prefixes and quotes. Strings containing escape sequences are skipped.
+pub(crate) fn string_values(root: &SyntaxNode) -> Vec<(String, TextRange)> {
+    root.descendants_with_tokens()
+        .filter_map(|element| element.into_token())
+        .filter(|token| token.kind() == SyntaxKind::STRING)
+        .filter_map(|token| {
+            let (value, offset) = ast::String::cast(token.clone())?.value_and_offset()?;
+            let start = offset as usize;
+            if value.is_empty() || token.text().get(start..start + value.len()) != Some(&*value) {
+                return None;
+            }
+            let start = token.text_range().start() + TextSize::from(offset);
+            Some((
+                value.to_string(),
+                TextRange::at(start, TextSize::of(&*value)),
+            ))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{path::PathBuf, sync::Arc};
+
+    use starpls_bazel::APIContext;
+    use starpls_common::{Dialect, FileId, LoadItemCandidate, ResolvedPath};
+
+    use crate::{Analysis, Change, FileLoader};
+
+    /// Resolves labels in the root package of a workspace at `/ws` to source files.
+    struct WorkspaceLoader;
+
+    impl FileLoader for WorkspaceLoader {
+        fn resolve_path(
+            &self,
+            path: &str,
+            _dialect: Dialect,
+            _from: FileId,
+        ) -> anyhow::Result<Option<ResolvedPath>> {
+            let target = match path.strip_prefix("//:").or_else(|| path.strip_prefix(':')) {
+                Some(target) => target,
+                None => anyhow::bail!("unsupported label {:?}", path),
+            };
+            Ok(target.ends_with(".bzl").then(|| ResolvedPath::Source {
+                path: PathBuf::from("/ws").join(target),
+            }))
+        }
+
+        fn load_file(
+            &self,
+            _path: &str,
+            _dialect: Dialect,
+            _from: FileId,
+        ) -> anyhow::Result<Option<(FileId, Dialect, Option<APIContext>, Option<String>)>> {
+            Ok(None)
+        }
+
+        fn list_load_candidates(
+            &self,
+            _path: &str,
+            _dialect: Dialect,
+            _from: FileId,
+        ) -> anyhow::Result<Option<Vec<LoadItemCandidate>>> {
+            Ok(None)
+        }
+
+        fn list_label_candidates(
+            &self,
+            _path: &str,
+            _dialect: Dialect,
+            _from: FileId,
+        ) -> anyhow::Result<Option<(Vec<LoadItemCandidate>, Option<(FileId, Option<String>)>)>>
+        {
+            Ok(None)
+        }
+
+        /// `/ws/BUILD.bazel`, which isn't open, loads `/ws/defs.bzl`.
+        fn workspace_loaders(&self, file_id: FileId) -> Option<Vec<FileId>> {
+            Some(if file_id == FileId(0) {
+                vec![FileId(1)]
+            } else {
+                vec![]
+            })
+        }
+
+        fn read_workspace_file(
+            &self,
+            file_id: FileId,
+        ) -> Option<(Dialect, Option<APIContext>, String)> {
+            (file_id == FileId(1)).then(|| {
+                (
+                    Dialect::Bazel,
+                    Some(APIContext::Build),
+                    "load(\"//:defs.bzl\", \"foo\")\n".to_string(),
+                )
+            })
+        }
+    }
+
+    #[test]
+    fn test_label_references() {
+        let contents = r#"load("//:defs.bzl", "foo")
+load(":other.bzl", "bar")
+
+exports_files(["//:defs.bzl", "data.txt", ":esc\x61ped.bzl"])
+"#;
+        let mut change = Change::default();
+        change.create_file(
+            FileId(0),
+            Dialect::Bazel,
+            Some(APIContext::Build),
+            contents.to_string(),
+        );
+        let mut analysis = Analysis::new(Arc::new(WorkspaceLoader), Default::default());
+        analysis.apply_change(change);
+
+        let actual = analysis
+            .snapshot()
+            .label_references()
+            .unwrap()
+            .into_iter()
+            .map(|reference| {
+                assert_eq!(reference.file_id, FileId(0));
+                assert_eq!(&contents[reference.range], reference.value);
+                (reference.value, reference.resolved_path)
+            })
+            .collect::<Vec<_>>();
+        let source = |path: &str| ResolvedPath::Source {
+            path: PathBuf::from(path),
+        };
+        assert_eq!(
+            actual,
+            vec![
+                ("//:defs.bzl".to_string(), source("/ws/defs.bzl")),
+                (":other.bzl".to_string(), source("/ws/other.bzl")),
+                ("//:defs.bzl".to_string(), source("/ws/defs.bzl")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_label_references_outside_database() {
+        let mut change = Change::default();
+        change.create_file(
+            FileId(0),
+            Dialect::Bazel,
+            Some(APIContext::Bzl),
+            "def foo():\n    pass\n".to_string(),
+        );
+        let mut analysis = Analysis::new(Arc::new(WorkspaceLoader), Default::default());
+        analysis.apply_change(change);
+        let references = |analysis: &Analysis| {
+            analysis
+                .snapshot()
+                .label_references()
+                .unwrap()
+                .into_iter()
+                .map(|reference| (reference.file_id, reference.value))
+                .collect::<Vec<_>>()
+        };
+
+        // Files that aren't in the database aren't searched, even if they're in the workspace.
+        assert_eq!(references(&analysis), vec![]);
+
+        // Finding the loaders of a file adds them to the database, after which they're searched.
+        assert_eq!(
+            analysis
+                .snapshot()
+                .load_graph(FileId(0))
+                .unwrap()
+                .unwrap()
+                .loaders,
+            vec![FileId(1)]
+        );
+        assert_eq!(
+            references(&analysis),
+            vec![(FileId(1), "//:defs.bzl".to_string())]
+        );
+    }
+}
@@ -0,0 +1,481 @@
+//! Rewrites the labels in `BUILD` files in either their full or their shortest form, and checks
+//! that they follow the style chosen for the workspace, if any.
+//!
+//! In full form, every label names its package and target, e.g. `//foo/bar:bar` or
+//! `//foo/bar:baz`. In short form, labels in the same package are relative, e.g. `:baz`, and
+//! targets named after their package use the shorthand, e.g. `//foo/bar`. Expanding relative
+//! labels requires the name of the file's package, which is provided by the
+//! [`FileLoader`](crate::FileLoader).
+
+use starpls_bazel::{label::RepoKind, APIContext, Label};
+use starpls_common::{parse, Db as _, Diagnostic, DiagnosticCode, File, FileRange, Severity};
+use starpls_hir::{Semantics, StringKind};
+use starpls_syntax::{
+    ast::{self, AstNode, AstToken},
+    SyntaxNode, TextRange,
+};
+
+use crate::{
+    label_references::string_values, module_deps::callee_name, BuildifierTables, CodeAction,
+    CodeActionKind, Database, TextEdit,
+};
+
+/// How labels in `BUILD` files are written.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LabelStyle {
+    /// Relative labels for targets in the same package, and `//foo/bar` for `//foo/bar:bar`.
+    Short,
+    /// Labels that always name their package and target, e.g. `//foo/bar:bar`.
+    Full,
+}
+
+/// A label that doesn't match the workspace's label style.
+struct LabelStyleFix {
+    label: String,
+    edit: TextEdit,
+}
+
+pub(crate) fn label_style_diagnostics(db: &Database, file: File) -> Vec<Diagnostic> {
+    fixes(db, file)
+        .into_iter()
+        .map(|fix| Diagnostic {
+            message: format!(
+                "Label \"{}\" should be written as \"{}\"",
+                fix.label, fix.edit.new_text
+            ),
+            code: DiagnosticCode::NonCanonicalLabel,
+            severity: Severity::Warning,
+            range: FileRange {
+                file_id: file.id(db),
+                range: fix.edit.range,
+            },
+        })
+        .collect()
+}
+
+pub(crate) fn label_style_actions(db: &Database, file: File, range: TextRange) -> Vec<CodeAction> {
+    let package = db.loader.package_name(file.id(db));
+    let mut actions = Vec::new();
+    for (label, label_range) in labels(db, file)
+        .into_iter()
+        .filter(|(_, label_range)| label_range.intersect(range).is_some())
+    {
+        for (style, verb) in [(LabelStyle::Full, "Expand"), (LabelStyle::Short, "Shorten")] {
+            let new_label = match restyle(&label, package.as_deref(), style) {
+                Some(new_label) => new_label,
+                None => continue,
+            };
+
+            // Rewriting the label in the workspace's style fixes a diagnostic, while the other
+            // way around is only a refactoring.
+            let is_fix = db.diagnostics_options.label_style == Some(style);
+            actions.push(CodeAction {
+                title: format!("{} label to `{}`", verb, new_label),
+                kind: if is_fix {
+                    CodeActionKind::QuickFix
+                } else {
+                    CodeActionKind::RefactorRewrite
+                },
+                edits: vec![TextEdit {
+                    range: label_range,
+                    new_text: new_label,
+                }],
+                file_edits: Vec::new(),
+                command: None,
+                is_preferred: is_fix,
+            });
+        }
+    }
+
+    let fixes = fixes(db, file);
+    if fixes.len() > 1
+        && fixes
+            .iter()
+            .any(|fix| fix.edit.range.intersect(range).is_some())
+    {
+        actions.push(CodeAction {
+            title: "Canonicalize all labels in file".to_string(),
+            kind: CodeActionKind::QuickFix,
+            edits: fixes.into_iter().map(|fix| fix.edit).collect(),
+            file_edits: Vec::new(),
+            command: None,
+            is_preferred: false,
+        });
+    }
+
+    actions
+}
+
+/// Returns the edits that rewrite every label in the file in the workspace's label style.
+pub(crate) fn label_style_edits(db: &Database, file: File) -> Vec<TextEdit> {
+    fixes(db, file).into_iter().map(|fix| fix.edit).collect()
+}
+
+fn fixes(db: &Database, file: File) -> Vec<LabelStyleFix> {
+    let style = match db.diagnostics_options.label_style {
+        Some(style) => style,
+        None => return Vec::new(),
+    };
+    let package = db.loader.package_name(file.id(db));
+    labels(db, file)
+        .into_iter()
+        .filter_map(|(label, range)| {
+            let new_text = restyle(&label, package.as_deref(), style)?;
+            Some(LabelStyleFix {
+                label,
+                edit: TextEdit { range, new_text },
+            })
+        })
+        .collect()
+}
+
+/// Returns the labels in a `BUILD` file, along with the ranges of their values. Only strings
+/// that are unambiguously labels, i.e. that start with `//`, `:`, or `@`, are included, so that
+/// e.g. the names of source files are left alone, and format strings like `"//%s:foo"` are
+/// skipped since they're only labels once formatted. Labels in `load` statements refer to files
+/// rather than targets, and are skipped too. If the workspace has Buildifier tables, only the
+/// values of the attributes that they list as labels are included.
+fn labels(db: &Database, file: File) -> Vec<(String, TextRange)> {
+    if file.api_context(db) != Some(APIContext::Build) {
+        return Vec::new();
+    }
+    let root = parse(db, file).syntax(db);
+    let loads = root
+        .descendants()
+        .filter_map(ast::LoadStmt::cast)
+        .map(|load_stmt| load_stmt.syntax().text_range())
+        .collect::<Vec<_>>();
+    let sema = Semantics::new(db);
+    string_values(&root)
+        .into_iter()
+        .filter(|(_, range)| {
+            !loads.iter().any(|load| load.contains_range(*range))
+                && root
+                    .token_at_offset(range.start())
+                    .right_biased()
+                    .and_then(ast::String::cast)
+                    .map_or(false, |string| {
+                        matches!(
+                            sema.classify_string(file, &string),
+                            StringKind::Label | StringKind::Visibility
+                        )
+                    })
+        })
+        .filter(|(_, range)| {
+            db.buildifier_tables
+                .as_deref()
+                .map_or(true, |tables| is_label_arg(&root, tables, *range))
+        })
+        .collect()
+}
+
+/// Returns whether the string at `range` is part of the value of a keyword argument whose values
+/// are labels according to the given tables.
+fn is_label_arg(root: &SyntaxNode, tables: &BuildifierTables, range: TextRange) -> bool {
+    let arg = root
+        .token_at_offset(range.start())
+        .right_biased()
+        .and_then(|token| token.parent())
+        .and_then(|parent| parent.ancestors().find_map(ast::KeywordArgument::cast));
+    let arg = match arg {
+        Some(arg) => arg,
+        None => return false,
+    };
+    let name = match arg.name().and_then(|name| name.name()) {
+        Some(name) => name.text().to_string(),
+        None => return false,
+    };
+    let rule = arg
+        .syntax()
+        .parent()
+        .and_then(|args| args.parent())
+        .and_then(ast::CallExpr::cast)
+        .and_then(|call_expr| callee_name(&call_expr))
+        .unwrap_or_default();
+    tables.is_label_arg(&rule, &name)
+}
+
+/// Rewrites a label in the given style, or returns `None` if it's already written that way or
+/// can't be rewritten. `package` is the package of the file containing the label, if known.
+fn restyle(label: &str, package: Option<&str>, style: LabelStyle) -> Option<String> {
+    let parsed = Label::parse(label).ok()?;
+    let target = parsed.target();
+
+    // Package specifications like `//foo:__pkg__` aren't targets, and keep their full form.
+    if target.starts_with("__") {
+        return None;
+    }
+
+    let repo = match parsed.kind() {
+        RepoKind::Canonical => format!("@@{}", parsed.repo()),
+        RepoKind::Apparent if parsed.repo().is_empty() => return None,
+        RepoKind::Apparent => format!("@{}", parsed.repo()),
+        RepoKind::Current => String::new(),
+    };
+    let label_package = if parsed.is_relative() && parsed.kind() == RepoKind::Current {
+        package?
+    } else if parsed.has_leading_slashes() {
+        parsed.package()
+    } else {
+        // The shorthand for the main target of a repository, e.g. `@foo` for `@foo//:foo`.
+        return None;
+    };
+
+    let restyled = match style {
+        LabelStyle::Full => format!("{}//{}:{}", repo, label_package, target),
+        LabelStyle::Short if repo.is_empty() && package == Some(label_package) => {
+            format!(":{}", target)
+        }
+        LabelStyle::Short if label_package.rsplit('/').next() == Some(target) => {
+            format!("{}//{}", repo, label_package)
+        }
+        LabelStyle::Short => format!("{}//{}:{}", repo, label_package, target),
+    };
+    (restyled != label).then_some(restyled)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use starpls_bazel::APIContext;
+    use starpls_common::{Dialect, FileId, FileRange, LoadItemCandidate, ResolvedPath};
+    use starpls_syntax::{TextRange, TextSize};
+
+    use super::{restyle, LabelStyle};
+    use crate::{
+        Analysis, AnalysisSnapshot, BuildifierTables, Change, CodeActionKind, DiagnosticsOptions,
+        FileLoader, TextEdit,
+    };
+
+    /// Places every file in the `foo/bar` package.
+    struct PackageLoader;
+
+    impl FileLoader for PackageLoader {
+        fn resolve_path(
+            &self,
+            _path: &str,
+            _dialect: Dialect,
+            _from: FileId,
+        ) -> anyhow::Result<Option<ResolvedPath>> {
+            Ok(None)
+        }
+
+        fn load_file(
+            &self,
+            _path: &str,
+            _dialect: Dialect,
+            _from: FileId,
+        ) -> anyhow::Result<Option<(FileId, Dialect, Option<APIContext>, Option<String>)>> {
+            Ok(None)
+        }
+
+        fn list_load_candidates(
+            &self,
+            _path: &str,
+            _dialect: Dialect,
+            _from: FileId,
+        ) -> anyhow::Result<Option<Vec<LoadItemCandidate>>> {
+            Ok(None)
+        }
+
+        fn list_label_candidates(
+            &self,
+            _path: &str,
+            _dialect: Dialect,
+            _from: FileId,
+        ) -> anyhow::Result<Option<(Vec<LoadItemCandidate>, Option<(FileId, Option<String>)>)>>
+        {
+            Ok(None)
+        }
+
+        fn package_name(&self, _file_id: FileId) -> Option<String> {
+            Some("foo/bar".to_string())
+        }
+    }
+
+    const BUILD: &str = r#"
+load("//foo/bar:defs.bzl", "my_rule")
+
+my_rule(
+    name = "bar",
+    srcs = ["main.cc"],
+    deps = [
+        ":baz",
+        "//foo/bar:qux",
+        "//other/pkg:pkg",
+        "@repo//lib",
+    ],
+    data = ["//foo/bar:%s" % "data"],
+    visibility = ["//foo/bar:__pkg__"],
+)
+"#;
+
+    fn snapshot(label_style: Option<LabelStyle>) -> AnalysisSnapshot {
+        snapshot_with_tables(label_style, None)
+    }
+
+    fn snapshot_with_tables(
+        label_style: Option<LabelStyle>,
+        tables: Option<BuildifierTables>,
+    ) -> AnalysisSnapshot {
+        let mut change = Change::default();
+        change.create_file(
+            FileId(0),
+            Dialect::Bazel,
+            Some(APIContext::Build),
+            BUILD.to_string(),
+        );
+        let mut analysis = Analysis::new(Arc::new(PackageLoader), Default::default());
+        analysis.apply_change(change);
+        analysis.set_diagnostics_options(DiagnosticsOptions {
+            label_style,
+            ..Default::default()
+        });
+        analysis.set_buildifier_tables(tables);
+        analysis.snapshot()
+    }
+
+    fn apply(text: &str, edits: &[TextEdit]) -> String {
+        let mut edits = edits.to_vec();
+        edits.sort_by_key(|edit| std::cmp::Reverse(edit.range.start()));
+        let mut text = text.to_string();
+        for edit in edits {
+            text.replace_range(std::ops::Range::<usize>::from(edit.range), &edit.new_text);
+        }
+        text
+    }
+
+    fn canonicalize_all(snap: &AnalysisSnapshot) -> String {
+        let actions = snap
+            .code_actions(FileRange {
+                file_id: FileId(0),
+                range: TextRange::up_to(TextSize::of(BUILD)),
+            })
+            .unwrap()
+            .unwrap();
+        let action = actions
+            .iter()
+            .find(|action| action.title == "Canonicalize all labels in file")
+            .unwrap();
+        apply(BUILD, &action.edits)
+    }
+
+    #[test]
+    fn test_restyle() {
+        fn check(label: &str, style: LabelStyle, expected: Option<&str>) {
+            assert_eq!(
+                restyle(label, Some("foo/bar"), style).as_deref(),
+                expected,
+                "{}",
+                label
+            );
+        }
+
+        check(":baz", LabelStyle::Full, Some("//foo/bar:baz"));
+        check(":baz", LabelStyle::Short, None);
+        check("//foo/bar:baz", LabelStyle::Short, Some(":baz"));
+        check("//foo/bar", LabelStyle::Full, Some("//foo/bar:bar"));
+        check("//foo/bar", LabelStyle::Short, Some(":bar"));
+        check("//foo:foo", LabelStyle::Short, Some("//foo"));
+        check("//:foo", LabelStyle::Short, None);
+        check("@repo//lib", LabelStyle::Full, Some("@repo//lib:lib"));
+        check(
+            "@repo//foo/bar:bar",
+            LabelStyle::Short,
+            Some("@repo//foo/bar"),
+        );
+        check("@@repo~//foo/bar:baz", LabelStyle::Short, None);
+        check("@repo", LabelStyle::Full, None);
+        check("//foo/bar:__pkg__", LabelStyle::Short, None);
+
+        // Relative labels can't be expanded without knowing the package.
+        assert_eq!(restyle(":baz", None, LabelStyle::Full), None);
+    }
+
+    #[test]
+    fn test_label_style_diagnostics() {
+        let messages = |label_style| {
+            snapshot(label_style)
+                .diagnostics(FileId(0))
+                .unwrap()
+                .into_iter()
+                .filter(|diagnostic| diagnostic.code.name() == "non-canonical-label")
+                .map(|diagnostic| diagnostic.message)
+                .collect::<Vec<_>>()
+        };
+        assert!(messages(None).is_empty());
+        assert_eq!(
+            messages(Some(LabelStyle::Short)),
+            vec![
+                "Label \"//foo/bar:qux\" should be written as \":qux\"",
+                "Label \"//other/pkg:pkg\" should be written as \"//other/pkg\"",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_buildifier_label_args() {
+        let mut tables = BuildifierTables::default();
+        tables.merge(
+            BuildifierTables::parse(r#"{"LabelDenylist": {"my_rule.deps": true}}"#).unwrap(),
+        );
+        let diagnostics = snapshot_with_tables(Some(LabelStyle::Short), Some(tables))
+            .diagnostics(FileId(0))
+            .unwrap();
+        assert!(!diagnostics
+            .iter()
+            .any(|diagnostic| diagnostic.code.name() == "non-canonical-label"));
+    }
+
+    #[test]
+    fn test_canonicalize_all() {
+        assert_eq!(
+            canonicalize_all(&snapshot(Some(LabelStyle::Full))),
+            BUILD
+                .replace("\":baz\"", "\"//foo/bar:baz\"")
+                .replace("\"@repo//lib\"", "\"@repo//lib:lib\"")
+        );
+        assert_eq!(
+            canonicalize_all(&snapshot(Some(LabelStyle::Short))),
+            BUILD
+                .replace("\"//foo/bar:qux\"", "\":qux\"")
+                .replace("\"//other/pkg:pkg\"", "\"//other/pkg\"")
+        );
+    }
+
+    #[test]
+    fn test_label_style_actions() {
+        let offset = BUILD.find(":baz").unwrap();
+        let actions = snapshot(Some(LabelStyle::Full))
+            .code_actions(FileRange {
+                file_id: FileId(0),
+                range: TextRange::empty(TextSize::from(offset as u32)),
+            })
+            .unwrap()
+            .unwrap();
+        let action = actions
+            .iter()
+            .find(|action| action.title == "Expand label to `//foo/bar:baz`")
+            .unwrap();
+        assert_eq!(action.kind, CodeActionKind::QuickFix);
+        assert!(action.is_preferred);
+
+        // Without a configured style, the same rewrite is offered as a refactoring.
+        let actions = snapshot(None)
+            .code_actions(FileRange {
+                file_id: FileId(0),
+                range: TextRange::empty(TextSize::from(offset as u32)),
+            })
+            .unwrap()
+            .unwrap();
+        let action = actions
+            .iter()
+            .find(|action| action.title == "Expand label to `//foo/bar:baz`")
+            .unwrap();
+        assert_eq!(action.kind, CodeActionKind::RefactorRewrite);
+        assert!(!actions
+            .iter()
+            .any(|action| action.title == "Canonicalize all labels in file"));
+    }
+}
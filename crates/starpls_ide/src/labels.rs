@@ -0,0 +1,542 @@
+//! Resolves the labels written as strings in Bazel files, e.g. `"//foo/bar:baz.bzl"` or
+//! `":my_target"`, to the source files and targets that they refer to.
+//!
+//! Go-to-definition, hover, and completion all go through this module, so that they agree on
+//! what a label refers to. Parsing labels and finding the packages they belong to is left to the
+//! [`FileLoader`](crate::FileLoader); this module finds the targets within the packages.
+
+use std::path::{Path, PathBuf};
+
+use starpls_bazel::APIContext;
+use starpls_common::{Db as _, File, FileId, FileRange, ResolvedPath};
+use starpls_hir::{Semantics, StringKind};
+use starpls_syntax::{
+    ast::{self, AstNode, AstToken},
+    SyntaxToken, TextRange,
+};
+
+use crate::{
+    build_settings,
+    targets::{find_generating_target, macro_target_names, targets, Target, TargetAttributeValue},
+    Database, Hover, Markup,
+};
+
+/// The most elements of a list attribute to show when hovering a target, before eliding them.
+const MAX_HOVER_LIST_ITEMS: usize = 3;
+
+/// The longest attribute value, e.g. a `select()`, to show when hovering a target.
+const MAX_HOVER_VALUE_LEN: usize = 40;
+
+/// The most lines of a file to show when hovering a label referring to it.
+const MAX_PREVIEW_LINES: usize = 10;
+
+/// The most bytes of a file to read for its preview.
+const MAX_PREVIEW_LEN: usize = 4096;
+
+/// What a label refers to.
+pub(crate) enum LabelTarget {
+    /// A source file, e.g. the file loaded by a `load` statement or one of the `srcs` of a rule.
+    File(PathBuf),
+    /// A target in a `BUILD` file. The target may also be generated by `target`, e.g. as one of
+    /// its outputs or by a macro, rather than being `target` itself.
+    Target {
+        build_file: FileId,
+        name: String,
+        target: Target,
+    },
+}
+
+pub(crate) struct ResolvedLabel {
+    /// The label, as written.
+    pub(crate) label: String,
+    /// The range of the string literal containing the label.
+    pub(crate) range: TextRange,
+    pub(crate) target: LabelTarget,
+}
+
+/// Resolves the label in the string literal that the given token belongs to, if the string is
+/// used as a label or a file path. Other string literals are only resolved if they name an
+/// existing file, e.g. `"config.json"` passed to a macro whose attributes aren't known.
+pub(crate) fn resolve_label(
+    db: &Database,
+    file: File,
+    token: &SyntaxToken,
+) -> Option<ResolvedLabel> {
+    let string = ast::String::cast(token.clone())?;
+    let is_label = matches!(
+        Semantics::new(db).classify_string(file, &string),
+        StringKind::Label | StringKind::FilePath | StringKind::Visibility
+    );
+    if !is_label && !ast::LiteralExpr::can_cast(token.parent()?.kind()) {
+        return None;
+    }
+    let label = string.value()?.to_string();
+    let target = match db
+        .resolve_path(&label, file.dialect(db), file.id(db))
+        .ok()??
+    {
+        ResolvedPath::Source { path } => LabelTarget::File(path),
+        ResolvedPath::BuildTarget { .. } if !is_label => return None,
+        ResolvedPath::BuildTarget {
+            build_file, target, ..
+        } => LabelTarget::Target {
+            build_file,
+            target: find_generating_target(db, db.get_file(build_file)?, &target)?,
+            name: target,
+        },
+    };
+    Some(ResolvedLabel {
+        label,
+        range: token.text_range(),
+        target,
+    })
+}
+
+pub(crate) fn hover(db: &Database, file: File, token: &SyntaxToken) -> Option<Hover> {
+    let resolved = resolve_label(db, file, token)?;
+    let (value, definition) = match &resolved.target {
+        LabelTarget::File(path) => {
+            let mut text = format!("```python\n(file) {}\n```\n", path.display());
+            if let Some(preview) = file_preview(db, path) {
+                text.push_str(&preview);
+            }
+            (text, None)
+        }
+        LabelTarget::Target {
+            build_file,
+            name,
+            target,
+        } => {
+            let mut text = format!("```python\n{}```\n", render_target(target));
+            if *name != target.name {
+                text.push_str(&format!(
+                    "`{}` is declared by the `{}` target `:{}`.\n",
+                    resolved.label, target.kind, target.name
+                ));
+            } else if let Some(setting) =
+                build_settings::build_setting(db, db.get_file(*build_file)?, target)
+            {
+                text.push_str(&setting.describe());
+            }
+            (
+                text,
+                Some(FileRange {
+                    file_id: *build_file,
+                    range: target.range,
+                }),
+            )
+        }
+    };
+    Some(Hover {
+        contents: Markup { value },
+        range: Some(resolved.range),
+        definition,
+    })
+}
+
+/// Renders the size of a file, along with its first lines unless it looks like a binary file.
+fn file_preview(db: &Database, path: &Path) -> Option<String> {
+    let (prefix, size) = db.loader.read_file_prefix(path, MAX_PREVIEW_LEN)?;
+    let mut text = format!("{}\n", format_size(size));
+    if prefix.contains(&0) {
+        return Some(text);
+    }
+
+    // Drop the last line if it was cut off.
+    let decoded = String::from_utf8_lossy(&prefix);
+    let is_cut_off = (prefix.len() as u64) < size;
+    let contents: &str = match decoded.rsplit_once('\n') {
+        Some((complete, _)) if is_cut_off => complete,
+        _ => &decoded,
+    };
+    let lines = contents.lines().collect::<Vec<_>>();
+    if lines.is_empty() {
+        return Some(text);
+    }
+    text.push_str(&format!("\n```{}\n", preview_language(path)));
+    for line in lines.iter().take(MAX_PREVIEW_LINES) {
+        text.push_str(line);
+        text.push('\n');
+    }
+    if is_cut_off || lines.len() > MAX_PREVIEW_LINES {
+        text.push_str("...\n");
+    }
+    text.push_str("```\n");
+    Some(text)
+}
+
+fn format_size(size: u64) -> String {
+    match size {
+        0..=1023 => format!("{} bytes", size),
+        1024..=1048575 => format!("{:.1} KiB", size as f64 / 1024.0),
+        _ => format!("{:.1} MiB", size as f64 / (1024.0 * 1024.0)),
+    }
+}
+
+/// Returns the language to highlight a file preview with, based on the file's extension.
+fn preview_language(path: &Path) -> &'static str {
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("bzl" | "bazel" | "py" | "star") => "python",
+        Some("json") => "json",
+        Some("md") => "markdown",
+        Some("sh") => "sh",
+        Some("toml") => "toml",
+        Some("yaml" | "yml") => "yaml",
+        _ => "",
+    }
+}
+
+/// Renders the call that declares a target, with long attribute values elided.
+fn render_target(target: &Target) -> String {
+    let mut text = format!("{}(\n", target.kind);
+    for attr in &target.attributes {
+        let value = match &attr.value {
+            TargetAttributeValue::String(value) => quote(value),
+            TargetAttributeValue::StringList(values) if values.len() <= MAX_HOVER_LIST_ITEMS => {
+                let values = values.iter().map(|value| quote(value)).collect::<Vec<_>>();
+                format!("[{}]", values.join(", "))
+            }
+            TargetAttributeValue::StringList(_) => "[...]".to_string(),
+            TargetAttributeValue::Bool(value) => if *value { "True" } else { "False" }.to_string(),
+            TargetAttributeValue::Int(value) => value.to_string(),
+            TargetAttributeValue::Other(text)
+                if text.len() <= MAX_HOVER_VALUE_LEN && !text.contains('\n') =>
+            {
+                text.clone()
+            }
+            TargetAttributeValue::Other(_) => "...".to_string(),
+        };
+        text.push_str(&format!("    {} = {},\n", attr.name, value));
+    }
+    text.push_str(")\n");
+    text
+}
+
+fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Returns the names of the targets declared in a `BUILD` file, along with the ranges of the
+/// calls declaring them. This includes the targets declared by macro calls, as far as they can
+/// be worked out.
+pub(crate) fn target_names(db: &Database, file: File) -> Vec<(String, TextRange)> {
+    if file.api_context(db) != Some(APIContext::Build) {
+        return Vec::new();
+    }
+    let mut names = Vec::new();
+    for target in targets(db, file.id(db)).unwrap_or_default() {
+        names.push((target.name.clone(), target.range));
+        if !target.is_macro {
+            continue;
+        }
+        for name in macro_target_names(db, file, &target) {
+            if !names.iter().any(|(existing, _)| *existing == name) {
+                names.push((name, target.range));
+            }
+        }
+    }
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        path::{Path, PathBuf},
+        sync::Arc,
+    };
+
+    use starpls_bazel::APIContext;
+    use starpls_common::{Dialect, FileId, LoadItemCandidate, LoadItemCandidateKind, ResolvedPath};
+    use starpls_syntax::{TextRange, TextSize};
+    use starpls_test_util::parse_fixture;
+
+    use crate::{
+        Analysis, AnalysisSnapshot, Change, FileLoader, FilePosition, Hover, LocationLink,
+    };
+
+    /// Resolves labels to targets in the package of the file with ID 0, or to source files for
+    /// `.json` and `.bin` files.
+    struct PackageLoader;
+
+    impl FileLoader for PackageLoader {
+        fn resolve_path(
+            &self,
+            path: &str,
+            _dialect: Dialect,
+            _from: FileId,
+        ) -> anyhow::Result<Option<ResolvedPath>> {
+            let target = path.strip_prefix(':').unwrap_or(path);
+            Ok(Some(
+                if target.ends_with(".json") || target.ends_with(".bin") {
+                    ResolvedPath::Source {
+                        path: PathBuf::from(target),
+                    }
+                } else {
+                    ResolvedPath::BuildTarget {
+                        build_file: FileId(0),
+                        target: target.to_string(),
+                        contents: None,
+                    }
+                },
+            ))
+        }
+
+        fn load_file(
+            &self,
+            _path: &str,
+            _dialect: Dialect,
+            _from: FileId,
+        ) -> anyhow::Result<Option<(FileId, Dialect, Option<APIContext>, Option<String>)>> {
+            Ok(None)
+        }
+
+        fn list_load_candidates(
+            &self,
+            _path: &str,
+            _dialect: Dialect,
+            _from: FileId,
+        ) -> anyhow::Result<Option<Vec<LoadItemCandidate>>> {
+            Ok(None)
+        }
+
+        fn list_label_candidates(
+            &self,
+            path: &str,
+            _dialect: Dialect,
+            _from: FileId,
+        ) -> anyhow::Result<Option<(Vec<LoadItemCandidate>, Option<(FileId, Option<String>)>)>>
+        {
+            Ok(path.starts_with(':').then(|| {
+                (
+                    vec![LoadItemCandidate {
+                        kind: LoadItemCandidateKind::File,
+                        path: "data.txt".to_string(),
+                    }],
+                    Some((FileId(0), None)),
+                )
+            }))
+        }
+
+        fn read_file_prefix(&self, path: &Path, max_len: usize) -> Option<(Vec<u8>, u64)> {
+            let contents: &[u8] = match path.to_str()? {
+                "config.json" => b"{\n  \"a\": 1,\n  \"b\": 2\n}\n",
+                "long.json" => {
+                    b"[\n  1,\n  2,\n  3,\n  4,\n  5,\n  6,\n  7,\n  8,\n  9,\n  10,\n]\n"
+                }
+                "data.bin" => b"\x7fELF\x00\x01",
+                _ => return None,
+            };
+            let len = contents.len().min(max_len);
+            Some((contents[..len].to_vec(), 8192))
+        }
+    }
+
+    const BUILD: &str = r#"
+def my_macro(name):
+    native.cc_library(name = name + "_lib")
+
+my_macro(name = "m")
+
+cc_library(
+    name = "foo",
+    deps = [":gen.h"],
+)
+
+genrule(
+    name = "gen",
+    outs = ["gen.h"],
+)
+"#;
+
+    fn snapshot(contents: &str) -> AnalysisSnapshot {
+        let mut change = Change::default();
+        change.create_file(
+            FileId(0),
+            Dialect::Bazel,
+            Some(APIContext::Build),
+            contents.to_string(),
+        );
+        let mut analysis = Analysis::new(Arc::new(PackageLoader), Default::default());
+        analysis.apply_change(change);
+        analysis.snapshot()
+    }
+
+    fn hover(contents: &str, needle: &str) -> Hover {
+        let snap = snapshot(contents);
+        let pos = TextSize::from(contents.find(needle).unwrap() as u32 + 1);
+        snap.hover(FilePosition {
+            file_id: FileId(0),
+            pos,
+        })
+        .unwrap()
+        .unwrap()
+    }
+
+    fn check_goto_definition(fixture: &str) {
+        let (contents, pos, expected) = parse_fixture(fixture);
+        let snap = snapshot(&contents);
+        let actual = snap
+            .goto_definition(FilePosition {
+                file_id: FileId(0),
+                pos,
+            })
+            .unwrap()
+            .unwrap()
+            .into_iter()
+            .map(|loc| match loc {
+                LocationLink::Local {
+                    target_selection_range,
+                    ..
+                } => target_selection_range,
+                _ => panic!("expected local location"),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn test_goto_target() {
+        check_goto_definition(
+            r#"
+cc_library(
+    name = "foo",
+           #^^^^
+)
+
+cc_binary(name = "bin", deps = [":f$0oo"])
+"#,
+        );
+    }
+
+    #[test]
+    fn test_goto_generated_target() {
+        check_goto_definition(
+            r#"
+genrule(
+    name = "gen",
+           #^^^^
+    outs = ["gen.h"],
+)
+
+cc_binary(name = "bin", srcs = [":ge$0n.h"])
+"#,
+        );
+    }
+
+    #[test]
+    fn test_hover_macro_target() {
+        let contents = format!(
+            "{}\ncc_binary(name = \"bin\", deps = [\":m_lib\"])\n",
+            BUILD
+        );
+        let hover = hover(&contents, "m_lib");
+        assert_eq!(
+            hover.contents.value,
+            "```python\nmy_macro(\n    name = \"m\",\n)\n```\n`:m_lib` is declared by the `my_macro` target `:m`.\n"
+        );
+    }
+
+    #[test]
+    fn test_hover_target() {
+        let contents = r#"
+cc_library(
+    name = "foo",
+    srcs = ["a.cc", "b.cc", "c.cc", "d.cc"],
+    hdrs = ["foo.h"],
+    linkstatic = True,
+    copts = select({"//conditions:default": ["-DCOMMON_OPTION_WITH_A_LONG_NAME"]}),
+)
+
+cc_binary(name = "bin", deps = [":foo"])
+"#;
+        let hover = hover(contents, ":foo\"]");
+        assert_eq!(
+            hover.contents.value,
+            r#"```python
+cc_library(
+    name = "foo",
+    srcs = [...],
+    hdrs = ["foo.h"],
+    linkstatic = True,
+    copts = ...,
+)
+```
+"#
+        );
+        let start = contents.find("cc_library").unwrap();
+        let end = contents.find(")\n").unwrap() + 1;
+        let definition = hover.definition.unwrap();
+        assert_eq!(definition.file_id, FileId(0));
+        assert_eq!(
+            definition.range,
+            TextRange::new(TextSize::from(start as u32), TextSize::from(end as u32))
+        );
+    }
+
+    #[test]
+    fn test_hover_file_preview() {
+        let contents = "filegroup(name = \"data\", srcs = [\":config.json\"])\n";
+        assert_eq!(
+            hover(contents, ":config.json").contents.value,
+            "```python\n(file) config.json\n```\n8.0 KiB\n\n```json\n{\n  \"a\": 1,\n  \"b\": 2\n}\n...\n```\n"
+        );
+    }
+
+    #[test]
+    fn test_hover_file_passed_to_unknown_macro() {
+        let contents = "my_macro(name = \"m\", config = \"config.json\")\n";
+        assert_eq!(
+            hover(contents, "config.json").contents.value,
+            "```python\n(file) config.json\n```\n8.0 KiB\n\n```json\n{\n  \"a\": 1,\n  \"b\": 2\n}\n...\n```\n"
+        );
+
+        // Plain strings that don't name a file aren't resolved as targets.
+        let contents = "cc_library(name = \"foo\")\n\nmy_macro(name = \"m\", config = \"foo\")\n";
+        let snap = snapshot(contents);
+        let pos = TextSize::from(contents.rfind("foo").unwrap() as u32);
+        assert!(snap
+            .hover(FilePosition {
+                file_id: FileId(0),
+                pos,
+            })
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_hover_file_preview_truncated() {
+        let contents = "filegroup(name = \"data\", srcs = [\":long.json\"])\n";
+        let value = hover(contents, ":long.json").contents.value;
+        assert!(value.ends_with("  9,\n...\n```\n"), "{}", value);
+    }
+
+    #[test]
+    fn test_hover_binary_file() {
+        let contents = "filegroup(name = \"data\", srcs = [\":data.bin\"])\n";
+        assert_eq!(
+            hover(contents, ":data.bin").contents.value,
+            "```python\n(file) data.bin\n```\n8.0 KiB\n"
+        );
+    }
+
+    #[test]
+    fn test_complete_targets() {
+        let contents = format!("{}\ncc_binary(name = \"bin\", deps = [\":\"])\n", BUILD);
+        let snap = snapshot(&contents);
+        let pos = TextSize::from(contents.rfind("\":\"").unwrap() as u32 + 2);
+        let labels = snap
+            .completion(
+                FilePosition {
+                    file_id: FileId(0),
+                    pos,
+                },
+                None,
+            )
+            .unwrap()
+            .unwrap()
+            .into_iter()
+            .map(|item| item.label)
+            .collect::<Vec<_>>();
+        assert_eq!(labels, vec!["data.txt", ":m", ":m_lib", ":foo", ":gen"]);
+    }
+}
@@ -0,0 +1,608 @@
+//! A refactoring that converts a rule implementation returning a legacy struct provider, e.g.
+//! `return struct(files = ..., my_info = struct(srcs = ...))`, to one returning a list of modern
+//! providers.
+//!
+//! The fields that Bazel handles itself are passed to their modern counterparts: `files`,
+//! `runfiles`, `data_runfiles`, `default_runfiles` and `executable` to `DefaultInfo`, and
+//! `output_groups` to `OutputGroupInfo`, while the providers listed in `providers` are returned
+//! as is. Every other field is a legacy provider, which gets a `provider()` declaration named
+//! after it, e.g. `MyInfo` for `my_info`. A legacy provider created with `struct(...)` keeps its
+//! fields, and any other value becomes the single field of the same name.
+//!
+//! Dependents access legacy providers as fields of their targets. `dep.my_info`,
+//! `hasattr(dep, "my_info")` and `getattr(dep, "my_info")` are rewritten to `dep[MyInfo]`,
+//! `MyInfo in dep` and `dep[MyInfo]` in the rule's file and the files that load it directly,
+//! which also load the new providers. Targets usually aren't typed, so every access whose
+//! receiver is a `Target` or of an unknown type is rewritten.
+
+use rustc_hash::FxHashSet;
+use starpls_bazel::APIContext;
+use starpls_common::{parse, Db as _, File};
+use starpls_hir::Semantics;
+use starpls_syntax::{
+    ast::{self, AstNode},
+    SyntaxNode, TextRange,
+};
+
+use crate::{
+    load_graph::load_graph,
+    module_deps::callee_name,
+    util::{load_stmts, local_name},
+    CodeAction, CodeActionKind, Database, EditTarget, FileEdit, TextEdit,
+};
+
+/// The fields of a legacy provider struct that are passed to `DefaultInfo`.
+const DEFAULT_INFO_FIELDS: &[&str] = &[
+    "data_runfiles",
+    "default_runfiles",
+    "executable",
+    "files",
+    "runfiles",
+];
+
+/// A field of the returned struct that isn't handled by Bazel itself.
+struct LegacyProvider {
+    /// The name of the modern provider that replaces it.
+    name: String,
+    /// The names of the provider's fields, along with their values.
+    fields: Vec<(String, String)>,
+}
+
+pub(crate) fn legacy_provider_actions(
+    db: &Database,
+    file: File,
+    range: TextRange,
+) -> Option<CodeAction> {
+    if file.api_context(db) != Some(APIContext::Bzl) {
+        return None;
+    }
+    let root = parse(db, file).syntax(db);
+    let return_stmt = root
+        .descendants()
+        .filter_map(ast::ReturnStmt::cast)
+        .find(|stmt| stmt.syntax().text_range().intersect(range).is_some())?;
+    let call = match return_stmt.expr()? {
+        ast::Expression::Call(call) if callee_name(&call).as_deref() == Some("struct") => call,
+        _ => return None,
+    };
+    let def_stmt = return_stmt
+        .syntax()
+        .ancestors()
+        .find_map(ast::DefStmt::cast)?;
+    let impl_name = def_stmt.name()?.name()?.text().to_string();
+    if def_stmt.syntax().parent().as_ref() != Some(&root)
+        || !is_rule_implementation(&root, &impl_name)
+    {
+        return None;
+    }
+
+    let sema = Semantics::new(db);
+    let mut used_names = top_level_names(&root);
+    let mut default_info = Vec::new();
+    let mut output_groups = None;
+    let mut providers = None;
+    let mut legacy_providers = Vec::new();
+    for arg in call.arguments()?.arguments() {
+        let arg = match arg {
+            ast::Argument::Keyword(arg) => arg,
+            _ => return None,
+        };
+        let key = arg.name()?.name()?.text().to_string();
+        let value = arg.expr()?;
+        match key.as_str() {
+            field if DEFAULT_INFO_FIELDS.contains(&field) => {
+                default_info.push((field.to_string(), value))
+            }
+            "output_groups" => output_groups = Some(value),
+            "providers" => providers = Some(value),
+            // Coverage information has to be recreated with `coverage_common`.
+            "instrumented_files" => return None,
+            _ => {
+                let name = provider_name(&key, &used_names);
+                used_names.insert(name.clone());
+                legacy_providers.push((key, name, value));
+            }
+        }
+    }
+    // Rewrite the accesses first, since some of them may be part of the returned struct.
+    let provider_names = legacy_providers
+        .iter()
+        .map(|(key, name, _)| (key.clone(), name.clone()))
+        .collect::<Vec<_>>();
+    let access_edits = access_edits(db, &sema, file, &root, &provider_names)
+        .into_iter()
+        .map(|(edit, _)| edit)
+        .collect::<Vec<_>>();
+    let value_text = |expr: &ast::Expression| rewritten_text(expr.syntax(), &access_edits);
+
+    let legacy_providers = legacy_providers
+        .into_iter()
+        .map(|(key, name, value)| {
+            let fields = match &value {
+                ast::Expression::Call(call) if callee_name(call).as_deref() == Some("struct") => {
+                    call.arguments()?
+                        .arguments()
+                        .map(|arg| match arg {
+                            ast::Argument::Keyword(arg) => Some((
+                                arg.name()?.name()?.text().to_string(),
+                                value_text(&arg.expr()?),
+                            )),
+                            _ => None,
+                        })
+                        .collect::<Option<Vec<_>>>()?
+                }
+                _ => vec![(key.clone(), value_text(&value))],
+            };
+            Some(LegacyProvider { name, fields })
+        })
+        .collect::<Option<Vec<_>>>()?;
+
+    let mut elements = Vec::new();
+    if !default_info.is_empty() {
+        elements.push(provider_call(
+            "DefaultInfo",
+            default_info
+                .iter()
+                .map(|(key, value)| (key.clone(), value_text(value))),
+        ));
+    }
+    if let Some(output_groups) = &output_groups {
+        elements.push(output_group_info_call(output_groups, &value_text));
+    }
+    for provider in &legacy_providers {
+        elements.push(provider_call(
+            &provider.name,
+            provider.fields.iter().cloned(),
+        ));
+    }
+    let mut providers_suffix = None;
+    match &providers {
+        Some(ast::Expression::List(list)) => {
+            elements.extend(list.elements().map(|element| value_text(&element)))
+        }
+        Some(providers) => providers_suffix = Some(value_text(providers)),
+        None => {}
+    }
+
+    let contents = file.contents(db);
+    let start = usize::from(return_stmt.syntax().text_range().start());
+    let line_start = contents[..start].rfind('\n').map_or(0, |pos| pos + 1);
+    let indent = contents[line_start..start]
+        .chars()
+        .take_while(|c| c.is_whitespace())
+        .collect::<String>();
+    let mut list = String::from("[\n");
+    for element in elements {
+        list.push_str(&format!("{}    {},\n", indent, element));
+    }
+    list.push_str(&indent);
+    list.push(']');
+    if let Some(suffix) = providers_suffix {
+        list.push_str(" + ");
+        list.push_str(&suffix);
+    }
+
+    let call_range = call.syntax().text_range();
+    let mut edits = Vec::new();
+    if !legacy_providers.is_empty() {
+        edits.push(TextEdit {
+            range: TextRange::empty(def_stmt.syntax().text_range().start()),
+            new_text: legacy_providers
+                .iter()
+                .map(|provider| {
+                    format!(
+                        "{} = provider(fields = [{}])\n\n",
+                        provider.name,
+                        provider
+                            .fields
+                            .iter()
+                            .map(|(name, _)| format!("\"{}\"", name))
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    )
+                })
+                .collect(),
+        });
+    }
+    edits.extend(
+        access_edits
+            .iter()
+            .filter(|edit| !call_range.contains_range(edit.range))
+            .cloned(),
+    );
+    edits.push(TextEdit {
+        range: call_range,
+        new_text: list,
+    });
+
+    // Files that load the rule's file directly load the providers that they now use.
+    let file_id = file.id(db);
+    let loaders = load_graph(db, file_id)
+        .map(|graph| graph.edges)
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|(_, loadee)| *loadee == file_id)
+        .filter_map(|(loader, _)| db.get_file(loader));
+    let mut file_edits = Vec::new();
+    for loader in loaders {
+        let loader_root = parse(db, loader).syntax(db);
+        let load_stmt = match load_stmts(&loader_root)
+            .into_iter()
+            .map(|(load_stmt, _)| load_stmt)
+            .find(|load_stmt| {
+                sema.resolve_load_stmt(loader, load_stmt)
+                    .map_or(false, |loaded| loaded.id(db) == file_id)
+            }) {
+            Some(load_stmt) => load_stmt,
+            None => continue,
+        };
+        let loaded_names = load_stmt
+            .items()
+            .filter_map(|item| local_name(&item))
+            .collect::<FxHashSet<_>>();
+        let mut loader_edits = Vec::new();
+        let mut names = Vec::new();
+        for (edit, name) in access_edits(db, &sema, loader, &loader_root, &provider_names) {
+            if !loaded_names.contains(name) && !names.contains(&name) {
+                names.push(name);
+            }
+            loader_edits.push(edit);
+        }
+        if loader_edits.is_empty() {
+            continue;
+        }
+        let end = load_stmt
+            .items()
+            .last()
+            .map(|item| item.syntax().text_range().end())
+            .or_else(|| {
+                load_stmt
+                    .module()
+                    .map(|module| module.syntax().text_range().end())
+            });
+        if let (Some(end), false) = (end, names.is_empty()) {
+            loader_edits.insert(
+                0,
+                TextEdit {
+                    range: TextRange::empty(end),
+                    new_text: names.iter().map(|name| format!(", \"{}\"", name)).collect(),
+                },
+            );
+        }
+        file_edits.push(FileEdit {
+            target: EditTarget::File(loader.id(db)),
+            edits: loader_edits,
+        });
+    }
+
+    Some(CodeAction {
+        title: "Convert legacy struct providers to modern providers".to_string(),
+        kind: CodeActionKind::RefactorRewrite,
+        edits,
+        file_edits,
+        command: None,
+        is_preferred: false,
+    })
+}
+
+/// Whether the function with the given name is passed as the `implementation` of a rule declared
+/// in the same file.
+fn is_rule_implementation(root: &SyntaxNode, name: &str) -> bool {
+    root.descendants()
+        .filter_map(ast::CallExpr::cast)
+        .filter(|call| callee_name(call).as_deref() == Some("rule"))
+        .filter_map(|call| call.arguments())
+        .flat_map(|args| args.arguments())
+        .any(|arg| match arg {
+            ast::Argument::Keyword(arg) => {
+                arg.name()
+                    .and_then(|name| name.name())
+                    .map_or(false, |token| token.text() == "implementation")
+                    && matches!(arg.expr(), Some(ast::Expression::Name(name_ref))
+                        if name_ref.name().map_or(false, |token| token.text() == name))
+            }
+            _ => false,
+        })
+}
+
+/// Returns the names of the file's top-level functions, variables and loaded symbols.
+fn top_level_names(root: &SyntaxNode) -> FxHashSet<String> {
+    let mut names = FxHashSet::default();
+    for stmt in root.children().filter_map(ast::Statement::cast) {
+        match stmt {
+            ast::Statement::Def(def_stmt) => names.extend(
+                def_stmt
+                    .name()
+                    .and_then(|name| name.name())
+                    .map(|token| token.text().to_string()),
+            ),
+            ast::Statement::Assign(assign_stmt) => names.extend(
+                assign_stmt
+                    .lhs()
+                    .into_iter()
+                    .flat_map(|lhs| lhs.syntax().descendants().collect::<Vec<_>>())
+                    .filter_map(ast::NameRef::cast)
+                    .filter_map(|name_ref| name_ref.name())
+                    .map(|token| token.text().to_string()),
+            ),
+            ast::Statement::Load(load_stmt) => {
+                names.extend(load_stmt.items().filter_map(|item| local_name(&item)))
+            }
+            _ => {}
+        }
+    }
+    names
+}
+
+/// Names a provider after the legacy provider's field, e.g. `MyInfo` for `my_info` and
+/// `TransitiveSrcsInfo` for `transitive_srcs`, avoiding the names that are already used.
+fn provider_name(key: &str, used_names: &FxHashSet<String>) -> String {
+    let mut base = key
+        .split('_')
+        .map(|part| {
+            let mut chars = part.chars();
+            chars
+                .next()
+                .map(|first| first.to_uppercase().chain(chars).collect::<String>())
+                .unwrap_or_default()
+        })
+        .collect::<String>();
+    if !base.ends_with("Info") {
+        base.push_str("Info");
+    }
+    let mut name = base.clone();
+    let mut i = 2;
+    while used_names.contains(&name) {
+        name = format!("{}{}", base, i);
+        i += 1;
+    }
+    name
+}
+
+fn provider_call<I>(name: &str, fields: I) -> String
+where
+    I: Iterator<Item = (String, String)>,
+{
+    format!(
+        "{}({})",
+        name,
+        fields
+            .map(|(field, value)| format!("{} = {}", field, value))
+            .collect::<Vec<_>>()
+            .join(", ")
+    )
+}
+
+/// Output groups written as a dict literal become keyword arguments, while any other value is
+/// unpacked.
+fn output_group_info_call<F>(output_groups: &ast::Expression, value_text: &F) -> String
+where
+    F: Fn(&ast::Expression) -> String,
+{
+    let groups = match output_groups {
+        ast::Expression::Dict(dict) => dict
+            .entries()
+            .map(|entry| {
+                let name = match entry.key()? {
+                    ast::Expression::Literal(lit) => match lit.kind() {
+                        ast::LiteralKind::String(s) => s.value()?.to_string(),
+                        _ => return None,
+                    },
+                    _ => return None,
+                };
+                let is_identifier = name
+                    .chars()
+                    .next()
+                    .map_or(false, |c| c.is_ascii_alphabetic() || c == '_')
+                    && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+                if !is_identifier {
+                    return None;
+                }
+                Some((name, value_text(&entry.value()?)))
+            })
+            .collect::<Option<Vec<_>>>(),
+        _ => None,
+    };
+    match groups {
+        Some(groups) => provider_call("OutputGroupInfo", groups.into_iter()),
+        None => format!("OutputGroupInfo(**{})", value_text(output_groups)),
+    }
+}
+
+/// Returns the edits that rewrite the accesses to the given legacy providers in a file, along with
+/// the name of the provider that each one uses. The edits are ordered and don't overlap.
+fn access_edits<'a>(
+    db: &Database,
+    sema: &Semantics,
+    file: File,
+    root: &SyntaxNode,
+    providers: &'a [(String, String)],
+) -> Vec<(TextEdit, &'a str)> {
+    let mut edits: Vec<(TextEdit, &'a str)> = Vec::new();
+    for node in root.descendants() {
+        let (new_text, name) = match rewrite_access(db, sema, file, &node, providers) {
+            Some(rewrite) => rewrite,
+            None => continue,
+        };
+        // Accesses nested within one that's already rewritten are left as they are.
+        let range = node.text_range();
+        if edits
+            .last()
+            .map_or(false, |(last, _)| range.start() < last.range.end())
+        {
+            continue;
+        }
+        edits.push((TextEdit { range, new_text }, name));
+    }
+    edits
+}
+
+fn rewrite_access<'a>(
+    db: &Database,
+    sema: &Semantics,
+    file: File,
+    node: &SyntaxNode,
+    providers: &'a [(String, String)],
+) -> Option<(String, &'a str)> {
+    let provider_for = |key: &str| {
+        providers
+            .iter()
+            .find(|(provider_key, _)| provider_key == key)
+            .map(|(_, name)| name.as_str())
+    };
+    let may_be_target = |expr: &ast::Expression| {
+        sema.type_of_expr(file, expr)
+            .map_or(true, |ty| ty.is_unknown() || ty.annotation(db) == "Target")
+    };
+
+    if let Some(dot_expr) = ast::DotExpr::cast(node.clone()) {
+        let target = dot_expr.expr()?;
+        let name = provider_for(dot_expr.field()?.name()?.text())?;
+        return may_be_target(&target)
+            .then(|| (format!("{}[{}]", target.syntax().text(), name), name));
+    }
+
+    let call = ast::CallExpr::cast(node.clone())?;
+    let callee = callee_name(&call)?;
+    let args = call.arguments()?.arguments().collect::<Vec<_>>();
+    let (target, key) = match &args[..] {
+        [ast::Argument::Simple(target), ast::Argument::Simple(key)] => {
+            (target.expr()?, key.expr()?)
+        }
+        _ => return None,
+    };
+    let key = match key {
+        ast::Expression::Literal(lit) => match lit.kind() {
+            ast::LiteralKind::String(s) => s.value()?,
+            _ => return None,
+        },
+        _ => return None,
+    };
+    let name = provider_for(&key)?;
+    if !may_be_target(&target) {
+        return None;
+    }
+    match callee.as_str() {
+        "getattr" => Some((format!("{}[{}]", target.syntax().text(), name), name)),
+        "hasattr" => Some((format!("{} in {}", name, target.syntax().text()), name)),
+        _ => None,
+    }
+}
+
+/// Returns the text of a node with the edits that fall within it applied.
+fn rewritten_text(node: &SyntaxNode, edits: &[TextEdit]) -> String {
+    let range = node.text_range();
+    let mut text = node.text().to_string();
+    for edit in edits
+        .iter()
+        .rev()
+        .filter(|edit| range.contains_range(edit.range))
+    {
+        let start = usize::from(edit.range.start() - range.start());
+        let end = usize::from(edit.range.end() - range.start());
+        text.replace_range(start..end, &edit.new_text);
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use starpls_bazel::APIContext;
+    use starpls_common::{Dialect, FileId, FileRange};
+    use starpls_syntax::{TextRange, TextSize};
+
+    use crate::{AnalysisSnapshot, EditTarget, TextEdit};
+
+    const TITLE: &str = "Convert legacy struct providers to modern providers";
+
+    fn apply(text: &str, edits: &[TextEdit]) -> String {
+        let mut edits = edits.to_vec();
+        edits.sort_by_key(|edit| std::cmp::Reverse(edit.range.start()));
+        let mut text = text.to_string();
+        for edit in edits {
+            text.replace_range(std::ops::Range::<usize>::from(edit.range), &edit.new_text);
+        }
+        text
+    }
+
+    #[test]
+    fn test_convert_legacy_providers() {
+        let defs = r#"def _impl(ctx):
+    files = depset(ctx.files.srcs)
+    return struct(
+        files = files,
+        my_info = struct(srcs = files, count = 1),
+        transitive_srcs = files,
+    )
+
+my_rule = rule(implementation = _impl)
+
+def _consumer_impl(ctx):
+    srcs = [dep.my_info.srcs for dep in ctx.attr.deps if hasattr(dep, "my_info")]
+    return []
+"#;
+        let user = r#"load("defs.bzl", "my_rule")
+
+def get_srcs(dep):
+    return dep.transitive_srcs
+"#;
+        let pos = TextSize::from(defs.find("return struct").unwrap() as u32);
+        let actions =
+            AnalysisSnapshot::from_files(&[("defs.bzl", defs), ("user.bzl", user)], Dialect::Bazel)
+                .code_actions(FileRange {
+                    file_id: FileId(0),
+                    range: TextRange::empty(pos),
+                })
+                .unwrap()
+                .unwrap();
+        let action = actions.iter().find(|action| action.title == TITLE).unwrap();
+        assert_eq!(
+            apply(defs, &action.edits),
+            r#"MyInfo = provider(fields = ["srcs", "count"])
+
+TransitiveSrcsInfo = provider(fields = ["transitive_srcs"])
+
+def _impl(ctx):
+    files = depset(ctx.files.srcs)
+    return [
+        DefaultInfo(files = files),
+        MyInfo(srcs = files, count = 1),
+        TransitiveSrcsInfo(transitive_srcs = files),
+    ]
+
+my_rule = rule(implementation = _impl)
+
+def _consumer_impl(ctx):
+    srcs = [dep[MyInfo].srcs for dep in ctx.attr.deps if MyInfo in dep]
+    return []
+"#
+        );
+        assert_eq!(action.file_edits.len(), 1);
+        assert_eq!(action.file_edits[0].target, EditTarget::File(FileId(1)));
+        assert_eq!(
+            apply(user, &action.file_edits[0].edits),
+            r#"load("defs.bzl", "my_rule", "TransitiveSrcsInfo")
+
+def get_srcs(dep):
+    return dep[TransitiveSrcsInfo]
+"#
+        );
+    }
+
+    #[test]
+    fn test_not_a_rule_implementation() {
+        let (snap, file_id) = AnalysisSnapshot::from_single_file(
+            "def make_info():\n    return struct(my_info = 1)\n",
+            Dialect::Bazel,
+            Some(APIContext::Bzl),
+        );
+        let actions = snap
+            .code_actions(FileRange {
+                file_id,
+                range: TextRange::empty(TextSize::from(20)),
+            })
+            .unwrap()
+            .unwrap();
+        assert!(actions.iter().all(|action| action.title != TITLE));
+    }
+}
@@ -1,38 +1,144 @@
-use std::{fmt::Debug, panic, path::PathBuf, sync::Arc};
+use std::{
+    fmt::Debug,
+    panic,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Instant,
+};
 
 use dashmap::{mapref::entry::Entry, DashMap};
 use rustc_hash::FxHashMap;
 use salsa::ParallelDatabase;
-use starpls_bazel::{APIContext, Builtins};
-use starpls_common::{Db, Diagnostic, Dialect, File, FileId, LoadItemCandidate, ResolvedPath};
-use starpls_hir::{BuiltinDefs, Db as _, ExprId, GlobalCtxt, LoadItemId, LoadStmt, ParamId, Ty};
-pub use starpls_hir::{Cancelled, InferenceOptions};
+use starpls_bazel::{lockfile::ModuleLockfile, registry::RegistryMetadata, APIContext, Builtins};
+use starpls_common::{
+    Db, Diagnostic, Dialect, File, FileId, FileRange, LabelCandidates, LoadItemCandidate,
+    ResolvedPath,
+};
+pub use starpls_fmt::Tables as BuildifierTables;
+use starpls_hir::{
+    BuiltinDefs, Db as _, ExprId, GlobalCtxt, LoadItemId, LoadStmt, ModuleInterface, ParamId, Ty,
+};
+pub use starpls_hir::{Cancelled, InferenceOptions, StringKind};
 use starpls_syntax::{LineIndex, TextRange, TextSize};
 use starpls_test_util::make_test_builtins;
 
+#[cfg(feature = "lint-plugins")]
+pub use crate::lint_rules::{Lint, LintContext, LintFix, LintRule};
 pub use crate::{
-    completions::{CompletionItem, CompletionItemKind, CompletionMode},
+    builtin_stubs::BuiltinStub,
+    call_hierarchy::{CallHierarchyItem, IncomingCall, OutgoingCall},
+    code_actions::{CodeAction, CodeActionCommand, CodeActionKind, EditTarget, FileEdit},
+    completions::{CompletionItem, CompletionItemKind, CompletionMode, TextEdit},
+    diagnostics::{DiagnosticCategory, DiagnosticsOptions, SeverityOverrides},
     document_symbols::{DocumentSymbol, SymbolKind, SymbolTag},
+    file_stats::FileStats,
+    folding_ranges::{FoldingRange, FoldingRangeKind},
+    formatting::minimal_edit,
+    fuzz::analyze_arbitrary_input,
     hover::{Hover, Markup},
+    indentation::{indentation_rules, IndentAction, IndentationRules, OnEnterRule},
+    inlay_hints::{InlayHint, InlayHintKind, InlayHintsConfig},
+    inline_values::InlineValue,
+    label_references::LabelReference,
+    label_style::LabelStyle,
+    load_aliases::{AliasStandardization, LoadAliasDrift, LoadAliasSite},
+    load_graph::{index_load_paths, LoadGraph},
+    load_hotspots::LoadHotspot,
+    missing_loads::ExportedSymbol,
+    module_deps::{parse_module_file, DependencyUpdate},
+    module_symbols::{ModuleSymbol, ModuleSymbolKind},
+    rename::Rename,
+    rule_attributes::{RuleAttribute, RuleSchema},
+    runnables::{Runnable, RunnableKind},
+    semantic_tokens::SemanticToken,
     signature_help::{ParameterInfo, SignatureHelp, SignatureInfo},
+    symbol_usages::SymbolUsage,
+    target_index::{IndexedTarget, TargetIndex},
+    target_query::TargetQuery,
+    targets::{Target, TargetAttribute, TargetAttributeValue},
+    todos::{TodoItem, DEFAULT_TODO_TAGS},
+    workspace_symbols::{
+        fuzzy_match, index_workspace_symbols, WorkspaceSymbol, WorkspaceSymbolKind,
+    },
 };
 
+mod attr_values;
+mod build_settings;
+mod builtin_stubs;
+mod call_hierarchy;
+mod code_actions;
 mod completions;
 mod diagnostics;
+mod dict_keys;
 mod document_symbols;
+mod duplicates;
+mod encoding;
+mod extract_macro;
+mod field_references;
+mod file_stats;
+mod folding_ranges;
+mod formatting;
+mod fuzz;
+#[cfg(test)]
+mod golden_tests;
 mod goto_definition;
+mod header;
 mod hover;
+mod indentation;
+mod inlay_hints;
+mod inline_values;
+mod label_references;
+mod label_style;
+mod labels;
+mod legacy_providers;
 mod line_index;
+#[cfg(feature = "lint-plugins")]
+mod lint_rules;
+mod load_aliases;
+mod load_graph;
+mod load_hotspots;
+mod macro_targets;
+mod missing_loads;
+mod module_deps;
+mod module_symbols;
+mod native_prefix;
+mod organize_loads;
+mod package_moves;
+mod platform_targets;
+mod references;
+mod rename;
+mod rule_attributes;
+mod runnables;
+mod selection_ranges;
+mod semantic_tokens;
 mod show_hir;
 mod show_syntax_tree;
 mod signature_help;
+mod sort_lists;
+mod spellcheck;
+mod symbol_usages;
+mod target_index;
+mod target_query;
+mod targets;
+mod test_scaffold;
+mod todos;
+mod type_annotations;
+mod unused;
 mod util;
+mod workspace_symbols;
 
 pub type Cancellable<T> = Result<T, Cancelled>;
 
 #[salsa::db(starpls_common::Jar, starpls_hir::Jar)]
 pub(crate) struct Database {
+    buildifier_tables: Option<Arc<BuildifierTables>>,
     builtin_defs: Arc<DashMap<Dialect, BuiltinDefs>>,
+    diagnostics_options: Arc<DiagnosticsOptions>,
+    #[cfg(feature = "lint-plugins")]
+    lint_rules: Arc<Vec<Arc<dyn LintRule>>>,
+    severity_overrides: Arc<DashMap<FileId, SeverityOverrides>>,
+    module_lockfiles: Arc<DashMap<FileId, Arc<ModuleLockfile>>>,
+    registry_metadata: Arc<RegistryMetadata>,
     storage: salsa::Storage<Self>,
     files: Arc<DashMap<FileId, File>>,
     loader: Arc<dyn FileLoader>,
@@ -42,7 +148,12 @@ pub(crate) struct Database {
 impl Database {
     fn apply_file_changes(&mut self, changes: Vec<(FileId, FileChange)>) {
         let gcx = self.gcx.clone();
-        let _guard = gcx.cancel();
+        let mut guard = gcx.cancel();
+
+        // Inference results are only kept for the files that aren't affected by the change. If a
+        // file was created, every result is discarded, since loads that previously failed might
+        // now resolve to it.
+        let mut updated_files = Some(Vec::new());
         for (file_id, change) in changes {
             match change {
                 FileChange::Create {
@@ -50,13 +161,49 @@ impl Database {
                     api_context,
                     contents,
                 } => {
+                    updated_files = None;
                     self.create_file(file_id, dialect, api_context, contents);
                 }
                 FileChange::Update { contents } => {
+                    let file = self.get_file(file_id);
+                    if let (Some(updated_files), Some(file)) = (&mut updated_files, file) {
+                        updated_files.push((file, ModuleInterface::new(self, file)));
+                    }
                     self.update_file(file_id, contents);
                 }
             }
         }
+
+        if let Some(updated_files) = updated_files {
+            let changed_files = updated_files
+                .into_iter()
+                .map(|(file, interface)| (file, ModuleInterface::new(self, file) != interface))
+                .collect::<Vec<_>>();
+            guard.invalidate_files(self, changed_files);
+        }
+    }
+
+    /// Returns the files that directly `load` the given file according to the loader's index of
+    /// the workspace, adding the ones that aren't in the database yet. Returns `None` if the
+    /// workspace isn't indexed, in which case loaders can only be found among the files in the
+    /// database.
+    pub(crate) fn workspace_loaders(&self, file_id: FileId) -> Option<Vec<File>> {
+        let loaders = self.loader.workspace_loaders(file_id)?;
+        Some(
+            loaders
+                .into_iter()
+                .filter_map(|loader_id| {
+                    if let Some(file) = self.get_file(loader_id) {
+                        return Some(file);
+                    }
+                    let (dialect, api_context, contents) =
+                        self.loader.read_workspace_file(loader_id)?;
+                    Some(*self.files.entry(loader_id).or_insert_with(|| {
+                        File::new(self, loader_id, dialect, api_context, contents)
+                    }))
+                })
+                .collect(),
+        )
     }
 }
 
@@ -65,7 +212,14 @@ impl salsa::Database for Database {}
 impl salsa::ParallelDatabase for Database {
     fn snapshot(&self) -> salsa::Snapshot<Self> {
         salsa::Snapshot::new(Database {
+            buildifier_tables: self.buildifier_tables.clone(),
             builtin_defs: self.builtin_defs.clone(),
+            diagnostics_options: self.diagnostics_options.clone(),
+            #[cfg(feature = "lint-plugins")]
+            lint_rules: self.lint_rules.clone(),
+            severity_overrides: self.severity_overrides.clone(),
+            module_lockfiles: self.module_lockfiles.clone(),
+            registry_metadata: self.registry_metadata.clone(),
             files: self.files.clone(),
             gcx: self.gcx.clone(),
             loader: self.loader.clone(),
@@ -132,6 +286,38 @@ impl starpls_common::Db for Database {
         self.loader.list_load_candidates(path, dialect, from)
     }
 
+    fn list_label_candidates(
+        &self,
+        path: &str,
+        from: FileId,
+    ) -> anyhow::Result<Option<LabelCandidates>> {
+        let dialect = match self.get_file(from) {
+            Some(file) => file.dialect(self),
+            None => return Ok(None),
+        };
+        let (candidates, build_file) =
+            match self.loader.list_label_candidates(path, dialect, from)? {
+                Some(res) => res,
+                None => return Ok(None),
+            };
+        let build_file = build_file.map(|(build_file, contents)| {
+            if let Entry::Vacant(entry) = self.files.entry(build_file) {
+                entry.insert(File::new(
+                    self,
+                    build_file,
+                    Dialect::Bazel,
+                    Some(APIContext::Build),
+                    contents.unwrap_or_default(),
+                ));
+            }
+            build_file
+        });
+        Ok(Some(LabelCandidates {
+            candidates,
+            build_file,
+        }))
+    }
+
     fn resolve_path(
         &self,
         path: &str,
@@ -155,7 +341,7 @@ impl starpls_common::Db for Database {
                         self,
                         build_file,
                         Dialect::Bazel,
-                        Some(APIContext::Bzl),
+                        Some(APIContext::Build),
                         contents.take().unwrap_or_default(),
                     ));
                 }
@@ -272,7 +458,14 @@ impl Analysis {
     pub fn new(loader: Arc<dyn FileLoader>, options: InferenceOptions) -> Self {
         Self {
             db: Database {
+                buildifier_tables: None,
                 builtin_defs: Default::default(),
+                diagnostics_options: Default::default(),
+                #[cfg(feature = "lint-plugins")]
+                lint_rules: Default::default(),
+                severity_overrides: Default::default(),
+                module_lockfiles: Default::default(),
+                registry_metadata: Default::default(),
                 files: Default::default(),
                 gcx: Arc::new(GlobalCtxt::new(options)),
                 storage: Default::default(),
@@ -281,6 +474,53 @@ impl Analysis {
         }
     }
 
+    /// Creates an analysis of several files that can load each other by their paths. Files are
+    /// given IDs in order, starting from 0. Bazel files get the API context that their names
+    /// imply, e.g. `BUILD` files are `Build` files.
+    pub fn from_files(files: &[(&str, &str)], dialect: Dialect) -> Self {
+        Self::from_workspace(files, &[], dialect)
+    }
+
+    /// Like [`Analysis::from_files`], but the given files make up the whole workspace, and only
+    /// the `open` ones are added to the database. The `closed` ones are like files that aren't
+    /// open in the editor: they're only found by loading them or through the loader's index of
+    /// the workspace. IDs are given to the `open` files first.
+    pub fn from_workspace(
+        open: &[(&str, &str)],
+        closed: &[(&str, &str)],
+        dialect: Dialect,
+    ) -> Self {
+        let mut file_set = FxHashMap::default();
+        let mut change = Change::default();
+        for (i, (path, contents)) in open.iter().chain(closed).enumerate() {
+            let file_id = FileId(i as u32);
+            file_set.insert(path.to_string(), (file_id, contents.to_string()));
+            if i < open.len() {
+                change.create_file(
+                    file_id,
+                    dialect,
+                    api_context_for_path(dialect, path),
+                    contents.to_string(),
+                );
+            }
+        }
+        let mut analysis = Analysis::new(
+            Arc::new(SimpleFileLoader::from_workspace(file_set, dialect)),
+            Default::default(),
+        );
+        analysis.db.set_builtin_defs(
+            Dialect::Bazel,
+            make_test_builtins(
+                vec!["provider".to_string(), "struct".to_string()],
+                vec![],
+                vec![],
+            ),
+            Builtins::default(),
+        );
+        analysis.apply_change(change);
+        analysis
+    }
+
     pub fn apply_change(&mut self, change: Change) {
         self.db.apply_file_changes(change.changed_files);
     }
@@ -291,9 +531,54 @@ impl Analysis {
         }
     }
 
+    /// Sets the Buildifier tables used to sort the arguments and lists of calls in `BUILD` files,
+    /// both when formatting and in code actions. Without tables, formatting keeps their order,
+    /// and code actions use Buildifier's default tables.
+    pub fn set_buildifier_tables(&mut self, tables: Option<BuildifierTables>) {
+        self.db.buildifier_tables = tables.map(Arc::new);
+    }
+
     pub fn set_builtin_defs(&mut self, builtins: Builtins, rules: Builtins) {
         self.db.set_builtin_defs(Dialect::Bazel, builtins, rules);
     }
+
+    /// Sets the options for opt-in diagnostics. These take effect for all subsequent snapshots.
+    pub fn set_diagnostics_options(&mut self, options: DiagnosticsOptions) {
+        self.db.diagnostics_options = Arc::new(options);
+    }
+
+    /// Sets the custom lint rules to run alongside the builtin diagnostics. These take effect for
+    /// all subsequent snapshots.
+    #[cfg(feature = "lint-plugins")]
+    pub fn set_lint_rules(&mut self, rules: Vec<Arc<dyn LintRule>>) {
+        self.db.lint_rules = Arc::new(rules);
+    }
+
+    /// Sets the per-category severity overrides for the given file.
+    pub fn set_severity_overrides(&mut self, file_id: FileId, overrides: SeverityOverrides) {
+        if overrides.is_empty() {
+            self.db.severity_overrides.remove(&file_id);
+        } else {
+            self.db.severity_overrides.insert(file_id, overrides);
+        }
+    }
+
+    /// Sets the lockfile that records the resolved dependencies of the given `MODULE.bazel` file.
+    pub fn set_module_lockfile(&mut self, file_id: FileId, lockfile: Option<ModuleLockfile>) {
+        match lockfile {
+            Some(lockfile) => {
+                self.db.module_lockfiles.insert(file_id, Arc::new(lockfile));
+            }
+            None => {
+                self.db.module_lockfiles.remove(&file_id);
+            }
+        }
+    }
+
+    /// Sets the cached registry metadata used to find newer versions of `bazel_dep`s.
+    pub fn set_registry_metadata(&mut self, metadata: RegistryMetadata) {
+        self.db.registry_metadata = Arc::new(metadata);
+    }
 }
 
 pub struct AnalysisSnapshot {
@@ -328,6 +613,68 @@ impl AnalysisSnapshot {
         (analysis.snapshot(), file_id)
     }
 
+    /// Like [`AnalysisSnapshot::from_single_file`], but for several files that can load each other
+    /// by their paths. See [`Analysis::from_files`].
+    pub fn from_files(files: &[(&str, &str)], dialect: Dialect) -> Self {
+        Analysis::from_files(files, dialect).snapshot()
+    }
+
+    pub fn code_actions(&self, range: FileRange) -> Cancellable<Option<Vec<CodeAction>>> {
+        self.query(|db| code_actions::code_actions(db, range))
+    }
+
+    /// Returns the `source.fixAll` action for a file, which combines every fix that can be applied
+    /// without user input. This is separate from [`AnalysisSnapshot::code_actions`], since most
+    /// requests for code actions don't ask for it.
+    pub fn fix_all_action(&self, file_id: FileId) -> Cancellable<Option<CodeAction>> {
+        self.query(|db| code_actions::fix_all_action(db, db.get_file(file_id)?))
+    }
+
+    /// Returns the names in the given range that aren't defined, which
+    /// [`AnalysisSnapshot::add_load_actions`] can offer to load.
+    pub fn unresolved_names(&self, range: FileRange) -> Cancellable<Option<Vec<String>>> {
+        self.query(|db| {
+            let file = db.get_file(range.file_id)?;
+            Some(missing_loads::unresolved_names(db, file, range.range))
+        })
+    }
+
+    /// Returns quick fixes that load the undefined names in the given range, from the modules in
+    /// `exports` that define them or from well-known rules repositories.
+    pub fn add_load_actions(
+        &self,
+        range: FileRange,
+        exports: Vec<ExportedSymbol>,
+    ) -> Cancellable<Option<Vec<CodeAction>>> {
+        self.query(|db| {
+            let file = db.get_file(range.file_id)?;
+            Some(missing_loads::add_load_actions(
+                db,
+                file,
+                range.range,
+                &exports,
+            ))
+        })
+    }
+
+    /// Returns actions that generate analysis tests for the rule or macro at the given range.
+    /// `file_name` is the name of the file, which the generated files are named after.
+    pub fn test_scaffold_actions(
+        &self,
+        range: FileRange,
+        file_name: String,
+    ) -> Cancellable<Option<Vec<CodeAction>>> {
+        self.query(|db| {
+            let file = db.get_file(range.file_id)?;
+            Some(test_scaffold::test_scaffold_actions(
+                db,
+                file,
+                range.range,
+                &file_name,
+            ))
+        })
+    }
+
     pub fn completion(
         &self,
         pos: FilePosition,
@@ -336,6 +683,13 @@ impl AnalysisSnapshot {
         self.query(|db| completions::completions(db, pos, trigger_character))
     }
 
+    pub fn dependency_updates(
+        &self,
+        file_id: FileId,
+    ) -> Cancellable<Option<Vec<DependencyUpdate>>> {
+        self.query(|db| module_deps::dependency_updates(db, file_id))
+    }
+
     pub fn diagnostics(&self, file_id: FileId) -> Cancellable<Vec<Diagnostic>> {
         self.query(|db| diagnostics::diagnostics(db, file_id))
     }
@@ -344,6 +698,24 @@ impl AnalysisSnapshot {
         self.query(|db| document_symbols::document_symbols(db, file_id))
     }
 
+    pub fn file_stats(&self, file_id: FileId) -> Cancellable<Option<FileStats>> {
+        self.query(|db| file_stats::file_stats(db, file_id))
+    }
+
+    pub fn folding_ranges(&self, file_id: FileId) -> Cancellable<Option<Vec<FoldingRange>>> {
+        self.query(|db| folding_ranges::folding_ranges(db, file_id))
+    }
+
+    /// Formats a file, returning `None` if it has syntax errors.
+    pub fn format(&self, file_id: FileId) -> Cancellable<Option<Vec<TextEdit>>> {
+        self.query(|db| formatting::format(db, file_id))
+    }
+
+    /// Formats the top-level statements that intersect the given range.
+    pub fn format_range(&self, range: FileRange) -> Cancellable<Option<Vec<TextEdit>>> {
+        self.query(|db| formatting::format_range(db, range))
+    }
+
     pub fn goto_definition(&self, pos: FilePosition) -> Cancellable<Option<Vec<LocationLink>>> {
         self.query(|db| {
             let res = goto_definition::goto_definition(db, pos);
@@ -355,10 +727,189 @@ impl AnalysisSnapshot {
         self.query(|db| hover::hover(db, pos))
     }
 
+    /// Returns the calls to the call hierarchy item with the given selection range, grouped by
+    /// their callers.
+    pub fn incoming_calls(&self, item: FileRange) -> Cancellable<Option<Vec<IncomingCall>>> {
+        self.query(|db| call_hierarchy::incoming_calls(db, item))
+    }
+
+    /// Finds the calls like [`AnalysisSnapshot::incoming_calls`], but passes them to `sink` in
+    /// batches as they're found, so that they can be reported before the search finishes.
+    pub fn incoming_calls_in_batches(
+        &self,
+        item: FileRange,
+        sink: &mut dyn FnMut(Vec<IncomingCall>),
+    ) -> Cancellable<Option<()>> {
+        let sink = panic::AssertUnwindSafe(sink);
+        self.query(move |db| {
+            // Move the whole wrapper into the closure, rather than just the reference inside it.
+            let sink = sink;
+            call_hierarchy::find_incoming_calls(db, item, sink.0)
+        })
+    }
+
+    pub fn inlay_hints(
+        &self,
+        range: FileRange,
+        config: InlayHintsConfig,
+    ) -> Cancellable<Option<Vec<InlayHint>>> {
+        self.query(|db| inlay_hints::inlay_hints(db, range, config))
+    }
+
+    pub fn inline_values(
+        &self,
+        range: FileRange,
+        stopped_at: TextSize,
+    ) -> Cancellable<Option<Vec<InlineValue>>> {
+        self.query(|db| inline_values::inline_values(db, range, stopped_at))
+    }
+
+    pub fn label_references(&self) -> Cancellable<Vec<LabelReference>> {
+        self.query(|db| label_references::label_references(db))
+    }
+
     pub fn line_index<'a>(&'a self, file_id: FileId) -> Cancellable<Option<&'a LineIndex>> {
         self.query(move |db| line_index::line_index(db, file_id))
     }
 
+    /// Finds the symbols that the given files load under more than one local name.
+    pub fn load_alias_drift(&self, file_ids: &[FileId]) -> Cancellable<Vec<LoadAliasDrift>> {
+        self.query(|db| load_aliases::load_alias_drift(db, file_ids))
+    }
+
+    pub fn load_graph(&self, file_id: FileId) -> Cancellable<Option<LoadGraph>> {
+        self.query(|db| load_graph::load_graph(db, file_id))
+    }
+
+    pub fn load_hotspots(
+        &self,
+        file_ids: &[FileId],
+        min_build_files: usize,
+    ) -> Cancellable<Vec<LoadHotspot>> {
+        self.query(|db| load_hotspots::load_hotspots(db, file_ids, min_build_files))
+    }
+
+    /// Lowers a file to HIR, parsing it first if needed. Like [`AnalysisSnapshot::parse`], this is
+    /// mostly useful for measuring how long lowering takes.
+    pub fn lower(&self, file_id: FileId) -> Cancellable<Option<()>> {
+        self.query(|db| {
+            let file = db.get_file(file_id)?;
+            starpls_hir::lower(db, file);
+            Some(())
+        })
+    }
+
+    pub fn module_symbols(&self, file_id: FileId) -> Cancellable<Option<Vec<ModuleSymbol>>> {
+        self.query(|db| module_symbols::module_symbols(db, file_id))
+    }
+
+    pub fn on_type_formatting(
+        &self,
+        pos: FilePosition,
+        ch: char,
+        default_indent: &str,
+    ) -> Cancellable<Option<Vec<TextEdit>>> {
+        self.query(|db| indentation::on_type_formatting(db, pos, ch, default_indent))
+    }
+
+    /// Returns the calls made by the call hierarchy item with the given selection range, grouped
+    /// by their callees.
+    pub fn outgoing_calls(&self, item: FileRange) -> Cancellable<Option<Vec<OutgoingCall>>> {
+        self.query(|db| call_hierarchy::outgoing_calls(db, item))
+    }
+
+    /// Returns the edits to the given files that rewrite the labels referring to the package
+    /// `from` or its subpackages to refer to `to` instead.
+    pub fn package_move_edits(
+        &self,
+        file_ids: &[FileId],
+        from: &str,
+        to: &str,
+    ) -> Cancellable<Vec<(FileId, Vec<TextEdit>)>> {
+        self.query(|db| package_moves::package_move_edits(db, file_ids, from, to))
+    }
+
+    /// Parses a file. The result is cached for other queries, so this is mostly useful for
+    /// measuring how long parsing takes.
+    pub fn parse(&self, file_id: FileId) -> Cancellable<Option<()>> {
+        self.query(|db| {
+            let file = db.get_file(file_id)?;
+            starpls_common::parse(db, file);
+            Some(())
+        })
+    }
+
+    pub fn prepare_call_hierarchy(
+        &self,
+        pos: FilePosition,
+    ) -> Cancellable<Option<Vec<CallHierarchyItem>>> {
+        self.query(|db| call_hierarchy::prepare_call_hierarchy(db, pos))
+    }
+
+    /// Finds the references to the symbol or dict key at the given position, including those in
+    /// the files that `load` the symbol.
+    pub fn references(
+        &self,
+        pos: FilePosition,
+        include_declaration: bool,
+    ) -> Cancellable<Option<Vec<FileRange>>> {
+        self.query(|db| references::references(db, pos, include_declaration))
+    }
+
+    /// Finds the references like [`AnalysisSnapshot::references`], but passes them to `sink` in
+    /// batches as they're found, so that they can be reported before the search finishes. Files
+    /// that load the symbol are only searched until `deadline`. Returns whether every file was
+    /// searched.
+    pub fn references_in_batches(
+        &self,
+        pos: FilePosition,
+        include_declaration: bool,
+        deadline: Option<Instant>,
+        sink: &mut dyn FnMut(Vec<FileRange>),
+    ) -> Cancellable<Option<bool>> {
+        let sink = panic::AssertUnwindSafe(sink);
+        self.query(move |db| {
+            // Move the whole wrapper into the closure, rather than just the reference inside it.
+            let sink = sink;
+            references::find_references(db, pos, include_declaration, deadline, sink.0)
+        })
+    }
+
+    pub fn rename(
+        &self,
+        pos: FilePosition,
+        new_name: &str,
+    ) -> Cancellable<anyhow::Result<Option<Rename>>> {
+        self.query(|db| rename::rename(db, pos, new_name))
+    }
+
+    pub fn rule_attributes(&self, file_id: FileId, name: &str) -> Cancellable<Option<RuleSchema>> {
+        self.query(|db| rule_attributes::rule_attributes(db, file_id, name))
+    }
+
+    pub fn runnables(
+        &self,
+        file_id: FileId,
+        pos: Option<TextSize>,
+    ) -> Cancellable<Option<Vec<Runnable>>> {
+        self.query(|db| runnables::runnables(db, file_id, pos))
+    }
+
+    /// Returns the ranges that expanding the selection at each offset goes through, from the
+    /// innermost range to the whole file.
+    pub fn selection_ranges(
+        &self,
+        file_id: FileId,
+        offsets: Vec<TextSize>,
+    ) -> Cancellable<Option<Vec<Vec<TextRange>>>> {
+        self.query(|db| selection_ranges::selection_ranges(db, file_id, &offsets))
+    }
+
+    /// Returns the string literals in a file that are used as labels, paths, or patterns.
+    pub fn semantic_tokens(&self, file_id: FileId) -> Cancellable<Option<Vec<SemanticToken>>> {
+        self.query(|db| semantic_tokens::semantic_tokens(db, file_id))
+    }
+
     pub fn show_hir(&self, file_id: FileId) -> Cancellable<Option<String>> {
         self.query(|db| show_hir::show_hir(db, file_id))
     }
@@ -371,6 +922,36 @@ impl AnalysisSnapshot {
         self.query(|db| signature_help::signature_help(db, pos))
     }
 
+    /// Returns the edits that make every `load` item for a drifting symbol use `alias`.
+    pub fn standardize_load_alias(
+        &self,
+        drift: &LoadAliasDrift,
+        alias: &str,
+    ) -> Cancellable<AliasStandardization> {
+        self.query(|db| load_aliases::standardize_alias(db, drift, alias))
+    }
+
+    pub fn symbol_usages(
+        &self,
+        file_id: FileId,
+        loaded_file_id: FileId,
+        names: &[String],
+    ) -> Cancellable<Option<Vec<SymbolUsage>>> {
+        self.query(|db| symbol_usages::symbol_usages(db, file_id, loaded_file_id, names))
+    }
+
+    pub fn target_index(&self, file_ids: &[FileId]) -> Cancellable<TargetIndex> {
+        self.query(|db| target_index::target_index(db, file_ids))
+    }
+
+    pub fn targets(&self, file_id: FileId) -> Cancellable<Option<Vec<Target>>> {
+        self.query(|db| targets::targets(db, file_id))
+    }
+
+    pub fn todos(&self, file_id: FileId) -> Cancellable<Option<Vec<TodoItem>>> {
+        self.query(|db| todos::todos(db, file_id))
+    }
+
     /// Helper method to handle Salsa cancellations.
     fn query<'a, F, T>(&'a self, f: F) -> Cancellable<T>
     where
@@ -394,6 +975,13 @@ pub enum LocationLink {
         origin_selection_range: Option<TextRange>,
         target_path: PathBuf,
     },
+    /// A line in a generated stub describing builtins, which don't have a source file of their
+    /// own.
+    Builtin {
+        origin_selection_range: Option<TextRange>,
+        stub: BuiltinStub,
+        target_line: u32,
+    },
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -426,17 +1014,88 @@ pub trait FileLoader: Send + Sync + 'static {
         dialect: Dialect,
         from: FileId,
     ) -> anyhow::Result<Option<Vec<LoadItemCandidate>>>;
+
+    /// Returns the packages or files that the partial label in `path` can be completed with,
+    /// along with the `BUILD` file of the label's package if its target is being typed. The
+    /// `BUILD` file's contents are returned too if it hasn't been loaded before.
+    fn list_label_candidates(
+        &self,
+        path: &str,
+        dialect: Dialect,
+        from: FileId,
+    ) -> anyhow::Result<Option<(Vec<LoadItemCandidate>, Option<(FileId, Option<String>)>)>>;
+
+    /// Returns the name of the package that the given `BUILD` file declares, e.g. `foo/bar` for
+    /// `foo/bar/BUILD.bazel`, or `None` if it isn't known.
+    fn package_name(&self, _file_id: FileId) -> Option<String> {
+        None
+    }
+
+    /// Reads up to `max_len` bytes from the start of a file that isn't Starlark, e.g. a data file
+    /// referred to by a label, along with the file's size in bytes. Returns `None` if the file
+    /// can't be read.
+    fn read_file_prefix(&self, _path: &Path, _max_len: usize) -> Option<(Vec<u8>, u64)> {
+        None
+    }
+
+    /// Returns the files in the workspace that directly `load` the given file, or `None` if the
+    /// workspace hasn't been indexed. Unlike the files in the database, these include files that
+    /// aren't open and aren't loaded by any open file.
+    fn workspace_loaders(&self, _file_id: FileId) -> Option<Vec<FileId>> {
+        None
+    }
+
+    /// Reads a file returned by [`FileLoader::workspace_loaders`] so that it can be added to the
+    /// database.
+    fn read_workspace_file(
+        &self,
+        _file_id: FileId,
+    ) -> Option<(Dialect, Option<APIContext>, String)> {
+        None
+    }
+}
+
+/// Returns the API context that a file's name implies in the given dialect, for the files that
+/// tests create by path.
+fn api_context_for_path(dialect: Dialect, path: &str) -> Option<APIContext> {
+    let file_name = path.rsplit('/').next().unwrap_or(path);
+    match (dialect, file_name) {
+        (Dialect::Standard, _) => None,
+        (_, "BUILD" | "BUILD.bazel" | "BUCK" | "BUCK.v2") => Some(APIContext::Build),
+        (_, "MODULE.bazel") => Some(APIContext::Module),
+        (_, "REPO.bazel") => Some(APIContext::Repo),
+        (_, "WORKSPACE" | "WORKSPACE.bazel") => Some(APIContext::Workspace),
+        _ => Some(APIContext::Bzl),
+    }
 }
 
 /// [`FileLoader`] that looks up files by path from a hash map.
 pub(crate) struct SimpleFileLoader {
     file_set: FxHashMap<String, (FileId, String)>,
+    /// The dialect of the files if they make up the whole workspace, in which case their `load`s
+    /// are indexed.
+    workspace_dialect: Option<Dialect>,
 }
 
 impl SimpleFileLoader {
     /// Creates a [`SimpleFileLoader`] from a static set of files.
     pub(crate) fn from_file_set(file_set: FxHashMap<String, (FileId, String)>) -> Self {
-        Self { file_set }
+        Self {
+            file_set,
+            workspace_dialect: None,
+        }
+    }
+
+    /// Creates a [`SimpleFileLoader`] whose files make up the whole workspace, so that files that
+    /// load each other are found even if they aren't in the database.
+    pub(crate) fn from_workspace(
+        file_set: FxHashMap<String, (FileId, String)>,
+        dialect: Dialect,
+    ) -> Self {
+        Self {
+            file_set,
+            workspace_dialect: Some(dialect),
+        }
     }
 }
 
@@ -447,10 +1106,15 @@ impl FileLoader for SimpleFileLoader {
         dialect: Dialect,
         _from: FileId,
     ) -> anyhow::Result<Option<(FileId, Dialect, Option<APIContext>, Option<String>)>> {
+        // Files in a workspace get the API context that their names imply, like when they're
+        // read through the workspace's index.
+        let api_context = self
+            .workspace_dialect
+            .and_then(|dialect| api_context_for_path(dialect, path));
         Ok(self
             .file_set
             .get(path)
-            .map(|(file_id, contents)| (*file_id, dialect, None, Some(contents.clone()))))
+            .map(|(file_id, contents)| (*file_id, dialect, api_context, Some(contents.clone()))))
     }
 
     fn list_load_candidates(
@@ -462,6 +1126,15 @@ impl FileLoader for SimpleFileLoader {
         Ok(None)
     }
 
+    fn list_label_candidates(
+        &self,
+        _path: &str,
+        _dialect: Dialect,
+        _from: FileId,
+    ) -> anyhow::Result<Option<(Vec<LoadItemCandidate>, Option<(FileId, Option<String>)>)>> {
+        Ok(None)
+    }
+
     fn resolve_path(
         &self,
         _path: &str,
@@ -470,4 +1143,37 @@ impl FileLoader for SimpleFileLoader {
     ) -> anyhow::Result<Option<ResolvedPath>> {
         Ok(None)
     }
+
+    fn workspace_loaders(&self, file_id: FileId) -> Option<Vec<FileId>> {
+        self.workspace_dialect?;
+        let path = self
+            .file_set
+            .iter()
+            .find_map(|(path, (id, _))| (*id == file_id).then_some(path))?;
+        let mut loaders = self
+            .file_set
+            .values()
+            .filter(|(_, contents)| index_load_paths(contents).contains(path))
+            .map(|(loader_id, _)| *loader_id)
+            .collect::<Vec<_>>();
+        loaders.sort();
+        Some(loaders)
+    }
+
+    fn read_workspace_file(
+        &self,
+        file_id: FileId,
+    ) -> Option<(Dialect, Option<APIContext>, String)> {
+        let dialect = self.workspace_dialect?;
+        self.file_set
+            .iter()
+            .find(|(_, (id, _))| *id == file_id)
+            .map(|(path, (_, contents))| {
+                (
+                    dialect,
+                    api_context_for_path(dialect, path),
+                    contents.clone(),
+                )
+            })
+    }
 }
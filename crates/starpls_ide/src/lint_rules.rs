@@ -0,0 +1,244 @@
+//! A plugin point for custom lint rules, so that organizations can enforce their own conventions,
+//! e.g. banning a deprecated macro, without forking the builtin checks.
+//!
+//! Rules are compiled into the server when the `lint-plugins` feature is enabled, and registered
+//! with [`Analysis::set_lint_rules`](crate::Analysis::set_lint_rules). Every rule runs on each file
+//! that diagnostics are computed for. Their lints are reported as diagnostics in the
+//! [`Lint`](crate::DiagnosticCategory::Lint) category, whose severity can be overridden like any
+//! other, and their fixes are offered as quick fixes.
+
+use starpls_bazel::APIContext;
+use starpls_common::{
+    parse, Db as _, Diagnostic, DiagnosticCode, Dialect, File, FileId, FileRange, Severity,
+};
+use starpls_hir::Semantics;
+use starpls_syntax::{ast, SyntaxNode, TextRange};
+
+use crate::{CodeAction, CodeActionKind, Database, TextEdit};
+
+/// A custom check that runs alongside the builtin diagnostics.
+pub trait LintRule: Send + Sync + 'static {
+    /// The code of the diagnostics reported by the rule, e.g. `"acme-no-glob"`. Codes should be
+    /// prefixed with the organization's name, so that they don't clash with builtin codes.
+    fn code(&self) -> &'static str;
+
+    /// Checks a file, returning the problems found in it.
+    fn check(&self, ctx: &LintContext) -> Vec<Lint>;
+}
+
+/// The file being checked by a [`LintRule`], along with the semantic model of the workspace.
+pub struct LintContext<'a> {
+    db: &'a Database,
+    file: File,
+}
+
+impl<'a> LintContext<'a> {
+    pub fn file(&self) -> File {
+        self.file
+    }
+
+    pub fn file_id(&self) -> FileId {
+        self.file.id(self.db)
+    }
+
+    pub fn dialect(&self) -> Dialect {
+        self.file.dialect(self.db)
+    }
+
+    pub fn api_context(&self) -> Option<APIContext> {
+        self.file.api_context(self.db)
+    }
+
+    pub fn contents(&self) -> &'a str {
+        self.file.contents(self.db)
+    }
+
+    pub fn module(&self) -> ast::Module {
+        parse(self.db, self.file).tree(self.db)
+    }
+
+    pub fn syntax(&self) -> SyntaxNode {
+        parse(self.db, self.file).syntax(self.db)
+    }
+
+    /// Returns the semantic model, for resolving names, calls, and `load`s, and for looking up
+    /// the inferred types of expressions. Pass [`LintContext::file`] to its methods.
+    pub fn semantics(&self) -> Semantics<'a> {
+        Semantics::new(self.db)
+    }
+}
+
+/// A problem found by a [`LintRule`].
+#[derive(Clone, Debug)]
+pub struct Lint {
+    pub message: String,
+    pub severity: Severity,
+    pub range: TextRange,
+    /// The fixes to offer as quick fixes when the cursor is within `range`.
+    pub fixes: Vec<LintFix>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LintFix {
+    pub title: String,
+    pub edits: Vec<TextEdit>,
+    pub is_preferred: bool,
+}
+
+pub(crate) fn lint_diagnostics(db: &Database, file: File) -> Vec<Diagnostic> {
+    let file_id = file.id(db);
+    lints(db, file)
+        .into_iter()
+        .map(|(code, lint)| Diagnostic {
+            message: lint.message,
+            severity: lint.severity,
+            range: FileRange {
+                file_id,
+                range: lint.range,
+            },
+            code: DiagnosticCode::Lint(code),
+        })
+        .collect()
+}
+
+pub(crate) fn lint_actions(db: &Database, file: File, range: TextRange) -> Vec<CodeAction> {
+    lints(db, file)
+        .into_iter()
+        .filter(|(_, lint)| lint.range.intersect(range).is_some())
+        .flat_map(|(_, lint)| lint.fixes)
+        .map(|fix| CodeAction {
+            title: fix.title,
+            kind: CodeActionKind::QuickFix,
+            edits: fix.edits,
+            file_edits: Vec::new(),
+            command: None,
+            is_preferred: fix.is_preferred,
+        })
+        .collect()
+}
+
+fn lints(db: &Database, file: File) -> Vec<(&'static str, Lint)> {
+    let ctx = LintContext { db, file };
+    db.lint_rules
+        .iter()
+        .flat_map(|rule| {
+            let code = rule.code();
+            rule.check(&ctx).into_iter().map(move |lint| (code, lint))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use starpls_common::{Dialect, FileId, FileRange, Severity};
+    use starpls_syntax::{
+        ast::{self, AstNode},
+        TextRange, TextSize,
+    };
+
+    use super::{Lint, LintContext, LintFix, LintRule};
+    use crate::{Analysis, AnalysisSnapshot, CodeActionKind, DiagnosticCategory, TextEdit};
+
+    /// Reports calls to `glob()`, with a fix that replaces them with an empty list.
+    struct NoGlob;
+
+    impl LintRule for NoGlob {
+        fn code(&self) -> &'static str {
+            "acme-no-glob"
+        }
+
+        fn check(&self, ctx: &LintContext) -> Vec<Lint> {
+            ctx.syntax()
+                .descendants()
+                .filter_map(ast::CallExpr::cast)
+                .filter(|call| match call.callee() {
+                    Some(ast::Expression::Name(name_ref)) => {
+                        name_ref.name().map_or(false, |name| name.text() == "glob")
+                    }
+                    _ => false,
+                })
+                .map(|call| Lint {
+                    message: "Don't use `glob()`".to_string(),
+                    severity: Severity::Warning,
+                    range: call.syntax().text_range(),
+                    fixes: vec![LintFix {
+                        title: "Replace with `[]`".to_string(),
+                        edits: vec![TextEdit {
+                            range: call.syntax().text_range(),
+                            new_text: "[]".to_string(),
+                        }],
+                        is_preferred: true,
+                    }],
+                })
+                .collect()
+        }
+    }
+
+    const CONTENTS: &str = "srcs = glob([\"*.cc\"])\n";
+
+    fn analysis() -> Analysis {
+        let mut analysis = Analysis::from_files(&[("main.star", CONTENTS)], Dialect::Standard);
+        analysis.set_lint_rules(vec![Arc::new(NoGlob)]);
+        analysis
+    }
+
+    fn glob_range() -> TextRange {
+        let start = CONTENTS.find("glob").unwrap();
+        let end = CONTENTS.find(')').unwrap() + 1;
+        TextRange::new(TextSize::from(start as u32), TextSize::from(end as u32))
+    }
+
+    fn lint_codes(snap: &AnalysisSnapshot) -> Vec<String> {
+        snap.diagnostics(FileId(0))
+            .unwrap()
+            .into_iter()
+            .filter(|diagnostic| diagnostic.code.name().starts_with("acme-"))
+            .map(|diagnostic| {
+                assert_eq!(diagnostic.range.range, glob_range());
+                diagnostic.code.name().to_string()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_lint_diagnostics() {
+        let snap = analysis().snapshot();
+        assert_eq!(lint_codes(&snap), vec!["acme-no-glob"]);
+    }
+
+    #[test]
+    fn test_lint_severity_override() {
+        let mut analysis = analysis();
+        analysis.set_severity_overrides(
+            FileId(0),
+            [(DiagnosticCategory::Lint, None)].into_iter().collect(),
+        );
+        assert_eq!(lint_codes(&analysis.snapshot()), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_lint_fixes() {
+        let actions = analysis()
+            .snapshot()
+            .code_actions(FileRange {
+                file_id: FileId(0),
+                range: TextRange::empty(TextSize::from(CONTENTS.find("glob").unwrap() as u32)),
+            })
+            .unwrap()
+            .unwrap()
+            .into_iter()
+            .filter(|action| action.title == "Replace with `[]`")
+            .collect::<Vec<_>>();
+        assert_eq!(actions.len(), 1);
+        assert_eq!(actions[0].kind, CodeActionKind::QuickFix);
+        assert_eq!(
+            actions[0].edits,
+            vec![TextEdit {
+                range: glob_range(),
+                new_text: "[]".to_string(),
+            }]
+        );
+    }
+}
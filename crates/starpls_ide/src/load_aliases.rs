@@ -0,0 +1,444 @@
+//! Finds symbols that are loaded under different local names in different files, e.g.
+//! `load("//lib:defs.bzl", "my_rule")` in one package and
+//! `load("//lib:defs.bzl", rule = "my_rule")` in another, and rewrites the `load` items, along with
+//! the references to them, to use a single name.
+//!
+//! Symbols are identified by the file that exports them rather than by the label in the `load`
+//! statement, so that equivalent labels like `//lib:defs.bzl` and `:defs.bzl` are grouped
+//! together.
+
+use rustc_hash::FxHashMap;
+use starpls_common::{parse, Db as _, File, FileId};
+use starpls_hir::{Name, Semantics};
+use starpls_syntax::{
+    ast::{self, AstNode},
+    TextRange,
+};
+
+use crate::{
+    symbol_usages::symbol_usages,
+    util::{loaded_name, local_name},
+    CodeAction, CodeActionKind, Database, EditTarget, FileEdit, TextEdit,
+};
+
+/// A `load` item that loads a symbol whose local name differs across files.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LoadAliasSite {
+    pub file_id: FileId,
+    /// The range of the `load` item.
+    pub range: TextRange,
+    /// The name that the `load` item binds, which is the symbol's own name for unaliased items.
+    pub alias: String,
+}
+
+/// A symbol that's loaded under more than one local name.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LoadAliasDrift {
+    /// The file that exports the symbol.
+    pub module: FileId,
+    /// The name of the symbol, as exported by the module.
+    pub name: String,
+    /// The local names that the symbol is loaded under, along with the number of `load` items
+    /// using each of them, from the most to the least used.
+    pub aliases: Vec<(String, usize)>,
+    pub sites: Vec<LoadAliasSite>,
+}
+
+impl LoadAliasDrift {
+    /// The alias that most `load` items already use. Ties are broken in favor of loading the
+    /// symbol without an alias.
+    pub fn preferred_alias(&self) -> &str {
+        &self.aliases[0].0
+    }
+}
+
+/// The edits that make every `load` item for a symbol use the same local name.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AliasStandardization {
+    pub edits: Vec<(FileId, Vec<TextEdit>)>,
+    /// The `load` items that were left alone because the new name is already used in their files.
+    pub conflicts: Vec<LoadAliasSite>,
+}
+
+/// Finds the symbols that the given files load under more than one local name, sorted by the
+/// module and then the name of the symbol.
+pub(crate) fn load_alias_drift(db: &Database, file_ids: &[FileId]) -> Vec<LoadAliasDrift> {
+    let sema = Semantics::new(db);
+    let mut groups: FxHashMap<(FileId, String), Vec<LoadAliasSite>> = FxHashMap::default();
+    for file in file_ids.iter().filter_map(|file_id| db.get_file(*file_id)) {
+        for (module, name, site) in load_sites(db, &sema, file) {
+            groups.entry((module, name)).or_default().push(site);
+        }
+    }
+
+    let mut drifts = groups
+        .into_iter()
+        .filter_map(|((module, name), sites)| drift(module, name, sites))
+        .collect::<Vec<_>>();
+    drifts.sort_by(|a, b| (a.module, &a.name).cmp(&(b.module, &b.name)));
+    drifts
+}
+
+fn drift(module: FileId, name: String, sites: Vec<LoadAliasSite>) -> Option<LoadAliasDrift> {
+    let mut aliases: Vec<(String, usize)> = Vec::new();
+    for site in &sites {
+        match aliases.iter_mut().find(|(alias, _)| *alias == site.alias) {
+            Some((_, count)) => *count += 1,
+            None => aliases.push((site.alias.clone(), 1)),
+        }
+    }
+    if aliases.len() < 2 {
+        return None;
+    }
+    aliases.sort_by(|(a, a_count), (b, b_count)| {
+        b_count
+            .cmp(a_count)
+            .then_with(|| (*b == name).cmp(&(*a == name)))
+            .then_with(|| a.cmp(b))
+    });
+    Some(LoadAliasDrift {
+        module,
+        name,
+        aliases,
+        sites,
+    })
+}
+
+/// Returns the `load` items in a file whose modules resolve, along with the module and the name
+/// of the symbol that each of them loads.
+fn load_sites(db: &Database, sema: &Semantics, file: File) -> Vec<(FileId, String, LoadAliasSite)> {
+    let mut sites = Vec::new();
+    for load_stmt in parse(db, file)
+        .syntax(db)
+        .children()
+        .filter_map(ast::LoadStmt::cast)
+    {
+        let module = match sema.resolve_load_stmt(file, &load_stmt) {
+            Some(module) => module.id(db),
+            None => continue,
+        };
+        for load_item in load_stmt.items() {
+            let (name, alias) = match (loaded_name(&load_item), local_name(&load_item)) {
+                (Some(name), Some(alias)) => (name, alias),
+                _ => continue,
+            };
+            sites.push((
+                module,
+                name,
+                LoadAliasSite {
+                    file_id: file.id(db),
+                    range: load_item.syntax().text_range(),
+                    alias,
+                },
+            ));
+        }
+    }
+    sites
+}
+
+/// Rewrites the `load` items of a drifting symbol, and the references to them, to use `alias`.
+/// Items in files where `alias` is already used for something else are reported as conflicts.
+pub(crate) fn standardize_alias(
+    db: &Database,
+    drift: &LoadAliasDrift,
+    alias: &str,
+) -> AliasStandardization {
+    let mut standardization = AliasStandardization::default();
+    let mut sites_by_file: Vec<(FileId, Vec<&LoadAliasSite>)> = Vec::new();
+    for site in drift.sites.iter().filter(|site| site.alias != alias) {
+        match sites_by_file
+            .iter_mut()
+            .find(|(file_id, _)| *file_id == site.file_id)
+        {
+            Some((_, sites)) => sites.push(site),
+            None => sites_by_file.push((site.file_id, vec![site])),
+        }
+    }
+
+    for (file_id, sites) in sites_by_file {
+        let file = match db.get_file(file_id) {
+            Some(file) => file,
+            None => continue,
+        };
+        if is_name_used(db, file, alias) {
+            standardization.conflicts.extend(sites.into_iter().cloned());
+            continue;
+        }
+
+        let root = parse(db, file).syntax(db);
+        let usages =
+            symbol_usages(db, file_id, drift.module, &[drift.name.clone()]).unwrap_or_default();
+        let mut edits = Vec::new();
+        for site in sites {
+            let load_item = match root
+                .descendants()
+                .filter_map(ast::LoadItem::cast)
+                .find(|load_item| load_item.syntax().text_range() == site.range)
+            {
+                Some(load_item) => load_item,
+                None => continue,
+            };
+            let name_token = match &load_item {
+                ast::LoadItem::Direct(item) => item.name(),
+                ast::LoadItem::Aliased(item) => item.name(),
+            };
+            let name_text = match name_token {
+                Some(token) => token.text().to_string(),
+                None => continue,
+            };
+            edits.push(TextEdit {
+                range: site.range,
+                new_text: if alias == drift.name {
+                    name_text
+                } else {
+                    format!("{} = {}", alias, name_text)
+                },
+            });
+            if let Some(usage) = usages.iter().find(|usage| usage.load_range == site.range) {
+                edits.extend(usage.reference_ranges.iter().map(|range| TextEdit {
+                    range: *range,
+                    new_text: alias.to_string(),
+                }));
+            }
+        }
+        if !edits.is_empty() {
+            edits.sort_by_key(|edit| edit.range.start());
+            standardization.edits.push((file_id, edits));
+        }
+    }
+    standardization
+}
+
+/// Returns whether renaming a binding to `name` could change what a name in the file refers to,
+/// i.e. whether `name` is already bound at the top level of the file or referred to anywhere in
+/// it. This is stricter than it needs to be, but it means that no other checks are needed.
+fn is_name_used(db: &Database, file: File, name: &str) -> bool {
+    let sema = Semantics::new(db);
+    let is_bound = sema
+        .scope_for_module(file)
+        .resolve_name(&Name::from(name))
+        .map_or(false, |defs| defs.iter().any(|def| def.is_user_defined()));
+    is_bound
+        || parse(db, file)
+            .syntax(db)
+            .descendants()
+            .filter_map(ast::NameRef::cast)
+            .any(|name_ref| Name::from_ast_node(name_ref) == Name::from(name))
+}
+
+/// Offers to standardize the local names of the symbols loaded by the `load` items in the given
+/// range, across every file known to the database.
+pub(crate) fn load_alias_actions(db: &Database, file: File, range: TextRange) -> Vec<CodeAction> {
+    let sema = Semantics::new(db);
+    let own_sites = load_sites(db, &sema, file);
+    let mut selected: Vec<(FileId, String)> = Vec::new();
+    for (module, name, site) in &own_sites {
+        if site.range.intersect(range).is_some()
+            && !selected.iter().any(|(selected_module, selected_name)| {
+                selected_module == module && selected_name == name
+            })
+        {
+            selected.push((*module, name.clone()));
+        }
+    }
+    if selected.is_empty() {
+        return Vec::new();
+    }
+
+    // Drift can only be found among the files that are already known, so copy them out first,
+    // since resolving a `load` statement may insert new files into the map.
+    let mut file_ids = db
+        .files
+        .iter()
+        .map(|entry| *entry.key())
+        .collect::<Vec<_>>();
+    file_ids.sort();
+    let mut other_sites: Vec<(FileId, String, LoadAliasSite)> = Vec::new();
+    for other_file in file_ids
+        .into_iter()
+        .filter(|file_id| *file_id != file.id(db))
+        .filter_map(|file_id| db.get_file(file_id))
+    {
+        other_sites.extend(load_sites(db, &sema, other_file).into_iter().filter(
+            |(module, name, _)| {
+                selected.iter().any(|(selected_module, selected_name)| {
+                    module == selected_module && name == selected_name
+                })
+            },
+        ));
+    }
+
+    let mut actions = Vec::new();
+    for (module, name) in &selected {
+        let sites = own_sites
+            .iter()
+            .chain(&other_sites)
+            .cloned()
+            .filter(|(site_module, site_name, _)| site_module == module && site_name == name)
+            .map(|(_, _, site)| site)
+            .collect();
+        let drift = match drift(*module, name.clone(), sites) {
+            Some(drift) => drift,
+            None => continue,
+        };
+        for (alias, _) in &drift.aliases {
+            let standardization = standardize_alias(db, &drift, alias);
+            if standardization.edits.is_empty() {
+                continue;
+            }
+            let mut edits = Vec::new();
+            let mut file_edits = Vec::new();
+            for (file_id, file_edit) in standardization.edits {
+                if file_id == file.id(db) {
+                    edits = file_edit;
+                } else {
+                    file_edits.push(FileEdit {
+                        target: EditTarget::File(file_id),
+                        edits: file_edit,
+                    });
+                }
+            }
+            actions.push(CodeAction {
+                title: if *alias == drift.name {
+                    format!("Load \"{}\" without an alias in every file", drift.name)
+                } else {
+                    format!("Load \"{}\" as \"{}\" in every file", drift.name, alias)
+                },
+                kind: CodeActionKind::RefactorRewrite,
+                edits,
+                file_edits,
+                command: None,
+                is_preferred: false,
+            });
+        }
+    }
+    actions
+}
+
+#[cfg(test)]
+mod tests {
+    use starpls_common::{Dialect, FileId, FileRange};
+    use starpls_syntax::{TextRange, TextSize};
+
+    use crate::{AnalysisSnapshot, EditTarget, TextEdit};
+
+    const DEFS: &str = "def my_rule():\n    pass\n\ndef other():\n    pass\n";
+
+    fn apply(text: &str, edits: &[TextEdit]) -> String {
+        let mut text = text.to_string();
+        for edit in edits.iter().rev() {
+            text.replace_range(std::ops::Range::<usize>::from(edit.range), &edit.new_text);
+        }
+        text
+    }
+
+    #[test]
+    fn test_load_alias_drift() {
+        let files = [
+            ("defs.star", DEFS),
+            (
+                "a.star",
+                "load(\"defs.star\", \"my_rule\", \"other\")\nmy_rule()\n",
+            ),
+            (
+                "b.star",
+                "load(\"defs.star\", \"my_rule\", \"other\")\nmy_rule()\n",
+            ),
+            (
+                "c.star",
+                "load(\"defs.star\", r = \"my_rule\", \"other\")\nr()\n",
+            ),
+        ];
+        let snap = AnalysisSnapshot::from_files(&files, Dialect::Standard);
+        let file_ids = (0..files.len() as u32).map(FileId).collect::<Vec<_>>();
+        let drifts = snap.load_alias_drift(&file_ids).unwrap();
+        assert_eq!(drifts.len(), 1);
+        assert_eq!(drifts[0].module, FileId(0));
+        assert_eq!(drifts[0].name, "my_rule");
+        assert_eq!(
+            drifts[0].aliases,
+            vec![("my_rule".to_string(), 2), ("r".to_string(), 1)]
+        );
+        assert_eq!(drifts[0].preferred_alias(), "my_rule");
+
+        let standardization = snap.standardize_load_alias(&drifts[0], "my_rule").unwrap();
+        assert!(standardization.conflicts.is_empty());
+        assert_eq!(standardization.edits.len(), 1);
+        let (file_id, edits) = &standardization.edits[0];
+        assert_eq!(*file_id, FileId(3));
+        assert_eq!(
+            apply(files[3].1, edits),
+            "load(\"defs.star\", \"my_rule\", \"other\")\nmy_rule()\n"
+        );
+    }
+
+    #[test]
+    fn test_standardize_on_alias_with_conflict() {
+        let files = [
+            ("defs.star", DEFS),
+            ("a.star", "load(\"defs.star\", \"my_rule\")\nmy_rule()\n"),
+            ("b.star", "load(\"defs.star\", r = \"my_rule\")\nr()\n"),
+            (
+                "c.star",
+                "load(\"defs.star\", \"my_rule\")\nr = 1\nmy_rule()\n",
+            ),
+        ];
+        let snap = AnalysisSnapshot::from_files(&files, Dialect::Standard);
+        let file_ids = (0..files.len() as u32).map(FileId).collect::<Vec<_>>();
+        let drifts = snap.load_alias_drift(&file_ids).unwrap();
+        let standardization = snap.standardize_load_alias(&drifts[0], "r").unwrap();
+        assert_eq!(standardization.edits.len(), 1);
+        let (file_id, edits) = &standardization.edits[0];
+        assert_eq!(*file_id, FileId(1));
+        assert_eq!(
+            apply(files[1].1, edits),
+            "load(\"defs.star\", r = \"my_rule\")\nr()\n"
+        );
+        assert_eq!(standardization.conflicts.len(), 1);
+        assert_eq!(standardization.conflicts[0].file_id, FileId(3));
+    }
+
+    #[test]
+    fn test_load_alias_actions() {
+        let files = [
+            ("defs.star", DEFS),
+            ("a.star", "load(\"defs.star\", r = \"my_rule\")\nr()\n"),
+            ("b.star", "load(\"defs.star\", \"my_rule\")\nmy_rule()\n"),
+        ];
+        let snap = AnalysisSnapshot::from_files(&files, Dialect::Standard);
+        let start = files[1].1.find("r =").unwrap() as u32;
+        let actions = snap
+            .code_actions(FileRange {
+                file_id: FileId(1),
+                range: TextRange::empty(TextSize::from(start)),
+            })
+            .unwrap()
+            .unwrap()
+            .into_iter()
+            .filter(|action| action.title.ends_with("in every file"))
+            .collect::<Vec<_>>();
+        let titles = actions
+            .iter()
+            .map(|action| action.title.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            titles,
+            vec![
+                "Load \"my_rule\" without an alias in every file",
+                "Load \"my_rule\" as \"r\" in every file",
+            ]
+        );
+        assert_eq!(
+            apply(files[1].1, &actions[0].edits),
+            "load(\"defs.star\", \"my_rule\")\nmy_rule()\n"
+        );
+        assert!(actions[0].file_edits.is_empty());
+        assert!(actions[1].edits.is_empty());
+        assert_eq!(actions[1].file_edits.len(), 1);
+        assert_eq!(actions[1].file_edits[0].target, EditTarget::File(FileId(2)));
+        assert_eq!(
+            apply(files[2].1, &actions[1].file_edits[0].edits),
+            "load(\"defs.star\", r = \"my_rule\")\nr()\n"
+        );
+    }
+}
@@ -0,0 +1,148 @@
+use std::collections::VecDeque;
+
+use rustc_hash::{FxHashMap, FxHashSet};
+use starpls_common::{parse, Db as _, File, FileId};
+use starpls_hir::Semantics;
+use starpls_syntax::{ast, parse_module};
+
+use crate::{util::load_stmts, Database};
+
+/// The transitive `load` relationships of a single file.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LoadGraph {
+    /// Files transitively loaded by the root file, in breadth-first order.
+    pub loadees: Vec<FileId>,
+    /// Files that transitively load the root file, in breadth-first order.
+    pub loaders: Vec<FileId>,
+    /// Direct `load` edges between the root file and the files above, as `(loader, loadee)` pairs.
+    pub edges: Vec<(FileId, FileId)>,
+}
+
+pub(crate) fn load_graph(db: &Database, file_id: FileId) -> Option<LoadGraph> {
+    let root = db.get_file(file_id)?;
+    let mut graph = LoadGraph::default();
+    let mut edges = FxHashSet::default();
+
+    // Walk forwards from the root file to find everything it loads.
+    let mut seen = FxHashSet::default();
+    let mut queue = VecDeque::from([root]);
+    seen.insert(file_id);
+    while let Some(file) = queue.pop_front() {
+        for loaded_file in direct_loadees(db, file) {
+            let loaded_file_id = loaded_file.id(db);
+            edges.insert((file.id(db), loaded_file_id));
+            if seen.insert(loaded_file_id) {
+                graph.loadees.push(loaded_file_id);
+                queue.push_back(loaded_file);
+            }
+        }
+    }
+
+    // Then walk backwards from the root file. The loaders of each file come from the loader's
+    // index of the workspace, which also knows about the files that aren't open. Without an index,
+    // they're found among the files that the database already knows about.
+    let mut reverse_edges = None;
+    let mut seen = FxHashSet::default();
+    let mut queue = VecDeque::from([file_id]);
+    seen.insert(file_id);
+    while let Some(loaded_file_id) = queue.pop_front() {
+        let mut loaders = match db.workspace_loaders(loaded_file_id) {
+            Some(loaders) => loaders,
+            None => reverse_edges
+                .get_or_insert_with(|| reverse_edges_in_db(db))
+                .get(&loaded_file_id)
+                .cloned()
+                .unwrap_or_default(),
+        }
+        .into_iter()
+        .map(|file| file.id(db))
+        .collect::<Vec<_>>();
+        loaders.sort();
+        for loader_id in loaders {
+            edges.insert((loader_id, loaded_file_id));
+            if seen.insert(loader_id) {
+                graph.loaders.push(loader_id);
+                queue.push_back(loader_id);
+            }
+        }
+    }
+
+    graph.edges = edges.into_iter().collect();
+    graph.edges.sort();
+    Some(graph)
+}
+
+/// Builds the reverse graph of the `load`s between the files in the database and everything they
+/// transitively load. The file map is copied out first, since resolving a `load` statement may
+/// insert new files into it.
+fn reverse_edges_in_db(db: &Database) -> FxHashMap<FileId, Vec<File>> {
+    let mut reverse_edges: FxHashMap<FileId, Vec<File>> = FxHashMap::default();
+    let mut pending: Vec<File> = db.files.iter().map(|entry| *entry.value()).collect();
+    let mut visited: FxHashSet<FileId> = pending.iter().map(|file| file.id(db)).collect();
+    while let Some(file) = pending.pop() {
+        for loaded_file in direct_loadees(db, file) {
+            let loaded_file_id = loaded_file.id(db);
+            reverse_edges.entry(loaded_file_id).or_default().push(file);
+            if visited.insert(loaded_file_id) {
+                pending.push(loaded_file);
+            }
+        }
+    }
+    reverse_edges
+}
+
+/// Returns the modules loaded by a file with the given contents, e.g. `//foo:defs.bzl`, without
+/// resolving them. This lets the workspace be indexed without adding every file to the database.
+pub fn index_load_paths(contents: &str) -> Vec<String> {
+    let root = parse_module(contents, &mut |_| {}).syntax();
+    load_stmts(&root)
+        .into_iter()
+        .map(|(_, module)| module)
+        .collect()
+}
+
+pub(crate) fn direct_loadees(db: &Database, file: File) -> Vec<File> {
+    let sema = Semantics::new(db);
+    parse(db, file)
+        .tree(db)
+        .statements()
+        .filter_map(|stmt| match stmt {
+            ast::Statement::Load(load_stmt) => sema.resolve_load_stmt(file, &load_stmt),
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use starpls_common::{Dialect, FileId};
+
+    use crate::{AnalysisSnapshot, LoadGraph};
+
+    #[test]
+    fn test_transitive_loaders_and_loadees() {
+        let files = [
+            ("a.star", "load(\"b.star\", \"b\")\n"),
+            ("b.star", "load(\"c.star\", \"c\")\nb = c\n"),
+            ("c.star", "c = 1\n"),
+            ("d.star", "load(\"a.star\", \"b\")\n"),
+        ];
+        let graph = AnalysisSnapshot::from_files(&files, Dialect::Standard)
+            .load_graph(FileId(1))
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(
+            graph,
+            LoadGraph {
+                loadees: vec![FileId(2)],
+                loaders: vec![FileId(0), FileId(3)],
+                edges: vec![
+                    (FileId(0), FileId(1)),
+                    (FileId(1), FileId(2)),
+                    (FileId(3), FileId(0)),
+                ],
+            }
+        );
+    }
+}
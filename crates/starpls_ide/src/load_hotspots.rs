@@ -0,0 +1,219 @@
+//! Finds `.bzl` files that are loaded by many `BUILD` files but export symbols that few of their
+//! loaders use.
+//!
+//! Bazel evaluates a `.bzl` file, along with everything that it loads, for every package that
+//! loads it. Moving rarely used symbols, and the loads that only they need, out of such a file
+//! into a separate one means that most packages no longer pay for them.
+
+use std::collections::VecDeque;
+
+use rustc_hash::{FxHashMap, FxHashSet};
+use starpls_bazel::APIContext;
+use starpls_common::{Db as _, File, FileId};
+use starpls_hir::{ScopeDef, Semantics};
+
+use crate::{load_graph::direct_loadees, symbol_usages::symbol_usages, Database};
+
+/// Symbols that are loaded by at most this fraction of a file's direct loaders are considered
+/// rarely used.
+const RARELY_USED_RATIO: f64 = 0.1;
+
+/// A `.bzl` file that many `BUILD` files load, along with the symbols that few of them use.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LoadHotspot {
+    pub file_id: FileId,
+    /// The number of `BUILD` files that load the file, either directly or through other files.
+    pub build_file_count: usize,
+    /// The number of files that load the file directly.
+    pub direct_loader_count: usize,
+    /// The symbols exported by the file, along with the number of files that load each of them
+    /// directly, sorted from the least to the most loaded.
+    pub symbols: Vec<(String, usize)>,
+    /// The names of the exported symbols that few of the direct loaders load, which are
+    /// candidates for moving into a separate file.
+    pub rarely_used: Vec<String>,
+}
+
+/// Finds the `.bzl` files among the given files that at least `min_build_files` `BUILD` files
+/// load, and that export both commonly and rarely used symbols. Hotspots are sorted by the
+/// number of `BUILD` files that load them, from most to least.
+pub(crate) fn load_hotspots(
+    db: &Database,
+    file_ids: &[FileId],
+    min_build_files: usize,
+) -> Vec<LoadHotspot> {
+    let files = file_ids
+        .iter()
+        .filter_map(|file_id| db.get_file(*file_id))
+        .collect::<Vec<_>>();
+
+    // Build the reverse graph once, rather than walking it for every file.
+    let mut reverse_edges: FxHashMap<FileId, Vec<FileId>> = FxHashMap::default();
+    let mut build_files = FxHashSet::default();
+    let mut pending = files.clone();
+    let mut visited = files
+        .iter()
+        .map(|file| file.id(db))
+        .collect::<FxHashSet<_>>();
+    while let Some(file) = pending.pop() {
+        if file.api_context(db) == Some(APIContext::Build) {
+            build_files.insert(file.id(db));
+        }
+        for loaded_file in direct_loadees(db, file) {
+            let loaders = reverse_edges.entry(loaded_file.id(db)).or_default();
+            if !loaders.contains(&file.id(db)) {
+                loaders.push(file.id(db));
+            }
+            if visited.insert(loaded_file.id(db)) {
+                pending.push(loaded_file);
+            }
+        }
+    }
+
+    let sema = Semantics::new(db);
+    let mut hotspots = Vec::new();
+    for file in files {
+        if file.api_context(db) != Some(APIContext::Bzl) {
+            continue;
+        }
+        let file_id = file.id(db);
+        let build_file_count = transitive_loaders(&reverse_edges, file_id)
+            .iter()
+            .filter(|loader| build_files.contains(loader))
+            .count();
+        if build_file_count < min_build_files {
+            continue;
+        }
+
+        let names = exported_names(&sema, file);
+        if names.len() < 2 {
+            continue;
+        }
+        let direct_loaders = reverse_edges.get(&file_id).cloned().unwrap_or_default();
+        let mut counts = names
+            .iter()
+            .map(|name| (name.clone(), 0))
+            .collect::<FxHashMap<_, _>>();
+        for loader in &direct_loaders {
+            let loaded_names = symbol_usages(db, *loader, file_id, &names)
+                .unwrap_or_default()
+                .into_iter()
+                .map(|usage| usage.name)
+                .collect::<FxHashSet<_>>();
+            for name in loaded_names {
+                *counts.entry(name).or_default() += 1;
+            }
+        }
+
+        let threshold = direct_loaders.len() as f64 * RARELY_USED_RATIO;
+        let mut symbols = counts.into_iter().collect::<Vec<_>>();
+        symbols.sort_by(|(a_name, a_count), (b_name, b_count)| {
+            a_count.cmp(b_count).then_with(|| a_name.cmp(b_name))
+        });
+        let rarely_used = symbols
+            .iter()
+            .filter(|(_, count)| *count as f64 <= threshold)
+            .map(|(name, _)| name.clone())
+            .collect::<Vec<_>>();
+        if rarely_used.is_empty() || rarely_used.len() == symbols.len() {
+            continue;
+        }
+        hotspots.push(LoadHotspot {
+            file_id,
+            build_file_count,
+            direct_loader_count: direct_loaders.len(),
+            symbols,
+            rarely_used,
+        });
+    }
+
+    hotspots.sort_by(|a, b| {
+        b.build_file_count
+            .cmp(&a.build_file_count)
+            .then_with(|| a.file_id.cmp(&b.file_id))
+    });
+    hotspots
+}
+
+fn transitive_loaders(
+    reverse_edges: &FxHashMap<FileId, Vec<FileId>>,
+    file_id: FileId,
+) -> FxHashSet<FileId> {
+    let mut seen = FxHashSet::default();
+    let mut queue = VecDeque::from([file_id]);
+    while let Some(loaded_file_id) = queue.pop_front() {
+        for loader in reverse_edges.get(&loaded_file_id).into_iter().flatten() {
+            if seen.insert(*loader) {
+                queue.push_back(*loader);
+            }
+        }
+    }
+    seen
+}
+
+/// Returns the names of the symbols that other files can load from the given file, i.e. the
+/// functions and variables that it defines at the top level, other than private ones.
+fn exported_names(sema: &Semantics, file: File) -> Vec<String> {
+    let mut names = sema
+        .scope_for_module(file)
+        .names()
+        .filter(|(name, def)| {
+            matches!(def, ScopeDef::Callable(_) | ScopeDef::Variable(_))
+                && def.is_user_defined()
+                && !name.as_str().starts_with('_')
+        })
+        .map(|(name, _)| name.to_string())
+        .collect::<Vec<_>>();
+    names.sort();
+    names.dedup();
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use starpls_common::{Dialect, FileId};
+
+    use crate::{AnalysisSnapshot, LoadHotspot};
+
+    #[test]
+    fn test_load_hotspots() {
+        let mut files = vec![
+            (
+                "defs.bzl".to_string(),
+                "def common():\n    pass\n\ndef rare():\n    pass\n\n_private = 1\n".to_string(),
+            ),
+            (
+                "macros.bzl".to_string(),
+                "load(\"defs.bzl\", \"common\")\n\ndef macro():\n    common()\n".to_string(),
+            ),
+        ];
+        for i in 0..10 {
+            files.push((
+                format!("pkg{}/BUILD", i),
+                "load(\"defs.bzl\", \"common\")\n".to_string(),
+            ));
+        }
+        files.push((
+            "other/BUILD".to_string(),
+            "load(\"defs.bzl\", \"rare\")\nload(\"macros.bzl\", \"macro\")\n".to_string(),
+        ));
+        let files = files
+            .iter()
+            .map(|(path, contents)| (path.as_str(), contents.as_str()))
+            .collect::<Vec<_>>();
+
+        let file_ids = (0..files.len() as u32).map(FileId).collect::<Vec<_>>();
+        let snap = AnalysisSnapshot::from_files(&files, Dialect::Bazel);
+        assert_eq!(
+            snap.load_hotspots(&file_ids, 5).unwrap(),
+            vec![LoadHotspot {
+                file_id: FileId(0),
+                build_file_count: 11,
+                direct_loader_count: 12,
+                symbols: vec![("rare".to_string(), 1), ("common".to_string(), 11)],
+                rarely_used: vec!["rare".to_string()],
+            }]
+        );
+        assert!(snap.load_hotspots(&file_ids, 12).unwrap().is_empty());
+    }
+}
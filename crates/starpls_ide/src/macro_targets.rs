@@ -0,0 +1,371 @@
+//! Works out the names of the targets that a macro call declares, e.g. `foo_lib` and `foo_test`
+//! for `my_macro(name = "foo")`, so that hovers can list the labels that the call creates.
+//!
+//! The names are found by evaluating the `name` arguments of the calls in the macro's body
+//! symbolically, starting from the arguments at the call site. Only simple string construction
+//! is understood: literals, parameters, local variables, concatenation with `+`, `%s`
+//! formatting, and `str.format`. Calls to other macros are followed a few levels deep. Calls
+//! under `if` and `for` statements are included as well, so the targets may not all be declared
+//! for every set of arguments.
+
+use rustc_hash::FxHashMap;
+use starpls_common::{parse, File};
+use starpls_hir::Semantics;
+use starpls_syntax::{
+    ast::{self, AstNode, BinaryOp},
+    SyntaxNode,
+};
+
+use crate::Database;
+
+/// How many levels of nested macro calls to follow.
+const MAX_DEPTH: usize = 4;
+
+/// Returns the names of the targets declared by a call to a user-defined function, in the order
+/// that the calls declaring them appear. Returns `None` if the call isn't to a user-defined
+/// function, or if none of the names could be worked out.
+pub(crate) fn macro_targets(
+    db: &Database,
+    sema: &Semantics,
+    file: File,
+    call: &ast::CallExpr,
+) -> Option<Vec<String>> {
+    let mut targets = Vec::new();
+    expand_call(db, sema, file, call, &FxHashMap::default(), 0, &mut targets)?;
+    let mut seen = Vec::new();
+    targets.retain(|name| {
+        let is_new = !seen.contains(name);
+        if is_new {
+            seen.push(name.clone());
+        }
+        is_new
+    });
+    (!targets.is_empty()).then_some(targets)
+}
+
+/// Adds the targets declared by the body of the macro that the given call invokes. Returns
+/// `None` if the call isn't to a user-defined function.
+fn expand_call(
+    db: &Database,
+    sema: &Semantics,
+    file: File,
+    call: &ast::CallExpr,
+    env: &FxHashMap<String, String>,
+    depth: usize,
+    targets: &mut Vec<String>,
+) -> Option<()> {
+    let source = sema.resolve_call_expr(file, call)?.source(db)?;
+    let def_stmt = ast::DefStmt::cast(source.value.to_node(&parse(db, source.file).syntax(db)))?;
+    let suite = def_stmt.suite()?;
+
+    // Bind the parameters to the string arguments at the call site, or to their defaults.
+    let args = call
+        .arguments()
+        .into_iter()
+        .flat_map(|args| args.arguments())
+        .collect::<Vec<_>>();
+    let mut macro_env = FxHashMap::default();
+    for (index, param) in def_stmt
+        .parameters()
+        .into_iter()
+        .flat_map(|params| params.parameters())
+        .enumerate()
+    {
+        let param = match param {
+            ast::Parameter::Simple(param) => param,
+            _ => break,
+        };
+        let name = match param.name().and_then(|name| name.name()) {
+            Some(name) => name.text().to_string(),
+            None => continue,
+        };
+        let positional_arg = || match args.get(index) {
+            Some(ast::Argument::Simple(arg)) => arg.expr(),
+            _ => None,
+        };
+        let value = keyword_arg(call, &name)
+            .or_else(positional_arg)
+            .and_then(|expr| eval(&expr, env))
+            .or_else(|| eval(&param.default()?, &FxHashMap::default()));
+        if let Some(value) = value {
+            macro_env.insert(name, value);
+        }
+    }
+
+    expand_suite(
+        db,
+        sema,
+        source.file,
+        &suite,
+        &mut macro_env,
+        depth,
+        targets,
+    );
+    Some(())
+}
+
+fn expand_suite(
+    db: &Database,
+    sema: &Semantics,
+    file: File,
+    suite: &ast::Suite,
+    env: &mut FxHashMap<String, String>,
+    depth: usize,
+    targets: &mut Vec<String>,
+) {
+    for stmt in suite.statements() {
+        match &stmt {
+            // Nested functions only declare targets if they're called, which isn't followed.
+            ast::Statement::Def(_) => continue,
+            ast::Statement::Assign(assign_stmt) => {
+                if let Some(ast::Expression::Name(name_ref)) = assign_stmt.lhs() {
+                    if let Some(name) = name_ref.name() {
+                        match assign_stmt.rhs().and_then(|rhs| eval(&rhs, env)) {
+                            Some(value) => env.insert(name.text().to_string(), value),
+                            None => env.remove(name.text()),
+                        };
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        // Calls in nested suites, e.g. the bodies of `if` statements, are handled along with them.
+        let is_direct_child = |node: &SyntaxNode| {
+            node.parent()
+                .and_then(|parent| parent.ancestors().find_map(ast::Suite::cast))
+                .map_or(false, |parent| parent.syntax() == suite.syntax())
+        };
+        for nested in stmt
+            .syntax()
+            .descendants()
+            .filter(is_direct_child)
+            .filter_map(ast::Suite::cast)
+        {
+            expand_suite(db, sema, file, &nested, &mut env.clone(), depth, targets);
+        }
+        let calls = stmt
+            .syntax()
+            .descendants()
+            .filter(is_direct_child)
+            .filter_map(ast::CallExpr::cast);
+        for call in calls {
+            let name = match keyword_arg(&call, "name").and_then(|expr| eval(&expr, env)) {
+                Some(name) => name,
+                None => continue,
+            };
+            let count = targets.len();
+            if depth < MAX_DEPTH
+                && expand_call(db, sema, file, &call, env, depth + 1, targets).is_some()
+                && targets.len() > count
+            {
+                continue;
+            }
+            targets.push(name);
+        }
+    }
+}
+
+pub(crate) fn keyword_arg(call: &ast::CallExpr, name: &str) -> Option<ast::Expression> {
+    call.arguments()?.arguments().find_map(|arg| match arg {
+        ast::Argument::Keyword(arg)
+            if arg
+                .name()
+                .and_then(|arg_name| arg_name.name())
+                .map_or(false, |arg_name| arg_name.text() == name) =>
+        {
+            arg.expr()
+        }
+        _ => None,
+    })
+}
+
+/// Evaluates an expression that builds a string, given the values of the variables in scope.
+fn eval(expr: &ast::Expression, env: &FxHashMap<String, String>) -> Option<String> {
+    match expr {
+        ast::Expression::Literal(lit) => match lit.kind() {
+            ast::LiteralKind::String(s) => Some(s.value()?.to_string()),
+            _ => None,
+        },
+        ast::Expression::Name(name_ref) => env.get(name_ref.name()?.text()).cloned(),
+        ast::Expression::Paren(paren) => eval(&paren.expr()?, env),
+        ast::Expression::Binary(binary) => {
+            let lhs = eval(&binary.lhs()?, env)?;
+            match binary.binary_op_info()?.1 {
+                BinaryOp::Arith(ast::ArithOp::Add) => Some(lhs + &eval(&binary.rhs()?, env)?),
+                BinaryOp::Arith(ast::ArithOp::Mod) => {
+                    let args = match binary.rhs()? {
+                        ast::Expression::Tuple(tuple) => tuple
+                            .elements()
+                            .map(|element| eval(&element, env))
+                            .collect::<Option<Vec<_>>>()?,
+                        rhs => vec![eval(&rhs, env)?],
+                    };
+                    percent_format(&lhs, &args)
+                }
+                _ => None,
+            }
+        }
+        ast::Expression::Call(call) => {
+            let dot = match call.callee()? {
+                ast::Expression::Dot(dot) => dot,
+                _ => return None,
+            };
+            if dot.field()?.name()?.text() != "format" {
+                return None;
+            }
+            let template = eval(&dot.expr()?, env)?;
+            let mut positional = Vec::new();
+            let mut named = FxHashMap::default();
+            for arg in call.arguments()?.arguments() {
+                match arg {
+                    ast::Argument::Simple(arg) => positional.push(eval(&arg.expr()?, env)?),
+                    ast::Argument::Keyword(arg) => {
+                        named.insert(
+                            arg.name()?.name()?.text().to_string(),
+                            eval(&arg.expr()?, env)?,
+                        );
+                    }
+                    _ => return None,
+                }
+            }
+            str_format(&template, &positional, &named)
+        }
+        _ => None,
+    }
+}
+
+/// Formats a string with `%`, supporting only the `%s` and `%%` conversions.
+fn percent_format(template: &str, args: &[String]) -> Option<String> {
+    let mut result = String::new();
+    let mut args = args.iter();
+    let mut chars = template.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            result.push(c);
+            continue;
+        }
+        match chars.next()? {
+            '%' => result.push('%'),
+            's' => result.push_str(args.next()?),
+            _ => return None,
+        }
+    }
+    args.next().is_none().then_some(result)
+}
+
+/// Formats a string like `str.format`, supporting `{}`, `{0}`, and `{name}` fields without
+/// format specs.
+fn str_format(
+    template: &str,
+    positional: &[String],
+    named: &FxHashMap<String, String>,
+) -> Option<String> {
+    let mut result = String::new();
+    let mut next_index = 0;
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                result.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                result.push('}');
+            }
+            '{' => {
+                let mut field = String::new();
+                loop {
+                    match chars.next()? {
+                        '}' => break,
+                        c => field.push(c),
+                    }
+                }
+                let value = if field.is_empty() {
+                    next_index += 1;
+                    positional.get(next_index - 1)?
+                } else if let Ok(index) = field.parse::<usize>() {
+                    positional.get(index)?
+                } else {
+                    named.get(&field)?
+                };
+                result.push_str(value);
+            }
+            '}' => return None,
+            c => result.push(c),
+        }
+    }
+    Some(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use rustc_hash::FxHashMap;
+    use starpls_common::{Dialect, FileId};
+    use starpls_syntax::TextSize;
+
+    use super::{percent_format, str_format};
+    use crate::{AnalysisSnapshot, FilePosition};
+
+    #[test]
+    fn test_formatting() {
+        let args = ["foo".to_string(), "bar".to_string()];
+        assert_eq!(percent_format("%s_%s", &args).as_deref(), Some("foo_bar"));
+        assert_eq!(percent_format("%s%%", &args[..1]).as_deref(), Some("foo%"));
+        assert_eq!(percent_format("%d", &args[..1]), None);
+        assert_eq!(percent_format("%s", &args), None);
+
+        let mut named = FxHashMap::default();
+        named.insert("suffix".to_string(), "test".to_string());
+        assert_eq!(
+            str_format("{}_{}", &args, &named).as_deref(),
+            Some("foo_bar")
+        );
+        assert_eq!(
+            str_format("{1}_{0}_{suffix}", &args, &named).as_deref(),
+            Some("bar_foo_test")
+        );
+        assert_eq!(
+            str_format("{{{}}}", &args, &named).as_deref(),
+            Some("{foo}")
+        );
+        assert_eq!(str_format("{missing}", &args, &named), None);
+    }
+
+    #[test]
+    fn test_hover_macro_targets() {
+        let defs = r#"def _helper(name, suffix = "_bin"):
+    native.cc_binary(name = name + suffix)
+
+def my_macro(name, visibility = None):
+    lib_name = "%s_lib" % name
+    native.cc_library(name = lib_name, visibility = visibility)
+    native.cc_test(name = "{}_test".format(name), deps = [":" + lib_name])
+    _helper(name = name)
+    if visibility:
+        native.filegroup(name = name + "_files")
+    for src in ["a", "b"]:
+        native.genrule(name = name + "_" + src)
+"#;
+        let build = r#"load("defs.bzl", "my_macro")
+
+my_macro(name = "foo")
+"#;
+        let hover =
+            AnalysisSnapshot::from_files(&[("defs.bzl", defs), ("BUILD", build)], Dialect::Bazel)
+                .hover(FilePosition {
+                    file_id: FileId(1),
+                    pos: TextSize::from(build.find("my_macro(").unwrap() as u32 + 1),
+                })
+                .unwrap()
+                .unwrap();
+        assert!(
+            hover.contents.value.ends_with(
+                "Declares the targets:\n- `:foo_lib`\n- `:foo_test`\n- `:foo_bin`\n- `:foo_files`\n"
+            ),
+            "{}",
+            hover.contents.value
+        );
+    }
+}
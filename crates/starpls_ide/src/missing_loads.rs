@@ -0,0 +1,261 @@
+//! Quick fixes that add a `load` statement for a name that isn't defined, e.g.
+//! `load("@bazel_skylib//lib:paths.bzl", "paths")` for an undefined `paths`.
+//!
+//! Most of the files that could export a name aren't in the database, so the caller looks up the
+//! modules exporting each undefined name, usually in an index of the workspace's `.bzl` files. A
+//! few well-known symbols from common rules repositories are offered regardless. New `load`
+//! statements are inserted in sorted position among the existing ones, and names loaded from a
+//! module that's already loaded are added to its statement.
+
+use starpls_common::{parse, File};
+use starpls_hir::{Name, Semantics};
+use starpls_syntax::{
+    ast::{self, AstNode},
+    edit::range_with_comments,
+    SyntaxNode, TextRange,
+};
+
+use crate::{
+    organize_loads::label_key,
+    util::{insert_load, load_stmts},
+    CodeAction, CodeActionKind, Database, TextEdit,
+};
+
+/// Symbols exported by common rules repositories, along with the modules that export them.
+const WELL_KNOWN_SYMBOLS: &[(&str, &str)] = &[
+    ("analysistest", "@bazel_skylib//lib:unittest.bzl"),
+    ("asserts", "@bazel_skylib//lib:unittest.bzl"),
+    ("bool_flag", "@bazel_skylib//rules:common_settings.bzl"),
+    ("copy_file", "@bazel_skylib//rules:copy_file.bzl"),
+    ("dicts", "@bazel_skylib//lib:dicts.bzl"),
+    (
+        "git_repository",
+        "@bazel_tools//tools/build_defs/repo:git.bzl",
+    ),
+    ("go_binary", "@io_bazel_rules_go//go:def.bzl"),
+    ("go_library", "@io_bazel_rules_go//go:def.bzl"),
+    ("go_test", "@io_bazel_rules_go//go:def.bzl"),
+    (
+        "http_archive",
+        "@bazel_tools//tools/build_defs/repo:http.bzl",
+    ),
+    ("http_file", "@bazel_tools//tools/build_defs/repo:http.bzl"),
+    ("int_flag", "@bazel_skylib//rules:common_settings.bzl"),
+    ("paths", "@bazel_skylib//lib:paths.bzl"),
+    ("pkg_tar", "@rules_pkg//pkg:tar.bzl"),
+    ("pkg_zip", "@rules_pkg//pkg:zip.bzl"),
+    ("py_binary", "@rules_python//python:py_binary.bzl"),
+    ("py_library", "@rules_python//python:py_library.bzl"),
+    ("py_test", "@rules_python//python:py_test.bzl"),
+    ("selects", "@bazel_skylib//lib:selects.bzl"),
+    ("sh_binary", "@rules_shell//shell:sh_binary.bzl"),
+    ("sh_library", "@rules_shell//shell:sh_library.bzl"),
+    ("sh_test", "@rules_shell//shell:sh_test.bzl"),
+    ("string_flag", "@bazel_skylib//rules:common_settings.bzl"),
+    (
+        "string_list_flag",
+        "@bazel_skylib//rules:common_settings.bzl",
+    ),
+    ("unittest", "@bazel_skylib//lib:unittest.bzl"),
+    ("write_file", "@bazel_skylib//rules:write_file.bzl"),
+];
+
+/// A public symbol defined by a module, e.g. `my_rule` in `//tools:defs.bzl`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExportedSymbol {
+    pub name: String,
+    /// The label of the module, as it would be written in a `load` statement.
+    pub module: String,
+}
+
+/// Returns the names in the given range that aren't defined, in the order they first appear.
+pub(crate) fn unresolved_names(db: &Database, file: File, range: TextRange) -> Vec<String> {
+    let sema = Semantics::new(db);
+    let mut names: Vec<String> = Vec::new();
+    for name_ref in parse(db, file)
+        .syntax(db)
+        .descendants()
+        .filter(|node| node.text_range().intersect(range).is_some())
+        .filter_map(ast::NameRef::cast)
+    {
+        let expr = match ast::Expression::cast(name_ref.syntax().clone()) {
+            Some(expr) => expr,
+            None => continue,
+        };
+        let name = Name::from_ast_node(name_ref.clone());
+        let is_resolved = sema
+            .scope_for_expr(file, &expr)
+            .and_then(|scope| scope.resolve_name(&name))
+            .map_or(false, |defs| !defs.is_empty());
+        if !is_resolved && !names.iter().any(|existing| existing == name.as_str()) {
+            names.push(name.as_str().to_string());
+        }
+    }
+    names
+}
+
+pub(crate) fn add_load_actions(
+    db: &Database,
+    file: File,
+    range: TextRange,
+    exports: &[ExportedSymbol],
+) -> Vec<CodeAction> {
+    let root = parse(db, file).syntax(db);
+    let mut actions = Vec::new();
+    for name in unresolved_names(db, file, range) {
+        let mut modules = exports
+            .iter()
+            .filter(|export| export.name == name)
+            .map(|export| export.module.as_str())
+            .collect::<Vec<_>>();
+        for (_, module) in WELL_KNOWN_SYMBOLS
+            .iter()
+            .filter(|(well_known, _)| *well_known == name)
+        {
+            if !modules.contains(module) {
+                modules.push(*module);
+            }
+        }
+        modules.sort_by(|a, b| label_key(a).cmp(&label_key(b)));
+        modules.dedup();
+
+        let is_preferred = modules.len() == 1;
+        for module in modules {
+            actions.push(CodeAction {
+                title: format!("Load \"{}\" from \"{}\"", name, module),
+                kind: CodeActionKind::QuickFix,
+                edits: vec![add_load_edit(&root, module, &name)],
+                file_edits: Vec::new(),
+                command: None,
+                is_preferred,
+            });
+        }
+    }
+    actions
+}
+
+/// Loads a name from a module, by adding it to an existing `load` statement for the module, or by
+/// inserting a new statement before the first one whose label sorts after the module's.
+fn add_load_edit(root: &SyntaxNode, module: &str, name: &str) -> TextEdit {
+    let loads = load_stmts(root);
+    if let Some((load_stmt, _)) = loads.iter().find(|(_, loaded)| loaded == module) {
+        if let Some(last_item) = load_stmt.items().last() {
+            return TextEdit {
+                range: TextRange::empty(last_item.syntax().text_range().end()),
+                new_text: format!(", \"{}\"", name),
+            };
+        }
+    }
+    match loads
+        .iter()
+        .find(|(_, loaded)| label_key(loaded) > label_key(module))
+    {
+        Some((next, _)) => TextEdit {
+            range: TextRange::empty(range_with_comments(next.syntax()).start()),
+            new_text: format!("load(\"{}\", \"{}\")\n", module, name),
+        },
+        None => insert_load(Some(root), module, &[name]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use starpls_bazel::APIContext;
+    use starpls_common::{Dialect, FileRange};
+    use starpls_syntax::{TextRange, TextSize};
+
+    use super::ExportedSymbol;
+    use crate::{AnalysisSnapshot, CodeAction, TextEdit};
+
+    fn actions(contents: &str, needle: &str, exports: &[(&str, &str)]) -> Vec<CodeAction> {
+        let (snap, file_id) =
+            AnalysisSnapshot::from_single_file(contents, Dialect::Bazel, Some(APIContext::Bzl));
+        let start = contents.find(needle).unwrap() as u32;
+        let range = FileRange {
+            file_id,
+            range: TextRange::empty(TextSize::from(start)),
+        };
+        let exports = exports
+            .iter()
+            .map(|(name, module)| ExportedSymbol {
+                name: name.to_string(),
+                module: module.to_string(),
+            })
+            .collect();
+        snap.add_load_actions(range, exports).unwrap().unwrap()
+    }
+
+    fn apply(text: &str, edits: &[TextEdit]) -> String {
+        let mut text = text.to_string();
+        for edit in edits.iter().rev() {
+            text.replace_range(std::ops::Range::<usize>::from(edit.range), &edit.new_text);
+        }
+        text
+    }
+
+    #[test]
+    fn test_insert_sorted_load() {
+        let contents = r#"load("//a:a.bzl", "a")
+load("//c:c.bzl", "c")
+
+x = my_rule
+"#;
+        let actions = actions(contents, "my_rule", &[("my_rule", "//b:defs.bzl")]);
+        assert_eq!(actions.len(), 1);
+        assert_eq!(actions[0].title, "Load \"my_rule\" from \"//b:defs.bzl\"");
+        assert!(actions[0].is_preferred);
+        assert_eq!(
+            apply(contents, &actions[0].edits),
+            r#"load("//a:a.bzl", "a")
+load("//b:defs.bzl", "my_rule")
+load("//c:c.bzl", "c")
+
+x = my_rule
+"#
+        );
+    }
+
+    #[test]
+    fn test_add_to_existing_load() {
+        let contents = r#"load("//b:defs.bzl", "other")
+
+x = my_rule
+"#;
+        let actions = actions(contents, "my_rule", &[("my_rule", "//b:defs.bzl")]);
+        assert_eq!(
+            apply(contents, &actions[0].edits),
+            r#"load("//b:defs.bzl", "other", "my_rule")
+
+x = my_rule
+"#
+        );
+    }
+
+    #[test]
+    fn test_well_known_symbol() {
+        let contents = "\"\"\"Docstring.\"\"\"\n\nx = paths.join(\"a\", \"b\")\n";
+        let actions = actions(contents, "paths", &[("paths", "//lib:paths.bzl")]);
+        let titles = actions
+            .iter()
+            .map(|action| action.title.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            titles,
+            vec![
+                "Load \"paths\" from \"@bazel_skylib//lib:paths.bzl\"",
+                "Load \"paths\" from \"//lib:paths.bzl\"",
+            ]
+        );
+        assert!(!actions[0].is_preferred);
+        assert_eq!(
+            apply(contents, &actions[0].edits),
+            "\"\"\"Docstring.\"\"\"\n\nload(\"@bazel_skylib//lib:paths.bzl\", \"paths\")\n\nx = paths.join(\"a\", \"b\")\n"
+        );
+    }
+
+    #[test]
+    fn test_defined_name() {
+        let contents = "my_rule = 1\nx = my_rule\n";
+        assert!(actions(contents, "my_rule\n", &[("my_rule", "//b:defs.bzl")]).is_empty());
+    }
+}
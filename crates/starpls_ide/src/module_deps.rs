@@ -0,0 +1,556 @@
+//! Information about the `bazel_dep`s declared in a `MODULE.bazel` file, as resolved by the
+//! accompanying `MODULE.bazel.lock` file.
+
+use std::{cmp::Ordering, fmt::Write};
+
+use rustc_hash::FxHashMap;
+use starpls_bazel::{
+    bzlmod::{BazelDep as ModuleDep, ExtensionRepo, ModuleFile},
+    lockfile::{compare_versions, ResolvedModule},
+};
+use starpls_common::{
+    parse, Db as _, Diagnostic, DiagnosticCode, File, FileId, FileRange, Severity,
+};
+use starpls_syntax::{
+    ast::{self, AstNode},
+    parse_module, SyntaxKind, SyntaxToken, TextRange,
+};
+
+use crate::{CodeAction, CodeActionCommand, CodeActionKind, Database, Hover, Markup, TextEdit};
+
+const OVERRIDE_FUNCTIONS: &[&str] = &[
+    "archive_override",
+    "git_override",
+    "local_path_override",
+    "multiple_version_override",
+    "single_version_override",
+];
+
+/// A `bazel_dep` whose declared version is older than the latest version in the registry.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DependencyUpdate {
+    pub name: String,
+    pub current_version: String,
+    pub latest_version: String,
+    /// The edit that replaces the declared version with the latest version.
+    pub edit: TextEdit,
+}
+
+struct BazelDep {
+    name: String,
+    name_range: TextRange,
+    version: Option<(String, TextRange)>,
+    repo_name: Option<String>,
+}
+
+pub(crate) fn module_dep_diagnostics(db: &Database, file: File) -> Vec<Diagnostic> {
+    let lockfile = match db.module_lockfiles.get(&file.id(db)) {
+        Some(lockfile) => lockfile.clone(),
+        None => return Vec::new(),
+    };
+    let root = parse(db, file).syntax(db);
+    let overrides = overrides(&root);
+
+    bazel_deps(&root)
+        .into_iter()
+        .filter(|dep| !overrides.contains_key(&dep.name))
+        .filter_map(|dep| {
+            let (declared, range) = dep.version?;
+            let resolved = lockfile.module(&dep.name)?.version.as_ref()?;
+            if compare_versions(&declared, resolved) == Ordering::Equal {
+                return None;
+            }
+            Some(Diagnostic {
+                code: DiagnosticCode::ModuleVersionMismatch,
+                message: format!(
+                    "Module \"{}\" is declared at version {}, but version {} was resolved",
+                    dep.name, declared, resolved
+                ),
+                severity: Severity::Warning,
+                range: FileRange {
+                    file_id: file.id(db),
+                    range,
+                },
+            })
+        })
+        .collect()
+}
+
+/// Finds the `bazel_dep`s in the given file that can be updated to a newer version, according
+/// to the cached registry metadata. Overridden modules are skipped.
+pub(crate) fn dependency_updates(db: &Database, file_id: FileId) -> Option<Vec<DependencyUpdate>> {
+    let file = db.get_file(file_id)?;
+    Some(file_dependency_updates(db, file))
+}
+
+fn file_dependency_updates(db: &Database, file: File) -> Vec<DependencyUpdate> {
+    let root = parse(db, file).syntax(db);
+    let overrides = overrides(&root);
+
+    bazel_deps(&root)
+        .into_iter()
+        .filter(|dep| !overrides.contains_key(&dep.name))
+        .filter_map(|dep| {
+            let (current_version, range) = dep.version?;
+            let latest_version = db
+                .registry_metadata
+                .module(&dep.name)?
+                .latest_version()?
+                .to_string();
+            if compare_versions(&latest_version, &current_version) != Ordering::Greater {
+                return None;
+            }
+            let quote = root
+                .covering_element(range)
+                .to_string()
+                .chars()
+                .next()
+                .filter(|c| *c == '\'')
+                .unwrap_or('"');
+            Some(DependencyUpdate {
+                edit: TextEdit {
+                    range,
+                    new_text: format!("{}{}{}", quote, latest_version, quote),
+                },
+                name: dep.name,
+                current_version,
+                latest_version,
+            })
+        })
+        .collect()
+}
+
+/// Returns the code actions for updating the `bazel_dep`s whose versions intersect the given
+/// range, along with an action for updating every `bazel_dep` in the file.
+pub(crate) fn update_actions(db: &Database, file: File, range: TextRange) -> Vec<CodeAction> {
+    let updates = file_dependency_updates(db, file);
+    let mut actions = updates
+        .iter()
+        .filter(|update| update.edit.range.intersect(range).is_some())
+        .map(|update| CodeAction {
+            title: format!("Update to latest ({})", update.latest_version),
+            kind: CodeActionKind::QuickFix,
+            edits: vec![update.edit.clone()],
+            file_edits: Vec::new(),
+            command: None,
+            is_preferred: true,
+        })
+        .collect::<Vec<_>>();
+    if !actions.is_empty() && updates.len() > 1 {
+        actions.push(CodeAction {
+            title: "Update all dependencies to latest".to_string(),
+            kind: CodeActionKind::QuickFix,
+            edits: Vec::new(),
+            file_edits: Vec::new(),
+            command: Some(CodeActionCommand::UpdateAllDependencies),
+            is_preferred: false,
+        });
+    }
+    actions
+}
+
+/// Returns hover information for the `bazel_dep` whose name or version is at the given token.
+pub(crate) fn hover(db: &Database, file: File, token: &SyntaxToken) -> Option<Hover> {
+    if token.kind() != SyntaxKind::STRING {
+        return None;
+    }
+    let root = parse(db, file).syntax(db);
+    let dep = bazel_deps(&root).into_iter().find(|dep| {
+        dep.name_range.contains_range(token.text_range())
+            || dep
+                .version
+                .as_ref()
+                .map_or(false, |(_, range)| range.contains_range(token.text_range()))
+    })?;
+    let resolved = db
+        .module_lockfiles
+        .get(&file.id(db))
+        .and_then(|lockfile| lockfile.module(&dep.name).cloned());
+    let override_kind = overrides(&root).remove(&dep.name);
+
+    let mut text = format!("```python\n(module) {}\n```\n", dep.name);
+    if let Some((version, _)) = &dep.version {
+        writeln!(text, "Declared version: `{}`  ", version).ok()?;
+    }
+    match &resolved {
+        Some(ResolvedModule {
+            version: Some(version),
+            ..
+        }) => writeln!(text, "Resolved version: `{}`  ", version).ok()?,
+        Some(ResolvedModule { version: None, .. }) => {
+            writeln!(text, "Resolved version: none, the module is overridden  ").ok()?
+        }
+        None => {}
+    }
+    if let Some(override_kind) = override_kind {
+        writeln!(text, "Override: `{}`  ", override_kind).ok()?;
+    }
+    if let Some(registry) = resolved.and_then(|module| module.registry) {
+        writeln!(text, "Registry: {}  ", registry).ok()?;
+    }
+
+    Some(Hover {
+        contents: Markup {
+            value: text.trim_end().to_string(),
+        },
+        range: Some(token.text_range()),
+        definition: None,
+    })
+}
+
+fn bazel_deps(root: &starpls_syntax::SyntaxNode) -> Vec<BazelDep> {
+    top_level_calls(root, &["bazel_dep"])
+        .filter_map(|(_, mut args)| {
+            let (name, name_range) = args.remove("name")?;
+            Some(BazelDep {
+                name,
+                name_range,
+                version: args.remove("version"),
+                repo_name: args.remove("repo_name").map(|(repo_name, _)| repo_name),
+            })
+        })
+        .collect()
+}
+
+/// Returns the modules overridden in the file, along with the kind of override.
+fn overrides(root: &starpls_syntax::SyntaxNode) -> FxHashMap<String, String> {
+    top_level_calls(root, OVERRIDE_FUNCTIONS)
+        .filter_map(|(kind, mut args)| Some((args.remove("module_name")?.0, kind)))
+        .collect()
+}
+
+/// Reads the declarations that determine a module's repository mapping from the contents of its
+/// `MODULE.bazel` file. The repositories imported by `use_repo` are matched with their extensions
+/// by the name of the variable that the result of `use_extension` is assigned to.
+pub fn parse_module_file(contents: &str) -> ModuleFile {
+    let root = parse_module(contents, &mut |_| {}).syntax();
+    let mut module_file = ModuleFile::default();
+    if let Some((_, mut args)) = top_level_calls(&root, &["module"]).next() {
+        module_file.name = args.remove("name").map(|(name, _)| name);
+        module_file.repo_name = args.remove("repo_name").map(|(repo_name, _)| repo_name);
+    }
+    module_file.deps = bazel_deps(&root)
+        .into_iter()
+        .map(|dep| ModuleDep {
+            name: dep.name,
+            repo_name: dep.repo_name,
+        })
+        .collect();
+
+    let mut extensions = FxHashMap::default();
+    for node in root.children() {
+        if let Some(assign_stmt) = ast::AssignStmt::cast(node.clone()) {
+            let name = match assign_stmt.lhs() {
+                Some(ast::Expression::Name(name_ref)) => name_ref.name(),
+                _ => None,
+            };
+            let extension = match assign_stmt.rhs() {
+                Some(ast::Expression::Call(call_expr)) => use_extension(&call_expr),
+                _ => None,
+            };
+            if let (Some(name), Some(extension)) = (name, extension) {
+                extensions.insert(name.text().to_string(), extension);
+            }
+            continue;
+        }
+
+        let call_expr = match ast::CallExpr::cast(node) {
+            Some(call_expr) if callee_name(&call_expr).as_deref() == Some("use_repo") => call_expr,
+            _ => continue,
+        };
+        let mut args = call_expr
+            .arguments()
+            .into_iter()
+            .flat_map(|arguments| arguments.arguments());
+        let extension = match args.next() {
+            Some(ast::Argument::Simple(arg)) => match arg.expr() {
+                Some(ast::Expression::Name(name_ref)) => name_ref
+                    .name()
+                    .and_then(|name| extensions.get(name.text()).cloned()),
+                _ => None,
+            },
+            _ => None,
+        };
+        let (extension_file, extension_name) = match extension {
+            Some(extension) => extension,
+            None => continue,
+        };
+        for arg in args {
+            let (apparent_name, name) = match arg {
+                ast::Argument::Simple(arg) => match arg.expr().as_ref().and_then(string_value) {
+                    Some(name) => (name.clone(), name),
+                    None => continue,
+                },
+                ast::Argument::Keyword(arg) => {
+                    match (
+                        arg.name().and_then(|name| name.name()),
+                        arg.expr().as_ref().and_then(string_value),
+                    ) {
+                        (Some(apparent_name), Some(name)) => {
+                            (apparent_name.text().to_string(), name)
+                        }
+                        _ => continue,
+                    }
+                }
+                _ => continue,
+            };
+            module_file.extension_repos.push(ExtensionRepo {
+                extension_file: extension_file.clone(),
+                extension_name: extension_name.clone(),
+                apparent_name,
+                name,
+            });
+        }
+    }
+    module_file
+}
+
+/// Returns the `.bzl` file and the name of the extension used by a `use_extension` call.
+fn use_extension(call_expr: &ast::CallExpr) -> Option<(String, String)> {
+    if callee_name(call_expr)?.as_str() != "use_extension" {
+        return None;
+    }
+    let mut extension_file = None;
+    let mut extension_name = None;
+    let mut positional = 0;
+    for arg in call_expr.arguments()?.arguments() {
+        match arg {
+            ast::Argument::Simple(arg) => {
+                let value = arg.expr().as_ref().and_then(string_value);
+                match positional {
+                    0 => extension_file = value,
+                    1 => extension_name = value,
+                    _ => {}
+                }
+                positional += 1;
+            }
+            ast::Argument::Keyword(arg) => {
+                let value = arg.expr().as_ref().and_then(string_value);
+                match arg.name().and_then(|name| name.name()) {
+                    Some(name) if name.text() == "extension_bzl_file" => extension_file = value,
+                    Some(name) if name.text() == "extension_name" => extension_name = value,
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+    Some((extension_file?, extension_name?))
+}
+
+pub(crate) fn callee_name(call_expr: &ast::CallExpr) -> Option<String> {
+    match call_expr.callee()? {
+        ast::Expression::Name(name_ref) => Some(name_ref.name()?.text().to_string()),
+        _ => None,
+    }
+}
+
+fn string_value(expr: &ast::Expression) -> Option<String> {
+    match expr {
+        ast::Expression::Literal(lit) => match lit.kind() {
+            ast::LiteralKind::String(s) => Some(s.value()?.to_string()),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Finds top-level calls to the given functions, returning the name of the called function along
+/// with the string literal keyword arguments of each call.
+fn top_level_calls<'a>(
+    root: &'a starpls_syntax::SyntaxNode,
+    functions: &'a [&'a str],
+) -> impl Iterator<Item = (String, FxHashMap<String, (String, TextRange)>)> + 'a {
+    root.children()
+        .filter_map(ast::CallExpr::cast)
+        .filter_map(move |call_expr| {
+            let callee = callee_name(&call_expr)?;
+            if !functions.contains(&callee.as_str()) {
+                return None;
+            }
+            let args = call_expr
+                .arguments()?
+                .arguments()
+                .filter_map(|arg| match arg {
+                    ast::Argument::Keyword(arg) => {
+                        let name = arg.name()?.name()?.text().to_string();
+                        let expr = arg.expr()?;
+                        Some((name, (string_value(&expr)?, expr.syntax().text_range())))
+                    }
+                    _ => None,
+                })
+                .collect();
+            Some((callee, args))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use starpls_bazel::{
+        bzlmod::{BazelDep, ExtensionRepo, ModuleFile},
+        lockfile::ModuleLockfile,
+        registry::{ModuleMetadata, RegistryMetadata},
+    };
+    use starpls_common::{Dialect, FileId};
+    use starpls_syntax::TextSize;
+
+    use crate::{parse_module_file, Analysis, FilePosition};
+
+    const MODULE_BAZEL: &str = r#"
+bazel_dep(name = "rules_go", version = "0.41.0")
+bazel_dep(name = "rules_cc", version = "0.0.9")
+bazel_dep(name = "gazelle", version = "0.30.0")
+
+single_version_override(module_name = "gazelle", version = "0.35.0")
+"#;
+
+    const LOCKFILE: &str = r#"{
+        "registryFileHashes": {
+            "https://bcr.bazel.build/modules/rules_go/0.41.0/MODULE.bazel": "abc",
+            "https://bcr.bazel.build/modules/rules_go/0.46.0/MODULE.bazel": "abc",
+            "https://bcr.bazel.build/modules/rules_cc/0.0.9/MODULE.bazel": "abc",
+            "https://bcr.bazel.build/modules/gazelle/0.35.0/MODULE.bazel": "abc"
+        }
+    }"#;
+
+    fn analysis() -> Analysis {
+        let mut analysis = Analysis::from_files(&[("MODULE.bazel", MODULE_BAZEL)], Dialect::Bazel);
+        analysis.set_module_lockfile(FileId(0), Some(ModuleLockfile::parse(LOCKFILE).unwrap()));
+
+        let mut registry_metadata = RegistryMetadata::default();
+        for (name, versions) in [
+            ("rules_go", &["0.41.0", "0.46.0", "0.48.0"][..]),
+            ("rules_cc", &["0.0.9"][..]),
+            ("gazelle", &["0.30.0", "0.36.0"][..]),
+        ] {
+            registry_metadata.insert(
+                name.to_string(),
+                ModuleMetadata {
+                    versions: versions.iter().map(|version| version.to_string()).collect(),
+                    ..Default::default()
+                },
+            );
+        }
+        analysis.set_registry_metadata(registry_metadata);
+        analysis
+    }
+
+    #[test]
+    fn test_dependency_updates() {
+        let updates = analysis()
+            .snapshot()
+            .dependency_updates(FileId(0))
+            .unwrap()
+            .unwrap();
+        assert_eq!(updates.len(), 1);
+        assert_eq!(updates[0].name, "rules_go");
+        assert_eq!(updates[0].current_version, "0.41.0");
+        assert_eq!(updates[0].latest_version, "0.48.0");
+        assert_eq!(&MODULE_BAZEL[updates[0].edit.range], "\"0.41.0\"");
+        assert_eq!(updates[0].edit.new_text, "\"0.48.0\"");
+    }
+
+    #[test]
+    fn test_version_mismatch_diagnostics() {
+        let messages = analysis()
+            .snapshot()
+            .diagnostics(FileId(0))
+            .unwrap()
+            .into_iter()
+            .map(|diagnostic| diagnostic.message)
+            .filter(|message| message.starts_with("Module "))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            messages,
+            vec![
+                "Module \"rules_go\" is declared at version 0.41.0, but version 0.46.0 was resolved"
+                    .to_string()
+            ]
+        );
+    }
+
+    #[test]
+    fn test_hover() {
+        let snap = analysis().snapshot();
+        let hover = |needle: &str| {
+            let pos = TextSize::from(MODULE_BAZEL.find(needle).unwrap() as u32 + 1);
+            snap.hover(FilePosition {
+                file_id: FileId(0),
+                pos,
+            })
+            .unwrap()
+            .unwrap()
+            .contents
+            .value
+        };
+
+        assert_eq!(
+            hover("\"rules_go\""),
+            "```python\n(module) rules_go\n```\nDeclared version: `0.41.0`  \nResolved version: `0.46.0`  \nRegistry: https://bcr.bazel.build"
+        );
+        assert_eq!(
+            hover("\"0.30.0\""),
+            "```python\n(module) gazelle\n```\nDeclared version: `0.30.0`  \nResolved version: `0.35.0`  \nOverride: `single_version_override`  \nRegistry: https://bcr.bazel.build"
+        );
+    }
+
+    #[test]
+    fn test_parse_module_file() {
+        let module_file = parse_module_file(
+            r#"
+module(name = "my_project", version = "1.0")
+
+bazel_dep(name = "rules_go", version = "0.46.0", repo_name = "io_bazel_rules_go")
+bazel_dep(name = "platforms", version = "0.0.8")
+
+go_sdk = use_extension("@io_bazel_rules_go//go:extensions.bzl", "go_sdk")
+use_repo(go_sdk, "go_toolchains", sdk = "go_default_sdk")
+
+tools = use_extension(extension_bzl_file = "//:extensions.bzl", extension_name = "tools")
+use_repo(tools, "tools")
+use_repo(unknown, "ignored")
+"#,
+        );
+        let extension_repo =
+            |extension_file: &str, extension_name: &str, apparent_name: &str, name: &str| {
+                ExtensionRepo {
+                    extension_file: extension_file.to_string(),
+                    extension_name: extension_name.to_string(),
+                    apparent_name: apparent_name.to_string(),
+                    name: name.to_string(),
+                }
+            };
+        assert_eq!(
+            module_file,
+            ModuleFile {
+                name: Some("my_project".to_string()),
+                repo_name: None,
+                deps: vec![
+                    BazelDep {
+                        name: "rules_go".to_string(),
+                        repo_name: Some("io_bazel_rules_go".to_string()),
+                    },
+                    BazelDep {
+                        name: "platforms".to_string(),
+                        repo_name: None,
+                    },
+                ],
+                extension_repos: vec![
+                    extension_repo(
+                        "@io_bazel_rules_go//go:extensions.bzl",
+                        "go_sdk",
+                        "go_toolchains",
+                        "go_toolchains"
+                    ),
+                    extension_repo(
+                        "@io_bazel_rules_go//go:extensions.bzl",
+                        "go_sdk",
+                        "sdk",
+                        "go_default_sdk"
+                    ),
+                    extension_repo("//:extensions.bzl", "tools", "tools", "tools"),
+                ],
+            }
+        );
+    }
+}
@@ -0,0 +1,97 @@
+use starpls_common::{Db as _, FileId};
+use starpls_hir::{DisplayWithDb, ScopeDef, Semantics};
+use starpls_syntax::TextRange;
+
+use crate::Database;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ModuleSymbolKind {
+    Function,
+    Provider,
+    Rule,
+    Variable,
+}
+
+/// A top-level definition in a module, along with its inferred type and documentation.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ModuleSymbol {
+    pub name: String,
+    pub kind: ModuleSymbolKind,
+    /// The rendered type of the symbol. For functions, this is the function's signature.
+    pub ty: String,
+    pub doc: Option<String>,
+    pub range: TextRange,
+}
+
+pub(crate) fn module_symbols(db: &Database, file_id: FileId) -> Option<Vec<ModuleSymbol>> {
+    let sema = Semantics::new(db);
+    let file = db.get_file(file_id)?;
+    let mut symbols = sema
+        .scope_for_module(file)
+        .names()
+        .filter_map(|(name, def)| {
+            let kind = match def {
+                ScopeDef::Callable(_) => ModuleSymbolKind::Function,
+                ScopeDef::Variable(_) => ModuleSymbolKind::Variable,
+                _ => return None,
+            };
+            let range = def.syntax_node_ptr(db, file)?.text_range();
+            let ty = def.ty(db);
+            let kind = if ty.is_rule() {
+                ModuleSymbolKind::Rule
+            } else if ty.is_provider() {
+                ModuleSymbolKind::Provider
+            } else {
+                kind
+            };
+            Some(ModuleSymbol {
+                name: name.as_str().to_string(),
+                kind,
+                ty: ty.display(db).to_string(),
+                doc: ty.doc(db).map(|doc| unindent::unindent(&doc)),
+                range,
+            })
+        })
+        .collect::<Vec<_>>();
+    symbols.sort_by_key(|symbol| symbol.range.start());
+    Some(symbols)
+}
+
+#[cfg(test)]
+mod tests {
+    use starpls_bazel::APIContext;
+    use starpls_common::Dialect;
+
+    use super::ModuleSymbolKind;
+    use crate::AnalysisSnapshot;
+
+    #[test]
+    fn test_functions_and_variables() {
+        let (snap, file_id) = AnalysisSnapshot::from_single_file(
+            r#"
+def foo(x, y = 1):
+    """Does foo."""
+    pass
+
+BAR = "bar"
+
+Info = provider()
+"#,
+            Dialect::Bazel,
+            Some(APIContext::Bzl),
+        );
+        let symbols = snap.module_symbols(file_id).unwrap().unwrap();
+        assert_eq!(
+            symbols
+                .iter()
+                .map(|symbol| (symbol.name.as_str(), symbol.kind.clone()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("foo", ModuleSymbolKind::Function),
+                ("BAR", ModuleSymbolKind::Variable),
+                ("Info", ModuleSymbolKind::Provider),
+            ]
+        );
+        assert_eq!(symbols[0].doc.as_deref(), Some("Does foo."));
+    }
+}
@@ -0,0 +1,330 @@
+//! Checks that native rules and functions are accessed through the `native` module in `.bzl`
+//! files, and directly in `BUILD` files.
+//!
+//! Only calls are checked, and only when the called name resolves to the builtin, so that
+//! locally defined or loaded functions with the same name as a native rule are left alone.
+
+use rustc_hash::FxHashSet;
+use starpls_bazel::APIContext;
+use starpls_common::{parse, Db as _, Diagnostic, DiagnosticCode, File, FileRange, Severity};
+use starpls_hir::{Db as _, Name, Semantics};
+use starpls_syntax::{
+    ast::{self, AstNode},
+    TextRange,
+};
+
+use crate::{CodeAction, CodeActionKind, Database, TextEdit};
+
+const NATIVE: &str = "native";
+
+/// A call whose callee is missing the `native.` prefix, or has one where it isn't allowed.
+struct NativePrefixFix {
+    code: DiagnosticCode,
+    name: String,
+    /// The range to report the diagnostic at, i.e. the name of the callee in `.bzl` files and
+    /// the `native.` prefix in `BUILD` files.
+    range: TextRange,
+    edit: TextEdit,
+}
+
+pub(crate) fn native_prefix_diagnostics(db: &Database, file: File) -> Vec<Diagnostic> {
+    fixes(db, file)
+        .into_iter()
+        .map(|fix| Diagnostic {
+            message: match fix.code {
+                DiagnosticCode::MissingNativePrefix => format!(
+                    "\"{}\" must be accessed as \"native.{}\" in .bzl files",
+                    fix.name, fix.name
+                ),
+                _ => format!(
+                    "\"native\" is not available in BUILD files, call \"{}\" directly",
+                    fix.name
+                ),
+            },
+            code: fix.code,
+            severity: Severity::Warning,
+            range: FileRange {
+                file_id: file.id(db),
+                range: fix.range,
+            },
+        })
+        .collect()
+}
+
+pub(crate) fn native_prefix_actions(
+    db: &Database,
+    file: File,
+    range: TextRange,
+) -> Vec<CodeAction> {
+    let fixes = fixes(db, file);
+    let mut actions = Vec::new();
+    for fix in fixes
+        .iter()
+        .filter(|fix| fix.range.intersect(range).is_some())
+    {
+        actions.push(CodeAction {
+            title: match fix.code {
+                DiagnosticCode::MissingNativePrefix => "Qualify with `native.`",
+                _ => "Remove `native.` prefix",
+            }
+            .to_string(),
+            kind: CodeActionKind::QuickFix,
+            edits: vec![fix.edit.clone()],
+            file_edits: Vec::new(),
+            command: None,
+            is_preferred: true,
+        });
+    }
+
+    // Offer to fix every occurrence at once, but only if there's more than the one already offered.
+    if !actions.is_empty() && fixes.len() > 1 {
+        actions.push(CodeAction {
+            title: "Fix all `native.` prefixes in file".to_string(),
+            kind: CodeActionKind::QuickFix,
+            edits: fixes.into_iter().map(|fix| fix.edit).collect(),
+            file_edits: Vec::new(),
+            command: None,
+            is_preferred: false,
+        });
+    }
+
+    actions
+}
+
+/// Returns the edits that fix every missing or unnecessary `native.` prefix in the file.
+pub(crate) fn native_prefix_edits(db: &Database, file: File) -> Vec<TextEdit> {
+    fixes(db, file).into_iter().map(|fix| fix.edit).collect()
+}
+
+fn fixes(db: &Database, file: File) -> Vec<NativePrefixFix> {
+    let is_bzl = match file.api_context(db) {
+        Some(APIContext::Bzl) => true,
+        Some(APIContext::Build) => false,
+        _ => return Vec::new(),
+    };
+
+    let members = native_members(db, file);
+    if members.is_empty() {
+        return Vec::new();
+    }
+
+    let sema = Semantics::new(db);
+    let resolves_to_builtin = |expr: &ast::Expression, name: &str| {
+        sema.scope_for_expr(file, expr)
+            .and_then(|scope| scope.resolve_name(&Name::from(name)))
+            .and_then(|defs| defs.into_iter().last())
+            .map(|def| !def.is_user_defined())
+    };
+
+    parse(db, file)
+        .syntax(db)
+        .descendants()
+        .filter_map(ast::CallExpr::cast)
+        .filter_map(|call| match call.callee()? {
+            ast::Expression::Name(name_ref) if is_bzl => {
+                let token = name_ref.name()?;
+                let name = token.text();
+                if !members.contains(name)
+                    || resolves_to_builtin(&ast::Expression::Name(name_ref.clone()), name)
+                        != Some(true)
+                {
+                    return None;
+                }
+                let range = token.text_range();
+                Some(NativePrefixFix {
+                    code: DiagnosticCode::MissingNativePrefix,
+                    name: name.to_string(),
+                    range,
+                    edit: TextEdit {
+                        range: TextRange::empty(range.start()),
+                        new_text: format!("{}.", NATIVE),
+                    },
+                })
+            }
+            ast::Expression::Dot(dot_expr) if !is_bzl => {
+                let base = match dot_expr.expr()? {
+                    ast::Expression::Name(base) => base,
+                    _ => return None,
+                };
+                if base.name()?.text() != NATIVE
+                    || resolves_to_builtin(&ast::Expression::Name(base), NATIVE) == Some(false)
+                {
+                    return None;
+                }
+
+                // The prefix can only be removed if the member is also available directly.
+                let field = dot_expr.field()?.name()?;
+                let name = field.text();
+                if !members.contains(name)
+                    || resolves_to_builtin(&ast::Expression::Dot(dot_expr.clone()), name)
+                        != Some(true)
+                {
+                    return None;
+                }
+                let range = TextRange::new(
+                    dot_expr.syntax().text_range().start(),
+                    field.text_range().start(),
+                );
+                Some(NativePrefixFix {
+                    code: DiagnosticCode::UnnecessaryNativePrefix,
+                    name: name.to_string(),
+                    range,
+                    edit: TextEdit {
+                        range,
+                        new_text: String::new(),
+                    },
+                })
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// Returns the names of the members of the `native` module, i.e. the native rules along with
+/// functions like `glob` and `package_name`.
+fn native_members(db: &Database, file: File) -> FxHashSet<String> {
+    let defs = db.get_builtin_defs(&file.dialect(db));
+    let rules = defs.rules(db).global.iter();
+    let functions = defs
+        .builtins(db)
+        .r#type
+        .iter()
+        .filter(|type_| type_.name == NATIVE)
+        .flat_map(|type_| type_.field.iter());
+    rules
+        .chain(functions)
+        .filter(|value| value.callable.is_some())
+        .map(|value| value.name.clone())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use starpls_common::{Db as _, DiagnosticCode, Dialect, FileId, FileRange};
+    use starpls_hir::Db as _;
+    use starpls_syntax::{TextRange, TextSize};
+    use starpls_test_util::make_test_builtins;
+
+    use super::native_prefix_diagnostics;
+    use crate::{Analysis, CodeActionKind, TextEdit};
+
+    fn apply(text: &str, edits: &[TextEdit]) -> String {
+        let mut edits = edits.to_vec();
+        edits.sort_by_key(|edit| std::cmp::Reverse(edit.range.start()));
+        let mut text = text.to_string();
+        for edit in edits {
+            text.replace_range(std::ops::Range::<usize>::from(edit.range), &edit.new_text);
+        }
+        text
+    }
+
+    fn check(path: &str, contents: &str, expected_codes: &[DiagnosticCode]) -> String {
+        let file_id = FileId(0);
+        let mut analysis = Analysis::from_files(&[(path, contents)], Dialect::Bazel);
+        analysis.db.set_builtin_defs(
+            Dialect::Bazel,
+            make_test_builtins(
+                vec![],
+                vec![("native".to_string(), "native".to_string())],
+                vec![],
+            ),
+            make_test_builtins(
+                vec!["cc_library".to_string(), "cc_binary".to_string()],
+                vec![],
+                vec![],
+            ),
+        );
+        let snap = analysis.snapshot();
+
+        let file = snap.db.get_file(file_id).unwrap();
+        assert_eq!(
+            native_prefix_diagnostics(&snap.db, file)
+                .into_iter()
+                .map(|diagnostic| diagnostic.code)
+                .collect::<Vec<_>>(),
+            expected_codes
+        );
+
+        let actions = snap
+            .code_actions(FileRange {
+                file_id,
+                range: TextRange::new(TextSize::from(0), TextSize::of(contents)),
+            })
+            .unwrap()
+            .unwrap();
+        let fix_all = actions
+            .iter()
+            .find(|action| action.title == "Fix all `native.` prefixes in file")
+            .unwrap();
+
+        // The same fixes are also part of the file's `source.fixAll` action.
+        let source_fix_all = snap.fix_all_action(file_id).unwrap().unwrap();
+        assert_eq!(source_fix_all.kind, CodeActionKind::SourceFixAll);
+        assert_eq!(source_fix_all.edits, fix_all.edits);
+        apply(contents, &fix_all.edits)
+    }
+
+    #[test]
+    fn test_missing_native_prefix() {
+        let fixed = check(
+            "defs.bzl",
+            r#"
+def cc_binary(**kwargs):
+    pass
+
+def my_macro(name):
+    cc_library(name = name)
+    native.cc_library(name = name + "_native")
+    cc_binary(name = name + "_bin")
+    cc_library(name = name + "_other")
+"#,
+            &[
+                DiagnosticCode::MissingNativePrefix,
+                DiagnosticCode::MissingNativePrefix,
+            ],
+        );
+        assert_eq!(
+            fixed,
+            r#"
+def cc_binary(**kwargs):
+    pass
+
+def my_macro(name):
+    native.cc_library(name = name)
+    native.cc_library(name = name + "_native")
+    cc_binary(name = name + "_bin")
+    native.cc_library(name = name + "_other")
+"#
+        );
+    }
+
+    #[test]
+    fn test_unnecessary_native_prefix() {
+        let fixed = check(
+            "BUILD",
+            r#"
+native.cc_library(name = "foo")
+
+native.cc_binary(
+    name = "bar",
+    deps = [":foo"],
+)
+"#,
+            &[
+                DiagnosticCode::UnnecessaryNativePrefix,
+                DiagnosticCode::UnnecessaryNativePrefix,
+            ],
+        );
+        assert_eq!(
+            fixed,
+            r#"
+cc_library(name = "foo")
+
+cc_binary(
+    name = "bar",
+    deps = [":foo"],
+)
+"#
+        );
+    }
+}
@@ -0,0 +1,389 @@
+//! Sorts and merges `load` statements, following Buildifier's conventions: statements are sorted
+//! by the label of the loaded file, with labels in explicit repositories first, symbols are sorted
+//! by the name that they're bound to, and statements that load the same file are merged.
+//!
+//! Statements separated by anything other than whitespace, e.g. by another statement or by a
+//! comment that isn't attached to either of them, are organized separately. Comments attached to
+//! a statement or to one of its symbols move along with it. Files with comments in other places
+//! inside of `load` statements are left alone, since there's no way to keep them in place.
+
+use rustc_hash::FxHashSet;
+use starpls_common::{parse, File};
+use starpls_syntax::{
+    ast::{self, AstNode},
+    edit::{attached_comments, range_with_comments},
+    SyntaxKind, SyntaxNode, SyntaxToken, TextRange,
+};
+
+use crate::{
+    util::{loaded_name, local_name},
+    CodeAction, CodeActionKind, Database, TextEdit,
+};
+
+const INDENT: &str = "    ";
+
+/// A symbol loaded by a `load` statement, along with its attached comments.
+struct Item {
+    local_name: String,
+    loaded_name: String,
+    text: String,
+    leading: Vec<String>,
+    trailing: Option<String>,
+}
+
+/// One or more `load` statements for the same file, merged together.
+struct Load {
+    module: String,
+    module_text: String,
+    leading: Vec<String>,
+    trailing: Vec<String>,
+    items: Vec<Item>,
+    is_multiline: bool,
+}
+
+impl Load {
+    /// Sorts the items by the name they're bound to, removing ones that are repeated verbatim.
+    fn sort_items(&mut self) {
+        self.items.sort_by(|a, b| {
+            a.local_name
+                .cmp(&b.local_name)
+                .then_with(|| a.loaded_name.cmp(&b.loaded_name))
+        });
+        let mut items: Vec<Item> = Vec::with_capacity(self.items.len());
+        for item in self.items.drain(..) {
+            match items.last_mut() {
+                Some(last)
+                    if last.local_name == item.local_name
+                        && last.loaded_name == item.loaded_name =>
+                {
+                    last.leading.extend(item.leading);
+                    if last.trailing.is_none() {
+                        last.trailing = item.trailing;
+                    }
+                }
+                _ => items.push(item),
+            }
+        }
+        self.items = items;
+    }
+
+    fn render(&self) -> String {
+        let mut leading = self.leading.clone();
+        let trailing = match self.trailing.split_last() {
+            Some((last, rest)) => {
+                leading.extend(rest.iter().cloned());
+                format!("  {}", last)
+            }
+            None => String::new(),
+        };
+
+        let mut text = String::new();
+        for comment in &leading {
+            text.push_str(comment);
+            text.push('\n');
+        }
+        let has_item_comments = self
+            .items
+            .iter()
+            .any(|item| !item.leading.is_empty() || item.trailing.is_some());
+        if !self.is_multiline && !has_item_comments {
+            text.push_str("load(");
+            text.push_str(&self.module_text);
+            for item in &self.items {
+                text.push_str(", ");
+                text.push_str(&item.text);
+            }
+            text.push(')');
+        } else {
+            text.push_str("load(\n");
+            text.push_str(&format!("{}{},\n", INDENT, self.module_text));
+            for item in &self.items {
+                for comment in &item.leading {
+                    text.push_str(&format!("{}{}\n", INDENT, comment));
+                }
+                text.push_str(&format!("{}{},", INDENT, item.text));
+                if let Some(comment) = &item.trailing {
+                    text.push_str("  ");
+                    text.push_str(comment);
+                }
+                text.push('\n');
+            }
+            text.push(')');
+        }
+        text.push_str(&trailing);
+        text
+    }
+}
+
+/// Returns the edits that organize every group of `load` statements in the file.
+pub(crate) fn organize_loads_edits(db: &Database, file: File) -> Vec<TextEdit> {
+    let root = parse(db, file).syntax(db);
+    let text = root.text().to_string();
+    let mut edits = Vec::new();
+    for block in load_blocks(&root, &text) {
+        let (first, last) = match (block.first(), block.last()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => continue,
+        };
+        let range = TextRange::new(
+            range_with_comments(first.syntax()).start(),
+            range_with_comments(last.syntax()).end(),
+        );
+        let new_text = match organize_block(&block) {
+            Some(new_text) => new_text,
+            None => return Vec::new(),
+        };
+        if new_text != text[range] {
+            edits.push(TextEdit { range, new_text });
+        }
+    }
+    edits
+}
+
+pub(crate) fn organize_loads_actions(
+    db: &Database,
+    file: File,
+    range: TextRange,
+) -> Vec<CodeAction> {
+    let edits = organize_loads_edits(db, file);
+    if edits.is_empty() {
+        return Vec::new();
+    }
+
+    let mut actions = Vec::new();
+    let in_loads = parse(db, file)
+        .syntax(db)
+        .children()
+        .filter(|node| node.kind() == SyntaxKind::LOAD_STMT)
+        .any(|node| node.text_range().intersect(range).is_some());
+    if in_loads {
+        actions.push(CodeAction {
+            title: "Sort and merge `load` statements".to_string(),
+            kind: CodeActionKind::RefactorRewrite,
+            edits: edits.clone(),
+            file_edits: Vec::new(),
+            command: None,
+            is_preferred: false,
+        });
+    }
+    actions.push(CodeAction {
+        title: "Organize `load` statements".to_string(),
+        kind: CodeActionKind::SourceOrganizeImports,
+        edits,
+        file_edits: Vec::new(),
+        command: None,
+        is_preferred: false,
+    });
+    actions
+}
+
+/// Groups the top-level `load` statements that are only separated by whitespace.
+fn load_blocks(root: &SyntaxNode, text: &str) -> Vec<Vec<ast::LoadStmt>> {
+    let mut blocks: Vec<Vec<ast::LoadStmt>> = Vec::new();
+    for load_stmt in root.children().filter_map(ast::LoadStmt::cast) {
+        if let Some(block) = blocks.last_mut() {
+            let prev_end = block
+                .last()
+                .map(|prev| range_with_comments(prev.syntax()).end());
+            let start = range_with_comments(load_stmt.syntax()).start();
+            if let Some(prev_end) = prev_end.filter(|prev_end| *prev_end <= start) {
+                if text[TextRange::new(prev_end, start)].trim().is_empty() {
+                    block.push(load_stmt);
+                    continue;
+                }
+            }
+        }
+        blocks.push(vec![load_stmt]);
+    }
+    blocks
+}
+
+/// Returns the organized text of a group of `load` statements, or `None` if the statements are
+/// malformed or have comments that can't be kept.
+fn organize_block(block: &[ast::LoadStmt]) -> Option<String> {
+    let mut loads: Vec<Load> = Vec::new();
+    for load_stmt in block {
+        let module_node = load_stmt.module()?;
+        let module = module_node
+            .name()
+            .and_then(ast::String::cast)?
+            .value()?
+            .to_string();
+        let (leading, trailing) = attached_comments(load_stmt.syntax());
+        let mut kept = leading
+            .iter()
+            .chain(trailing.iter())
+            .map(|comment| comment.text_range())
+            .collect::<FxHashSet<_>>();
+
+        let mut items = Vec::new();
+        for item in load_stmt.items() {
+            let (item_leading, item_trailing) = attached_comments(item.syntax());
+            kept.extend(
+                item_leading
+                    .iter()
+                    .chain(item_trailing.iter())
+                    .map(|comment| comment.text_range()),
+            );
+            items.push(Item {
+                local_name: local_name(&item)?,
+                loaded_name: loaded_name(&item)?,
+                text: item.syntax().text().to_string(),
+                leading: comment_texts(&item_leading),
+                trailing: item_trailing.map(|comment| comment.text().to_string()),
+            });
+        }
+
+        // Bail out instead of dropping comments that aren't attached to anything we move.
+        let has_other_comments = load_stmt
+            .syntax()
+            .descendants_with_tokens()
+            .filter_map(|el| el.into_token())
+            .any(|token| {
+                token.kind() == SyntaxKind::COMMENT && !kept.contains(&token.text_range())
+            });
+        if has_other_comments {
+            return None;
+        }
+
+        let is_multiline = load_stmt.syntax().text().contains_char('\n');
+        let leading = comment_texts(&leading);
+        let trailing = trailing.map(|comment| comment.text().to_string());
+        match loads.iter_mut().find(|load| load.module == module) {
+            Some(load) => {
+                load.leading.extend(leading);
+                load.trailing.extend(trailing);
+                load.items.extend(items);
+                load.is_multiline |= is_multiline;
+            }
+            None => loads.push(Load {
+                module,
+                module_text: module_node.syntax().text().to_string(),
+                leading,
+                trailing: trailing.into_iter().collect(),
+                items,
+                is_multiline,
+            }),
+        }
+    }
+
+    loads.sort_by(|a, b| label_key(&a.module).cmp(&label_key(&b.module)));
+    for load in &mut loads {
+        load.sort_items();
+    }
+    Some(
+        loads
+            .iter()
+            .map(|load| load.render())
+            .collect::<Vec<_>>()
+            .join("\n"),
+    )
+}
+
+fn comment_texts(comments: &[SyntaxToken]) -> Vec<String> {
+    comments
+        .iter()
+        .map(|comment| comment.text().to_string())
+        .collect()
+}
+
+/// The key that labels are sorted by, as in Buildifier: labels with an explicit repository come
+/// first, then labels are compared by package and then by file name.
+pub(crate) fn label_key(label: &str) -> (bool, &str, &str) {
+    let (package, name) = label.rsplit_once(':').unwrap_or((label, ""));
+    (!label.starts_with('@'), package, name)
+}
+
+#[cfg(test)]
+mod tests {
+    use starpls_common::{Dialect, FileRange};
+    use starpls_syntax::{TextRange, TextSize};
+
+    use crate::{AnalysisSnapshot, CodeActionKind, TextEdit};
+
+    fn apply(text: &str, edits: &[TextEdit]) -> String {
+        let mut edits = edits.to_vec();
+        edits.sort_by_key(|edit| std::cmp::Reverse(edit.range.start()));
+        let mut text = text.to_string();
+        for edit in edits {
+            text.replace_range(std::ops::Range::<usize>::from(edit.range), &edit.new_text);
+        }
+        text
+    }
+
+    fn check(contents: &str, expected: Option<&str>) {
+        let (snap, file_id) = AnalysisSnapshot::from_single_file(contents, Dialect::Bazel, None);
+        let actions = snap
+            .code_actions(FileRange {
+                file_id,
+                range: TextRange::up_to(TextSize::of(contents)),
+            })
+            .unwrap()
+            .unwrap();
+        let action = actions
+            .iter()
+            .find(|action| action.kind == CodeActionKind::SourceOrganizeImports);
+        assert_eq!(
+            action
+                .map(|action| apply(contents, &action.edits))
+                .as_deref(),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_sort_and_merge() {
+        check(
+            r#"# Rules.
+load("//b:defs.bzl", "z", "a")
+load("@repo//:x.bzl", "x")
+load(":local.bzl", "l")
+
+load("//b:defs.bzl", "m", "a")  # More.
+
+x()
+load("//c:c.bzl", "c")
+"#,
+            Some(
+                r#"load("@repo//:x.bzl", "x")
+load(":local.bzl", "l")
+# Rules.
+load("//b:defs.bzl", "a", "m", "z")  # More.
+
+x()
+load("//c:c.bzl", "c")
+"#,
+            ),
+        );
+    }
+
+    #[test]
+    fn test_keep_item_comments() {
+        check(
+            r#"load(
+    "//:a.bzl",
+    # Second.
+    "b",
+    "a",  # First.
+)
+"#,
+            Some(
+                r#"load(
+    "//:a.bzl",
+    "a",  # First.
+    # Second.
+    "b",
+)
+"#,
+            ),
+        );
+    }
+
+    #[test]
+    fn test_already_organized() {
+        check(
+            "load(\"@repo//:x.bzl\", \"x\")\nload(\"//:a.bzl\", \"b\", a = \"c\")\n",
+            None,
+        );
+    }
+}
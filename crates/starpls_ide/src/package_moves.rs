@@ -0,0 +1,169 @@
+//! Rewrites the labels that refer to a package or its subpackages when the package is moved to
+//! another directory, e.g. `//old/pkg:lib` becomes `//new/pkg:lib` when moving `old/pkg` to
+//! `new/pkg`.
+//!
+//! Any string that parses as a label in the current repository is rewritten, which covers `load`
+//! paths, dependencies, and visibility entries like `//old/pkg:__subpackages__`, along with
+//! package specs like `-//old/pkg/...` in `package_group`s. Relative labels are left alone, since
+//! they can only refer to the package that contains them and move along with it.
+
+use starpls_bazel::{label::RepoKind, Label};
+use starpls_common::{parse, Db as _, FileId};
+use starpls_syntax::edit::EditBuilder;
+
+use crate::{label_references::string_values, Database, TextEdit};
+
+/// Returns the edits to each of the given files that rewrite the labels referring to the
+/// package `from` or its subpackages to refer to `to` instead. Packages are given without leading
+/// slashes, e.g. `old/pkg`.
+pub(crate) fn package_move_edits(
+    db: &Database,
+    file_ids: &[FileId],
+    from: &str,
+    to: &str,
+) -> Vec<(FileId, Vec<TextEdit>)> {
+    let mut edits = Vec::new();
+    for &file_id in file_ids {
+        let file = match db.get_file(file_id) {
+            Some(file) => file,
+            None => continue,
+        };
+        let mut builder = EditBuilder::new();
+        for (value, range) in string_values(&parse(db, file).syntax(db)) {
+            if let Some(new_text) = moved_label(&value, from, to) {
+                builder.replace(range, new_text);
+            }
+        }
+        let file_edits = builder
+            .finish()
+            .into_iter()
+            .map(TextEdit::from)
+            .collect::<Vec<_>>();
+        if !file_edits.is_empty() {
+            edits.push((file_id, file_edits));
+        }
+    }
+    edits
+}
+
+/// Returns the text of a label once the package `from` is moved to `to`, or `None` if the label
+/// doesn't refer to `from` or one of its subpackages.
+fn moved_label(value: &str, from: &str, to: &str) -> Option<String> {
+    let (negation, spec) = match value.strip_prefix('-') {
+        Some(spec) => ("-", spec),
+        None => ("", value),
+    };
+    let label = Label::parse(spec).ok()?;
+    let is_current_repo = match label.kind() {
+        RepoKind::Current => true,
+        RepoKind::Apparent | RepoKind::Canonical => label.repo().is_empty(),
+    };
+    if !is_current_repo || !label.has_leading_slashes() {
+        return None;
+    }
+
+    let package = label.package();
+    let rest = package.strip_prefix(from)?;
+    if !rest.is_empty() && !rest.starts_with('/') {
+        return None;
+    }
+
+    let (prefix, path) = spec.split_once("//")?;
+    let mut new_text = format!("{}{}//{}{}", negation, prefix, to, rest);
+    match path.split_once(':') {
+        Some((_, target)) => {
+            new_text.push(':');
+            new_text.push_str(target);
+        }
+        // Labels like `//old/pkg` are short for `//old/pkg:pkg`, so keep the target explicit if
+        // the last component of the package changes.
+        None if !rest.is_empty() || last_component(from) == last_component(to) => {}
+        None => {
+            new_text.push(':');
+            new_text.push_str(last_component(from));
+        }
+    }
+    Some(new_text)
+}
+
+fn last_component(package: &str) -> &str {
+    package.rsplit('/').next().unwrap_or(package)
+}
+
+#[cfg(test)]
+mod tests {
+    use starpls_bazel::APIContext;
+    use starpls_common::Dialect;
+    use starpls_syntax::edit::{apply_edits, Edit};
+
+    use super::moved_label;
+    use crate::AnalysisSnapshot;
+
+    #[test]
+    fn test_moved_label() {
+        let check = |value: &str, expected: Option<&str>| {
+            assert_eq!(
+                moved_label(value, "old/pkg", "new/lib").as_deref(),
+                expected,
+                "{}",
+                value
+            );
+        };
+        check("//old/pkg:lib", Some("//new/lib:lib"));
+        check("@//old/pkg:defs.bzl", Some("@//new/lib:defs.bzl"));
+        check("@@//old/pkg/sub:lib", Some("@@//new/lib/sub:lib"));
+        check("//old/pkg", Some("//new/lib:pkg"));
+        check("//old/pkg/sub", Some("//new/lib/sub"));
+        check(
+            "//old/pkg:__subpackages__",
+            Some("//new/lib:__subpackages__"),
+        );
+        check("-//old/pkg/...", Some("-//new/lib/..."));
+        check("//old/pkgs:lib", None);
+        check("//old:pkg", None);
+        check("@repo//old/pkg:lib", None);
+        check(":lib", None);
+        check("old/pkg", None);
+    }
+
+    #[test]
+    fn test_package_move_edits() {
+        let contents = r#"load("//old/pkg:defs.bzl", "my_rule")
+
+my_rule(
+    name = "app",
+    # Keep this comment.
+    deps = ["//old/pkg", "//other:lib", ":local"],
+    visibility = ["//old/pkg:__subpackages__"],
+)
+"#;
+        let (snap, file_id) =
+            AnalysisSnapshot::from_single_file(contents, Dialect::Bazel, Some(APIContext::Build));
+
+        let edits = snap
+            .package_move_edits(&[file_id], "old/pkg", "new/pkg")
+            .unwrap();
+        assert_eq!(edits.len(), 1);
+        assert_eq!(edits[0].0, file_id);
+        let edits = edits[0]
+            .1
+            .iter()
+            .map(|edit| Edit {
+                range: edit.range,
+                new_text: edit.new_text.clone(),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            apply_edits(contents, &edits),
+            r#"load("//new/pkg:defs.bzl", "my_rule")
+
+my_rule(
+    name = "app",
+    # Keep this comment.
+    deps = ["//new/pkg", "//other:lib", ":local"],
+    visibility = ["//new/pkg:__subpackages__"],
+)
+"#
+        );
+    }
+}
@@ -0,0 +1,544 @@
+//! Finds targets that are copies of each other for different platforms, e.g. `foo_linux` and
+//! `foo_macos` that only differ in their `copts`, and merges them into a single target that picks
+//! the differing attribute values with `select()`.
+//!
+//! Targets are grouped by rule and by their name without the platform suffix. A group can be
+//! merged if all of its targets set the same attributes with keyword arguments, and if no other
+//! target already has the merged name. The keys of the `select()` are the `config_setting`s that
+//! the workspace already selects on for each platform, falling back to the constraint values in
+//! `@platforms//os`. References to the old targets are only updated within the same package.
+
+use rustc_hash::FxHashSet;
+use starpls_bazel::APIContext;
+use starpls_common::{
+    parse, Db as _, Diagnostic, DiagnosticCode, Dialect, File, FileRange, Severity,
+};
+use starpls_syntax::{
+    ast::{self, AstNode, AstToken},
+    edit::{range_with_comments, EditBuilder},
+    SyntaxNode, TextRange, TextSize,
+};
+
+use crate::{targets, CodeAction, CodeActionKind, Database, TextEdit};
+
+const INDENT: &str = "    ";
+
+/// Name suffixes that mark platform-specific targets, along with the names of the matching
+/// constraint values in `@platforms//os`.
+const PLATFORM_SUFFIXES: &[(&str, &str)] = &[
+    ("android", "android"),
+    ("darwin", "macos"),
+    ("freebsd", "freebsd"),
+    ("ios", "ios"),
+    ("linux", "linux"),
+    ("mac", "macos"),
+    ("macos", "macos"),
+    ("osx", "macos"),
+    ("win", "windows"),
+    ("windows", "windows"),
+];
+
+struct PlatformTarget {
+    call: ast::CallExpr,
+    name: String,
+    name_range: TextRange,
+    suffix: &'static str,
+    os: &'static str,
+    /// The keyword arguments other than `name`, along with their values.
+    attrs: Vec<(String, ast::Expression)>,
+}
+
+/// Targets that only differ in some of their attribute values and in the platform that their
+/// names end with.
+struct PlatformGroup {
+    base: String,
+    targets: Vec<PlatformTarget>,
+    /// The attributes whose values differ between the targets, in the order they're set in the
+    /// first target.
+    differing: Vec<String>,
+}
+
+pub(crate) fn platform_target_diagnostics(db: &Database, file: File) -> Vec<Diagnostic> {
+    platform_groups(db, file)
+        .into_iter()
+        .map(|group| Diagnostic {
+            message: format!(
+                "Targets {} only differ in {}, and can be merged into \"{}\" with select()",
+                quoted_list(group.targets.iter().map(|target| &target.name)),
+                quoted_list(group.differing.iter()),
+                group.base
+            ),
+            code: DiagnosticCode::MergeablePlatformTargets,
+            severity: Severity::Hint,
+            range: FileRange {
+                file_id: file.id(db),
+                range: group.targets[0].name_range,
+            },
+        })
+        .collect()
+}
+
+pub(crate) fn platform_target_actions(
+    db: &Database,
+    file: File,
+    range: TextRange,
+) -> Vec<CodeAction> {
+    let groups = platform_groups(db, file);
+    let group = match groups.iter().find(|group| {
+        group
+            .targets
+            .iter()
+            .any(|target| target.call.syntax().text_range().intersect(range).is_some())
+    }) {
+        Some(group) => group,
+        None => return Vec::new(),
+    };
+    let keys = select_keys(db);
+    vec![CodeAction {
+        title: format!("Merge into `{}` with `select()`", group.base),
+        kind: CodeActionKind::RefactorRewrite,
+        edits: merge_edits(db, file, group, &keys),
+        file_edits: Vec::new(),
+        command: None,
+        is_preferred: false,
+    }]
+}
+
+fn platform_groups(db: &Database, file: File) -> Vec<PlatformGroup> {
+    if file.api_context(db) != Some(APIContext::Build) {
+        return Vec::new();
+    }
+    let root = parse(db, file).syntax(db);
+    let targets = targets::targets(db, file.id(db)).unwrap_or_default();
+    let names = targets
+        .iter()
+        .map(|target| target.name.as_str())
+        .collect::<FxHashSet<_>>();
+
+    let mut groups: Vec<(String, Vec<PlatformTarget>)> = Vec::new();
+    for target in targets.iter().filter(|target| !target.is_macro) {
+        let (base, suffix, os) = match target.name.rsplit_once('_').and_then(|(base, suffix)| {
+            PLATFORM_SUFFIXES
+                .iter()
+                .find(|(known, _)| *known == suffix)
+                .map(|(suffix, os)| (base, *suffix, *os))
+        }) {
+            Some(res) if !res.0.is_empty() => res,
+            _ => continue,
+        };
+        let call = match root
+            .children()
+            .filter_map(ast::CallExpr::cast)
+            .find(|call| call.syntax().text_range() == target.range)
+        {
+            Some(call) => call,
+            None => continue,
+        };
+        let attrs = match keyword_arguments(&call) {
+            Some(attrs) => attrs,
+            None => continue,
+        };
+        let platform_target = PlatformTarget {
+            call,
+            name: target.name.clone(),
+            name_range: target.name_range,
+            suffix,
+            os,
+            attrs,
+        };
+        let key = format!("{}:{}", target.kind, base);
+        match groups.iter_mut().find(|(existing, _)| *existing == key) {
+            Some((_, group)) => group.push(platform_target),
+            None => groups.push((key, vec![platform_target])),
+        }
+    }
+
+    groups
+        .into_iter()
+        .filter_map(|(_, targets)| {
+            let base = targets[0].name.rsplit_once('_')?.0.to_string();
+            if names.contains(base.as_str()) {
+                return None;
+            }
+            let differing = differing_attrs(&targets)?;
+            Some(PlatformGroup {
+                base,
+                targets,
+                differing,
+            })
+        })
+        .collect()
+}
+
+/// Returns the keyword arguments of a call other than `name`, or `None` if the call has any
+/// other kinds of arguments.
+fn keyword_arguments(call: &ast::CallExpr) -> Option<Vec<(String, ast::Expression)>> {
+    let mut attrs = Vec::new();
+    for arg in call.arguments()?.arguments() {
+        let arg = match arg {
+            ast::Argument::Keyword(arg) => arg,
+            _ => return None,
+        };
+        let name = arg.name()?.name()?.text().to_string();
+        if name != "name" {
+            attrs.push((name, arg.expr()?));
+        }
+    }
+    Some(attrs)
+}
+
+/// Returns the attributes whose values differ between the targets, or `None` if the targets
+/// can't be merged, e.g. because they set different attributes or are for the same platform.
+fn differing_attrs(targets: &[PlatformTarget]) -> Option<Vec<String>> {
+    if targets.len() < 2 {
+        return None;
+    }
+    let mut oses = FxHashSet::default();
+    if !targets.iter().all(|target| oses.insert(target.os)) {
+        return None;
+    }
+
+    let attr_names = |target: &PlatformTarget| {
+        let mut names = target
+            .attrs
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>();
+        names.sort();
+        names
+    };
+    let first = &targets[0];
+    let first_names = attr_names(first);
+    if targets[1..]
+        .iter()
+        .any(|target| attr_names(target) != first_names)
+    {
+        return None;
+    }
+
+    let mut differing = Vec::new();
+    for (name, first_value) in &first.attrs {
+        let values = targets
+            .iter()
+            .map(|target| {
+                target
+                    .attrs
+                    .iter()
+                    .find(|(other, _)| other == name)
+                    .map(|(_, value)| value.syntax())
+            })
+            .collect::<Option<Vec<_>>>()?;
+        if values
+            .iter()
+            .all(|value| normalize(value) == normalize(first_value.syntax()))
+        {
+            continue;
+        }
+        // `select()`s can't be nested.
+        if values.iter().any(|value| contains_select(value)) {
+            return None;
+        }
+        differing.push(name.clone());
+    }
+    (!differing.is_empty()).then_some(differing)
+}
+
+fn normalize(node: &SyntaxNode) -> String {
+    node.text()
+        .to_string()
+        .split_whitespace()
+        .collect::<String>()
+}
+
+fn contains_select(node: &SyntaxNode) -> bool {
+    node.descendants()
+        .filter_map(ast::CallExpr::cast)
+        .any(|call| is_select(&call))
+}
+
+fn is_select(call: &ast::CallExpr) -> bool {
+    matches!(
+        call.callee(),
+        Some(ast::Expression::Name(name_ref))
+            if name_ref.name().map_or(false, |name| name.text() == "select")
+    )
+}
+
+/// Returns the keys of the `select()`s in the Bazel files that have been loaded so far, sorted.
+fn select_keys(db: &Database) -> Vec<String> {
+    let mut keys = FxHashSet::default();
+    for entry in db.files.iter() {
+        let file = *entry.value();
+        if file.dialect(db) != Dialect::Bazel {
+            continue;
+        }
+        for call in parse(db, file)
+            .syntax(db)
+            .descendants()
+            .filter_map(ast::CallExpr::cast)
+            .filter(is_select)
+        {
+            let dict = match call.arguments().and_then(|args| args.arguments().next()) {
+                Some(ast::Argument::Simple(arg)) => match arg.expr() {
+                    Some(ast::Expression::Dict(dict)) => dict,
+                    _ => continue,
+                },
+                _ => continue,
+            };
+            keys.extend(
+                dict.entries()
+                    .filter_map(|entry| match entry.key()? {
+                        ast::Expression::Literal(lit) => match lit.kind() {
+                            ast::LiteralKind::String(s) => Some(s.value()?.to_string()),
+                            _ => None,
+                        },
+                        _ => None,
+                    })
+                    .filter(|key| key != "//conditions:default"),
+            );
+        }
+    }
+    let mut keys = keys.into_iter().collect::<Vec<_>>();
+    keys.sort();
+    keys
+}
+
+/// Returns the key to select the given platform with, preferring keys that the workspace already
+/// uses, e.g. `//config:linux`.
+fn select_key(keys: &[String], target: &PlatformTarget) -> String {
+    keys.iter()
+        .find(|key| {
+            let name = key
+                .rsplit_once(':')
+                .map(|(_, name)| name)
+                .or_else(|| key.rsplit_once('/').map(|(_, name)| name))
+                .unwrap_or(key.as_str());
+            name == target.suffix || name == target.os
+        })
+        .cloned()
+        .unwrap_or_else(|| format!("@platforms//os:{}", target.os))
+}
+
+fn merge_edits(db: &Database, file: File, group: &PlatformGroup, keys: &[String]) -> Vec<TextEdit> {
+    let text = parse(db, file).syntax(db).text().to_string();
+    let first = &group.targets[0];
+    let call_start = first.call.syntax().text_range().start();
+
+    // Rewrite the first target's call, replacing its name and the differing values.
+    let mut builder = EditBuilder::new();
+    builder.replace(first.name_range, format!("\"{}\"", group.base));
+    for (name, value) in &first.attrs {
+        if !group.differing.contains(name) {
+            continue;
+        }
+        let indent = line_indent(&text, value.syntax().text_range().start());
+        let mut select = "select({\n".to_string();
+        for target in &group.targets {
+            let value = target
+                .attrs
+                .iter()
+                .find(|(other, _)| other == name)
+                .map(|(_, value)| value.syntax().text().to_string())
+                .unwrap_or_default();
+            select.push_str(&format!(
+                "{}{}\"{}\": {},\n",
+                indent,
+                INDENT,
+                select_key(keys, target),
+                value.replace('\n', &format!("\n{}", INDENT))
+            ));
+        }
+        select.push_str(&format!("{}}})", indent));
+        builder.replace(value.syntax().text_range(), select);
+    }
+    let call_text = first.call.syntax().text().to_string();
+    let merged = starpls_syntax::edit::apply_edits(
+        &call_text,
+        &builder
+            .finish()
+            .into_iter()
+            .map(|mut edit| {
+                edit.range -= call_start;
+                edit
+            })
+            .collect::<Vec<_>>(),
+    );
+
+    let mut edits = vec![TextEdit {
+        range: first.call.syntax().text_range(),
+        new_text: merged,
+    }];
+    for target in &group.targets[1..] {
+        edits.push(TextEdit {
+            range: removal_range(&text, target.call.syntax()),
+            new_text: String::new(),
+        });
+    }
+
+    // Point references within the package at the merged target.
+    let root = parse(db, file).syntax(db);
+    for token in root
+        .descendants_with_tokens()
+        .filter_map(|el| el.into_token())
+        .filter_map(ast::String::cast)
+    {
+        let range = token.syntax().text_range();
+        if group
+            .targets
+            .iter()
+            .any(|target| target.call.syntax().text_range().contains_range(range))
+        {
+            continue;
+        }
+        let value = match token.value() {
+            Some(value) => value,
+            None => continue,
+        };
+        if group
+            .targets
+            .iter()
+            .any(|target| value.strip_prefix(':') == Some(target.name.as_str()))
+        {
+            edits.push(TextEdit {
+                range,
+                new_text: format!("\":{}\"", group.base),
+            });
+        }
+    }
+    edits.sort_by_key(|edit| edit.range.start());
+    edits
+}
+
+/// Returns the whitespace that the line containing the given offset starts with.
+fn line_indent(text: &str, offset: TextSize) -> String {
+    let line_start = text[..usize::from(offset)]
+        .rfind('\n')
+        .map_or(0, |pos| pos + 1);
+    text[line_start..]
+        .chars()
+        .take_while(|c| *c == ' ' || *c == '\t')
+        .collect()
+}
+
+/// Returns the range to delete to remove a top-level call along with its attached comments, the
+/// rest of its last line, and the blank line separating it from the previous statement.
+fn removal_range(text: &str, node: &SyntaxNode) -> TextRange {
+    let range = range_with_comments(node);
+    let mut start = usize::from(range.start());
+    let mut end = usize::from(range.end());
+    start = text[..start].rfind('\n').map_or(0, |pos| pos + 1);
+    end = text[end..]
+        .find('\n')
+        .map_or(text.len(), |pos| end + pos + 1);
+    if text[..start].ends_with("\n\n") {
+        start -= 1;
+    }
+    TextRange::new(TextSize::from(start as u32), TextSize::from(end as u32))
+}
+
+fn quoted_list<'a>(items: impl Iterator<Item = &'a String>) -> String {
+    items
+        .map(|item| format!("\"{}\"", item))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use starpls_bazel::APIContext;
+    use starpls_common::{Dialect, FileRange};
+    use starpls_syntax::{TextRange, TextSize};
+
+    use crate::{AnalysisSnapshot, TextEdit};
+
+    fn apply(text: &str, edits: &[TextEdit]) -> String {
+        let mut text = text.to_string();
+        for edit in edits.iter().rev() {
+            text.replace_range(std::ops::Range::<usize>::from(edit.range), &edit.new_text);
+        }
+        text
+    }
+
+    fn check(contents: &str, expected: Option<&str>) {
+        let (snap, file_id) =
+            AnalysisSnapshot::from_single_file(contents, Dialect::Bazel, Some(APIContext::Build));
+        let actions = snap
+            .code_actions(FileRange {
+                file_id,
+                range: TextRange::empty(TextSize::from(contents.find("_linux").unwrap() as u32)),
+            })
+            .unwrap()
+            .unwrap();
+        let action = actions
+            .iter()
+            .find(|action| action.title.starts_with("Merge into"));
+        assert_eq!(
+            action
+                .map(|action| apply(contents, &action.edits))
+                .as_deref(),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_merge_platform_targets() {
+        check(
+            r#"cc_library(
+    name = "foo_linux",
+    srcs = ["foo.cc"],
+    copts = ["-DLINUX"],
+)
+
+cc_library(
+    name = "foo_mac",
+    srcs = ["foo.cc"],
+    copts = [
+        "-DMAC",
+    ],
+)
+
+cc_binary(
+    name = "bin",
+    deps = [":foo_mac"],
+    defines = select({"//config:mac": ["X"]}),
+)
+"#,
+            Some(
+                r#"cc_library(
+    name = "foo",
+    srcs = ["foo.cc"],
+    copts = select({
+        "@platforms//os:linux": ["-DLINUX"],
+        "//config:mac": [
+            "-DMAC",
+        ],
+    }),
+)
+
+cc_binary(
+    name = "bin",
+    deps = [":foo"],
+    defines = select({"//config:mac": ["X"]}),
+)
+"#,
+            ),
+        );
+    }
+
+    #[test]
+    fn test_different_attributes() {
+        check(
+            r#"cc_library(
+    name = "foo_linux",
+    copts = ["-DLINUX"],
+)
+
+cc_library(
+    name = "foo_windows",
+    linkopts = ["-lws2_32"],
+)
+"#,
+            None,
+        );
+    }
+}
@@ -0,0 +1,389 @@
+//! Finds the references to a symbol, including its uses in the files that `load` it.
+
+use std::time::Instant;
+
+use starpls_common::{parse, Db as _, File, FileRange};
+use starpls_hir::{LoadItem, Name, ScopeDef, Semantics};
+use starpls_syntax::{
+    ast::{self, AstNode, SyntaxNodePtr},
+    SyntaxToken, TextRange, T,
+};
+
+use crate::{
+    build_settings, dict_keys, field_references, load_graph::load_graph,
+    symbol_usages::symbol_usages, util::pick_best_token, Database, FilePosition,
+};
+
+/// A symbol along with the definitions that it resolves to, e.g. every assignment to a global
+/// variable.
+pub(crate) struct Symbol {
+    pub(crate) file: File,
+    pub(crate) name: Name,
+    pub(crate) defs: Vec<SyntaxNodePtr>,
+}
+
+impl Symbol {
+    /// Returns the ranges of the names bound by the symbol's definitions.
+    pub(crate) fn def_name_ranges(&self, db: &Database) -> Vec<TextRange> {
+        let root = parse(db, self.file).syntax(db);
+        self.defs
+            .iter()
+            .map(|ptr| {
+                let node = ptr.to_node(&root);
+                ast::DefStmt::cast(node.clone())
+                    .and_then(|def_stmt| def_stmt.name())
+                    .map_or_else(|| node.text_range(), |name| name.syntax().text_range())
+            })
+            .collect()
+    }
+
+    /// Returns the ranges of the references to the symbol within the file that defines it.
+    pub(crate) fn local_references(&self, db: &Database, sema: &Semantics) -> Vec<TextRange> {
+        let def_ranges = self.def_ranges();
+        parse(db, self.file)
+            .syntax(db)
+            .descendants()
+            .filter_map(ast::NameRef::cast)
+            .filter(|name_ref| {
+                Name::from_ast_node(name_ref.clone()) == self.name
+                    && !def_ranges.contains(&name_ref.syntax().text_range())
+                    && ast::Expression::cast(name_ref.syntax().clone())
+                        .and_then(|expr| sema.scope_for_expr(self.file, &expr))
+                        .and_then(|scope| scope.resolve_name(&self.name))
+                        .map_or(false, |defs| {
+                            resolves_to_any(db, self.file, &defs, &def_ranges)
+                        })
+            })
+            .map(|name_ref| name_ref.syntax().text_range())
+            .collect()
+    }
+
+    /// Returns whether other files can load the symbol. Only globals that aren't private can be
+    /// loaded.
+    pub(crate) fn is_exported(&self, db: &Database, sema: &Semantics) -> bool {
+        !self.name.as_str().starts_with('_')
+            && sema
+                .scope_for_module(self.file)
+                .resolve_name(&self.name)
+                .map_or(false, |defs| {
+                    resolves_to_any(db, self.file, &defs, &self.def_ranges())
+                })
+    }
+
+    /// Returns the ranges of the symbol's definitions, e.g. entire `def` statements.
+    pub(crate) fn def_ranges(&self) -> Vec<TextRange> {
+        self.defs.iter().map(|ptr| ptr.text_range()).collect()
+    }
+}
+
+pub(crate) fn references(
+    db: &Database,
+    pos: FilePosition,
+    include_declaration: bool,
+) -> Option<Vec<FileRange>> {
+    let mut references = Vec::new();
+    find_references(db, pos, include_declaration, None, &mut |batch: Vec<
+        FileRange,
+    >| {
+        references.extend(batch)
+    })?;
+    sort_references(&mut references);
+    Some(references)
+}
+
+/// Finds the references like [`references`], but passes them to `sink` in batches as they're
+/// found: first the references within the file defining the symbol, then those in each file that
+/// loads it. If `deadline` passes, the remaining loading files are skipped. Returns whether every
+/// file was searched.
+pub(crate) fn find_references(
+    db: &Database,
+    pos: FilePosition,
+    include_declaration: bool,
+    deadline: Option<Instant>,
+    sink: &mut dyn FnMut(Vec<FileRange>),
+) -> Option<bool> {
+    if let Some(references) = dict_keys::references(db, pos.clone(), include_declaration) {
+        sink(references);
+        return Some(true);
+    }
+    if let Some(references) = build_settings::references(db, pos.clone(), include_declaration) {
+        sink(references);
+        return Some(true);
+    }
+    if let Some(references) = field_references::references(db, pos.clone(), include_declaration) {
+        sink(references);
+        return Some(true);
+    }
+
+    let sema = Semantics::new(db);
+    let symbol = symbol_at(db, &sema, pos)?;
+    let symbol_file_id = symbol.file.id(db);
+    let to_file_range = |file_id, range| FileRange { file_id, range };
+
+    let mut references = Vec::new();
+    if include_declaration {
+        references.extend(
+            symbol
+                .def_name_ranges(db)
+                .into_iter()
+                .map(|range| to_file_range(symbol_file_id, range)),
+        );
+    }
+    references.extend(
+        symbol
+            .local_references(db, &sema)
+            .into_iter()
+            .map(|range| to_file_range(symbol_file_id, range)),
+    );
+    sort_references(&mut references);
+    sink(references);
+
+    // Uses in the files that load the symbol.
+    if !symbol.is_exported(db, &sema) {
+        return Some(true);
+    }
+    let names = [symbol.name.to_string()];
+    let loaders = load_graph(db, symbol_file_id)
+        .map(|graph| graph.loaders)
+        .unwrap_or_default();
+    for loader in loaders {
+        if deadline.map_or(false, |deadline| Instant::now() >= deadline) {
+            return Some(false);
+        }
+        let mut references = Vec::new();
+        for usage in symbol_usages(db, loader, symbol_file_id, &names).unwrap_or_default() {
+            references.extend(
+                std::iter::once(usage.load_range)
+                    .chain(usage.reference_ranges)
+                    .map(|range| to_file_range(loader, range)),
+            );
+        }
+        if !references.is_empty() {
+            sort_references(&mut references);
+            sink(references);
+        }
+    }
+    Some(true)
+}
+
+fn sort_references(references: &mut Vec<FileRange>) {
+    references.sort_by_key(|reference| {
+        (
+            reference.file_id,
+            reference.range.start(),
+            reference.range.end(),
+        )
+    });
+    references.dedup_by_key(|reference| (reference.file_id, reference.range));
+}
+
+/// Finds the symbol at the given position, either a name that refers to it, the name in a `def`
+/// statement, or a `load` item that loads it.
+pub(crate) fn symbol_at(db: &Database, sema: &Semantics, pos: FilePosition) -> Option<Symbol> {
+    let file = db.get_file(pos.file_id)?;
+    let token = pick_best_token(
+        parse(db, file).syntax(db).token_at_offset(pos.pos),
+        |kind| match kind {
+            T![ident] => 2,
+            kind if kind.is_trivia_token() => 0,
+            _ => 1,
+        },
+    )?;
+    resolve_symbol(db, sema, file, &token)
+}
+
+/// Finds the symbol that the given token refers to or defines. Symbols loaded from other files
+/// are followed to their definitions.
+pub(crate) fn resolve_symbol(
+    db: &Database,
+    sema: &Semantics,
+    file: File,
+    token: &SyntaxToken,
+) -> Option<Symbol> {
+    let parent = token.parent()?;
+    if let Some(load_item) = parent.ancestors().find_map(ast::LoadItem::cast) {
+        let load_item = sema.resolve_load_item(file, &load_item)?;
+        return symbol_for_load_item(db, sema, &load_item);
+    }
+
+    let (scope, name) = if let Some(name_ref) = ast::NameRef::cast(parent.clone()) {
+        let expr = ast::Expression::cast(name_ref.syntax().clone())?;
+        (
+            sema.scope_for_expr(file, &expr)?,
+            Name::from_ast_node(name_ref),
+        )
+    } else {
+        let def_stmt = ast::DefStmt::cast(ast::Name::cast(parent)?.syntax().parent()?)?;
+        (
+            sema.scope_for_offset(file, def_stmt.syntax().text_range().start()),
+            Name::from_str(token.text()),
+        )
+    };
+
+    let defs = scope.resolve_name(&name)?;
+    if let Some(ScopeDef::LoadItem(load_item)) = defs.first() {
+        return symbol_for_load_item(db, sema, load_item);
+    }
+    Some(Symbol {
+        file,
+        name,
+        defs: defs
+            .iter()
+            .filter_map(|def| def.syntax_node_ptr(db, file))
+            .collect(),
+    })
+}
+
+fn symbol_for_load_item(db: &Database, sema: &Semantics, load_item: &LoadItem) -> Option<Symbol> {
+    let def = sema.def_for_load_item(load_item)?;
+    let name = load_item.name(db);
+    let defs = sema.scope_for_module(def.file).resolve_name(&name)?;
+    Some(Symbol {
+        file: def.file,
+        name,
+        defs: defs
+            .iter()
+            .filter_map(|def_| def_.syntax_node_ptr(db, def.file))
+            .collect(),
+    })
+}
+
+/// Returns whether any of the given definitions is the one at one of the given ranges.
+pub(crate) fn resolves_to_any(
+    db: &Database,
+    file: File,
+    defs: &[ScopeDef],
+    def_ranges: &[TextRange],
+) -> bool {
+    defs.iter().any(|def| {
+        def.syntax_node_ptr(db, file)
+            .map_or(false, |ptr| def_ranges.contains(&ptr.text_range()))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use starpls_common::{Dialect, FileId};
+    use starpls_syntax::TextSize;
+
+    use crate::{Analysis, AnalysisSnapshot, FilePosition};
+
+    const DEFS: &str = r#"def my_rule():
+    pass
+
+my_rule()
+_private = my_rule
+"#;
+
+    const USER: &str = r#"load("defs.star", "my_rule", alias = "my_rule")
+
+my_rule()
+alias()
+
+def f(my_rule):
+    return my_rule
+"#;
+
+    const FILES: [(&str, &str); 2] = [("defs.star", DEFS), ("user.star", USER)];
+
+    fn snapshot() -> AnalysisSnapshot {
+        AnalysisSnapshot::from_files(&FILES, Dialect::Standard)
+    }
+
+    fn check_references(file_id: FileId, offset: usize, expected: &[(u32, &str, usize)]) {
+        assert_eq!(references(&snapshot(), file_id, offset), expected);
+    }
+
+    fn references(
+        snap: &AnalysisSnapshot,
+        file_id: FileId,
+        offset: usize,
+    ) -> Vec<(u32, &'static str, usize)> {
+        snap.references(
+            FilePosition {
+                file_id,
+                pos: TextSize::from(offset as u32),
+            },
+            true,
+        )
+        .unwrap()
+        .unwrap()
+        .into_iter()
+        .map(|reference| {
+            let text = FILES[reference.file_id.0 as usize].1;
+            (
+                reference.file_id.0,
+                &text[reference.range],
+                usize::from(reference.range.start()),
+            )
+        })
+        .collect()
+    }
+
+    fn expected_for_my_rule() -> Vec<(u32, &'static str, usize)> {
+        vec![
+            (0, "my_rule", 4),
+            (0, "my_rule", DEFS.find("my_rule()\n_").unwrap()),
+            (0, "my_rule", DEFS.rfind("my_rule").unwrap()),
+            (1, "\"my_rule\"", USER.find("\"my_rule\"").unwrap()),
+            (1, "alias = \"my_rule\"", USER.find("alias").unwrap()),
+            (1, "my_rule", USER.find("my_rule()").unwrap()),
+            (1, "alias", USER.find("alias()").unwrap()),
+        ]
+    }
+
+    #[test]
+    fn test_references_from_definition() {
+        check_references(FileId(0), 5, &expected_for_my_rule());
+    }
+
+    #[test]
+    fn test_references_from_loading_file() {
+        check_references(
+            FileId(1),
+            USER.find("my_rule()").unwrap() + 1,
+            &expected_for_my_rule(),
+        );
+    }
+
+    #[test]
+    fn test_references_in_closed_loading_file() {
+        // Only `defs.star` is open, so `user.star` is only found through the workspace's index.
+        let snap = Analysis::from_workspace(&FILES[..1], &FILES[1..], Dialect::Standard).snapshot();
+        assert_eq!(references(&snap, FileId(0), 5), expected_for_my_rule());
+    }
+
+    #[test]
+    fn test_references_to_private_symbol() {
+        check_references(
+            FileId(0),
+            DEFS.find("_private").unwrap() + 1,
+            &[(0, "_private", DEFS.find("_private").unwrap())],
+        );
+    }
+
+    #[test]
+    fn test_references_past_deadline() {
+        let mut batches = Vec::new();
+        let is_complete = snapshot()
+            .references_in_batches(
+                FilePosition {
+                    file_id: FileId(0),
+                    pos: TextSize::from(5),
+                },
+                true,
+                Some(Instant::now()),
+                &mut |batch| batches.push(batch),
+            )
+            .unwrap()
+            .unwrap();
+        assert!(!is_complete);
+        assert_eq!(batches.len(), 1);
+        assert!(batches[0]
+            .iter()
+            .all(|reference| reference.file_id == FileId(0)));
+        assert_eq!(batches[0].len(), 3);
+    }
+}
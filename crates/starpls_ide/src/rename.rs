@@ -0,0 +1,813 @@
+//! Renames function parameters, functions, variables, and the fields of providers and structs.
+//!
+//! Along with a parameter itself, this updates the references to it in the function's body, its
+//! entry in the `Args:` section of the function's docstring, and the keyword arguments that pass
+//! it at every call site that resolves to the function, including call sites in the files that
+//! load the function. Functions and variables are renamed along with their references and the
+//! `load` items that load them from other files, and fields along with their declarations and
+//! accesses, as found by [`field_references`]. Renames that would make a name refer to a
+//! different binding than it did before are refused.
+
+use anyhow::bail;
+use starpls_common::{parse, Db as _, File, FileId, InFile};
+use starpls_hir::{Name, ScopeDef, Semantics, SemanticsScope};
+use starpls_syntax::{
+    ast::{self, AstNode, AstToken, SyntaxNodePtr},
+    SyntaxKind, SyntaxNode, SyntaxToken, TextRange, TextSize, T,
+};
+
+use crate::{
+    field_references::{self, FieldReferences},
+    load_graph::load_graph,
+    references::{resolves_to_any, symbol_at},
+    symbol_usages::symbol_usages,
+    util::{docstring_args, pick_best_token},
+    Database, EditTarget, FileEdit, FilePosition, TextEdit,
+};
+
+const KEYWORDS: &[&str] = &[
+    "and", "as", "assert", "async", "await", "break", "class", "continue", "def", "del", "elif",
+    "else", "except", "finally", "for", "from", "global", "if", "import", "in", "is", "lambda",
+    "load", "nonlocal", "not", "or", "pass", "raise", "return", "try", "while", "with", "yield",
+];
+
+/// The edits that rename a symbol.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Rename {
+    /// The edits to each affected file, starting with the file that defines the symbol.
+    pub file_edits: Vec<FileEdit>,
+    /// The file that defines the symbol, if other files can load it. Call sites in files that
+    /// haven't been analyzed, e.g. in other external repositories, aren't updated in this case.
+    pub exported_from: Option<FileId>,
+}
+
+pub(crate) fn rename(
+    db: &Database,
+    pos: FilePosition,
+    new_name: &str,
+) -> anyhow::Result<Option<Rename>> {
+    if !is_valid_name(new_name) {
+        bail!("\"{}\" is not a valid name", new_name);
+    }
+
+    if let Some(references) = field_references::field_references(db, pos.clone()) {
+        return Ok(Some(rename_field(db, references, new_name)));
+    }
+
+    let sema = Semantics::new(db);
+    let (file, param) = match find_param(db, &sema, pos.clone()) {
+        Some(res) => res,
+        None => return rename_symbol(db, &sema, pos, new_name),
+    };
+    let (old_name, name_token) = match param
+        .syntax()
+        .children()
+        .find_map(ast::Name::cast)
+        .and_then(|name| name.name())
+    {
+        Some(token) => (token.text().to_string(), token),
+        None => return Ok(None),
+    };
+
+    // The function or lambda that the parameter belongs to.
+    let params = match param.syntax().parent().and_then(ast::Parameters::cast) {
+        Some(params) => params,
+        None => return Ok(None),
+    };
+    let owner = match params.syntax().parent() {
+        Some(owner) => owner,
+        None => return Ok(None),
+    };
+    if params
+        .parameters()
+        .any(|other| other != param && other.name().as_deref() == Some(new_name))
+    {
+        bail!("a parameter named \"{}\" already exists", new_name);
+    }
+
+    let mut edits = vec![TextEdit {
+        range: name_token.text_range(),
+        new_text: new_name.to_string(),
+    }];
+    let param_range = param.syntax().text_range();
+    for name_ref in owner.descendants().filter_map(ast::NameRef::cast) {
+        let token = match name_ref.name() {
+            Some(token) => token,
+            None => continue,
+        };
+        if token.text() == new_name {
+            bail!(
+                "renaming the parameter would shadow \"{}\", which is used in the function",
+                new_name
+            );
+        }
+        if token.text() != old_name {
+            continue;
+        }
+        let resolves_to_param = ast::Expression::cast(name_ref.syntax().clone())
+            .and_then(|expr| sema.scope_for_expr(file, &expr))
+            .and_then(|scope| scope.resolve_name(&Name::from(old_name.as_str())))
+            .and_then(|defs| defs.into_iter().last())
+            .map_or(false, |def| {
+                matches!(def, ScopeDef::Parameter(_))
+                    && def.syntax_node_ptr(db, file).map(|ptr| ptr.text_range())
+                        == Some(param_range)
+            });
+        if resolves_to_param {
+            edits.push(TextEdit {
+                range: token.text_range(),
+                new_text: new_name.to_string(),
+            });
+        }
+    }
+
+    let def_stmt = match ast::DefStmt::cast(owner) {
+        Some(def_stmt) => def_stmt,
+        None => {
+            return Ok(Some(Rename {
+                file_edits: vec![file_edit(file.id(db), edits)],
+                exported_from: None,
+            }))
+        }
+    };
+    edits.extend(docstring_edit(&def_stmt, &old_name, new_name));
+
+    // Only simple parameters can be passed by keyword.
+    let mut file_edits = Vec::new();
+    let mut call_site_edits = Vec::new();
+    if matches!(param, ast::Parameter::Simple(_)) {
+        let def = InFile {
+            file,
+            value: SyntaxNodePtr::new(def_stmt.syntax()),
+        };
+        let loaders = load_graph(db, file.id(db))
+            .map(|graph| graph.loaders)
+            .unwrap_or_default();
+        edits.extend(keyword_arg_edits(
+            db, &sema, file, &def, &old_name, new_name,
+        ));
+        for loader in loaders
+            .into_iter()
+            .filter_map(|file_id| db.get_file(file_id))
+        {
+            let loader_edits = keyword_arg_edits(db, &sema, loader, &def, &old_name, new_name);
+            if !loader_edits.is_empty() {
+                call_site_edits.push(file_edit(loader.id(db), loader_edits));
+            }
+        }
+    }
+    file_edits.push(file_edit(file.id(db), edits));
+    file_edits.extend(call_site_edits);
+
+    let is_exported = def_stmt
+        .syntax()
+        .parent()
+        .map_or(false, |parent| parent.kind() == SyntaxKind::MODULE)
+        && def_stmt
+            .name()
+            .and_then(|name| name.name())
+            .map_or(false, |name| !name.text().starts_with('_'));
+    Ok(Some(Rename {
+        file_edits,
+        exported_from: is_exported.then(|| file.id(db)),
+    }))
+}
+
+/// Renames the function or variable at the given position, along with its references and the
+/// `load` items that load it from other files.
+fn rename_symbol(
+    db: &Database,
+    sema: &Semantics,
+    pos: FilePosition,
+    new_name: &str,
+) -> anyhow::Result<Option<Rename>> {
+    let symbol = match symbol_at(db, sema, pos) {
+        Some(symbol) => symbol,
+        None => return Ok(None),
+    };
+    let old_name = symbol.name.as_str().to_string();
+    if symbol.defs.is_empty() {
+        bail!("\"{}\" is a builtin and can't be renamed", old_name);
+    }
+    let file_id = symbol.file.id(db);
+    let root = parse(db, symbol.file).syntax(db);
+    let references = symbol.local_references(db, sema);
+    let scopes = symbol
+        .defs
+        .iter()
+        .map(|ptr| binding_scope(sema, symbol.file, &ptr.to_node(&root)))
+        .chain(
+            references
+                .iter()
+                .map(|range| sema.scope_for_offset(symbol.file, range.start())),
+        )
+        .collect::<Vec<_>>();
+    check_conflicts(
+        db,
+        sema,
+        symbol.file,
+        &old_name,
+        new_name,
+        &symbol.def_ranges(),
+        &scopes,
+        "",
+    )?;
+    let sites = symbol
+        .def_name_ranges(db)
+        .into_iter()
+        .chain(references)
+        .collect::<Vec<_>>();
+    let mut file_edits = vec![file_edit(file_id, rename_edits(&sites, new_name))];
+
+    let is_exported = symbol.is_exported(db, sema);
+    if is_exported {
+        let loaders = load_graph(db, file_id)
+            .map(|graph| graph.loaders)
+            .unwrap_or_default();
+        for loader in loaders {
+            let loader_file = match db.get_file(loader) {
+                Some(file) => file,
+                None => continue,
+            };
+            let root = parse(db, loader_file).syntax(db);
+            let mut edits = Vec::new();
+            for usage in symbol_usages(db, loader, file_id, &[old_name.clone()]).unwrap_or_default()
+            {
+                let load_item = match root
+                    .descendants()
+                    .filter_map(ast::LoadItem::cast)
+                    .find(|load_item| load_item.syntax().text_range() == usage.load_range)
+                {
+                    Some(load_item) => load_item,
+                    None => continue,
+                };
+                let (name, is_direct) = match &load_item {
+                    ast::LoadItem::Direct(item) => (item.name(), true),
+                    ast::LoadItem::Aliased(item) => (item.name(), false),
+                };
+                let name_range = match name.as_ref().and_then(string_value_range) {
+                    Some(range) => range,
+                    None => bail!(
+                        "\"{}\" is loaded with an escaped name, which can't be renamed",
+                        old_name
+                    ),
+                };
+
+                // Aliased items keep their local names, so only the loaded name changes.
+                edits.push(TextEdit {
+                    range: name_range,
+                    new_text: new_name.to_string(),
+                });
+                if is_direct {
+                    let scopes = std::iter::once(sema.scope_for_module(loader_file))
+                        .chain(
+                            usage
+                                .reference_ranges
+                                .iter()
+                                .map(|range| sema.scope_for_offset(loader_file, range.start())),
+                        )
+                        .collect::<Vec<_>>();
+                    check_conflicts(
+                        db,
+                        sema,
+                        loader_file,
+                        &old_name,
+                        new_name,
+                        &[usage.load_range],
+                        &scopes,
+                        " in a file that loads it",
+                    )?;
+                    edits.extend(rename_edits(&usage.reference_ranges, new_name));
+                }
+            }
+            if !edits.is_empty() {
+                file_edits.push(file_edit(loader, edits));
+            }
+        }
+    }
+
+    Ok(Some(Rename {
+        file_edits,
+        exported_from: is_exported.then_some(file_id),
+    }))
+}
+
+/// Returns the scope that a definition binds its name in. Offsets within a `def` statement are
+/// in the scope of the function itself, so the enclosing block is used for those instead.
+fn binding_scope<'a>(sema: &'a Semantics, file: File, node: &SyntaxNode) -> SemanticsScope<'a> {
+    if !ast::DefStmt::can_cast(node.kind()) {
+        return sema.scope_for_offset(file, node.text_range().start());
+    }
+    match node.parent() {
+        Some(parent) if parent.kind() != SyntaxKind::MODULE => {
+            sema.scope_for_offset(file, parent.text_range().start())
+        }
+        _ => sema.scope_for_module(file),
+    }
+}
+
+/// Checks that renaming the binding with the given definitions to `new_name` leaves every name
+/// in the file referring to what it did before. This fails if `new_name` is already bound in any
+/// of the given scopes, i.e. where the binding is defined or used, or if the renamed binding
+/// would shadow another binding, e.g. a builtin, where `new_name` is used.
+#[allow(clippy::too_many_arguments)]
+fn check_conflicts(
+    db: &Database,
+    sema: &Semantics,
+    file: File,
+    old_name: &str,
+    new_name: &str,
+    def_ranges: &[TextRange],
+    scopes: &[SemanticsScope],
+    location: &str,
+) -> anyhow::Result<()> {
+    if old_name == new_name {
+        return Ok(());
+    }
+    let old = Name::from_str(old_name);
+    let new = Name::from_str(new_name);
+    let is_bound = |scope: &SemanticsScope| {
+        scope
+            .resolve_name(&new)
+            .map_or(false, |defs| defs.iter().any(|def| def.is_user_defined()))
+    };
+
+    if scopes.iter().any(&is_bound) {
+        bail!("\"{}\" is already defined{}", new_name, location);
+    }
+    for name_ref in parse(db, file)
+        .syntax(db)
+        .descendants()
+        .filter_map(ast::NameRef::cast)
+        .filter(|name_ref| Name::from_ast_node(name_ref.clone()) == new)
+    {
+        let scope = match ast::Expression::cast(name_ref.syntax().clone())
+            .and_then(|expr| sema.scope_for_expr(file, &expr))
+        {
+            Some(scope) => scope,
+            None => continue,
+        };
+        let is_captured = scope
+            .resolve_name(&old)
+            .map_or(false, |defs| resolves_to_any(db, file, &defs, def_ranges));
+        if is_captured && !is_bound(&scope) {
+            bail!(
+                "renaming \"{}\" would shadow \"{}\", which is used{}",
+                old_name,
+                new_name,
+                location
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Renames a field of a provider or struct, along with its entry in the provider's `fields` and
+/// the keyword arguments and field accesses that refer to it.
+fn rename_field(db: &Database, references: FieldReferences, new_name: &str) -> Rename {
+    let mut edits: Vec<(FileId, Vec<TextEdit>)> = Vec::new();
+    let mut push = |file_id: FileId, range: TextRange| {
+        let edit = TextEdit {
+            range,
+            new_text: new_name.to_string(),
+        };
+        match edits.iter_mut().find(|(id, _)| *id == file_id) {
+            Some((_, file_edits)) => file_edits.push(edit),
+            None => edits.push((file_id, vec![edit])),
+        }
+    };
+
+    // Only the value of the string declaring the field is replaced, keeping its quotes.
+    let declaration = references.declaration.and_then(|declaration| {
+        let file = db.get_file(declaration.file_id)?;
+        let token = parse(db, file)
+            .syntax(db)
+            .token_at_offset(declaration.range.start())
+            .right_biased()?;
+        Some((declaration.file_id, string_value_range(&token)?))
+    });
+    if let Some((file_id, range)) = declaration {
+        push(file_id, range);
+    }
+    for reference in references.uses {
+        push(reference.file_id, reference.range);
+    }
+    Rename {
+        file_edits: edits
+            .into_iter()
+            .map(|(file_id, edits)| file_edit(file_id, edits))
+            .collect(),
+        exported_from: declaration.map(|(file_id, _)| file_id),
+    }
+}
+
+fn rename_edits(ranges: &[TextRange], new_name: &str) -> Vec<TextEdit> {
+    ranges
+        .iter()
+        .map(|range| TextEdit {
+            range: *range,
+            new_text: new_name.to_string(),
+        })
+        .collect()
+}
+
+/// Returns the range of a string's value, excluding its prefix and quotes, if the string
+/// doesn't contain escape sequences.
+fn string_value_range(token: &SyntaxToken) -> Option<TextRange> {
+    let (value, offset) = ast::String::cast(token.clone())?.value_and_offset()?;
+    let start = offset as usize;
+    if token.text().get(start..start + value.len()) != Some(&*value) {
+        return None;
+    }
+    Some(TextRange::at(
+        token.text_range().start() + TextSize::from(offset),
+        TextSize::of(&*value),
+    ))
+}
+
+/// Finds the parameter at the given position. This is either the parameter's name in a `def`
+/// statement or lambda, a reference to the parameter in the function's body, or a keyword
+/// argument that passes the parameter, in which case the parameter may be in another file.
+fn find_param(
+    db: &Database,
+    sema: &Semantics,
+    pos: FilePosition,
+) -> Option<(File, ast::Parameter)> {
+    let file = db.get_file(pos.file_id)?;
+    let root = parse(db, file).syntax(db);
+    let token = pick_best_token(root.token_at_offset(pos.pos), |kind| match kind {
+        T![ident] => 2,
+        kind if kind.is_trivia_token() => 0,
+        _ => 1,
+    })?;
+    if token.kind() != T![ident] {
+        return None;
+    }
+    let parent = token.parent()?;
+
+    if let Some(name_ref) = ast::NameRef::cast(parent.clone()) {
+        let ptr = sema
+            .scope_for_expr(file, &ast::Expression::cast(name_ref.syntax().clone())?)?
+            .resolve_name(&Name::from_ast_node(name_ref))?
+            .into_iter()
+            .last()
+            .and_then(|def| match def {
+                ScopeDef::Parameter(param) => param.syntax_node_ptr(db),
+                _ => None,
+            })?;
+        return Some((file, ast::Parameter::cast(ptr.to_node(&root))?));
+    }
+
+    let node = ast::Name::cast(parent)?.syntax().parent()?;
+    if let Some(param) = ast::Parameter::cast(node.clone()) {
+        return Some((file, param));
+    }
+
+    let call = ast::KeywordArgument::cast(node)?
+        .syntax()
+        .parent()?
+        .parent()
+        .and_then(ast::CallExpr::cast)?;
+    let source = sema.resolve_call_expr(file, &call)?.source(db)?;
+    let def_stmt = ast::DefStmt::cast(source.value.to_node(&parse(db, source.file).syntax(db)))?;
+    let param = def_stmt
+        .parameters()?
+        .parameters()
+        .find(|param| param.name().as_deref() == Some(token.text()))?;
+    Some((source.file, param))
+}
+
+/// Renames the parameter's entry in the `Args:` section of the function's docstring.
+fn docstring_edit(def_stmt: &ast::DefStmt, old_name: &str, new_name: &str) -> Option<TextEdit> {
+    let doc = def_stmt.doc()?;
+    let start = doc.syntax().text_range().start();
+    let (offset, name) = docstring_args(doc.text())
+        .into_iter()
+        .find(|(_, name)| *name == old_name)?;
+    Some(TextEdit {
+        range: TextRange::at(start + TextSize::from(offset as u32), TextSize::of(name)),
+        new_text: new_name.to_string(),
+    })
+}
+
+/// Renames the keyword arguments for the parameter in the calls to the given function.
+fn keyword_arg_edits(
+    db: &Database,
+    sema: &Semantics,
+    file: File,
+    def: &InFile<SyntaxNodePtr>,
+    old_name: &str,
+    new_name: &str,
+) -> Vec<TextEdit> {
+    parse(db, file)
+        .syntax(db)
+        .descendants()
+        .filter_map(ast::CallExpr::cast)
+        .filter(|call| {
+            sema.resolve_call_expr(file, call)
+                .and_then(|callable| callable.source(db))
+                .as_ref()
+                == Some(def)
+        })
+        .flat_map(|call| {
+            call.arguments()
+                .into_iter()
+                .flat_map(|args| args.arguments())
+        })
+        .filter_map(|arg| match arg {
+            ast::Argument::Keyword(arg) => arg.name()?.name(),
+            _ => None,
+        })
+        .filter(|token| token.text() == old_name)
+        .map(|token| TextEdit {
+            range: token.text_range(),
+            new_text: new_name.to_string(),
+        })
+        .collect()
+}
+
+fn file_edit(file_id: FileId, mut edits: Vec<TextEdit>) -> FileEdit {
+    edits.sort_by_key(|edit| edit.range.start());
+    FileEdit {
+        target: EditTarget::File(file_id),
+        edits,
+    }
+}
+
+fn is_valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .map_or(false, |c| c == '_' || c.is_ascii_alphabetic())
+        && chars.all(|c| c == '_' || c.is_ascii_alphanumeric())
+        && !KEYWORDS.contains(&name)
+}
+
+#[cfg(test)]
+mod tests {
+    use starpls_common::{Dialect, FileId};
+    use starpls_syntax::TextSize;
+
+    use crate::{Analysis, AnalysisSnapshot, EditTarget, FilePosition, TextEdit};
+
+    fn apply(text: &str, edits: &[TextEdit]) -> String {
+        let mut edits = edits.to_vec();
+        edits.sort_by_key(|edit| std::cmp::Reverse(edit.range.start()));
+        let mut text = text.to_string();
+        for edit in edits {
+            text.replace_range(std::ops::Range::<usize>::from(edit.range), &edit.new_text);
+        }
+        text
+    }
+
+    #[test]
+    fn test_rename_param() {
+        let files = [
+            (
+                "defs.star",
+                r#"def make(name, srcs = []):
+    """Makes things.
+
+    Args:
+        name (string): The name.
+        srcs: The sources.
+    """
+    print(name)
+
+    def inner(name):
+        return name
+
+    return [name] + srcs
+
+make(name = "a")
+"#,
+            ),
+            (
+                "user.star",
+                r#"load("defs.star", "make")
+
+make(name = "b", srcs = [])
+make("c")
+"#,
+            ),
+        ];
+        let rename = AnalysisSnapshot::from_files(&files, Dialect::Standard)
+            .rename(
+                FilePosition {
+                    file_id: FileId(1),
+                    pos: TextSize::from(files[1].1.find("name =").unwrap() as u32),
+                },
+                "label",
+            )
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        assert_eq!(rename.exported_from, Some(FileId(0)));
+
+        let renamed = rename
+            .file_edits
+            .iter()
+            .map(|file_edit| match file_edit.target {
+                EditTarget::File(file_id) => apply(files[file_id.0 as usize].1, &file_edit.edits),
+                EditTarget::NewFile(_) => unreachable!(),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            renamed,
+            vec![
+                r#"def make(label, srcs = []):
+    """Makes things.
+
+    Args:
+        label (string): The name.
+        srcs: The sources.
+    """
+    print(label)
+
+    def inner(name):
+        return name
+
+    return [label] + srcs
+
+make(label = "a")
+"#,
+                r#"load("defs.star", "make")
+
+make(label = "b", srcs = [])
+make("c")
+"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_rename_param_conflict() {
+        let files = [("main.star", "def f(x, y):\n    return x + len(y)\n")];
+        let snap = AnalysisSnapshot::from_files(&files, Dialect::Standard);
+        let rename = |new_name: &str| {
+            snap.rename(
+                FilePosition {
+                    file_id: FileId(0),
+                    pos: TextSize::from(6),
+                },
+                new_name,
+            )
+            .unwrap()
+        };
+        assert!(rename("y").is_err());
+        assert!(rename("len").is_err());
+        assert!(rename("for").is_err());
+        assert!(rename("z").unwrap().is_some());
+    }
+
+    fn renamed_files(
+        files: &[(&str, &str)],
+        file_id: FileId,
+        offset: usize,
+        new_name: &str,
+    ) -> Vec<String> {
+        let rename = AnalysisSnapshot::from_files(files, Dialect::Standard)
+            .rename(
+                FilePosition {
+                    file_id,
+                    pos: TextSize::from(offset as u32),
+                },
+                new_name,
+            )
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        rename
+            .file_edits
+            .iter()
+            .map(|file_edit| match file_edit.target {
+                EditTarget::File(file_id) => apply(files[file_id.0 as usize].1, &file_edit.edits),
+                EditTarget::NewFile(_) => unreachable!(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_rename_function() {
+        let files = [
+            ("defs.star", "def my_rule():\n    pass\n\nmy_rule()\n"),
+            (
+                "user.star",
+                r#"load("defs.star", "my_rule", alias = "my_rule")
+
+my_rule()
+alias()
+
+def f(my_rule):
+    return my_rule
+"#,
+            ),
+        ];
+        assert_eq!(
+            renamed_files(
+                &files,
+                FileId(1),
+                files[1].1.find("my_rule()").unwrap(),
+                "build_rule"
+            ),
+            vec![
+                "def build_rule():\n    pass\n\nbuild_rule()\n".to_string(),
+                r#"load("defs.star", "build_rule", alias = "build_rule")
+
+build_rule()
+alias()
+
+def f(my_rule):
+    return my_rule
+"#
+                .to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_rename_global_variable() {
+        let files = [(
+            "main.star",
+            "COUNT = 1\n\ndef f():\n    return COUNT + 1\n\nCOUNT = f()\n",
+        )];
+        assert_eq!(
+            renamed_files(
+                &files,
+                FileId(0),
+                files[0].1.find("COUNT +").unwrap(),
+                "TOTAL"
+            ),
+            vec!["TOTAL = 1\n\ndef f():\n    return TOTAL + 1\n\nTOTAL = f()\n".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_rename_symbol_conflict() {
+        let files = [
+            (
+                "defs.star",
+                "X = 1\nY = 2\n\ndef f(z):\n    return X + len(z)\n",
+            ),
+            ("user.star", "load(\"defs.star\", \"X\")\n\nW = X\n"),
+        ];
+        let snap = AnalysisSnapshot::from_files(&files, Dialect::Standard);
+        let rename = |offset: usize, new_name: &str| {
+            snap.rename(
+                FilePosition {
+                    file_id: FileId(0),
+                    pos: TextSize::from(offset as u32),
+                },
+                new_name,
+            )
+            .unwrap()
+        };
+        assert!(rename(0, "Y").is_err());
+        assert!(rename(0, "z").is_err());
+        assert!(rename(0, "len").is_err());
+        assert!(rename(0, "W").is_err());
+        assert!(rename(0, "V").unwrap().is_some());
+        assert!(rename(files[0].1.find("len").unwrap(), "size").is_err());
+    }
+
+    #[test]
+    fn test_rename_function_in_closed_loader() {
+        // `user.star` isn't open and isn't loaded by `defs.star`, so its call site and its
+        // conflicting global are only found through the loader's index of the workspace.
+        let files = [
+            ("defs.star", "def my_rule():\n    pass\n"),
+            (
+                "user.star",
+                "load(\"defs.star\", \"my_rule\")\n\nmy_rule()\n\nbuild_rule = 1\n",
+            ),
+        ];
+        let snap = Analysis::from_workspace(&files[..1], &files[1..], Dialect::Standard).snapshot();
+        let rename = |new_name: &str| {
+            snap.rename(
+                FilePosition {
+                    file_id: FileId(0),
+                    pos: TextSize::from(4),
+                },
+                new_name,
+            )
+            .unwrap()
+        };
+        assert!(rename("build_rule").is_err());
+        let rename = rename("make_rule").unwrap().unwrap();
+        assert_eq!(
+            rename
+                .file_edits
+                .iter()
+                .map(|file_edit| match file_edit.target {
+                    EditTarget::File(file_id) =>
+                        apply(files[file_id.0 as usize].1, &file_edit.edits),
+                    EditTarget::NewFile(_) => unreachable!(),
+                })
+                .collect::<Vec<_>>(),
+            vec![
+                "def make_rule():\n    pass\n".to_string(),
+                "load(\"defs.star\", \"make_rule\")\n\nmake_rule()\n\nbuild_rule = 1\n".to_string(),
+            ]
+        );
+    }
+}
@@ -0,0 +1,92 @@
+use starpls_common::{Db as _, File, FileId, FileRange};
+use starpls_hir::{Db as _, DisplayWithDb, Name, Semantics};
+use starpls_syntax::ast::{self, AstNode};
+
+use crate::Database;
+
+/// The attribute schema of a rule, as declared by a call to `rule()` or by the builtin
+/// build language definitions.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RuleSchema {
+    pub name: String,
+    pub doc: Option<String>,
+    pub attributes: Vec<RuleAttribute>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RuleAttribute {
+    pub name: String,
+    /// The rendered type of values accepted by the attribute.
+    pub ty: String,
+    pub doc: Option<String>,
+    pub mandatory: bool,
+    pub default_value: Option<String>,
+}
+
+pub(crate) fn rule_attributes(db: &Database, file_id: FileId, name: &str) -> Option<RuleSchema> {
+    let sema = Semantics::new(db);
+    let file = db.get_file(file_id)?;
+    let def = sema
+        .scope_for_module(file)
+        .resolve_name(&Name::from(name))?
+        .pop()?;
+    let ty = def.ty(db);
+
+    // Native rules are typed as regular builtin functions, so identify them by name instead.
+    let is_builtin_rule = ty.is_function()
+        && db
+            .get_builtin_defs(&file.dialect(db))
+            .rules(db)
+            .global
+            .iter()
+            .any(|rule| rule.name == name);
+    if !ty.is_rule() && !is_builtin_rule {
+        return None;
+    }
+
+    let attributes = ty
+        .params(db)
+        .into_iter()
+        .filter(|(param, _)| !param.is_args_list(db) && !param.is_kwargs_dict(db))
+        .filter_map(|(param, param_ty)| {
+            Some(RuleAttribute {
+                name: param.name(db)?.as_str().to_string(),
+                ty: param_ty.display(db).to_string(),
+                doc: param.doc(db).filter(|doc| !doc.is_empty()),
+                mandatory: param.is_mandatory(db),
+                default_value: param.default_value(db),
+            })
+        })
+        .collect();
+
+    Some(RuleSchema {
+        name: name.to_string(),
+        doc: ty.doc(db).map(|doc| unindent::unindent(&doc)),
+        attributes,
+    })
+}
+
+/// Finds the declaration of the attribute set by a keyword argument in a call to a rule, i.e. its
+/// key in the dict passed as the rule's `attrs`, e.g. `"srcs"` in
+/// `rule(attrs = {"srcs": attr.label_list()})` for `srcs = [...]`.
+pub(crate) fn attr_definition(db: &Database, file: File, name: &ast::Name) -> Option<FileRange> {
+    let arg = ast::KeywordArgument::cast(name.syntax().parent()?)?;
+    let call = ast::CallExpr::cast(arg.syntax().parent()?.parent()?)?;
+    let attr_name = name.name()?;
+    let attrs = Semantics::new(db)
+        .resolve_call_expr(file, &call)?
+        .ty(db)
+        .rule_attrs_source(db)?;
+    attrs.value.entries().find_map(|entry| match entry.key()? {
+        ast::Expression::Literal(lit) => match lit.kind() {
+            ast::LiteralKind::String(s) if s.value().as_deref() == Some(attr_name.text()) => {
+                Some(FileRange {
+                    file_id: attrs.file.id(db),
+                    range: lit.syntax().text_range(),
+                })
+            }
+            _ => None,
+        },
+        _ => None,
+    })
+}
@@ -0,0 +1,140 @@
+//! Computes the Bazel commands that can be run for the targets in a `BUILD` file.
+
+use starpls_common::FileId;
+use starpls_syntax::{TextRange, TextSize};
+
+use crate::{
+    targets::{self, rule_name},
+    Database,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RunnableKind {
+    Build,
+    Test,
+    Run,
+}
+
+/// A Bazel command for either a single target or all targets in the file's package.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Runnable {
+    pub kind: RunnableKind,
+    /// The name of the target, or `None` for all targets in the package.
+    pub target: Option<String>,
+    /// The range of the target's declaration, or `None` for all targets in the package.
+    pub range: Option<TextRange>,
+}
+
+/// Returns the runnables for the given file. If a position is given, only the runnables for
+/// the target declared at that position, along with those for the entire package, are returned.
+pub(crate) fn runnables(
+    db: &Database,
+    file_id: FileId,
+    pos: Option<TextSize>,
+) -> Option<Vec<Runnable>> {
+    let targets = targets::targets(db, file_id)?;
+    let mut runnables = Vec::new();
+    if targets.is_empty() {
+        return Some(runnables);
+    }
+
+    runnables.push(Runnable {
+        kind: RunnableKind::Build,
+        target: None,
+        range: None,
+    });
+    if targets.iter().any(|target| is_test_kind(&target.kind)) {
+        runnables.push(Runnable {
+            kind: RunnableKind::Test,
+            target: None,
+            range: None,
+        });
+    }
+
+    for target in targets
+        .iter()
+        .filter(|target| pos.map_or(true, |pos| target.range.contains_inclusive(pos)))
+    {
+        let mut push = |kind| {
+            runnables.push(Runnable {
+                kind,
+                target: Some(target.name.clone()),
+                range: Some(target.range),
+            })
+        };
+        push(RunnableKind::Build);
+        if is_test_kind(&target.kind) {
+            push(RunnableKind::Test);
+        } else if rule_name(&target.kind).ends_with("_binary") {
+            push(RunnableKind::Run);
+        }
+    }
+
+    Some(runnables)
+}
+
+fn is_test_kind(kind: &str) -> bool {
+    let name = rule_name(kind);
+    name.ends_with("_test") || name == "test_suite"
+}
+
+#[cfg(test)]
+mod tests {
+    use starpls_bazel::APIContext;
+    use starpls_common::Dialect;
+    use starpls_syntax::TextSize;
+
+    use crate::{AnalysisSnapshot, RunnableKind};
+
+    #[test]
+    fn test_runnables() {
+        let input = r#"
+cc_library(
+    name = "lib",
+)
+
+cc_binary(
+    name = "main",
+    deps = [":lib"],
+)
+
+native.cc_test(
+    name = "lib_test",
+)
+"#;
+        let (snap, file_id) =
+            AnalysisSnapshot::from_single_file(input, Dialect::Bazel, Some(APIContext::Build));
+        let summarize = |pos| {
+            snap.runnables(file_id, pos)
+                .unwrap()
+                .unwrap()
+                .into_iter()
+                .map(|runnable| (runnable.kind, runnable.target))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            summarize(None),
+            vec![
+                (RunnableKind::Build, None),
+                (RunnableKind::Test, None),
+                (RunnableKind::Build, Some("lib".to_string())),
+                (RunnableKind::Build, Some("main".to_string())),
+                (RunnableKind::Run, Some("main".to_string())),
+                (RunnableKind::Build, Some("lib_test".to_string())),
+                (RunnableKind::Test, Some("lib_test".to_string())),
+            ]
+        );
+
+        let pos = TextSize::from(input.find("deps").unwrap() as u32);
+        assert_eq!(
+            summarize(Some(pos)),
+            vec![
+                (RunnableKind::Build, None),
+                (RunnableKind::Test, None),
+                (RunnableKind::Build, Some("main".to_string())),
+                (RunnableKind::Run, Some("main".to_string())),
+            ]
+        );
+    }
+}
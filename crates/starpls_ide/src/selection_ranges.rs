@@ -0,0 +1,194 @@
+//! Computes the ranges that "expand selection" walks through from a position, innermost first:
+//! the token at the position, the expressions and statements containing it, the blocks of those
+//! statements, and finally the whole file.
+//!
+//! A few ranges are added that don't correspond to syntax nodes, since they're what users
+//! usually want to select: the contents of a string without its quotes, and the contents of
+//! brackets without the brackets, e.g. every argument of a call or every element of a list.
+
+use starpls_common::{parse, Db as _, FileId};
+use starpls_syntax::{SyntaxKind, SyntaxNode, SyntaxToken, TextRange, TextSize, T};
+
+use crate::{folding_ranges::code_range, util::pick_best_token, Database};
+
+pub(crate) fn selection_ranges(
+    db: &Database,
+    file_id: FileId,
+    offsets: &[TextSize],
+) -> Option<Vec<Vec<TextRange>>> {
+    let file = db.get_file(file_id)?;
+    let root = parse(db, file).syntax(db);
+    Some(
+        offsets
+            .iter()
+            .map(|offset| selection_range(&root, *offset))
+            .collect(),
+    )
+}
+
+fn selection_range(root: &SyntaxNode, offset: TextSize) -> Vec<TextRange> {
+    let mut ranges = Vec::new();
+    let token = pick_best_token(root.token_at_offset(offset), |kind| match kind {
+        T![ident]
+        | SyntaxKind::STRING
+        | SyntaxKind::BYTES
+        | SyntaxKind::INT
+        | SyntaxKind::FLOAT => 3,
+        SyntaxKind::COMMENT => 2,
+        kind if is_layout(kind) => 0,
+        _ => 1,
+    });
+    if let Some(token) = &token {
+        if !is_layout(token.kind()) {
+            if let Some(contents) = string_contents(token).filter(|range| !range.is_empty()) {
+                push(&mut ranges, contents);
+            }
+            push(&mut ranges, token.text_range());
+        }
+    }
+
+    let parent = token
+        .and_then(|token| token.parent())
+        .unwrap_or_else(|| root.clone());
+    for node in parent.ancestors() {
+        if let Some(inner) = bracket_contents(&node) {
+            push(&mut ranges, inner);
+        }
+        if let Some(range) = code_range(&node) {
+            push(&mut ranges, range);
+        }
+    }
+    push(&mut ranges, root.text_range());
+    ranges
+}
+
+/// Adds a range to the end of the list if it strictly contains the last range added, so that
+/// each step of the expansion selects more text.
+fn push(ranges: &mut Vec<TextRange>, range: TextRange) {
+    if ranges
+        .last()
+        .map_or(true, |last| range != *last && range.contains_range(*last))
+    {
+        ranges.push(range);
+    }
+}
+
+fn is_layout(kind: SyntaxKind) -> bool {
+    matches!(
+        kind,
+        SyntaxKind::WHITESPACE | SyntaxKind::NEWLINE | SyntaxKind::INDENT | SyntaxKind::DEDENT
+    )
+}
+
+/// Returns the range of a string literal's contents, between its quotes.
+fn string_contents(token: &SyntaxToken) -> Option<TextRange> {
+    if !matches!(token.kind(), SyntaxKind::STRING | SyntaxKind::BYTES) {
+        return None;
+    }
+    let text = token.text();
+    let prefix = text.find(['"', '\''])?;
+    let quote = &text[prefix..];
+    let quote_len = if quote.starts_with("\"\"\"") || quote.starts_with("'''") {
+        3
+    } else {
+        1
+    };
+    let quote = &quote[..quote_len];
+    let start = prefix + quote_len;
+    if text.len() < start + quote_len || !text[start..].ends_with(quote) {
+        return None;
+    }
+    let end = text.len() - quote_len;
+    let offset = token.text_range().start();
+    Some(TextRange::new(
+        offset + TextSize::from(start as u32),
+        offset + TextSize::from(end as u32),
+    ))
+}
+
+/// Returns the range between the brackets of a bracketed node, e.g. the arguments of a call
+/// without its parentheses, or `None` if the node isn't bracketed or the brackets are empty.
+fn bracket_contents(node: &SyntaxNode) -> Option<TextRange> {
+    let elements = node
+        .children_with_tokens()
+        .filter(|element| !element.kind().is_trivia_token() && !is_layout(element.kind()))
+        .collect::<Vec<_>>();
+    let (open, contents, close) = match elements.as_slice() {
+        [open, contents @ .., close] if !contents.is_empty() => (open, contents, close),
+        _ => return None,
+    };
+    match (open.kind(), close.kind()) {
+        (T!['('], T![')']) | (T!['['], T![']']) | (T!['{'], T!['}']) => Some(TextRange::new(
+            contents[0].text_range().start(),
+            contents[contents.len() - 1].text_range().end(),
+        )),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use starpls_bazel::APIContext;
+    use starpls_common::Dialect;
+    use starpls_syntax::TextSize;
+
+    use crate::AnalysisSnapshot;
+
+    fn check(contents: &str, needle: &str, expected: &[&str]) {
+        let (snap, file_id) =
+            AnalysisSnapshot::from_single_file(contents, Dialect::Bazel, Some(APIContext::Bzl));
+        let offset = TextSize::from(contents.find(needle).unwrap() as u32);
+        let ranges = snap
+            .selection_ranges(file_id, vec![offset])
+            .unwrap()
+            .unwrap();
+        let actual = ranges[0]
+            .iter()
+            .map(|range| &contents[*range])
+            .collect::<Vec<_>>();
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_call_arguments() {
+        check(
+            "def f():\n    return g(\"a b\", key = [1, 2])\n",
+            "b\"",
+            &[
+                "a b",
+                "\"a b\"",
+                "\"a b\", key = [1, 2]",
+                "(\"a b\", key = [1, 2])",
+                "g(\"a b\", key = [1, 2])",
+                "return g(\"a b\", key = [1, 2])",
+                "def f():\n    return g(\"a b\", key = [1, 2])",
+                "def f():\n    return g(\"a b\", key = [1, 2])\n",
+            ],
+        );
+    }
+
+    #[test]
+    fn test_list_elements() {
+        check(
+            "x = [1, 2]\ny = 3\n",
+            "2]",
+            &[
+                "2",
+                "1, 2",
+                "[1, 2]",
+                "x = [1, 2]",
+                "x = [1, 2]\ny = 3",
+                "x = [1, 2]\ny = 3\n",
+            ],
+        );
+    }
+
+    #[test]
+    fn test_triple_quoted_string() {
+        check(
+            "x = '''doc'''\n",
+            "doc",
+            &["doc", "'''doc'''", "x = '''doc'''", "x = '''doc'''\n"],
+        );
+    }
+}
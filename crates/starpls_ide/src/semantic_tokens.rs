@@ -0,0 +1,89 @@
+//! Computes the semantic tokens of a file. Only string literals with a meaning beyond their text,
+//! e.g. labels and glob patterns, are classified, using the same classification that hover and
+//! completion use; everything else is left to the editor's syntax highlighting.
+
+use starpls_common::{parse, Db as _, FileId};
+use starpls_hir::{Semantics, StringKind};
+use starpls_syntax::{
+    ast::{self, AstToken},
+    TextRange,
+};
+
+use crate::Database;
+
+pub struct SemanticToken {
+    pub range: TextRange,
+    pub kind: StringKind,
+}
+
+pub(crate) fn semantic_tokens(db: &Database, file_id: FileId) -> Option<Vec<SemanticToken>> {
+    let file = db.get_file(file_id)?;
+    let sema = Semantics::new(db);
+    Some(
+        parse(db, file)
+            .syntax(db)
+            .descendants_with_tokens()
+            .filter_map(|element| element.into_token())
+            .filter_map(ast::String::cast)
+            .filter_map(|string| {
+                let kind = sema.classify_string(file, &string);
+                (kind != StringKind::Plain).then(|| SemanticToken {
+                    range: string.syntax().text_range(),
+                    kind,
+                })
+            })
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use starpls_bazel::APIContext;
+    use starpls_common::Dialect;
+    use starpls_hir::StringKind;
+
+    use crate::AnalysisSnapshot;
+
+    #[test]
+    fn test_string_kinds() {
+        let contents = r#"
+load("//foo:defs.bzl", "my_macro")
+
+cc_library(
+    name = "lib",
+    srcs = glob(["*.cc"], exclude = ["main.cc"]) + select({
+        "//conditions:default": ["extra.cc"],
+    }),
+    deps = [":dep", "//other:%s" % "lib"],
+    outs = "{}.o".format("lib"),
+    visibility = ["//visibility:public"],
+)
+
+exports_files(["data.txt"])
+"#;
+        let (snap, file_id) =
+            AnalysisSnapshot::from_single_file(contents, Dialect::Bazel, Some(APIContext::Build));
+        let actual = snap
+            .semantic_tokens(file_id)
+            .unwrap()
+            .unwrap()
+            .into_iter()
+            .map(|token| (&contents[token.range], token.kind))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            actual,
+            vec![
+                ("\"//foo:defs.bzl\"", StringKind::Label),
+                ("\"*.cc\"", StringKind::GlobPattern),
+                ("\"main.cc\"", StringKind::GlobPattern),
+                ("\"//conditions:default\"", StringKind::Label),
+                ("\"extra.cc\"", StringKind::FilePath),
+                ("\":dep\"", StringKind::Label),
+                ("\"//other:%s\"", StringKind::FormatString),
+                ("\"{}.o\"", StringKind::FormatString),
+                ("\"//visibility:public\"", StringKind::Visibility),
+                ("\"data.txt\"", StringKind::FilePath),
+            ]
+        );
+    }
+}
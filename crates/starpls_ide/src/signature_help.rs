@@ -162,3 +162,113 @@ pub(crate) fn signature_help(
         }],
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use starpls_bazel::APIContext;
+    use starpls_common::Dialect;
+    use starpls_test_util::parse_fixture;
+
+    use crate::{AnalysisSnapshot, FilePosition, SignatureInfo};
+
+    fn signature_at(fixture: &str) -> SignatureInfo {
+        let (contents, pos, _) = parse_fixture(fixture);
+        let (snap, file_id) =
+            AnalysisSnapshot::from_single_file(&contents, Dialect::Bazel, Some(APIContext::Bzl));
+        let mut help = snap
+            .signature_help(FilePosition { file_id, pos })
+            .unwrap()
+            .expect("expected signature help");
+        assert_eq!(help.signatures.len(), 1);
+        help.signatures.remove(0)
+    }
+
+    fn parameter_labels(signature: &SignatureInfo) -> Vec<&str> {
+        signature
+            .parameters
+            .iter()
+            .flatten()
+            .map(|param| param.label.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_active_parameter_follows_commas() {
+        let fixture = |call: &str| {
+            format!(
+                r#"
+def f(a, b, c):
+    pass
+
+{}
+"#,
+                call
+            )
+        };
+
+        let signature = signature_at(&fixture("f($0)"));
+        assert!(signature.label.starts_with("def f(a, b, c)"));
+        assert_eq!(parameter_labels(&signature), vec!["a", "b", "c"]);
+        assert_eq!(signature.active_parameter, Some(0));
+
+        assert_eq!(signature_at(&fixture("f(1$0)")).active_parameter, Some(0));
+        assert_eq!(signature_at(&fixture("f(1, $0)")).active_parameter, Some(1));
+        assert_eq!(
+            signature_at(&fixture("f(1, 2$0)")).active_parameter,
+            Some(1)
+        );
+        assert_eq!(
+            signature_at(&fixture("f(1, 2, 3$0)")).active_parameter,
+            Some(2)
+        );
+    }
+
+    #[test]
+    fn test_active_parameter_for_keyword_argument() {
+        let signature = signature_at(
+            r#"
+def f(a, b, c):
+    pass
+
+f(1, c = 3$0)
+"#,
+        );
+        assert_eq!(signature.active_parameter, Some(2));
+    }
+
+    #[test]
+    fn test_parameter_docs() {
+        let signature = signature_at(
+            r#"
+def f(name, deps = []):
+    """Does something.
+
+    Args:
+        name: The name of the target.
+        deps: The dependencies.
+    """
+    pass
+
+f($0)
+"#,
+        );
+        assert_eq!(
+            signature
+                .documentation
+                .as_deref()
+                .map(str::lines)
+                .and_then(|mut lines| lines.next()),
+            Some("Does something.")
+        );
+        let docs = signature
+            .parameters
+            .iter()
+            .flatten()
+            .map(|param| param.documentation.as_deref().map(str::trim))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            docs,
+            vec![Some("The name of the target."), Some("The dependencies.")]
+        );
+    }
+}
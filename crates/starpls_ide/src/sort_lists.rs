@@ -0,0 +1,258 @@
+//! Sorts the string lists passed as sortable attributes in `BUILD` files, e.g. `deps` and `srcs`,
+//! in the same order that Buildifier and the formatter use. Which attributes are sortable, and
+//! how labels are ordered, come from the workspace's Buildifier tables if they're set.
+//!
+//! Like Buildifier, each run of string literals is sorted on its own, so that the elements
+//! between variables or `select` expressions keep their places. Lists with comments, or marked
+//! `# do not sort`, are left to the formatter, which keeps comments with their elements.
+
+use starpls_bazel::APIContext;
+use starpls_common::{parse, File};
+use starpls_syntax::{
+    ast::{self, AstNode},
+    SyntaxKind, TextRange, TextSize,
+};
+
+use crate::{
+    module_deps::callee_name, BuildifierTables, CodeAction, CodeActionKind, Database, TextEdit,
+};
+
+pub(crate) fn sort_list_actions(db: &Database, file: File, range: TextRange) -> Vec<CodeAction> {
+    sortable_args(db, file, range)
+        .into_iter()
+        .filter_map(|arg| {
+            let edits = arg.list_edits.into_iter().flatten().collect::<Vec<_>>();
+            (!edits.is_empty()).then(|| CodeAction {
+                title: format!("Sort `{}` of `{}`", arg.name, arg.rule),
+                kind: CodeActionKind::RefactorRewrite,
+                edits,
+                file_edits: Vec::new(),
+                command: None,
+                is_preferred: false,
+            })
+        })
+        .collect()
+}
+
+/// Returns the edits that sort every sortable list in the file for the "Fix all" action, grouped
+/// by list. Each group must be applied as a whole, since applying only some of a list's edits
+/// would duplicate some of its elements.
+pub(crate) fn sort_list_edits(db: &Database, file: File) -> Vec<Vec<TextEdit>> {
+    let full_range = TextRange::up_to(TextSize::of(file.contents(db).as_str()));
+    sortable_args(db, file, full_range)
+        .into_iter()
+        .flat_map(|arg| arg.list_edits)
+        .filter(|edits| !edits.is_empty())
+        .collect()
+}
+
+/// A sortable attribute passed to a rule, along with the edits that sort each of its lists.
+struct SortableArg {
+    name: String,
+    rule: String,
+    list_edits: Vec<Vec<TextEdit>>,
+}
+
+fn sortable_args(db: &Database, file: File, range: TextRange) -> Vec<SortableArg> {
+    if file.api_context(db) != Some(APIContext::Build) {
+        return Vec::new();
+    }
+    let default_tables;
+    let tables = match db.buildifier_tables.as_deref() {
+        Some(tables) => tables,
+        None => {
+            default_tables = BuildifierTables::default();
+            &default_tables
+        }
+    };
+
+    let root = parse(db, file).syntax(db);
+    let mut args = Vec::new();
+    for arg in root
+        .descendants()
+        .filter(|node| node.text_range().intersect(range).is_some())
+        .filter_map(ast::KeywordArgument::cast)
+    {
+        let (name, expr) = match (arg.name().and_then(|name| name.name()), arg.expr()) {
+            (Some(name), Some(expr)) => (name.text().to_string(), expr),
+            _ => continue,
+        };
+        let rule = arg
+            .syntax()
+            .parent()
+            .and_then(|args| args.parent())
+            .and_then(ast::CallExpr::cast)
+            .and_then(|call_expr| callee_name(&call_expr));
+        let rule = match rule {
+            Some(rule) if tables.is_sortable_list_arg(&rule, &name) => rule,
+            _ => continue,
+        };
+        args.push(SortableArg {
+            name,
+            rule,
+            list_edits: lists(expr)
+                .iter()
+                .map(|list| sort_edits(tables, list))
+                .collect(),
+        });
+    }
+    args
+}
+
+/// Returns the list literals that make up an attribute's value, e.g. `[":a"]` and `[":b"]` in
+/// `[":a"] + [":b"] + select(...)`.
+fn lists(expr: ast::Expression) -> Vec<ast::ListExpr> {
+    match expr {
+        ast::Expression::List(list) => vec![list],
+        ast::Expression::Binary(binary)
+            if matches!(
+                binary.binary_op_info(),
+                Some((_, ast::BinaryOp::Arith(ast::ArithOp::Add)))
+            ) =>
+        {
+            binary
+                .lhs()
+                .into_iter()
+                .chain(binary.rhs())
+                .flat_map(lists)
+                .collect()
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// Returns the edits that sort each run of string literals in a list, or no edits if the list is
+/// already sorted or has comments.
+fn sort_edits(tables: &BuildifierTables, list: &ast::ListExpr) -> Vec<TextEdit> {
+    let has_comments = list
+        .syntax()
+        .descendants_with_tokens()
+        .any(|element| element.kind() == SyntaxKind::COMMENT);
+    if has_comments {
+        return Vec::new();
+    }
+
+    let mut edits = Vec::new();
+    let mut run: Vec<(String, TextRange, String)> = Vec::new();
+    let mut elements = list.elements().map(Some).collect::<Vec<_>>();
+    elements.push(None);
+    for element in elements {
+        let value = element.as_ref().and_then(|element| match element {
+            ast::Expression::Literal(lit) => match lit.kind() {
+                ast::LiteralKind::String(s) => Some(s.value()?.to_string()),
+                _ => None,
+            },
+            _ => None,
+        });
+        match (element, value) {
+            (Some(element), Some(value)) => {
+                let syntax = element.syntax();
+                run.push((value, syntax.text_range(), syntax.text().to_string()));
+            }
+            _ => {
+                let mut sorted = run.clone();
+                sorted.sort_by(|(a, _, _), (b, _, _)| tables.compare_strings(a, b));
+                for ((_, range, text), (_, _, new_text)) in run.drain(..).zip(sorted) {
+                    if text != new_text {
+                        edits.push(TextEdit { range, new_text });
+                    }
+                }
+            }
+        }
+    }
+    edits
+}
+
+#[cfg(test)]
+mod tests {
+    use starpls_common::{Dialect, FileId, FileRange};
+    use starpls_syntax::{TextRange, TextSize};
+
+    use crate::{Analysis, BuildifierTables, CodeAction, TextEdit};
+
+    fn actions(contents: &str, needle: &str, tables: Option<BuildifierTables>) -> Vec<CodeAction> {
+        let mut analysis = Analysis::from_files(&[("BUILD", contents)], Dialect::Bazel);
+        analysis.set_buildifier_tables(tables);
+        let start = contents.find(needle).unwrap() as u32;
+        analysis
+            .snapshot()
+            .code_actions(FileRange {
+                file_id: FileId(0),
+                range: TextRange::empty(TextSize::from(start)),
+            })
+            .unwrap()
+            .unwrap()
+            .into_iter()
+            .filter(|action| action.title.starts_with("Sort `"))
+            .collect()
+    }
+
+    fn apply(text: &str, edits: &[TextEdit]) -> String {
+        let mut text = text.to_string();
+        for edit in edits.iter().rev() {
+            text.replace_range(std::ops::Range::<usize>::from(edit.range), &edit.new_text);
+        }
+        text
+    }
+
+    #[test]
+    fn test_sort_deps() {
+        let contents = r#"cc_library(
+    name = "lib",
+    deps = [
+        "@foo//:foo",
+        ":b",
+        "//a:a",
+        ":a",
+    ] + select({
+        "//conditions:default": [],
+    }),
+)
+"#;
+        let actions = actions(contents, "\":b\"", None);
+        assert_eq!(actions.len(), 1);
+        assert_eq!(actions[0].title, "Sort `deps` of `cc_library`");
+        assert_eq!(
+            apply(contents, &actions[0].edits),
+            r#"cc_library(
+    name = "lib",
+    deps = [
+        ":a",
+        ":b",
+        "//a:a",
+        "@foo//:foo",
+    ] + select({
+        "//conditions:default": [],
+    }),
+)
+"#
+        );
+    }
+
+    #[test]
+    fn test_runs_and_sorted_lists() {
+        let contents = "filegroup(\n    name = \"a\",\n    srcs = [\"b\", \"a\", VAR, \"d\", \"c\"],\n    tags = [\"x\", \"y\"],\n)\n";
+        let srcs_actions = actions(contents, "VAR", None);
+        assert_eq!(
+            apply(contents, &srcs_actions[0].edits),
+            "filegroup(\n    name = \"a\",\n    srcs = [\"a\", \"b\", VAR, \"c\", \"d\"],\n    tags = [\"x\", \"y\"],\n)\n"
+        );
+        assert!(actions(contents, "\"x\"", None).is_empty());
+    }
+
+    #[test]
+    fn test_custom_tables() {
+        let contents = "cc_library(\n    name = \"a\",\n    copts = [\"-b\", \"-a\"],\n    srcs = [\"b\", \"a\"],\n)\n";
+        assert!(actions(contents, "\"-b\"", None).is_empty());
+
+        let tables = BuildifierTables::parse(r#"{"IsSortableListArg": {"copts": true}}"#).unwrap();
+        assert_eq!(actions(contents, "\"-b\"", Some(tables.clone())).len(), 1);
+        assert!(actions(contents, "\"b\"", Some(tables)).is_empty());
+    }
+
+    #[test]
+    fn test_comments() {
+        let contents = "filegroup(\n    name = \"a\",\n    srcs = [\n        \"b\",  # Second.\n        \"a\",\n    ],\n)\n";
+        assert!(actions(contents, "\"b\"", None).is_empty());
+    }
+}
@@ -0,0 +1,452 @@
+//! Spell-checking for docstrings, `doc = "..."` attributes, and comments.
+//!
+//! Words are checked against a bundled dictionary of English and Bazel-specific terms, as well as
+//! any additional words provided through [`DiagnosticsOptions`](crate::DiagnosticsOptions). Chunks
+//! of text that look like code (identifiers, paths, labels, etc.) are skipped entirely, since
+//! flagging them would be more noise than help.
+
+use std::{mem, sync::OnceLock};
+
+use rustc_hash::FxHashSet;
+use starpls_common::{parse, Diagnostic, DiagnosticCode, File, FileRange, Severity};
+use starpls_syntax::{
+    ast::{self, AstNode, AstToken},
+    SyntaxKind, TextRange, TextSize,
+};
+
+use crate::Database;
+
+const MAX_EDIT_DISTANCE: usize = 2;
+
+const MAX_SUGGESTIONS: usize = 3;
+
+const MIN_WORD_LEN: usize = 3;
+
+/// Suffixes that are stripped from a word when it isn't found in the dictionary as-is, along with
+/// the possible endings of the word's stem.
+const SUFFIXES: &[(&str, &[&str])] = &[
+    ("ies", &["y"]),
+    ("es", &["", "e"]),
+    ("s", &[""]),
+    ("ed", &["", "e"]),
+    ("ing", &["", "e"]),
+    ("ly", &[""]),
+    ("ers", &["", "e"]),
+    ("er", &["", "e"]),
+];
+
+fn bundled_words() -> &'static FxHashSet<&'static str> {
+    static WORDS: OnceLock<FxHashSet<&'static str>> = OnceLock::new();
+    WORDS.get_or_init(|| {
+        include_str!("../data/dictionary.txt")
+            .lines()
+            .map(str::trim)
+            .filter(|word| !word.is_empty())
+            .collect()
+    })
+}
+
+/// A word that wasn't found in the dictionary.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Misspelling {
+    pub(crate) word: String,
+    pub(crate) range: TextRange,
+}
+
+struct Dictionary {
+    bundled_words: &'static FxHashSet<&'static str>,
+    extra_words: FxHashSet<String>,
+}
+
+impl Dictionary {
+    fn new(extra_words: &[String]) -> Self {
+        Self {
+            bundled_words: bundled_words(),
+            extra_words: extra_words
+                .iter()
+                .map(|word| word.trim().to_lowercase())
+                .collect(),
+        }
+    }
+
+    fn contains_exact(&self, word: &str) -> bool {
+        self.bundled_words.contains(word) || self.extra_words.contains(word)
+    }
+
+    fn contains(&self, word: &str) -> bool {
+        let word = word.to_lowercase();
+        if self.contains_exact(&word) {
+            return true;
+        }
+
+        SUFFIXES.iter().any(|(suffix, endings)| {
+            let stem = match word.strip_suffix(suffix) {
+                Some(stem) if !stem.is_empty() => stem,
+                _ => return false,
+            };
+
+            // Handle doubled consonants, e.g. "mapped" -> "map".
+            if has_doubled_last_char(stem) && self.contains_exact(&stem[..stem.len() - 1]) {
+                return true;
+            }
+
+            endings
+                .iter()
+                .any(|ending| self.contains_exact(&format!("{}{}", stem, ending)))
+        })
+    }
+
+    fn words(&self) -> impl Iterator<Item = &str> {
+        self.bundled_words
+            .iter()
+            .copied()
+            .chain(self.extra_words.iter().map(String::as_str))
+    }
+}
+
+fn has_doubled_last_char(s: &str) -> bool {
+    let bytes = s.as_bytes();
+    bytes.len() >= 2 && bytes[bytes.len() - 1] == bytes[bytes.len() - 2]
+}
+
+pub(crate) fn spellcheck_diagnostics(db: &Database, file: File) -> Vec<Diagnostic> {
+    misspellings(db, file)
+        .into_iter()
+        .map(|misspelling| Diagnostic {
+            code: DiagnosticCode::Misspelling,
+            message: format!("Unknown word \"{}\"", misspelling.word),
+            severity: Severity::Hint,
+            range: FileRange {
+                file_id: file.id(db),
+                range: misspelling.range,
+            },
+        })
+        .collect()
+}
+
+pub(crate) fn misspellings(db: &Database, file: File) -> Vec<Misspelling> {
+    let dictionary = Dictionary::new(&db.diagnostics_options.spellcheck_words);
+    let tree = parse(db, file).tree(db);
+    let mut misspellings = Vec::new();
+    let mut check = |text: &str, offset: TextSize, is_markdown: bool| {
+        for (word, range) in words(text, offset, is_markdown) {
+            if !dictionary.contains(word) {
+                misspellings.push(Misspelling {
+                    word: word.to_string(),
+                    range,
+                });
+            }
+        }
+    };
+
+    // Check the module docstring, as well as any function docstrings.
+    for string in module_docstring(&tree).into_iter().chain(
+        tree.syntax()
+            .descendants()
+            .filter_map(ast::DefStmt::cast)
+            .filter_map(|def_stmt| {
+                def_stmt
+                    .suite()
+                    .and_then(|suite| suite.statements().next())
+                    .and_then(string_from_statement)
+            }),
+    ) {
+        check_string(&string, &mut check);
+    }
+
+    // Check `doc = "..."` arguments, e.g. for rules, providers, and attributes.
+    for arg in tree
+        .syntax()
+        .descendants()
+        .filter_map(ast::KeywordArgument::cast)
+        .filter(|arg| {
+            arg.name()
+                .and_then(|name| name.name())
+                .map_or(false, |name| name.text() == "doc")
+        })
+    {
+        for lit in arg.expr().into_iter().flat_map(|expr| {
+            expr.syntax()
+                .descendants()
+                .filter_map(ast::LiteralExpr::cast)
+        }) {
+            if let ast::LiteralKind::String(string) = lit.kind() {
+                check_string(&string, &mut check);
+            }
+        }
+    }
+
+    // Finally, check comments.
+    for token in tree
+        .syntax()
+        .descendants_with_tokens()
+        .filter_map(|el| el.into_token())
+        .filter(|token| token.kind() == SyntaxKind::COMMENT)
+    {
+        let text = token.text();
+        let content = text.trim_start_matches('#');
+        let offset = token.text_range().start() + TextSize::of(&text[..text.len() - content.len()]);
+        check(content, offset, false);
+    }
+
+    misspellings.sort_by_key(|misspelling| misspelling.range.start());
+    misspellings.dedup();
+    misspellings
+}
+
+/// Returns up to [`MAX_SUGGESTIONS`] dictionary words that are close to the given word.
+pub(crate) fn suggestions(db: &Database, word: &str) -> Vec<String> {
+    let dictionary = Dictionary::new(&db.diagnostics_options.spellcheck_words);
+    let lowercase = word.to_lowercase();
+    let len = lowercase.chars().count();
+    let mut candidates = dictionary
+        .words()
+        .filter(|candidate| {
+            let candidate_len = candidate.chars().count();
+            candidate_len >= MIN_WORD_LEN && candidate_len.abs_diff(len) <= MAX_EDIT_DISTANCE
+        })
+        .filter_map(|candidate| {
+            let distance = edit_distance(&lowercase, candidate);
+            (distance <= MAX_EDIT_DISTANCE).then_some((distance, candidate))
+        })
+        .collect::<Vec<_>>();
+    candidates.sort();
+    candidates.dedup();
+
+    let is_capitalized = word.chars().next().map_or(false, |c| c.is_uppercase());
+    candidates
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(_, candidate)| {
+            if is_capitalized {
+                let mut chars = candidate.chars();
+                chars
+                    .next()
+                    .map(|first| first.to_uppercase().chain(chars).collect())
+                    .unwrap_or_default()
+            } else {
+                candidate.to_string()
+            }
+        })
+        .collect()
+}
+
+fn module_docstring(module: &ast::Module) -> Option<ast::String> {
+    module.statements().next().and_then(string_from_statement)
+}
+
+fn string_from_statement(stmt: ast::Statement) -> Option<ast::String> {
+    match stmt {
+        ast::Statement::Expr(ast::Expression::Literal(lit)) => match lit.kind() {
+            ast::LiteralKind::String(string) => Some(string),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn check_string(string: &ast::String, check: &mut impl FnMut(&str, TextSize, bool)) {
+    // Strip the string's prefix and quotes, so that they don't get attached to the first and
+    // last words.
+    let text = string.text();
+    let rest = text.trim_start_matches(|c: char| c.is_ascii_alphabetic());
+    let num_quotes = if rest.starts_with("\"\"\"") || rest.starts_with("'''") {
+        3
+    } else {
+        1
+    };
+    let num_quotes = num_quotes.min(rest.len());
+    let content = &rest[num_quotes..];
+    let content = content.strip_suffix(&rest[..num_quotes]).unwrap_or(content);
+    let offset = string.syntax().text_range().start()
+        + TextSize::from((text.len() - rest.len() + num_quotes) as u32);
+    check(content, offset, true);
+}
+
+/// Splits the given text into words that should be spell-checked, along with their ranges.
+/// `offset` is the offset of the start of the text within the file. If `is_markdown` is set,
+/// fenced code blocks are skipped.
+fn words(text: &str, offset: TextSize, is_markdown: bool) -> Vec<(&str, TextRange)> {
+    let mut words = Vec::new();
+    let mut is_in_code_block = false;
+    let mut line_start = 0;
+    for line in text.split_inclusive('\n') {
+        let start = line_start;
+        line_start += line.len();
+        if is_markdown && line.trim_start().starts_with("```") {
+            is_in_code_block = !is_in_code_block;
+            continue;
+        }
+        if is_in_code_block {
+            continue;
+        }
+
+        let mut chunk_start = None;
+        for (i, c) in line.char_indices().chain([(line.len(), ' ')]) {
+            match (c.is_whitespace(), chunk_start) {
+                (false, None) => chunk_start = Some(i),
+                (true, Some(chunk_start_inner)) => {
+                    chunk_start = None;
+                    let chunk = &line[chunk_start_inner..i];
+                    if let Some((word, word_offset)) = word_from_chunk(chunk) {
+                        let word_start = offset
+                            + TextSize::from((start + chunk_start_inner + word_offset) as u32);
+                        words.push((word, TextRange::at(word_start, TextSize::of(word))));
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    words
+}
+
+/// Extracts a word from a whitespace-delimited chunk of text, returning the word and its offset
+/// within the chunk. Returns `None` for chunks that look like code rather than prose.
+fn word_from_chunk(chunk: &str) -> Option<(&str, usize)> {
+    // Skip inline code, labels, paths, and command-line flags.
+    if chunk.starts_with("--") || chunk.contains(['`', '@', '/', '\\', '=']) {
+        return None;
+    }
+
+    let trimmed_start = chunk.trim_start_matches(|c: char| !c.is_alphanumeric());
+    let word = trimmed_start.trim_end_matches(|c: char| !c.is_alphanumeric());
+    let word_offset = chunk.len() - trimmed_start.len();
+    let without_possessive = word.strip_suffix("'s").unwrap_or(word);
+
+    // Skip anything else containing characters that don't usually appear in prose, e.g.
+    // identifiers and dotted names.
+    if without_possessive.len() < MIN_WORD_LEN
+        || !without_possessive.chars().all(|c| c.is_ascii_alphabetic())
+    {
+        return None;
+    }
+
+    // Skip acronyms and camel-cased identifiers.
+    if without_possessive.chars().skip(1).any(|c| c.is_uppercase()) {
+        return None;
+    }
+
+    Some((without_possessive, word_offset))
+}
+
+/// Computes the optimal string alignment distance between two strings, i.e. the Levenshtein
+/// distance with the addition of transpositions of adjacent characters.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a = a.chars().collect::<Vec<_>>();
+    let b = b.chars().collect::<Vec<_>>();
+    let mut prev_prev = vec![0; b.len() + 1];
+    let mut prev = (0..=b.len()).collect::<Vec<_>>();
+    let mut curr = vec![0; b.len() + 1];
+
+    for i in 1..=a.len() {
+        curr[0] = i;
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            curr[j] = (prev[j] + 1).min(curr[j - 1] + 1).min(prev[j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                curr[j] = curr[j].min(prev_prev[j - 2] + 1);
+            }
+        }
+        mem::swap(&mut prev_prev, &mut prev);
+        mem::swap(&mut prev, &mut curr);
+    }
+
+    prev[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use starpls_common::{Dialect, FileId, FileRange, Severity};
+    use starpls_syntax::{TextRange, TextSize};
+
+    use crate::{Analysis, AnalysisSnapshot, CodeActionCommand, DiagnosticsOptions};
+
+    fn snapshot(input: &str, spellcheck_words: &[&str]) -> (AnalysisSnapshot, FileId) {
+        let mut analysis = Analysis::from_files(&[("main.star", input)], Dialect::Standard);
+        analysis.set_diagnostics_options(DiagnosticsOptions {
+            enable_spellcheck: true,
+            spellcheck_words: spellcheck_words
+                .iter()
+                .map(|word| word.to_string())
+                .collect(),
+            ..Default::default()
+        });
+        (analysis.snapshot(), FileId(0))
+    }
+
+    fn check_misspellings(input: &str, spellcheck_words: &[&str], expected: &[&str]) {
+        let (snap, file_id) = snapshot(input, spellcheck_words);
+        let actual = snap
+            .diagnostics(file_id)
+            .unwrap()
+            .into_iter()
+            .filter(|diagnostic| matches!(diagnostic.severity, Severity::Hint))
+            .map(|diagnostic| &input[diagnostic.range.range])
+            .collect::<Vec<_>>();
+        assert_eq!(actual, expected);
+    }
+
+    const INPUT: &str = r#""""Helpers for bulding containers."""
+
+# Teh default value should not be chnaged.
+# See //foo:bar and `some_identifier` for details.
+VALUE = 1
+
+def foo(name):
+    """Creates a targett.
+
+    ```
+    notaword = 1
+    ```
+    """
+    pass
+
+foo(doc = "Produces an archve.", name = "mispeled")
+"#;
+
+    #[test]
+    fn test_misspellings() {
+        check_misspellings(
+            INPUT,
+            &[],
+            &["bulding", "Teh", "chnaged", "targett", "archve"],
+        );
+    }
+
+    #[test]
+    fn test_workspace_words() {
+        check_misspellings(
+            INPUT,
+            &["Bulding", "targett"],
+            &["Teh", "chnaged", "archve"],
+        );
+    }
+
+    #[test]
+    fn test_code_actions() {
+        let (snap, file_id) = snapshot(INPUT, &[]);
+        let start = TextSize::from(INPUT.find("Teh").unwrap() as u32);
+        let actions = snap
+            .code_actions(FileRange {
+                file_id,
+                range: TextRange::empty(start),
+            })
+            .unwrap()
+            .unwrap();
+
+        let first = actions.first().unwrap();
+        assert_eq!(first.title, "Change to \"The\"");
+        assert!(first.is_preferred);
+        assert_eq!(
+            first.edits[0].range,
+            TextRange::at(start, TextSize::of("Teh"))
+        );
+
+        let last = actions.last().unwrap();
+        assert_eq!(
+            last.command,
+            Some(CodeActionCommand::AddWordToDictionary("teh".to_string()))
+        );
+    }
+}
@@ -0,0 +1,155 @@
+use starpls_common::{parse, Db as _, FileId};
+use starpls_hir::{Name, ScopeDef, Semantics};
+use starpls_syntax::{
+    ast::{self, AstNode},
+    TextRange,
+};
+
+use crate::Database;
+
+/// The uses, within a single file, of a symbol loaded from another file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SymbolUsage {
+    /// The name of the symbol, as exported by the loaded file.
+    pub name: String,
+    /// The range of the `load` item that imports the symbol.
+    pub load_range: TextRange,
+    /// The ranges of the references to the symbol, not including the `load` item itself.
+    pub reference_ranges: Vec<TextRange>,
+}
+
+/// Finds the uses in the given file of the symbols with the given names that are loaded from
+/// `loaded_file_id`, following any aliases introduced by the `load` statements.
+pub(crate) fn symbol_usages(
+    db: &Database,
+    file_id: FileId,
+    loaded_file_id: FileId,
+    names: &[String],
+) -> Option<Vec<SymbolUsage>> {
+    let file = db.get_file(file_id)?;
+    let sema = Semantics::new(db);
+    let root = parse(db, file).syntax(db);
+    let mut usages = Vec::new();
+
+    for load_stmt in root.children().filter_map(ast::LoadStmt::cast) {
+        if sema
+            .resolve_load_stmt(file, &load_stmt)
+            .map(|loaded_file| loaded_file.id(db))
+            != Some(loaded_file_id)
+        {
+            continue;
+        }
+
+        for load_item in load_stmt.items() {
+            let name = match sema.resolve_load_item(file, &load_item) {
+                Some(item) => item.name(db),
+                None => continue,
+            };
+            if !names.iter().any(|candidate| candidate == name.as_str()) {
+                continue;
+            }
+            let local_name = match &load_item {
+                ast::LoadItem::Direct(_) => name.clone(),
+                ast::LoadItem::Aliased(item) => match item.alias() {
+                    Some(alias) => Name::from_ast_node(alias),
+                    None => continue,
+                },
+            };
+
+            let load_range = load_item.syntax().text_range();
+            let reference_ranges = root
+                .descendants()
+                .filter_map(ast::NameRef::cast)
+                .filter(|name_ref| Name::from_ast_node(name_ref.clone()) == local_name)
+                .filter(|name_ref| {
+                    ast::Expression::cast(name_ref.syntax().clone())
+                        .and_then(|expr| sema.scope_for_expr(file, &expr))
+                        .and_then(|scope| scope.resolve_name(&local_name))
+                        .map_or(false, |defs| {
+                            defs.iter().any(|def| {
+                                matches!(def, ScopeDef::LoadItem(_))
+                                    && def.syntax_node_ptr(db, file).map(|ptr| ptr.text_range())
+                                        == Some(load_range)
+                            })
+                        })
+                })
+                .map(|name_ref| name_ref.syntax().text_range())
+                .collect();
+
+            usages.push(SymbolUsage {
+                name: name.to_string(),
+                load_range,
+                reference_ranges,
+            });
+        }
+    }
+
+    Some(usages)
+}
+
+#[cfg(test)]
+mod tests {
+    use starpls_common::{Dialect, FileId};
+
+    use crate::AnalysisSnapshot;
+
+    #[test]
+    fn test_symbol_usages() {
+        let files = [
+            (
+                "defs.star",
+                "def old_rule():\n    pass\n\ndef other():\n    pass\n",
+            ),
+            (
+                "user.star",
+                r#"load("defs.star", "old_rule", renamed = "other")
+
+old_rule()
+renamed()
+
+def f(old_rule):
+    return old_rule
+"#,
+            ),
+        ];
+        let usages = AnalysisSnapshot::from_files(&files, Dialect::Standard)
+            .symbol_usages(
+                FileId(1),
+                FileId(0),
+                &["old_rule".to_string(), "other".to_string()],
+            )
+            .unwrap()
+            .unwrap();
+
+        let contents = files[1].1;
+        let summary = usages
+            .iter()
+            .map(|usage| {
+                (
+                    usage.name.as_str(),
+                    &contents[usage.load_range],
+                    usage
+                        .reference_ranges
+                        .iter()
+                        .map(|range| usize::from(range.start()))
+                        .collect::<Vec<_>>(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            vec![
+                (
+                    "old_rule",
+                    "\"old_rule\"",
+                    vec![contents.find("old_rule()").unwrap()]
+                ),
+                (
+                    "other",
+                    "renamed = \"other\"",
+                    vec![contents.find("renamed()").unwrap()]
+                ),
+            ]
+        );
+    }
+}
@@ -0,0 +1,120 @@
+//! An index of the targets declared across a set of `BUILD` files, keyed by rule kind.
+
+use rustc_hash::FxHashMap;
+use starpls_common::{FileId, Symbol};
+
+use crate::{
+    targets::{self, rule_name},
+    Database, Target,
+};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IndexedTarget {
+    pub file_id: FileId,
+    pub target: Target,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct TargetIndex {
+    by_kind: FxHashMap<Symbol, Vec<IndexedTarget>>,
+}
+
+impl TargetIndex {
+    /// Returns the targets declared with the given rule or macro. Module prefixes are ignored, so
+    /// e.g. `cc_library` matches both `cc_library(...)` and `native.cc_library(...)`.
+    pub fn targets_of_kind(&self, kind: &str) -> &[IndexedTarget] {
+        self.by_kind
+            .get(&Symbol::new(rule_name(kind)))
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// Returns each indexed kind along with the number of targets declared with it, sorted by
+    /// kind.
+    pub fn kinds(&self) -> Vec<(&str, usize)> {
+        let mut kinds = self
+            .by_kind
+            .iter()
+            .map(|(kind, targets)| (kind.as_str(), targets.len()))
+            .collect::<Vec<_>>();
+        kinds.sort();
+        kinds
+    }
+
+    /// Returns all indexed targets, in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = &IndexedTarget> {
+        self.by_kind.values().flatten()
+    }
+}
+
+pub(crate) fn target_index(db: &Database, file_ids: &[FileId]) -> TargetIndex {
+    let mut index = TargetIndex::default();
+    for &file_id in file_ids {
+        for target in targets::targets(db, file_id).unwrap_or_default() {
+            index
+                .by_kind
+                .entry(Symbol::new(rule_name(&target.kind)))
+                .or_default()
+                .push(IndexedTarget { file_id, target });
+        }
+    }
+    index
+}
+
+#[cfg(test)]
+mod tests {
+    use starpls_common::{Dialect, FileId};
+
+    use crate::AnalysisSnapshot;
+
+    #[test]
+    fn test_target_index() {
+        let snap = AnalysisSnapshot::from_files(
+            &[
+                (
+                    "a/BUILD",
+                    r#"
+java_library(name = "a", javacopts = ["-Xlint"])
+native.java_library(name = "b")
+"#,
+                ),
+                (
+                    "c/BUILD",
+                    r#"
+java_library(name = "c")
+go_binary(name = "d")
+"#,
+                ),
+            ],
+            Dialect::Bazel,
+        );
+
+        let index = snap.target_index(&[FileId(0), FileId(1)]).unwrap();
+        assert_eq!(index.kinds(), vec![("go_binary", 1), ("java_library", 3)]);
+
+        let mut names = index
+            .targets_of_kind("native.java_library")
+            .iter()
+            .map(|indexed| (indexed.file_id, indexed.target.name.as_str()))
+            .collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(
+            names,
+            vec![(FileId(0), "a"), (FileId(0), "b"), (FileId(1), "c")]
+        );
+        assert!(index.targets_of_kind("cc_library").is_empty());
+    }
+
+    #[test]
+    fn test_buck2_target_index() {
+        let snap = AnalysisSnapshot::from_files(
+            &[(
+                "BUCK",
+                "cxx_library(name = \"a\")\nnative.cxx_binary(name = \"b\")\n",
+            )],
+            Dialect::Buck2,
+        );
+        let index = snap.target_index(&[FileId(0)]).unwrap();
+        assert_eq!(index.kinds(), vec![("cxx_binary", 1), ("cxx_library", 1)]);
+    }
+}
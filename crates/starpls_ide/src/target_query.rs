@@ -0,0 +1,353 @@
+//! A small query language for finding targets in a [`TargetIndex`] without invoking Bazel.
+//!
+//! Queries are built from the following functions, where patterns may contain `*` wildcards:
+//!
+//! - `kind(pattern)` matches targets declared with a matching rule or macro.
+//! - `name(pattern)` matches targets with a matching name.
+//! - `attr(name)` matches targets that set the given attribute.
+//! - `attr(name, pattern)` matches targets whose attribute value matches the pattern. For list
+//!   values, it suffices for any element to match.
+//!
+//! These can be combined with the `and`, `or`, and `except` operators, which all have the same
+//! precedence and are left-associative as in `bazel query`, and grouped with parentheses. For
+//! example, `kind(cc_*) and attr(copts, "-fno-exceptions")`.
+
+use anyhow::bail;
+
+use crate::{targets::rule_name, IndexedTarget, Target, TargetAttributeValue, TargetIndex};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TargetQuery {
+    Kind(String),
+    Name(String),
+    Attr { name: String, value: Option<String> },
+    And(Box<TargetQuery>, Box<TargetQuery>),
+    Or(Box<TargetQuery>, Box<TargetQuery>),
+    Except(Box<TargetQuery>, Box<TargetQuery>),
+}
+
+impl TargetQuery {
+    pub fn parse(input: &str) -> anyhow::Result<TargetQuery> {
+        let mut parser = Parser {
+            tokens: tokenize(input)?,
+            pos: 0,
+        };
+        let query = parser.parse_expr()?;
+        match parser.peek() {
+            Some(token) => bail!("unexpected {}", token),
+            None => Ok(query),
+        }
+    }
+
+    pub fn matches(&self, target: &Target) -> bool {
+        match self {
+            TargetQuery::Kind(pattern) => matches_pattern(pattern, rule_name(&target.kind)),
+            TargetQuery::Name(pattern) => matches_pattern(pattern, &target.name),
+            TargetQuery::Attr { name, value } => target
+                .attributes
+                .iter()
+                .filter(|attr| attr.name == name.as_str())
+                .any(|attr| {
+                    let pattern = match value {
+                        Some(pattern) => pattern,
+                        None => return true,
+                    };
+                    match &attr.value {
+                        TargetAttributeValue::String(s) => matches_pattern(pattern, s),
+                        TargetAttributeValue::Other(s) => matches_pattern(pattern, s),
+                        TargetAttributeValue::StringList(values) => {
+                            values.iter().any(|s| matches_pattern(pattern, s))
+                        }
+                        TargetAttributeValue::Bool(b) => {
+                            matches_pattern(pattern, if *b { "True" } else { "False" })
+                        }
+                        TargetAttributeValue::Int(i) => matches_pattern(pattern, &i.to_string()),
+                    }
+                }),
+            TargetQuery::And(lhs, rhs) => lhs.matches(target) && rhs.matches(target),
+            TargetQuery::Or(lhs, rhs) => lhs.matches(target) || rhs.matches(target),
+            TargetQuery::Except(lhs, rhs) => lhs.matches(target) && !rhs.matches(target),
+        }
+    }
+
+    /// Returns the kind that all matching targets must have, if there is one, so that only
+    /// targets of that kind need to be checked.
+    fn required_kind(&self) -> Option<&str> {
+        match self {
+            TargetQuery::Kind(pattern) if !pattern.contains('*') => Some(pattern),
+            TargetQuery::And(lhs, rhs) => lhs.required_kind().or_else(|| rhs.required_kind()),
+            TargetQuery::Except(lhs, _) => lhs.required_kind(),
+            _ => None,
+        }
+    }
+}
+
+impl TargetIndex {
+    /// Returns the indexed targets that match the given query, in no particular order.
+    pub fn query(&self, query: &TargetQuery) -> Vec<&IndexedTarget> {
+        match query.required_kind() {
+            Some(kind) => self
+                .targets_of_kind(kind)
+                .iter()
+                .filter(|indexed| query.matches(&indexed.target))
+                .collect(),
+            None => self
+                .iter()
+                .filter(|indexed| query.matches(&indexed.target))
+                .collect(),
+        }
+    }
+}
+
+fn matches_pattern(pattern: &str, s: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let mut rest = match s.strip_prefix(first) {
+        Some(rest) => rest,
+        None => return false,
+    };
+    let parts = parts.collect::<Vec<_>>();
+    let (last, middle) = match parts.split_last() {
+        Some(res) => res,
+        None => return rest.is_empty(),
+    };
+
+    for part in middle {
+        rest = match rest.find(part) {
+            Some(pos) => &rest[pos + part.len()..],
+            None => return false,
+        };
+    }
+    rest.ends_with(last)
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Token {
+    LParen,
+    RParen,
+    Comma,
+    Word { text: String, quoted: bool },
+}
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::LParen => f.write_str("\"(\""),
+            Token::RParen => f.write_str("\")\""),
+            Token::Comma => f.write_str("\",\""),
+            Token::Word { text, .. } => write!(f, "{:?}", text),
+        }
+    }
+}
+
+fn tokenize(input: &str) -> anyhow::Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = input.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        match c {
+            '(' | ')' | ',' => {
+                chars.next();
+                tokens.push(match c {
+                    '(' => Token::LParen,
+                    ')' => Token::RParen,
+                    _ => Token::Comma,
+                });
+            }
+            '"' | '\'' => {
+                chars.next();
+                let mut text = String::new();
+                loop {
+                    match chars.next() {
+                        Some(next) if next == c => break,
+                        Some('\\') => text.extend(chars.next()),
+                        Some(next) => text.push(next),
+                        None => bail!("unterminated string"),
+                    }
+                }
+                tokens.push(Token::Word { text, quoted: true });
+            }
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            _ => {
+                let mut text = String::new();
+                while let Some(&next) = chars.peek() {
+                    if next.is_whitespace() || matches!(next, '(' | ')' | ',' | '"' | '\'') {
+                        break;
+                    }
+                    text.push(next);
+                    chars.next();
+                }
+                tokens.push(Token::Word {
+                    text,
+                    quoted: false,
+                });
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn expect(&mut self, expected: Token) -> anyhow::Result<()> {
+        match self.next() {
+            Some(token) if token == expected => Ok(()),
+            Some(token) => bail!("expected {}, found {}", expected, token),
+            None => bail!("expected {}, found end of query", expected),
+        }
+    }
+
+    fn parse_expr(&mut self) -> anyhow::Result<TargetQuery> {
+        let mut lhs = self.parse_primary()?;
+        loop {
+            let op: fn(Box<TargetQuery>, Box<TargetQuery>) -> TargetQuery = match self.peek() {
+                Some(Token::Word {
+                    text,
+                    quoted: false,
+                }) => match text.as_str() {
+                    "and" => TargetQuery::And,
+                    "or" => TargetQuery::Or,
+                    "except" => TargetQuery::Except,
+                    _ => bail!("expected an operator, found {:?}", text),
+                },
+                _ => return Ok(lhs),
+            };
+            self.next();
+            let rhs = self.parse_primary()?;
+            lhs = op(Box::new(lhs), Box::new(rhs));
+        }
+    }
+
+    fn parse_primary(&mut self) -> anyhow::Result<TargetQuery> {
+        let name = match self.next() {
+            Some(Token::LParen) => {
+                let query = self.parse_expr()?;
+                self.expect(Token::RParen)?;
+                return Ok(query);
+            }
+            Some(Token::Word {
+                text,
+                quoted: false,
+            }) => text,
+            Some(token) => bail!("expected a function, found {}", token),
+            None => bail!("expected a function, found end of query"),
+        };
+
+        self.expect(Token::LParen)?;
+        let mut args = Vec::new();
+        loop {
+            match self.next() {
+                Some(Token::Word { text, .. }) => args.push(text),
+                Some(token) => bail!("expected an argument, found {}", token),
+                None => bail!("expected an argument, found end of query"),
+            }
+            match self.next() {
+                Some(Token::Comma) => continue,
+                Some(Token::RParen) => break,
+                Some(token) => bail!("expected \",\" or \")\", found {}", token),
+                None => bail!("expected \")\", found end of query"),
+            }
+        }
+
+        let mut args = args.into_iter();
+        let query = match (name.as_str(), args.next(), args.next()) {
+            ("kind", Some(pattern), None) => TargetQuery::Kind(pattern),
+            ("name", Some(pattern), None) => TargetQuery::Name(pattern),
+            ("attr", Some(name), value) => TargetQuery::Attr { name, value },
+            ("kind" | "name" | "attr", _, _) => {
+                bail!("wrong number of arguments to {}()", name)
+            }
+            _ => bail!("unknown function {:?}", name),
+        };
+        if args.next().is_some() {
+            bail!("wrong number of arguments to {}()", name);
+        }
+        Ok(query)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use starpls_common::{Dialect, FileId};
+
+    use super::{matches_pattern, TargetQuery};
+    use crate::AnalysisSnapshot;
+
+    #[test]
+    fn test_matches_pattern() {
+        assert!(matches_pattern("cc_library", "cc_library"));
+        assert!(matches_pattern("cc_*", "cc_library"));
+        assert!(matches_pattern("*_test", "go_test"));
+        assert!(matches_pattern("*lib*", "cc_library"));
+        assert!(matches_pattern("*", ""));
+        assert!(!matches_pattern("cc_*", "go_library"));
+        assert!(!matches_pattern("a*a", "a"));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        for (input, err) in [
+            ("kind(cc_library", "expected \")\", found end of query"),
+            ("kind()", "expected an argument, found \")\""),
+            ("kind(a, b)", "wrong number of arguments to kind()"),
+            ("kind(a) xor kind(b)", "expected an operator, found \"xor\""),
+            ("deps(//foo)", "unknown function \"deps\""),
+            ("attr(copts, \"-O2)", "unterminated string"),
+        ] {
+            assert_eq!(TargetQuery::parse(input).unwrap_err().to_string(), err);
+        }
+    }
+
+    #[test]
+    fn test_query() {
+        let snap = AnalysisSnapshot::from_files(
+            &[(
+                "BUILD",
+                r#"
+cc_library(name = "a", copts = ["-Wall", "-fno-exceptions"])
+cc_library(name = "b", copts = ["-Wall"])
+cc_binary(name = "c", copts = ["-fno-exceptions"], linkstatic = True)
+java_library(name = "d")
+"#,
+            )],
+            Dialect::Bazel,
+        );
+        let index = snap.target_index(&[FileId(0)]).unwrap();
+
+        let check = |input: &str, expected: &[&str]| {
+            let query = TargetQuery::parse(input).unwrap();
+            let mut names = index
+                .query(&query)
+                .into_iter()
+                .map(|indexed| indexed.target.name.as_str())
+                .collect::<Vec<_>>();
+            names.sort();
+            assert_eq!(names, expected, "{}", input);
+        };
+
+        check(
+            r#"kind(cc_library) and attr(copts, "-fno-exceptions")"#,
+            &["a"],
+        );
+        check("kind(cc_*) and attr(copts, -fno-exceptions)", &["a", "c"]);
+        check("kind(cc_*) except attr(copts, '-fno-*')", &["b"]);
+        check("attr(linkstatic, True) or kind(java_library)", &["c", "d"]);
+        check("(kind(java_library) or name(a)) and attr(copts)", &["a"]);
+        check("name(*)", &["a", "b", "c", "d"]);
+    }
+}
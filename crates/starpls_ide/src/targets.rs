@@ -0,0 +1,266 @@
+use starpls_bazel::{
+    implicit_outputs::{implicit_outputs, OUTPUT_ATTRIBUTES},
+    APIContext,
+};
+use starpls_common::{parse, Db as _, File, FileId, Symbol};
+use starpls_hir::Semantics;
+use starpls_syntax::{
+    ast::{self, AstNode},
+    TextRange,
+};
+
+use crate::{macro_targets::macro_targets, Database};
+
+/// A target declared in a `BUILD` file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Target {
+    pub name: String,
+    /// The rule or macro used to declare the target, as written at the call site, e.g.
+    /// `cc_library` or `native.filegroup`.
+    pub kind: Symbol,
+    /// The attributes set with keyword arguments, in the order they're written.
+    pub attributes: Vec<TargetAttribute>,
+    /// Whether the target is declared with a user-defined function rather than a rule. Such
+    /// functions are usually macros that may declare several targets.
+    pub is_macro: bool,
+    pub range: TextRange,
+    /// The range of the target's `name` attribute value.
+    pub name_range: TextRange,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TargetAttribute {
+    pub name: Symbol,
+    pub value: TargetAttributeValue,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TargetAttributeValue {
+    String(Symbol),
+    StringList(Vec<Symbol>),
+    Bool(bool),
+    Int(u64),
+    /// Any other expression, e.g. a `select()`, as written in the source.
+    Other(String),
+}
+
+pub(crate) fn targets(db: &Database, file_id: FileId) -> Option<Vec<Target>> {
+    let file = db.get_file(file_id)?;
+    if file.api_context(db) != Some(APIContext::Build) {
+        return Some(Vec::new());
+    }
+    Some(file_targets(db, file))
+}
+
+/// Finds the target in the given `BUILD` file that declares the target with the given name,
+/// either directly, as one of its declared outputs (e.g. the `outs` of a `genrule`), as one
+/// of the implicit outputs of its rule (e.g. the `lib<name>.so` of a `cc_library`), or as one of
+/// the targets declared by a macro (e.g. the `foo_test` of `my_macro(name = "foo")`).
+pub(crate) fn find_generating_target(db: &Database, file: File, name: &str) -> Option<Target> {
+    let targets = file_targets(db, file);
+    if let Some(target) = targets.iter().find(|target| target.name == name) {
+        return Some(target.clone());
+    }
+    let generating_target = targets.iter().find(|target| {
+        target.attributes.iter().any(|attr| {
+            OUTPUT_ATTRIBUTES.contains(&attr.name.as_str())
+                && match &attr.value {
+                    TargetAttributeValue::String(value) => value == name,
+                    TargetAttributeValue::StringList(values) => {
+                        values.iter().any(|value| value == name)
+                    }
+                    _ => false,
+                }
+        }) || implicit_outputs(rule_name(&target.kind), &target.name)
+            .iter()
+            .any(|output| output == name)
+    });
+    if let Some(target) = generating_target {
+        return Some(target.clone());
+    }
+    targets.into_iter().find(|target| {
+        target.is_macro
+            && macro_target_names(db, file, target)
+                .iter()
+                .any(|target_name| target_name == name)
+    })
+}
+
+/// Returns the names of the targets declared by a macro call, as far as they can be worked out.
+pub(crate) fn macro_target_names(db: &Database, file: File, target: &Target) -> Vec<String> {
+    let sema = Semantics::new(db);
+    parse(db, file)
+        .syntax(db)
+        .children()
+        .filter_map(ast::CallExpr::cast)
+        .find(|call| call.syntax().text_range() == target.range)
+        .and_then(|call| macro_targets(db, &sema, file, &call))
+        .unwrap_or_default()
+}
+
+fn file_targets(db: &Database, file: File) -> Vec<Target> {
+    let sema = Semantics::new(db);
+    let root = parse(db, file).syntax(db);
+    root.children()
+        .filter_map(ast::CallExpr::cast)
+        .filter_map(|call_expr| {
+            let mut name = None;
+            let mut attributes = Vec::new();
+            for arg in call_expr
+                .arguments()
+                .into_iter()
+                .flat_map(|args| args.arguments())
+            {
+                let arg = match arg {
+                    ast::Argument::Keyword(arg) => arg,
+                    _ => continue,
+                };
+                let (attr_name, expr) = match (arg.name().and_then(|name| name.name()), arg.expr())
+                {
+                    (Some(attr_name), Some(expr)) => (attr_name, expr),
+                    _ => continue,
+                };
+                let value = attribute_value(&expr).unwrap_or_else(|| {
+                    TargetAttributeValue::Other(expr.syntax().text().to_string())
+                });
+                if attr_name.text() == "name" {
+                    if let TargetAttributeValue::String(ref s) = value {
+                        name = Some((s.to_string(), expr.syntax().text_range()));
+                    }
+                }
+                attributes.push(TargetAttribute {
+                    name: Symbol::new(attr_name.text()),
+                    value,
+                });
+            }
+
+            let (name, name_range) = name?;
+            let is_macro = sema
+                .resolve_call_expr(file, &call_expr)
+                .map_or(false, |callable| callable.is_user_defined());
+            Some(Target {
+                name,
+                kind: Symbol::from(call_expr.callee()?.syntax().text().to_string()),
+                attributes,
+                is_macro,
+                range: call_expr.syntax().text_range(),
+                name_range,
+            })
+        })
+        .collect()
+}
+
+/// Strips any module prefix from a rule or macro name, e.g. `native.cc_test` becomes `cc_test`.
+pub(crate) fn rule_name(kind: &str) -> &str {
+    kind.rsplit('.').next().unwrap_or(kind)
+}
+
+fn attribute_value(expr: &ast::Expression) -> Option<TargetAttributeValue> {
+    Some(match expr {
+        ast::Expression::Literal(lit) => match lit.kind() {
+            ast::LiteralKind::String(s) => TargetAttributeValue::String(Symbol::new(&s.value()?)),
+            ast::LiteralKind::Bool(b) => TargetAttributeValue::Bool(b),
+            ast::LiteralKind::Int(i) => TargetAttributeValue::Int(i.value()?),
+            _ => return None,
+        },
+        ast::Expression::List(list) => TargetAttributeValue::StringList(
+            list.elements()
+                .map(|element| match element {
+                    ast::Expression::Literal(lit) => match lit.kind() {
+                        ast::LiteralKind::String(s) => s.value().map(|value| Symbol::new(&value)),
+                        _ => None,
+                    },
+                    _ => None,
+                })
+                .collect::<Option<Vec<_>>>()?,
+        ),
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use starpls_bazel::APIContext;
+    use starpls_common::{Db as _, Dialect};
+
+    use super::find_generating_target;
+    use crate::{AnalysisSnapshot, TargetAttribute, TargetAttributeValue};
+
+    #[test]
+    fn test_targets() {
+        let (snap, file_id) = AnalysisSnapshot::from_single_file(
+            r#"
+def my_macro(name, **kwargs):
+    pass
+
+cc_library(
+    name = "foo",
+    srcs = ["foo.cc"],
+    deps = [":bar"] + select({"//conditions:default": []}),
+    linkstatic = True,
+)
+
+my_macro(name = "bar")
+
+some_function()
+"#,
+            Dialect::Bazel,
+            Some(APIContext::Build),
+        );
+        let targets = snap.targets(file_id).unwrap().unwrap();
+        let summary = targets
+            .iter()
+            .map(|target| (target.name.as_str(), target.kind.as_str(), target.is_macro))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            vec![("foo", "cc_library", false), ("bar", "my_macro", true)]
+        );
+        assert_eq!(
+            targets[0].attributes,
+            vec![
+                TargetAttribute {
+                    name: "name".into(),
+                    value: TargetAttributeValue::String("foo".into()),
+                },
+                TargetAttribute {
+                    name: "srcs".into(),
+                    value: TargetAttributeValue::StringList(vec!["foo.cc".into()]),
+                },
+                TargetAttribute {
+                    name: "deps".into(),
+                    value: TargetAttributeValue::Other(
+                        r#"[":bar"] + select({"//conditions:default": []})"#.to_string()
+                    ),
+                },
+                TargetAttribute {
+                    name: "linkstatic".into(),
+                    value: TargetAttributeValue::Bool(true),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_find_generating_target() {
+        let (snap, file_id) = AnalysisSnapshot::from_single_file(
+            r#"
+cc_library(name = "foo")
+
+genrule(
+    name = "gen",
+    outs = ["gen.h", "gen.cc"],
+)
+"#,
+            Dialect::Bazel,
+            Some(APIContext::Build),
+        );
+        let file = snap.db.get_file(file_id).unwrap();
+        let generating_target =
+            |name: &str| find_generating_target(&snap.db, file, name).map(|target| target.name);
+        assert_eq!(generating_target("foo"), Some("foo".to_string()));
+        assert_eq!(generating_target("libfoo.so"), Some("foo".to_string()));
+        assert_eq!(generating_target("gen.cc"), Some("gen".to_string()));
+        assert_eq!(generating_target("libgen.so"), None);
+    }
+}
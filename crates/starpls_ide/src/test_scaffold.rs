@@ -0,0 +1,542 @@
+//! Code actions that generate skeleton analysis tests for a rule or macro defined in a `.bzl`
+//! file.
+//!
+//! The tests are written to a `<module>_tests.bzl` file next to the `.bzl` file, using either
+//! `rules_testing` or Skylib's `analysistest`. Every mandatory attribute gets its own test, each of
+//! which declares a target under test with placeholder values for all of the mandatory
+//! attributes. The tests are grouped into a test suite macro, which is instantiated in the
+//! package's `BUILD` file, creating the file if the package doesn't have one yet.
+
+use starpls_bazel::APIContext;
+use starpls_common::{parse, Db as _, Dialect, File, ResolvedPath};
+use starpls_hir::{Param, Semantics, Type};
+use starpls_syntax::{
+    ast::{self, AstNode},
+    SyntaxNode, TextRange,
+};
+
+use crate::{
+    util::{append_to_file, insert_load, load_stmts, local_name, merge_insertions},
+    CodeAction, CodeActionKind, Database, EditTarget, FileEdit, TextEdit,
+};
+
+/// The name of the `BUILD` file that is created if the package doesn't have one.
+const NEW_BUILD_FILE: &str = "BUILD.bazel";
+
+#[derive(Clone, Copy)]
+enum Framework {
+    RulesTesting,
+    Analysistest,
+}
+
+impl Framework {
+    fn name(self) -> &'static str {
+        match self {
+            Framework::RulesTesting => "rules_testing",
+            Framework::Analysistest => "analysistest",
+        }
+    }
+}
+
+/// The rule or macro that the tests are generated for.
+struct Subject {
+    name: String,
+    is_rule: bool,
+    /// The mandatory attributes other than `name`, along with placeholder values for them.
+    attrs: Vec<(String, &'static str)>,
+}
+
+impl Subject {
+    /// The suffixes that distinguish the tests, one for each mandatory attribute, or a single
+    /// test if there aren't any.
+    fn test_suffixes(&self) -> Vec<&str> {
+        if self.attrs.is_empty() {
+            vec!["basic"]
+        } else {
+            self.attrs.iter().map(|(name, _)| name.as_str()).collect()
+        }
+    }
+}
+
+pub(crate) fn test_scaffold_actions(
+    db: &Database,
+    file: File,
+    range: TextRange,
+    file_name: &str,
+) -> Vec<CodeAction> {
+    if file.api_context(db) != Some(APIContext::Bzl) {
+        return Vec::new();
+    }
+    let module_name = match file_name.strip_suffix(".bzl") {
+        Some(module_name) => module_name,
+        None => return Vec::new(),
+    };
+    let subject = match find_subject(db, file, range) {
+        Some(subject) => subject,
+        None => return Vec::new(),
+    };
+    let test_file_name = format!("{}_tests.bzl", module_name);
+    let suite_name = format!("{}_test_suite", subject.name);
+    let target_name = format!("{}_tests", subject.name);
+
+    // Add the tests to the existing test file, unless it already has a suite for the subject.
+    let test_root = match db.load_file(&format!(":{}", test_file_name), Dialect::Bazel, file.id(db))
+    {
+        Ok(test_file) => test_file.map(|test_file| (test_file, parse(db, test_file).syntax(db))),
+        Err(_) => return Vec::new(),
+    };
+    if let Some((_, test_root)) = &test_root {
+        if defines(test_root, &suite_name) {
+            return Vec::new();
+        }
+    }
+
+    // Resolving a label in the package finds its `BUILD` file, if it has one.
+    let build_root =
+        match db.resolve_path(&format!(":{}", target_name), Dialect::Bazel, file.id(db)) {
+            Ok(Some(ResolvedPath::BuildTarget { build_file, .. })) => match db.get_file(build_file)
+            {
+                Some(build_file) => Some((build_file, parse(db, build_file).syntax(db))),
+                None => return Vec::new(),
+            },
+            Ok(None) => None,
+            _ => return Vec::new(),
+        };
+
+    [Framework::RulesTesting, Framework::Analysistest]
+        .into_iter()
+        .map(|framework| {
+            let subject_label = format!(":{}", file_name);
+            let mut loads = match framework {
+                Framework::RulesTesting => vec![(
+                    "@rules_testing//lib:analysis_test.bzl",
+                    vec!["analysis_test", "test_suite"],
+                )],
+                Framework::Analysistest => vec![(
+                    "@bazel_skylib//lib:unittest.bzl",
+                    vec!["analysistest", "asserts"],
+                )],
+            };
+            if matches!(framework, Framework::RulesTesting) && subject.is_rule {
+                loads.push(("@rules_testing//lib:util.bzl", vec!["util"]));
+            }
+            loads.push((subject_label.as_str(), vec![subject.name.as_str()]));
+            let tests = test_file_contents(framework, &subject, &suite_name);
+
+            let test_file_edit = match &test_root {
+                Some((test_file, test_root)) => {
+                    let loaded = loaded_names(test_root);
+                    let mut edits = loads
+                        .iter()
+                        .filter_map(|(module, names)| {
+                            let names = names
+                                .iter()
+                                .copied()
+                                .filter(|name| !loaded.iter().any(|loaded| loaded == name))
+                                .collect::<Vec<_>>();
+                            (!names.is_empty())
+                                .then(|| insert_load(Some(test_root), module, &names))
+                        })
+                        .collect::<Vec<_>>();
+                    edits.push(append_to_file(test_root, &tests));
+                    merge_insertions(&mut edits);
+                    FileEdit {
+                        target: EditTarget::File(test_file.id(db)),
+                        edits,
+                    }
+                }
+                None => {
+                    let mut text =
+                        format!("\"\"\"Analysis tests for `{}`.\"\"\"\n\n", subject.name);
+                    for (module, names) in &loads {
+                        text.push_str(&load_text(module, names));
+                    }
+                    text.push('\n');
+                    text.push_str(&tests);
+                    FileEdit {
+                        target: EditTarget::NewFile(test_file_name.clone()),
+                        edits: vec![TextEdit {
+                            range: TextRange::empty(0.into()),
+                            new_text: text,
+                        }],
+                    }
+                }
+            };
+
+            let test_label = format!(":{}", test_file_name);
+            let suite_target = format!("{}(\n    name = \"{}\",\n)\n", suite_name, target_name);
+            let build_file_edit = match &build_root {
+                Some((build_file, build_root)) => {
+                    let mut edits = vec![
+                        insert_load(Some(build_root), &test_label, &[suite_name.as_str()]),
+                        append_to_file(build_root, &suite_target),
+                    ];
+                    merge_insertions(&mut edits);
+                    FileEdit {
+                        target: EditTarget::File(build_file.id(db)),
+                        edits,
+                    }
+                }
+                None => FileEdit {
+                    target: EditTarget::NewFile(NEW_BUILD_FILE.to_string()),
+                    edits: vec![TextEdit {
+                        range: TextRange::empty(0.into()),
+                        new_text: format!(
+                            "{}\n{}",
+                            load_text(&test_label, &[suite_name.as_str()]),
+                            suite_target
+                        ),
+                    }],
+                },
+            };
+
+            CodeAction {
+                title: format!(
+                    "Generate analysis tests for `{}` with {}",
+                    subject.name,
+                    framework.name()
+                ),
+                kind: CodeActionKind::Source,
+                edits: Vec::new(),
+                file_edits: vec![test_file_edit, build_file_edit],
+                command: None,
+                is_preferred: false,
+            }
+        })
+        .collect()
+}
+
+/// Finds the public rule or macro declared by the top-level statement at the given range.
+/// Macros are functions with a `name` parameter.
+fn find_subject(db: &Database, file: File, range: TextRange) -> Option<Subject> {
+    let sema = Semantics::new(db);
+    for node in parse(db, file).syntax(db).children() {
+        if let Some(assign_stmt) = ast::AssignStmt::cast(node.clone()) {
+            if assign_stmt.syntax().text_range().intersect(range).is_none() {
+                continue;
+            }
+            let lhs = assign_stmt.lhs()?;
+            let name = match &lhs {
+                ast::Expression::Name(name_ref) => name_ref.name()?.text().to_string(),
+                _ => return None,
+            };
+            let is_rule_call = match assign_stmt.rhs()? {
+                ast::Expression::Call(call_expr) => matches!(
+                    call_expr.callee(),
+                    Some(ast::Expression::Name(callee))
+                        if callee.name().map_or(false, |callee| callee.text() == "rule")
+                ),
+                _ => false,
+            };
+            let ty = sema.type_of_expr(file, &lhs)?;
+            if name.starts_with('_') || !is_rule_call || !ty.is_rule() {
+                return None;
+            }
+            return Some(Subject {
+                name,
+                is_rule: true,
+                attrs: mandatory_attrs(db, ty.params(db)),
+            });
+        }
+
+        if let Some(def_stmt) = ast::DefStmt::cast(node) {
+            // Only offer the actions on the function's signature, not its body.
+            let signature_end = def_stmt
+                .suite()
+                .map_or(def_stmt.syntax().text_range().end(), |suite| {
+                    suite.syntax().text_range().start()
+                });
+            let signature_range =
+                TextRange::new(def_stmt.syntax().text_range().start(), signature_end);
+            if signature_range.intersect(range).is_none() {
+                continue;
+            }
+            let name = def_stmt.name()?.name()?.text().to_string();
+            let params = sema.callable_for_def(file, def_stmt)?.params(db);
+            let has_name_param = params.iter().any(|(param, _)| {
+                param
+                    .name(db)
+                    .map_or(false, |param_name| param_name.as_str() == "name")
+            });
+            if name.starts_with('_') || !has_name_param {
+                return None;
+            }
+            return Some(Subject {
+                name,
+                is_rule: false,
+                attrs: mandatory_attrs(db, params),
+            });
+        }
+    }
+    None
+}
+
+fn mandatory_attrs(db: &Database, params: Vec<(Param, Type)>) -> Vec<(String, &'static str)> {
+    params
+        .into_iter()
+        .filter(|(param, _)| {
+            param.is_mandatory(db) && !param.is_args_list(db) && !param.is_kwargs_dict(db)
+        })
+        .filter_map(|(param, ty)| {
+            let name = param.name(db)?;
+            (name.as_str() != "name").then(|| (name.to_string(), placeholder(db, &ty)))
+        })
+        .collect()
+}
+
+/// Returns a value of the given type to pass to the target under test.
+fn placeholder(db: &Database, ty: &Type) -> &'static str {
+    let annotation = ty.annotation(db);
+    if annotation.starts_with("list") || annotation.starts_with("Sequence") {
+        "[]"
+    } else if annotation.starts_with("dict") {
+        "{}"
+    } else {
+        match annotation.as_str() {
+            "bool" => "False",
+            "int" => "0",
+            "string" => "\"\"",
+            _ => "None",
+        }
+    }
+}
+
+fn test_file_contents(framework: Framework, subject: &Subject, suite_name: &str) -> String {
+    let suffixes = subject.test_suffixes();
+    let mut text = String::new();
+    for suffix in &suffixes {
+        let test_name = format!("_test_{}_{}", subject.name, suffix);
+        let todo = if subject.attrs.is_empty() {
+            "Check the target's providers and actions.".to_string()
+        } else {
+            format!("Check how `{}` affects the target.", suffix)
+        };
+        match framework {
+            Framework::RulesTesting => {
+                text.push_str(&format!("def {}(name):\n", test_name));
+                text.push_str(&target_under_test(framework, subject));
+                text.push_str(&format!(
+                    "    analysis_test(\n        name = name,\n        impl = {}_impl,\n        target = name + \"_subject\",\n    )\n\n",
+                    test_name
+                ));
+                text.push_str(&format!(
+                    "def {}_impl(env, target):\n    # TODO: {}\n    env.expect.that_target(target).has_provider(DefaultInfo)\n\n",
+                    test_name, todo
+                ));
+            }
+            Framework::Analysistest => {
+                let test_rule = format!("{}_{}_test", subject.name, suffix);
+                text.push_str(&format!(
+                    "def {}_impl(ctx):\n    env = analysistest.begin(ctx)\n    target_under_test = analysistest.target_under_test(env)\n\n    # TODO: {}\n    asserts.true(env, target_under_test != None)\n\n    return analysistest.end(env)\n\n",
+                    test_name, todo
+                ));
+                text.push_str(&format!(
+                    "{} = analysistest.make({}_impl)\n\n",
+                    test_rule, test_name
+                ));
+                text.push_str(&format!("def {}(name):\n", test_name));
+                text.push_str(&target_under_test(framework, subject));
+                text.push_str(&format!(
+                    "    {}(\n        name = name,\n        target_under_test = \":\" + name + \"_subject\",\n    )\n\n",
+                    test_rule
+                ));
+            }
+        }
+    }
+
+    text.push_str(&format!(
+        "def {}(name):\n    \"\"\"Defines the analysis tests for `{}`.\n\n    Args:\n        name: The name of the test suite.\n    \"\"\"\n",
+        suite_name, subject.name
+    ));
+    match framework {
+        Framework::RulesTesting => {
+            text.push_str("    test_suite(\n        name = name,\n        tests = [\n");
+            for suffix in &suffixes {
+                text.push_str(&format!("            _test_{}_{},\n", subject.name, suffix));
+            }
+        }
+        Framework::Analysistest => {
+            for suffix in &suffixes {
+                text.push_str(&format!(
+                    "    _test_{}_{}(name = name + \"_{}\")\n",
+                    subject.name, suffix, suffix
+                ));
+            }
+            text.push_str("    native.test_suite(\n        name = name,\n        tests = [\n");
+            for suffix in &suffixes {
+                text.push_str(&format!("            \":\" + name + \"_{}\",\n", suffix));
+            }
+        }
+    }
+    text.push_str("        ],\n    )\n");
+    text
+}
+
+/// Declares the target under test in the body of a test macro. Targets of rules are tagged as
+/// `manual` so that they're only built by the tests.
+fn target_under_test(framework: Framework, subject: &Subject) -> String {
+    let mut text = match (framework, subject.is_rule) {
+        // `util.helper_target()` adds the `manual` tag itself.
+        (Framework::RulesTesting, true) => {
+            format!("    util.helper_target(\n        {},\n", subject.name)
+        }
+        _ => format!("    {}(\n", subject.name),
+    };
+    text.push_str("        name = name + \"_subject\",\n");
+    for (name, value) in &subject.attrs {
+        text.push_str(&format!("        {} = {},\n", name, value));
+    }
+    if matches!(framework, Framework::Analysistest) && subject.is_rule {
+        text.push_str("        tags = [\"manual\"],\n");
+    }
+    text.push_str("    )\n");
+    text
+}
+
+fn load_text(module: &str, names: &[&str]) -> String {
+    format!(
+        "load(\"{}\", {})\n",
+        module,
+        names
+            .iter()
+            .map(|name| format!("\"{}\"", name))
+            .collect::<Vec<_>>()
+            .join(", ")
+    )
+}
+
+fn loaded_names(root: &SyntaxNode) -> Vec<String> {
+    load_stmts(root)
+        .iter()
+        .flat_map(|(load_stmt, _)| load_stmt.items())
+        .filter_map(|item| local_name(&item))
+        .collect()
+}
+
+/// Returns whether a file has a top-level function with the given name.
+fn defines(root: &SyntaxNode, name: &str) -> bool {
+    root.children()
+        .filter_map(ast::DefStmt::cast)
+        .filter_map(|def_stmt| def_stmt.name()?.name())
+        .any(|def_name| def_name.text() == name)
+}
+
+#[cfg(test)]
+mod tests {
+    use starpls_bazel::Builtins;
+    use starpls_common::{Dialect, FileId, FileRange};
+    use starpls_hir::Db as _;
+    use starpls_syntax::{TextRange, TextSize};
+    use starpls_test_util::{make_test_builtins, FixtureType};
+
+    use crate::{Analysis, EditTarget};
+
+    #[test]
+    fn test_generate_tests_for_rule() {
+        let contents = r#"def _impl(ctx):
+    pass
+
+my_rule = rule(
+    implementation = _impl,
+    attrs = {
+        "srcs": attr.label_list(mandatory = True),
+        "out": attr.string(),
+    },
+)
+"#;
+        let file_id = FileId(0);
+        let mut analysis = Analysis::from_files(&[("defs.bzl", contents)], Dialect::Bazel);
+        analysis.db.set_builtin_defs(
+            Dialect::Bazel,
+            make_test_builtins(
+                vec!["rule".to_string()],
+                vec![("attr".to_string(), "attr".to_string())],
+                vec![FixtureType::new(
+                    "attr",
+                    vec![],
+                    vec!["label_list", "string"],
+                )],
+            ),
+            Builtins::default(),
+        );
+
+        let start = TextSize::from(contents.find("my_rule").unwrap() as u32);
+        let actions = analysis
+            .snapshot()
+            .test_scaffold_actions(
+                FileRange {
+                    file_id,
+                    range: TextRange::empty(start),
+                },
+                "defs.bzl".to_string(),
+            )
+            .unwrap();
+        assert_eq!(
+            actions
+                .iter()
+                .map(|action| action.title.as_str())
+                .collect::<Vec<_>>(),
+            vec![
+                "Generate analysis tests for `my_rule` with rules_testing",
+                "Generate analysis tests for `my_rule` with analysistest",
+            ]
+        );
+
+        let file_edits = &actions[0].file_edits;
+        assert_eq!(
+            file_edits[0].target,
+            EditTarget::NewFile("defs_tests.bzl".to_string())
+        );
+        assert_eq!(
+            file_edits[0].edits[0].new_text,
+            r#""""Analysis tests for `my_rule`."""
+
+load("@rules_testing//lib:analysis_test.bzl", "analysis_test", "test_suite")
+load("@rules_testing//lib:util.bzl", "util")
+load(":defs.bzl", "my_rule")
+
+def _test_my_rule_srcs(name):
+    util.helper_target(
+        my_rule,
+        name = name + "_subject",
+        srcs = [],
+    )
+    analysis_test(
+        name = name,
+        impl = _test_my_rule_srcs_impl,
+        target = name + "_subject",
+    )
+
+def _test_my_rule_srcs_impl(env, target):
+    # TODO: Check how `srcs` affects the target.
+    env.expect.that_target(target).has_provider(DefaultInfo)
+
+def my_rule_test_suite(name):
+    """Defines the analysis tests for `my_rule`.
+
+    Args:
+        name: The name of the test suite.
+    """
+    test_suite(
+        name = name,
+        tests = [
+            _test_my_rule_srcs,
+        ],
+    )
+"#
+        );
+        assert_eq!(
+            file_edits[1].target,
+            EditTarget::NewFile("BUILD.bazel".to_string())
+        );
+        assert_eq!(
+            file_edits[1].edits[0].new_text,
+            r#"load(":defs_tests.bzl", "my_rule_test_suite")
+
+my_rule_test_suite(
+    name = "my_rule_tests",
+)
+"#
+        );
+    }
+}
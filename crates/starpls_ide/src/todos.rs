@@ -0,0 +1,192 @@
+//! Collects tagged comments, e.g. `# TODO(owner): ...`, from a file.
+
+use starpls_common::{
+    parse, Db as _, Diagnostic, DiagnosticCode, File, FileId, FileRange, Severity,
+};
+use starpls_syntax::{SyntaxKind, TextRange, TextSize};
+
+use crate::Database;
+
+/// The tags that are collected when no tags have been configured.
+pub const DEFAULT_TODO_TAGS: &[&str] = &["TODO", "FIXME", "DEPRECATED"];
+
+/// A comment beginning with one of the configured tags.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TodoItem {
+    pub tag: String,
+    /// The owner of the item, e.g. `alice` in `TODO(alice): ...`.
+    pub owner: Option<String>,
+    /// The issue tracking the item, e.g. `#123` in `TODO(#123): ...`, or an issue URL.
+    pub issue: Option<String>,
+    pub message: String,
+    /// The range of the comment, excluding the leading `#`.
+    pub range: TextRange,
+}
+
+pub(crate) fn todos(db: &Database, file_id: FileId) -> Option<Vec<TodoItem>> {
+    let file = db.get_file(file_id)?;
+    Some(collect_todos(db, file))
+}
+
+pub(crate) fn todo_diagnostics(db: &Database, file: File) -> Vec<Diagnostic> {
+    collect_todos(db, file)
+        .into_iter()
+        .map(|todo| Diagnostic {
+            code: DiagnosticCode::Todo,
+            message: match &todo.owner {
+                Some(owner) => format!("{}({}): {}", todo.tag, owner, todo.message),
+                None => format!("{}: {}", todo.tag, todo.message),
+            },
+            severity: Severity::Hint,
+            range: FileRange {
+                file_id: file.id(db),
+                range: todo.range,
+            },
+        })
+        .collect()
+}
+
+fn collect_todos(db: &Database, file: File) -> Vec<TodoItem> {
+    let configured_tags = &db.diagnostics_options.todo_tags;
+    let tags = if configured_tags.is_empty() {
+        DEFAULT_TODO_TAGS.iter().copied().collect::<Vec<_>>()
+    } else {
+        configured_tags.iter().map(String::as_str).collect()
+    };
+
+    parse(db, file)
+        .syntax(db)
+        .descendants_with_tokens()
+        .filter_map(|el| el.into_token())
+        .filter(|token| token.kind() == SyntaxKind::COMMENT)
+        .filter_map(|token| {
+            let text = token.text();
+            let content = text.trim_start_matches('#').trim_start();
+            let offset = TextSize::of(&text[..text.len() - content.len()]);
+            let mut todo = parse_todo(content, &tags)?;
+            todo.range = TextRange::at(
+                token.text_range().start() + offset,
+                TextSize::of(content.trim_end()),
+            );
+            Some(todo)
+        })
+        .collect()
+}
+
+/// Parses comments of the form `TAG`, `TAG: message`, `TAG(owner): message`,
+/// `TAG(#123): message`, or `TAG(owner, #123): message`. If no issue is given in parentheses,
+/// the first `#123`-style reference in the message is used instead.
+fn parse_todo(content: &str, tags: &[&str]) -> Option<TodoItem> {
+    let tag = tags.iter().find(|tag| {
+        content.strip_prefix(**tag).map_or(false, |rest| {
+            !rest.starts_with(|c: char| c.is_alphanumeric() || c == '_')
+        })
+    })?;
+    let mut rest = &content[tag.len()..];
+    let mut owner = None;
+    let mut issue = None;
+
+    if let Some(inner) = rest.strip_prefix('(') {
+        let end = inner.find(')')?;
+        for part in inner[..end].split(',').map(str::trim) {
+            if is_issue(part) {
+                issue.get_or_insert_with(|| part.to_string());
+            } else if !part.is_empty() {
+                owner.get_or_insert_with(|| part.trim_start_matches('@').to_string());
+            }
+        }
+        rest = &inner[end + 1..];
+    }
+
+    let message = rest
+        .trim_start_matches(|c: char| c == ':' || c == '-' || c.is_whitespace())
+        .trim_end()
+        .to_string();
+    if issue.is_none() {
+        issue = message
+            .split(|c: char| c.is_whitespace() || c == '(' || c == ')' || c == ',')
+            .map(|word| word.trim_end_matches(|c: char| !c.is_ascii_digit()))
+            .find(|word| is_issue_number(word))
+            .map(|word| word.to_string());
+    }
+
+    Some(TodoItem {
+        tag: tag.to_string(),
+        owner,
+        issue,
+        message,
+        range: TextRange::default(),
+    })
+}
+
+fn is_issue(s: &str) -> bool {
+    is_issue_number(s) || s.starts_with("http://") || s.starts_with("https://")
+}
+
+fn is_issue_number(s: &str) -> bool {
+    s.strip_prefix('#').map_or(false, |digits| {
+        !digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use starpls_common::Dialect;
+
+    use crate::{AnalysisSnapshot, TodoItem};
+
+    fn check(input: &str, expected: &[(&str, Option<&str>, Option<&str>, &str)]) {
+        let (snap, file_id) = AnalysisSnapshot::from_single_file(input, Dialect::Standard, None);
+        let actual = snap.todos(file_id).unwrap().unwrap();
+        let actual = actual
+            .iter()
+            .map(
+                |TodoItem {
+                     tag,
+                     owner,
+                     issue,
+                     message,
+                     ..
+                 }| {
+                    (
+                        tag.as_str(),
+                        owner.as_deref(),
+                        issue.as_deref(),
+                        message.as_str(),
+                    )
+                },
+            )
+            .collect::<Vec<_>>();
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_todos() {
+        check(
+            r#"
+# TODO: Remove this once everything is migrated.
+x = 1 # FIXME(alice): Handle the empty case.
+
+# TODO(@bob, #123) Support more platforms.
+# DEPRECATED - Use `y` instead, see #456.
+# TODOS are not matched, and neither is a TODO in the middle of a comment.
+"#,
+            &[
+                (
+                    "TODO",
+                    None,
+                    None,
+                    "Remove this once everything is migrated.",
+                ),
+                ("FIXME", Some("alice"), None, "Handle the empty case."),
+                ("TODO", Some("bob"), Some("#123"), "Support more platforms."),
+                (
+                    "DEPRECATED",
+                    None,
+                    Some("#456"),
+                    "Use `y` instead, see #456.",
+                ),
+            ],
+        );
+    }
+}
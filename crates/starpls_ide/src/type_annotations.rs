@@ -0,0 +1,301 @@
+//! Code actions that write the inferred types of a function's parameters and return value into
+//! the file, either as a function type comment or as types in the `Args:` section of the
+//! function's docstring.
+//!
+//! Parameters without a type are typed by their default values, and the return type is the
+//! union of the types of the function's `return` statements.
+
+use starpls_common::{parse, File};
+use starpls_hir::{Semantics, Type};
+use starpls_syntax::{
+    ast::{self, AstNode, AstToken},
+    SyntaxKind, TextRange, TextSize,
+};
+
+use crate::{util::docstring_args, CodeAction, CodeActionKind, Database, TextEdit};
+
+pub(crate) const UNKNOWN: &str = "Unknown";
+
+pub(crate) fn type_annotation_actions(
+    db: &Database,
+    file: File,
+    range: TextRange,
+) -> Vec<CodeAction> {
+    let sema = Semantics::new(db);
+    let mut actions = Vec::new();
+    for def_stmt in parse(db, file)
+        .syntax(db)
+        .descendants()
+        .filter_map(ast::DefStmt::cast)
+    {
+        // Only offer the actions on the function's signature, not its body.
+        let suite = match def_stmt.suite() {
+            Some(suite) => suite,
+            None => continue,
+        };
+        let signature_range = TextRange::new(
+            def_stmt.syntax().text_range().start(),
+            suite.syntax().text_range().start(),
+        );
+        if signature_range.intersect(range).is_none() {
+            continue;
+        }
+
+        let params = def_stmt
+            .parameters()
+            .into_iter()
+            .flat_map(|params| params.parameters())
+            .collect::<Vec<_>>();
+        actions.extend(add_type_comment_action(
+            db, &sema, file, &def_stmt, &suite, &params,
+        ));
+        actions.extend(add_docstring_types_action(
+            db, &sema, file, &def_stmt, &params,
+        ));
+    }
+    actions
+}
+
+fn add_type_comment_action(
+    db: &Database,
+    sema: &Semantics,
+    file: File,
+    def_stmt: &ast::DefStmt,
+    suite: &ast::Suite,
+    params: &[ast::Parameter],
+) -> Option<CodeAction> {
+    if suite.type_comment().is_some() || params.iter().any(|param| param.type_comment().is_some()) {
+        return None;
+    }
+
+    // The type comment goes on its own line before the first statement of the body, so
+    // single-line bodies, e.g. `def f(): pass`, are skipped.
+    let indent = suite
+        .syntax()
+        .children_with_tokens()
+        .filter_map(|el| el.into_token())
+        .find(|token| token.kind() == SyntaxKind::INDENT)?;
+    let offset = indent.text_range().end();
+
+    let param_types = params
+        .iter()
+        .map(|param| match param {
+            ast::Parameter::Simple(_) => param_type(db, sema, file, param)
+                .map_or_else(|| UNKNOWN.to_string(), |ty| ty.param_annotation(db)),
+            ast::Parameter::ArgsList(_) => format!("*{}", UNKNOWN),
+            ast::Parameter::KwargsDict(_) => format!("**{}", UNKNOWN),
+        })
+        .collect::<Vec<_>>();
+    let ret_type = return_type(db, sema, file, def_stmt);
+
+    Some(CodeAction {
+        title: "Add type comment".to_string(),
+        kind: CodeActionKind::RefactorRewrite,
+        edits: vec![TextEdit {
+            range: TextRange::empty(offset),
+            new_text: format!(
+                "# type: ({}) -> {}\n{}",
+                param_types.join(", "),
+                ret_type,
+                indent.text()
+            ),
+        }],
+        file_edits: Vec::new(),
+        command: None,
+        is_preferred: false,
+    })
+}
+
+/// Adds types to the entries in the `Args:` section of the function's docstring that don't
+/// have one, e.g. turning `x: The first argument` into `x (int): The first argument`.
+fn add_docstring_types_action(
+    db: &Database,
+    sema: &Semantics,
+    file: File,
+    def_stmt: &ast::DefStmt,
+    params: &[ast::Parameter],
+) -> Option<CodeAction> {
+    let doc = def_stmt.doc()?;
+    let text = doc.text();
+    let start = doc.syntax().text_range().start();
+    let mut edits = Vec::new();
+    for (offset, name) in docstring_args(&text) {
+        let name_end = offset + name.len();
+        if !text[name_end..].starts_with(':') {
+            continue;
+        }
+        let param = match params
+            .iter()
+            .find(|param| param.name().as_deref() == Some(name))
+        {
+            Some(param) => param,
+            None => continue,
+        };
+        let ty = match param {
+            ast::Parameter::Simple(_) => param_type(db, sema, file, param)
+                .map_or_else(|| UNKNOWN.to_string(), |ty| ty.annotation(db)),
+            _ => continue,
+        };
+        edits.push(TextEdit {
+            range: TextRange::empty(start + TextSize::from(name_end as u32)),
+            new_text: format!(" ({})", ty),
+        });
+    }
+
+    if edits.is_empty() {
+        return None;
+    }
+    Some(CodeAction {
+        title: "Add types to docstring".to_string(),
+        kind: CodeActionKind::RefactorRewrite,
+        edits,
+        file_edits: Vec::new(),
+        command: None,
+        is_preferred: false,
+    })
+}
+
+/// Returns the inferred type of a simple parameter, falling back to the type of its default
+/// value. Returns `None` if neither is known.
+pub(crate) fn param_type(
+    db: &Database,
+    sema: &Semantics,
+    file: File,
+    param: &ast::Parameter,
+) -> Option<Type> {
+    if let Some(ty) = sema
+        .type_of_param(file, param)
+        .filter(|ty| !ty.is_unknown())
+    {
+        return Some(ty);
+    }
+
+    // A default of `None` usually stands in for a value of some other type, so it doesn't say
+    // anything about the parameter's type.
+    let default = match param {
+        ast::Parameter::Simple(param) => param.default()?,
+        _ => return None,
+    };
+    sema.type_of_expr(file, &default)
+        .filter(|ty| !ty.is_unknown() && ty.annotation(db) != "None")
+}
+
+/// Returns the union of the types of the function's `return` statements, which is `None` if it
+/// doesn't have any.
+pub(crate) fn return_type(
+    db: &Database,
+    sema: &Semantics,
+    file: File,
+    def_stmt: &ast::DefStmt,
+) -> String {
+    let mut parts: Vec<String> = Vec::new();
+    for return_stmt in def_stmt
+        .syntax()
+        .descendants()
+        .filter_map(ast::ReturnStmt::cast)
+        .filter(|return_stmt| {
+            // Skip the `return` statements of nested functions.
+            return_stmt
+                .syntax()
+                .ancestors()
+                .find_map(ast::DefStmt::cast)
+                .map_or(false, |parent| parent.syntax() == def_stmt.syntax())
+        })
+    {
+        let part = match return_stmt.expr() {
+            Some(expr) => sema
+                .type_of_expr(file, &expr)
+                .map_or_else(|| UNKNOWN.to_string(), |ty| ty.annotation(db)),
+            None => "None".to_string(),
+        };
+        if !parts.contains(&part) {
+            parts.push(part);
+        }
+    }
+
+    if parts.is_empty() {
+        "None".to_string()
+    } else {
+        parts.join(" | ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use starpls_common::{Dialect, FileRange};
+    use starpls_syntax::{TextRange, TextSize};
+
+    use crate::{AnalysisSnapshot, TextEdit};
+
+    fn apply(text: &str, edits: &[TextEdit]) -> String {
+        let mut edits = edits.to_vec();
+        edits.sort_by_key(|edit| std::cmp::Reverse(edit.range.start()));
+        let mut text = text.to_string();
+        for edit in edits {
+            text.replace_range(std::ops::Range::<usize>::from(edit.range), &edit.new_text);
+        }
+        text
+    }
+
+    fn check(contents: &str, title: &str, expected: &str) {
+        let (snap, file_id) = AnalysisSnapshot::from_single_file(contents, Dialect::Standard, None);
+        let actions = snap
+            .code_actions(FileRange {
+                file_id,
+                range: TextRange::empty(TextSize::from(4)),
+            })
+            .unwrap()
+            .unwrap();
+        let action = actions.iter().find(|action| action.title == title).unwrap();
+        assert_eq!(apply(contents, &action.edits), expected);
+    }
+
+    #[test]
+    fn test_add_type_comment() {
+        check(
+            r#"def f(x, y = "a", z = None, *args, **kwargs):
+    if x:
+        return ["a"]
+    return None
+"#,
+            "Add type comment",
+            r#"def f(x, y = "a", z = None, *args, **kwargs):
+    # type: (Unknown, string, Unknown, *Unknown, **Unknown) -> list[string] | None
+    if x:
+        return ["a"]
+    return None
+"#,
+        );
+    }
+
+    #[test]
+    fn test_add_docstring_types() {
+        check(
+            r#"def f(x, y = 1):
+    """Does things.
+
+    Args:
+        x: The x.
+        y: The y.
+
+    Returns:
+        Nothing.
+    """
+    pass
+"#,
+            "Add types to docstring",
+            r#"def f(x, y = 1):
+    """Does things.
+
+    Args:
+        x (Unknown): The x.
+        y (int): The y.
+
+    Returns:
+        Nothing.
+    """
+    pass
+"#,
+        );
+    }
+}
@@ -0,0 +1,409 @@
+//! Checks for `load` items that are never referenced, and for local variables that are assigned
+//! but never read.
+//!
+//! Names are matched by resolving every name reference in the file, so a reference only counts if
+//! it resolves to the load item or assignment in question. Only the variables of functions are
+//! checked, since top-level variables may be loaded by other files. Variables starting with an
+//! underscore are assumed to be unused on purpose.
+
+use rustc_hash::FxHashSet;
+use starpls_common::{parse, Db as _, Diagnostic, DiagnosticCode, File, FileRange, Severity};
+use starpls_hir::{Name, Semantics};
+use starpls_syntax::{
+    ast::{self, AssignOp, AstNode},
+    edit::EditBuilder,
+    SyntaxNode, TextRange,
+};
+
+use crate::{
+    inlay_hints::collect_assigned_names, util::local_name, CodeAction, CodeActionKind, Database,
+    TextEdit,
+};
+
+/// A `load` item or assignment that isn't used, along with the fix that removes it.
+struct Unused {
+    code: DiagnosticCode,
+    name: String,
+    range: TextRange,
+    fix_title: String,
+    edits: Vec<TextEdit>,
+}
+
+fn unused(db: &Database, file: File) -> Vec<Unused> {
+    let sema = Semantics::new(db);
+    let root = parse(db, file).syntax(db);
+
+    // The names bound by plain assignments. All other name references are reads.
+    let assign_stmts = root
+        .descendants()
+        .filter_map(ast::AssignStmt::cast)
+        .filter(|assign_stmt| matches!(assign_stmt.assign_op_info(), Some((_, AssignOp::Normal))))
+        .collect::<Vec<_>>();
+    let mut targets = Vec::new();
+    for assign_stmt in &assign_stmts {
+        if let Some(lhs) = assign_stmt.lhs() {
+            collect_assigned_names(lhs, &mut targets);
+        }
+    }
+    let target_ranges = targets
+        .iter()
+        .map(|name_ref| name_ref.syntax().text_range())
+        .collect::<FxHashSet<_>>();
+
+    // The ranges of the definitions that are read somewhere.
+    let mut read = FxHashSet::default();
+    for name_ref in root.descendants().filter_map(ast::NameRef::cast) {
+        if target_ranges.contains(&name_ref.syntax().text_range()) {
+            continue;
+        }
+        let name = Name::from_ast_node(name_ref.clone());
+        let defs = ast::Expression::cast(name_ref.syntax().clone())
+            .and_then(|expr| sema.scope_for_expr(file, &expr))
+            .and_then(|scope| scope.resolve_name(&name))
+            .unwrap_or_default();
+        read.extend(
+            defs.iter()
+                .filter_map(|def| def.syntax_node_ptr(db, file))
+                .map(|ptr| ptr.text_range()),
+        );
+    }
+
+    let mut unused = unused_loads(&root, &read);
+    for assign_stmt in assign_stmts {
+        let is_local = assign_stmt
+            .syntax()
+            .ancestors()
+            .any(|node| ast::DefStmt::can_cast(node.kind()));
+        let lhs = match assign_stmt.lhs() {
+            Some(lhs) if is_local => lhs,
+            _ => continue,
+        };
+        let mut names = Vec::new();
+        collect_assigned_names(lhs.clone(), &mut names);
+        for name_ref in names {
+            let range = name_ref.syntax().text_range();
+            let name = match name_ref.name() {
+                Some(name) if !name.text().starts_with('_') => name.text().to_string(),
+                _ => continue,
+            };
+            if read.contains(&range) {
+                continue;
+            }
+
+            let (fix_title, edits) = if matches!(lhs, ast::Expression::Name(_)) {
+                (
+                    format!("Remove unused variable `{}`", name),
+                    remove_assignment_edits(&assign_stmt),
+                )
+            } else {
+                // Other names may be used, so only replace the unused one in the unpacking.
+                (
+                    format!("Replace unused variable `{}` with `_`", name),
+                    vec![TextEdit {
+                        range,
+                        new_text: "_".to_string(),
+                    }],
+                )
+            };
+            unused.push(Unused {
+                code: DiagnosticCode::UnusedVariable,
+                name,
+                range,
+                fix_title,
+                edits,
+            });
+        }
+    }
+    unused
+}
+
+fn unused_loads(root: &SyntaxNode, read: &FxHashSet<TextRange>) -> Vec<Unused> {
+    let load_stmts = root
+        .children()
+        .filter_map(ast::LoadStmt::cast)
+        .collect::<Vec<_>>();
+
+    // Names that are bound more than once are reported as duplicates instead.
+    let mut seen = FxHashSet::default();
+    let mut duplicates = FxHashSet::default();
+    for item in load_stmts.iter().flat_map(|load_stmt| load_stmt.items()) {
+        if let Some(name) = local_name(&item) {
+            if !seen.insert(name.clone()) {
+                duplicates.insert(name);
+            }
+        }
+    }
+
+    let mut unused = Vec::new();
+    for load_stmt in load_stmts {
+        let items = load_stmt.items().collect::<Vec<_>>();
+        let unused_items = items
+            .iter()
+            .filter_map(|item| {
+                let name = local_name(item)?;
+                let range = item.syntax().text_range();
+                (!duplicates.contains(&name) && !read.contains(&range)).then_some((name, item))
+            })
+            .collect::<Vec<_>>();
+
+        // Remove the whole statement if none of its items are used.
+        let remove_stmt = unused_items.len() == items.len();
+        for (name, item) in unused_items {
+            let mut builder = EditBuilder::new();
+            if remove_stmt {
+                builder.remove_node(load_stmt.syntax());
+            } else {
+                builder.remove_list_item(load_stmt.syntax(), item.syntax());
+            }
+            unused.push(Unused {
+                code: DiagnosticCode::UnusedLoad,
+                fix_title: format!("Remove unused load of `{}`", name),
+                name,
+                range: item.syntax().text_range(),
+                edits: builder.finish().into_iter().map(TextEdit::from).collect(),
+            });
+        }
+    }
+    unused
+}
+
+/// Removes an assignment to a single name. If the assigned value has side effects, i.e. it
+/// contains a call, the value itself is kept as an expression statement.
+fn remove_assignment_edits(assign_stmt: &ast::AssignStmt) -> Vec<TextEdit> {
+    let mut builder = EditBuilder::new();
+    let rhs = assign_stmt.rhs();
+    let has_call = rhs.as_ref().map_or(false, |rhs| {
+        rhs.syntax()
+            .descendants()
+            .any(|node| ast::CallExpr::can_cast(node.kind()))
+    });
+    match (rhs, assign_stmt.lhs()) {
+        (Some(rhs), Some(lhs)) if has_call => builder.delete(TextRange::new(
+            lhs.syntax().text_range().start(),
+            rhs.syntax().text_range().start(),
+        )),
+        _ => builder.remove_node(assign_stmt.syntax()),
+    }
+    builder.finish().into_iter().map(TextEdit::from).collect()
+}
+
+pub(crate) fn unused_diagnostics(db: &Database, file: File) -> Vec<Diagnostic> {
+    unused(db, file)
+        .into_iter()
+        .map(|unused| Diagnostic {
+            message: match unused.code {
+                DiagnosticCode::UnusedLoad => {
+                    format!("\"{}\" is loaded but never used", unused.name)
+                }
+                _ => format!(
+                    "Local variable \"{}\" is assigned but never used",
+                    unused.name
+                ),
+            },
+            code: unused.code,
+            severity: Severity::Warning,
+            range: FileRange {
+                file_id: file.id(db),
+                range: unused.range,
+            },
+        })
+        .collect()
+}
+
+pub(crate) fn unused_actions(db: &Database, file: File, range: TextRange) -> Vec<CodeAction> {
+    let unused = unused(db, file);
+    let mut actions = unused
+        .iter()
+        .filter(|unused| unused.range.intersect(range).is_some())
+        .map(|unused| CodeAction {
+            title: unused.fix_title.clone(),
+            kind: CodeActionKind::QuickFix,
+            edits: unused.edits.clone(),
+            file_edits: Vec::new(),
+            command: None,
+            is_preferred: true,
+        })
+        .collect::<Vec<_>>();
+
+    // Offer to remove every unused load at once, but only if there's more than the one already
+    // offered.
+    let offers_load = actions
+        .iter()
+        .any(|action| action.title.starts_with("Remove unused load"));
+    let unused_loads = unused
+        .iter()
+        .filter(|unused| unused.code == DiagnosticCode::UnusedLoad)
+        .count();
+    if offers_load && unused_loads > 1 {
+        actions.push(CodeAction {
+            title: "Remove all unused loads".to_string(),
+            kind: CodeActionKind::QuickFix,
+            edits: remove_all_loads_edits(db, file),
+            file_edits: Vec::new(),
+            command: None,
+            is_preferred: false,
+        });
+    }
+    actions
+}
+
+/// Returns the edits that remove every unused `load` item in the file, collected into a single
+/// set so that items of the same statement are removed together.
+fn remove_all_loads_edits(db: &Database, file: File) -> Vec<TextEdit> {
+    let mut builder = EditBuilder::new();
+    for (load_stmt, unused_items) in unused_load_items(db, file) {
+        remove_load_items(&mut builder, &load_stmt, &unused_items);
+    }
+    builder.finish().into_iter().map(TextEdit::from).collect()
+}
+
+/// Returns the edits that remove every unused `load` item in the file for the "Fix all" action,
+/// grouped by statement. Each group must be applied as a whole, since removing only some of a
+/// statement's items could leave its commas behind.
+pub(crate) fn unused_load_edits(db: &Database, file: File) -> Vec<Vec<TextEdit>> {
+    unused_load_items(db, file)
+        .into_iter()
+        .map(|(load_stmt, unused_items)| {
+            let mut builder = EditBuilder::new();
+            remove_load_items(&mut builder, &load_stmt, &unused_items);
+            builder.finish().into_iter().map(TextEdit::from).collect()
+        })
+        .collect()
+}
+
+/// Returns the `load` statements that have unused items, along with those items.
+fn unused_load_items(db: &Database, file: File) -> Vec<(ast::LoadStmt, Vec<SyntaxNode>)> {
+    let root = parse(db, file).syntax(db);
+    let unused_ranges = unused(db, file)
+        .into_iter()
+        .filter(|unused| unused.code == DiagnosticCode::UnusedLoad)
+        .map(|unused| unused.range)
+        .collect::<FxHashSet<_>>();
+    root.children()
+        .filter_map(ast::LoadStmt::cast)
+        .filter_map(|load_stmt| {
+            let unused_items = load_stmt
+                .items()
+                .map(|item| item.syntax().clone())
+                .filter(|item| unused_ranges.contains(&item.text_range()))
+                .collect::<Vec<_>>();
+            (!unused_items.is_empty()).then_some((load_stmt, unused_items))
+        })
+        .collect()
+}
+
+/// Removes the given items from a `load` statement, or the whole statement if none of its items
+/// are left.
+fn remove_load_items(
+    builder: &mut EditBuilder,
+    load_stmt: &ast::LoadStmt,
+    unused_items: &[SyntaxNode],
+) {
+    if unused_items.len() == load_stmt.items().count() {
+        builder.remove_node(load_stmt.syntax());
+    } else {
+        builder.remove_list_items(load_stmt.syntax(), unused_items);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use starpls_common::{Db as _, DiagnosticCode, Dialect, FileRange};
+    use starpls_syntax::{TextRange, TextSize};
+
+    use super::unused_diagnostics;
+    use crate::{AnalysisSnapshot, TextEdit};
+
+    fn apply(text: &str, edits: &[TextEdit]) -> String {
+        let mut edits = edits.to_vec();
+        edits.sort_by_key(|edit| std::cmp::Reverse(edit.range.start()));
+        let mut text = text.to_string();
+        for edit in edits {
+            text.replace_range(std::ops::Range::<usize>::from(edit.range), &edit.new_text);
+        }
+        text
+    }
+
+    fn check_fix(contents: &str, title: &str, expected: &str) {
+        let (snap, file_id) = AnalysisSnapshot::from_single_file(contents, Dialect::Standard, None);
+        let actions = snap
+            .code_actions(FileRange {
+                file_id,
+                range: TextRange::up_to(TextSize::of(contents)),
+            })
+            .unwrap()
+            .unwrap();
+        let action = actions
+            .iter()
+            .find(|action| action.title == title)
+            .unwrap_or_else(|| panic!("no action {:?} in {:?}", title, actions));
+        assert_eq!(apply(contents, &action.edits), expected);
+    }
+
+    const CONTENTS: &str = r#"load("a.star", "used", "unused", "unused2")
+load("b.star", "other")
+
+top_level = used
+
+def f(x):
+    result = used(x)
+    _ignored = 1
+    a, b = x
+    tmp = 2
+    count = 0
+    count += 1
+    return a
+"#;
+
+    #[test]
+    fn test_unused_diagnostics() {
+        let (snap, file_id) = AnalysisSnapshot::from_single_file(CONTENTS, Dialect::Standard, None);
+        let file = snap.db.get_file(file_id).unwrap();
+        let actual = unused_diagnostics(&snap.db, file)
+            .into_iter()
+            .map(|diagnostic| (diagnostic.code, &CONTENTS[diagnostic.range.range]))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            actual,
+            vec![
+                (DiagnosticCode::UnusedLoad, "\"unused\""),
+                (DiagnosticCode::UnusedLoad, "\"unused2\""),
+                (DiagnosticCode::UnusedLoad, "\"other\""),
+                (DiagnosticCode::UnusedVariable, "result"),
+                (DiagnosticCode::UnusedVariable, "b"),
+                (DiagnosticCode::UnusedVariable, "tmp"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unused_fixes() {
+        check_fix(
+            CONTENTS,
+            "Remove all unused loads",
+            &CONTENTS
+                .replace(", \"unused\", \"unused2\"", "")
+                .replace("load(\"b.star\", \"other\")\n", ""),
+        );
+        check_fix(
+            CONTENTS,
+            "Remove unused load of `unused`",
+            &CONTENTS.replace("\"unused\", ", ""),
+        );
+        check_fix(
+            CONTENTS,
+            "Remove unused variable `result`",
+            &CONTENTS.replace("result = used(x)", "used(x)"),
+        );
+        check_fix(
+            CONTENTS,
+            "Remove unused variable `tmp`",
+            &CONTENTS.replace("    tmp = 2\n", ""),
+        );
+        check_fix(
+            CONTENTS,
+            "Replace unused variable `b` with `_`",
+            &CONTENTS.replace("a, b = x", "a, _ = x"),
+        );
+    }
+}
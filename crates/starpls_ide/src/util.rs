@@ -1,4 +1,9 @@
-use starpls_syntax::{SyntaxKind, SyntaxToken, TokenAtOffset};
+use starpls_syntax::{
+    ast::{self, AstNode, AstToken},
+    SyntaxKind, SyntaxNode, SyntaxToken, TextRange, TextSize, TokenAtOffset,
+};
+
+use crate::TextEdit;
 
 pub(crate) fn pick_best_token(
     tokens: TokenAtOffset<SyntaxToken>,
@@ -31,3 +36,182 @@ pub(crate) fn unindent_doc(doc: &str) -> String {
         .collect::<Vec<_>>()
         .join("\n")
 }
+
+/// Returns the names of the arguments documented in the `Args:` section of a Google-style
+/// docstring, along with their offsets in the docstring. Names don't include the `*` or `**`
+/// of variadic arguments.
+pub(crate) fn docstring_args(doc: &str) -> Vec<(usize, &str)> {
+    let mut args = Vec::new();
+    let mut section_indent = None;
+    let mut entry_indent = None;
+    let mut line_start = 0;
+
+    for line in doc.split_inclusive('\n') {
+        let line_offset = line_start;
+        line_start += line.len();
+        let trimmed = line.trim_start();
+        let indent = line.len() - trimmed.len();
+        let trimmed = trimmed.trim_end();
+
+        match section_indent {
+            None => {
+                if matches!(trimmed, "Args:" | "Arguments:") {
+                    section_indent = Some(indent);
+                }
+                continue;
+            }
+            // The section ends at the first non-empty line that isn't indented past its header.
+            Some(section_indent) if !trimmed.is_empty() && indent <= section_indent => break,
+            _ => {}
+        }
+
+        // Lines indented past the first entry continue the description of the previous one.
+        if trimmed.is_empty() || *entry_indent.get_or_insert(indent) != indent {
+            continue;
+        }
+        let entry = trimmed.trim_start_matches('*');
+        let len = entry
+            .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .unwrap_or(entry.len());
+        if len == 0
+            || !entry[len..]
+                .trim_start()
+                .starts_with(|c| c == ':' || c == '(')
+        {
+            continue;
+        }
+        args.push((
+            line_offset + indent + (trimmed.len() - entry.len()),
+            &entry[..len],
+        ));
+    }
+
+    args
+}
+
+pub(crate) fn load_stmts(root: &SyntaxNode) -> Vec<(ast::LoadStmt, String)> {
+    root.children()
+        .filter_map(ast::LoadStmt::cast)
+        .filter_map(|load_stmt| {
+            let module = load_stmt
+                .module()?
+                .name()
+                .and_then(ast::String::cast)?
+                .value()?
+                .to_string();
+            Some((load_stmt, module))
+        })
+        .collect()
+}
+
+/// Returns the name of the symbol that a load item loads.
+pub(crate) fn loaded_name(item: &ast::LoadItem) -> Option<String> {
+    let name = match item {
+        ast::LoadItem::Direct(item) => item.name(),
+        ast::LoadItem::Aliased(item) => item.name(),
+    };
+    Some(ast::String::cast(name?)?.value()?.to_string())
+}
+
+/// Returns the name that a load item binds in the loading file.
+pub(crate) fn local_name(item: &ast::LoadItem) -> Option<String> {
+    match item {
+        ast::LoadItem::Direct(_) => loaded_name(item),
+        ast::LoadItem::Aliased(item) => Some(item.alias()?.name()?.text().to_string()),
+    }
+}
+
+/// Inserts a new `load` statement for the given names after the last one in the given file, or at the start of the
+/// file, after its docstring.
+pub(crate) fn insert_load(root: Option<&SyntaxNode>, module: &str, names: &[&str]) -> TextEdit {
+    let load = format!(
+        "load(\"{}\", {})\n",
+        module,
+        names
+            .iter()
+            .map(|name| format!("\"{}\"", name))
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+    let root = match root {
+        Some(root) => root,
+        None => {
+            return TextEdit {
+                range: TextRange::empty(0.into()),
+                new_text: format!("{}\n", load),
+            }
+        }
+    };
+
+    if let Some(last_load) = root.children().filter_map(ast::LoadStmt::cast).last() {
+        let end = line_end(root, last_load.syntax().text_range().end());
+        return TextEdit {
+            range: TextRange::empty(end),
+            new_text: load,
+        };
+    }
+
+    let docstring_end = root
+        .children()
+        .next()
+        .filter(|node| {
+            ast::LiteralExpr::cast(node.clone()).map_or(false, |expr| {
+                matches!(expr.kind(), ast::LiteralKind::String(_))
+            })
+        })
+        .map(|node| line_end(root, node.text_range().end()));
+    match docstring_end {
+        Some(end) => TextEdit {
+            range: TextRange::empty(end),
+            new_text: format!("\n{}", load),
+        },
+        None => TextEdit {
+            range: TextRange::empty(0.into()),
+            new_text: format!("{}\n", load),
+        },
+    }
+}
+
+/// Returns the offset just past the end of the line containing the given offset.
+fn line_end(root: &SyntaxNode, offset: TextSize) -> TextSize {
+    let text = root.text().to_string();
+    let start = usize::from(offset);
+    text[start..]
+        .find('\n')
+        .map_or(root.text_range().end(), |pos| {
+            TextSize::from((start + pos + 1) as u32)
+        })
+}
+
+/// Merges edits that insert text at the same offset, so that the order they're applied in is
+/// unambiguous, e.g. when creating a new file.
+pub(crate) fn merge_insertions(edits: &mut Vec<TextEdit>) {
+    let mut merged: Vec<TextEdit> = Vec::with_capacity(edits.len());
+    for edit in edits.drain(..) {
+        match merged.last_mut() {
+            Some(last) if last.range == edit.range && edit.range.is_empty() => {
+                last.new_text.push_str(&edit.new_text)
+            }
+            _ => merged.push(edit),
+        }
+    }
+    *edits = merged;
+}
+
+/// Appends text to the end of a file, separated from the existing contents by a blank line.
+pub(crate) fn append_to_file(root: &SyntaxNode, text: &str) -> TextEdit {
+    let contents = root.text().to_string();
+    let separator = if contents.trim().is_empty() {
+        ""
+    } else if contents.ends_with("\n\n") {
+        "\n"
+    } else if contents.ends_with('\n') {
+        "\n\n"
+    } else {
+        "\n\n\n"
+    };
+    TextEdit {
+        range: TextRange::empty(root.text_range().end()),
+        new_text: format!("{}{}", separator, text),
+    }
+}
@@ -0,0 +1,224 @@
+//! Finds the symbols that can be searched for across the workspace: the functions, rules,
+//! providers, and macros defined by `.bzl` files, and the targets declared by `BUILD` files.
+//!
+//! Symbols are found from a file's text alone, without resolving its `load`s, so that the
+//! workspace can be indexed without adding every file to the database. Rules and the like are
+//! recognized by the functions that declare them, e.g. `rule()` or `provider()`.
+
+use starpls_bazel::APIContext;
+use starpls_syntax::{
+    ast::{self, AstNode},
+    parse_module, TextRange,
+};
+
+use crate::module_deps::callee_name;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WorkspaceSymbolKind {
+    Function,
+    Macro,
+    Provider,
+    Rule,
+    Target,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WorkspaceSymbol {
+    pub name: String,
+    pub kind: WorkspaceSymbolKind,
+    /// The rule or macro that declares a target, e.g. `go_binary`.
+    pub detail: Option<String>,
+    /// The range of the symbol's name.
+    pub range: TextRange,
+}
+
+/// Returns the symbols defined by a file with the given contents.
+pub fn index_workspace_symbols(
+    contents: &str,
+    api_context: Option<APIContext>,
+) -> Vec<WorkspaceSymbol> {
+    let root = parse_module(contents, &mut |_| {}).syntax();
+    let mut symbols = Vec::new();
+    for node in root.children() {
+        if let Some(def_stmt) = ast::DefStmt::cast(node.clone()) {
+            if let Some(name) = def_stmt.name().and_then(|name| name.name()) {
+                symbols.push(WorkspaceSymbol {
+                    name: name.text().to_string(),
+                    kind: WorkspaceSymbolKind::Function,
+                    detail: None,
+                    range: name.text_range(),
+                });
+            }
+        } else if let Some(assign_stmt) = ast::AssignStmt::cast(node.clone()) {
+            let name = match assign_stmt.lhs() {
+                Some(ast::Expression::Name(name_ref)) => name_ref.name(),
+                _ => None,
+            };
+            let kind = match assign_stmt.rhs() {
+                Some(ast::Expression::Call(call_expr)) => {
+                    match callee_name(&call_expr).as_deref() {
+                        Some("aspect" | "repository_rule" | "rule") => WorkspaceSymbolKind::Rule,
+                        Some("macro") => WorkspaceSymbolKind::Macro,
+                        Some("provider") => WorkspaceSymbolKind::Provider,
+                        _ => continue,
+                    }
+                }
+                _ => continue,
+            };
+            if let Some(name) = name {
+                symbols.push(WorkspaceSymbol {
+                    name: name.text().to_string(),
+                    kind,
+                    detail: None,
+                    range: name.text_range(),
+                });
+            }
+        } else if let Some(call_expr) = ast::CallExpr::cast(node) {
+            if api_context != Some(APIContext::Build) {
+                continue;
+            }
+            let name = call_expr
+                .arguments()
+                .into_iter()
+                .flat_map(|args| args.arguments())
+                .find_map(|arg| match arg {
+                    ast::Argument::Keyword(arg) if arg.name()?.name()?.text() == "name" => {
+                        match arg.expr()? {
+                            ast::Expression::Literal(lit) => match lit.kind() {
+                                ast::LiteralKind::String(s) => {
+                                    Some((s.value()?.to_string(), lit.syntax().text_range()))
+                                }
+                                _ => None,
+                            },
+                            _ => None,
+                        }
+                    }
+                    _ => None,
+                });
+            if let (Some((name, range)), Some(callee)) = (name, call_expr.callee()) {
+                symbols.push(WorkspaceSymbol {
+                    name,
+                    kind: WorkspaceSymbolKind::Target,
+                    detail: Some(callee.syntax().text().to_string()),
+                    range,
+                });
+            }
+        }
+    }
+    symbols
+}
+
+/// Scores how well a symbol's name matches a search query, or returns `None` if it doesn't match
+/// at all. The query matches if its characters appear in the name in order, ignoring case, e.g.
+/// `go_bin` matches `go_binary`. Names score higher the more of the query they match in runs, and
+/// especially at the start of the name or of its words.
+pub fn fuzzy_match(query: &str, name: &str) -> Option<u32> {
+    let name_chars = name.chars().collect::<Vec<_>>();
+    let mut score = 0;
+    let mut pos = 0;
+    let mut prev_match = None;
+    for query_char in query.chars().filter(|c| !c.is_whitespace()) {
+        let index = (pos..name_chars.len())
+            .find(|&index| name_chars[index].eq_ignore_ascii_case(&query_char))?;
+        score += 1;
+        if index == 0 {
+            score += 8;
+        } else if is_word_start(&name_chars, index) {
+            score += 4;
+        }
+        if prev_match.map_or(false, |prev| prev + 1 == index) {
+            score += 4;
+        }
+        prev_match = Some(index);
+        pos = index + 1;
+    }
+    // Prefer shorter names among ones that match equally well, e.g. `go_binary` over
+    // `go_binary_wrapper`.
+    let unmatched = name_chars.len().saturating_sub(query.len()) as u32;
+    Some((score * 16).saturating_sub(unmatched.min(15)))
+}
+
+fn is_word_start(chars: &[char], index: usize) -> bool {
+    let (prev, c) = (chars[index - 1], chars[index]);
+    matches!(prev, '_' | '-' | '.' | '/') || (prev.is_lowercase() && c.is_uppercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use starpls_bazel::APIContext;
+
+    use super::{fuzzy_match, index_workspace_symbols, WorkspaceSymbolKind};
+
+    fn check(contents: &str, api_context: APIContext, expected: &[(&str, WorkspaceSymbolKind)]) {
+        let actual = index_workspace_symbols(contents, Some(api_context))
+            .into_iter()
+            .map(|symbol| {
+                assert_eq!(&contents[symbol.range], symbol.name);
+                (symbol.name, symbol.kind)
+            })
+            .collect::<Vec<_>>();
+        let expected = expected
+            .iter()
+            .map(|(name, kind)| (name.to_string(), *kind))
+            .collect::<Vec<_>>();
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_bzl_symbols() {
+        check(
+            r#"
+load("//:defs.bzl", "helper")
+
+GoInfo = provider(fields = ["srcs"])
+
+def _go_binary_impl(ctx):
+    pass
+
+go_binary = rule(implementation = _go_binary_impl)
+
+go_library_macro = macro(implementation = helper)
+
+VERSION = "1.0"
+"#,
+            APIContext::Bzl,
+            &[
+                ("GoInfo", WorkspaceSymbolKind::Provider),
+                ("_go_binary_impl", WorkspaceSymbolKind::Function),
+                ("go_binary", WorkspaceSymbolKind::Rule),
+                ("go_library_macro", WorkspaceSymbolKind::Macro),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_build_targets() {
+        let contents = r#"
+go_binary(
+    name = "server",
+    srcs = ["main.go"],
+)
+
+exports_files(["LICENSE"])
+"#;
+        let symbols = index_workspace_symbols(contents, Some(APIContext::Build));
+        assert_eq!(symbols.len(), 1);
+        assert_eq!(symbols[0].name, "server");
+        assert_eq!(symbols[0].kind, WorkspaceSymbolKind::Target);
+        assert_eq!(symbols[0].detail.as_deref(), Some("go_binary"));
+        assert_eq!(&contents[symbols[0].range], "\"server\"");
+    }
+
+    #[test]
+    fn test_fuzzy_match() {
+        assert!(fuzzy_match("go_bin", "go_binary").is_some());
+        assert!(fuzzy_match("gobin", "go_binary").is_some());
+        assert!(fuzzy_match("bin_go", "go_binary").is_none());
+        assert!(fuzzy_match("GOBIN", "go_binary").is_some());
+
+        // Exact prefixes beat scattered matches, and shorter names beat longer ones.
+        assert!(fuzzy_match("go_bin", "go_binary") > fuzzy_match("go_bin", "go_tool_binary"));
+        assert!(fuzzy_match("go_bin", "go_binary") > fuzzy_match("go_bin", "go_binary_wrapper"));
+        assert!(fuzzy_match("GoInfo", "GoInfo") > fuzzy_match("GoInfo", "GoSourceInfo"));
+    }
+}
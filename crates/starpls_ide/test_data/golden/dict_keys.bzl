@@ -0,0 +1,6 @@
+TOOLCHAINS = {
+    "linux": 1,
+}
+
+DEFAULT = TOOLCHAINS["linux"]
+#                     ^goto
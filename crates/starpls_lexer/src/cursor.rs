@@ -76,6 +76,10 @@ impl<'a> Cursor<'a> {
         }
     }
 
+    pub(crate) fn remaining(&self) -> &'a str {
+        self.chars.as_str()
+    }
+
     pub(crate) fn str_until_pos_within_token(&self) -> &str {
         let start = self.input.len() - self.len_remaining;
         &self.input[start..start + self.pos_within_token() as usize]
@@ -90,8 +94,14 @@ impl<'a> Cursor<'a> {
     }
 
     pub(crate) fn close_block(&mut self, closer: char) {
-        if closer == self.closers.last().cloned().unwrap_or(EOF_CHAR) {
-            self.closers.pop();
+        // If the closer doesn't match the innermost open block but does match an enclosing one, then the blocks
+        // in-between are missing their closers, so we close them as well.
+        if let Some(pos) = self.closers.iter().rposition(|c| *c == closer) {
+            self.closers.truncate(pos);
         }
     }
+
+    pub(crate) fn close_all_blocks(&mut self) {
+        self.closers.clear();
+    }
 }
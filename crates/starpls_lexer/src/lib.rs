@@ -330,7 +330,11 @@ impl Cursor<'_> {
 
                     // If we are at an equal indentation level, or are currently at a newline or EOF, we can just
                     // emit the whitespace that we consumed.
-                    if indent == last_indent || self.first() == '\n' || self.is_eof() {
+                    if indent == last_indent
+                        || self.first() == '\n'
+                        || (self.first() == '\r' && self.second() == '\n')
+                        || self.is_eof()
+                    {
                         self.state = CursorState::AfterLeadingSpaces;
                         let pos_within_token = self.reset_pos_within_token();
                         if pos_within_token == 0 {
@@ -392,9 +396,12 @@ impl Cursor<'_> {
 
         let token_kind = match first_char {
             // Skip emitting newlines if we currently have an opened parenthesis, bracket, or brace.
+            // If the next line can only start a new statement, then the open blocks are missing their closers;
+            // close them here instead of joining the rest of the file onto this line.
             c if is_whitespace(c) => {
                 let has_open_block = self.has_open_block();
-                if c == '\n' && !has_open_block {
+                if c == '\n' && (!has_open_block || self.starts_statement(self.remaining())) {
+                    self.close_all_blocks();
                     self.state = CursorState::BeforeLeadingSpaces;
                     Newline
                 } else {
@@ -826,12 +833,28 @@ impl Cursor<'_> {
         (false, triple_quoted)
     }
 
+    /// Determines whether the given line can only be the start of a new statement, even though it
+    /// follows an open parenthesis, bracket, or brace. This is the case if the line starts with a
+    /// keyword that can't appear within an expression. Indentation isn't taken into account, since the
+    /// lines within brackets may be indented arbitrarily.
+    fn starts_statement(&self, line: &str) -> bool {
+        let contents = line.trim_start_matches([' ', '\t']);
+        let word_len = contents
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .unwrap_or(contents.len());
+        matches!(
+            &contents[..word_len],
+            "break" | "continue" | "def" | "load" | "pass" | "return"
+        )
+    }
+
     fn eat_whitespace(&mut self, newline_allowed: bool) {
         while !self.is_eof() {
             match self.first() {
                 ' ' | '\t' => {
                     self.bump();
                 }
+                '\n' if newline_allowed && self.starts_statement(&self.remaining()[1..]) => break,
                 '\r' | '\n' if newline_allowed => {
                     self.bump();
                 }
@@ -797,3 +797,129 @@ foo(3 + \
         "#]],
     )
 }
+
+#[test]
+fn test_unclosed_block_recovery() {
+    check_lexing(
+        r#"
+foo(1, [2)
+foo(
+    x = [1,
+def f():
+    y = bar(
+    return y
+    "#,
+        expect![[r#"
+            Token { kind: Newline, len: 1 }
+            Token { kind: Ident, len: 3 }
+            Token { kind: OpenParen, len: 1 }
+            Token { kind: Literal { kind: Int { base: Decimal, empty_int: false } }, len: 1 }
+            Token { kind: Comma, len: 1 }
+            Token { kind: Whitespace, len: 1 }
+            Token { kind: OpenBrack, len: 1 }
+            Token { kind: Literal { kind: Int { base: Decimal, empty_int: false } }, len: 1 }
+            Token { kind: CloseParen, len: 1 }
+            Token { kind: Newline, len: 1 }
+            Token { kind: Ident, len: 3 }
+            Token { kind: OpenParen, len: 1 }
+            Token { kind: Whitespace, len: 5 }
+            Token { kind: Ident, len: 1 }
+            Token { kind: Whitespace, len: 1 }
+            Token { kind: Eq, len: 1 }
+            Token { kind: Whitespace, len: 1 }
+            Token { kind: OpenBrack, len: 1 }
+            Token { kind: Literal { kind: Int { base: Decimal, empty_int: false } }, len: 1 }
+            Token { kind: Comma, len: 1 }
+            Token { kind: Newline, len: 1 }
+            Token { kind: Def, len: 3 }
+            Token { kind: Whitespace, len: 1 }
+            Token { kind: Ident, len: 1 }
+            Token { kind: OpenParen, len: 1 }
+            Token { kind: CloseParen, len: 1 }
+            Token { kind: Colon, len: 1 }
+            Token { kind: Newline, len: 1 }
+            Token { kind: Indent, len: 4 }
+            Token { kind: Ident, len: 1 }
+            Token { kind: Whitespace, len: 1 }
+            Token { kind: Eq, len: 1 }
+            Token { kind: Whitespace, len: 1 }
+            Token { kind: Ident, len: 3 }
+            Token { kind: OpenParen, len: 1 }
+            Token { kind: Newline, len: 1 }
+            Token { kind: Whitespace, len: 4 }
+            Token { kind: Return, len: 6 }
+            Token { kind: Whitespace, len: 1 }
+            Token { kind: Ident, len: 1 }
+            Token { kind: Newline, len: 1 }
+            Token { kind: Whitespace, len: 4 }
+            Token { kind: Dedent { consistent: true }, len: 0 }
+        "#]],
+    );
+}
+
+#[test]
+fn test_dedented_block_continuation() {
+    check_lexing(
+        r#"def f():
+    x = [
+"a",
+    ]
+"#,
+        expect![[r#"
+            Token { kind: Def, len: 3 }
+            Token { kind: Whitespace, len: 1 }
+            Token { kind: Ident, len: 1 }
+            Token { kind: OpenParen, len: 1 }
+            Token { kind: CloseParen, len: 1 }
+            Token { kind: Colon, len: 1 }
+            Token { kind: Newline, len: 1 }
+            Token { kind: Indent, len: 4 }
+            Token { kind: Ident, len: 1 }
+            Token { kind: Whitespace, len: 1 }
+            Token { kind: Eq, len: 1 }
+            Token { kind: Whitespace, len: 1 }
+            Token { kind: OpenBrack, len: 1 }
+            Token { kind: Whitespace, len: 1 }
+            Token { kind: Literal { kind: Str { terminated: true, triple_quoted: false } }, len: 3 }
+            Token { kind: Comma, len: 1 }
+            Token { kind: Whitespace, len: 5 }
+            Token { kind: CloseBrack, len: 1 }
+            Token { kind: Newline, len: 1 }
+            Token { kind: Dedent { consistent: true }, len: 0 }
+        "#]],
+    );
+}
+
+#[test]
+fn test_crlf_line_endings() {
+    check_lexing(
+        "def f():\r\n    x = 1\r\n\r\n    return x\r\n",
+        expect![[r#"
+            Token { kind: Def, len: 3 }
+            Token { kind: Whitespace, len: 1 }
+            Token { kind: Ident, len: 1 }
+            Token { kind: OpenParen, len: 1 }
+            Token { kind: CloseParen, len: 1 }
+            Token { kind: Colon, len: 1 }
+            Token { kind: Whitespace, len: 1 }
+            Token { kind: Newline, len: 1 }
+            Token { kind: Indent, len: 4 }
+            Token { kind: Ident, len: 1 }
+            Token { kind: Whitespace, len: 1 }
+            Token { kind: Eq, len: 1 }
+            Token { kind: Whitespace, len: 1 }
+            Token { kind: Literal { kind: Int { base: Decimal, empty_int: false } }, len: 1 }
+            Token { kind: Whitespace, len: 1 }
+            Token { kind: Newline, len: 1 }
+            Token { kind: Whitespace, len: 1 }
+            Token { kind: Newline, len: 1 }
+            Token { kind: Whitespace, len: 4 }
+            Token { kind: Return, len: 6 }
+            Token { kind: Whitespace, len: 1 }
+            Token { kind: Ident, len: 1 }
+            Token { kind: Whitespace, len: 1 }
+            Token { kind: Newline, len: 1 }
+            Token { kind: Dedent { consistent: true }, len: 0 }
+        "#]],
+    );
+}
@@ -33,6 +33,28 @@ pub(crate) const EXPR_START: SyntaxKindSet = PRIMARY_EXPR_START.union(SyntaxKind
     T![lambda],
 ]));
 
+/// Set of tokens to recover to when a parenthesized, bracketed, or braced construct isn't closed.
+const CLOSER_RECOVERY: SyntaxKindSet =
+    STMT_RECOVERY.union(SyntaxKindSet::new(&[T![')'], T![']'], T!['}']]));
+
+/// Expects the closing delimiter of a parenthesized, bracketed, or braced construct. Unexpected tokens
+/// before the closer are wrapped in an error node. If the closer is missing altogether, e.g. because
+/// we're at the closer of an enclosing construct or at the end of the statement, then only an error
+/// is reported, leaving the remaining tokens to be parsed as if the closer were present.
+pub(crate) fn expect_closer(p: &mut Parser, closer: SyntaxKind, message: &str) -> bool {
+    if p.eat(closer) {
+        return true;
+    }
+    if !p.at_kinds(CLOSER_RECOVERY) {
+        p.error_recover_until("Unexpected token", CLOSER_RECOVERY);
+        if p.eat(closer) {
+            return true;
+        }
+    }
+    p.error(message);
+    false
+}
+
 /// Grammar: `BinaryExpr = Test {Binop Test} .`
 pub(crate) fn binary_expr(
     p: &mut Parser,
@@ -167,17 +189,22 @@ pub(crate) fn primary_expr(p: &mut Parser) -> Option<CompletedMarker> {
 
 /// Grammar: `CallSuffix  = '(' [Arguments [',']] ')' .`
 fn call_expr(p: &mut Parser, m: Marker) -> CompletedMarker {
+    // test_err test_call_expr_mismatched_closer
+    // f(x, [y)
+    // g()
+
+    // test_err test_call_expr_unclosed
+    // f(
+    //     x = 1,
+    // def g():
+    //     pass
     let arguments_marker = p.start();
     p.bump(T!['(']);
     if ARGUMENT_START.contains(p.current()) {
         arguments(p);
     }
-    // If we aren't at the closing paren, recover to the next newline.
-    let is_closed = p.eat(T![')']);
+    expect_closer(p, T![')'], "\"(\" was not closed");
     arguments_marker.complete(p, ARGUMENTS);
-    if !is_closed {
-        p.error_recover_until("\"(\" was not closed", STMT_RECOVERY);
-    }
     m.complete(p, CALL_EXPR)
 }
 
@@ -215,9 +242,7 @@ fn slice_or_index_expr(p: &mut Parser, m: Marker) -> CompletedMarker {
             }
         }
     }
-    if !p.eat(T![']']) {
-        p.error_recover_until("\"[\" was not closed", STMT_RECOVERY);
-    }
+    expect_closer(p, T![']'], "\"[\" was not closed");
     m.complete(p, kind)
 }
 
@@ -295,8 +320,7 @@ pub(crate) fn tuple_or_paren_expr(p: &mut Parser, is_enclosed_in_parens: bool) -
         has_trailing_comma = p.eat(T![,]);
     }
 
-    if is_enclosed_in_parens && !p.eat(T![')']) {
-        p.error_recover_until("\"(\" was not closed", STMT_RECOVERY);
+    if is_enclosed_in_parens && !expect_closer(p, T![')'], "\"(\" was not closed") {
         num_parsed += 1;
     }
 
@@ -311,6 +335,8 @@ pub(crate) fn tuple_or_paren_expr(p: &mut Parser, is_enclosed_in_parens: bool) -
 
 /// Grammar: `ListExpr = '[' [Expression [',']] ']' . ListComp = '[' Test {CompClause} ']'.`
 fn list_expr_or_comp(p: &mut Parser) -> CompletedMarker {
+    // test_err test_list_expr_unexpected_token
+    // [1, 2 3]
     let m = p.start();
     let mut kind = LIST_EXPR;
     p.bump(T!['[']);
@@ -332,9 +358,7 @@ fn list_expr_or_comp(p: &mut Parser) -> CompletedMarker {
             p.eat(T![,]);
         }
     }
-    if !p.eat(T![']']) {
-        p.error_recover_until("\"[\" was not closed", STMT_RECOVERY);
-    }
+    expect_closer(p, T![']'], "\"[\" was not closed");
     m.complete(p, kind)
 }
 
@@ -361,9 +385,7 @@ fn dict_expr_or_comp(p: &mut Parser) -> CompletedMarker {
             p.eat(T![,]);
         }
     }
-    if !p.eat(T!['}']) {
-        p.error_recover_until("\"{\" was not closed", STMT_RECOVERY);
-    }
+    expect_closer(p, T!['}'], "\"{\" was not closed");
     m.complete(p, kind)
 }
 
@@ -402,7 +424,10 @@ fn entry(p: &mut Parser) {
     let m = p.start();
     test(p);
     if !p.eat(T![:]) {
-        p.error_recover_until("Expected \":\"", STMT_RECOVERY);
+        p.error_recover_until(
+            "Expected \":\"",
+            CLOSER_RECOVERY.union(SyntaxKindSet::new(&[T![,]])),
+        );
     } else {
         test(p);
     }
@@ -304,11 +304,7 @@ pub(crate) fn load_stmt(p: &mut Parser) {
         }
     }
     p.eat(T![,]);
-    if !p.eat(T![')']) {
-        p.error_recover_until("\"(\" was not closed", STMT_RECOVERY);
-        m.complete(p, LOAD_STMT);
-        return;
-    }
+    expect_closer(p, T![')'], "\"(\" was not closed");
     m.complete(p, LOAD_STMT);
 }
 
@@ -389,6 +385,9 @@ pub(crate) fn loop_variables(p: &mut Parser) {
     m.complete(p, LOOP_VARIABLES);
 }
 
+/// Wraps an unexpectedly indented block in an error node. The statements in the block are still
+/// parsed, so that they remain available to IDE features, e.g. the body of an `if` statement that
+/// is missing its colon.
 fn error_block(p: &mut Parser) {
     // test_err test_error_block
     // x = 1
@@ -396,24 +395,11 @@ fn error_block(p: &mut Parser) {
     //     z = 3
     // a = 4
     let m = p.start();
-    let mut level = 1;
     p.error("Unexpected indentation");
     p.bump(INDENT);
-
-    while !p.at(EOF) {
-        let kind = p.current();
-        p.bump_any();
-        match kind {
-            INDENT => level += 1,
-            DEDENT => {
-                level -= 1;
-                if level == 0 {
-                    break;
-                }
-            }
-            _ => (),
-        }
+    while !p.at(EOF) && !p.at(DEDENT) {
+        statement(p);
     }
-
+    p.eat(DEDENT);
     m.complete(p, ERROR);
 }
@@ -1198,6 +1198,9 @@ ast_token! {
 impl Int {
     pub fn value(&self) -> Option<u64> {
         let text = self.syntax.text();
+        if text.starts_with("0x") {
+            return u64::from_str_radix(&text[2..], 16).ok();
+        }
         if text.starts_with("0") {
             return Some(if text.len() == 1 {
                 0
@@ -1205,9 +1208,6 @@ impl Int {
                 u64::from_str_radix(&text[1..], 8).ok()?
             });
         }
-        if text.starts_with("0x") {
-            return u64::from_str_radix(&text[2..], 16).ok();
-        }
         return u64::from_str_radix(text, 10).ok();
     }
 }
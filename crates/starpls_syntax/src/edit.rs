@@ -0,0 +1,391 @@
+//! Utilities for building text edits that preserve the comments and formatting of the code
+//! around them.
+//!
+//! Comments aren't part of the nodes they describe in the syntax tree, so naively replacing or
+//! removing a node's range either leaves its comments dangling or deletes unrelated ones. The
+//! functions here treat the comment lines directly above a node, and the comment at the end of
+//! its last line, as attached to it. The [`EditBuilder`] uses this to remove nodes together
+//! with their comments, and to insert into and remove from comma-separated lists like list
+//! expressions, call arguments, and `load` statements while keeping the list's existing layout.
+
+use rowan::{TextRange, TextSize};
+
+use crate::{SyntaxKind, SyntaxNode, SyntaxToken, T};
+
+/// The indentation used for the items of a multi-line list that doesn't have any items yet,
+/// relative to its closing delimiter.
+const INDENT: &str = "    ";
+
+/// A replacement of a range of text.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Edit {
+    pub range: TextRange,
+    pub new_text: String,
+}
+
+/// Collects edits to a single syntax tree.
+#[derive(Clone, Debug, Default)]
+pub struct EditBuilder {
+    edits: Vec<Edit>,
+}
+
+impl EditBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, offset: TextSize, text: impl Into<String>) {
+        self.replace(TextRange::empty(offset), text);
+    }
+
+    pub fn replace(&mut self, range: TextRange, text: impl Into<String>) {
+        self.edits.push(Edit {
+            range,
+            new_text: text.into(),
+        });
+    }
+
+    pub fn delete(&mut self, range: TextRange) {
+        self.replace(range, String::new());
+    }
+
+    /// Replaces a node, leaving its attached comments in place.
+    pub fn replace_node(&mut self, node: &SyntaxNode, text: impl Into<String>) {
+        self.replace(node.text_range(), text);
+    }
+
+    /// Removes a node along with its attached comments. If nothing else is left on the lines
+    /// that the node spanned, the lines are removed as well.
+    pub fn remove_node(&mut self, node: &SyntaxNode) {
+        let text = root_text(node);
+        let range = range_with_comments(node);
+        self.delete(expand_to_lines(&text, range).unwrap_or(range));
+    }
+
+    /// Inserts an item into a comma-separated list at the given index, following the list's
+    /// layout: items of multi-line lists are put on their own lines, with the same indentation
+    /// as the existing items and a trailing comma.
+    ///
+    /// `list` is the node that directly contains the items and their delimiters, e.g. a
+    /// `LIST_EXPR`, `ARGUMENTS`, or `LOAD_STMT` node. Indices past the end of the list append
+    /// the item.
+    pub fn insert_list_item(&mut self, list: &SyntaxNode, index: usize, item: &str) {
+        let text = root_text(list);
+        let items = list.children().collect::<Vec<_>>();
+        let index = index.min(items.len());
+
+        if !is_multiline(&text, list, &items) {
+            if let Some(next) = items.get(index) {
+                self.insert(next.text_range().start(), format!("{}, ", item));
+            } else if let Some(last) = items.last() {
+                self.insert(last.text_range().end(), format!(", {}", item));
+            } else if let Some(open) = open_delimiter(list) {
+                self.insert(open.text_range().end(), item);
+            }
+            return;
+        }
+
+        let indent = match items.first() {
+            Some(first) => line_indent(&text, first.text_range().start()).to_string(),
+            None => match close_delimiter(list) {
+                Some(close) => format!(
+                    "{}{}",
+                    line_indent(&text, close.text_range().start()),
+                    INDENT
+                ),
+                None => INDENT.to_string(),
+            },
+        };
+
+        if let Some(next) = items.get(index) {
+            // Insert before the comments attached to the next item, so that they stay with it.
+            let start = line_start(&text, range_with_comments(next).start());
+            self.insert(start, format!("{}{},\n", indent, item));
+        } else if let Some(last) = items.last() {
+            if comma_after(last).is_none() {
+                self.insert(last.text_range().end(), ",");
+            }
+            let end = range_with_comments(last).end();
+            self.insert(end, format!("\n{}{},", indent, item));
+        } else if let Some(open) = open_delimiter(list) {
+            self.insert(open.text_range().end(), format!("\n{}{},", indent, item));
+        }
+    }
+
+    /// Removes an item from a comma-separated list, along with its separator and attached
+    /// comments.
+    pub fn remove_list_item(&mut self, list: &SyntaxNode, item: &SyntaxNode) {
+        let text = root_text(list);
+        let items = list.children().collect::<Vec<_>>();
+        let index = match items.iter().position(|it| it == item) {
+            Some(index) => index,
+            None => return,
+        };
+
+        if is_multiline(&text, list, &items) {
+            let range = range_with_comments(item);
+            if let Some(range) = expand_to_lines(&text, range) {
+                self.delete(range);
+                return;
+            }
+        }
+
+        // Remove the separator on one side of the item, preferring the one after it so that a
+        // trailing comma is kept.
+        let range = match (items.get(index + 1), index.checked_sub(1)) {
+            (Some(next), _) => TextRange::new(item.text_range().start(), next.text_range().start()),
+            (None, Some(prev)) => {
+                TextRange::new(items[prev].text_range().end(), item.text_range().end())
+            }
+            (None, None) => TextRange::new(
+                item.text_range().start(),
+                comma_after(item).map_or(item.text_range().end(), |comma| comma.text_range().end()),
+            ),
+        };
+        self.delete(range);
+    }
+
+    /// Removes several items from a comma-separated list. Unlike calling
+    /// [`EditBuilder::remove_list_item`] for each of them, adjacent items on the same line are
+    /// removed together, so that their separators aren't removed twice.
+    pub fn remove_list_items(&mut self, list: &SyntaxNode, items_to_remove: &[SyntaxNode]) {
+        let text = root_text(list);
+        let items = list.children().collect::<Vec<_>>();
+        let mut remove = items
+            .iter()
+            .map(|item| items_to_remove.contains(item))
+            .collect::<Vec<_>>();
+
+        // Items on their own lines are removed along with their lines.
+        if is_multiline(&text, list, &items) {
+            for (item, remove) in items.iter().zip(remove.iter_mut()) {
+                if !*remove {
+                    continue;
+                }
+                if let Some(range) = expand_to_lines(&text, range_with_comments(item)) {
+                    self.delete(range);
+                    *remove = false;
+                }
+            }
+        }
+
+        // Remove the remaining items in runs of adjacent items, along with the separator on one
+        // side of each run.
+        let mut index = 0;
+        while index < items.len() {
+            if !remove[index] {
+                index += 1;
+                continue;
+            }
+            let start = index;
+            while index < items.len() && remove[index] {
+                index += 1;
+            }
+            let (first, last) = (&items[start], &items[index - 1]);
+            let range = match (items.get(index), start.checked_sub(1)) {
+                (Some(next), _) => {
+                    TextRange::new(first.text_range().start(), next.text_range().start())
+                }
+                (None, Some(prev)) => {
+                    TextRange::new(items[prev].text_range().end(), last.text_range().end())
+                }
+                (None, None) => TextRange::new(
+                    first.text_range().start(),
+                    comma_after(last)
+                        .map_or(last.text_range().end(), |comma| comma.text_range().end()),
+                ),
+            };
+            self.delete(range);
+        }
+    }
+
+    /// Returns the collected edits, ordered by their position in the file. Edits at the same
+    /// position are kept in the order they were added.
+    pub fn finish(mut self) -> Vec<Edit> {
+        self.edits.sort_by_key(|edit| edit.range.start());
+        debug_assert!(
+            self.edits
+                .windows(2)
+                .all(|pair| pair[0].range.end() <= pair[1].range.start()),
+            "overlapping edits: {:?}",
+            self.edits
+        );
+        self.edits
+    }
+}
+
+/// Applies edits, as returned by [`EditBuilder::finish`], to the given text.
+pub fn apply_edits(text: &str, edits: &[Edit]) -> String {
+    let mut res = String::with_capacity(text.len());
+    let mut pos = 0;
+    for edit in edits {
+        let start = usize::from(edit.range.start());
+        res.push_str(&text[pos..start]);
+        res.push_str(&edit.new_text);
+        pos = usize::from(edit.range.end());
+    }
+    res.push_str(&text[pos..]);
+    res
+}
+
+/// Returns the comments attached to a node: the comment lines directly above it, without any
+/// blank lines in between, and the comment at the end of its last line, if any. Comments
+/// following a separator, e.g. in `"a",  # comment`, are attached to the item before the
+/// separator.
+pub fn attached_comments(node: &SyntaxNode) -> (Vec<SyntaxToken>, Option<SyntaxToken>) {
+    let root = match node.ancestors().last() {
+        Some(root) => root,
+        None => return (Vec::new(), None),
+    };
+    let text = root.text().to_string();
+
+    // Leading comments only exist for nodes that start their own line.
+    let mut leading = Vec::new();
+    let mut start = node.text_range().start();
+    if line_prefix(&text, start).trim().is_empty() {
+        while let Some(comment) = previous_line(&text, start)
+            .and_then(|prev_start| comment_at_line_start(&root, &text, prev_start))
+        {
+            start = line_start(&text, comment.text_range().start());
+            leading.push(comment);
+        }
+        leading.reverse();
+    }
+
+    let end = comma_after(node).map_or(node.text_range().end(), |comma| comma.text_range().end());
+    let trailing = root
+        .token_at_offset(end)
+        .right_biased()
+        .into_iter()
+        .flat_map(|token| std::iter::successors(Some(token), |token| token.next_token()))
+        .find(|token| {
+            !matches!(token.kind(), SyntaxKind::WHITESPACE) || token.text().contains('\n')
+        })
+        .filter(|token| token.kind() == SyntaxKind::COMMENT && token.text_range().start() >= end);
+
+    (leading, trailing)
+}
+
+/// Returns the range of a node extended to cover its attached comments, as well as the
+/// separator after it, if any.
+pub fn range_with_comments(node: &SyntaxNode) -> TextRange {
+    let (leading, trailing) = attached_comments(node);
+    let start = leading
+        .first()
+        .map_or(node.text_range().start(), |comment| {
+            comment.text_range().start()
+        });
+    let end = trailing
+        .map(|comment| comment.text_range().end())
+        .or_else(|| comma_after(node).map(|comma| comma.text_range().end()))
+        .unwrap_or(node.text_range().end());
+    TextRange::new(start, end)
+}
+
+fn root_text(node: &SyntaxNode) -> String {
+    node.ancestors()
+        .last()
+        .map(|root| root.text().to_string())
+        .unwrap_or_default()
+}
+
+/// Returns the comma that directly follows a node, ignoring whitespace.
+fn comma_after(node: &SyntaxNode) -> Option<SyntaxToken> {
+    std::iter::successors(node.last_token()?.next_token(), |token| token.next_token())
+        .find(|token| token.kind() != SyntaxKind::WHITESPACE)
+        .filter(|token| token.kind() == T![,])
+}
+
+fn open_delimiter(list: &SyntaxNode) -> Option<SyntaxToken> {
+    list.children_with_tokens()
+        .filter_map(|el| el.into_token())
+        .find(|token| matches!(token.kind(), T!['('] | T!['['] | T!['{']))
+}
+
+fn close_delimiter(list: &SyntaxNode) -> Option<SyntaxToken> {
+    list.children_with_tokens()
+        .filter_map(|el| el.into_token())
+        .filter(|token| matches!(token.kind(), T![')'] | T![']'] | T!['}']))
+        .last()
+}
+
+/// A list is laid out over multiple lines if its first item, or its closing delimiter if it
+/// has no items, is on a different line than its opening delimiter.
+fn is_multiline(text: &str, list: &SyntaxNode, items: &[SyntaxNode]) -> bool {
+    let open = match open_delimiter(list) {
+        Some(open) => open.text_range().end(),
+        None => list.text_range().start(),
+    };
+    let first = items
+        .first()
+        .map(|item| item.text_range().start())
+        .or_else(|| close_delimiter(list).map(|close| close.text_range().start()));
+    match first {
+        Some(first) if first >= open => text[usize::from(open)..usize::from(first)].contains('\n'),
+        _ => false,
+    }
+}
+
+/// If a range is the only thing on its lines, returns the range extended to cover the lines,
+/// including the line break at the end of the last line.
+fn expand_to_lines(text: &str, range: TextRange) -> Option<TextRange> {
+    let start = usize::from(range.start());
+    let end = usize::from(range.end());
+    let line_start = text[..start].rfind('\n').map_or(0, |pos| pos + 1);
+    let line_end = text[end..]
+        .find('\n')
+        .map_or(text.len(), |pos| end + pos + 1);
+    if !text[line_start..start].trim().is_empty() || !text[end..line_end].trim().is_empty() {
+        return None;
+    }
+    Some(TextRange::new(
+        TextSize::from(line_start as u32),
+        TextSize::from(line_end as u32),
+    ))
+}
+
+fn line_start(text: &str, offset: TextSize) -> TextSize {
+    let offset = usize::from(offset);
+    TextSize::from(text[..offset].rfind('\n').map_or(0, |pos| pos + 1) as u32)
+}
+
+/// Returns the text between the start of the line and the given offset.
+fn line_prefix(text: &str, offset: TextSize) -> &str {
+    &text[usize::from(line_start(text, offset))..usize::from(offset)]
+}
+
+/// Returns the leading whitespace of the line containing the given offset.
+fn line_indent(text: &str, offset: TextSize) -> &str {
+    let prefix = line_prefix(text, offset);
+    &prefix[..prefix.len() - prefix.trim_start().len()]
+}
+
+/// Returns the start of the line before the one containing the given offset.
+fn previous_line(text: &str, offset: TextSize) -> Option<TextSize> {
+    let start = usize::from(line_start(text, offset));
+    if start == 0 {
+        return None;
+    }
+    Some(line_start(text, TextSize::from(start as u32 - 1)))
+}
+
+/// Returns the comment on the line starting at the given offset, if the line contains nothing
+/// but the comment.
+fn comment_at_line_start(
+    root: &SyntaxNode,
+    text: &str,
+    line_start: TextSize,
+) -> Option<SyntaxToken> {
+    let line = &text[usize::from(line_start)..];
+    let line = &line[..line.find('\n').unwrap_or(line.len())];
+    let trimmed = line.trim_start();
+    if !trimmed.starts_with('#') {
+        return None;
+    }
+
+    // Check the syntax tree to rule out lines inside of multi-line strings.
+    let offset = line_start + TextSize::from((line.len() - trimmed.len()) as u32);
+    root.token_at_offset(offset)
+        .right_biased()
+        .filter(|token| token.kind() == SyntaxKind::COMMENT)
+}
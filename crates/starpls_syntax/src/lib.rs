@@ -1,7 +1,7 @@
 pub use {
     crate::{
         ast::Module,
-        parser::{line_index, parse_module, ParseTree, SyntaxError},
+        parser::{line_index, parse_any, parse_module, ParseTree, SyntaxError},
     },
     line_index::LineIndex,
     rowan::{TextRange, TextSize, TokenAtOffset},
@@ -9,6 +9,7 @@ pub use {
 };
 
 pub mod ast;
+pub mod edit;
 mod parser;
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -82,6 +82,15 @@ pub fn parse_module(input: &str, errors_sink: &mut dyn FnMut(SyntaxError)) -> Pa
     ParseTree::new(green_node)
 }
 
+/// Parses arbitrary bytes as a Starlark module, replacing invalid UTF-8 sequences first. This is
+/// meant for fuzzing, and must not panic on any input.
+pub fn parse_any(input: &[u8]) -> (ParseTree<Module>, Vec<SyntaxError>) {
+    let text = String::from_utf8_lossy(input);
+    let mut errors = Vec::new();
+    let tree = parse_module(&text, &mut |error| errors.push(error));
+    (tree, errors)
+}
+
 fn build_type_comment(
     builder: &mut GreenNodeBuilder,
     text: &str,
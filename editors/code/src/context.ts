@@ -4,6 +4,17 @@ import { Executable, LanguageClient, LanguageClientOptions, ServerOptions } from
 import { CommandFactory } from './commands';
 import { StarlarkTextEditor, isStarlarkTextEditor } from './util';
 
+interface LanguageConfigurationResult {
+  indentationRules: {
+    increaseIndentPattern: string;
+    decreaseIndentPattern: string;
+  };
+  onEnterRules: {
+    beforeText: string;
+    action: { indent: 'indent' | 'outdent' };
+  }[];
+}
+
 /**
  * The `Context` class wraps a `vscode.ExtensionContext and keeps track of the current state
  * of the editor.
@@ -40,6 +51,10 @@ export class Context {
     // Register commands with the VSCode API.
     console.log('context: registering commands');
     this.registerCommands();
+
+    // Indent lines as they're typed using the server's rules.
+    console.log('context: setting language configuration');
+    await this.setLanguageConfiguration();
   }
 
   async stop() {
@@ -89,6 +104,23 @@ export class Context {
     }
   }
 
+  private async setLanguageConfiguration() {
+    const config = await this.client.sendRequest<LanguageConfigurationResult>('starpls/languageConfiguration', null);
+    const disposable = vscode.languages.setLanguageConfiguration('starlark', {
+      indentationRules: {
+        increaseIndentPattern: new RegExp(config.indentationRules.increaseIndentPattern),
+        decreaseIndentPattern: new RegExp(config.indentationRules.decreaseIndentPattern),
+      },
+      onEnterRules: config.onEnterRules.map((rule) => ({
+        beforeText: new RegExp(rule.beforeText),
+        action: {
+          indentAction: rule.action.indent === 'outdent' ? vscode.IndentAction.Outdent : vscode.IndentAction.Indent,
+        },
+      })),
+    });
+    this.disposables.push(disposable);
+  }
+
   get activeStarlarkTextEditor(): StarlarkTextEditor | undefined {
     const activeTextEditor = vscode.window.activeTextEditor;
     return activeTextEditor && isStarlarkTextEditor(activeTextEditor) ? activeTextEditor : undefined;